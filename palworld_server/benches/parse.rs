@@ -0,0 +1,67 @@
+//! Parsing hot paths: `showplayers` responses polled by the watchers and settings files
+//! rewritten by provisioning and password rotation.
+//!
+//! Run with `cargo bench -p palworld_server --bench parse`.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use palworld_server::config::WorldSettings;
+use palworld_server::parse;
+
+/// A `showplayers` response listing `players` players.
+fn showplayers_response(players: usize) -> String {
+    let mut response = "name,playeruid,steamid\n".to_string();
+    for i in 0..players {
+        response.push_str(&format!("Player{i},{},7656119{i:010}\n", 1_000_000 + i));
+    }
+    response
+}
+
+/// A settings file with about as many options as the server writes.
+fn settings_ini() -> String {
+    let mut options = vec![
+        "Difficulty=None".to_string(),
+        "ServerName=\"Benchmark Server\"".to_string(),
+        "ServerDescription=\"Commas, quotes and (parentheses)\"".to_string(),
+        "AdminPassword=\"MyRCONPassword\"".to_string(),
+        "RCONEnabled=True".to_string(),
+        "RCONPort=25575".to_string(),
+    ];
+    options.extend((0..64).map(|i| format!("Setting{i}={i}.000000")));
+    format!(
+        "[/Script/Pal.PalGameWorldSettings]\nOptionSettings=({})\n",
+        options.join(",")
+    )
+}
+
+fn bench_showplayers(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse_player_info");
+    for players in [32, 100, 500] {
+        let response = showplayers_response(players);
+        group.throughput(Throughput::Elements(players as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(players), &response, |b, r| {
+            b.iter(|| parse::parse_player_info(black_box(r)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_settings(c: &mut Criterion) {
+    let ini = settings_ini();
+    let settings = WorldSettings::parse(&ini).unwrap();
+    c.bench_function("settings_parse", |b| {
+        b.iter(|| WorldSettings::parse(black_box(&ini)).unwrap())
+    });
+    c.bench_function("settings_to_ini", |b| {
+        b.iter(|| black_box(&settings).to_ini())
+    });
+    c.bench_function("settings_round_trip", |b| {
+        b.iter(|| {
+            let mut settings = WorldSettings::parse(black_box(&ini)).unwrap();
+            settings.set_quoted("AdminPassword", "NewRCONPassword");
+            settings.to_ini()
+        })
+    });
+}
+
+criterion_group!(benches, bench_showplayers, bench_settings);
+criterion_main!(benches);
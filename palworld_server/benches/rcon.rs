@@ -0,0 +1,95 @@
+//! RCON round trips against an in-process server answering like Palworld, so they measure
+//! the client and not the game: connecting and logging in, and many tasks querying at once.
+//!
+//! Run with `cargo bench -p palworld_server --bench rcon`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use futures::future::join_all;
+use palworld_server::rcon::PalworldRCON;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::runtime::Runtime;
+
+const SERVERDATA_RESPONSE_VALUE: i32 = 0;
+const SERVERDATA_AUTH_RESPONSE: i32 = 2;
+const SERVERDATA_AUTH: i32 = 3;
+
+fn packet(id: i32, packet_type: i32, body: &str) -> Vec<u8> {
+    let mut packet = (10 + body.len() as i32).to_le_bytes().to_vec();
+    packet.extend_from_slice(&id.to_le_bytes());
+    packet.extend_from_slice(&packet_type.to_le_bytes());
+    packet.extend_from_slice(body.as_bytes());
+    packet.extend_from_slice(&[0, 0]);
+    packet
+}
+
+/// Accepts any password and answers every command with 32 players.
+async fn serve(mut stream: TcpStream) -> std::io::Result<()> {
+    let mut players = "name,playeruid,steamid\n".to_string();
+    for i in 0..32 {
+        players.push_str(&format!("Player{i},{},7656119{i:010}\n", 1_000_000 + i));
+    }
+    loop {
+        let len = stream.read_i32_le().await?;
+        let id = stream.read_i32_le().await?;
+        let packet_type = stream.read_i32_le().await?;
+        let mut body = vec![0; len as usize - 8];
+        stream.read_exact(&mut body).await?;
+        let response = match packet_type {
+            SERVERDATA_AUTH => packet(id, SERVERDATA_AUTH_RESPONSE, ""),
+            _ => packet(id, SERVERDATA_RESPONSE_VALUE, &players),
+        };
+        stream.write_all(&response).await?;
+    }
+}
+
+/// Starts the server and returns a client of it.
+fn start_server(runtime: &Runtime) -> PalworldRCON {
+    let listener = runtime.block_on(TcpListener::bind("127.0.0.1:0")).unwrap();
+    let port = listener.local_addr().unwrap().port();
+    runtime.spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(serve(stream));
+        }
+    });
+    let mut rcon = PalworldRCON::new("127.0.0.1", port, "MyRCONPassword");
+    rcon.protocol.detect = false;
+    rcon
+}
+
+fn bench_round_trip(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let rcon = start_server(&runtime);
+    // Every command connects and logs in again.
+    c.bench_function("connect_auth_command", |b| {
+        b.to_async(&runtime)
+            .iter(|| async { rcon.send_command("Info").await.unwrap() })
+    });
+    c.bench_function("get_player_info", |b| {
+        b.to_async(&runtime)
+            .iter(|| async { rcon.get_player_info().await.unwrap() })
+    });
+}
+
+fn bench_concurrent(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let rcon = start_server(&runtime);
+    let mut group = c.benchmark_group("concurrent");
+    for tasks in [8, 32] {
+        group.throughput(Throughput::Elements(tasks));
+        // Every task makes its own round trip.
+        group.bench_with_input(BenchmarkId::new("send_command", tasks), &tasks, |b, &n| {
+            b.to_async(&runtime)
+                .iter(|| join_all((0..n).map(|_| rcon.send_command("ShowPlayers"))))
+        });
+        // Tasks waiting for the same response share it.
+        group.bench_with_input(BenchmarkId::new("query", tasks), &tasks, |b, &n| {
+            b.to_async(&runtime)
+                .iter(|| join_all((0..n).map(|_| rcon.query("ShowPlayers"))))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_round_trip, bench_concurrent);
+criterion_main!(benches);
@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use palworld_server::parse;
+
+// Output of the commands run over SSH.
+fuzz_target!(|data: &str| {
+    parse::parse_meminfo(data);
+    parse::parse_key_values(data);
+    let _ = parse::parse_df(data);
+    parse::parse_processes(data);
+    parse::parse_systemctl_is_active(data);
+});
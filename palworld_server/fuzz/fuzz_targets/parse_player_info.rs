@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use palworld_server::parse;
+
+fuzz_target!(|data: &str| {
+    for player in parse::parse_player_info(data) {
+        assert!(!player.name.contains('\n'));
+    }
+});
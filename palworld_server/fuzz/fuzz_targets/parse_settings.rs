@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use palworld_server::config::WorldSettings;
+
+fuzz_target!(|data: &str| {
+    if let Ok(settings) = WorldSettings::parse(data) {
+        // Anything that parses must survive a round trip.
+        let reparsed = WorldSettings::parse(&settings.to_ini()).unwrap();
+        assert_eq!(reparsed, settings);
+    }
+});
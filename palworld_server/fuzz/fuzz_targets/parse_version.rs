@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use palworld_server::parse;
+
+fuzz_target!(|data: &str| {
+    if let Ok(version) = parse::parse_version(data) {
+        assert!(data.contains(&version));
+        parse::parse_version_numbers(&version).unwrap();
+    }
+    let _ = parse::compare_versions(data, "v0.1.5.0");
+});
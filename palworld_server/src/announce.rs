@@ -0,0 +1,564 @@
+//! In-game events like a boss raid at 20:00, announced with reminders and RSVPs.
+//!
+//! An [EventAnnouncer] broadcasts reminders before each [ScheduledEvent], lets players sign
+//! up with `!join` through the [ChatBridge] and, from the [PlayerWatcher]'s joins and the
+//! players online at the start, tracks who showed up. Once an event is over its
+//! [AttendanceReport] is broadcast, logged and kept for [EventAnnouncer::report]. RSVPs are
+//! tracked per Steam ID, so players renaming themselves keep their place.
+//!
+//! [PlayerWatcher]: crate::watcher::PlayerWatcher
+//!
+//! # Example:
+//! ```no_run
+//! use std::sync::Arc;
+//! use std::time::{Duration, SystemTime};
+//!
+//! use palworld_server::announce::{EventAnnouncer, ScheduledEvent};
+//! use palworld_server::chat::ChatBridge;
+//! use palworld_server::events::EventBus;
+//! use palworld_server::rcon::{PalworldRCON, DEFAULT_SOURCE_PORT};
+//! use palworld_server::ssh::PalworldConnection;
+//! use palworld_server::watcher::PlayerWatcher;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let rcon = PalworldRCON::new("localhost", DEFAULT_SOURCE_PORT, "MyRCONPassword");
+//!     let ssh = PalworldConnection::new("localhost:22", "steam", "MySSHPassword");
+//!     let bus = EventBus::default();
+//!
+//!     let announcer = Arc::new(EventAnnouncer::new(rcon.clone()));
+//!     let starts_at = SystemTime::now() + Duration::from_secs(2 * 60 * 60);
+//!     announcer.add(ScheduledEvent::new("Boss raid", starts_at)).unwrap();
+//!     announcer.clone().spawn(&bus);
+//!     PlayerWatcher::new(rcon.clone(), bus).spawn();
+//!
+//!     let mut bridge = ChatBridge::new(ssh, rcon, "/home/steam/palworld.log");
+//!     announcer.register(&mut bridge, "join");
+//!     bridge.run().await.unwrap();
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{bail, Result};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+
+use crate::chat::ChatBridge;
+use crate::events::{Event, EventBus};
+use crate::models::PlayerInfo;
+use crate::rcon::PalworldRCON;
+
+/// Default reminders, an hour, 10 minutes and a minute before the start.
+pub const DEFAULT_REMINDERS: [Duration; 3] = [
+    Duration::from_secs(60 * 60),
+    Duration::from_secs(10 * 60),
+    Duration::from_secs(60),
+];
+
+/// Default length of an event, players joining within it count as attending.
+pub const DEFAULT_EVENT_DURATION: Duration = Duration::from_secs(60 * 60);
+
+/// An in-game event at a fixed time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduledEvent {
+    /// Name shown to players, also what `!join <name>` matches ignoring case.
+    pub name: String,
+    pub starts_at: SystemTime,
+    pub duration: Duration,
+    /// Time before the start each reminder is broadcast at.
+    pub reminders: Vec<Duration>,
+}
+
+impl ScheduledEvent {
+    /// An event lasting [DEFAULT_EVENT_DURATION] with the [DEFAULT_REMINDERS].
+    pub fn new(name: impl Into<String>, starts_at: SystemTime) -> Self {
+        Self {
+            name: name.into(),
+            starts_at,
+            duration: DEFAULT_EVENT_DURATION,
+            reminders: DEFAULT_REMINDERS.to_vec(),
+        }
+    }
+
+    /// An event at the next `at` on the host's clock, like `"20:00".parse()` for 20:00 in
+    /// the host's timezone.
+    #[cfg(feature = "schedule")]
+    pub fn next_at(
+        name: impl Into<String>,
+        at: &crate::clock::DailySchedule,
+        clock: &crate::clock::RemoteClock,
+    ) -> Self {
+        let starts_at = clock.to_local(at.next_after(clock.now(), clock.timezone));
+        Self::new(name, starts_at)
+    }
+
+    pub fn ends_at(&self) -> SystemTime {
+        self.starts_at + self.duration
+    }
+}
+
+/// Result of a player's `!join`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RsvpOutcome {
+    /// Signed up, `count` players are going now.
+    Joined {
+        count: usize,
+    },
+    AlreadyJoined,
+    /// The event is over.
+    Closed,
+}
+
+/// Who signed up for an event and who showed up.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct AttendanceReport {
+    pub event: String,
+    /// Names of the players that signed up.
+    pub rsvps: Vec<String>,
+    /// Players that signed up and were online during the event.
+    pub attended: Vec<String>,
+    /// Players that signed up but weren't online during the event.
+    pub no_shows: Vec<String>,
+    /// Players online during the event without signing up.
+    pub walk_ins: Vec<String>,
+}
+
+/// What to broadcast about an event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Announcement {
+    /// The event starts in the given time.
+    Reminder(Duration),
+    Started,
+    Ended(AttendanceReport),
+}
+
+/// RSVPs, attendance and announcements of one event, independent of any connection.
+#[derive(Debug, Clone)]
+pub struct EventRoster {
+    pub event: ScheduledEvent,
+    /// Names by Steam ID of the players that signed up.
+    rsvps: BTreeMap<String, String>,
+    /// Names by Steam ID of the players online during the event.
+    present: BTreeMap<String, String>,
+    reminders_sent: usize,
+    started: bool,
+    ended: bool,
+}
+
+impl EventRoster {
+    pub fn new(mut event: ScheduledEvent) -> Self {
+        // Earliest reminder first.
+        event.reminders.sort_unstable_by(|a, b| b.cmp(a));
+        Self {
+            event,
+            rsvps: BTreeMap::new(),
+            present: BTreeMap::new(),
+            reminders_sent: 0,
+            started: false,
+            ended: false,
+        }
+    }
+
+    /// Signs `player` up, possible until the event ends.
+    pub fn rsvp(&mut self, player: &PlayerInfo, now: SystemTime) -> RsvpOutcome {
+        if self.ended || now >= self.event.ends_at() {
+            return RsvpOutcome::Closed;
+        }
+        let previous = self
+            .rsvps
+            .insert(player.steamid.clone(), player.name.clone());
+        match previous {
+            Some(_) => RsvpOutcome::AlreadyJoined,
+            None => RsvpOutcome::Joined {
+                count: self.rsvps.len(),
+            },
+        }
+    }
+
+    /// Number of players signed up.
+    pub fn rsvp_count(&self) -> usize {
+        self.rsvps.len()
+    }
+
+    /// Records `player` as online at `now`, counting as attendance while the event runs.
+    pub fn seen(&mut self, player: &PlayerInfo, now: SystemTime) {
+        if now >= self.event.starts_at && now < self.event.ends_at() {
+            self.present
+                .insert(player.steamid.clone(), player.name.clone());
+        }
+    }
+
+    /// Whether [Announcement::Ended] was returned already.
+    pub fn is_over(&self) -> bool {
+        self.ended
+    }
+
+    /// The next announcement due at `now`, call until None. Reminders missed while the
+    /// announcer wasn't running collapse into one with the actual time left.
+    pub fn due(&mut self, now: SystemTime) -> Option<Announcement> {
+        if self.ended {
+            return None;
+        }
+        if now >= self.event.starts_at {
+            self.reminders_sent = self.event.reminders.len();
+            if !self.started {
+                self.started = true;
+                return Some(Announcement::Started);
+            }
+            if now >= self.event.ends_at() {
+                self.ended = true;
+                return Some(Announcement::Ended(self.report()));
+            }
+            return None;
+        }
+        let left = self.event.starts_at.duration_since(now).unwrap_or_default();
+        let due = self.event.reminders[self.reminders_sent..]
+            .iter()
+            .take_while(|before| left <= **before)
+            .count();
+        if due == 0 {
+            return None;
+        }
+        self.reminders_sent += due;
+        Some(Announcement::Reminder(left))
+    }
+
+    /// Attendance so far, complete once the event is over.
+    pub fn report(&self) -> AttendanceReport {
+        let present = |steamid: &&String| self.present.contains_key(*steamid);
+        let signed_up = |steamid: &&String| self.rsvps.contains_key(*steamid);
+        AttendanceReport {
+            event: self.event.name.clone(),
+            rsvps: self.rsvps.values().cloned().collect(),
+            attended: names(&self.rsvps, present),
+            no_shows: names(&self.rsvps, |steamid| !present(steamid)),
+            walk_ins: names(&self.present, |steamid| !signed_up(steamid)),
+        }
+    }
+}
+
+/// Names of the `players` whose Steam ID passes `filter`.
+fn names(players: &BTreeMap<String, String>, filter: impl Fn(&&String) -> bool) -> Vec<String> {
+    players
+        .iter()
+        .filter(|(steamid, _)| filter(steamid))
+        .map(|(_, name)| name.clone())
+        .collect()
+}
+
+/// Whole minutes, rounded up, for reminders like "starts in 10 min".
+fn minutes(duration: Duration) -> u64 {
+    duration.as_secs().div_ceil(60)
+}
+
+/// Announces [ScheduledEvent]s and tracks their RSVPs, see the [module documentation](self).
+#[derive(Debug)]
+pub struct EventAnnouncer {
+    rcon: PalworldRCON,
+    /// Chat command players sign up with, as registered with [EventAnnouncer::register].
+    pub join_command: String,
+    /// Time between checks for due announcements.
+    pub tick: Duration,
+    /// Space replacement passed to [PalworldRCON::broadcast].
+    pub replace_space: Option<String>,
+    rosters: Mutex<Vec<EventRoster>>,
+}
+
+impl EventAnnouncer {
+    pub fn new(rcon: PalworldRCON) -> Self {
+        Self {
+            rcon,
+            join_command: "join".to_string(),
+            tick: Duration::from_secs(15),
+            replace_space: None,
+            rosters: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn rosters(&self) -> std::sync::MutexGuard<'_, Vec<EventRoster>> {
+        self.rosters.lock().expect("Event rosters lock poisoned")
+    }
+
+    /// Adds an event, failing if one with the same name hasn't ended yet.
+    pub fn add(&self, event: ScheduledEvent) -> Result<()> {
+        let mut rosters = self.rosters();
+        let name = event.name.to_lowercase();
+        if rosters
+            .iter()
+            .any(|roster| !roster.is_over() && roster.event.name.to_lowercase() == name)
+        {
+            bail!("An event named '{}' is already scheduled", event.name);
+        }
+        rosters.push(EventRoster::new(event));
+        Ok(())
+    }
+
+    /// Events that haven't ended yet, soonest first.
+    pub fn upcoming(&self) -> Vec<ScheduledEvent> {
+        let mut events: Vec<ScheduledEvent> = self
+            .rosters()
+            .iter()
+            .filter(|roster| !roster.is_over())
+            .map(|roster| roster.event.clone())
+            .collect();
+        events.sort_by_key(|event| event.starts_at);
+        events
+    }
+
+    /// Attendance of the event named `name`, the latest if it ran several times.
+    pub fn report(&self, name: &str) -> Option<AttendanceReport> {
+        self.rosters()
+            .iter()
+            .rev()
+            .find(|roster| roster.event.name.eq_ignore_ascii_case(name))
+            .map(EventRoster::report)
+    }
+
+    /// Signs the online player named `player` up for the event named `event`, or for the
+    /// next event without a name. Returns the message to broadcast.
+    pub async fn join(&self, player: &str, event: Option<&str>) -> Result<String> {
+        let online = self.rcon.get_player_info().await?;
+        let Some(player) = online.iter().find(|online| online.name == player) else {
+            return Ok(format!("{player} is not online"));
+        };
+        let now = SystemTime::now();
+        let mut rosters = self.rosters();
+        let roster = match event {
+            Some(event) => rosters
+                .iter_mut()
+                .filter(|roster| !roster.is_over())
+                .find(|roster| roster.event.name.eq_ignore_ascii_case(event)),
+            None => rosters
+                .iter_mut()
+                .filter(|roster| !roster.is_over())
+                .min_by_key(|roster| roster.event.starts_at),
+        };
+        let Some(roster) = roster else {
+            return Ok(match event {
+                Some(event) => format!("No event named '{event}'"),
+                None => "No upcoming events".to_string(),
+            });
+        };
+        let name = roster.event.name.clone();
+        let message = match roster.rsvp(player, now) {
+            RsvpOutcome::Joined { count } => {
+                format!("{} joined {name}, {count} player(s) going", player.name)
+            }
+            RsvpOutcome::AlreadyJoined => format!("{} already joined {name}", player.name),
+            RsvpOutcome::Closed => format!("{name} is over"),
+        };
+        Ok(message)
+    }
+
+    /// Registers `!name` with the chat bridge to sign up, `!name <event>` for a specific
+    /// event. `name` should match [EventAnnouncer::join_command], which reminders mention.
+    pub fn register(self: Arc<Self>, bridge: &mut ChatBridge, name: &str) {
+        bridge.command(name, move |command| {
+            let announcer = self.clone();
+            async move {
+                let event = command.args.join(" ");
+                let event = (!event.is_empty()).then_some(event.as_str());
+                Ok(Some(announcer.join(&command.player, event).await?))
+            }
+        });
+    }
+
+    /// The broadcast for `announcement` of `event`.
+    pub fn message(&self, event: &ScheduledEvent, announcement: &Announcement) -> String {
+        let name = &event.name;
+        match announcement {
+            Announcement::Reminder(left) => format!(
+                "{name} starts in {} min, type !{} to join",
+                minutes(*left),
+                self.join_command
+            ),
+            Announcement::Started => format!("{name} starts now!"),
+            Announcement::Ended(report) => format!(
+                "{name} is over, {}/{} attended",
+                report.attended.len(),
+                report.rsvps.len()
+            ),
+        }
+    }
+
+    /// Broadcasts the announcements due at `now`. Players online when an event starts
+    /// count as attending.
+    pub async fn announce_due(&self, now: SystemTime) -> Result<()> {
+        let mut announcements = Vec::new();
+        for roster in self.rosters().iter_mut() {
+            while let Some(announcement) = roster.due(now) {
+                announcements.push((roster.event.clone(), announcement));
+            }
+        }
+        for (event, announcement) in announcements {
+            match &announcement {
+                Announcement::Started => {
+                    // The watcher only reports joins, not who is online already.
+                    let online = self.rcon.get_player_info().await?;
+                    self.players_seen(&online, now);
+                }
+                Announcement::Ended(report) => log::info!(
+                    "{}: {} attended, {} no-show(s), {} walk-in(s)",
+                    report.event,
+                    report.attended.len(),
+                    report.no_shows.len(),
+                    report.walk_ins.len()
+                ),
+                Announcement::Reminder(_) => (),
+            }
+            let message = self.message(&event, &announcement);
+            self.rcon
+                .broadcast(message.as_str(), self.replace_space.clone())
+                .await?;
+        }
+        Ok(())
+    }
+
+    fn players_seen(&self, players: &[PlayerInfo], now: SystemTime) {
+        for roster in self.rosters().iter_mut() {
+            for player in players {
+                roster.seen(player, now);
+            }
+        }
+    }
+
+    /// Announces due events every [EventAnnouncer::tick] and records the
+    /// [Event::PlayerJoined]s of `bus` as attendance, in background tasks.
+    pub fn spawn(self: Arc<Self>, bus: &EventBus) -> JoinHandle<()> {
+        let mut events = bus.subscribe();
+        let announcer = self.clone();
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(Event::PlayerJoined(player)) => {
+                        announcer.players_seen(&[player], SystemTime::now())
+                    }
+                    Ok(_) => (),
+                    Err(RecvError::Lagged(missed)) => {
+                        log::warn!("Event announcer missed {missed} events")
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = self.announce_due(SystemTime::now()).await {
+                    log::warn!("Failed to announce events: {e}");
+                }
+                tokio::time::sleep(self.tick).await;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player(name: &str, steamid: &str) -> PlayerInfo {
+        PlayerInfo {
+            name: name.to_string(),
+            uid: "0".to_string(),
+            steamid: steamid.to_string(),
+        }
+    }
+
+    fn mins(minutes: u64) -> Duration {
+        Duration::from_secs(minutes * 60)
+    }
+
+    #[test]
+    fn test_announcements() {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let mut roster = EventRoster::new(ScheduledEvent::new("Boss raid", start));
+        assert_eq!(roster.due(start - mins(120)), None);
+        assert_eq!(
+            roster.due(start - mins(60)),
+            Some(Announcement::Reminder(mins(60)))
+        );
+        assert_eq!(roster.due(start - mins(30)), None);
+        // Started late: the 10 and 1 minute reminders collapse into one.
+        assert_eq!(
+            roster.due(start - Duration::from_secs(30)),
+            Some(Announcement::Reminder(Duration::from_secs(30)))
+        );
+        assert_eq!(roster.due(start - Duration::from_secs(20)), None);
+        assert_eq!(roster.due(start), Some(Announcement::Started));
+        assert_eq!(roster.due(start + mins(5)), None);
+        assert!(matches!(
+            roster.due(start + mins(60)),
+            Some(Announcement::Ended(_))
+        ));
+        assert!(roster.is_over());
+        assert_eq!(roster.due(start + mins(61)), None);
+    }
+
+    #[test]
+    fn test_attendance() {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let mut roster = EventRoster::new(ScheduledEvent::new("Boss raid", start));
+        let (alice, bob, carol) = (
+            player("Alice", "1"),
+            player("Bob", "2"),
+            player("Carol", "3"),
+        );
+        let before = start - mins(10);
+        assert_eq!(
+            roster.rsvp(&alice, before),
+            RsvpOutcome::Joined { count: 1 }
+        );
+        assert_eq!(roster.rsvp(&bob, before), RsvpOutcome::Joined { count: 2 });
+        // Same Steam ID under a new name.
+        assert_eq!(
+            roster.rsvp(&player("Alicia", "1"), before),
+            RsvpOutcome::AlreadyJoined
+        );
+        // Online before the start doesn't count.
+        roster.seen(&bob, before);
+        roster.seen(&alice, start + mins(5));
+        roster.seen(&carol, start + mins(10));
+        roster.seen(&bob, start + mins(90));
+        assert_eq!(roster.rsvp(&carol, start + mins(60)), RsvpOutcome::Closed);
+        assert_eq!(
+            roster.report(),
+            AttendanceReport {
+                event: "Boss raid".to_string(),
+                rsvps: vec!["Alicia".to_string(), "Bob".to_string()],
+                attended: vec!["Alicia".to_string()],
+                no_shows: vec!["Bob".to_string()],
+                walk_ins: vec!["Carol".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn test_announcer() {
+        let rcon = PalworldRCON::new("invalid host name", 25575, "password");
+        let announcer = EventAnnouncer::new(rcon);
+        let start = SystemTime::now() + mins(90);
+        announcer
+            .add(ScheduledEvent::new("Boss raid", start))
+            .unwrap();
+        assert!(announcer
+            .add(ScheduledEvent::new("BOSS RAID", start))
+            .is_err());
+        announcer
+            .add(ScheduledEvent::new("Egg hunt", start - mins(30)))
+            .unwrap();
+        let names: Vec<String> = announcer.upcoming().into_iter().map(|e| e.name).collect();
+        assert_eq!(names, vec!["Egg hunt", "Boss raid"]);
+        let event = &announcer.upcoming()[0];
+        assert_eq!(
+            announcer.message(event, &Announcement::Reminder(Duration::from_secs(550))),
+            "Egg hunt starts in 10 min, type !join to join"
+        );
+        assert!(announcer.report("boss raid").unwrap().rsvps.is_empty());
+        assert_eq!(announcer.report("Fishing"), None);
+    }
+}
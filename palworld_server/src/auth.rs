@@ -0,0 +1,517 @@
+//! Token-scoped permissions for exposing the library over an HTTP or WebSocket API.
+//!
+//! Each token in a [TokenStore] is granted a [Scope]. Handlers ask an [Authorizer] whether
+//! the token of a request may perform an [Operation] before calling into the library, so
+//! community moderators can get read-only or broadcast access without the admin password.
+//! Raw commands are checked against the [CommandPolicy] of the scope, and
+//! [Authorizer::rcon_for] returns a client enforcing that policy, on top of the client's own,
+//! for the generic command path.
+//!
+//! With the `http-auth` feature, [AuthLayer] is a [tower](https://docs.rs/tower) middleware
+//! doing the check for every request of an HTTP server built on tower, like axum or hyper.
+//!
+//! # Example:
+//! ```
+//! use palworld_server::auth::{AuthError, Authorizer, Operation, Scope, TokenStore};
+//!
+//! let mut tokens = TokenStore::new();
+//! tokens.add("s3cr3t-mod-token", "moderators", Scope::Broadcast);
+//! let authorizer = Authorizer::new(tokens);
+//!
+//! let header = Some("Bearer s3cr3t-mod-token");
+//! assert!(authorizer.authorize(header, &Operation::Broadcast).is_ok());
+//! let error = authorizer.authorize(header, &Operation::Shutdown).unwrap_err();
+//! assert!(matches!(
+//!     error.downcast_ref::<AuthError>(),
+//!     Some(AuthError::Forbidden { .. })
+//! ));
+//! ```
+
+use std::collections::HashMap;
+#[cfg(feature = "http-auth")]
+use std::future::Future;
+#[cfg(feature = "http-auth")]
+use std::pin::Pin;
+#[cfg(feature = "http-auth")]
+use std::sync::Arc;
+#[cfg(feature = "http-auth")]
+use std::task::{Context, Poll};
+
+use anyhow::Result;
+
+use crate::rcon::{CommandPolicy, PalworldRCON};
+use crate::secret::Secret;
+
+/// Commands a [Scope::ReadOnly] token may send through the generic command path.
+pub const READ_ONLY_COMMANDS: [&str; 2] = ["Info", "ShowPlayers"];
+
+/// What a token may do, each scope includes the ones before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Scope {
+    /// Status, players, metrics and events.
+    ReadOnly,
+    /// Also in-game broadcasts.
+    Broadcast,
+    /// Everything, including kicks, bans, saves, shutdowns and raw commands.
+    Admin,
+}
+
+impl Scope {
+    /// Raw commands the scope may send.
+    pub fn command_policy(&self) -> CommandPolicy {
+        let mut allow: Vec<String> = READ_ONLY_COMMANDS.iter().map(|c| c.to_string()).collect();
+        match self {
+            Self::ReadOnly => (),
+            Self::Broadcast => allow.push("Broadcast".to_string()),
+            Self::Admin => allow.clear(),
+        }
+        CommandPolicy {
+            allow,
+            ..CommandPolicy::new()
+        }
+    }
+}
+
+/// A library operation behind an API endpoint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operation {
+    Status,
+    Players,
+    Metrics,
+    /// Subscribing to the event stream.
+    Events,
+    Broadcast,
+    Save,
+    Kick,
+    Ban,
+    Shutdown,
+    Backup,
+    Service,
+    /// A raw RCON command, checked against [Scope::command_policy].
+    Command(String),
+}
+
+impl Operation {
+    /// Least scope allowed to perform the operation, raw commands are checked separately.
+    pub fn required_scope(&self) -> Scope {
+        match self {
+            Self::Status | Self::Players | Self::Metrics | Self::Events => Scope::ReadOnly,
+            Self::Broadcast => Scope::Broadcast,
+            Self::Save | Self::Kick | Self::Ban | Self::Shutdown | Self::Backup | Self::Service => {
+                Scope::Admin
+            }
+            Self::Command(_) => Scope::ReadOnly,
+        }
+    }
+}
+
+/// Why a request was refused, returned inside [anyhow::Error] and matched with
+/// `downcast_ref::<AuthError>()`. HTTP handlers answer 401 to the first two and 403 to
+/// [AuthError::Forbidden].
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuthError {
+    /// The request has no bearer token.
+    MissingToken,
+    /// The token isn't in the [TokenStore].
+    InvalidToken,
+    /// The token's scope doesn't allow the operation.
+    Forbidden { name: String, scope: Scope },
+}
+
+impl std::fmt::Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingToken => write!(f, "No API token given"),
+            Self::InvalidToken => write!(f, "Unknown API token"),
+            Self::Forbidden { name, scope } => {
+                write!(
+                    f,
+                    "Token '{name}' with scope {scope:?} isn't allowed to do that"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+impl AuthError {
+    /// HTTP status to answer the request with, 401 or 403.
+    pub fn status(&self) -> u16 {
+        match self {
+            Self::MissingToken | Self::InvalidToken => 401,
+            Self::Forbidden { .. } => 403,
+        }
+    }
+}
+
+/// Who a token belongs to and what it may do.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct TokenGrant {
+    /// Name shown in logs, like the moderator or bot using the token.
+    pub name: String,
+    pub scope: Scope,
+}
+
+/// Tokens and their grants, usually read from a JSON or TOML file. `Debug` output only
+/// lists the grants, not the tokens.
+#[derive(Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct TokenStore {
+    tokens: HashMap<Secret<String>, TokenGrant>,
+}
+
+impl TokenStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Grants `scope` to `token`, replacing an earlier grant.
+    pub fn add(&mut self, token: impl Into<String>, name: impl Into<String>, scope: Scope) {
+        let grant = TokenGrant {
+            name: name.into(),
+            scope,
+        };
+        self.tokens.insert(Secret::new(token.into()), grant);
+    }
+
+    /// Revokes `token`, returns false if it wasn't granted.
+    pub fn remove(&mut self, token: &str) -> bool {
+        self.tokens.remove(token).is_some()
+    }
+
+    pub fn get(&self, token: &str) -> Option<&TokenGrant> {
+        self.tokens.get(token)
+    }
+}
+
+impl std::fmt::Debug for TokenStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.tokens.values()).finish()
+    }
+}
+
+/// Checks the token of every request, see the [module documentation](self).
+#[derive(Debug, Clone, Default)]
+pub struct Authorizer {
+    pub tokens: TokenStore,
+}
+
+impl Authorizer {
+    pub fn new(tokens: TokenStore) -> Self {
+        Self { tokens }
+    }
+
+    /// Checks the `Authorization` header of a request allows `operation`, returning the
+    /// grant of its token.
+    pub fn authorize(&self, header: Option<&str>, operation: &Operation) -> Result<&TokenGrant> {
+        let token = header
+            .and_then(bearer_token)
+            .ok_or(AuthError::MissingToken)?;
+        let grant = self.tokens.get(token).ok_or(AuthError::InvalidToken)?;
+        let allowed = grant.scope >= operation.required_scope()
+            && match operation {
+                Operation::Command(cmd) => grant.scope.command_policy().check(cmd).is_ok(),
+                _ => true,
+            };
+        match allowed {
+            true => Ok(grant),
+            false => {
+                log::info!("Refused {operation:?} for '{}'", grant.name);
+                Err(AuthError::Forbidden {
+                    name: grant.name.clone(),
+                    scope: grant.scope,
+                }
+                .into())
+            }
+        }
+    }
+
+    /// `rcon` restricted to the commands `grant` may send, for handlers passing commands on.
+    /// Commands need to pass both the policy `rcon` already has and the scope's.
+    pub fn rcon_for(&self, rcon: &PalworldRCON, grant: &TokenGrant) -> PalworldRCON {
+        let mut rcon = rcon.clone();
+        rcon.policy = match (grant.scope, rcon.policy) {
+            (Scope::Admin, policy) => policy,
+            (scope, Some(policy)) => Some(policy.intersect(&scope.command_policy())),
+            (scope, None) => Some(scope.command_policy()),
+        };
+        rcon
+    }
+}
+
+/// Maps a request to the [Operation] it performs, see [AuthLayer].
+#[cfg(feature = "http-auth")]
+pub type Classify = dyn Fn(&http::Method, &http::Uri) -> Operation + Send + Sync;
+
+/// Tower middleware checking the `Authorization` header of every request with an
+/// [Authorizer] before it reaches the handlers. `classify` names the [Operation] of a request,
+/// refused requests are answered with the [AuthError::status] and an empty body. Allowed
+/// requests carry the [TokenGrant] in their extensions, for [Authorizer::rcon_for].
+///
+/// # Example:
+/// ```no_run
+/// use http::Method;
+/// use palworld_server::auth::{AuthLayer, Authorizer, Operation, TokenStore};
+///
+/// let layer = AuthLayer::new(Authorizer::new(TokenStore::new()), |method, uri| {
+///     match (method, uri.path()) {
+///         (&Method::POST, "/broadcast") => Operation::Broadcast,
+///         (&Method::POST, "/shutdown") => Operation::Shutdown,
+///         (_, "/players") => Operation::Players,
+///         _ => Operation::Status,
+///     }
+/// });
+/// ```
+#[cfg(feature = "http-auth")]
+#[derive(Clone)]
+pub struct AuthLayer {
+    authorizer: Arc<Authorizer>,
+    classify: Arc<Classify>,
+}
+
+#[cfg(feature = "http-auth")]
+impl AuthLayer {
+    pub fn new(
+        authorizer: Authorizer,
+        classify: impl Fn(&http::Method, &http::Uri) -> Operation + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            authorizer: Arc::new(authorizer),
+            classify: Arc::new(classify),
+        }
+    }
+}
+
+#[cfg(feature = "http-auth")]
+impl std::fmt::Debug for AuthLayer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuthLayer")
+            .field("authorizer", &self.authorizer)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "http-auth")]
+impl<S> tower_layer::Layer<S> for AuthLayer {
+    type Service = AuthService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AuthService {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+/// The service [AuthLayer] wraps handlers in.
+#[cfg(feature = "http-auth")]
+#[derive(Clone)]
+pub struct AuthService<S> {
+    inner: S,
+    layer: AuthLayer,
+}
+
+#[cfg(feature = "http-auth")]
+impl<S, ReqBody, ResBody> tower_service::Service<http::Request<ReqBody>> for AuthService<S>
+where
+    S: tower_service::Service<http::Request<ReqBody>, Response = http::Response<ResBody>>,
+    S::Future: Send + 'static,
+    S::Error: Send + 'static,
+    ResBody: Default + Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: http::Request<ReqBody>) -> Self::Future {
+        let operation = (self.layer.classify)(request.method(), request.uri());
+        let header = request
+            .headers()
+            .get(http::header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok());
+        let grant = self
+            .layer
+            .authorizer
+            .authorize(header, &operation)
+            .cloned();
+        match grant {
+            Ok(grant) => {
+                request.extensions_mut().insert(grant);
+                Box::pin(self.inner.call(request))
+            }
+            Err(e) => {
+                let status = e.downcast_ref::<AuthError>().map_or(401, AuthError::status);
+                let mut response = http::Response::new(ResBody::default());
+                *response.status_mut() = http::StatusCode::from_u16(status)
+                    .expect("Refusals are valid status codes");
+                if status == 401 {
+                    response.headers_mut().insert(
+                        http::header::WWW_AUTHENTICATE,
+                        http::HeaderValue::from_static("Bearer"),
+                    );
+                }
+                Box::pin(std::future::ready(Ok(response)))
+            }
+        }
+    }
+}
+
+/// The token of an `Authorization: Bearer <token>` header value.
+pub fn bearer_token(header: &str) -> Option<&str> {
+    let (scheme, token) = header.trim().split_once(' ')?;
+    let token = token.trim();
+    (scheme.eq_ignore_ascii_case("bearer") && !token.is_empty()).then_some(token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rcon::{RconError, DEFAULT_SOURCE_PORT};
+
+    fn authorizer() -> Authorizer {
+        let mut tokens = TokenStore::new();
+        tokens.add("ro", "dashboard", Scope::ReadOnly);
+        tokens.add("bc", "moderators", Scope::Broadcast);
+        tokens.add("admin", "owner", Scope::Admin);
+        Authorizer::new(tokens)
+    }
+
+    #[test]
+    fn test_authorize() {
+        let authorizer = authorizer();
+        let error = |header, operation| {
+            authorizer
+                .authorize(header, &operation)
+                .unwrap_err()
+                .downcast::<AuthError>()
+                .unwrap()
+        };
+        assert_eq!(error(None, Operation::Status), AuthError::MissingToken);
+        assert_eq!(
+            error(Some("Basic ro"), Operation::Status),
+            AuthError::MissingToken
+        );
+        assert_eq!(
+            error(Some("Bearer nope"), Operation::Status),
+            AuthError::InvalidToken
+        );
+        assert!(!format!("{authorizer:?}").contains("admin"));
+
+        assert!(authorizer
+            .authorize(Some("Bearer ro"), &Operation::Players)
+            .is_ok());
+        assert!(matches!(
+            error(Some("Bearer ro"), Operation::Broadcast),
+            AuthError::Forbidden { .. }
+        ));
+        assert!(authorizer
+            .authorize(Some("bearer  bc "), &Operation::Broadcast)
+            .is_ok());
+        assert!(authorizer
+            .authorize(Some("Bearer bc"), &Operation::Kick)
+            .is_err());
+        assert!(authorizer
+            .authorize(Some("Bearer admin"), &Operation::Shutdown)
+            .is_ok());
+
+        let command = |cmd: &str| Operation::Command(cmd.to_string());
+        assert!(authorizer
+            .authorize(Some("Bearer ro"), &command("showplayers"))
+            .is_ok());
+        assert!(authorizer
+            .authorize(Some("Bearer bc"), &command("Broadcast Hi"))
+            .is_ok());
+        assert!(authorizer
+            .authorize(Some("Bearer bc"), &command("DoExit"))
+            .is_err());
+        assert!(authorizer
+            .authorize(Some("Bearer admin"), &command("DoExit"))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_rcon_for() {
+        let authorizer = authorizer();
+        let rcon = PalworldRCON::new("localhost", DEFAULT_SOURCE_PORT, "MyRCONPassword");
+        let grant = authorizer.tokens.get("bc").unwrap();
+        let policy = authorizer.rcon_for(&rcon, grant).policy.unwrap();
+        let error = policy.check("KickPlayer 7656").unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<RconError>(),
+            Some(&RconError::PolicyDenied("KickPlayer 7656".to_string()))
+        );
+        let grant = authorizer.tokens.get("admin").unwrap();
+        assert_eq!(authorizer.rcon_for(&rcon, grant), rcon);
+
+        // The operator's own policy still applies.
+        let mut rcon = rcon;
+        rcon.policy = Some(CommandPolicy {
+            deny: vec!["ShowPlayers".to_string()],
+            ..CommandPolicy::new()
+        });
+        let grant = authorizer.tokens.get("bc").unwrap();
+        let policy = authorizer.rcon_for(&rcon, grant).policy.unwrap();
+        assert!(policy.check("ShowPlayers").is_err());
+        assert!(policy.check("Broadcast hi").is_ok());
+        assert!(policy.check("DoExit").is_err());
+    }
+
+    #[cfg(feature = "http-auth")]
+    #[tokio::test]
+    async fn test_auth_layer() {
+        use tower_layer::Layer;
+        use tower_service::Service;
+
+        #[derive(Clone)]
+        struct Handler;
+
+        impl Service<http::Request<()>> for Handler {
+            type Response = http::Response<String>;
+            type Error = std::convert::Infallible;
+            type Future = std::future::Ready<Result<Self::Response, Self::Error>>;
+
+            fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+                Poll::Ready(Ok(()))
+            }
+
+            fn call(&mut self, request: http::Request<()>) -> Self::Future {
+                let grant = request.extensions().get::<TokenGrant>().unwrap();
+                std::future::ready(Ok(http::Response::new(grant.name.clone())))
+            }
+        }
+
+        let layer = AuthLayer::new(authorizer(), |method, _| match *method {
+            http::Method::POST => Operation::Broadcast,
+            _ => Operation::Status,
+        });
+        let mut service = layer.layer(Handler);
+        let mut send = |method: http::Method, token: Option<&str>| {
+            let mut request = http::Request::builder().method(method).uri("/");
+            if let Some(token) = token {
+                request = request.header("Authorization", format!("Bearer {token}"));
+            }
+            service.call(request.body(()).unwrap())
+        };
+        let response = send(http::Method::GET, Some("ro")).await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.body(), "dashboard");
+        let response = send(http::Method::POST, Some("ro")).await.unwrap();
+        assert_eq!(response.status(), 403);
+        assert!(response.body().is_empty());
+        let response = send(http::Method::GET, None).await.unwrap();
+        assert_eq!(response.status(), 401);
+        assert_eq!(response.headers()["WWW-Authenticate"], "Bearer");
+        let response = send(http::Method::POST, Some("bc")).await.unwrap();
+        assert_eq!(response.body(), "moderators");
+    }
+}
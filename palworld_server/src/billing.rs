@@ -0,0 +1,177 @@
+//! Per-player monthly playtime from the session store, for billing rented slots.
+//!
+//! Months are calendar months in a configurable timezone, sessions crossing midnight at
+//! the end of a month are split between both months.
+//!
+//! # Example:
+//! ```no_run
+//! use std::time::{Duration, SystemTime};
+//!
+//! use palworld_server::billing::{self, Tz};
+//! use palworld_server::store::SessionStore;
+//!
+//! let store = SessionStore::open("palworld.db").unwrap();
+//! let now = SystemTime::now();
+//! let exempt = ["76561190000000001".to_string()];
+//! let rows = store
+//!     .monthly_playtime(now - Duration::from_secs(90 * 24 * 3600)..now, Tz::Europe__Berlin, &exempt)
+//!     .unwrap();
+//! billing::write_csv(&rows, std::io::stdout()).unwrap();
+//! ```
+
+use std::io::Write;
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+use chrono::{DateTime, Datelike, NaiveDate, TimeZone, Utc};
+pub use chrono_tz::Tz;
+
+/// Playtime of one player in one month.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct MonthlyPlaytime {
+    /// Month in `YYYY-MM` format.
+    pub month: String,
+    pub steamid: String,
+    /// Most recent name of the player.
+    pub name: String,
+    pub playtime: Duration,
+    /// Sessions with playtime in this month.
+    pub sessions: u64,
+}
+
+impl MonthlyPlaytime {
+    pub fn hours(&self) -> f64 {
+        self.playtime.as_secs_f64() / 3600.0
+    }
+}
+
+/// Splits `start..end` at the month boundaries of `tz`, returning the time spent in each
+/// `YYYY-MM` month in order.
+pub fn split_by_month(start: SystemTime, end: SystemTime, tz: Tz) -> Vec<(String, Duration)> {
+    let mut parts = Vec::new();
+    let mut current = DateTime::<Utc>::from(start).with_timezone(&tz);
+    let end = DateTime::<Utc>::from(end).with_timezone(&tz);
+    while current < end {
+        let next = next_month_start(&current, tz).min(end);
+        let month = format!("{:04}-{:02}", current.year(), current.month());
+        parts.push((month, (next - current).to_std().unwrap_or_default()));
+        current = next;
+    }
+    parts
+}
+
+/// Local midnight on the first of the month after `time`.
+fn next_month_start(time: &DateTime<Tz>, tz: Tz) -> DateTime<Tz> {
+    let (year, month) = match time.month() {
+        12 => (time.year() + 1, 1),
+        month => (time.year(), month + 1),
+    };
+    let first = NaiveDate::from_ymd_opt(year, month, 1).expect("valid first of month");
+    // Midnight is skipped by DST changes in a few zones, the first existing hour is used.
+    (0..24)
+        .find_map(|hour| {
+            tz.from_local_datetime(&first.and_hms_opt(hour, 0, 0)?)
+                .earliest()
+        })
+        .expect("a day has an existing hour")
+}
+
+/// Writes playtime as CSV, one row per player and month.
+pub fn write_csv(rows: &[MonthlyPlaytime], mut writer: impl Write) -> Result<()> {
+    writeln!(writer, "month,steamid,name,hours,seconds,sessions")?;
+    for row in rows {
+        writeln!(
+            writer,
+            "{},{},{},{:.2},{},{}",
+            row.month,
+            row.steamid,
+            csv_escape(&row.name),
+            row.hours(),
+            row.playtime.as_secs(),
+            row.sessions
+        )?;
+    }
+    Ok(())
+}
+
+/// Quotes names containing commas, quotes or line breaks.
+fn csv_escape(value: &str) -> String {
+    match value.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", value.replace('"', "\"\"")),
+        false => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::PlayerInfo;
+    use crate::store::{from_unix, SessionStore};
+
+    fn player(steamid: &str, name: &str) -> PlayerInfo {
+        PlayerInfo {
+            name: name.to_string(),
+            uid: "1".to_string(),
+            steamid: steamid.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_split_by_month() {
+        // 2024-01-31 22:00 UTC to 2024-02-01 02:00 UTC
+        let start = from_unix(1706738400);
+        let end = from_unix(1706752800);
+        let hour = Duration::from_secs(3600);
+        assert_eq!(
+            split_by_month(start, end, Tz::UTC),
+            vec![
+                ("2024-01".to_string(), hour * 2),
+                ("2024-02".to_string(), hour * 2)
+            ]
+        );
+        // Berlin is UTC+1, midnight is at 23:00 UTC.
+        assert_eq!(
+            split_by_month(start, end, Tz::Europe__Berlin),
+            vec![
+                ("2024-01".to_string(), hour),
+                ("2024-02".to_string(), hour * 3)
+            ]
+        );
+        assert!(split_by_month(end, start, Tz::UTC).is_empty());
+    }
+
+    #[test]
+    fn test_monthly_playtime() {
+        let store = SessionStore::open_in_memory().unwrap();
+        let hour = Duration::from_secs(3600);
+        let start = from_unix(1706738400);
+        store.record_join(&player("1", "Alice"), start).unwrap();
+        store
+            .record_leave(&player("1", "Alice"), start + hour * 4)
+            .unwrap();
+        store
+            .record_join(&player("1", "Alice, the Great"), start + hour * 5)
+            .unwrap();
+        store.record_join(&player("2", "Admin"), start).unwrap();
+
+        let exempt = ["2".to_string()];
+        let rows = store
+            .monthly_playtime(start..start + hour * 6, Tz::UTC, &exempt)
+            .unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].month, "2024-01");
+        assert_eq!(rows[0].playtime, hour * 2);
+        assert_eq!(rows[1].playtime, hour * 3);
+        assert_eq!(rows[1].sessions, 2);
+        assert_eq!(rows[1].name, "Alice, the Great");
+
+        let mut csv = Vec::new();
+        write_csv(&rows, &mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap().lines().nth(2),
+            Some("2024-02,1,\"Alice, the Great\",3.00,10800,2")
+        );
+    }
+}
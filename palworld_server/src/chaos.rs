@@ -0,0 +1,374 @@
+//! Fault injection between the RCON client and the server, for hardening retries.
+//!
+//! A [ChaosProxy] listens on localhost and forwards RCON connections to the real server,
+//! adding latency, dropping connections in the middle of a response, cutting response
+//! bodies short and failing logins on a fixed schedule from a [FaultConfig]. Point a
+//! [PalworldRCON] at it with [ChaosProxy::wrap]. Faults happen on every Nth connection or
+//! response, so tests can count on them.
+//!
+//! # Example:
+//! ```no_run
+//! use palworld_server::chaos::{ChaosProxy, FaultConfig};
+//! use palworld_server::rcon::{PalworldRCON, DEFAULT_SOURCE_PORT};
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let rcon = PalworldRCON::new("localhost", DEFAULT_SOURCE_PORT, "MyRCONPassword");
+//!     let config = FaultConfig::parse("latency=200ms,disconnect=5,auth=10").unwrap();
+//!     let proxy = ChaosProxy::start("localhost:25575", config).await.unwrap();
+//!     let flaky = proxy.wrap(&rcon);
+//!     for _ in 0..20 {
+//!         println!("{:?}", flaky.get_version().await);
+//!     }
+//! }
+//! ```
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+use crate::rcon::PalworldRCON;
+
+/// Packet types of the Source RCON protocol.
+const SERVERDATA_RESPONSE_VALUE: i32 = 0;
+const SERVERDATA_AUTH_RESPONSE: i32 = 2;
+const SERVERDATA_AUTH: i32 = 3;
+
+/// Largest packet the proxy accepts, well over what Palworld sends.
+const MAX_PACKET_LEN: i32 = 1024 * 1024;
+
+/// Faults a [ChaosProxy] injects. An interval of 0 never injects that fault.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct FaultConfig {
+    /// Delay before every response packet is passed on.
+    pub latency: Duration,
+    /// Close every Nth connection halfway through its first response.
+    pub disconnect_every: u32,
+    /// Cut the body of every Nth response packet in half, keeping the framing valid.
+    pub truncate_every: u32,
+    /// Reject every Nth login as a wrong password without asking the server.
+    pub auth_failure_every: u32,
+}
+
+impl FaultConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses comma separated faults like `latency=200ms,disconnect=5,truncate=3,auth=10`.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut config = Self::new();
+        for fault in spec.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+            let Some((name, value)) = fault.split_once('=') else {
+                bail!("Fault '{fault}' isn't name=value");
+            };
+            let every = || {
+                value
+                    .parse::<u32>()
+                    .with_context(|| format!("Invalid interval '{value}' of {name}"))
+            };
+            match name {
+                "latency" => config.latency = humantime::parse_duration(value)?,
+                "disconnect" => config.disconnect_every = every()?,
+                "truncate" => config.truncate_every = every()?,
+                "auth" => config.auth_failure_every = every()?,
+                _ => {
+                    bail!("Unknown fault '{name}', expected latency, disconnect, truncate or auth")
+                }
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Counts connections and responses to decide which get a fault.
+#[derive(Debug, Default)]
+struct Schedule {
+    connections: AtomicU32,
+    responses: AtomicU32,
+}
+
+/// Whether the `count`th occurrence, starting at 1, is due for a fault every `every`.
+fn is_due(count: u32, every: u32) -> bool {
+    every != 0 && count.is_multiple_of(every)
+}
+
+/// Local RCON endpoint injecting faults, stops when dropped.
+#[derive(Debug)]
+pub struct ChaosProxy {
+    port: u16,
+    handle: JoinHandle<()>,
+}
+
+impl ChaosProxy {
+    /// Listens on a free localhost port and forwards to `upstream`, like `palworld.lan:25575`.
+    pub async fn start(upstream: impl Into<String>, config: FaultConfig) -> Result<Self> {
+        let upstream = upstream.into();
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port();
+        log::warn!("Injecting {config:?} into RCON connections to {upstream} via port {port}");
+        let config = Arc::new(config);
+        let schedule = Arc::new(Schedule::default());
+        let handle = tokio::spawn(async move {
+            loop {
+                let client = match listener.accept().await {
+                    Ok((client, _)) => client,
+                    Err(e) => {
+                        log::warn!("Chaos proxy failed to accept: {e}");
+                        continue;
+                    }
+                };
+                let (upstream, config, schedule) =
+                    (upstream.clone(), config.clone(), schedule.clone());
+                tokio::spawn(async move {
+                    if let Err(e) = proxy(client, &upstream, &config, &schedule).await {
+                        log::debug!("Chaos proxy connection ended: {e}");
+                    }
+                });
+            }
+        });
+        Ok(Self { port, handle })
+    }
+
+    /// Local port the proxy listens on.
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// `rcon` connecting through the proxy. Protocol detection is turned off so every
+    /// connection is one the test counted on.
+    pub fn wrap(&self, rcon: &PalworldRCON) -> PalworldRCON {
+        let mut rcon = rcon.clone();
+        rcon.host = "127.0.0.1".to_string();
+        rcon.port = self.port;
+        rcon.protocol.detect = false;
+        rcon
+    }
+}
+
+impl Drop for ChaosProxy {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+async fn proxy(
+    client: TcpStream,
+    upstream: &str,
+    config: &FaultConfig,
+    schedule: &Schedule,
+) -> Result<()> {
+    let connection = schedule.connections.fetch_add(1, Ordering::SeqCst) + 1;
+    let (mut client_read, mut client_write) = client.into_split();
+
+    let Some(auth) = read_packet(&mut client_read).await? else {
+        return Ok(());
+    };
+    if is_due(connection, config.auth_failure_every) && packet_type(&auth) == SERVERDATA_AUTH {
+        log::info!("Chaos: failing the login of connection {connection}");
+        client_write
+            .write_all(&encode_packet(-1, SERVERDATA_AUTH_RESPONSE, ""))
+            .await?;
+        return Ok(());
+    }
+
+    let server = TcpStream::connect(upstream)
+        .await
+        .with_context(|| format!("Failed to connect to {upstream}"))?;
+    let (mut server_read, mut server_write) = server.into_split();
+    server_write.write_all(&auth).await?;
+    let requests = tokio::spawn(async move {
+        // Requests pass unchanged until either side hangs up.
+        let _ = tokio::io::copy(&mut client_read, &mut server_write).await;
+    });
+
+    let disconnect = is_due(connection, config.disconnect_every);
+    let result = async {
+        while let Some(mut packet) = read_packet(&mut server_read).await? {
+            tokio::time::sleep(config.latency).await;
+            if packet_type(&packet) == SERVERDATA_RESPONSE_VALUE {
+                if disconnect {
+                    log::info!("Chaos: disconnecting connection {connection} mid-response");
+                    client_write.write_all(&packet[..packet.len() / 2]).await?;
+                    return Ok(());
+                }
+                let response = schedule.responses.fetch_add(1, Ordering::SeqCst) + 1;
+                if is_due(response, config.truncate_every) {
+                    log::info!("Chaos: truncating response {response}");
+                    packet = truncate_packet(&packet);
+                }
+            }
+            client_write.write_all(&packet).await?;
+        }
+        Ok::<(), anyhow::Error>(())
+    }
+    .await;
+    requests.abort();
+    result
+}
+
+/// Reads one packet including its length prefix, None when the stream ends between packets.
+async fn read_packet(stream: &mut (impl AsyncRead + Unpin)) -> Result<Option<Vec<u8>>> {
+    let mut length = [0u8; 4];
+    match stream.read_exact(&mut length).await {
+        Ok(_) => (),
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let len = i32::from_le_bytes(length);
+    if !(10..=MAX_PACKET_LEN).contains(&len) {
+        bail!("Invalid RCON packet length {len}");
+    }
+    let mut packet = length.to_vec();
+    packet.resize(4 + len as usize, 0);
+    stream.read_exact(&mut packet[4..]).await?;
+    Ok(Some(packet))
+}
+
+fn packet_type(packet: &[u8]) -> i32 {
+    i32::from_le_bytes(packet[8..12].try_into().unwrap())
+}
+
+/// A packet with its length prefix.
+fn encode_packet(id: i32, packet_type: i32, body: &str) -> Vec<u8> {
+    let len = 10 + body.len() as i32;
+    let mut packet = Vec::with_capacity(4 + len as usize);
+    packet.extend_from_slice(&len.to_le_bytes());
+    packet.extend_from_slice(&id.to_le_bytes());
+    packet.extend_from_slice(&packet_type.to_le_bytes());
+    packet.extend_from_slice(body.as_bytes());
+    packet.extend_from_slice(&[0, 0]);
+    packet
+}
+
+/// The packet with the first half of its body, on a character boundary.
+fn truncate_packet(packet: &[u8]) -> Vec<u8> {
+    let id = i32::from_le_bytes(packet[4..8].try_into().unwrap());
+    let body = String::from_utf8_lossy(&packet[12..packet.len() - 2]);
+    let mut end = body.len() / 2;
+    while !body.is_char_boundary(end) {
+        end -= 1;
+    }
+    encode_packet(id, packet_type(packet), &body[..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let config = FaultConfig::parse("latency=200ms, disconnect=5,truncate=3,auth=10").unwrap();
+        assert_eq!(
+            config,
+            FaultConfig {
+                latency: Duration::from_millis(200),
+                disconnect_every: 5,
+                truncate_every: 3,
+                auth_failure_every: 10,
+            }
+        );
+        assert_eq!(FaultConfig::parse("").unwrap(), FaultConfig::new());
+        assert!(FaultConfig::parse("disconnect").is_err());
+        assert!(FaultConfig::parse("truncate=-1").is_err());
+        assert!(FaultConfig::parse("explode=1").is_err());
+        assert!(!is_due(3, 0));
+        assert!(is_due(6, 3));
+    }
+
+    #[test]
+    fn test_truncate_packet() {
+        let packet = encode_packet(7, SERVERDATA_RESPONSE_VALUE, "Welcome to Pal Server");
+        assert_eq!(packet.len(), 4 + 10 + 21);
+        assert_eq!(
+            truncate_packet(&packet),
+            encode_packet(7, SERVERDATA_RESPONSE_VALUE, "Welcome to")
+        );
+        // Not split inside a character.
+        let packet = encode_packet(7, SERVERDATA_RESPONSE_VALUE, "パル");
+        assert_eq!(
+            truncate_packet(&packet),
+            encode_packet(7, SERVERDATA_RESPONSE_VALUE, "パ")
+        );
+    }
+
+    /// Answers the login and then every command with its own text.
+    async fn echo_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    while let Ok(Some(packet)) = read_packet(&mut stream).await {
+                        let id = i32::from_le_bytes(packet[4..8].try_into().unwrap());
+                        let body = String::from_utf8_lossy(&packet[12..packet.len() - 2]);
+                        let response = match packet_type(&packet) {
+                            SERVERDATA_AUTH => encode_packet(id, SERVERDATA_AUTH_RESPONSE, ""),
+                            _ => encode_packet(id, SERVERDATA_RESPONSE_VALUE, &body),
+                        };
+                        stream.write_all(&response).await.unwrap();
+                    }
+                });
+            }
+        });
+        address
+    }
+
+    /// Logs in through the proxy and sends `command`, returning the response packets.
+    async fn exchange(port: u16, command: &str) -> Vec<Vec<u8>> {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        stream
+            .write_all(&encode_packet(1, SERVERDATA_AUTH, "password"))
+            .await
+            .unwrap();
+        let mut packets = vec![read_packet(&mut stream).await.unwrap().unwrap()];
+        if i32::from_le_bytes(packets[0][4..8].try_into().unwrap()) == -1 {
+            return packets;
+        }
+        stream
+            .write_all(&encode_packet(2, 2, command))
+            .await
+            .unwrap();
+        if let Ok(Some(packet)) = read_packet(&mut stream).await {
+            packets.push(packet);
+        }
+        packets
+    }
+
+    #[tokio::test]
+    async fn test_proxy_faults() {
+        let config = FaultConfig {
+            disconnect_every: 3,
+            truncate_every: 2,
+            auth_failure_every: 4,
+            ..FaultConfig::new()
+        };
+        let proxy = ChaosProxy::start(echo_server().await, config)
+            .await
+            .unwrap();
+        let response = |body: &str| encode_packet(2, SERVERDATA_RESPONSE_VALUE, body);
+
+        // Connection 1, response 1 passes.
+        let packets = exchange(proxy.port(), "info").await;
+        assert_eq!(packets[0], encode_packet(1, SERVERDATA_AUTH_RESPONSE, ""));
+        assert_eq!(packets[1], response("info"));
+        // Connection 2, response 2 is cut in half.
+        assert_eq!(exchange(proxy.port(), "info").await[1], response("in"));
+        // Connection 3 drops mid-response.
+        assert_eq!(exchange(proxy.port(), "info").await.len(), 1);
+        // Connection 4 fails to log in.
+        let packets = exchange(proxy.port(), "info").await;
+        assert_eq!(
+            packets,
+            vec![encode_packet(-1, SERVERDATA_AUTH_RESPONSE, "")]
+        );
+    }
+}
@@ -0,0 +1,307 @@
+//! In-game chat commands without server mods.
+//!
+//! Palworld has no RCON command to read chat, but chat lines end up in the server log.
+//! [ChatBridge] tails the log over SSH, parses chat lines and answers `!commands` with a
+//! broadcast. Tailing uses `tail -F`, so the log has to be on a Linux host.
+//!
+//! # Example:
+//! ```no_run
+//! use palworld_server::chat::ChatBridge;
+//! use palworld_server::rcon::{PalworldRCON, DEFAULT_SOURCE_PORT};
+//! use palworld_server::ssh::PalworldConnection;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let rcon = PalworldRCON::new("localhost", DEFAULT_SOURCE_PORT, "MyRCONPassword");
+//!     let ssh = PalworldConnection::new("localhost:22", "steam", "MySSHPassword");
+//!     let mut bridge = ChatBridge::new(ssh, rcon, "/home/steam/palworld.log");
+//!     bridge
+//!         .online("online")
+//!         .reply("discord", "Join us at discord.gg/example")
+//!         .command("roll", |cmd| async move {
+//!             Ok(Some(format!("{} rolled {}", cmd.player, cmd.args.len())))
+//!         });
+//!     bridge.run().await.unwrap();
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+use anyhow::Result;
+use regex::Regex;
+
+use crate::rcon::PalworldRCON;
+use crate::ssh::{shell_quote, HostOs, OutputLine, PalworldConnection};
+use crate::trace::{self, TraceContext, Trigger};
+
+/// Matches chat lines like `[Chat::Global]['Alice' (UserId=steam_765..., IP=...)]: hello`.
+/// The name runs up to the quote that closes it, so names may contain quotes themselves.
+pub static DEFAULT_CHAT_PATTERN: &str =
+    r"\[Chat::[A-Za-z]+\]\['(?P<name>.+?)'(?: \([^)]*\))?\]: (?P<message>.*)$";
+
+/// A chat message parsed from the log.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatMessage {
+    /// Name of the player that sent the message.
+    pub name: String,
+    pub message: String,
+}
+
+/// Parses chat lines out of the server log.
+#[derive(Debug, Clone)]
+pub struct ChatParser {
+    regex: Regex,
+}
+
+impl ChatParser {
+    /// Create a new [ChatParser] from a regex with `name` and `message` capture groups.
+    pub fn new(pattern: &str) -> Result<Self> {
+        let regex = Regex::new(pattern)?;
+        for group in ["name", "message"] {
+            if !regex.capture_names().any(|name| name == Some(group)) {
+                anyhow::bail!("Chat pattern is missing the '{group}' capture group");
+            }
+        }
+        Ok(Self { regex })
+    }
+
+    /// Parses a log line, returns None if it isn't a chat line.
+    pub fn parse_line(&self, line: &str) -> Option<ChatMessage> {
+        let captures = self.regex.captures(line.trim_end())?;
+        Some(ChatMessage {
+            name: captures["name"].to_string(),
+            message: captures["message"].trim().to_string(),
+        })
+    }
+}
+
+impl Default for ChatParser {
+    fn default() -> Self {
+        Self::new(DEFAULT_CHAT_PATTERN).expect("Default chat pattern is valid")
+    }
+}
+
+/// A `!command` sent by a player.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatCommand {
+    /// Name of the player that sent the command.
+    pub player: String,
+    /// Command name without the prefix, lowercase.
+    pub name: String,
+    /// Whitespace separated arguments.
+    pub args: Vec<String>,
+}
+
+impl ChatCommand {
+    /// Parses a chat message into a command if it starts with `prefix`.
+    pub fn parse(message: &ChatMessage, prefix: char) -> Option<Self> {
+        let text = message.message.strip_prefix(prefix)?;
+        let mut words = text.split_whitespace();
+        let name = words.next()?.to_lowercase();
+        Some(Self {
+            player: message.name.clone(),
+            name,
+            args: words.map(|arg| arg.to_string()).collect(),
+        })
+    }
+}
+
+type HandlerFuture = Pin<Box<dyn Future<Output = Result<Option<String>>> + Send>>;
+type Handler = Box<dyn Fn(ChatCommand) -> HandlerFuture + Send + Sync>;
+
+/// Tails the server log and answers chat commands with broadcasts.
+pub struct ChatBridge {
+    ssh: PalworldConnection,
+    rcon: PalworldRCON,
+    /// Path of the server log on the SSH host.
+    pub log_path: String,
+    pub parser: ChatParser,
+    /// Character that starts a command, `!` by default.
+    pub prefix: char,
+    /// Space replacement passed to [PalworldRCON::broadcast].
+    pub replace_space: Option<String>,
+    /// Delay before tailing the log again after the SSH connection drops.
+    pub reconnect_delay: Duration,
+    handlers: HashMap<String, Handler>,
+}
+
+impl ChatBridge {
+    pub fn new(ssh: PalworldConnection, rcon: PalworldRCON, log_path: impl Into<String>) -> Self {
+        Self {
+            ssh,
+            rcon,
+            log_path: log_path.into(),
+            parser: ChatParser::default(),
+            prefix: '!',
+            replace_space: None,
+            reconnect_delay: Duration::from_secs(10),
+            handlers: HashMap::new(),
+        }
+    }
+
+    /// Registers a handler for `!name`. The returned text, if any, is broadcast.
+    pub fn command<F, Fut>(&mut self, name: &str, handler: F) -> &mut Self
+    where
+        F: Fn(ChatCommand) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Option<String>>> + Send + 'static,
+    {
+        self.handlers.insert(
+            name.to_lowercase(),
+            Box::new(move |command| Box::pin(handler(command))),
+        );
+        self
+    }
+
+    /// Registers `!name` to always broadcast `text`.
+    pub fn reply(&mut self, name: &str, text: impl Into<String>) -> &mut Self {
+        let text = text.into();
+        self.command(name, move |_| {
+            let text = text.clone();
+            async move { Ok(Some(text)) }
+        })
+    }
+
+    /// Registers `!name` to broadcast the online players.
+    pub fn online(&mut self, name: &str) -> &mut Self {
+        let rcon = self.rcon.clone();
+        self.command(name, move |_| {
+            let rcon = rcon.clone();
+            async move {
+                let players = rcon.get_player_info().await?;
+                let names = players
+                    .iter()
+                    .map(|player| player.name.as_str())
+                    .collect::<Vec<&str>>();
+                Ok(Some(format!(
+                    "{} online: {}",
+                    names.len(),
+                    names.join(", ")
+                )))
+            }
+        })
+    }
+
+    /// Handles a single log line, returns the broadcast response if it was a known command.
+    pub async fn handle_line(&self, line: &str) -> Result<Option<String>> {
+        let Some(message) = self.parser.parse_line(line) else {
+            return Ok(None);
+        };
+        let Some(command) = ChatCommand::parse(&message, self.prefix) else {
+            return Ok(None);
+        };
+        let Some(handler) = self.handlers.get(&command.name) else {
+            log::debug!(
+                "Unknown chat command '{}' from {}",
+                command.name,
+                command.player
+            );
+            return Ok(None);
+        };
+        let context = TraceContext::new(Trigger::Chat {
+            player: command.player.clone(),
+            command: command.name.clone(),
+        });
+        trace::traced(context, async {
+            let Some(response) = handler(command).await? else {
+                return Ok(None);
+            };
+            self.rcon
+                .broadcast(response.as_str(), self.replace_space.clone())
+                .await?;
+            Ok(Some(response))
+        })
+        .await
+    }
+
+    /// Tails the log forever, reconnecting when the SSH connection drops. Fails if the SSH
+    /// host runs Windows, which has no `tail -F`.
+    pub async fn run(self) -> Result<()> {
+        if self.ssh.host_os().await? == HostOs::Windows {
+            anyhow::bail!(
+                "Can't tail {} on {}, chat commands need a Linux host",
+                self.log_path,
+                self.ssh.hostname
+            );
+        }
+        let cmd = format!("tail -n 0 -F {}", shell_quote(&self.log_path));
+        loop {
+            match self.ssh.command_streamed(cmd.as_str()).await {
+                Ok(mut stream) => {
+                    while let Some(line) = stream.next_line().await {
+                        if let OutputLine::Stdout(line) = line {
+                            if let Err(e) = self.handle_line(&line).await {
+                                log::warn!("Chat command failed: {e}");
+                            }
+                        }
+                    }
+                    log::warn!("Log tail of {} ended", self.log_path);
+                }
+                Err(e) => log::warn!("Failed to tail {}: {e}", self.log_path),
+            }
+            tokio::time::sleep(self.reconnect_delay).await;
+        }
+    }
+}
+
+impl std::fmt::Debug for ChatBridge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChatBridge")
+            .field("log_path", &self.log_path)
+            .field("prefix", &self.prefix)
+            .field("commands", &self.handlers.keys().collect::<Vec<&String>>())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_line() {
+        let parser = ChatParser::default();
+        let line = "[2024-02-01 12:00:00] [Chat::Global]['Alice' (UserId=steam_76561190000000001, IP=127.0.0.1)]: !online now";
+        let message = parser.parse_line(line).unwrap();
+        assert_eq!(message.name, "Alice");
+        assert_eq!(message.message, "!online now");
+        assert!(parser
+            .parse_line("[2024-02-01 12:00:00] Alice joined the server.")
+            .is_none());
+    }
+
+    #[test]
+    fn test_parse_line_quoted_name() {
+        let parser = ChatParser::default();
+        let line = "[2024-02-01 12:00:00] [Chat::Global]['O'Brien' (UserId=steam_76561190000000002, IP=127.0.0.1)]: !roll";
+        let message = parser.parse_line(line).unwrap();
+        assert_eq!(message.name, "O'Brien");
+        assert_eq!(message.message, "!roll");
+        let message = parser
+            .parse_line("[Chat::Guild]['It's me']: it's ']: here")
+            .unwrap();
+        assert_eq!(message.name, "It's me");
+        assert_eq!(message.message, "it's ']: here");
+    }
+
+    #[test]
+    fn test_parse_command() {
+        let message = ChatMessage {
+            name: "Alice".to_string(),
+            message: "!Vote 2 yes".to_string(),
+        };
+        let command = ChatCommand::parse(&message, '!').unwrap();
+        assert_eq!(command.name, "vote");
+        assert_eq!(command.args, vec!["2", "yes"]);
+        assert!(ChatCommand::parse(&message, '/').is_none());
+    }
+
+    #[test]
+    fn test_custom_pattern() {
+        assert!(ChatParser::new(r"(?P<name>\w+)").is_err());
+        let parser = ChatParser::new(r"^<(?P<name>\w+)> (?P<message>.*)$").unwrap();
+        assert_eq!(parser.parse_line("<Bob> hi").unwrap().message, "hi");
+    }
+}
@@ -0,0 +1,199 @@
+//! Finding bases of guilds whose members stopped playing.
+//!
+//! Members are looked up in the session store by their Unique ID and in the guild data of
+//! `Level.sav`, the most recent of both counts. Nothing is deleted, the plan lists the bases
+//! so they can be removed in game or with save editing tools.
+//!
+//! # Example:
+//! ```no_run
+//! use std::time::{Duration, SystemTime};
+//!
+//! use palworld_server::cleanup;
+//! use palworld_server::savefile::LevelSave;
+//! use palworld_server::store::SessionStore;
+//!
+//! let path = "Pal/Saved/SaveGames/0/0123456789ABCDEF/Level.sav";
+//! let level = LevelSave::open(path).unwrap();
+//! let saved_at = std::fs::metadata(path).unwrap().modified().unwrap();
+//! let store = SessionStore::open("palworld.db").unwrap();
+//! let month = Duration::from_secs(30 * 24 * 3600);
+//! let plan = cleanup::plan(&level, saved_at, &store, month, SystemTime::now()).unwrap();
+//! for guild in &plan.guilds {
+//!     println!("{}: {} base(s)", guild.name, guild.bases.len());
+//! }
+//! ```
+
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+
+use crate::savefile::{BaseCamp, Guid, GuildMember, LevelSave};
+use crate::store::SessionStore;
+
+/// A guild none of the members played in for the inactivity period.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct AbandonedGuild {
+    pub id: Guid,
+    pub name: String,
+    /// Names of the members.
+    pub members: Vec<String>,
+    /// When any member was last seen, None if none ever was.
+    pub last_seen: Option<SystemTime>,
+    pub bases: Vec<BaseCamp>,
+}
+
+/// Bases to remove, grouped by guild, least recently seen guild first.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct CleanupPlan {
+    pub inactive_for: Duration,
+    pub guilds: Vec<AbandonedGuild>,
+}
+
+impl CleanupPlan {
+    pub fn base_count(&self) -> usize {
+        self.guilds.iter().map(|guild| guild.bases.len()).sum()
+    }
+}
+
+/// When `member` was last online. `saved_at` is when `level` was written, usually the
+/// modification time of `Level.sav`.
+pub fn last_seen(
+    member: &GuildMember,
+    level: &LevelSave,
+    saved_at: SystemTime,
+    store: &SessionStore,
+    now: SystemTime,
+) -> Result<Option<SystemTime>> {
+    let recorded = store.last_seen_by_uid(&member.uid.player_uid().to_string(), now)?;
+    let saved = level
+        .offline_for(member.last_online_ticks)
+        .and_then(|offline| saved_at.checked_sub(offline));
+    Ok(recorded.max(saved))
+}
+
+/// Guilds with bases whose members were all last seen more than `inactive_for` before `now`.
+pub fn plan(
+    level: &LevelSave,
+    saved_at: SystemTime,
+    store: &SessionStore,
+    inactive_for: Duration,
+    now: SystemTime,
+) -> Result<CleanupPlan> {
+    let cutoff = now
+        .checked_sub(inactive_for)
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+    let mut guilds = Vec::new();
+    for guild in &level.guilds {
+        let bases: Vec<BaseCamp> = level.bases_of(guild).cloned().collect();
+        if bases.is_empty() {
+            continue;
+        }
+        let mut last_seen_at = None;
+        for member in &guild.members {
+            last_seen_at = last_seen_at.max(last_seen(member, level, saved_at, store, now)?);
+        }
+        if last_seen_at.is_some_and(|seen| seen > cutoff) {
+            continue;
+        }
+        guilds.push(AbandonedGuild {
+            id: guild.id,
+            name: guild.name.clone(),
+            members: guild.members.iter().map(|m| m.name.clone()).collect(),
+            last_seen: last_seen_at,
+            bases,
+        });
+    }
+    guilds.sort_by_key(|guild| guild.last_seen);
+    Ok(CleanupPlan {
+        inactive_for,
+        guilds,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::PlayerInfo;
+    use crate::savefile::Guild;
+    use crate::store::from_unix;
+
+    const TICKS_PER_DAY: i64 = 24 * 3600 * 10_000_000;
+
+    fn guild(id: u32, members: &[(u32, &str, i64)]) -> Guild {
+        Guild {
+            id: Guid([id, 0, 0, 0]),
+            name: format!("Guild {id}"),
+            admin: Guid::from_player_uid(members[0].0),
+            members: members
+                .iter()
+                .map(|(uid, name, day)| GuildMember {
+                    uid: Guid::from_player_uid(*uid),
+                    name: name.to_string(),
+                    last_online_ticks: day * TICKS_PER_DAY,
+                })
+                .collect(),
+            base_ids: vec![Guid([id, 1, 0, 0])],
+            base_camp_level: 1,
+        }
+    }
+
+    fn base(guild: &Guild) -> BaseCamp {
+        BaseCamp {
+            id: guild.base_ids[0],
+            name: String::new(),
+            guild_id: guild.id,
+            location: [0.0; 3],
+        }
+    }
+
+    #[test]
+    fn test_plan() {
+        let day = Duration::from_secs(24 * 3600);
+        let saved_at = from_unix(1_700_000_000);
+        let now = saved_at + day;
+        // The save clock is at day 100, Alice was online on day 50 and Bob on day 99.
+        let quiet = guild(1, &[(11, "Alice", 50)]);
+        let active = guild(2, &[(21, "Bob", 99), (22, "Carol", 10)]);
+        // Only online on day 10 according to the save, but the store saw Dave at day 100.
+        let recorded = guild(3, &[(31, "Dave", 10)]);
+        let mut baseless = guild(4, &[(41, "Erin", 0)]);
+        baseless.base_ids.clear();
+        let level = LevelSave {
+            base_camps: vec![base(&quiet), base(&active), base(&recorded)],
+            guilds: vec![quiet, active, recorded, baseless],
+            real_time_ticks: Some(100 * TICKS_PER_DAY),
+            ..Default::default()
+        };
+
+        let store = SessionStore::open_in_memory().unwrap();
+        let dave = PlayerInfo {
+            name: "Dave".to_string(),
+            uid: "31".to_string(),
+            steamid: "76561190000000031".to_string(),
+        };
+        store.record_join(&dave, saved_at - day * 2).unwrap();
+        store.record_leave(&dave, saved_at).unwrap();
+
+        let month = plan(&level, saved_at, &store, day * 30, now).unwrap();
+        assert_eq!(month.guilds.len(), 1);
+        assert_eq!(month.guilds[0].name, "Guild 1");
+        assert_eq!(month.guilds[0].members, vec!["Alice".to_string()]);
+        assert_eq!(month.guilds[0].last_seen, Some(saved_at - day * 50));
+        assert_eq!(month.base_count(), 1);
+
+        // Everyone is inactive after half a day, least recently seen first.
+        let half_day = plan(&level, saved_at, &store, day / 2, now).unwrap();
+        assert_eq!(
+            half_day
+                .guilds
+                .iter()
+                .map(|guild| guild.name.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["Guild 1", "Guild 2", "Guild 3"]
+        );
+    }
+}
@@ -0,0 +1,268 @@
+//! Clock and timezone of the server host, for schedules in server-local time.
+//!
+//! A [RemoteClock] read over SSH knows how far the host's clock is off from the local one
+//! and which timezone the host is in. [DailySchedule]s like "restart at 04:00" are computed
+//! in that timezone, so they stay at 04:00 on the host across DST changes and wherever the
+//! scheduler itself runs.
+//!
+//! # Example:
+//! ```no_run
+//! use palworld_server::clock::{DailySchedule, RemoteClock};
+//! use palworld_server::ssh::PalworldConnection;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let connection = PalworldConnection::new("palworld.lan:22", "root", "MySSHPassword");
+//!     let clock = RemoteClock::read(&connection).await.unwrap();
+//!     let restart: DailySchedule = "04:00".parse().unwrap();
+//!     println!("Host time is {}", clock.host_time());
+//!     tokio::time::sleep(clock.delay_until(&restart)).await;
+//!     // Restart the server
+//! }
+//! ```
+
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Days, NaiveTime, TimeZone, Utc};
+pub use chrono_tz::Tz;
+
+use crate::ssh::PalworldConnection;
+
+/// Prints the host's UTC time with nanoseconds and its timezone, from systemd, Debian's
+/// `/etc/timezone` or the `/etc/localtime` link.
+const CLOCK_COMMAND: &str = "date -u +%s.%N && (timedatectl show -p Timezone --value 2>/dev/null \
+    || cat /etc/timezone 2>/dev/null || readlink -f /etc/localtime)";
+
+/// Clock of a host relative to the local clock.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RemoteClock {
+    /// Timezone the host is configured for.
+    pub timezone: Tz,
+    /// Milliseconds the host's clock is ahead of the local clock, negative if behind.
+    pub skew_ms: i64,
+    /// Round trip of the reading, the skew may be off by up to half of it.
+    pub round_trip: Duration,
+}
+
+impl RemoteClock {
+    /// Reads the clock of a Linux host.
+    pub async fn read(connection: &PalworldConnection) -> Result<Self> {
+        let sent = SystemTime::now();
+        let result = connection.command(CLOCK_COMMAND).await?;
+        let received = SystemTime::now();
+        if !result.success() {
+            bail!("Failed to read the host clock: {}", result.stderr.trim());
+        }
+        let (host_time, timezone) = parse_clock(&result.output)?;
+        let clock = Self::from_sample(sent, received, host_time, timezone);
+        log::debug!(
+            "Host clock is {} ms ahead in {}, round trip {:?}",
+            clock.skew_ms,
+            clock.timezone,
+            clock.round_trip
+        );
+        Ok(clock)
+    }
+
+    /// A clock that read `host_time` between `sent` and `received` on the local clock,
+    /// assuming the host answered halfway through.
+    pub fn from_sample(
+        sent: SystemTime,
+        received: SystemTime,
+        host_time: SystemTime,
+        timezone: Tz,
+    ) -> Self {
+        let round_trip = received.duration_since(sent).unwrap_or_default();
+        let midpoint = sent + round_trip / 2;
+        Self {
+            timezone,
+            skew_ms: millis_between(midpoint, host_time),
+            round_trip,
+        }
+    }
+
+    /// The current time on the host's clock.
+    pub fn now(&self) -> SystemTime {
+        self.to_host(SystemTime::now())
+    }
+
+    /// The current time on the host's clock in its timezone.
+    pub fn host_time(&self) -> DateTime<Tz> {
+        DateTime::<Utc>::from(self.now()).with_timezone(&self.timezone)
+    }
+
+    /// The host's clock reading at `local` on the local clock.
+    pub fn to_host(&self, local: SystemTime) -> SystemTime {
+        shift(local, self.skew_ms)
+    }
+
+    /// The local clock reading at `host` on the host's clock.
+    pub fn to_local(&self, host: SystemTime) -> SystemTime {
+        shift(host, -self.skew_ms)
+    }
+
+    /// Time to wait on the local clock until the next run of `schedule` on the host.
+    pub fn delay_until(&self, schedule: &DailySchedule) -> Duration {
+        let now = self.now();
+        let next = schedule.next_after(now, self.timezone);
+        next.duration_since(now).unwrap_or_default()
+    }
+}
+
+/// A time of day, like `04:00` for a daily restart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DailySchedule {
+    pub at: NaiveTime,
+}
+
+impl DailySchedule {
+    pub fn new(at: NaiveTime) -> Self {
+        Self { at }
+    }
+
+    /// First run after `now` in `timezone`. Times skipped when clocks go forward run as
+    /// soon as they exist again, times repeated when clocks go back run the first time.
+    pub fn next_after(&self, now: SystemTime, timezone: Tz) -> SystemTime {
+        let today = DateTime::<Utc>::from(now)
+            .with_timezone(&timezone)
+            .date_naive();
+        (0..=2)
+            .filter_map(|days| today.checked_add_days(Days::new(days)))
+            .filter_map(|date| {
+                let at = date.and_time(self.at);
+                // DST gaps are at most a few hours.
+                (0..=4 * 60).find_map(|minutes| {
+                    let at = at + chrono::Duration::minutes(minutes);
+                    timezone.from_local_datetime(&at).earliest()
+                })
+            })
+            .map(SystemTime::from)
+            .find(|run| *run > now)
+            .expect("a daily time occurs within two days")
+    }
+}
+
+impl FromStr for DailySchedule {
+    type Err = anyhow::Error;
+
+    /// Parses `HH:MM` or `HH:MM:SS`.
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let at = NaiveTime::parse_from_str(s, "%H:%M:%S")
+            .or_else(|_| NaiveTime::parse_from_str(s, "%H:%M"))
+            .with_context(|| format!("Invalid time of day '{s}', expected HH:MM"))?;
+        Ok(Self::new(at))
+    }
+}
+
+/// Parses the output of [CLOCK_COMMAND]: seconds since the epoch and the timezone.
+pub fn parse_clock(output: &str) -> Result<(SystemTime, Tz)> {
+    let mut lines = output.lines().map(str::trim);
+    let timestamp = lines.next().unwrap_or_default();
+    let (secs, nanos) = timestamp.split_once('.').unwrap_or((timestamp, "0"));
+    let secs: u64 = secs
+        .parse()
+        .with_context(|| format!("Invalid host time '{timestamp}'"))?;
+    // Busybox date prints %N literally.
+    let nanos: u32 = format!("{nanos:0<9}")[..9].parse().unwrap_or(0);
+    let zone = lines.next().unwrap_or_default();
+    let zone = zone.rsplit_once("zoneinfo/").map_or(zone, |(_, zone)| zone);
+    let timezone = match zone {
+        "" => Tz::UTC,
+        zone => zone
+            .parse()
+            .map_err(|_| anyhow::anyhow!("Unknown host timezone '{zone}'"))?,
+    };
+    Ok((UNIX_EPOCH + Duration::new(secs, nanos), timezone))
+}
+
+/// Milliseconds from `from` to `to`, negative if `to` is earlier.
+fn millis_between(from: SystemTime, to: SystemTime) -> i64 {
+    match to.duration_since(from) {
+        Ok(ahead) => ahead.as_millis() as i64,
+        Err(behind) => -(behind.duration().as_millis() as i64),
+    }
+}
+
+fn shift(time: SystemTime, millis: i64) -> SystemTime {
+    match millis >= 0 {
+        true => time + Duration::from_millis(millis as u64),
+        false => time - Duration::from_millis(millis.unsigned_abs()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> SystemTime {
+        SystemTime::from(s.parse::<DateTime<Utc>>().unwrap())
+    }
+
+    #[test]
+    fn test_parse_clock() {
+        let (time, timezone) = parse_clock("1711846800.250000000\nEurope/Berlin\n").unwrap();
+        assert_eq!(time, utc("2024-03-31T01:00:00.25Z"));
+        assert_eq!(timezone, Tz::Europe__Berlin);
+        let (time, timezone) =
+            parse_clock("1711846800.N\n/usr/share/zoneinfo/America/New_York\n").unwrap();
+        assert_eq!(time, utc("2024-03-31T01:00:00Z"));
+        assert_eq!(timezone, Tz::America__New_York);
+        assert_eq!(parse_clock("1711846800\n").unwrap().1, Tz::UTC);
+        assert!(parse_clock("Thu Mar 31\n").is_err());
+        assert!(parse_clock("1711846800\nMars/Olympus_Mons\n").is_err());
+    }
+
+    #[test]
+    fn test_remote_clock() {
+        let sent = utc("2024-01-01T00:00:00Z");
+        let received = sent + Duration::from_millis(200);
+        // Answered at 00:00:00.100 local time with the host 5s ahead.
+        let host_time = sent + Duration::from_millis(5100);
+        let clock = RemoteClock::from_sample(sent, received, host_time, Tz::UTC);
+        assert_eq!(clock.skew_ms, 5000);
+        assert_eq!(clock.round_trip, Duration::from_millis(200));
+        assert_eq!(clock.to_host(sent), sent + Duration::from_secs(5));
+        assert_eq!(clock.to_local(sent), sent - Duration::from_secs(5));
+
+        let behind = RemoteClock::from_sample(sent, received, sent, Tz::UTC);
+        assert_eq!(behind.skew_ms, -100);
+    }
+
+    #[test]
+    fn test_daily_schedule() {
+        let berlin = Tz::Europe__Berlin;
+        let at_four: DailySchedule = "04:00".parse().unwrap();
+        // 04:00 is 03:00 UTC in winter and 02:00 UTC in summer.
+        let now = utc("2024-03-30T12:00:00Z");
+        let next = at_four.next_after(now, berlin);
+        assert_eq!(next, utc("2024-03-31T02:00:00Z"));
+        assert_eq!(
+            at_four.next_after(next, berlin),
+            utc("2024-04-01T02:00:00Z")
+        );
+        assert_eq!(
+            at_four.next_after(utc("2024-10-26T12:00:00Z"), berlin),
+            utc("2024-10-27T03:00:00Z")
+        );
+
+        // 02:30 doesn't exist on the last Sunday of March, it runs at 03:00 right after.
+        let at_half_two: DailySchedule = "02:30".parse().unwrap();
+        assert_eq!(
+            at_half_two.next_after(now, berlin),
+            utc("2024-03-31T01:00:00Z")
+        );
+        // 02:30 happens twice on the last Sunday of October, it runs the first time.
+        assert_eq!(
+            at_half_two.next_after(utc("2024-10-26T12:00:00Z"), berlin),
+            utc("2024-10-27T00:30:00Z")
+        );
+        assert!("25:00".parse::<DailySchedule>().is_err());
+        assert_eq!(
+            "04:00:30".parse::<DailySchedule>().unwrap().at,
+            NaiveTime::from_hms_opt(4, 0, 30).unwrap()
+        );
+    }
+}
@@ -0,0 +1,308 @@
+//! RCON commands as types, each knowing how to encode itself and parse its response.
+//!
+//! [crate::rcon::PalworldRCON::execute] sends any [Command]. The built-in commands cover
+//! what Palworld understands, commands of newer server versions only need a type
+//! implementing [Command] to be sent with a typed response.
+//!
+//! # Example:
+//! ```no_run
+//! use anyhow::Result;
+//! use palworld_server::command::{Command, Info, Kick};
+//! use palworld_server::rcon::{PalworldRCON, DEFAULT_SOURCE_PORT};
+//!
+//! /// `TeleportToPlayer`, not wrapped by the library.
+//! struct Teleport {
+//!     steamid: String,
+//! }
+//!
+//! impl Command for Teleport {
+//!     const NAME: &'static str = "TeleportToPlayer";
+//!     type Response = bool;
+//!
+//!     fn encode(&self) -> String {
+//!         format!("{} {}", Self::NAME, self.steamid)
+//!     }
+//!
+//!     fn parse(raw: &str) -> Result<bool> {
+//!         Ok(!raw.contains("Failed"))
+//!     }
+//! }
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let rcon = PalworldRCON::new("localhost", DEFAULT_SOURCE_PORT, "MyRCONPassword");
+//!     println!("{}", rcon.execute(&Info).await.unwrap());
+//!     rcon.execute(&Kick::new("76561190000000001")).await.unwrap();
+//!     let teleport = Teleport { steamid: "76561190000000001".to_string() };
+//!     rcon.execute(&teleport).await.unwrap();
+//! }
+//! ```
+
+use std::time::Duration;
+
+use anyhow::Result;
+
+use crate::message;
+use crate::models::{PlayerInfo, ServerInfo};
+use crate::parse;
+use crate::rcon::RconError;
+
+/// Default delay of [Shutdown], as the server uses without one.
+pub const DEFAULT_SHUTDOWN_DELAY: Duration = Duration::from_secs(30);
+
+/// An RCON command and the type its response parses into.
+pub trait Command {
+    /// Name of the command as sent to the server, like `ShowPlayers`.
+    const NAME: &'static str;
+    /// Commands without side effects are sent with [crate::rcon::PalworldRCON::query], so
+    /// identical ones sent at the same time share a round trip.
+    const READ_ONLY: bool = false;
+    type Response;
+
+    /// The command line sent to the server.
+    fn encode(&self) -> String;
+
+    /// Parses the response of the server.
+    fn parse(raw: &str) -> Result<Self::Response>;
+}
+
+/// Players online. Cut off lists fail with [RconError::Truncated], see
+/// [crate::rcon::PalworldRCON::get_player_info] for the REST API fallback.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShowPlayers;
+
+impl Command for ShowPlayers {
+    const NAME: &'static str = "ShowPlayers";
+    const READ_ONLY: bool = true;
+    type Response = Vec<PlayerInfo>;
+
+    fn encode(&self) -> String {
+        Self::NAME.to_string()
+    }
+
+    fn parse(raw: &str) -> Result<Vec<PlayerInfo>> {
+        if !parse::is_player_list_truncated(raw) {
+            return Ok(parse::parse_player_info(raw));
+        }
+        let complete = raw.rfind('\n').map_or("", |end| &raw[..=end]);
+        Err(RconError::Truncated(parse::parse_player_info(complete)).into())
+    }
+}
+
+/// Version and name of the server.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Info;
+
+impl Command for Info {
+    const NAME: &'static str = "Info";
+    const READ_ONLY: bool = true;
+    type Response = ServerInfo;
+
+    fn encode(&self) -> String {
+        Self::NAME.to_string()
+    }
+
+    fn parse(raw: &str) -> Result<ServerInfo> {
+        raw.parse()
+    }
+}
+
+/// Saves the world, true if the server confirmed it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Save;
+
+impl Command for Save {
+    const NAME: &'static str = "Save";
+    type Response = bool;
+
+    fn encode(&self) -> String {
+        Self::NAME.to_string()
+    }
+
+    fn parse(raw: &str) -> Result<bool> {
+        Ok(raw.contains("Complete Save"))
+    }
+}
+
+/// Shuts the server down after `delay`, showing `message`. True if the server confirmed it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shutdown {
+    pub delay: Duration,
+    pub message: String,
+}
+
+impl Shutdown {
+    pub fn new(delay: Option<Duration>, message: impl Into<String>) -> Self {
+        Self {
+            delay: delay.unwrap_or(DEFAULT_SHUTDOWN_DELAY),
+            message: message.into(),
+        }
+    }
+}
+
+impl Command for Shutdown {
+    const NAME: &'static str = "Shutdown";
+    type Response = bool;
+
+    fn encode(&self) -> String {
+        format!("{} {} {}", Self::NAME, self.delay.as_secs(), self.message)
+    }
+
+    fn parse(raw: &str) -> Result<bool> {
+        Ok(raw.contains("The server will shut down in"))
+    }
+}
+
+/// Shows a message to every player, true if the server confirmed it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Broadcast {
+    /// The message as sent, see [message::encode_broadcast].
+    encoded: String,
+}
+
+impl Broadcast {
+    /// Encodes `message` with its whitespace replaced by `replace_space`, failing if the
+    /// replacement contains whitespace itself.
+    pub fn new(message: &str, replace_space: Option<&str>) -> Result<Self> {
+        Ok(Self {
+            encoded: message::encode_broadcast(message, replace_space)?,
+        })
+    }
+
+    /// The message as the server receives it.
+    pub fn encoded(&self) -> &str {
+        &self.encoded
+    }
+}
+
+impl Command for Broadcast {
+    const NAME: &'static str = "Broadcast";
+    type Response = bool;
+
+    fn encode(&self) -> String {
+        format!("{} {}", Self::NAME, self.encoded)
+    }
+
+    fn parse(raw: &str) -> Result<bool> {
+        Ok(raw.contains("Broadcasted"))
+    }
+}
+
+/// Kicks a player by Steam ID, true if the server kicked them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Kick {
+    pub steamid: String,
+}
+
+impl Kick {
+    pub fn new(steamid: impl Into<String>) -> Self {
+        Self {
+            steamid: steamid.into(),
+        }
+    }
+}
+
+impl Command for Kick {
+    const NAME: &'static str = "KickPlayer";
+    type Response = bool;
+
+    fn encode(&self) -> String {
+        format!("{} {}", Self::NAME, self.steamid)
+    }
+
+    fn parse(raw: &str) -> Result<bool> {
+        Ok(raw.contains("Kicked"))
+    }
+}
+
+/// Bans a player by Steam ID, true if the server banned them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ban {
+    pub steamid: String,
+}
+
+impl Ban {
+    pub fn new(steamid: impl Into<String>) -> Self {
+        Self {
+            steamid: steamid.into(),
+        }
+    }
+}
+
+impl Command for Ban {
+    const NAME: &'static str = "BanPlayer";
+    type Response = bool;
+
+    fn encode(&self) -> String {
+        format!("{} {}", Self::NAME, self.steamid)
+    }
+
+    fn parse(raw: &str) -> Result<bool> {
+        Ok(raw.contains("Banned"))
+    }
+}
+
+/// Names of the built-in commands with [Command::READ_ONLY] set.
+pub fn read_only_commands() -> Vec<&'static str> {
+    #[allow(unused_mut)]
+    let mut names = vec![ShowPlayers::NAME, Info::NAME];
+    #[cfg(feature = "palguard")]
+    names.push(crate::palguard::GetPos::NAME);
+    names
+}
+
+/// Whether the command of `line`, its first word ignoring case, is a built-in command without
+/// side effects. Commands this doesn't know, like those of server mods, may change anything.
+pub fn is_read_only(line: &str) -> bool {
+    let name = line.split_whitespace().next().unwrap_or_default();
+    read_only_commands()
+        .iter()
+        .any(|read_only| read_only.eq_ignore_ascii_case(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(ShowPlayers.encode(), "ShowPlayers");
+        assert_eq!(
+            Shutdown::new(None, "Restarting").encode(),
+            "Shutdown 30 Restarting"
+        );
+        let broadcast = Broadcast::new("Restart in 5 minutes", Some("_")).unwrap();
+        assert_eq!(broadcast.encode(), "Broadcast Restart_in_5_minutes");
+        assert!(Broadcast::new("hi", Some(" ")).is_err());
+        assert_eq!(Kick::new("7656").encode(), "KickPlayer 7656");
+        assert_eq!(Ban::new("7656").encode(), "BanPlayer 7656");
+    }
+
+    #[test]
+    fn test_parse() {
+        let players = ShowPlayers::parse("name,playeruid,steamid\nShadow,1001,7656\n").unwrap();
+        assert_eq!(players[0].name, "Shadow");
+        let truncated =
+            ShowPlayers::parse("name,playeruid,steamid\nShadow,1001,7656\nBob,10").unwrap_err();
+        assert!(matches!(
+            truncated.downcast_ref::<RconError>(),
+            Some(RconError::Truncated(players)) if players.len() == 1
+        ));
+        let info = Info::parse("Welcome to Pal Server[v0.1.3.0] Default Palworld Server").unwrap();
+        assert_eq!(info.name, "Default Palworld Server");
+        assert!(Save::parse("Complete Save\n").unwrap());
+        assert!(!Kick::parse("Failed to kick").unwrap());
+        assert!(Ban::parse("Banned: 7656").unwrap());
+        assert!(Shutdown::parse("The server will shut down in 30 seconds.").unwrap());
+    }
+
+    #[test]
+    fn test_is_read_only() {
+        assert!(is_read_only("showplayers"));
+        assert!(is_read_only(" Info"));
+        assert!(!is_read_only("Shutdown 10"));
+        assert!(!is_read_only("DoExit"));
+        assert!(!is_read_only("whitelist_remove 7656"));
+        assert!(!is_read_only(""));
+    }
+}
@@ -0,0 +1,637 @@
+//! `PalWorldSettings.ini` parsing and writing.
+//!
+//! All settings live in a single `OptionSettings=(...)` line. Values are kept as the
+//! raw strings from the file, in order, so a parsed file writes back unchanged apart
+//! from comments.
+//!
+//! # Example:
+//! ```
+//! use palworld_server::config::WorldSettings;
+//!
+//! let ini = "[/Script/Pal.PalGameWorldSettings]\n\
+//!     OptionSettings=(ServerName=\"My Server\",RCONEnabled=False,RCONPort=25575)\n";
+//! let mut settings = WorldSettings::parse(ini).unwrap();
+//! assert_eq!(settings.get("ServerName"), Some("My Server"));
+//! settings.set("RCONEnabled", "True");
+//! assert!(settings.to_ini().contains("RCONEnabled=True"));
+//! ```
+//!
+//! [diff] compares the settings of two servers, for checking a fleet shares the intended
+//! settings:
+//! ```
+//! use palworld_server::config::{diff, WorldSettings};
+//!
+//! let a = WorldSettings::parse("OptionSettings=(ExpRate=1.000000,PublicPort=8211)").unwrap();
+//! let b = WorldSettings::parse("OptionSettings=(ExpRate=2.000000,PublicPort=8211)").unwrap();
+//! let changes = diff(&a, &b).changes;
+//! assert_eq!(changes.len(), 1);
+//! assert_eq!(changes[0].key, "ExpRate");
+//! assert_eq!(changes[0].new.as_deref(), Some("2.000000"));
+//! ```
+//!
+//! [enforce] checks settings against a [SettingsTemplate] of required values and allowed
+//! ranges, [WorldSettings::apply] fixes the violations:
+//! ```
+//! use palworld_server::config::{enforce, SettingRule, SettingsTemplate, WorldSettings};
+//!
+//! let mut template = SettingsTemplate::new();
+//! template.rules.insert("ServerPlayerMaxNum".to_string(), SettingRule::range(4.0, 32.0));
+//! let mut settings = WorldSettings::parse("OptionSettings=(ServerPlayerMaxNum=64)").unwrap();
+//! let violations = enforce(&template, &settings);
+//! assert_eq!(violations[0].fix.as_deref(), Some("32"));
+//! settings.apply(&violations);
+//! assert!(enforce(&template, &settings).is_empty());
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use anyhow::{bail, Result};
+
+use crate::secret::{is_secret_key, REDACTED};
+
+/// Section header of the server settings.
+pub static SETTINGS_SECTION: &str = "[/Script/Pal.PalGameWorldSettings]";
+
+/// A single `Key=Value` entry of `OptionSettings`. Values of passwords like `AdminPassword`
+/// are redacted in `Debug` output.
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct Setting {
+    pub key: String,
+    /// The value without surrounding quotes.
+    pub value: String,
+    /// Written back in double quotes, like `ServerName="..."`.
+    pub quoted: bool,
+}
+
+impl std::fmt::Debug for Setting {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut debug = f.debug_struct("Setting");
+        debug.field("key", &self.key);
+        match is_secret_key(&self.key) {
+            true => debug.field("value", &format_args!("{REDACTED}")),
+            false => debug.field("value", &self.value),
+        };
+        debug.field("quoted", &self.quoted).finish()
+    }
+}
+
+/// Settings of a world, in file order.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct WorldSettings {
+    pub settings: Vec<Setting>,
+}
+
+impl WorldSettings {
+    /// Parses the contents of `PalWorldSettings.ini`. Lines other than `OptionSettings`
+    /// are ignored.
+    pub fn parse(ini: &str) -> Result<Self> {
+        let Some(line) = ini
+            .lines()
+            .map(str::trim)
+            .find(|line| line.starts_with("OptionSettings="))
+        else {
+            bail!("No OptionSettings in settings file");
+        };
+        let options = &line["OptionSettings=".len()..];
+        let Some(options) = options
+            .strip_prefix('(')
+            .and_then(|options| options.strip_suffix(')'))
+        else {
+            bail!("OptionSettings is not wrapped in parentheses");
+        };
+        let settings = split_options(options)?
+            .into_iter()
+            .map(parse_setting)
+            .collect::<Result<Vec<Setting>>>()?;
+        Ok(Self { settings })
+    }
+
+    /// Value of `key`, keys are case sensitive like in the server.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.settings
+            .iter()
+            .find(|setting| setting.key == key)
+            .map(|setting| setting.value.as_str())
+    }
+
+    /// Replaces the value of `key`, keeping its quoting, or appends a new unquoted setting.
+    pub fn set(&mut self, key: &str, value: impl Into<String>) {
+        let value = value.into();
+        match self.settings.iter_mut().find(|setting| setting.key == key) {
+            Some(setting) => setting.value = value,
+            None => self.settings.push(Setting {
+                key: key.to_string(),
+                value,
+                quoted: false,
+            }),
+        }
+    }
+
+    /// Replaces or appends `key` as a quoted value, like `AdminPassword="..."`.
+    pub fn set_quoted(&mut self, key: &str, value: impl Into<String>) {
+        self.set(key, value);
+        if let Some(setting) = self.settings.iter_mut().find(|setting| setting.key == key) {
+            setting.quoted = true;
+        }
+    }
+
+    /// Sets the fix of every violation that has one, see [enforce]. Returns the number of
+    /// settings changed.
+    pub fn apply(&mut self, violations: &[Violation]) -> usize {
+        let mut changed = 0;
+        for violation in violations {
+            if let Some(fix) = &violation.fix {
+                self.set(&violation.key, fix.as_str());
+                changed += 1;
+            }
+        }
+        changed
+    }
+
+    /// Writes the settings back in the `PalWorldSettings.ini` format.
+    pub fn to_ini(&self) -> String {
+        let options = self
+            .settings
+            .iter()
+            .map(|setting| match setting.quoted {
+                true => format!("{}=\"{}\"", setting.key, setting.value),
+                false => format!("{}={}", setting.key, setting.value),
+            })
+            .collect::<Vec<String>>()
+            .join(",");
+        format!("{SETTINGS_SECTION}\nOptionSettings=({options})\n")
+    }
+}
+
+/// A setting that differs between two [WorldSettings], see [diff]. Values of passwords are
+/// redacted in `Debug` output.
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct SettingChange {
+    pub key: String,
+    /// The value in the first settings, None if it doesn't have the key.
+    pub old: Option<String>,
+    /// The value in the second settings, None if it doesn't have the key.
+    pub new: Option<String>,
+}
+
+impl std::fmt::Debug for SettingChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let redact = |value: &Option<String>| match is_secret_key(&self.key) {
+            true => value.as_ref().map(|_| REDACTED.to_string()),
+            false => value.clone(),
+        };
+        f.debug_struct("SettingChange")
+            .field("key", &self.key)
+            .field("old", &redact(&self.old))
+            .field("new", &redact(&self.new))
+            .finish()
+    }
+}
+
+/// Differences between two [WorldSettings], see [diff].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct SettingsDiff {
+    /// In the order of the first settings, then keys only the second has.
+    pub changes: Vec<SettingChange>,
+}
+
+impl SettingsDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// The changes with the values of passwords replaced by [REDACTED], for printing.
+    pub fn redacted(&self) -> Self {
+        let changes = self.changes.iter().cloned().map(|mut change| {
+            if is_secret_key(&change.key) {
+                let redact = |value: Option<String>| value.map(|_| REDACTED.to_string());
+                change.old = redact(change.old);
+                change.new = redact(change.new);
+            }
+            change
+        });
+        Self {
+            changes: changes.collect(),
+        }
+    }
+
+    /// Drops the changes of `keys`, like `ServerName` or `PublicPort` that are meant to
+    /// differ between servers. Keys are matched case-insensitively.
+    pub fn ignore<S: AsRef<str>>(&mut self, keys: &[S]) {
+        self.changes.retain(|change| {
+            !keys
+                .iter()
+                .any(|key| key.as_ref().eq_ignore_ascii_case(&change.key))
+        });
+    }
+}
+
+/// The settings that differ between `a` and `b`. Numbers are compared by value, so
+/// `1.000000` and `1.0` are the same, and quoting is ignored.
+pub fn diff(a: &WorldSettings, b: &WorldSettings) -> SettingsDiff {
+    let mut changes = Vec::new();
+    for setting in &a.settings {
+        let new = b.get(&setting.key);
+        if !new.is_some_and(|new| same_value(&setting.value, new)) {
+            changes.push(SettingChange {
+                key: setting.key.clone(),
+                old: Some(setting.value.clone()),
+                new: new.map(str::to_string),
+            });
+        }
+    }
+    for setting in &b.settings {
+        if a.get(&setting.key).is_none() {
+            changes.push(SettingChange {
+                key: setting.key.clone(),
+                old: None,
+                new: Some(setting.value.clone()),
+            });
+        }
+    }
+    SettingsDiff { changes }
+}
+
+fn same_value(a: &str, b: &str) -> bool {
+    match (a.parse::<f64>(), b.parse::<f64>()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// What a setting of a [SettingsTemplate] has to be, constraints that are None or empty
+/// aren't checked.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct SettingRule {
+    /// The required value, numbers are compared by value like in [diff].
+    pub value: Option<String>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// Allowed values, the first is the fix for any other.
+    pub one_of: Vec<String>,
+}
+
+impl SettingRule {
+    /// A rule requiring `value`.
+    pub fn value(value: impl Into<String>) -> Self {
+        Self {
+            value: Some(value.into()),
+            ..Default::default()
+        }
+    }
+
+    /// A rule requiring a number from `min` to `max`.
+    pub fn range(min: f64, max: f64) -> Self {
+        Self {
+            min: Some(min),
+            max: Some(max),
+            ..Default::default()
+        }
+    }
+
+    /// The rule as a sentence fragment, like `between 4 and 32`.
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(value) = &self.value {
+            parts.push(value.clone());
+        }
+        if !self.one_of.is_empty() {
+            parts.push(format!("one of {}", self.one_of.join(", ")));
+        }
+        match (self.min, self.max) {
+            (Some(min), Some(max)) => parts.push(format!("between {min} and {max}")),
+            (Some(min), None) => parts.push(format!("at least {min}")),
+            (None, Some(max)) => parts.push(format!("at most {max}")),
+            (None, None) => (),
+        }
+        parts.join(" and ")
+    }
+
+    /// Why `actual` breaks the rule and the value fixing it, None if it doesn't.
+    fn check(&self, actual: Option<&str>) -> Option<Option<String>> {
+        if let Some(value) = &self.value {
+            return match actual.is_some_and(|actual| same_value(actual, value)) {
+                true => None,
+                false => Some(Some(value.clone())),
+            };
+        }
+        if !self.one_of.is_empty()
+            && !actual.is_some_and(|actual| self.one_of.iter().any(|v| v == actual))
+        {
+            return Some(Some(self.one_of[0].clone()));
+        }
+        if self.min.is_none() && self.max.is_none() {
+            return None;
+        }
+        let Some(actual) = actual else {
+            return Some(self.min.or(self.max).map(|bound| format_number(bound, "")));
+        };
+        let Ok(number) = actual.parse::<f64>() else {
+            return Some(
+                self.min
+                    .or(self.max)
+                    .map(|bound| format_number(bound, actual)),
+            );
+        };
+        let clamped = number
+            .max(self.min.unwrap_or(f64::MIN))
+            .min(self.max.unwrap_or(f64::MAX));
+        match clamped == number {
+            true => None,
+            false => Some(Some(format_number(clamped, actual))),
+        }
+    }
+}
+
+/// `number` written like `like`: with the server's six decimals if `like` has decimals,
+/// else without any when it is whole.
+fn format_number(number: f64, like: &str) -> String {
+    match like.contains('.') || number.fract() != 0.0 {
+        true => format!("{number:.6}"),
+        false => format!("{number}"),
+    }
+}
+
+/// Required values and allowed ranges of settings, by key. Deserializes from a map like
+/// `{"ExpRate": {"value": "1.000000"}, "ServerPlayerMaxNum": {"min": 4, "max": 32}}`.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct SettingsTemplate {
+    pub rules: BTreeMap<String, SettingRule>,
+}
+
+impl SettingsTemplate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// A setting breaking its rule in a [SettingsTemplate], see [enforce]. Values of passwords
+/// are redacted when displayed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct Violation {
+    pub key: String,
+    /// The value in the settings, None if missing.
+    pub actual: Option<String>,
+    /// What the rule requires, see [SettingRule::describe].
+    pub expected: String,
+    /// The value satisfying the rule, None if there is none to pick.
+    pub fix: Option<String>,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secret = is_secret_key(&self.key);
+        match (&self.actual, secret) {
+            (None, _) => write!(f, "{} is missing", self.key)?,
+            (Some(_), true) => write!(f, "{} is {REDACTED}", self.key)?,
+            (Some(actual), false) => write!(f, "{} is {actual}", self.key)?,
+        }
+        match secret {
+            true => write!(f, ", expected {REDACTED}"),
+            false => write!(f, ", expected {}", self.expected),
+        }
+    }
+}
+
+impl Violation {
+    /// The violation with the values of passwords replaced by [REDACTED], for printing.
+    pub fn redacted(&self) -> Self {
+        let mut violation = self.clone();
+        if is_secret_key(&violation.key) {
+            let redact = |value: Option<String>| value.map(|_| REDACTED.to_string());
+            violation.actual = redact(violation.actual);
+            violation.expected = REDACTED.to_string();
+            violation.fix = redact(violation.fix);
+        }
+        violation
+    }
+}
+
+/// The settings breaking the rules of `template`, in the order of its keys.
+pub fn enforce(template: &SettingsTemplate, settings: &WorldSettings) -> Vec<Violation> {
+    template
+        .rules
+        .iter()
+        .filter_map(|(key, rule)| {
+            let actual = settings.get(key);
+            let fix = rule.check(actual)?;
+            Some(Violation {
+                key: key.clone(),
+                actual: actual.map(str::to_string),
+                expected: rule.describe(),
+                fix,
+            })
+        })
+        .collect()
+}
+
+/// Splits on commas outside of quotes and nested parentheses, like
+/// `CrossplayPlatforms=(Steam,Xbox)`.
+fn split_options(options: &str) -> Result<Vec<&str>> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, c) in options.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '(' if !quoted => depth += 1,
+            ')' if !quoted => {
+                depth = match depth.checked_sub(1) {
+                    Some(depth) => depth,
+                    None => bail!("Unbalanced parentheses in OptionSettings"),
+                }
+            }
+            ',' if !quoted && depth == 0 => {
+                parts.push(&options[start..i]);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    if quoted || depth != 0 {
+        bail!("Unterminated value in OptionSettings");
+    }
+    if !options[start..].is_empty() {
+        parts.push(&options[start..]);
+    }
+    Ok(parts)
+}
+
+fn parse_setting(option: &str) -> Result<Setting> {
+    let Some((key, value)) = option.split_once('=') else {
+        bail!("Setting '{option}' has no value");
+    };
+    let (value, quoted) = match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        Some(value) => (value, true),
+        None => (value, false),
+    };
+    Ok(Setting {
+        key: key.trim().to_string(),
+        value: value.to_string(),
+        quoted,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEFAULT_SETTINGS: &str = "; This configuration file is a sample of the default server settings.\n\
+        [/Script/Pal.PalGameWorldSettings]\n\
+        OptionSettings=(Difficulty=None,DayTimeSpeedRate=1.000000,ServerName=\"Default Palworld Server\",\
+        ServerDescription=\"\",AdminPassword=\"a,b\",PublicPort=8211,RCONEnabled=False,\
+        RCONPort=25575,CrossplayPlatforms=(Steam,Xbox,PS5,Mac),\
+        BanListURL=\"https://api.palworldgame.com/api/banlist.txt\")\n";
+
+    #[test]
+    fn test_parse_settings() {
+        let settings = WorldSettings::parse(DEFAULT_SETTINGS).unwrap();
+        assert_eq!(settings.settings.len(), 10);
+        assert_eq!(settings.get("ServerName"), Some("Default Palworld Server"));
+        assert_eq!(settings.get("ServerDescription"), Some(""));
+        assert_eq!(settings.get("AdminPassword"), Some("a,b"));
+        assert!(!format!("{settings:?}").contains("a,b"));
+        assert_eq!(
+            settings.get("CrossplayPlatforms"),
+            Some("(Steam,Xbox,PS5,Mac)")
+        );
+        assert_eq!(settings.get("Missing"), None);
+
+        // Round trip, only the comment is lost.
+        let ini = settings.to_ini();
+        assert_eq!(
+            ini,
+            DEFAULT_SETTINGS
+                .lines()
+                .skip(1)
+                .collect::<Vec<&str>>()
+                .join("\n")
+                + "\n"
+        );
+        assert_eq!(WorldSettings::parse(&ini).unwrap(), settings);
+    }
+
+    #[test]
+    fn test_diff() {
+        let a = WorldSettings::parse(DEFAULT_SETTINGS).unwrap();
+        assert!(diff(&a, &a).is_empty());
+
+        let mut b = a.clone();
+        b.set("DayTimeSpeedRate", "1.0");
+        b.set_quoted("ServerName", "Other Server");
+        b.set("AdminPassword", "hunter2");
+        b.settings.retain(|setting| setting.key != "Difficulty");
+        b.set("ExpRate", "2.000000");
+        let mut changes = diff(&a, &b);
+        let keys: Vec<&str> = changes.changes.iter().map(|c| c.key.as_str()).collect();
+        assert_eq!(
+            keys,
+            ["Difficulty", "ServerName", "AdminPassword", "ExpRate"]
+        );
+        assert_eq!(changes.changes[0].new, None);
+        assert_eq!(
+            changes.changes[1].old.as_deref(),
+            Some("Default Palworld Server")
+        );
+        assert_eq!(changes.changes[1].new.as_deref(), Some("Other Server"));
+        assert_eq!(changes.changes[3].old, None);
+        assert!(!format!("{changes:?}").contains("hunter2"));
+        assert_eq!(changes.redacted().changes[2].new.as_deref(), Some(REDACTED));
+
+        changes.ignore(&["servername", "AdminPassword"]);
+        assert_eq!(changes.changes.len(), 2);
+    }
+
+    #[test]
+    fn test_enforce() {
+        let mut template = SettingsTemplate::new();
+        let mut rule = |key: &str, rule: SettingRule| template.rules.insert(key.to_string(), rule);
+        rule("DayTimeSpeedRate", SettingRule::range(0.5, 2.0));
+        rule("Difficulty", SettingRule::value("Normal"));
+        rule("PublicPort", SettingRule::value("8211.0"));
+        rule("RCONEnabled", SettingRule::value("True"));
+        rule(
+            "DeathPenalty",
+            SettingRule {
+                one_of: vec!["Item".to_string(), "None".to_string()],
+                ..Default::default()
+            },
+        );
+        let max = SettingRule {
+            max: Some(32.0),
+            ..Default::default()
+        };
+        rule("ServerPlayerMaxNum", max);
+
+        let mut settings = WorldSettings::parse(DEFAULT_SETTINGS).unwrap();
+        settings.set("ServerPlayerMaxNum", "64");
+        let violations = enforce(&template, &settings);
+        let keys: Vec<&str> = violations.iter().map(|v| v.key.as_str()).collect();
+        assert_eq!(
+            keys,
+            [
+                "DeathPenalty",
+                "Difficulty",
+                "RCONEnabled",
+                "ServerPlayerMaxNum"
+            ]
+        );
+        assert_eq!(violations[0].actual, None);
+        assert_eq!(violations[0].fix.as_deref(), Some("Item"));
+        assert_eq!(
+            violations[0].to_string(),
+            "DeathPenalty is missing, expected one of Item, None"
+        );
+        assert_eq!(violations[3].fix.as_deref(), Some("32"));
+        assert_eq!(
+            violations[3].to_string(),
+            "ServerPlayerMaxNum is 64, expected at most 32"
+        );
+
+        assert_eq!(settings.apply(&violations), 4);
+        assert!(enforce(&template, &settings).is_empty());
+        assert_eq!(settings.get("ServerPlayerMaxNum"), Some("32"));
+
+        settings.set("DayTimeSpeedRate", "3.000000");
+        let violations = enforce(&template, &settings);
+        assert_eq!(violations[0].fix.as_deref(), Some("2.000000"));
+
+        let mut template = SettingsTemplate::new();
+        template
+            .rules
+            .insert("AdminPassword".to_string(), SettingRule::value("new"));
+        let violation = &enforce(&template, &settings)[0];
+        assert!(!violation.to_string().contains("a,b"));
+        assert!(!violation.to_string().contains("new"));
+        assert_eq!(violation.redacted().fix.as_deref(), Some(REDACTED));
+    }
+
+    #[test]
+    fn test_parse_settings_invalid() {
+        assert!(WorldSettings::parse("").is_err());
+        assert!(WorldSettings::parse("OptionSettings=Difficulty=None").is_err());
+        assert!(WorldSettings::parse("OptionSettings=(ServerName=\"unterminated)").is_err());
+        assert!(WorldSettings::parse("OptionSettings=(Difficulty)").is_err());
+        assert_eq!(
+            WorldSettings::parse("OptionSettings=()").unwrap(),
+            WorldSettings::default()
+        );
+    }
+}
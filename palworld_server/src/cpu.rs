@@ -0,0 +1,43 @@
+//! CPU usage of the local machine.
+
+use anyhow::Result;
+
+/// Measures CPU usage between calls to [CpuSampler::sample].
+#[derive(Debug)]
+pub struct CpuSampler {
+    #[cfg(target_os = "linux")]
+    collector: psutil::cpu::CpuPercentCollector,
+    #[cfg(not(target_os = "linux"))]
+    system: sysinfo::System,
+}
+
+impl CpuSampler {
+    /// Create a new [CpuSampler], the first sample covers the time since this call.
+    #[cfg(target_os = "linux")]
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            collector: psutil::cpu::CpuPercentCollector::new()?,
+        })
+    }
+
+    /// Create a new [CpuSampler], the first sample covers the time since this call.
+    #[cfg(not(target_os = "linux"))]
+    pub fn new() -> Result<Self> {
+        let mut system = sysinfo::System::new();
+        system.refresh_cpu();
+        Ok(Self { system })
+    }
+
+    /// Average CPU usage over all cores since the previous sample, 0-100.
+    #[cfg(target_os = "linux")]
+    pub fn sample(&mut self) -> Result<f64> {
+        Ok(self.collector.cpu_percent()? as f64)
+    }
+
+    /// Average CPU usage over all cores since the previous sample, 0-100.
+    #[cfg(not(target_os = "linux"))]
+    pub fn sample(&mut self) -> Result<f64> {
+        self.system.refresh_cpu();
+        Ok(self.system.global_cpu_info().cpu_usage() as f64)
+    }
+}
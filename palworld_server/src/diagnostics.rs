@@ -0,0 +1,445 @@
+//! Step by step checks of the connection to a server, with suggested fixes.
+//!
+//! When "it doesn't connect" could be DNS, a firewall, RCON being disabled, a wrong password
+//! or a network dropping large packets, [Diagnostics::run] checks each in turn: resolving the
+//! host, connecting to the RCON port, authenticating, reading a player list that may not fit
+//! in one packet, probing the game UDP port and connecting to SSH. Checks that depend on a
+//! failed one are skipped.
+//!
+//! # Example:
+//! ```no_run
+//! use palworld_server::diagnostics::Diagnostics;
+//! use palworld_server::rcon::{PalworldRCON, DEFAULT_SOURCE_PORT};
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let rcon = PalworldRCON::new("palworld.lan", DEFAULT_SOURCE_PORT, "MyRCONPassword");
+//!     let report = Diagnostics::new().run(&rcon).await;
+//!     for check in &report.checks {
+//!         println!("{} {}: {}", check.status, check.kind, check.detail);
+//!         if let Some(fix) = &check.fix {
+//!             println!("  {fix}");
+//!         }
+//!     }
+//! }
+//! ```
+
+use std::fmt;
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use tokio::net::TcpStream;
+
+use crate::gameport::{GamePortProbe, DEFAULT_GAME_PORT};
+use crate::parse;
+use crate::rcon::{PalworldRCON, RconError};
+
+/// Default time each check may take.
+pub const DEFAULT_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What a check looks at, in the order they run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum CheckKind {
+    Dns,
+    RconPort,
+    RconAuth,
+    LargeResponse,
+    GamePort,
+    Ssh,
+}
+
+impl fmt::Display for CheckKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Dns => write!(f, "DNS"),
+            Self::RconPort => write!(f, "RCON port"),
+            Self::RconAuth => write!(f, "RCON authentication"),
+            Self::LargeResponse => write!(f, "Large responses"),
+            Self::GamePort => write!(f, "Game port"),
+            Self::Ssh => write!(f, "SSH"),
+        }
+    }
+}
+
+/// Outcome of a check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum CheckStatus {
+    Pass,
+    /// Works, but likely causes problems, or couldn't be told apart from working.
+    Warn,
+    Fail,
+    /// Not run because a check it depends on failed, or the server gave it nothing to test.
+    Skipped,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pass => write!(f, "pass"),
+            Self::Warn => write!(f, "warn"),
+            Self::Fail => write!(f, "fail"),
+            Self::Skipped => write!(f, "skipped"),
+        }
+    }
+}
+
+/// Result of one check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct CheckResult {
+    pub kind: CheckKind,
+    pub status: CheckStatus,
+    /// What was found, for people.
+    pub detail: String,
+    /// Suggested fix for warnings and failures.
+    pub fix: Option<String>,
+    pub duration: Duration,
+}
+
+/// Results of [Diagnostics::run], in the order the checks ran.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DiagnosticReport {
+    pub checks: Vec<CheckResult>,
+}
+
+impl DiagnosticReport {
+    /// True if no check failed, warnings included.
+    pub fn passed(&self) -> bool {
+        self.checks
+            .iter()
+            .all(|check| check.status != CheckStatus::Fail)
+    }
+
+    /// Checks that warned or failed.
+    pub fn problems(&self) -> impl Iterator<Item = &CheckResult> {
+        self.checks
+            .iter()
+            .filter(|check| matches!(check.status, CheckStatus::Warn | CheckStatus::Fail))
+    }
+
+    pub fn get(&self, kind: CheckKind) -> Option<&CheckResult> {
+        self.checks.iter().find(|check| check.kind == kind)
+    }
+
+    fn push(&mut self, kind: CheckKind, start: Instant, outcome: Outcome) {
+        let (status, detail, fix) = outcome;
+        self.checks.push(CheckResult {
+            kind,
+            status,
+            detail,
+            fix,
+            duration: start.elapsed(),
+        });
+    }
+
+    fn skip(&mut self, kinds: &[CheckKind], reason: CheckKind) {
+        for kind in kinds {
+            self.push(
+                *kind,
+                Instant::now(),
+                (CheckStatus::Skipped, format!("{reason} failed"), None),
+            );
+        }
+    }
+}
+
+type Outcome = (CheckStatus, String, Option<String>);
+
+fn pass(detail: String) -> Outcome {
+    (CheckStatus::Pass, detail, None)
+}
+
+fn problem(status: CheckStatus, detail: impl fmt::Display, fix: impl Into<String>) -> Outcome {
+    (status, detail.to_string(), Some(fix.into()))
+}
+
+/// The checks to run, see the [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct Diagnostics {
+    /// Time each check may take.
+    pub timeout: Duration,
+    /// Game port to probe, None to skip the check.
+    pub game_port: Option<u16>,
+    /// SSH port to connect to, None to skip the check.
+    pub ssh_port: Option<u16>,
+}
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Self {
+            timeout: DEFAULT_CHECK_TIMEOUT,
+            game_port: Some(DEFAULT_GAME_PORT),
+            ssh_port: Some(22),
+        }
+    }
+
+    /// Runs every check against the host of `rcon`.
+    pub async fn run(&self, rcon: &PalworldRCON) -> DiagnosticReport {
+        use CheckKind::*;
+
+        let mut report = DiagnosticReport::default();
+        let start = Instant::now();
+        let address = match self.limit(rcon.resolve()).await {
+            Ok(addresses) if !addresses.is_empty() => {
+                report.push(
+                    Dns,
+                    start,
+                    pass(format!("{} is {}", rcon.host, addresses[0].ip())),
+                );
+                addresses[0]
+            }
+            result => {
+                let detail = result.map_or_else(|e| e.to_string(), |_| "No addresses".to_string());
+                let fix = "Check the host name, or connect with the server's IP address";
+                report.push(Dns, start, problem(CheckStatus::Fail, detail, fix));
+                report.skip(&[RconPort, RconAuth, LargeResponse, GamePort, Ssh], Dns);
+                return report;
+            }
+        };
+
+        let start = Instant::now();
+        let outcome = self.connect(address, "RCON").await;
+        let rcon_reachable = outcome.0 == CheckStatus::Pass;
+        report.push(RconPort, start, outcome);
+        match rcon_reachable {
+            true => self.check_rcon(rcon, &mut report).await,
+            false => report.skip(&[RconAuth, LargeResponse], RconPort),
+        }
+
+        if let Some(port) = self.game_port {
+            let start = Instant::now();
+            let mut probe = GamePortProbe::new(address.ip().to_string(), port);
+            probe.timeout = self.timeout;
+            let result = probe.run().await;
+            let outcome = match (result.latency, result.failure) {
+                (Some(latency), _) => pass(format!("UDP port {port} answered in {latency:?}")),
+                (None, failure) => problem(
+                    CheckStatus::Warn,
+                    failure.unwrap_or_default(),
+                    format!(
+                        "Open UDP port {port} (PublicPort) in the firewall and forward it on the \
+                        router. Servers that ignore queries look the same, so this is only a \
+                        warning"
+                    ),
+                ),
+            };
+            report.push(GamePort, start, outcome);
+        }
+
+        if let Some(port) = self.ssh_port {
+            let start = Instant::now();
+            let outcome = self
+                .connect(SocketAddr::new(address.ip(), port), "SSH")
+                .await;
+            report.push(Ssh, start, outcome);
+        }
+        report
+    }
+
+    async fn check_rcon(&self, rcon: &PalworldRCON, report: &mut DiagnosticReport) {
+        use CheckKind::*;
+
+        let start = Instant::now();
+        let outcome = match self.limit(rcon.get_version()).await {
+            Ok(version) => pass(format!("Server {version}")),
+            Err(e) => match e.downcast_ref::<RconError>() {
+                Some(RconError::AuthFailed) | Some(RconError::EmptyPassword) => problem(
+                    CheckStatus::Fail,
+                    &e,
+                    "Use the AdminPassword of PalWorldSettings.ini as the RCON password",
+                ),
+                _ => problem(
+                    CheckStatus::Fail,
+                    &e,
+                    "Something accepts connections on the RCON port but doesn't answer like \
+                    Palworld: check RCONPort isn't used by another service or an RCON proxy",
+                ),
+            },
+        };
+        let authenticated = outcome.0 == CheckStatus::Pass;
+        report.push(RconAuth, start, outcome);
+        if !authenticated {
+            report.skip(&[LargeResponse], RconAuth);
+            return;
+        }
+
+        // Straight over RCON, get_player_info would hide a cut off list behind the REST API.
+        let start = Instant::now();
+        let outcome = match self.limit(rcon.query("showplayers")).await {
+            Ok(response) if parse::is_player_list_truncated(&response) => {
+                let complete = response.rfind('\n').map_or("", |end| &response[..=end]);
+                problem(
+                    CheckStatus::Warn,
+                    format!(
+                        "Player list cut off after {} players, responses over one packet are \
+                        truncated",
+                        parse::parse_player_info(complete).len()
+                    ),
+                    "Set PalworldRCON::rest_url to read long player lists over the REST API, \
+                    or check for a path MTU problem if this happens with few players",
+                )
+            }
+            Ok(response) => (
+                CheckStatus::Skipped,
+                format!(
+                    "Player list of {} fits in one packet, large responses not exercised",
+                    parse::parse_player_info(&response).len()
+                ),
+                None,
+            ),
+            Err(e) => problem(
+                CheckStatus::Fail,
+                &e,
+                "The connection drops on larger responses: check the MTU along the path, \
+                like VPN or tunnel interfaces, and try the REST API",
+            ),
+        };
+        report.push(LargeResponse, start, outcome);
+    }
+
+    /// Connects to `address` over TCP.
+    async fn connect(&self, address: SocketAddr, service: &str) -> Outcome {
+        let port = address.port();
+        match tokio::time::timeout(self.timeout, TcpStream::connect(address)).await {
+            Ok(Ok(_)) => pass(format!("TCP port {port} accepts connections")),
+            Ok(Err(e)) if e.kind() == ErrorKind::ConnectionRefused => problem(
+                CheckStatus::Fail,
+                format!("TCP port {port} refused the connection"),
+                match service {
+                    "RCON" => "Set RCONEnabled=True and RCONPort in PalWorldSettings.ini, then \
+                        restart the server"
+                        .to_string(),
+                    _ => format!("Check the {service} service runs and listens on port {port}"),
+                },
+            ),
+            Ok(Err(e)) => problem(
+                CheckStatus::Fail,
+                e,
+                format!("Check the route to the host and that {service} listens on port {port}"),
+            ),
+            Err(_) => problem(
+                CheckStatus::Fail,
+                format!("TCP port {port} didn't answer within {:?}", self.timeout),
+                format!(
+                    "Open TCP port {port} in the firewall of the host and forward it on the router"
+                ),
+            ),
+        }
+    }
+
+    async fn limit<T>(&self, future: impl std::future::Future<Output = Result<T>>) -> Result<T> {
+        match tokio::time::timeout(self.timeout, future).await {
+            Ok(result) => result,
+            Err(_) => anyhow::bail!("No answer within {:?}", self.timeout),
+        }
+    }
+}
+
+impl Default for Diagnostics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_unresolvable_host() {
+        let rcon = PalworldRCON::new("invalid host name", 25575, "password");
+        let report = Diagnostics::new().run(&rcon).await;
+        assert_eq!(report.checks.len(), 6);
+        assert_eq!(
+            report.get(CheckKind::Dns).unwrap().status,
+            CheckStatus::Fail
+        );
+        assert!(report.checks[1..]
+            .iter()
+            .all(|check| check.status == CheckStatus::Skipped));
+        assert!(!report.passed());
+    }
+
+    #[tokio::test]
+    async fn test_closed_ports() {
+        // A port that was just free, so connections are refused.
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = closed.local_addr().unwrap().port();
+        drop(closed);
+        let rcon = PalworldRCON::new("127.0.0.1", port, "password");
+        let mut diagnostics = Diagnostics::new();
+        diagnostics.timeout = Duration::from_millis(500);
+        diagnostics.game_port = Some(port);
+        diagnostics.ssh_port = Some(port);
+        let report = diagnostics.run(&rcon).await;
+
+        let status = |kind| report.get(kind).unwrap().status;
+        assert_eq!(status(CheckKind::Dns), CheckStatus::Pass);
+        assert_eq!(status(CheckKind::RconPort), CheckStatus::Fail);
+        assert_eq!(status(CheckKind::RconAuth), CheckStatus::Skipped);
+        assert_eq!(status(CheckKind::GamePort), CheckStatus::Warn);
+        assert_eq!(status(CheckKind::Ssh), CheckStatus::Fail);
+        let rcon_port = report.get(CheckKind::RconPort).unwrap();
+        assert!(rcon_port.fix.as_ref().unwrap().contains("RCONEnabled=True"));
+        assert_eq!(report.problems().count(), 3);
+    }
+
+    #[cfg(feature = "rcon")]
+    async fn large_response(players: usize) -> CheckResult {
+        let server = crate::mock::MockRcon::start("password", move |cmd| match cmd {
+            "info" => Some("Welcome to Pal Server[v0.1.3.0] Default Palworld Server".to_string()),
+            _ => {
+                let lines = (0..players)
+                    .map(|i| format!("Player{i},{i},7656119800000{i:04}\n"))
+                    .collect::<String>();
+                let list = format!("name,playeruid,steamid\n{lines}");
+                // Cut off like the server does.
+                Some(list[..list.len().min(parse::MAX_RESPONSE_LEN)].to_string())
+            }
+        })
+        .await;
+        let mut rcon = PalworldRCON::new("127.0.0.1", server.port, "password");
+        rcon.protocol.detect = false;
+        // Never reached, the check reads the list over RCON only.
+        rcon.rest_url = Some("http://127.0.0.1:1".to_string());
+        let mut diagnostics = Diagnostics::new();
+        diagnostics.game_port = None;
+        diagnostics.ssh_port = None;
+        let report = diagnostics.run(&rcon).await;
+        assert_eq!(
+            report.get(CheckKind::RconAuth).unwrap().status,
+            CheckStatus::Pass
+        );
+        report.get(CheckKind::LargeResponse).unwrap().clone()
+    }
+
+    #[cfg(feature = "rcon")]
+    #[tokio::test]
+    async fn test_large_response_not_exercised() {
+        let check = large_response(3).await;
+        assert_eq!(check.status, CheckStatus::Skipped);
+        assert!(check.detail.contains("not exercised"), "{}", check.detail);
+    }
+
+    #[cfg(feature = "rcon")]
+    #[tokio::test]
+    async fn test_large_response_truncated() {
+        let check = large_response(200).await;
+        assert_eq!(check.status, CheckStatus::Warn);
+        assert!(
+            check.detail.starts_with("Player list cut off"),
+            "{}",
+            check.detail
+        );
+    }
+}
@@ -0,0 +1,98 @@
+//! Recording what changes to a host would be made instead of making them.
+//!
+//! Set [crate::ssh::PalworldConnection::dry_run] and every operation that changes the host,
+//! provisioning, settings edits, service control, backups, firewall rules, transfers and
+//! migrations, records its commands here instead of running them. Commands that only read,
+//! like `cat` of the settings, still run so the recorded commands are the ones that would
+//! really be run.
+//!
+//! # Example:
+//! ```no_run
+//! use palworld_server::dryrun::DryRun;
+//! use palworld_server::ssh::{PalworldConnection, ServiceAction};
+//! use palworld_server::world::WorldProfile;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let dry_run = DryRun::new();
+//!     let mut connection = PalworldConnection::new("palworld.lan:22", "root", "MySSHPassword");
+//!     connection.dry_run = Some(dry_run.clone());
+//!     let world = WorldProfile::new("main");
+//!     world.service(&connection, ServiceAction::Stop).await.unwrap();
+//!     world.service(&connection, ServiceAction::Start).await.unwrap();
+//!     for action in dry_run.actions() {
+//!         println!("{action}");
+//!     }
+//! }
+//! ```
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// A change that would have been made.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub enum PlannedAction {
+    /// A shell command on `host`.
+    Command { host: String, command: String },
+    /// A file copied over SFTP, `from` and `to` are `host:path` or a local path.
+    Transfer { from: String, to: String },
+}
+
+impl fmt::Display for PlannedAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Command { host, command } => write!(f, "{host}$ {command}"),
+            Self::Transfer { from, to } => write!(f, "copy {from} -> {to}"),
+        }
+    }
+}
+
+/// Shared list of [PlannedAction]s, clones record to the same list.
+#[derive(Debug, Clone, Default)]
+pub struct DryRun {
+    actions: Arc<Mutex<Vec<PlannedAction>>>,
+}
+
+impl DryRun {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, action: PlannedAction) {
+        log::info!("Dry run: {action}");
+        self.actions.lock().unwrap().push(action);
+    }
+
+    /// Actions recorded so far, in order.
+    pub fn actions(&self) -> Vec<PlannedAction> {
+        self.actions.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dry_run() {
+        let dry_run = DryRun::new();
+        dry_run.clone().record(PlannedAction::Command {
+            host: "palworld.lan:22".to_string(),
+            command: "systemctl restart palworld".to_string(),
+        });
+        dry_run.record(PlannedAction::Transfer {
+            from: "palworld.lan:22:/tmp/a.tar.gz".to_string(),
+            to: "a.tar.gz".to_string(),
+        });
+        let actions: Vec<String> = dry_run.actions().iter().map(|a| a.to_string()).collect();
+        assert_eq!(
+            actions,
+            vec![
+                "palworld.lan:22$ systemctl restart palworld",
+                "copy palworld.lan:22:/tmp/a.tar.gz -> a.tar.gz"
+            ]
+        );
+    }
+}
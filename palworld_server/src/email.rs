@@ -0,0 +1,255 @@
+//! SMTP notifier for serious failures, like crashes, failed backups and full disks.
+//!
+//! # Example:
+//! ```no_run
+//! use palworld_server::email::{EmailNotifier, SmtpSecurity};
+//! use palworld_server::events::Event;
+//! use palworld_server::notify::Notifier;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let email = EmailNotifier::new(
+//!         "smtp.example.com",
+//!         SmtpSecurity::StartTls,
+//!         Some("user"),
+//!         "MySMTPPassword",
+//!         "palworld@example.com",
+//!         vec!["admin@example.com".to_string()],
+//!     )
+//!     .unwrap();
+//!     email.notify(&Event::Crash { reason: "exit code 139".to_string() }).await.unwrap();
+//! }
+//! ```
+
+use anyhow::Result;
+use async_trait::async_trait;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+use crate::events::{Event, Severity};
+use crate::notify::{Notifier, NOTIFICATION_TITLE};
+
+/// Default subject, see [render_template] for the placeholders.
+pub static DEFAULT_SUBJECT_TEMPLATE: &str = "[{severity}] {title}: {event}";
+/// Default body, see [render_template] for the placeholders.
+pub static DEFAULT_BODY_TEMPLATE: &str = "{event}\n\nSeverity: {severity}\n";
+
+/// How the connection to the SMTP server is secured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmtpSecurity {
+    /// Implicit TLS, port 465 by default.
+    Tls,
+    /// STARTTLS upgrade, port 587 by default.
+    StartTls,
+    /// Unencrypted, port 25 by default. Only use this for a relay on localhost, which is
+    /// why credentials are refused.
+    None,
+}
+
+/// Sends events by email.
+pub struct EmailNotifier {
+    pub from: String,
+    pub to: Vec<String>,
+    pub subject_template: String,
+    pub body_template: String,
+    /// Events less severe than this are skipped, defaults to [Severity::Critical] to avoid spam.
+    pub min_severity: Severity,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl EmailNotifier {
+    /// Create a new [EmailNotifier] sending through `host` on the default port of `security`.
+    /// Without a username, mail is sent unauthenticated and `password` is ignored.
+    pub fn new(
+        host: &str,
+        security: SmtpSecurity,
+        username: Option<&str>,
+        password: impl Into<String>,
+        from: impl Into<String>,
+        to: Vec<String>,
+    ) -> Result<Self> {
+        Self::with_port(host, None, security, username, password, from, to)
+    }
+
+    /// Create a new [EmailNotifier] sending through `host:port`.
+    pub fn with_port(
+        host: &str,
+        port: Option<u16>,
+        security: SmtpSecurity,
+        username: Option<&str>,
+        password: impl Into<String>,
+        from: impl Into<String>,
+        to: Vec<String>,
+    ) -> Result<Self> {
+        if username.is_some() && security == SmtpSecurity::None {
+            anyhow::bail!("Refusing to send SMTP credentials to {host} without TLS");
+        }
+        let mut builder = match security {
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)?,
+            SmtpSecurity::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?,
+            SmtpSecurity::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+        };
+        if let Some(port) = port {
+            builder = builder.port(port);
+        }
+        if let Some(username) = username {
+            builder = builder.credentials(Credentials::new(username.to_string(), password.into()));
+        }
+        let transport = builder.build();
+        Ok(Self {
+            from: from.into(),
+            to,
+            subject_template: DEFAULT_SUBJECT_TEMPLATE.to_string(),
+            body_template: DEFAULT_BODY_TEMPLATE.to_string(),
+            min_severity: Severity::Critical,
+            transport,
+        })
+    }
+
+    /// Whether `event` is severe enough to be sent.
+    pub fn sends(&self, event: &Event) -> bool {
+        event.severity() >= self.min_severity
+    }
+}
+
+/// Leaves out the transport, which holds the SMTP credentials.
+impl std::fmt::Debug for EmailNotifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EmailNotifier")
+            .field("from", &self.from)
+            .field("to", &self.to)
+            .field("subject_template", &self.subject_template)
+            .field("body_template", &self.body_template)
+            .field("min_severity", &self.min_severity)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl Notifier for EmailNotifier {
+    async fn notify(&self, event: &Event) -> Result<()> {
+        if !self.sends(event) {
+            return Ok(());
+        }
+        let mut message = Message::builder()
+            .from(self.from.parse()?)
+            .subject(render_template(&self.subject_template, event));
+        for to in &self.to {
+            message = message.to(to.parse()?);
+        }
+        let message = message.body(render_template(&self.body_template, event))?;
+        self.transport.send(message).await?;
+        Ok(())
+    }
+}
+
+/// Replaces `{title}`, `{event}` and `{severity}` in `template`. Other text in braces is kept
+/// as is, and placeholders in the inserted text are not replaced again.
+pub fn render_template(template: &str, event: &Event) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let value = rest.find('}').and_then(|end| match &rest[1..end] {
+            "title" => Some((end, NOTIFICATION_TITLE.to_string())),
+            "event" => Some((end, event.to_string())),
+            "severity" => Some((end, event.severity().to_string())),
+            _ => None,
+        });
+        match value {
+            Some((end, value)) => {
+                rendered.push_str(&value);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                rendered.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ByteSize, DiskUsage};
+
+    #[test]
+    fn test_render_template() {
+        let event = Event::Crash {
+            reason: "exit code 139".to_string(),
+        };
+        assert_eq!(
+            render_template(DEFAULT_SUBJECT_TEMPLATE, &event),
+            "[critical] Palworld Server: Server crashed: exit code 139"
+        );
+    }
+
+    #[test]
+    fn test_render_template_braces() {
+        let event = Event::Crash {
+            reason: "{severity} in {title} {unknown".to_string(),
+        };
+        assert_eq!(
+            render_template("{event} ({severity}) {other} {", &event),
+            "Server crashed: {severity} in {title} {unknown (critical) {other} {"
+        );
+    }
+
+    #[test]
+    fn test_credentials() {
+        let to = vec!["admin@example.com".to_string()];
+        let with_credentials = |security, username| {
+            EmailNotifier::new(
+                "localhost",
+                security,
+                username,
+                "password",
+                "palworld@example.com",
+                to.clone(),
+            )
+        };
+        assert!(with_credentials(SmtpSecurity::None, Some("user")).is_err());
+        assert!(with_credentials(SmtpSecurity::None, None).is_ok());
+        assert!(with_credentials(SmtpSecurity::StartTls, Some("user")).is_ok());
+    }
+
+    #[test]
+    fn test_sends_failures() {
+        let email = EmailNotifier::new(
+            "localhost",
+            SmtpSecurity::None,
+            None,
+            "password",
+            "palworld@example.com",
+            vec!["admin@example.com".to_string()],
+        )
+        .unwrap();
+        let backup_failed = Event::BackupFailed {
+            world: "main".to_string(),
+            reason: "tar: SaveGames: Cannot open".to_string(),
+        };
+        let disk_low = Event::DiskLow {
+            path: "/home/steam".to_string(),
+            usage: DiskUsage {
+                total: ByteSize::from_kib(100 * 1024 * 1024),
+                used: ByteSize::from_kib(95 * 1024 * 1024),
+                available: ByteSize::from_kib(5 * 1024 * 1024),
+            },
+        };
+        assert!(email.sends(&backup_failed));
+        assert!(email.sends(&disk_low));
+        assert!(!email.sends(&Event::SaveCompleted));
+        assert_eq!(
+            render_template(DEFAULT_SUBJECT_TEMPLATE, &backup_failed),
+            "[critical] Palworld Server: Backup of world main failed: tar: SaveGames: Cannot open"
+        );
+        assert_eq!(
+            render_template("{event}", &disk_low),
+            "Disk of /home/steam 95.0% full, 5.00 GiB left"
+        );
+    }
+}
@@ -0,0 +1,130 @@
+//! Passphrase encryption of files holding credentials, like password files and world profiles.
+//!
+//! Files are encrypted with [age](https://age-encryption.org) to a passphrase, so they can be
+//! decrypted with the `age` command line tool too. The key is derived from the passphrase with
+//! scrypt, tuned to take about a second. [read_to_string] reads a file whether or not it is
+//! encrypted and only asks for the passphrase when it is, so tools can accept both.
+//!
+//! # Example:
+//! ```no_run
+//! use palworld_server::encryption;
+//!
+//! let encrypted = encryption::encrypt(b"MyRCONPassword\n", "correct horse").unwrap();
+//! std::fs::write("password.age", encrypted).unwrap();
+//!
+//! let password = encryption::read_to_string("password.age", || Ok("correct horse".to_string()));
+//! assert_eq!(password.unwrap(), "MyRCONPassword\n");
+//! ```
+
+use std::iter;
+use std::path::Path;
+
+use age::secrecy::SecretString;
+use age::{scrypt, DecryptError};
+use anyhow::{bail, Context, Result};
+
+/// First line of every age file.
+const HEADER: &[u8] = b"age-encryption.org/v1\n";
+
+/// Whether `data` is an age encrypted file.
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(HEADER)
+}
+
+/// `plaintext` encrypted to `passphrase`.
+pub fn encrypt(plaintext: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    encrypt_with_work_factor(plaintext, passphrase, None)
+}
+
+/// Encrypts with an scrypt work factor of `2^log_n`, or one taking about a second if None.
+fn encrypt_with_work_factor(
+    plaintext: &[u8],
+    passphrase: &str,
+    log_n: Option<u8>,
+) -> Result<Vec<u8>> {
+    if passphrase.is_empty() {
+        bail!("The passphrase is empty");
+    }
+    let mut recipient = scrypt::Recipient::new(SecretString::from(passphrase));
+    if let Some(log_n) = log_n {
+        recipient.set_work_factor(log_n);
+    }
+    age::encrypt(&recipient, plaintext).context("Failed to encrypt")
+}
+
+/// The plaintext of `ciphertext`, fails on a wrong passphrase.
+pub fn decrypt(ciphertext: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    let identity = scrypt::Identity::new(SecretString::from(passphrase));
+    let decryptor = age::Decryptor::new_buffered(ciphertext).context("Not an age file")?;
+    if !decryptor.is_scrypt() {
+        bail!("Encrypted to a key rather than a passphrase");
+    }
+    let mut reader = match decryptor.decrypt(iter::once(&identity as &dyn age::Identity)) {
+        Ok(reader) => reader,
+        Err(DecryptError::DecryptionFailed | DecryptError::NoMatchingKeys) => {
+            bail!("Wrong passphrase")
+        }
+        Err(e) => return Err(e).context("Failed to decrypt"),
+    };
+    let mut plaintext = Vec::new();
+    std::io::Read::read_to_end(&mut reader, &mut plaintext).context("Failed to decrypt")?;
+    Ok(plaintext)
+}
+
+/// Contents of the text file at `path`, decrypted if it is encrypted. `passphrase` is only
+/// called for encrypted files.
+pub fn read_to_string(
+    path: impl AsRef<Path>,
+    passphrase: impl FnOnce() -> Result<String>,
+) -> Result<String> {
+    let path = path.as_ref();
+    let data = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let data = match is_encrypted(&data) {
+        true => decrypt(&data, &passphrase()?)
+            .with_context(|| format!("Failed to decrypt {}", path.display()))?,
+        false => data,
+    };
+    String::from_utf8(data).with_context(|| format!("{} isn't text", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt() {
+        // A low work factor keeps the test fast.
+        let encrypted = encrypt_with_work_factor(b"MyRCONPassword", "passphrase", Some(4)).unwrap();
+        assert!(is_encrypted(&encrypted));
+        assert!(!is_encrypted(b"MyRCONPassword"));
+        assert_eq!(
+            decrypt(&encrypted, "passphrase").unwrap(),
+            b"MyRCONPassword"
+        );
+        let error = decrypt(&encrypted, "wrong").unwrap_err();
+        assert_eq!(error.to_string(), "Wrong passphrase");
+        assert!(encrypt(b"MyRCONPassword", "").is_err());
+    }
+
+    #[test]
+    fn test_read_to_string() {
+        let dir = std::env::temp_dir().join(format!("palworld-encryption-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let plain = dir.join("plain.txt");
+        std::fs::write(&plain, "MyRCONPassword\n").unwrap();
+        let unused = || -> Result<String> { panic!("asked for a passphrase") };
+        assert_eq!(read_to_string(&plain, unused).unwrap(), "MyRCONPassword\n");
+
+        let encrypted = dir.join("encrypted.age");
+        let data = encrypt_with_work_factor(b"MyRCONPassword\n", "passphrase", Some(4)).unwrap();
+        std::fs::write(&encrypted, data).unwrap();
+        let passphrase = || Ok("passphrase".to_string());
+        assert_eq!(
+            read_to_string(&encrypted, passphrase).unwrap(),
+            "MyRCONPassword\n"
+        );
+        let wrong = || Ok("wrong".to_string());
+        assert!(read_to_string(&encrypted, wrong).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
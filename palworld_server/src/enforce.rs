@@ -0,0 +1,85 @@
+//! Scheduled enforcement of a settings template.
+//!
+//! Settings edited by hand on one server drift away from the rest of a fleet. A
+//! [SettingsEnforcer] checks a world's settings against a [SettingsTemplate] over SSH on every
+//! run of a [Schedule], logging each violation and, with [SettingsEnforcer::fix], writing the
+//! fixes back. Fixed settings take effect when the service restarts.
+//!
+//! # Example:
+//! ```no_run
+//! use std::sync::Arc;
+//!
+//! use palworld_server::config::{SettingRule, SettingsTemplate};
+//! use palworld_server::enforce::SettingsEnforcer;
+//! use palworld_server::scheduler::{Schedule, Scheduler, Tz};
+//! use palworld_server::ssh::PalworldConnection;
+//! use palworld_server::world::WorldProfile;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let ssh = PalworldConnection::new("palworld.lan:22", "root", "MySSHPassword");
+//!     let mut template = SettingsTemplate::new();
+//!     template.rules.insert("ExpRate".to_string(), SettingRule::value("1.000000"));
+//!     let mut enforcer = SettingsEnforcer::new(ssh, WorldProfile::new("main"), template);
+//!     enforcer.fix = true;
+//!
+//!     let mut scheduler = Scheduler::new();
+//!     Arc::new(enforcer).schedule(&mut scheduler, Schedule::parse("0 * * * *", Tz::UTC).unwrap());
+//!     scheduler.run().await;
+//! }
+//! ```
+
+use std::sync::Arc;
+
+use anyhow::Result;
+
+use crate::config::{SettingsTemplate, Violation};
+use crate::scheduler::{Job, Schedule, Scheduler};
+use crate::ssh::PalworldConnection;
+use crate::world::WorldProfile;
+
+/// Keeps a world's settings to a template, see the [module documentation](self).
+#[derive(Debug)]
+pub struct SettingsEnforcer {
+    ssh: PalworldConnection,
+    pub world: WorldProfile,
+    pub template: SettingsTemplate,
+    /// Write fixes for the violations, only report them if false.
+    pub fix: bool,
+}
+
+impl SettingsEnforcer {
+    pub fn new(ssh: PalworldConnection, world: WorldProfile, template: SettingsTemplate) -> Self {
+        Self {
+            ssh,
+            world,
+            template,
+            fix: false,
+        }
+    }
+
+    /// Checks the settings once, see [WorldProfile::enforce_settings].
+    pub async fn check(&self) -> Result<Vec<Violation>> {
+        self.world
+            .enforce_settings(&self.ssh, &self.template, self.fix)
+            .await
+    }
+
+    /// Adds the job checking the settings to `scheduler`, run on `schedule`. Missed runs
+    /// are skipped, the next run finds the same drift.
+    pub fn schedule<'a>(
+        self: &Arc<Self>,
+        scheduler: &'a mut Scheduler,
+        schedule: Schedule,
+    ) -> &'a mut Job {
+        let enforcer = self.clone();
+        let name = format!("enforce-settings-{}", self.world.name);
+        scheduler.add(name, schedule, move || {
+            let enforcer = enforcer.clone();
+            async move {
+                enforcer.check().await?;
+                Ok(())
+            }
+        })
+    }
+}
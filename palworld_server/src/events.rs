@@ -0,0 +1,512 @@
+//! Central event bus shared by every subsystem.
+//!
+//! Watchers publish [Event]s to an [EventBus] and notifiers or exporters subscribe to it,
+//! so integrations compose instead of each feature exposing its own channel.
+//!
+//! The bus is bounded so long-running daemons don't grow without limit. [EventBusConfig]
+//! sets the capacity and what happens when a slow subscriber, like a webhook notifier,
+//! falls behind, see [OverflowPolicy].
+//!
+//! # Example:
+//! ```
+//! use palworld_server::events::{Event, EventBus};
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let bus = EventBus::default();
+//!     let mut events = bus.subscribe();
+//!     bus.publish(Event::SaveCompleted);
+//!     assert_eq!(events.recv().await.unwrap(), Event::SaveCompleted);
+//! }
+//! ```
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{bail, Result};
+use tokio::sync::{broadcast, Notify};
+
+use crate::mem::MemInfo;
+use crate::models::{DiskUsage, PlayerInfo};
+use crate::progress::{Progress, ProgressUpdate};
+use crate::trace::{self, TraceContext};
+
+/// Default number of events buffered per subscriber before the oldest are dropped.
+pub const DEFAULT_EVENT_CAPACITY: usize = 256;
+
+/// How urgent an [Event] is, ordered from least to most severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Info => write!(f, "info"),
+            Self::Warning => write!(f, "warning"),
+            Self::Critical => write!(f, "critical"),
+        }
+    }
+}
+
+/// Something that happened on the server.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+#[non_exhaustive]
+pub enum Event {
+    /// A player logged in.
+    PlayerJoined(PlayerInfo),
+    /// A player logged out.
+    PlayerLeft(PlayerInfo),
+    /// Players already online when a [crate::watcher::PlayerWatcher] started, whose joins
+    /// it didn't see.
+    PlayersOnline(Vec<PlayerInfo>),
+    /// The world was saved.
+    SaveCompleted,
+    /// A shutdown was requested.
+    ShutdownScheduled { delay: Duration, message: String },
+    /// Memory usage crossed a threshold.
+    MemoryAlert(MemInfo),
+    /// A backup was written to `path`.
+    BackupFinished { path: String },
+    /// Archiving the saves of `world` failed, see [crate::world::WorldProfile::backup].
+    BackupFailed { world: String, reason: String },
+    /// The filesystem holding `path` filled past
+    /// [crate::ssh::PalworldConnection::disk_low_percent].
+    DiskLow { path: String, usage: DiskUsage },
+    /// The server stopped unexpectedly.
+    Crash { reason: String },
+    /// A player's total playtime reached a milestone.
+    PlaytimeMilestone {
+        player: PlayerInfo,
+        playtime: Duration,
+    },
+    /// A background task returned an error or panicked.
+    TaskFailed { task: String, reason: String },
+    /// A long operation like a backup or migration started a step.
+    Progress(ProgressUpdate),
+    /// `operation` failed over `from` one transport, like REST, and succeeded over `to` the
+    /// other, see [crate::failover].
+    Failover {
+        operation: String,
+        from: String,
+        to: String,
+        reason: String,
+    },
+    /// A player was banned until `until`, see [crate::tempban].
+    PlayerBanned {
+        steamid: String,
+        reason: String,
+        until: SystemTime,
+    },
+    /// A temporary ban lapsed and the player was removed from the world's ban list. The
+    /// server reads the list when it starts, so the player can only join again after the
+    /// next restart.
+    BanExpired { steamid: String, reason: String },
+}
+
+/// The kind of an [Event] without its data, used to route events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum EventKind {
+    PlayerJoined,
+    PlayerLeft,
+    PlayersOnline,
+    SaveCompleted,
+    ShutdownScheduled,
+    MemoryAlert,
+    BackupFinished,
+    BackupFailed,
+    DiskLow,
+    Crash,
+    PlaytimeMilestone,
+    TaskFailed,
+    Progress,
+    Failover,
+    PlayerBanned,
+    BanExpired,
+}
+
+impl Event {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Self::PlayerJoined(_) => EventKind::PlayerJoined,
+            Self::PlayerLeft(_) => EventKind::PlayerLeft,
+            Self::PlayersOnline(_) => EventKind::PlayersOnline,
+            Self::SaveCompleted => EventKind::SaveCompleted,
+            Self::ShutdownScheduled { .. } => EventKind::ShutdownScheduled,
+            Self::MemoryAlert(_) => EventKind::MemoryAlert,
+            Self::BackupFinished { .. } => EventKind::BackupFinished,
+            Self::BackupFailed { .. } => EventKind::BackupFailed,
+            Self::DiskLow { .. } => EventKind::DiskLow,
+            Self::Crash { .. } => EventKind::Crash,
+            Self::PlaytimeMilestone { .. } => EventKind::PlaytimeMilestone,
+            Self::TaskFailed { .. } => EventKind::TaskFailed,
+            Self::Progress(_) => EventKind::Progress,
+            Self::Failover { .. } => EventKind::Failover,
+            Self::PlayerBanned { .. } => EventKind::PlayerBanned,
+            Self::BanExpired { .. } => EventKind::BanExpired,
+        }
+    }
+
+    /// Default severity of the event.
+    pub fn severity(&self) -> Severity {
+        match self {
+            Self::PlayerJoined(_)
+            | Self::PlayerLeft(_)
+            | Self::PlayersOnline(_)
+            | Self::SaveCompleted
+            | Self::BackupFinished { .. }
+            | Self::PlaytimeMilestone { .. }
+            | Self::Progress(_)
+            | Self::PlayerBanned { .. }
+            | Self::BanExpired { .. } => Severity::Info,
+            Self::ShutdownScheduled { .. }
+            | Self::MemoryAlert(_)
+            | Self::TaskFailed { .. }
+            | Self::Failover { .. } => Severity::Warning,
+            Self::Crash { .. } | Self::BackupFailed { .. } | Self::DiskLow { .. } => {
+                Severity::Critical
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PlayerJoined(player) => write!(f, "{} joined the server", player.name),
+            Self::PlayerLeft(player) => write!(f, "{} left the server", player.name),
+            Self::PlayersOnline(players) => write!(f, "{} player(s) online", players.len()),
+            Self::SaveCompleted => write!(f, "World saved"),
+            Self::ShutdownScheduled { delay, message } => {
+                write!(f, "Shutdown in {}s", delay.as_secs())?;
+                if !message.is_empty() {
+                    write!(f, ": {message}")?;
+                }
+                Ok(())
+            }
+            Self::MemoryAlert(mem_info) => match mem_info.used_percent() {
+                Some(percent) => write!(f, "Memory usage at {percent:.1}%"),
+                None => write!(f, "Memory usage alert"),
+            },
+            Self::BackupFinished { path } => write!(f, "Backup written to {path}"),
+            Self::BackupFailed { world, reason } => {
+                write!(f, "Backup of world {world} failed: {reason}")
+            }
+            Self::DiskLow { path, usage } => write!(
+                f,
+                "Disk of {path} {:.1}% full, {} left",
+                usage.used_percent().unwrap_or(100.0),
+                usage.available
+            ),
+            Self::Crash { reason } => write!(f, "Server crashed: {reason}"),
+            Self::PlaytimeMilestone { player, playtime } => write!(
+                f,
+                "{} reached {}h of playtime",
+                player.name,
+                playtime.as_secs() / 3600
+            ),
+            Self::TaskFailed { task, reason } => write!(f, "Task {task} failed: {reason}"),
+            Self::Progress(update) => write!(f, "{update}"),
+            Self::Failover {
+                operation,
+                from,
+                to,
+                reason,
+            } => write!(
+                f,
+                "{operation} failed over {from} ({reason}), used {to} instead"
+            ),
+            Self::PlayerBanned {
+                steamid,
+                reason,
+                until,
+            } => write!(
+                f,
+                "{steamid} banned until {}: {reason}",
+                humantime::format_rfc3339_seconds(*until)
+            ),
+            Self::BanExpired { steamid, reason } => {
+                write!(f, "Ban of {steamid} expired ({reason})")
+            }
+        }
+    }
+}
+
+/// What [EventBus::publish] does when the slowest subscriber has `capacity` events queued.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum OverflowPolicy {
+    /// Publish anyway, slow subscribers skip the oldest events and get
+    /// [broadcast::error::RecvError::Lagged].
+    #[default]
+    DropOldest,
+    /// [EventBus::publish_wait] waits until every subscriber has room. [EventBus::publish]
+    /// can't wait and behaves like [OverflowPolicy::DropOldest].
+    Block,
+    /// Drop new events of these kinds so they can't push out more important ones, other
+    /// events behave like [OverflowPolicy::DropOldest].
+    DropKinds(Vec<EventKind>),
+}
+
+/// Capacity and overflow policy of an [EventBus].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct EventBusConfig {
+    /// Events buffered per subscriber.
+    pub capacity: usize,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub overflow: OverflowPolicy,
+}
+
+impl Default for EventBusConfig {
+    fn default() -> Self {
+        Self {
+            capacity: DEFAULT_EVENT_CAPACITY,
+            overflow: OverflowPolicy::default(),
+        }
+    }
+}
+
+/// An [Event] with the trace it was published in, see [crate::trace].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct TracedEvent {
+    pub event: Event,
+    /// None for events published outside [trace::traced].
+    pub trace: Option<TraceContext>,
+}
+
+/// Broadcast channel of [Event]s, cheap to clone and share between tasks.
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<TracedEvent>,
+    config: Arc<EventBusConfig>,
+    /// Woken whenever a subscriber receives an event, for [OverflowPolicy::Block].
+    space: Arc<Notify>,
+}
+
+impl EventBus {
+    /// Create a new [EventBus] buffering up to `capacity` events per subscriber. Fails if
+    /// `capacity` is 0.
+    pub fn new(capacity: usize) -> Result<Self> {
+        Self::with_config(EventBusConfig {
+            capacity,
+            ..Default::default()
+        })
+    }
+
+    /// Fails if [EventBusConfig::capacity] is 0.
+    pub fn with_config(config: EventBusConfig) -> Result<Self> {
+        if config.capacity == 0 {
+            bail!("The event bus capacity must be at least 1");
+        }
+        let (sender, _) = broadcast::channel(config.capacity);
+        Ok(Self {
+            sender,
+            config: Arc::new(config),
+            space: Arc::new(Notify::new()),
+        })
+    }
+
+    pub fn config(&self) -> &EventBusConfig {
+        &self.config
+    }
+
+    fn is_full(&self) -> bool {
+        self.sender.len() >= self.config.capacity
+    }
+
+    /// Publishes an event to every subscriber. Returns the number of subscribers that
+    /// received it, events published without subscribers or dropped by the
+    /// [OverflowPolicy] return 0.
+    pub fn publish(&self, event: Event) -> usize {
+        if let OverflowPolicy::DropKinds(kinds) = &self.config.overflow {
+            if self.is_full() && kinds.contains(&event.kind()) {
+                log::debug!("Event bus full, dropping {event:?}");
+                return 0;
+            }
+        }
+        log::debug!("{}Publishing event {event:?}", trace::log_prefix());
+        let event = TracedEvent {
+            event,
+            trace: trace::current(),
+        };
+        self.sender.send(event).unwrap_or(0)
+    }
+
+    /// Like [EventBus::publish], but with [OverflowPolicy::Block] waits until every
+    /// subscriber has room first.
+    pub async fn publish_wait(&self, event: Event) -> usize {
+        if self.config.overflow == OverflowPolicy::Block {
+            loop {
+                let notified = self.space.notified();
+                tokio::pin!(notified);
+                // Register before checking so a receive in between isn't missed.
+                notified.as_mut().enable();
+                if !self.is_full() || self.subscriber_count() == 0 {
+                    break;
+                }
+                notified.await;
+            }
+        }
+        self.publish(event)
+    }
+
+    /// Subscribes to all events published after this call.
+    pub fn subscribe(&self) -> EventReceiver {
+        EventReceiver {
+            receiver: self.sender.subscribe(),
+            space: self.space.clone(),
+        }
+    }
+
+    /// Number of active subscribers.
+    pub fn subscriber_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::with_config(EventBusConfig::default()).expect("The default capacity isn't 0")
+    }
+}
+
+/// Receives events from an [EventBus].
+#[derive(Debug)]
+pub struct EventReceiver {
+    receiver: broadcast::Receiver<TracedEvent>,
+    space: Arc<Notify>,
+}
+
+impl EventReceiver {
+    /// Receives the next event, see [broadcast::Receiver::recv].
+    pub async fn recv(&mut self) -> Result<Event, broadcast::error::RecvError> {
+        self.recv_traced().await.map(|traced| traced.event)
+    }
+
+    /// Receives the next event with the trace it was published in.
+    pub async fn recv_traced(&mut self) -> Result<TracedEvent, broadcast::error::RecvError> {
+        let event = self.receiver.recv().await;
+        self.space.notify_waiters();
+        event
+    }
+
+    /// Receives the next event if one is queued, see [broadcast::Receiver::try_recv].
+    pub fn try_recv(&mut self) -> Result<Event, broadcast::error::TryRecvError> {
+        let event = self.receiver.try_recv();
+        if event.is_ok() {
+            self.space.notify_waiters();
+        }
+        event.map(|traced| traced.event)
+    }
+}
+
+/// Publishes the start of every step as [Event::Progress]. Byte counts aren't published,
+/// a transfer reports every chunk and would flood the subscribers.
+impl Progress for EventBus {
+    fn update(&self, update: &ProgressUpdate) {
+        if update.bytes.is_none() {
+            self.publish(Event::Progress(update.clone()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_drop_kinds() {
+        let bus = EventBus::with_config(EventBusConfig {
+            capacity: 2,
+            overflow: OverflowPolicy::DropKinds(vec![EventKind::SaveCompleted]),
+        })
+        .unwrap();
+        let mut events = bus.subscribe();
+        let crash = Event::Crash {
+            reason: String::new(),
+        };
+        assert_eq!(bus.publish(Event::SaveCompleted), 1);
+        assert_eq!(bus.publish(crash.clone()), 1);
+        // Full, saves are dropped but crashes still go through and push out the oldest.
+        assert_eq!(bus.publish(Event::SaveCompleted), 0);
+        assert_eq!(bus.publish(crash.clone()), 1);
+        assert!(matches!(
+            events.recv().await,
+            Err(broadcast::error::RecvError::Lagged(1))
+        ));
+        assert_eq!(events.recv().await.unwrap(), crash);
+        assert_eq!(events.recv().await.unwrap(), crash);
+    }
+
+    #[tokio::test]
+    async fn test_block() {
+        let bus = EventBus::with_config(EventBusConfig {
+            capacity: 1,
+            overflow: OverflowPolicy::Block,
+        })
+        .unwrap();
+        assert!(EventBus::new(0).is_err());
+        let mut events = bus.subscribe();
+        bus.publish_wait(Event::SaveCompleted).await;
+        let publisher = {
+            let bus = bus.clone();
+            tokio::spawn(async move { bus.publish_wait(Event::SaveCompleted).await })
+        };
+        tokio::task::yield_now().await;
+        assert!(!publisher.is_finished());
+        // Receiving makes room, nothing is lost.
+        assert_eq!(events.recv().await.unwrap(), Event::SaveCompleted);
+        assert_eq!(publisher.await.unwrap(), 1);
+        assert_eq!(events.recv().await.unwrap(), Event::SaveCompleted);
+    }
+
+    #[tokio::test]
+    async fn test_traced_events() {
+        use crate::trace::Trigger;
+
+        let bus = EventBus::new(8).unwrap();
+        let mut events = bus.subscribe();
+        bus.publish(Event::SaveCompleted);
+        let context = TraceContext::new(Trigger::Schedule("autosave".to_string()));
+        trace::traced(context.clone(), async {
+            bus.publish(Event::SaveCompleted);
+        })
+        .await;
+        assert_eq!(events.recv_traced().await.unwrap().trace, None);
+        let traced = events.recv_traced().await.unwrap();
+        assert_eq!(traced.event, Event::SaveCompleted);
+        assert_eq!(traced.trace, Some(context));
+    }
+
+    #[tokio::test]
+    async fn test_progress() {
+        use crate::progress::Reporter;
+
+        let bus = EventBus::new(8).unwrap();
+        let mut events = bus.subscribe();
+        let mut reporter = Reporter::new(Some(Arc::new(bus.clone())), "backup", 1);
+        reporter.step("Archiving");
+        // Byte counts aren't published.
+        reporter.bytes(1, 2);
+        drop(reporter);
+        drop(bus);
+        let event = events.recv().await.unwrap();
+        assert_eq!(event.kind(), EventKind::Progress);
+        assert_eq!(event.to_string(), "backup [1/1] Archiving");
+        assert!(events.recv().await.is_err());
+    }
+}
@@ -0,0 +1,385 @@
+//! One client over both the REST API and RCON, falling back to the other when one fails.
+//!
+//! Palworld's RCON drops connections and cuts responses off, the REST API is down when it
+//! isn't enabled or the HTTP port is firewalled. A [FailoverClient] tries the
+//! [FailoverClient::preferred] transport first and the other one when it errors, publishing
+//! an [Event::Failover] when that worked. A transport failing
+//! [FailoverClient::failure_threshold] times in a row is tried second until
+//! [FailoverClient::retry_after] has passed, so calls don't wait on a dead transport first.
+//!
+//! Commands denied by the [crate::rcon::CommandPolicy] of the RCON client are refused before
+//! either transport is tried, so falling back to REST doesn't get around the policy.
+//!
+//! # Example:
+//! ```no_run
+//! use palworld_server::events::EventBus;
+//! use palworld_server::failover::{FailoverClient, Transport};
+//! use palworld_server::rcon::{PalworldRCON, DEFAULT_SOURCE_PORT};
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let mut rcon = PalworldRCON::new("palworld.lan", DEFAULT_SOURCE_PORT, "MyRCONPassword");
+//!     rcon.rest_url = Some("http://palworld.lan:8212".to_string());
+//!     let mut client = FailoverClient::from_rcon(rcon).unwrap();
+//!     client.bus = Some(EventBus::default());
+//!     client.save().await.unwrap();
+//!     println!("{:?}", client.health(Transport::Rest));
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{bail, Result};
+
+use crate::events::{Event, EventBus};
+use crate::models::PlayerInfo;
+use crate::rcon::PalworldRCON;
+use crate::rest::RestApi;
+
+/// Default number of failures in a row after which a transport is tried second.
+pub const DEFAULT_FAILURE_THRESHOLD: u32 = 3;
+
+/// Default time a failing transport is tried second.
+pub const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+type Attempt<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+/// The ways to reach a server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Transport {
+    Rest,
+    Rcon,
+}
+
+impl Transport {
+    /// The transport that isn't this one.
+    pub fn other(&self) -> Self {
+        match self {
+            Self::Rest => Self::Rcon,
+            Self::Rcon => Self::Rest,
+        }
+    }
+}
+
+impl fmt::Display for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rest => write!(f, "REST"),
+            Self::Rcon => write!(f, "RCON"),
+        }
+    }
+}
+
+/// Recent results of calls over a transport.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct TransportHealth {
+    /// Calls failed in a row, 0 after a success.
+    pub consecutive_failures: u32,
+    pub last_success: Option<SystemTime>,
+    pub last_failure: Option<SystemTime>,
+    pub last_error: Option<String>,
+}
+
+impl TransportHealth {
+    /// Whether the transport reached `threshold` failures in a row less than `retry_after` ago.
+    pub fn is_down(&self, threshold: u32, retry_after: Duration, now: SystemTime) -> bool {
+        self.consecutive_failures >= threshold
+            && self.last_failure.is_some_and(|failure| {
+                now.duration_since(failure).unwrap_or_default() < retry_after
+            })
+    }
+}
+
+/// Sends operations over REST or RCON, see the [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct FailoverClient {
+    pub rcon: PalworldRCON,
+    pub rest: RestApi,
+    /// Transport tried first while it is up.
+    pub preferred: Transport,
+    /// Failures in a row after which a transport is tried second.
+    pub failure_threshold: u32,
+    /// How long a transport that reached [FailoverClient::failure_threshold] is tried second.
+    pub retry_after: Duration,
+    /// Receives an [Event::Failover] whenever the second transport was used.
+    pub bus: Option<EventBus>,
+    health: Arc<Mutex<HashMap<Transport, TransportHealth>>>,
+}
+
+impl FailoverClient {
+    /// Create a [FailoverClient] preferring REST.
+    pub fn new(rcon: PalworldRCON, rest: RestApi) -> Self {
+        Self {
+            rcon,
+            rest,
+            preferred: Transport::Rest,
+            failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+            retry_after: DEFAULT_RETRY_AFTER,
+            bus: None,
+            health: Arc::default(),
+        }
+    }
+
+    /// Create a [FailoverClient] using the [PalworldRCON::rest_url] of `rcon`.
+    pub fn from_rcon(rcon: PalworldRCON) -> Result<Self> {
+        let Some(url) = &rcon.rest_url else {
+            bail!("The RCON client has no REST URL to fail over to");
+        };
+        let rest = RestApi::new(url, rcon.password.expose());
+        Ok(Self::new(rcon, rest))
+    }
+
+    /// Recent results over `transport`.
+    pub fn health(&self, transport: Transport) -> TransportHealth {
+        let health = self.health.lock().unwrap();
+        health.get(&transport).cloned().unwrap_or_default()
+    }
+
+    /// Transports in the order the next call tries them.
+    pub fn order(&self) -> [Transport; 2] {
+        let preferred = self.preferred;
+        let now = SystemTime::now();
+        let down = |transport| {
+            self.health(transport)
+                .is_down(self.failure_threshold, self.retry_after, now)
+        };
+        match down(preferred) && !down(preferred.other()) {
+            true => [preferred.other(), preferred],
+            false => [preferred, preferred.other()],
+        }
+    }
+
+    fn record(&self, transport: Transport, error: Option<&anyhow::Error>) {
+        let mut health = self.health.lock().unwrap();
+        let health = health.entry(transport).or_default();
+        match error {
+            Some(e) => {
+                health.consecutive_failures += 1;
+                health.last_failure = Some(SystemTime::now());
+                health.last_error = Some(e.to_string());
+            }
+            None => {
+                health.consecutive_failures = 0;
+                health.last_success = Some(SystemTime::now());
+            }
+        }
+    }
+
+    /// Runs `operation` over the transports in [FailoverClient::order]. Fails with the
+    /// error of the last transport if both fail.
+    async fn call<'a, T>(
+        &self,
+        operation: &str,
+        rest: Attempt<'a, T>,
+        rcon: Attempt<'a, T>,
+    ) -> Result<T> {
+        let mut attempts = HashMap::from([(Transport::Rest, rest), (Transport::Rcon, rcon)]);
+        let mut failed: Option<(Transport, anyhow::Error)> = None;
+        for transport in self.order() {
+            let Some(attempt) = attempts.remove(&transport) else {
+                continue;
+            };
+            match attempt.await {
+                Ok(value) => {
+                    self.record(transport, None);
+                    if let (Some((from, e)), Some(bus)) = (&failed, &self.bus) {
+                        bus.publish(Event::Failover {
+                            operation: operation.to_string(),
+                            from: from.to_string(),
+                            to: transport.to_string(),
+                            reason: e.to_string(),
+                        });
+                    }
+                    return Ok(value);
+                }
+                Err(e) => {
+                    self.record(transport, Some(&e));
+                    match &failed {
+                        None => {
+                            log::warn!("{operation} failed over {transport}, failing over: {e}")
+                        }
+                        Some((from, first)) => {
+                            return Err(e.context(format!(
+                                "{operation} failed over {transport} and over {from} ({first})"
+                            )))
+                        }
+                    }
+                    failed = Some((transport, e));
+                }
+            }
+        }
+        match failed {
+            Some((_, e)) => Err(e),
+            None => bail!("{operation} wasn't attempted"),
+        }
+    }
+
+    /// Fails if the command policy of the RCON client denies `command`.
+    fn check_policy(&self, command: &str) -> Result<()> {
+        match &self.rcon.policy {
+            Some(policy) => policy.check(command),
+            None => Ok(()),
+        }
+    }
+
+    /// Server version, like `v0.3.6.0`.
+    pub async fn get_version(&self) -> Result<String> {
+        self.check_policy("info")?;
+        let rest = Box::pin(self.rest.version());
+        let rcon = Box::pin(self.rcon.get_version());
+        self.call("Getting the version", rest, rcon).await
+    }
+
+    /// Players online.
+    pub async fn get_player_info(&self) -> Result<Vec<PlayerInfo>> {
+        self.check_policy("showplayers")?;
+        let rest = Box::pin(self.rest.players());
+        let rcon = Box::pin(self.rcon.get_player_info());
+        self.call("Listing players", rest, rcon).await
+    }
+
+    /// Shows `message` to every player, spaces are replaced by `replace_space` over RCON.
+    pub async fn broadcast(&self, message: &str, replace_space: Option<String>) -> Result<()> {
+        self.check_policy("broadcast")?;
+        let rest = Box::pin(self.rest.announce(message));
+        let rcon = Box::pin(async move {
+            self.rcon.broadcast(message, replace_space).await?;
+            Ok(())
+        });
+        self.call("Broadcasting", rest, rcon).await
+    }
+
+    /// Saves the world.
+    pub async fn save(&self) -> Result<()> {
+        self.check_policy("save")?;
+        let rest = Box::pin(self.rest.save());
+        let rcon = Box::pin(async {
+            match self.rcon.save().await? {
+                true => Ok(()),
+                false => bail!("The server didn't confirm the save"),
+            }
+        });
+        self.call("Saving", rest, rcon).await
+    }
+
+    /// Shuts the server down after `delay`, showing `message` to the players.
+    pub async fn shutdown(&self, delay: Duration, message: &str) -> Result<()> {
+        self.check_policy("shutdown")?;
+        let rest = Box::pin(self.rest.shutdown(delay, message));
+        let rcon = Box::pin(async move {
+            match self.rcon.shutdown(Some(delay), message).await? {
+                true => Ok(()),
+                false => bail!("The server didn't confirm the shutdown"),
+            }
+        });
+        self.call("Shutting down", rest, rcon).await
+    }
+
+    /// Kicks a player by Steam ID.
+    pub async fn kick_player(&self, steamid: &str) -> Result<()> {
+        self.check_policy("KickPlayer")?;
+        let rest = Box::pin(self.rest.kick(steamid, ""));
+        let rcon = Box::pin(async move {
+            match self.rcon.kick_player(steamid).await? {
+                true => Ok(()),
+                false => bail!("The server didn't kick {steamid}"),
+            }
+        });
+        self.call("Kicking", rest, rcon).await
+    }
+
+    /// Bans a player by Steam ID.
+    pub async fn ban_player(&self, steamid: &str) -> Result<()> {
+        self.check_policy("BanPlayer")?;
+        let rest = Box::pin(self.rest.ban(steamid, ""));
+        let rcon = Box::pin(async move {
+            match self.rcon.ban_player(steamid).await? {
+                true => Ok(()),
+                false => bail!("The server didn't ban {steamid}"),
+            }
+        });
+        self.call("Banning", rest, rcon).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rcon::{CommandPolicy, DEFAULT_SOURCE_PORT};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Answers every HTTP request with `body`.
+    async fn http_server(body: &'static str) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = [0; 4096];
+                let _ = stream.read(&mut request).await;
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        url
+    }
+
+    fn client(rest_url: &str) -> FailoverClient {
+        let rcon = PalworldRCON::new("invalid host name", DEFAULT_SOURCE_PORT, "password");
+        FailoverClient::new(rcon, RestApi::new(rest_url, "password"))
+    }
+
+    #[tokio::test]
+    async fn test_failover() {
+        let url = http_server(r#"{"version": "v0.3.6.0", "servername": "Test"}"#).await;
+        let mut client = client(&url);
+        client.preferred = Transport::Rcon;
+        let bus = EventBus::default();
+        let mut events = bus.subscribe();
+        client.bus = Some(bus);
+
+        assert_eq!(client.get_version().await.unwrap(), "v0.3.6.0");
+        let Event::Failover { from, to, .. } = events.recv().await.unwrap() else {
+            panic!("Expected a failover event");
+        };
+        assert_eq!((from.as_str(), to.as_str()), ("RCON", "REST"));
+        assert_eq!(client.health(Transport::Rcon).consecutive_failures, 1);
+        assert!(client.health(Transport::Rest).last_success.is_some());
+
+        // RCON is tried second once it failed often enough.
+        assert_eq!(client.order(), [Transport::Rcon, Transport::Rest]);
+        client.failure_threshold = 1;
+        assert_eq!(client.order(), [Transport::Rest, Transport::Rcon]);
+        client.retry_after = Duration::ZERO;
+        assert_eq!(client.order(), [Transport::Rcon, Transport::Rest]);
+    }
+
+    #[tokio::test]
+    async fn test_both_fail() {
+        // Nothing listens on the discard port.
+        let client = client("http://127.0.0.1:9");
+        let error = client.save().await.unwrap_err();
+        assert!(error.to_string().contains("failed over RCON and over REST"));
+        assert_eq!(client.health(Transport::Rest).consecutive_failures, 1);
+        assert_eq!(client.health(Transport::Rcon).consecutive_failures, 1);
+
+        let mut client = client.clone();
+        let mut policy = CommandPolicy::new();
+        policy.deny.push("save".to_string());
+        client.rcon.policy = Some(policy);
+        assert!(client.save().await.is_err());
+        // Refused before trying either transport.
+        assert_eq!(client.health(Transport::Rest).consecutive_failures, 1);
+    }
+}
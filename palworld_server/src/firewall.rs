@@ -0,0 +1,300 @@
+//! Inspect and change the host firewall over SSH, with ufw or plain iptables.
+//!
+//! Rules changed with iptables are not persisted across reboots, use ufw or save them with
+//! `netfilter-persistent save`.
+//!
+//! # Example:
+//! ```no_run
+//! use palworld_server::firewall::Firewall;
+//! use palworld_server::models::Protocol;
+//! use palworld_server::ssh::PalworldConnection;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let connection = PalworldConnection::new("palworld.lan:22", "root", "MySSHPassword");
+//!     let firewall = Firewall::new(connection);
+//!     firewall.open_port(8211, Protocol::Udp).await.unwrap();
+//!     // Only the admin may reach RCON.
+//!     firewall.restrict_rcon_to("203.0.113.5", 25575).await.unwrap();
+//!     for rule in firewall.rules().await.unwrap() {
+//!         println!("{rule:?}");
+//!     }
+//! }
+//! ```
+
+use std::net::IpAddr;
+
+use anyhow::{bail, Result};
+
+pub use crate::models::{FirewallRule, Protocol, RuleAction};
+use crate::parse;
+use crate::ssh::{
+    change_privileged, run_privileged, shell_quote, CommandResult, PalworldConnection,
+};
+
+/// Firewall frontend on the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub enum FirewallBackend {
+    Ufw,
+    Iptables,
+}
+
+/// Firewall of an SSH host.
+#[derive(Debug, Clone)]
+pub struct Firewall {
+    pub connection: PalworldConnection,
+    /// None detects it before every command.
+    pub backend: Option<FirewallBackend>,
+    /// Run commands with `sudo -n`, for SSH users other than root.
+    pub sudo: bool,
+}
+
+impl Firewall {
+    pub fn new(connection: PalworldConnection) -> Self {
+        Self {
+            connection,
+            backend: None,
+            sudo: false,
+        }
+    }
+
+    /// Prefers ufw when it's installed, iptables otherwise.
+    pub async fn detect_backend(&self) -> Result<FirewallBackend> {
+        if self.connection.command("command -v ufw").await?.success() {
+            return Ok(FirewallBackend::Ufw);
+        }
+        if self
+            .connection
+            .command("command -v iptables")
+            .await?
+            .success()
+        {
+            return Ok(FirewallBackend::Iptables);
+        }
+        bail!("Neither ufw nor iptables is installed")
+    }
+
+    async fn backend(&self) -> Result<FirewallBackend> {
+        match self.backend {
+            Some(backend) => Ok(backend),
+            None => self.detect_backend().await,
+        }
+    }
+
+    async fn run(&self, command: &str) -> Result<CommandResult> {
+        run_privileged(&self.connection, self.sudo, command).await
+    }
+
+    async fn change(&self, command: &str) -> Result<CommandResult> {
+        change_privileged(&self.connection, self.sudo, command).await
+    }
+
+    /// Inbound rules for single ports.
+    pub async fn rules(&self) -> Result<Vec<FirewallRule>> {
+        Ok(match self.backend().await? {
+            FirewallBackend::Ufw => parse::parse_ufw_status(&self.run("ufw status").await?.output),
+            FirewallBackend::Iptables => {
+                parse::parse_iptables_rules(&self.run("iptables -S INPUT").await?.output)
+            }
+        })
+    }
+
+    /// Allows `port` from anywhere.
+    pub async fn open_port(&self, port: u16, protocol: Protocol) -> Result<()> {
+        let rule = FirewallRule {
+            port,
+            protocol: Some(protocol),
+            action: RuleAction::Allow,
+            from: None,
+        };
+        self.change(&add_rule(self.backend().await?, &rule)).await?;
+        Ok(())
+    }
+
+    /// Removes every rule allowing `port`, restricted ones included.
+    pub async fn close_port(&self, port: u16, protocol: Protocol) -> Result<()> {
+        self.remove_rules(port, protocol, |rule| rule.action == RuleAction::Allow)
+            .await
+    }
+
+    /// Only allows `source`, an address or network like `203.0.113.0/24`, to reach the
+    /// RCON port. Other rules for the port are replaced.
+    pub async fn restrict_rcon_to(&self, source: &str, rcon_port: u16) -> Result<()> {
+        validate_source(source)?;
+        self.remove_rules(rcon_port, Protocol::Tcp, |_| true)
+            .await?;
+        let backend = self.backend().await?;
+        let mut rule = FirewallRule {
+            port: rcon_port,
+            protocol: Some(Protocol::Tcp),
+            action: RuleAction::Allow,
+            from: Some(source.to_string()),
+        };
+        // Added in order, the allow has to match first.
+        self.change(&add_rule(backend, &rule)).await?;
+        rule.action = RuleAction::Deny;
+        rule.from = None;
+        self.change(&add_rule(backend, &rule)).await?;
+        Ok(())
+    }
+
+    /// Turns ufw on. Does nothing for iptables, whose rules apply immediately.
+    pub async fn enable(&self) -> Result<()> {
+        if self.backend().await? == FirewallBackend::Ufw {
+            self.change("ufw --force enable").await?;
+        }
+        Ok(())
+    }
+
+    async fn remove_rules(
+        &self,
+        port: u16,
+        protocol: Protocol,
+        filter: impl Fn(&FirewallRule) -> bool,
+    ) -> Result<()> {
+        let backend = self.backend().await?;
+        let rules = match self.rules().await {
+            Ok(rules) => rules,
+            // The firewall may only be installed by an earlier recorded command.
+            Err(e) if self.connection.dry_run.is_some() => {
+                log::warn!("Dry run: failed to list the firewall rules: {e}");
+                Vec::new()
+            }
+            Err(e) => return Err(e),
+        };
+        for rule in rules {
+            let matches = rule.port == port && rule.protocol.unwrap_or(protocol) == protocol;
+            if matches && filter(&rule) {
+                self.change(&delete_rule(backend, &rule)).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Checks `source` is an IP address or network so it can't inject shell syntax.
+fn validate_source(source: &str) -> Result<()> {
+    let (address, prefix) = match source.split_once('/') {
+        Some((address, prefix)) => (address, Some(prefix)),
+        None => (source, None),
+    };
+    let Ok(address) = address.parse::<IpAddr>() else {
+        bail!("Invalid source address '{source}'");
+    };
+    let max_prefix = if address.is_ipv4() { 32 } else { 128 };
+    match prefix.map(|prefix| prefix.parse::<u8>()) {
+        None => Ok(()),
+        Some(Ok(prefix)) if prefix <= max_prefix => Ok(()),
+        Some(_) => bail!("Invalid source network '{source}'"),
+    }
+}
+
+/// Command adding `rule`. Allow rules go before any catch-all drop with iptables.
+fn add_rule(backend: FirewallBackend, rule: &FirewallRule) -> String {
+    match backend {
+        FirewallBackend::Ufw => format!("ufw {}", ufw_rule(rule)),
+        FirewallBackend::Iptables => {
+            let spec = iptables_rule(rule);
+            let position = match rule.action {
+                RuleAction::Allow => "-I",
+                RuleAction::Deny => "-A",
+            };
+            format!("iptables -C INPUT {spec} 2>/dev/null || iptables {position} INPUT {spec}")
+        }
+    }
+}
+
+/// Command deleting `rule`.
+fn delete_rule(backend: FirewallBackend, rule: &FirewallRule) -> String {
+    match backend {
+        FirewallBackend::Ufw => format!("ufw delete {}", ufw_rule(rule)),
+        FirewallBackend::Iptables => format!("iptables -D INPUT {}", iptables_rule(rule)),
+    }
+}
+
+fn ufw_rule(rule: &FirewallRule) -> String {
+    let action = match rule.action {
+        RuleAction::Allow => "allow",
+        RuleAction::Deny => "deny",
+    };
+    match (&rule.from, rule.protocol) {
+        (None, Some(protocol)) => format!("{action} {}/{protocol}", rule.port),
+        (None, None) => format!("{action} {}", rule.port),
+        (Some(from), Some(protocol)) => format!(
+            "{action} from {} to any port {} proto {protocol}",
+            shell_quote(from),
+            rule.port
+        ),
+        (Some(from), None) => {
+            format!(
+                "{action} from {} to any port {}",
+                shell_quote(from),
+                rule.port
+            )
+        }
+    }
+}
+
+fn iptables_rule(rule: &FirewallRule) -> String {
+    let mut spec = String::new();
+    if let Some(from) = &rule.from {
+        spec.push_str(&format!("-s {} ", shell_quote(from)));
+    }
+    // --dport needs a protocol.
+    let protocol = rule.protocol.unwrap_or(Protocol::Tcp);
+    let target = match rule.action {
+        RuleAction::Allow => "ACCEPT",
+        RuleAction::Deny => "DROP",
+    };
+    spec.push_str(&format!(
+        "-p {protocol} -m {protocol} --dport {} -j {target}",
+        rule.port
+    ));
+    spec
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_source() {
+        assert!(validate_source("203.0.113.5").is_ok());
+        assert!(validate_source("203.0.113.0/24").is_ok());
+        assert!(validate_source("2001:db8::/32").is_ok());
+        assert!(validate_source("203.0.113.0/33").is_err());
+        assert!(validate_source("1.2.3.4; reboot").is_err());
+    }
+
+    #[test]
+    fn test_rule_commands() {
+        let rule = FirewallRule {
+            port: 25575,
+            protocol: Some(Protocol::Tcp),
+            action: RuleAction::Allow,
+            from: Some("203.0.113.5".to_string()),
+        };
+        assert_eq!(
+            add_rule(FirewallBackend::Ufw, &rule),
+            "ufw allow from '203.0.113.5' to any port 25575 proto tcp"
+        );
+        assert_eq!(
+            delete_rule(FirewallBackend::Iptables, &rule),
+            "iptables -D INPUT -s '203.0.113.5' -p tcp -m tcp --dport 25575 -j ACCEPT"
+        );
+        let rule = FirewallRule {
+            port: 8211,
+            protocol: Some(Protocol::Udp),
+            action: RuleAction::Deny,
+            from: None,
+        };
+        assert_eq!(add_rule(FirewallBackend::Ufw, &rule), "ufw deny 8211/udp");
+        assert_eq!(
+            add_rule(FirewallBackend::Iptables, &rule),
+            "iptables -C INPUT -p udp -m udp --dport 8211 -j DROP 2>/dev/null || \
+            iptables -A INPUT -p udp -m udp --dport 8211 -j DROP"
+        );
+    }
+}
@@ -0,0 +1,266 @@
+//! Reachability and latency of the game's UDP port.
+//!
+//! RCON answering doesn't mean players can connect: the game port may be firewalled or not
+//! forwarded. A [GamePortProbe] sends a Steam A2S_INFO query, or a raw payload, to the game
+//! port and times the reply. A2S_INFO replies also carry the name and player count the
+//! server shows in the server browser.
+//!
+//! # Example:
+//! ```no_run
+//! use palworld_server::gameport::{GamePortProbe, DEFAULT_GAME_PORT};
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let result = GamePortProbe::new("palworld.lan", DEFAULT_GAME_PORT).run().await;
+//!     match result.latency {
+//!         Some(latency) => println!("Game port answered in {latency:?}"),
+//!         None => println!("Game port unreachable: {}", result.failure.unwrap()),
+//!     }
+//! }
+//! ```
+
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use tokio::net::UdpSocket;
+
+/// Default game port of Palworld servers, `PublicPort` in the settings.
+pub const DEFAULT_GAME_PORT: u16 = 8211;
+
+/// Default time to wait for a reply.
+pub const DEFAULT_PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+const A2S_INFO: &[u8] = b"\xFF\xFF\xFF\xFFTSource Engine Query\0";
+const S2C_CHALLENGE: u8 = 0x41;
+const S2A_INFO: u8 = 0x49;
+
+/// What a [GamePortProbe] sends.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ProbePayload {
+    /// Steam A2S_INFO query, answering challenges.
+    A2sInfo,
+    /// Sends `payload` and waits for any reply, or one starting with `expect`.
+    Raw {
+        payload: Vec<u8>,
+        expect: Option<Vec<u8>>,
+    },
+}
+
+/// Server browser details from an A2S_INFO reply.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct A2sInfo {
+    pub name: String,
+    pub map: String,
+    pub players: u8,
+    pub max_players: u8,
+}
+
+impl A2sInfo {
+    /// Parses an S2A_INFO reply, header included.
+    pub fn parse(reply: &[u8]) -> Result<Self> {
+        let Some(body) = reply.strip_prefix(&[0xFF, 0xFF, 0xFF, 0xFF, S2A_INFO]) else {
+            bail!("Not an A2S_INFO reply");
+        };
+        // Protocol version, then null terminated strings.
+        let mut rest = body.get(1..).context("Truncated A2S_INFO reply")?;
+        let mut string = || -> Result<String> {
+            let end = rest
+                .iter()
+                .position(|&b| b == 0)
+                .context("Truncated A2S_INFO reply")?;
+            let value = String::from_utf8_lossy(&rest[..end]).to_string();
+            rest = &rest[end + 1..];
+            Ok(value)
+        };
+        let name = string()?;
+        let map = string()?;
+        let _folder = string()?;
+        let _game = string()?;
+        // App ID (u16), players, max players.
+        let [_, _, players, max_players, ..] = rest else {
+            bail!("Truncated A2S_INFO reply");
+        };
+        Ok(Self {
+            name,
+            map,
+            players: *players,
+            max_players: *max_players,
+        })
+    }
+}
+
+/// Result of a [GamePortProbe].
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct GamePortResult {
+    pub port: u16,
+    /// Round trip of the reply, None if the port didn't answer.
+    pub latency: Option<Duration>,
+    /// Only for [ProbePayload::A2sInfo].
+    pub info: Option<A2sInfo>,
+    /// Why the port is considered unreachable.
+    pub failure: Option<String>,
+}
+
+impl GamePortResult {
+    pub fn reachable(&self) -> bool {
+        self.latency.is_some()
+    }
+}
+
+/// Probes the game port, see the [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct GamePortProbe {
+    pub host: String,
+    pub port: u16,
+    pub payload: ProbePayload,
+    pub timeout: Duration,
+}
+
+impl GamePortProbe {
+    pub fn new(host: impl Into<String>, port: u16) -> Self {
+        Self {
+            host: host.into(),
+            port,
+            payload: ProbePayload::A2sInfo,
+            timeout: DEFAULT_PROBE_TIMEOUT,
+        }
+    }
+
+    /// Sends the probe, errors are reported as the failure rather than returned.
+    pub async fn run(&self) -> GamePortResult {
+        let mut result = GamePortResult {
+            port: self.port,
+            ..Default::default()
+        };
+        let start = Instant::now();
+        match tokio::time::timeout(self.timeout, self.exchange()).await {
+            Ok(Ok(info)) => {
+                result.latency = Some(start.elapsed());
+                result.info = info;
+            }
+            Ok(Err(e)) => result.failure = Some(format!("UDP port {}: {e}", self.port)),
+            Err(_) => {
+                result.failure = Some(format!(
+                    "UDP port {} didn't answer within {:?}",
+                    self.port, self.timeout
+                ))
+            }
+        }
+        result
+    }
+
+    async fn exchange(&self) -> Result<Option<A2sInfo>> {
+        let target = tokio::net::lookup_host((self.host.as_str(), self.port))
+            .await?
+            .next()
+            .with_context(|| format!("{} has no address", self.host))?;
+        let bind = match target.is_ipv4() {
+            true => "0.0.0.0:0",
+            false => "[::]:0",
+        };
+        let socket = UdpSocket::bind(bind).await?;
+        socket.connect(target).await?;
+        let mut buf = [0u8; 1400];
+        match &self.payload {
+            ProbePayload::Raw { payload, expect } => {
+                socket.send(payload).await?;
+                let len = socket.recv(&mut buf).await?;
+                if let Some(expect) = expect {
+                    if !buf[..len].starts_with(expect) {
+                        bail!("Unexpected reply");
+                    }
+                }
+                Ok(None)
+            }
+            ProbePayload::A2sInfo => {
+                socket.send(A2S_INFO).await?;
+                let mut len = socket.recv(&mut buf).await?;
+                if buf[..len].starts_with(&[0xFF, 0xFF, 0xFF, 0xFF, S2C_CHALLENGE]) && len >= 9 {
+                    let mut query = A2S_INFO.to_vec();
+                    query.extend_from_slice(&buf[5..9]);
+                    socket.send(&query).await?;
+                    len = socket.recv(&mut buf).await?;
+                }
+                A2sInfo::parse(&buf[..len]).map(Some)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info_reply() -> Vec<u8> {
+        let mut reply = vec![0xFF, 0xFF, 0xFF, 0xFF, S2A_INFO, 17];
+        for string in ["Palworld", "MainWorld", "Pal", "Palworld"] {
+            reply.extend_from_slice(string.as_bytes());
+            reply.push(0);
+        }
+        reply.extend_from_slice(&[0, 0, 3, 32, 0, b'd', b'l', 0, 0]);
+        reply
+    }
+
+    #[test]
+    fn test_parse_info() {
+        let info = A2sInfo::parse(&info_reply()).unwrap();
+        assert_eq!(info.name, "Palworld");
+        assert_eq!(info.map, "MainWorld");
+        assert_eq!((info.players, info.max_players), (3, 32));
+        assert!(A2sInfo::parse(&info_reply()[..12]).is_err());
+        assert!(A2sInfo::parse(b"\xFF\xFF\xFF\xFFA1234").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_probe() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let port = server.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 1400];
+            loop {
+                let (len, from) = server.recv_from(&mut buf).await.unwrap();
+                let reply = match &buf[..len] {
+                    query if query == A2S_INFO => b"\xFF\xFF\xFF\xFFA\x01\x02\x03\x04".to_vec(),
+                    query if query.starts_with(A2S_INFO) => info_reply(),
+                    _ => b"pong".to_vec(),
+                };
+                server.send_to(&reply, from).await.unwrap();
+            }
+        });
+
+        let result = GamePortProbe::new("127.0.0.1", port).run().await;
+        assert!(result.reachable(), "{:?}", result.failure);
+        assert_eq!(result.info.unwrap().players, 3);
+
+        let mut probe = GamePortProbe::new("127.0.0.1", port);
+        probe.payload = ProbePayload::Raw {
+            payload: b"ping".to_vec(),
+            expect: Some(b"pong".to_vec()),
+        };
+        assert!(probe.run().await.reachable());
+        probe.payload = ProbePayload::Raw {
+            payload: b"ping".to_vec(),
+            expect: Some(b"PONG".to_vec()),
+        };
+        assert!(!probe.run().await.reachable());
+    }
+
+    #[tokio::test]
+    async fn test_probe_timeout() {
+        // Bound but silent, so the probe neither gets a reply nor an ICMP error.
+        let silent = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let mut probe = GamePortProbe::new("127.0.0.1", silent.local_addr().unwrap().port());
+        probe.timeout = Duration::from_millis(200);
+        let result = probe.run().await;
+        assert!(!result.reachable());
+        assert!(result.failure.unwrap().contains("didn't answer"));
+    }
+}
@@ -0,0 +1,135 @@
+//! Fast pass/fail health checks for container liveness probes.
+//!
+//! # Example:
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use palworld_server::health::HealthCheck;
+//! use palworld_server::rcon::{PalworldRCON, DEFAULT_SOURCE_PORT};
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let rcon = PalworldRCON::new("localhost", DEFAULT_SOURCE_PORT, "MyRCONPassword");
+//!     let mut check = HealthCheck::new(Duration::from_secs(5));
+//!     check.min_version = Some("v0.1.5.0".to_string());
+//!     let report = check.run(&rcon).await;
+//!     std::process::exit(if report.healthy() { 0 } else { 1 });
+//! }
+//! ```
+
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+
+use crate::gameport::{GamePortProbe, GamePortResult};
+use crate::parse;
+use crate::rcon::PalworldRCON;
+
+/// Default time the whole check may take.
+pub const DEFAULT_HEALTH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Assertions made against the server.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct HealthCheck {
+    /// Time the check may take, including authentication.
+    pub timeout: Duration,
+    /// Fail if the server is older than this version.
+    pub min_version: Option<String>,
+    /// Fail if more players than this are online.
+    pub max_players: Option<usize>,
+    /// Also probe this game UDP port of the RCON host, fail if it doesn't answer.
+    pub game_port: Option<u16>,
+}
+
+/// Result of a [HealthCheck].
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct HealthReport {
+    /// Server version, None if the server couldn't be reached.
+    pub version: Option<String>,
+    /// Players online, only fetched when [HealthCheck::max_players] is set.
+    pub players: Option<usize>,
+    /// Time the check took.
+    pub latency: Duration,
+    /// Probe of the game port, only run when [HealthCheck::game_port] is set.
+    pub game: Option<GamePortResult>,
+    /// Why the check failed, empty when healthy.
+    pub failures: Vec<String>,
+}
+
+impl HealthReport {
+    pub fn healthy(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl HealthCheck {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            min_version: None,
+            max_players: None,
+            game_port: None,
+        }
+    }
+
+    /// Authenticates, runs `info` and checks the assertions within [HealthCheck::timeout].
+    /// The game port is probed meanwhile. Errors are reported as failures rather than
+    /// returned.
+    pub async fn run(&self, rcon: &PalworldRCON) -> HealthReport {
+        let start = Instant::now();
+        let mut report = HealthReport::default();
+        let game = async {
+            let port = self.game_port?;
+            let mut probe = GamePortProbe::new(rcon.host.as_str(), port);
+            probe.timeout = self.timeout;
+            Some(probe.run().await)
+        };
+        let checked = tokio::time::timeout(self.timeout, self.probe(rcon, &mut report));
+        let (checked, game) = tokio::join!(checked, game);
+        report.latency = start.elapsed();
+        match checked {
+            Ok(Ok(())) => (),
+            Ok(Err(e)) => report.failures.push(e.to_string()),
+            Err(_) => report
+                .failures
+                .push(format!("Timed out after {:?}", self.timeout)),
+        }
+        if let Some(failure) = game.as_ref().and_then(|game| game.failure.as_ref()) {
+            report.failures.push(format!("Game port: {failure}"));
+        }
+        report.game = game;
+        report
+    }
+
+    async fn probe(&self, rcon: &PalworldRCON, report: &mut HealthReport) -> Result<()> {
+        let version = rcon.get_version().await?;
+        report.version = Some(version.clone());
+        if let Some(min_version) = &self.min_version {
+            if parse::compare_versions(&version, min_version)?.is_lt() {
+                report
+                    .failures
+                    .push(format!("Version {version} is older than {min_version}"));
+            }
+        }
+        if let Some(max_players) = self.max_players {
+            let players = rcon.get_player_info().await?.len();
+            report.players = Some(players);
+            if players > max_players {
+                report
+                    .failures
+                    .push(format!("{players} players online, more than {max_players}"));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for HealthCheck {
+    fn default() -> Self {
+        Self::new(DEFAULT_HEALTH_TIMEOUT)
+    }
+}
@@ -0,0 +1,204 @@
+//! Heartbeats registering the server with community server lists.
+//!
+//! [HeartbeatSender::send] reads the server name, version and player count over RCON and
+//! posts them as JSON to a server list. With a [HeartbeatSender::secret], the body is signed
+//! with HMAC-SHA256 over `{timestamp}.{body}`: the timestamp goes into the
+//! `X-Heartbeat-Timestamp` header and the hex signature into `X-Heartbeat-Signature` as
+//! `sha256=...`, so the list can reject forged and replayed heartbeats.
+//! [HeartbeatSender::schedule] sends one on every run of a [Schedule].
+//!
+//! # Example:
+//! ```no_run
+//! use std::sync::Arc;
+//!
+//! use palworld_server::heartbeat::HeartbeatSender;
+//! use palworld_server::rcon::{PalworldRCON, DEFAULT_SOURCE_PORT};
+//! use palworld_server::scheduler::{Schedule, Scheduler, Tz};
+//! use palworld_server::secret::Secret;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let rcon = PalworldRCON::new("localhost", DEFAULT_SOURCE_PORT, "MyRCONPassword");
+//!     let mut sender = HeartbeatSender::new(rcon, "https://servers.example.com/heartbeat");
+//!     sender.address = Some("palworld.example.com:8211".to_string());
+//!     sender.secret = Some(Secret::new("MyListingKey".to_string()));
+//!
+//!     let mut scheduler = Scheduler::new();
+//!     Arc::new(sender).schedule(&mut scheduler, Schedule::parse("*/5 * * * *", Tz::UTC).unwrap());
+//!     scheduler.run().await;
+//! }
+//! ```
+
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+
+use crate::rcon::PalworldRCON;
+use crate::scheduler::{Job, Schedule, Scheduler};
+use crate::secret::Secret;
+
+/// Header with the unix time a heartbeat was signed at.
+pub const TIMESTAMP_HEADER: &str = "X-Heartbeat-Timestamp";
+/// Header with the `sha256=` prefixed hex signature of a heartbeat.
+pub const SIGNATURE_HEADER: &str = "X-Heartbeat-Signature";
+
+/// What a server list learns about the server.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct Heartbeat {
+    /// ServerName from the settings.
+    pub name: String,
+    pub version: String,
+    pub players: usize,
+    /// Address players connect to, None to let the list use the sender's.
+    pub address: Option<String>,
+    /// Unix time the heartbeat was sent at.
+    pub timestamp: i64,
+}
+
+/// Sends heartbeats to a server list, see the [module documentation](self).
+#[derive(Debug)]
+pub struct HeartbeatSender {
+    rcon: PalworldRCON,
+    client: reqwest::Client,
+    /// Endpoint the heartbeats are posted to.
+    pub url: String,
+    pub address: Option<String>,
+    /// Key the heartbeats are signed with, unsigned if None.
+    pub secret: Option<Secret<String>>,
+}
+
+impl HeartbeatSender {
+    pub fn new(rcon: PalworldRCON, url: impl Into<String>) -> Self {
+        Self {
+            rcon,
+            client: reqwest::Client::new(),
+            url: url.into(),
+            address: None,
+            secret: None,
+        }
+    }
+
+    /// Reads the current heartbeat from the server.
+    pub async fn collect(&self, now: SystemTime) -> Result<Heartbeat> {
+        let info = self.rcon.get_server_info().await?;
+        let players = self.rcon.get_player_info().await?;
+        Ok(Heartbeat {
+            name: info.name,
+            version: info.version.to_string(),
+            players: players.len(),
+            address: self.address.clone(),
+            timestamp: now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64,
+        })
+    }
+
+    /// Posts the current heartbeat and returns it.
+    pub async fn send(&self) -> Result<Heartbeat> {
+        let heartbeat = self.collect(SystemTime::now()).await?;
+        let body = serde_json::to_string(&heartbeat)?;
+        let mut request = self
+            .client
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json");
+        if let Some(secret) = &self.secret {
+            request = request
+                .header(TIMESTAMP_HEADER, heartbeat.timestamp.to_string())
+                .header(
+                    SIGNATURE_HEADER,
+                    signature(secret.expose(), heartbeat.timestamp, &body),
+                );
+        }
+        request.body(body).send().await?.error_for_status()?;
+        log::debug!(
+            "Heartbeat sent to {}: {} players",
+            self.url,
+            heartbeat.players
+        );
+        Ok(heartbeat)
+    }
+
+    /// Adds a job sending a heartbeat to `scheduler`, run on `schedule`. Missed runs are
+    /// skipped, a late heartbeat tells the list nothing new.
+    pub fn schedule<'a>(
+        self: &Arc<Self>,
+        scheduler: &'a mut Scheduler,
+        schedule: Schedule,
+    ) -> &'a mut Job {
+        let sender = self.clone();
+        scheduler.add("heartbeat", schedule, move || {
+            let sender = sender.clone();
+            async move {
+                sender.send().await?;
+                Ok(())
+            }
+        })
+    }
+}
+
+/// Value of the [SIGNATURE_HEADER] for a heartbeat `body` sent at `timestamp`.
+pub fn signature(secret: &str, timestamp: i64, body: &str) -> String {
+    let mac = hmac_sha256(secret.as_bytes(), format!("{timestamp}.{body}").as_bytes());
+    let hex: String = mac.iter().map(|byte| format!("{byte:02x}")).collect();
+    format!("sha256={hex}")
+}
+
+/// HMAC (RFC 2104) with SHA-256.
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    const BLOCK_SIZE: usize = 64;
+    let mut block = [0u8; BLOCK_SIZE];
+    match key.len() > BLOCK_SIZE {
+        true => block[..32].copy_from_slice(&Sha256::digest(key)),
+        false => block[..key.len()].copy_from_slice(key),
+    }
+    let pad = |byte: u8| block.map(|b| b ^ byte);
+    let inner = Sha256::new()
+        .chain_update(pad(0x36))
+        .chain_update(message)
+        .finalize();
+    Sha256::new()
+        .chain_update(pad(0x5c))
+        .chain_update(inner)
+        .finalize()
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hmac_sha256() {
+        // RFC 4231 test cases 2 and 6.
+        let hex = |mac: [u8; 32]| -> String { mac.iter().map(|b| format!("{b:02x}")).collect() };
+        assert_eq!(
+            hex(hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_eq!(
+            hex(hmac_sha256(
+                &[0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First"
+            )),
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+    }
+
+    #[test]
+    fn test_signature() {
+        let heartbeat = Heartbeat {
+            name: "Palworld".to_string(),
+            version: "v0.1.5.1".to_string(),
+            players: 3,
+            address: None,
+            timestamp: 1_700_000_000,
+        };
+        let body = serde_json::to_string(&heartbeat).unwrap();
+        let signed = signature("key", heartbeat.timestamp, &body);
+        assert!(signed.starts_with("sha256="));
+        assert_eq!(signed.len(), "sha256=".len() + 64);
+        assert_ne!(signed, signature("key", heartbeat.timestamp + 1, &body));
+        assert_ne!(signed, signature("other key", heartbeat.timestamp, &body));
+    }
+}
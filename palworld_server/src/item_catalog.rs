@@ -0,0 +1,52 @@
+// Item data only, the lookup code is in crate::items. Keep entries sorted by category, then
+// name. IDs are the internal names `give` commands take, names as shown in game (English).
+
+use crate::items::{Item, ItemCategory::*};
+
+pub(crate) static ITEMS: &[Item] = &[
+    Item::new("Bone", "Bone", Material),
+    Item::new("CarbonFiber", "Carbon Fiber", Material),
+    Item::new("CircuitBoard", "Circuit Board", Material),
+    Item::new("Cloth", "Cloth", Material),
+    Item::new("Coal", "Coal", Material),
+    Item::new("CrudeOil", "Crude Oil", Material),
+    Item::new("ElectricOrgan", "Electric Organ", Material),
+    Item::new("Fiber", "Fiber", Material),
+    Item::new("FireOrgan", "Flame Organ", Material),
+    Item::new("Money", "Gold Coin", Material),
+    Item::new("Gunpowder2", "Gunpowder", Material),
+    Item::new("PalOil", "High Quality Pal Oil", Material),
+    Item::new("Horn", "Horn", Material),
+    Item::new("IceOrgan", "Ice Organ", Material),
+    Item::new("Leather", "Leather", Material),
+    Item::new("CopperIngot", "Metal Ingot", Material),
+    Item::new("CopperOre", "Ore", Material),
+    Item::new("PalCrystal_Ex", "Paldium Fragment", Material),
+    Item::new("Polymer", "Polymer", Material),
+    Item::new("Quartz", "Quartz", Material),
+    Item::new("StealIngot", "Refined Ingot", Material),
+    Item::new("Stone", "Stone", Material),
+    Item::new("Sulfur", "Sulfur", Material),
+    Item::new("Wood", "Wood", Material),
+    Item::new("Wool", "Wool", Material),
+    Item::new("PalSphere_Giga", "Giga Sphere", Sphere),
+    Item::new("PalSphere_Tera", "Hyper Sphere", Sphere),
+    Item::new("PalSphere_Legend", "Legendary Sphere", Sphere),
+    Item::new("PalSphere_Mega", "Mega Sphere", Sphere),
+    Item::new("PalSphere", "Pal Sphere", Sphere),
+    Item::new("PalSphere_Master", "Ultra Sphere", Sphere),
+    Item::new("BakedBerries", "Baked Berries", Food),
+    Item::new("Bread", "Bread", Food),
+    Item::new("Cake", "Cake", Food),
+    Item::new("Egg", "Egg", Food),
+    Item::new("Flour", "Flour", Food),
+    Item::new("Honey", "Honey", Food),
+    Item::new("Lettuce", "Lettuce", Food),
+    Item::new("Milk", "Milk", Food),
+    Item::new("Mushroom", "Mushroom", Food),
+    Item::new("Berries", "Red Berries", Food),
+    Item::new("Tomato", "Tomato", Food),
+    Item::new("Wheat", "Wheat", Food),
+    Item::new("Arrow", "Arrow", Ammo),
+    Item::new("Arrow_Fire", "Fire Arrow", Ammo),
+];
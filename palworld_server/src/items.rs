@@ -0,0 +1,126 @@
+//! Item IDs for `give` commands, looked up by the names players know.
+//!
+//! Palworld's internal item IDs don't always match the names shown in game, Metal Ingots are
+//! `CopperIngot` and Refined Ingots `StealIngot`. [find] accepts an ID, a name, part of a
+//! name or a misspelled name and returns the catalog entry, [search] lists every match. The
+//! catalog covers common materials, spheres, food and ammo, items missing from it can still
+//! be given by ID with [crate::palguard::Give].
+//!
+//! # Example:
+//! ```
+//! use palworld_server::items;
+//!
+//! assert_eq!(items::find("refined ingot").unwrap().id, "StealIngot");
+//! assert_eq!(items::find("legendary").unwrap().id, "PalSphere_Legend");
+//! assert_eq!(items::find("Papyrus").unwrap_err().to_string(), "No item matches 'Papyrus'");
+//! ```
+
+use std::fmt;
+
+use anyhow::Result;
+
+use crate::item_catalog::ITEMS;
+use crate::lookup::{self, Entry};
+
+/// Kind of an item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum ItemCategory {
+    Material,
+    Sphere,
+    Food,
+    Ammo,
+}
+
+impl fmt::Display for ItemCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Material => write!(f, "material"),
+            Self::Sphere => write!(f, "sphere"),
+            Self::Food => write!(f, "food"),
+            Self::Ammo => write!(f, "ammo"),
+        }
+    }
+}
+
+/// An item of the catalog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Item {
+    /// Internal ID, as `give` commands take it.
+    pub id: &'static str,
+    /// Name shown in game.
+    pub name: &'static str,
+    pub category: ItemCategory,
+}
+
+impl Item {
+    pub const fn new(id: &'static str, name: &'static str, category: ItemCategory) -> Self {
+        Self { id, name, category }
+    }
+}
+
+impl Entry for Item {
+    fn id(&self) -> &'static str {
+        self.id
+    }
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl fmt::Display for Item {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.name, self.id)
+    }
+}
+
+/// Every item of the catalog.
+pub fn catalog() -> &'static [Item] {
+    ITEMS
+}
+
+/// Items whose ID or name is `query`, or whose name contains it, ignoring case. Exact
+/// matches come first.
+pub fn search(query: &str) -> Vec<&'static Item> {
+    lookup::search(ITEMS, query)
+}
+
+/// The item `query` names: an exact ID or name, the only item whose name contains it, or
+/// the closest name for typos. Fails listing the candidates if several items match.
+pub fn find(query: &str) -> Result<&'static Item> {
+    lookup::find(ITEMS, query, "item")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catalog() {
+        let mut ids: Vec<&str> = catalog().iter().map(|item| item.id).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        assert_eq!(ids.len(), catalog().len(), "item IDs are unique");
+    }
+
+    #[test]
+    fn test_find() {
+        assert_eq!(find("Wood").unwrap().id, "Wood");
+        assert_eq!(find("stealingot").unwrap().name, "Refined Ingot");
+        assert_eq!(find(" metal ingot ").unwrap().id, "CopperIngot");
+        // Exact names win over names containing them.
+        assert_eq!(find("pal sphere").unwrap().id, "PalSphere");
+        assert_eq!(find("paldium").unwrap().id, "PalCrystal_Ex");
+        assert_eq!(find("Tomatoe").unwrap().id, "Tomato");
+        let error = find("ingot").unwrap_err().to_string();
+        assert_eq!(
+            error,
+            "'ingot' matches Metal Ingot (CopperIngot), Refined Ingot (StealIngot)"
+        );
+        assert!(find("").is_err());
+        assert_eq!(search("sphere").len(), 6);
+    }
+}
@@ -1,3 +1,139 @@
+pub mod config;
+pub mod mem;
+pub mod message;
+pub mod models;
+pub mod parse;
+pub mod progress;
+pub mod secret;
+pub mod shutdown;
+pub mod validate;
+pub mod verbosity;
+
+#[cfg(feature = "rcon")]
 pub mod rcon;
+#[cfg(all(test, feature = "rcon"))]
+mod mock;
+#[cfg(feature = "rcon")]
+pub mod command;
+#[cfg(feature = "custom-commands")]
+pub mod registry;
+#[cfg(feature = "palguard")]
+pub mod palguard;
+#[cfg(feature = "palguard")]
+pub mod items;
+#[cfg(feature = "palguard")]
+mod item_catalog;
+#[cfg(feature = "palguard")]
+pub mod pals;
+#[cfg(feature = "palguard")]
+mod pal_catalog;
+#[cfg(feature = "palguard")]
+mod lookup;
+#[cfg(feature = "ssh")]
 pub mod ssh;
-pub mod mem;
\ No newline at end of file
+#[cfg(feature = "system")]
+pub mod cpu;
+#[cfg(feature = "rcon")]
+pub mod events;
+#[cfg(any(feature = "rcon", feature = "ssh"))]
+pub mod trace;
+#[cfg(feature = "ssh")]
+pub mod firewall;
+#[cfg(feature = "rcon")]
+pub mod diagnostics;
+#[cfg(feature = "rcon")]
+pub mod gameport;
+#[cfg(feature = "rcon")]
+pub mod health;
+#[cfg(feature = "rcon")]
+pub mod tasks;
+#[cfg(feature = "rcon")]
+pub mod verify;
+#[cfg(feature = "rcon")]
+pub mod watcher;
+#[cfg(feature = "rcon")]
+pub mod auth;
+#[cfg(feature = "billing")]
+pub mod billing;
+#[cfg(feature = "rcon")]
+pub mod chaos;
+#[cfg(all(feature = "rcon", feature = "ssh"))]
+pub mod chat;
+#[cfg(all(feature = "savefile", feature = "store"))]
+pub mod cleanup;
+#[cfg(feature = "schedule")]
+pub mod clock;
+#[cfg(feature = "ssh")]
+pub mod dryrun;
+#[cfg(feature = "encryption")]
+pub mod encryption;
+#[cfg(feature = "schedule")]
+pub mod enforce;
+#[cfg(all(feature = "rcon", feature = "ssh"))]
+pub mod migrate;
+#[cfg(feature = "rcon")]
+pub mod plugin;
+#[cfg(feature = "rcon")]
+pub mod ready;
+#[cfg(feature = "schedule")]
+pub mod scheduler;
+#[cfg(feature = "rcon")]
+pub mod session;
+#[cfg(feature = "rest")]
+pub mod rest;
+#[cfg(all(feature = "rcon", feature = "rest"))]
+pub mod failover;
+#[cfg(feature = "heartbeat")]
+pub mod heartbeat;
+#[cfg(feature = "ssh")]
+pub mod provision;
+#[cfg(all(feature = "rcon", feature = "ssh"))]
+pub mod rotation;
+#[cfg(all(feature = "rcon", feature = "ssh"))]
+pub mod vote;
+#[cfg(all(feature = "rcon", feature = "ssh"))]
+pub mod announce;
+#[cfg(feature = "rcon")]
+pub mod welcome;
+#[cfg(feature = "notify")]
+pub mod notify;
+#[cfg(all(feature = "rcon", feature = "serde"))]
+pub mod status;
+#[cfg(feature = "email")]
+pub mod email;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+#[cfg(feature = "slack")]
+pub mod slack;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(feature = "store")]
+pub mod milestone;
+#[cfg(feature = "store")]
+pub mod moderation;
+#[cfg(feature = "moddata")]
+pub mod moddata;
+#[cfg(feature = "savefile")]
+pub mod savefile;
+#[cfg(feature = "store")]
+pub mod store;
+#[cfg(feature = "support")]
+pub mod support;
+#[cfg(all(feature = "store", feature = "schedule"))]
+pub mod tempban;
+#[cfg(feature = "store")]
+pub mod uptime;
+#[cfg(feature = "telegram")]
+pub mod telegram;
+#[cfg(feature = "wol")]
+pub mod wol;
+#[cfg(feature = "ssh")]
+pub mod world;
+
+// The RCON client, player model and port constant have a single definition each, these are
+// the import paths other crates in the workspace should use.
+pub use models::PlayerInfo;
+#[cfg(feature = "rcon")]
+pub use rcon::{PalworldRCON, DEFAULT_SOURCE_PORT};
+// Embedding applications call `palworld_server::quiet()` once at startup.
+pub use verbosity::quiet;
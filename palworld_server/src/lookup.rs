@@ -0,0 +1,56 @@
+// Name lookup shared by the generated catalogs, crate::items and crate::pals.
+
+use std::fmt::Display;
+
+use anyhow::{bail, Result};
+
+use crate::validate::did_you_mean;
+
+/// An entry of a catalog, known by an internal ID and a name shown in game.
+pub(crate) trait Entry: Display {
+    fn id(&self) -> &'static str;
+    fn name(&self) -> &'static str;
+}
+
+/// Entries whose ID or name is `query`, or whose name or ID contains it, ignoring case.
+/// Exact matches come first.
+pub(crate) fn search<T: Entry>(entries: &'static [T], query: &str) -> Vec<&'static T> {
+    let query = query.trim().to_lowercase();
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let exact =
+        |entry: &T| entry.id().to_lowercase() == query || entry.name().to_lowercase() == query;
+    let mut matches: Vec<&T> = entries.iter().filter(|entry| exact(entry)).collect();
+    matches.extend(entries.iter().filter(|entry| {
+        !exact(entry)
+            && (entry.name().to_lowercase().contains(&query)
+                || entry.id().to_lowercase().contains(&query))
+    }));
+    matches
+}
+
+/// The entry `query` names: an exact ID or name, the only entry containing it, or the
+/// closest name for typos. Fails listing the candidates if several entries match, `kind`
+/// names the entries in errors.
+pub(crate) fn find<T: Entry>(entries: &'static [T], query: &str, kind: &str) -> Result<&'static T> {
+    let matches = search(entries, query);
+    let query = query.trim();
+    let exact = matches.first().filter(|entry| {
+        entry.id().eq_ignore_ascii_case(query) || entry.name().eq_ignore_ascii_case(query)
+    });
+    if let Some(entry) = exact {
+        return Ok(entry);
+    }
+    match matches.as_slice() {
+        [entry] => Ok(entry),
+        [] => match did_you_mean(query, entries.iter().map(Entry::name)) {
+            Some(name) => Ok(entries.iter().find(|entry| entry.name() == name).unwrap()),
+            None => bail!("No {kind} matches '{query}'"),
+        },
+        matches => {
+            let names: Vec<String> = matches.iter().map(|entry| entry.to_string()).collect();
+            bail!("'{query}' matches {}", names.join(", "))
+        }
+    }
+}
@@ -1,39 +1,99 @@
-use psutil::memory::{os::linux::VirtualMemoryExt, virtual_memory};
+#[cfg(feature = "system")]
 use anyhow::Result;
-use serde::{Serialize, Deserialize};
 
-#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+use crate::models::ByteSize;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
 pub struct MemInfo {
-    pub mem_total: u64,
-    pub mem_free: u64,
-    pub mem_available: u64,
-    pub buffers: u64,
-    pub cached: u64,
+    pub mem_total: ByteSize,
+    pub mem_free: ByteSize,
+    pub mem_available: ByteSize,
+    /// Only reported on Linux.
+    pub buffers: Option<ByteSize>,
+    /// Only reported on Linux.
+    pub cached: Option<ByteSize>,
 }
 
 impl MemInfo {
-    pub fn used(&self) -> Option<u64> {
+    pub fn used(&self) -> Option<ByteSize> {
         self.mem_total.checked_sub(self.mem_available)
     }
 
+    /// Used memory as a percentage from 0 to 100.
     pub fn used_percent(&self) -> Option<f64> {
         match self.used() {
-            Some(used) => {
-                let used = used as f64;
-                Some(used / (self.mem_total as f64))
+            Some(used) if self.mem_total.as_bytes() > 0 => {
+                let used = used.as_bytes() as f64;
+                Some(used / (self.mem_total.as_bytes() as f64) * 100.0)
             }
-            None => None,
+            _ => None,
         }
     }
 
+    /// Gets memory information of the local machine.
+    #[cfg(all(feature = "system", target_os = "linux"))]
     pub fn get_memory_info() -> Result<Self> {
+        use psutil::memory::{os::linux::VirtualMemoryExt, virtual_memory};
+
         let virt_mem = virtual_memory()?;
         Ok(Self {
-            mem_total: virt_mem.total(),
-            mem_free: virt_mem.free(),
-            mem_available: virt_mem.available(),
-            buffers: virt_mem.buffers(),
-            cached: virt_mem.cached(),
+            mem_total: ByteSize(virt_mem.total()),
+            mem_free: ByteSize(virt_mem.free()),
+            mem_available: ByteSize(virt_mem.available()),
+            buffers: Some(ByteSize(virt_mem.buffers())),
+            cached: Some(ByteSize(virt_mem.cached())),
         })
     }
+
+    /// Gets memory information of the local machine.
+    #[cfg(all(feature = "system", not(target_os = "linux")))]
+    pub fn get_memory_info() -> Result<Self> {
+        let mut system = sysinfo::System::new();
+        system.refresh_memory();
+        Ok(Self {
+            mem_total: ByteSize(system.total_memory()),
+            mem_free: ByteSize(system.free_memory()),
+            mem_available: ByteSize(system.available_memory()),
+            buffers: None,
+            cached: None,
+        })
+    }
+}
+
+impl std::fmt::Display for MemInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.used(), self.used_percent()) {
+            (Some(used), Some(percent)) => writeln!(f, "Used:      {used} ({percent:.2}%)")?,
+            _ => writeln!(f, "Used:      unknown")?,
+        }
+        writeln!(f, "Total:     {}", self.mem_total)?;
+        writeln!(f, "Free:      {}", self.mem_free)?;
+        write!(f, "Available: {}", self.mem_available)?;
+        if let Some(buffers) = self.buffers {
+            write!(f, "\nBuffers:   {buffers}")?;
+        }
+        if let Some(cached) = self.cached {
+            write!(f, "\nCached:    {cached}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_used_percent() {
+        let mem_info = MemInfo {
+            mem_total: ByteSize::from_kib(1000),
+            mem_available: ByteSize::from_kib(250),
+            ..Default::default()
+        };
+        assert_eq!(mem_info.used(), Some(ByteSize::from_kib(750)));
+        assert_eq!(mem_info.used_percent(), Some(75.0));
+        assert_eq!(MemInfo::default().used_percent(), None);
+    }
 }
@@ -0,0 +1,220 @@
+//! Encoding of in-game messages and decoding of server responses.
+//!
+//! Palworld cuts broadcast and shutdown messages at the first space, so whitespace is
+//! replaced before sending and messages are cut to fit a single RCON packet. Responses are
+//! decoded with [decode_response] before parsing, so player names are valid text.
+//!
+//! # Example:
+//! ```
+//! use palworld_server::message::{decode_broadcast, decode_response, encode_broadcast};
+//! use palworld_server::message::ResponseEncoding;
+//!
+//! let encoded = encode_broadcast("Restart in 5 minutes", Some("_")).unwrap();
+//! assert_eq!(encoded, "Restart_in_5_minutes");
+//! assert_eq!(decode_broadcast(&encoded, Some("_")), "Restart in 5 minutes");
+//! // UTF-8 read as Latin-1 on the way.
+//! assert_eq!(decode_response("ã\u{81}\u{93}ã\u{82}\u{93}", ResponseEncoding::Utf8), "こん");
+//! ```
+
+use anyhow::{bail, Result};
+
+/// Longest encoded message in bytes, longer messages are cut on a character boundary.
+/// Leaves room for the command name within an RCON packet.
+pub const MAX_BROADCAST_LEN: usize = 1000;
+
+/// Replaces every whitespace character with `replace_space` and cuts the result to
+/// [MAX_BROADCAST_LEN]. With no replacement the message is only cut.
+///
+/// The replacement itself can't contain whitespace, it would be cut by the server too.
+pub fn encode_broadcast(message: &str, replace_space: Option<&str>) -> Result<String> {
+    let encoded = match replace_space {
+        Some(replace) if replace.chars().any(char::is_whitespace) => {
+            bail!("Space replacement {replace:?} contains whitespace")
+        }
+        Some(replace) => message
+            .split(char::is_whitespace)
+            .collect::<Vec<&str>>()
+            .join(replace),
+        None => message.to_string(),
+    };
+    Ok(truncate(&encoded, MAX_BROADCAST_LEN).to_string())
+}
+
+/// Reverses [encode_broadcast], exact only when [is_reversible] holds for the message.
+pub fn decode_broadcast(encoded: &str, replace_space: Option<&str>) -> String {
+    match replace_space {
+        Some(replace) if !replace.is_empty() => encoded.replace(replace, " "),
+        _ => encoded.to_string(),
+    }
+}
+
+/// True if [decode_broadcast] gives back `message` exactly: it fits, its only whitespace
+/// is plain spaces and it shares no characters with a non-empty replacement.
+pub fn is_reversible(message: &str, replace_space: Option<&str>) -> bool {
+    let fits = |len: usize| len <= MAX_BROADCAST_LEN;
+    match replace_space {
+        None => fits(message.len()),
+        Some(replace) => {
+            let spaces = message.matches(' ').count();
+            !replace.is_empty()
+                && !replace.chars().any(char::is_whitespace)
+                && message
+                    .chars()
+                    .all(|c| (c == ' ' || !c.is_whitespace()) && !replace.contains(c))
+                && fits(message.len() - spaces + spaces * replace.len())
+        }
+    }
+}
+
+/// Encoding of the text in RCON responses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub enum ResponseEncoding {
+    /// What Palworld sends. Bytes that aren't UTF-8 become U+FFFD, and UTF-8 that a proxy or
+    /// console decoded as Latin-1 on the way is repaired.
+    #[default]
+    Utf8,
+    /// Japanese Windows code page. Responses that are valid UTF-8 are kept as they are.
+    #[cfg(feature = "cp932")]
+    Cp932,
+}
+
+/// Decodes the raw bytes of a response packet as `encoding`. Never fails, so one player name
+/// with bytes that aren't UTF-8 doesn't lose the whole response.
+pub fn decode_bytes(bytes: &[u8], encoding: ResponseEncoding) -> String {
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return text.to_string();
+    }
+    match encoding {
+        ResponseEncoding::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+        #[cfg(feature = "cp932")]
+        ResponseEncoding::Cp932 => encoding_rs::SHIFT_JIS.decode(bytes).0.into_owned(),
+    }
+}
+
+/// Decodes a response as `encoding`, control characters other than line breaks and tabs
+/// are replaced with U+FFFD so the result is always printable text. Raw bytes are decoded
+/// with [decode_bytes] first, this repairs text that went through Latin-1 on the way.
+pub fn decode_response(response: &str, encoding: ResponseEncoding) -> String {
+    // Bytes decoded as Latin-1 come back as characters up to U+00FF.
+    let latin1 = match response.is_ascii() {
+        true => None,
+        false => response
+            .chars()
+            .map(|c| u8::try_from(c).ok())
+            .collect::<Option<Vec<u8>>>(),
+    };
+    let decoded = match (encoding, latin1) {
+        (ResponseEncoding::Utf8, Some(bytes)) => {
+            String::from_utf8(bytes).unwrap_or_else(|_| response.to_string())
+        }
+        #[cfg(feature = "cp932")]
+        (ResponseEncoding::Cp932, Some(bytes)) => {
+            encoding_rs::SHIFT_JIS.decode(&bytes).0.into_owned()
+        }
+        (_, None) => response.to_string(),
+    };
+    decoded
+        .chars()
+        .map(
+            |c| match c.is_control() && !matches!(c, '\n' | '\r' | '\t') {
+                true => char::REPLACEMENT_CHARACTER,
+                false => c,
+            },
+        )
+        .collect()
+}
+
+/// Cuts `s` to at most `max` bytes without splitting a character.
+fn truncate(s: &str, max: usize) -> &str {
+    if s.len() <= max {
+        return s;
+    }
+    let mut end = max;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_encode_broadcast() {
+        assert_eq!(encode_broadcast("a b\tc\nd", Some("_")).unwrap(), "a_b_c_d");
+        assert_eq!(encode_broadcast("a b", None).unwrap(), "a b");
+        assert!(encode_broadcast("a b", Some(" ")).is_err());
+        // Cut on a character boundary.
+        let long = "é".repeat(MAX_BROADCAST_LEN);
+        assert_eq!(
+            encode_broadcast(&long, None).unwrap().len(),
+            MAX_BROADCAST_LEN
+        );
+        assert!(!is_reversible("snake_case", Some("_")));
+    }
+
+    #[test]
+    fn test_decode_response() {
+        let utf8 = ResponseEncoding::Utf8;
+        assert_eq!(
+            decode_response("name,playeruid,steamid\n", utf8),
+            "name,playeruid,steamid\n"
+        );
+        assert_eq!(decode_response("パル🐉,1,2", utf8), "パル🐉,1,2");
+        // "ü" and "🐉" read as Latin-1.
+        assert_eq!(decode_response("Ã¼", utf8), "ü");
+        assert_eq!(decode_response("ð\u{9f}\u{90}\u{89}", utf8), "🐉");
+        // Latin-1 text that isn't UTF-8 in disguise is kept.
+        assert_eq!(decode_response("Zoë", utf8), "Zoë");
+        assert_eq!(decode_response("a\u{0}b\u{1b}", utf8), "a\u{fffd}b\u{fffd}");
+    }
+
+    #[test]
+    fn test_decode_bytes() {
+        let utf8 = ResponseEncoding::Utf8;
+        assert_eq!(decode_bytes("パル,1,2".as_bytes(), utf8), "パル,1,2");
+        assert_eq!(decode_bytes(b"Pal\xffy,1,2", utf8), "Pal\u{fffd}y,1,2");
+    }
+
+    #[cfg(feature = "cp932")]
+    #[test]
+    fn test_decode_cp932() {
+        let cp932 = ResponseEncoding::Cp932;
+        // "パル" in CP932.
+        assert_eq!(decode_bytes(b"\x83p\x83\x8b", cp932), "パル");
+        assert_eq!(decode_bytes("パル".as_bytes(), cp932), "パル");
+        // Read as Latin-1 on the way.
+        assert_eq!(decode_response("\u{83}p\u{83}\u{8b}", cp932), "パル");
+        assert_eq!(decode_response("パル", cp932), "パル");
+    }
+
+    fn replacement() -> impl Strategy<Value = String> {
+        "[^\\s]{0,4}"
+    }
+
+    proptest! {
+        #[test]
+        fn encoded_fits(message in any::<String>(), replace in proptest::option::of(replacement())) {
+            let encoded = encode_broadcast(&message, replace.as_deref()).unwrap();
+            prop_assert!(encoded.len() <= MAX_BROADCAST_LEN);
+        }
+
+        #[test]
+        fn encoded_has_no_whitespace(message in any::<String>(), replace in replacement()) {
+            let encoded = encode_broadcast(&message, Some(&replace)).unwrap();
+            prop_assert!(!encoded.chars().any(char::is_whitespace));
+        }
+
+        #[test]
+        fn reversible_round_trip(message in "[a-zA-Z0-9 .!?é日本]{0,600}", replace in replacement()) {
+            if is_reversible(&message, Some(&replace)) {
+                let encoded = encode_broadcast(&message, Some(&replace)).unwrap();
+                prop_assert_eq!(decode_broadcast(&encoded, Some(&replace)), message);
+            }
+        }
+    }
+}
@@ -0,0 +1,232 @@
+//! Periodic samples of player count, memory and CPU, kept in the [SessionStore] so
+//! trends can be charted without a time series database.
+//!
+//! # Example:
+//! ```no_run
+//! use std::sync::Arc;
+//! use std::time::{Duration, SystemTime};
+//!
+//! use palworld_server::metrics::{write_csv, MetricsSampler};
+//! use palworld_server::rcon::{PalworldRCON, DEFAULT_SOURCE_PORT};
+//! use palworld_server::store::SessionStore;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let rcon = PalworldRCON::new("localhost", DEFAULT_SOURCE_PORT, "MyRCONPassword");
+//!     let store = Arc::new(SessionStore::open("palworld.db").unwrap());
+//!     MetricsSampler::new(rcon, store.clone()).unwrap().spawn();
+//!
+//!     let now = SystemTime::now();
+//!     let day = Duration::from_secs(24 * 3600);
+//!     let hourly = store.metrics_between(now - day, now, Duration::from_secs(3600)).unwrap();
+//!     write_csv(&hourly, std::io::stdout()).unwrap();
+//! }
+//! ```
+
+use std::io::Write;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+use tokio::task::JoinHandle;
+
+use crate::cpu::CpuSampler;
+use crate::mem::MemInfo;
+use crate::models::ByteSize;
+use crate::rcon::PalworldRCON;
+#[cfg(feature = "ssh")]
+use crate::ssh::PalworldConnection;
+use crate::store::{to_unix, SessionStore};
+use crate::watcher::PollInterval;
+
+/// Default interval between samples while players are online.
+pub const DEFAULT_SAMPLE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Default longest interval between samples of an empty or unreachable server, no longer
+/// than the default export step so exports don't get gaps.
+pub const DEFAULT_MAX_SAMPLE_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Server metrics at a point in time.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct MetricSample {
+    pub at: SystemTime,
+    /// Players online, averaged when samples are bucketed.
+    pub players: f64,
+    pub memory_used: Option<ByteSize>,
+    /// CPU usage (0-100) of the local machine, None when sampled over SSH.
+    pub cpu_percent: Option<f64>,
+}
+
+/// Writes samples as CSV with a header, timestamps in unix seconds and memory in bytes.
+pub fn write_csv(samples: &[MetricSample], mut writer: impl Write) -> Result<()> {
+    writeln!(writer, "timestamp,players,memory_used_bytes,cpu_percent")?;
+    for sample in samples {
+        writeln!(
+            writer,
+            "{},{:.2},{},{}",
+            to_unix(sample.at),
+            sample.players,
+            sample
+                .memory_used
+                .map(|m| m.as_bytes().to_string())
+                .unwrap_or_default(),
+            sample
+                .cpu_percent
+                .map(|c| format!("{c:.2}"))
+                .unwrap_or_default()
+        )?;
+    }
+    Ok(())
+}
+
+/// Renders `values` as a terminal sparkline scaled between their minimum and maximum.
+pub fn sparkline(values: &[f64]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    let range = max - min;
+    values
+        .iter()
+        .map(|value| match range > 0.0 {
+            true => BARS[((value - min) / range * (BARS.len() - 1) as f64).round() as usize],
+            false => BARS[0],
+        })
+        .collect()
+}
+
+/// Samples metrics and records them in a [SessionStore].
+#[derive(Debug)]
+pub struct MetricsSampler {
+    rcon: PalworldRCON,
+    store: Arc<SessionStore>,
+    /// Sample memory of the server over SSH instead of the local machine.
+    #[cfg(feature = "ssh")]
+    pub ssh: Option<PalworldConnection>,
+    /// Time between samples, backing off while nobody is online or sampling fails.
+    pub interval: PollInterval,
+    cpu: CpuSampler,
+}
+
+impl MetricsSampler {
+    /// Create a new [MetricsSampler] sampling every [DEFAULT_SAMPLE_INTERVAL].
+    pub fn new(rcon: PalworldRCON, store: Arc<SessionStore>) -> Result<Self> {
+        Ok(Self {
+            rcon,
+            store,
+            #[cfg(feature = "ssh")]
+            ssh: None,
+            interval: PollInterval::new(DEFAULT_SAMPLE_INTERVAL, DEFAULT_MAX_SAMPLE_INTERVAL),
+            cpu: CpuSampler::new()?,
+        })
+    }
+
+    /// Takes and records a sample.
+    pub async fn sample(&mut self) -> Result<MetricSample> {
+        let players = self.rcon.get_player_info().await?.len() as f64;
+        let (mem_info, cpu_percent) = match self.remote_memory().await {
+            Some(mem_info) => (mem_info, None),
+            None => (MemInfo::get_memory_info(), Some(self.cpu.sample()?)),
+        };
+        let memory_used = match mem_info {
+            Ok(mem_info) => mem_info.used(),
+            Err(e) => {
+                log::warn!("Failed to sample memory: {e}");
+                None
+            }
+        };
+        let sample = MetricSample {
+            at: SystemTime::now(),
+            players,
+            memory_used,
+            cpu_percent,
+        };
+        self.store.record_metrics(&sample)?;
+        Ok(sample)
+    }
+
+    /// Memory of the server over SSH, None when sampling the local machine.
+    async fn remote_memory(&self) -> Option<Result<MemInfo>> {
+        #[cfg(feature = "ssh")]
+        if let Some(ssh) = &self.ssh {
+            return Some(ssh.get_memory_info().await);
+        }
+        None
+    }
+
+    /// Samples forever. Failed samples are logged and retried with backoff.
+    pub async fn run(mut self) -> Result<()> {
+        let mut interval = self.interval.min;
+        loop {
+            let active = match self.sample().await {
+                Ok(sample) => sample.players > 0.0,
+                Err(e) => {
+                    log::warn!("Failed to sample metrics: {e}");
+                    false
+                }
+            };
+            interval = self.interval.next(interval, active);
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    /// Runs the sampler in a background task.
+    pub fn spawn(self) -> JoinHandle<Result<()>> {
+        tokio::spawn(self.run())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::from_unix;
+
+    #[test]
+    fn test_sparkline() {
+        assert_eq!(
+            sparkline(&[0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0]),
+            "▁▂▃▄▅▆▇█"
+        );
+        assert_eq!(sparkline(&[2.0, 2.0]), "▁▁");
+        assert_eq!(sparkline(&[]), "");
+    }
+
+    #[test]
+    fn test_metrics_between_csv() {
+        let store = SessionStore::open_in_memory().unwrap();
+        let start = 1_700_000_000;
+        for (offset, players, cpu) in [(0, 2.0, 10.0), (30, 4.0, 20.0), (60, 5.0, 30.0)] {
+            store
+                .record_metrics(&MetricSample {
+                    at: from_unix(start + offset),
+                    players,
+                    memory_used: Some(ByteSize::from_kib(offset as u64)),
+                    cpu_percent: Some(cpu),
+                })
+                .unwrap();
+        }
+        let samples = store
+            .metrics_between(
+                from_unix(start),
+                from_unix(start + 120),
+                Duration::from_secs(60),
+            )
+            .unwrap();
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[0].players, 3.0);
+        assert_eq!(samples[0].memory_used, Some(ByteSize(15 * 1024)));
+
+        let mut csv = Vec::new();
+        write_csv(&samples, &mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "timestamp,players,memory_used_bytes,cpu_percent\n\
+            1700000000,3.00,15360,15.00\n\
+            1700000060,5.00,61440,30.00\n"
+        );
+        assert!(store
+            .metrics_between(from_unix(start), from_unix(start), Duration::ZERO)
+            .is_err());
+    }
+}
@@ -0,0 +1,355 @@
+//! Moves a world from one host to another over SSH.
+//!
+//! [migrate] saves and stops the world on the source, archives its saves and settings,
+//! transfers the archive over SFTP, installs and starts the server on the destination and
+//! checks it answers over RCON with the source's password. The source is stopped but left
+//! installed, if anything fails it is started again. The returned [Migration] lists what is
+//! left to do by hand for the cutover.
+//!
+//! # Example:
+//! ```no_run
+//! use palworld_server::migrate;
+//! use palworld_server::ssh::PalworldConnection;
+//! use palworld_server::world::WorldProfile;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let old = PalworldConnection::new("old.palworld.lan:22", "root", "MySSHPassword");
+//!     let new = PalworldConnection::new("203.0.113.10:22", "root", "MySSHPassword");
+//!     let migration = migrate::migrate(&old, &new, &WorldProfile::new("main"))
+//!         .await
+//!         .unwrap();
+//!     for step in &migration.checklist {
+//!         println!("[ ] {step}");
+//!     }
+//! }
+//! ```
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+
+use crate::config::WorldSettings;
+use crate::progress::Reporter;
+use crate::provision;
+use crate::rcon::PalworldRCON;
+use crate::ssh::{change_privileged, shell_quote, PalworldConnection, ServiceAction};
+use crate::verify::StartupVerification;
+use crate::world::WorldProfile;
+
+/// Default time the destination gets to answer over RCON after starting.
+pub const DEFAULT_MIGRATION_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Options of [migrate_with].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct MigrationOptions {
+    /// Paths, service and ports on the destination, the source world's if None.
+    pub destination_world: Option<WorldProfile>,
+    /// Install the server on the destination first with [provision::install], off when it
+    /// is already installed.
+    pub install: bool,
+    /// Download the archive here and upload it from there, keeping a local copy. The archive
+    /// is streamed from host to host if None.
+    pub local_copy: Option<PathBuf>,
+    /// Directory on both hosts the archive is written to.
+    pub staging_dir: String,
+    /// Time the destination gets to answer over RCON before the migration is undone.
+    pub timeout: Duration,
+}
+
+impl MigrationOptions {
+    pub fn new() -> Self {
+        Self {
+            destination_world: None,
+            install: true,
+            local_copy: None,
+            staging_dir: "/tmp".to_string(),
+            timeout: DEFAULT_MIGRATION_TIMEOUT,
+        }
+    }
+}
+
+impl Default for MigrationOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A finished migration.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct Migration {
+    /// Archive of the saves and settings, left on the source.
+    pub archive: String,
+    /// Size of the transferred archive.
+    pub bytes: u64,
+    /// Version the destination answered with, empty in a dry run.
+    pub server_version: String,
+    /// Steps left to do by hand.
+    pub checklist: Vec<String>,
+}
+
+/// [migrate_with] with the default [MigrationOptions].
+pub async fn migrate(
+    source: &PalworldConnection,
+    destination: &PalworldConnection,
+    world: &WorldProfile,
+) -> Result<Migration> {
+    migrate_with(source, destination, world, &MigrationOptions::new()).await
+}
+
+/// Moves `world` from `source` to `destination`, see the [module documentation](self).
+pub async fn migrate_with(
+    source: &PalworldConnection,
+    destination: &PalworldConnection,
+    world: &WorldProfile,
+    options: &MigrationOptions,
+) -> Result<Migration> {
+    let target = options.destination_world.as_ref().unwrap_or(world);
+    let settings = world.read_settings(source).await?;
+    let password = settings
+        .get("AdminPassword")
+        .filter(|password| !password.is_empty())
+        .context("The source world has no AdminPassword, RCON is needed to verify the move")?
+        .to_string();
+
+    let steps = match options.install {
+        true => 7,
+        false => 6,
+    };
+    let mut progress = Reporter::new(source.progress.clone(), "migrate", steps);
+
+    // The last autosave may be minutes old.
+    progress.step(format!("Stopping {} on {}", world.service, source.hostname));
+    if source.dry_run.is_none() {
+        match PalworldRCON::via_ssh(source, world.rcon_port, password.as_str()).await {
+            Ok((rcon, _forward)) => {
+                if let Err(e) = rcon.save().await {
+                    log::warn!("Failed to save before stopping: {e}");
+                }
+            }
+            Err(e) => log::warn!("Failed to save before stopping: {e}"),
+        }
+    }
+    world.service(source, ServiceAction::Stop).await?;
+
+    match move_world(
+        source,
+        destination,
+        world,
+        target,
+        options,
+        settings,
+        &mut progress,
+    )
+    .await
+    {
+        Ok(migration) => Ok(migration),
+        Err(e) => {
+            log::error!("Migration failed, starting the source again: {e}");
+            if let Err(e) = target.service(destination, ServiceAction::Stop).await {
+                log::warn!("Failed to stop the destination: {e}");
+            }
+            world.service(source, ServiceAction::Start).await?;
+            Err(e.context("Migration failed, the source was started again"))
+        }
+    }
+}
+
+async fn move_world(
+    source: &PalworldConnection,
+    destination: &PalworldConnection,
+    world: &WorldProfile,
+    target: &WorldProfile,
+    options: &MigrationOptions,
+    mut settings: WorldSettings,
+    progress: &mut Reporter,
+) -> Result<Migration> {
+    let password = settings
+        .get("AdminPassword")
+        .unwrap_or_default()
+        .to_string();
+    let archive = archive_path(&options.staging_dir, &world.name);
+    progress.step(format!("Archiving {} to {archive}", world.saved_dir));
+    change_privileged(
+        source,
+        world.sudo,
+        &format!(
+            "mkdir -p {staging} && tar -czf {archive} -C {saved} SaveGames Config",
+            staging = shell_quote(&options.staging_dir),
+            archive = shell_quote(&archive),
+            saved = shell_quote(&world.saved_dir),
+        ),
+    )
+    .await?;
+
+    progress.step(format!(
+        "Transferring {archive} to {}",
+        destination.hostname
+    ));
+    let (source, destination) = (
+        with_transfer_progress(source, progress),
+        with_transfer_progress(destination, progress),
+    );
+    let (source, destination) = (&source, &destination);
+    let bytes = match &options.local_copy {
+        Some(local) => {
+            source.download(&archive, local).await?;
+            destination.upload(local, &archive).await?
+        }
+        None => source.copy_to(&archive, destination, &archive).await?,
+    };
+    log::info!("Transferred {bytes} bytes");
+
+    if options.install {
+        progress.step(format!("Installing the server on {}", destination.hostname));
+        let mut install = target.install_options();
+        install.rcon_password = Some(password.clone().into());
+        provision::install(destination, &install).await?;
+    }
+    target.service(destination, ServiceAction::Stop).await?;
+    progress.step(format!("Restoring {archive} to {}", target.saved_dir));
+    change_privileged(
+        destination,
+        target.sudo,
+        &format!(
+            "mkdir -p {saved} && tar -xzf {archive} -C {saved} && \
+            chown -R --reference={install_dir} {saved} && rm -f {archive}",
+            saved = shell_quote(&target.saved_dir),
+            archive = shell_quote(&archive),
+            install_dir = shell_quote(&target.install_dir),
+        ),
+    )
+    .await?;
+    // The restored settings carry the source's ports.
+    if target != world {
+        settings.set("RCONPort", target.rcon_port.to_string());
+        settings.set("PublicPort", target.game_port.to_string());
+        target.write_settings(destination, &settings).await?;
+    }
+
+    progress.step(format!(
+        "Starting {} on {}",
+        target.service, destination.hostname
+    ));
+    target.service(destination, ServiceAction::Start).await?;
+    progress.step("Waiting for the destination to answer over RCON");
+    let server_version = match destination.dry_run {
+        Some(_) => String::new(),
+        None => {
+            let (rcon, _forward) =
+                PalworldRCON::via_ssh(destination, target.rcon_port, password).await?;
+            StartupVerification::new(options.timeout)
+                .run(&rcon)
+                .await
+                .context("Destination not up")?
+                .version
+        }
+    };
+    log::info!("Destination is up, {server_version}");
+
+    Ok(Migration {
+        checklist: checklist(source, destination, world, target, &archive),
+        archive,
+        bytes,
+        server_version,
+    })
+}
+
+/// `connection` reporting the bytes of its transfers to the current step of `progress`, as
+/// well as to its own [crate::ssh::TransferOptions::progress].
+fn with_transfer_progress(
+    connection: &PalworldConnection,
+    progress: &Reporter,
+) -> PalworldConnection {
+    let mut connection = connection.clone();
+    if let Some(reporter) = progress.transfer_progress() {
+        connection.transfer.progress = Some(match connection.transfer.progress.take() {
+            Some(own) => Arc::new(move |bytes, total_bytes| {
+                own(bytes, total_bytes);
+                reporter(bytes, total_bytes);
+            }),
+            None => reporter,
+        });
+    }
+    connection
+}
+
+fn archive_path(staging_dir: &str, world: &str) -> String {
+    format!(
+        "{}/palworld-migrate-{world}.tar.gz",
+        staging_dir.trim_end_matches('/')
+    )
+}
+
+/// Cutover steps that can't be done over SSH.
+fn checklist(
+    source: &PalworldConnection,
+    destination: &PalworldConnection,
+    world: &WorldProfile,
+    target: &WorldProfile,
+    archive: &str,
+) -> Vec<String> {
+    let host = |connection: &PalworldConnection| {
+        connection
+            .hostname
+            .rsplit_once(':')
+            .map_or(connection.hostname.clone(), |(host, _)| host.to_string())
+    };
+    let (source, destination) = (host(source), host(destination));
+    vec![
+        format!(
+            "Point DNS and the server list at {destination}, players connect to UDP port {}",
+            target.game_port
+        ),
+        format!(
+            "Disable {} on {source} so it doesn't start again on reboot",
+            world.service
+        ),
+        format!("Update world profiles, monitors and bots to {destination}"),
+        format!("Join the world on {destination} and check bases and players are there"),
+        format!("Keep {archive} on {source} until players confirm their progress"),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checklist() {
+        assert_eq!(
+            archive_path("/tmp/", "main"),
+            "/tmp/palworld-migrate-main.tar.gz"
+        );
+        let source = PalworldConnection::new("old.palworld.lan:22", "root", "");
+        let destination = PalworldConnection::new("203.0.113.10:2222", "root", "");
+        let world = WorldProfile::new("main");
+        let mut target = world.clone();
+        target.game_port = 8212;
+        let checklist = checklist(
+            &source,
+            &destination,
+            &world,
+            &target,
+            "/tmp/palworld-migrate-main.tar.gz",
+        );
+        assert_eq!(
+            checklist[0],
+            "Point DNS and the server list at 203.0.113.10, players connect to UDP port 8212"
+        );
+        assert_eq!(
+            checklist[1],
+            "Disable palworld on old.palworld.lan so it doesn't start again on reboot"
+        );
+        assert!(checklist
+            .last()
+            .unwrap()
+            .starts_with("Keep /tmp/palworld-migrate-main.tar.gz on old.palworld.lan"));
+    }
+}
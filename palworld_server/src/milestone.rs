@@ -0,0 +1,188 @@
+//! Playtime milestones announced in game and published as events for reward systems.
+//!
+//! # Example:
+//! ```no_run
+//! use std::sync::Arc;
+//!
+//! use palworld_server::events::EventBus;
+//! use palworld_server::milestone::MilestonePlugin;
+//! use palworld_server::plugin::{PluginContext, PluginManager};
+//! use palworld_server::rcon::{PalworldRCON, DEFAULT_SOURCE_PORT};
+//! use palworld_server::store::SessionStore;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let rcon = PalworldRCON::new("localhost", DEFAULT_SOURCE_PORT, "MyRCONPassword");
+//!     let bus = EventBus::default();
+//!     let store = Arc::new(SessionStore::open("sessions.db").unwrap());
+//!     store.clone().spawn_recorder(&bus);
+//!     let mut manager = PluginManager::new(PluginContext::new(rcon, bus));
+//!     manager.register(MilestonePlugin::new(store));
+//!     manager.run(async { tokio::signal::ctrl_c().await.unwrap() }).await;
+//! }
+//! ```
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use crate::events::Event;
+use crate::models::PlayerInfo;
+use crate::plugin::{Plugin, PluginContext};
+use crate::store::SessionStore;
+
+/// Default announcement, `{name}` and `{hours}` are replaced.
+pub static DEFAULT_MILESTONE_TEMPLATE: &str = "{name} has played {hours} hours, thank you!";
+
+/// A playtime milestone.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct Milestone {
+    pub playtime: Duration,
+    /// Broadcast when reached, see [DEFAULT_MILESTONE_TEMPLATE].
+    pub template: String,
+}
+
+impl Milestone {
+    pub fn hours(hours: u64) -> Self {
+        Self {
+            playtime: Duration::from_secs(hours * 3600),
+            template: DEFAULT_MILESTONE_TEMPLATE.to_string(),
+        }
+    }
+
+    /// The broadcast for `player`.
+    pub fn render(&self, player: &PlayerInfo) -> String {
+        self.template
+            .replace("{name}", &player.name)
+            .replace("{hours}", &(self.playtime.as_secs() / 3600).to_string())
+    }
+}
+
+/// 10, 50 and 100 hours.
+pub fn default_milestones() -> Vec<Milestone> {
+    vec![
+        Milestone::hours(10),
+        Milestone::hours(50),
+        Milestone::hours(100),
+    ]
+}
+
+/// Checks the playtime of online players on every tick, broadcasts milestones they
+/// reached and publishes [Event::PlaytimeMilestone]. Each milestone is only announced once,
+/// a milestone whose broadcast failed is tried again on the next tick.
+#[derive(Debug)]
+pub struct MilestonePlugin {
+    store: Arc<SessionStore>,
+    pub milestones: Vec<Milestone>,
+    /// Space replacement passed to [crate::rcon::PalworldRCON::broadcast].
+    pub replace_space: Option<String>,
+}
+
+impl MilestonePlugin {
+    pub fn new(store: Arc<SessionStore>) -> Self {
+        Self {
+            store,
+            milestones: default_milestones(),
+            replace_space: None,
+        }
+    }
+
+    /// The milestones `player` reached by `now` that weren't announced yet, see
+    /// [MilestonePlugin::record].
+    pub fn check(&self, player: &PlayerInfo, now: SystemTime) -> Result<Vec<&Milestone>> {
+        let playtime = self.store.playtime(&player.steamid, now)?;
+        let mut reached = Vec::new();
+        for milestone in self.milestones.iter().filter(|m| m.playtime <= playtime) {
+            if !self
+                .store
+                .milestone_reached(&player.steamid, milestone.playtime)?
+            {
+                reached.push(milestone);
+            }
+        }
+        Ok(reached)
+    }
+
+    /// Marks `milestone` as announced for `player`.
+    pub fn record(
+        &self,
+        player: &PlayerInfo,
+        milestone: &Milestone,
+        now: SystemTime,
+    ) -> Result<()> {
+        self.store
+            .record_milestone(&player.steamid, milestone.playtime, now)?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Plugin for MilestonePlugin {
+    fn name(&self) -> &str {
+        "milestones"
+    }
+
+    async fn on_tick(&mut self, ctx: &PluginContext) -> Result<()> {
+        let now = SystemTime::now();
+        for player in ctx.rcon.get_player_info().await? {
+            for milestone in self.check(&player, now)? {
+                let broadcast = ctx
+                    .rcon
+                    .broadcast(milestone.render(&player), self.replace_space.clone())
+                    .await;
+                if let Err(e) = broadcast {
+                    log::warn!("Failed to announce a milestone of {}: {e:#}", player.name);
+                    continue;
+                }
+                self.record(&player, milestone, now)?;
+                ctx.events
+                    .publish_wait(Event::PlaytimeMilestone {
+                        player: player.clone(),
+                        playtime: milestone.playtime,
+                    })
+                    .await;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let store = Arc::new(SessionStore::open_in_memory().unwrap());
+        let plugin = MilestonePlugin::new(store.clone());
+        let alice = PlayerInfo {
+            name: "Alice".to_string(),
+            uid: "1".to_string(),
+            steamid: "76561190000000001".to_string(),
+        };
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let hour = Duration::from_secs(3600);
+        store.record_join(&alice, start).unwrap();
+
+        assert!(plugin.check(&alice, start + hour * 9).unwrap().is_empty());
+        let reached = plugin.check(&alice, start + hour * 51).unwrap();
+        assert_eq!(reached, vec![&Milestone::hours(10), &Milestone::hours(50)]);
+        assert_eq!(
+            reached[0].render(&alice),
+            "Alice has played 10 hours, thank you!"
+        );
+        // Not announced yet, so still pending.
+        assert_eq!(plugin.check(&alice, start + hour * 51).unwrap().len(), 2);
+        for milestone in [Milestone::hours(10), Milestone::hours(50)] {
+            plugin
+                .record(&alice, &milestone, start + hour * 51)
+                .unwrap();
+        }
+        // Already announced.
+        assert!(plugin.check(&alice, start + hour * 52).unwrap().is_empty());
+    }
+}
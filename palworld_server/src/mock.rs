@@ -0,0 +1,117 @@
+//! A local RCON server for tests.
+
+use std::sync::{Arc, Mutex};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+const SERVERDATA_AUTH: i32 = 3;
+const SERVERDATA_AUTH_RESPONSE: i32 = 2;
+const SERVERDATA_RESPONSE_VALUE: i32 = 0;
+
+/// The raw response to a command, None never answers it.
+pub type Handler = dyn Fn(&str) -> Option<Vec<u8>> + Send + Sync;
+
+/// Speaks RCON on a local port, answering logins with `password` and commands with `handler`.
+pub struct MockRcon {
+    pub port: u16,
+    /// Every command received with the number of the connection it came on, in order,
+    /// without the empty ones ending a response.
+    received: Arc<Mutex<Vec<(usize, String)>>>,
+}
+
+impl MockRcon {
+    pub async fn start(
+        password: &str,
+        handler: impl Fn(&str) -> Option<String> + Send + Sync + 'static,
+    ) -> Self {
+        Self::start_raw(password, move |command| {
+            handler(command).map(String::into_bytes)
+        })
+        .await
+    }
+
+    /// [MockRcon::start] with a handler answering raw bytes, for responses that aren't UTF-8.
+    pub async fn start_raw(
+        password: &str,
+        handler: impl Fn(&str) -> Option<Vec<u8>> + Send + Sync + 'static,
+    ) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let handler: Arc<Handler> = Arc::new(handler);
+        let password = password.to_string();
+        let commands = received.clone();
+        tokio::spawn(async move {
+            let mut connection = 0;
+            while let Ok((stream, _)) = listener.accept().await {
+                let handler = handler.clone();
+                let commands = commands.clone();
+                let password = password.clone();
+                tokio::spawn(async move {
+                    let _ = serve(stream, connection, &password, &*handler, &commands).await;
+                });
+                connection += 1;
+            }
+        });
+        Self { port, received }
+    }
+
+    /// Commands received so far.
+    pub fn commands(&self) -> Vec<String> {
+        let received = self.received.lock().unwrap();
+        received.iter().map(|(_, command)| command.clone()).collect()
+    }
+
+    /// Commands received so far with the number of the connection each came on, counted
+    /// from 0 in the order they were accepted.
+    pub fn commands_by_connection(&self) -> Vec<(usize, String)> {
+        self.received.lock().unwrap().clone()
+    }
+}
+
+async fn serve(
+    mut stream: TcpStream,
+    connection: usize,
+    password: &str,
+    handler: &Handler,
+    received: &Mutex<Vec<(usize, String)>>,
+) -> std::io::Result<()> {
+    loop {
+        let length = stream.read_i32_le().await?;
+        let id = stream.read_i32_le().await?;
+        let packet_type = stream.read_i32_le().await?;
+        let mut body = vec![0u8; (length - 8).max(0) as usize];
+        stream.read_exact(&mut body).await?;
+        let body = String::from_utf8_lossy(&body[..body.len().saturating_sub(2)]).to_string();
+        let response = match packet_type {
+            SERVERDATA_AUTH => {
+                let id = match body == password {
+                    true => id,
+                    false => -1,
+                };
+                packet(id, SERVERDATA_AUTH_RESPONSE, b"")
+            }
+            // Ends a response split over packets.
+            _ if body.is_empty() => packet(id, SERVERDATA_RESPONSE_VALUE, b""),
+            _ => {
+                received.lock().unwrap().push((connection, body.clone()));
+                match handler(&body) {
+                    Some(response) => packet(id, SERVERDATA_RESPONSE_VALUE, &response),
+                    None => continue,
+                }
+            }
+        };
+        stream.write_all(&response).await?;
+    }
+}
+
+fn packet(id: i32, packet_type: i32, body: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(14 + body.len());
+    packet.extend_from_slice(&(10 + body.len() as i32).to_le_bytes());
+    packet.extend_from_slice(&id.to_le_bytes());
+    packet.extend_from_slice(&packet_type.to_le_bytes());
+    packet.extend_from_slice(body);
+    packet.extend_from_slice(&[0, 0]);
+    packet
+}
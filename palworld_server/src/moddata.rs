@@ -0,0 +1,711 @@
+//! Import and export of bans, warnings and whitelist entries.
+//!
+//! [ModerationData] reads and writes the moderation records of the [SessionStore] as JSON or
+//! CSV, so communities moving from other tools keep their history. Timestamps are written as
+//! RFC 3339, imports also accept unix seconds, `steam_` prefixed IDs and `steamId` or
+//! `steam_id` fields. [ModerationData::import] merges records into the store following a
+//! [MergeStrategy].
+//!
+//! CSV files hold every record in one table, told apart by the `kind` column:
+//! `ban`, `warning` or `whitelist`. Columns a kind doesn't use are left empty.
+//!
+//! # Example:
+//! ```
+//! use palworld_server::moddata::{Format, MergeStrategy, ModerationData};
+//! use palworld_server::store::SessionStore;
+//!
+//! let csv = "kind,steamid,name,reason,time,expires_at,lifted_at,issued_by,action
+//! ban,steam_76561190000000001,,Griefing,2024-01-01T00:00:00Z,,,,
+//! whitelist,76561190000000002,Alice,,1704067200,,,,";
+//! let data = ModerationData::read(Format::Csv, csv.as_bytes()).unwrap();
+//! assert_eq!(data.bans[0].steamid, "76561190000000001");
+//!
+//! let store = SessionStore::open_in_memory().unwrap();
+//! let summary = data.import(&store, MergeStrategy::Union).unwrap();
+//! assert_eq!(summary.added, 2);
+//! ModerationData::from_store(&store).unwrap().write(Format::Json, std::io::stdout()).unwrap();
+//! ```
+
+use std::fmt;
+use std::io::{Read, Write};
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use anyhow::{bail, Context, Result};
+
+use crate::moderation::{BanRecord, Strike, StrikeAction, WhitelistEntry};
+use crate::store::{from_unix, SessionStore};
+
+/// File format of moderation data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Format {
+    Json,
+    Csv,
+}
+
+impl Format {
+    /// Format of a file by its extension, None for other extensions.
+    pub fn from_path(path: impl AsRef<std::path::Path>) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?;
+        extension.parse().ok()
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Json => write!(f, "json"),
+            Self::Csv => write!(f, "csv"),
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "csv" => Ok(Self::Csv),
+            _ => bail!("Unknown format '{s}', expected json or csv"),
+        }
+    }
+}
+
+/// How [ModerationData::import] resolves an imported record conflicting with one in the
+/// store: a ban of the same player at the same time, a warning of the same player at the
+/// same time, or a whitelist entry of the same player. Records without a conflict are
+/// always added and exact duplicates skipped.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum MergeStrategy {
+    /// Keep the record in the store.
+    Ours,
+    /// Replace the record in the store with the imported one.
+    Theirs,
+    /// Keep both bans or warnings. A player has a single whitelist entry, the one in the
+    /// store is kept.
+    #[default]
+    Union,
+}
+
+impl fmt::Display for MergeStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ours => write!(f, "ours"),
+            Self::Theirs => write!(f, "theirs"),
+            Self::Union => write!(f, "union"),
+        }
+    }
+}
+
+impl FromStr for MergeStrategy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "ours" => Ok(Self::Ours),
+            "theirs" => Ok(Self::Theirs),
+            "union" => Ok(Self::Union),
+            _ => bail!("Unknown merge strategy '{s}', expected ours, theirs or union"),
+        }
+    }
+}
+
+/// Records changed by [ModerationData::merge].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct MergeSummary {
+    pub added: usize,
+    pub replaced: usize,
+    /// Duplicates and conflicting records that weren't imported.
+    pub skipped: usize,
+}
+
+/// Bans, warnings and whitelist entries, see the [module documentation](self).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ModerationData {
+    pub bans: Vec<BanRecord>,
+    pub warnings: Vec<Strike>,
+    pub whitelist: Vec<WhitelistEntry>,
+}
+
+impl ModerationData {
+    /// Every moderation record of `store`.
+    pub fn from_store(store: &SessionStore) -> Result<Self> {
+        Ok(Self {
+            bans: store.bans()?,
+            warnings: store.all_strikes()?,
+            whitelist: store.whitelist()?,
+        })
+    }
+
+    /// Merges `theirs` into these records.
+    pub fn merge(&mut self, theirs: Self, strategy: MergeStrategy) -> MergeSummary {
+        let mut summary = MergeSummary::default();
+        merge_records(
+            &mut self.bans,
+            theirs.bans,
+            |ban| (ban.steamid.clone(), ban.banned_at),
+            strategy,
+            true,
+            &mut summary,
+        );
+        merge_records(
+            &mut self.warnings,
+            theirs.warnings,
+            |strike| (strike.steamid.clone(), strike.issued_at),
+            strategy,
+            true,
+            &mut summary,
+        );
+        merge_records(
+            &mut self.whitelist,
+            theirs.whitelist,
+            |entry| entry.steamid.clone(),
+            strategy,
+            false,
+            &mut summary,
+        );
+        summary
+    }
+
+    /// Merges these records into `store`.
+    pub fn import(self, store: &SessionStore, strategy: MergeStrategy) -> Result<MergeSummary> {
+        let mut ours = Self::from_store(store)?;
+        let summary = ours.merge(self, strategy);
+        store.replace_moderation(&ours.bans, &ours.warnings, &ours.whitelist)?;
+        log::info!(
+            "Imported moderation data ({strategy}): {} added, {} replaced, {} skipped",
+            summary.added,
+            summary.replaced,
+            summary.skipped
+        );
+        Ok(summary)
+    }
+
+    pub fn read(format: Format, mut reader: impl Read) -> Result<Self> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        match format {
+            Format::Json => {
+                let file: JsonFile = serde_json::from_str(&text)?;
+                file.try_into()
+            }
+            Format::Csv => parse_csv(&text),
+        }
+    }
+
+    pub fn write(&self, format: Format, mut writer: impl Write) -> Result<()> {
+        match format {
+            Format::Json => {
+                serde_json::to_writer_pretty(&mut writer, &JsonFile::from(self))?;
+                writeln!(writer)?;
+            }
+            Format::Csv => {
+                writeln!(writer, "{}", CSV_HEADER.join(","))?;
+                for ban in &self.bans {
+                    write_csv_row(
+                        &mut writer,
+                        [
+                            "ban",
+                            &ban.steamid,
+                            "",
+                            &ban.reason,
+                            &format_time(ban.banned_at),
+                            &ban.expires_at.map(format_time).unwrap_or_default(),
+                            &ban.lifted_at.map(format_time).unwrap_or_default(),
+                            "",
+                            "",
+                        ],
+                    )?;
+                }
+                for strike in &self.warnings {
+                    write_csv_row(
+                        &mut writer,
+                        [
+                            "warning",
+                            &strike.steamid,
+                            "",
+                            &strike.reason,
+                            &format_time(strike.issued_at),
+                            "",
+                            "",
+                            &strike.issued_by,
+                            &strike.action.to_string(),
+                        ],
+                    )?;
+                }
+                for entry in &self.whitelist {
+                    write_csv_row(
+                        &mut writer,
+                        [
+                            "whitelist",
+                            &entry.steamid,
+                            entry.name.as_deref().unwrap_or_default(),
+                            "",
+                            &format_time(entry.added_at),
+                            "",
+                            "",
+                            "",
+                            "",
+                        ],
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn merge_records<T: PartialEq, K: PartialEq>(
+    ours: &mut Vec<T>,
+    theirs: Vec<T>,
+    key: impl Fn(&T) -> K,
+    strategy: MergeStrategy,
+    keep_both: bool,
+    summary: &mut MergeSummary,
+) {
+    for record in theirs {
+        if ours.contains(&record) {
+            summary.skipped += 1;
+            continue;
+        }
+        let Some(index) = ours.iter().position(|our| key(our) == key(&record)) else {
+            ours.push(record);
+            summary.added += 1;
+            continue;
+        };
+        match (strategy, keep_both) {
+            (MergeStrategy::Theirs, _) => {
+                ours[index] = record;
+                summary.replaced += 1;
+            }
+            (MergeStrategy::Union, true) => {
+                ours.push(record);
+                summary.added += 1;
+            }
+            _ => summary.skipped += 1,
+        }
+    }
+}
+
+/// SteamID without the `steam_` prefix of Palworld's ban list.
+fn normalize_steamid(steamid: &str) -> Result<String> {
+    let steamid = steamid.trim();
+    let steamid = steamid.strip_prefix("steam_").unwrap_or(steamid);
+    if steamid.is_empty() {
+        bail!("Missing SteamID");
+    }
+    Ok(steamid.to_string())
+}
+
+fn format_time(time: SystemTime) -> String {
+    humantime::format_rfc3339_seconds(time).to_string()
+}
+
+/// RFC 3339 or unix seconds.
+fn parse_time(value: &str) -> Result<SystemTime> {
+    let value = value.trim();
+    match value.parse::<i64>() {
+        Ok(secs) => Ok(from_unix(secs)),
+        Err(_) => {
+            humantime::parse_rfc3339_weak(value).with_context(|| format!("Invalid time '{value}'"))
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+enum JsonTime {
+    Unix(i64),
+    Text(String),
+}
+
+impl JsonTime {
+    fn parse(&self) -> Result<SystemTime> {
+        match self {
+            Self::Unix(secs) => Ok(from_unix(*secs)),
+            Self::Text(text) => parse_time(text),
+        }
+    }
+}
+
+impl From<SystemTime> for JsonTime {
+    fn from(time: SystemTime) -> Self {
+        Self::Text(format_time(time))
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct JsonBan {
+    #[serde(alias = "steamId", alias = "steam_id")]
+    steamid: String,
+    #[serde(default)]
+    reason: String,
+    #[serde(alias = "bannedAt")]
+    banned_at: JsonTime,
+    #[serde(default, alias = "expiresAt")]
+    expires_at: Option<JsonTime>,
+    #[serde(default, alias = "liftedAt")]
+    lifted_at: Option<JsonTime>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct JsonWarning {
+    #[serde(alias = "steamId", alias = "steam_id")]
+    steamid: String,
+    #[serde(default)]
+    reason: String,
+    #[serde(alias = "issuedAt")]
+    issued_at: JsonTime,
+    #[serde(default, alias = "issuedBy")]
+    issued_by: Option<String>,
+    #[serde(default)]
+    action: Option<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct JsonWhitelistEntry {
+    #[serde(alias = "steamId", alias = "steam_id")]
+    steamid: String,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default, alias = "addedAt")]
+    added_at: Option<JsonTime>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct JsonFile {
+    #[serde(default)]
+    bans: Vec<JsonBan>,
+    #[serde(default)]
+    warnings: Vec<JsonWarning>,
+    #[serde(default)]
+    whitelist: Vec<JsonWhitelistEntry>,
+}
+
+impl From<&ModerationData> for JsonFile {
+    fn from(data: &ModerationData) -> Self {
+        Self {
+            bans: data
+                .bans
+                .iter()
+                .map(|ban| JsonBan {
+                    steamid: ban.steamid.clone(),
+                    reason: ban.reason.clone(),
+                    banned_at: ban.banned_at.into(),
+                    expires_at: ban.expires_at.map(JsonTime::from),
+                    lifted_at: ban.lifted_at.map(JsonTime::from),
+                })
+                .collect(),
+            warnings: data
+                .warnings
+                .iter()
+                .map(|strike| JsonWarning {
+                    steamid: strike.steamid.clone(),
+                    reason: strike.reason.clone(),
+                    issued_at: strike.issued_at.into(),
+                    issued_by: Some(strike.issued_by.clone()),
+                    action: Some(strike.action.to_string()),
+                })
+                .collect(),
+            whitelist: data
+                .whitelist
+                .iter()
+                .map(|entry| JsonWhitelistEntry {
+                    steamid: entry.steamid.clone(),
+                    name: entry.name.clone(),
+                    added_at: Some(entry.added_at.into()),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<JsonFile> for ModerationData {
+    type Error = anyhow::Error;
+
+    fn try_from(file: JsonFile) -> Result<Self> {
+        let now = SystemTime::now();
+        let bans = file
+            .bans
+            .into_iter()
+            .map(|ban| {
+                Ok(BanRecord {
+                    steamid: normalize_steamid(&ban.steamid)?,
+                    reason: ban.reason,
+                    banned_at: ban.banned_at.parse()?,
+                    expires_at: ban.expires_at.map(|time| time.parse()).transpose()?,
+                    lifted_at: ban.lifted_at.map(|time| time.parse()).transpose()?,
+                })
+            })
+            .collect::<Result<_>>()?;
+        let warnings = file
+            .warnings
+            .into_iter()
+            .map(|warning| {
+                Ok(Strike {
+                    steamid: normalize_steamid(&warning.steamid)?,
+                    reason: warning.reason,
+                    issued_at: warning.issued_at.parse()?,
+                    issued_by: warning.issued_by.unwrap_or(IMPORTED_BY.to_string()),
+                    action: parse_action(warning.action.as_deref())?,
+                })
+            })
+            .collect::<Result<_>>()?;
+        let whitelist = file
+            .whitelist
+            .into_iter()
+            .map(|entry| {
+                Ok(WhitelistEntry {
+                    steamid: normalize_steamid(&entry.steamid)?,
+                    name: entry.name,
+                    added_at: entry.added_at.map_or(Ok(now), |time| time.parse())?,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            bans,
+            warnings,
+            whitelist,
+        })
+    }
+}
+
+/// Moderator of imported warnings that don't name one.
+const IMPORTED_BY: &str = "import";
+
+fn parse_action(action: Option<&str>) -> Result<StrikeAction> {
+    match action.map(str::trim).filter(|action| !action.is_empty()) {
+        Some(action) => action.to_lowercase().parse(),
+        None => Ok(StrikeAction::Warned),
+    }
+}
+
+const CSV_HEADER: [&str; 9] = [
+    "kind",
+    "steamid",
+    "name",
+    "reason",
+    "time",
+    "expires_at",
+    "lifted_at",
+    "issued_by",
+    "action",
+];
+
+fn write_csv_row<const N: usize>(writer: &mut impl Write, fields: [&str; N]) -> Result<()> {
+    let fields: Vec<String> = fields.iter().map(|field| csv_escape(field)).collect();
+    writeln!(writer, "{}", fields.join(","))?;
+    Ok(())
+}
+
+fn csv_escape(value: &str) -> String {
+    match value.contains([',', '"', '\n']) {
+        true => format!("\"{}\"", value.replace('"', "\"\"")),
+        false => value.to_string(),
+    }
+}
+
+/// Fields of a CSV line, quoted fields may contain commas and `""` escaped quotes.
+fn split_csv_line(line: &str) -> Result<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            ('"', true) => quoted = false,
+            ('"', false) if field.is_empty() => quoted = true,
+            (',', false) => fields.push(std::mem::take(&mut field)),
+            (c, _) => field.push(c),
+        }
+    }
+    if quoted {
+        bail!("Unterminated quote");
+    }
+    fields.push(field);
+    Ok(fields)
+}
+
+fn parse_csv(text: &str) -> Result<ModerationData> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty());
+    let Some((_, header)) = lines.next() else {
+        return Ok(ModerationData::default());
+    };
+    let header: Vec<String> = split_csv_line(header)?
+        .into_iter()
+        .map(|column| column.trim().to_lowercase())
+        .collect();
+    let column = |name: &str| header.iter().position(|column| column == name);
+    let (Some(kind), Some(steamid)) = (column("kind"), column("steamid")) else {
+        bail!("CSV header needs kind and steamid columns");
+    };
+
+    let now = SystemTime::now();
+    let mut data = ModerationData::default();
+    for (index, line) in lines {
+        let fields = split_csv_line(line).with_context(|| format!("Line {}", index + 1))?;
+        let get = |name: &str| {
+            column(name)
+                .and_then(|column| fields.get(column))
+                .map(|field| field.trim())
+                .filter(|field| !field.is_empty())
+        };
+        let time = |name: &str| get(name).map(parse_time).transpose();
+        let mut record = || -> Result<()> {
+            let steamid = normalize_steamid(fields.get(steamid).map_or("", String::as_str))?;
+            let reason = get("reason").unwrap_or_default().to_string();
+            match fields[kind].trim() {
+                "ban" => data.bans.push(BanRecord {
+                    steamid,
+                    reason,
+                    banned_at: time("time")?.context("Missing time of the ban")?,
+                    expires_at: time("expires_at")?,
+                    lifted_at: time("lifted_at")?,
+                }),
+                "warning" => data.warnings.push(Strike {
+                    steamid,
+                    reason,
+                    issued_at: time("time")?.context("Missing time of the warning")?,
+                    issued_by: get("issued_by").unwrap_or(IMPORTED_BY).to_string(),
+                    action: parse_action(get("action"))?,
+                }),
+                "whitelist" => data.whitelist.push(WhitelistEntry {
+                    steamid,
+                    name: get("name").map(str::to_string),
+                    added_at: time("time")?.unwrap_or(now),
+                }),
+                kind => bail!("Unknown kind '{kind}', expected ban, warning or whitelist"),
+            }
+            Ok(())
+        };
+        record().with_context(|| format!("Line {}", index + 1))?;
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::to_unix;
+
+    fn sample() -> ModerationData {
+        let start = from_unix(1_700_000_000);
+        ModerationData {
+            bans: vec![BanRecord {
+                steamid: "76561190000000001".to_string(),
+                reason: "Griefing, twice".to_string(),
+                banned_at: start,
+                expires_at: Some(from_unix(1_700_086_400)),
+                lifted_at: None,
+            }],
+            warnings: vec![Strike {
+                steamid: "76561190000000001".to_string(),
+                reason: "Said \"hi\" too often".to_string(),
+                issued_at: start,
+                issued_by: "palworldcli by root".to_string(),
+                action: StrikeAction::Kicked,
+            }],
+            whitelist: vec![WhitelistEntry {
+                steamid: "76561190000000002".to_string(),
+                name: Some("Alice".to_string()),
+                added_at: start,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for format in [Format::Json, Format::Csv] {
+            let mut written = Vec::new();
+            sample().write(format, &mut written).unwrap();
+            let read = ModerationData::read(format, written.as_slice()).unwrap();
+            assert_eq!(read, sample(), "{format}");
+        }
+        assert_eq!(Format::from_path("bans.CSV"), Some(Format::Csv));
+        assert_eq!(Format::from_path("bans.txt"), None);
+    }
+
+    #[test]
+    fn test_read_other_tools() {
+        let json = r#"{"bans": [{"steamId": "steam_76561190000000003", "bannedAt": 1700000000}],
+            "whitelist": [{"steam_id": "76561190000000004"}]}"#;
+        let data = ModerationData::read(Format::Json, json.as_bytes()).unwrap();
+        assert_eq!(data.bans[0].steamid, "76561190000000003");
+        assert_eq!(data.bans[0].banned_at, from_unix(1_700_000_000));
+        assert_eq!(data.bans[0].expires_at, None);
+        assert_eq!(data.whitelist[0].steamid, "76561190000000004");
+
+        let csv = "steamid,kind,time\n76561190000000005,warning,2023-11-14T22:13:20Z\n";
+        let data = ModerationData::read(Format::Csv, csv.as_bytes()).unwrap();
+        assert_eq!(data.warnings[0].issued_by, "import");
+        assert_eq!(data.warnings[0].action, StrikeAction::Warned);
+        assert_eq!(data.warnings[0].issued_at, from_unix(1_700_000_000));
+        let error = ModerationData::read(Format::Csv, "kind,steamid\nmute,7656".as_bytes());
+        assert_eq!(
+            format!("{:#}", error.unwrap_err()),
+            "Line 2: Unknown kind 'mute', expected ban, warning or whitelist"
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut theirs = sample();
+        theirs.bans[0].reason = "Griefing".to_string();
+        theirs.whitelist[0].name = None;
+        theirs.whitelist.push(WhitelistEntry {
+            steamid: "76561190000000003".to_string(),
+            name: None,
+            added_at: from_unix(1_700_000_000),
+        });
+
+        let mut ours = sample();
+        let summary = ours.merge(theirs.clone(), MergeStrategy::Ours);
+        assert_eq!(
+            (summary.added, summary.replaced, summary.skipped),
+            (1, 0, 3)
+        );
+        assert_eq!(ours.bans[0].reason, "Griefing, twice");
+
+        let mut ours = sample();
+        let summary = ours.merge(theirs.clone(), MergeStrategy::Theirs);
+        assert_eq!(
+            (summary.added, summary.replaced, summary.skipped),
+            (1, 2, 1)
+        );
+        assert_eq!(ours.bans[0].reason, "Griefing");
+        assert_eq!(ours.whitelist[0].name, None);
+
+        let mut ours = sample();
+        let summary = ours.merge(theirs, MergeStrategy::Union);
+        assert_eq!(
+            (summary.added, summary.replaced, summary.skipped),
+            (2, 0, 2)
+        );
+        assert_eq!(ours.bans.len(), 2);
+        assert_eq!(ours.whitelist.len(), 2);
+    }
+
+    #[test]
+    fn test_import() {
+        let store = SessionStore::open_in_memory().unwrap();
+        sample().import(&store, MergeStrategy::Union).unwrap();
+        let summary = sample().import(&store, MergeStrategy::Union).unwrap();
+        assert_eq!(summary.skipped, 3);
+        assert_eq!(ModerationData::from_store(&store).unwrap(), sample());
+        assert_eq!(to_unix(store.bans().unwrap()[0].banned_at), 1_700_000_000);
+    }
+}
@@ -0,0 +1,318 @@
+//! Transport-free data types shared by every backend.
+//!
+//! Nothing in here depends on tokio or a socket so it also builds for wasm32.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use anyhow::{bail, Result};
+
+use crate::parse;
+
+/// Representation of /showplayers rcon command
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct PlayerInfo {
+    /// Player's name.
+    pub name: String,
+    /// Player's Unique ID inside the server.
+    pub uid: String,
+    /// Player's Steam ID.
+    pub steamid: String,
+}
+
+impl fmt::Display for PlayerInfo {
+    /// Tab separated name, UID and Steam ID, like a line of the CLI's player list.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\t{}\t{}", self.name, self.uid, self.steamid)
+    }
+}
+
+/// A 64-bit Steam ID of a player account.
+///
+/// # Example:
+/// ```
+/// use palworld_server::models::SteamId;
+///
+/// let id: SteamId = "steam_76561190000000001".parse().unwrap();
+/// assert_eq!(id, SteamId(76561190000000001));
+/// assert_eq!(id.to_string(), "76561190000000001");
+/// assert!("7656".parse::<SteamId>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct SteamId(pub u64);
+
+impl FromStr for SteamId {
+    type Err = anyhow::Error;
+
+    /// Parses the 17 digits of an individual account, with or without the `steam_` prefix
+    /// the REST API adds.
+    fn from_str(s: &str) -> Result<Self> {
+        let digits = s.trim();
+        let digits = digits.strip_prefix("steam_").unwrap_or(digits);
+        if digits.len() != 17
+            || !digits.starts_with("7656119")
+            || !digits.bytes().all(|b| b.is_ascii_digit())
+        {
+            bail!("Invalid Steam ID '{s}'");
+        }
+        Ok(Self(digits.parse()?))
+    }
+}
+
+impl fmt::Display for SteamId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A server version like `v0.1.3.0`. Missing components count as 0 when comparing, so
+/// `v0.1.3` equals `v0.1.3.0`.
+///
+/// # Example:
+/// ```
+/// use palworld_server::models::ServerVersion;
+///
+/// let version: ServerVersion = "v0.1.5.1".parse().unwrap();
+/// assert!(version > "0.1.5".parse().unwrap());
+/// assert_eq!(version.to_string(), "v0.1.5.1");
+/// ```
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct ServerVersion(pub Vec<u32>);
+
+impl ServerVersion {
+    fn padded(&self, len: usize) -> Vec<u32> {
+        let mut numbers = self.0.clone();
+        numbers.resize(len.max(numbers.len()), 0);
+        numbers
+    }
+}
+
+impl FromStr for ServerVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(Self(parse::parse_version_numbers(s)?))
+    }
+}
+
+impl fmt::Display for ServerVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let numbers: Vec<String> = self.0.iter().map(u32::to_string).collect();
+        write!(f, "v{}", numbers.join("."))
+    }
+}
+
+impl Ord for ServerVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let len = self.0.len().max(other.0.len());
+        self.padded(len).cmp(&other.padded(len))
+    }
+}
+
+impl PartialOrd for ServerVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for ServerVersion {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for ServerVersion {}
+
+/// Version and name of a server from the response of the `info` command.
+///
+/// # Example:
+/// ```
+/// use palworld_server::models::ServerInfo;
+///
+/// let info: ServerInfo = "Welcome to Pal Server[v0.1.3.0] Default Palworld Server"
+///     .parse()
+///     .unwrap();
+/// assert_eq!(info.name, "Default Palworld Server");
+/// assert_eq!(info.to_string(), "Default Palworld Server v0.1.3.0");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct ServerInfo {
+    pub version: ServerVersion,
+    /// ServerName from the settings.
+    pub name: String,
+}
+
+impl FromStr for ServerInfo {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let version = parse::parse_version(s)?;
+        let name = s
+            .split_once(&format!("[{version}]"))
+            .map_or("", |(_, name)| name.trim());
+        Ok(Self {
+            version: version.parse()?,
+            name: name.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for ServerInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.name, self.version)
+    }
+}
+
+/// A size in bytes.
+///
+/// # Example:
+/// ```
+/// use palworld_server::models::ByteSize;
+///
+/// let size = ByteSize::from_kib(1536);
+/// assert_eq!(size.as_bytes(), 1536 * 1024);
+/// assert_eq!(size.as_mib(), 1.5);
+/// assert_eq!(size.to_string(), "1.50 MiB");
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct ByteSize(pub u64);
+
+impl ByteSize {
+    pub const KIB: u64 = 1024;
+    pub const MIB: u64 = 1024 * Self::KIB;
+    pub const GIB: u64 = 1024 * Self::MIB;
+
+    pub fn from_bytes(bytes: u64) -> Self {
+        Self(bytes)
+    }
+
+    pub fn from_kib(kib: u64) -> Self {
+        Self(kib.saturating_mul(Self::KIB))
+    }
+
+    pub fn as_bytes(&self) -> u64 {
+        self.0
+    }
+
+    pub fn as_kib(&self) -> f64 {
+        self.0 as f64 / Self::KIB as f64
+    }
+
+    pub fn as_mib(&self) -> f64 {
+        self.0 as f64 / Self::MIB as f64
+    }
+
+    pub fn as_gib(&self) -> f64 {
+        self.0 as f64 / Self::GIB as f64
+    }
+
+    pub fn checked_sub(&self, other: ByteSize) -> Option<ByteSize> {
+        self.0.checked_sub(other.0).map(ByteSize)
+    }
+}
+
+impl std::fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            x if x >= Self::GIB => write!(f, "{:.2} GiB", self.as_gib()),
+            x if x >= Self::MIB => write!(f, "{:.2} MiB", self.as_mib()),
+            x if x >= Self::KIB => write!(f, "{:.2} KiB", self.as_kib()),
+            x => write!(f, "{x} B"),
+        }
+    }
+}
+
+/// Disk usage of the filesystem containing a path.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct DiskUsage {
+    pub total: ByteSize,
+    pub used: ByteSize,
+    pub available: ByteSize,
+}
+
+impl DiskUsage {
+    /// Used space as a percentage from 0 to 100, None for an empty filesystem.
+    pub fn used_percent(&self) -> Option<f64> {
+        match self.total.as_bytes() {
+            0 => None,
+            total => Some(self.used.as_bytes() as f64 / total as f64 * 100.0),
+        }
+    }
+}
+
+/// A process running on the host.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub name: String,
+    /// Resident memory.
+    pub memory: ByteSize,
+}
+
+/// Transport protocol of a firewall rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+impl std::fmt::Display for Protocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Tcp => write!(f, "tcp"),
+            Self::Udp => write!(f, "udp"),
+        }
+    }
+}
+
+/// Whether a firewall rule lets traffic through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum RuleAction {
+    Allow,
+    Deny,
+}
+
+/// An inbound firewall rule for a single port.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct FirewallRule {
+    pub port: u16,
+    /// None matches both TCP and UDP.
+    pub protocol: Option<Protocol>,
+    pub action: RuleAction,
+    /// Source address or network, None matches anywhere.
+    pub from: Option<String>,
+}
+
+/// A broadcast sent to every player, kept so admins can see what was already announced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct BroadcastRecord {
+    /// The message as given, before spaces were replaced.
+    pub message: String,
+    /// What sent it, like `chat command 'vote' from Shadow`, see [crate::trace::Trigger].
+    pub initiator: String,
+    pub sent_at: SystemTime,
+}
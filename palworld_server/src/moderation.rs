@@ -0,0 +1,250 @@
+//! Warnings with strikes that escalate to kicks and bans.
+//!
+//! [ModerationLedger::warn] records a [Strike] in the [SessionStore], broadcasts a warning
+//! naming the player and, per the [EscalationPolicy], kicks or bans players that collected
+//! enough strikes. Palworld has no private messages, so the warning is a broadcast everyone
+//! sees. The ledger outlives restarts with the store, [SessionStore::strikes] is a
+//! player's history.
+//!
+//! # Example:
+//! ```no_run
+//! use std::sync::Arc;
+//!
+//! use palworld_server::moderation::ModerationLedger;
+//! use palworld_server::rcon::{PalworldRCON, DEFAULT_SOURCE_PORT};
+//! use palworld_server::store::SessionStore;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let rcon = PalworldRCON::new("localhost", DEFAULT_SOURCE_PORT, "MyRCONPassword");
+//!     let store = Arc::new(SessionStore::open("sessions.db").unwrap());
+//!     let mut ledger = ModerationLedger::new(rcon, store);
+//!     ledger.policy.kick_at = Some(2);
+//!     let warning = ledger.warn("76561190000000001", "Griefing bases").await.unwrap();
+//!     println!("Strike {}: {}", warning.count, warning.strike.action);
+//! }
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use anyhow::{bail, Result};
+
+use crate::rcon::PalworldRCON;
+use crate::store::SessionStore;
+use crate::trace;
+
+/// What a strike led to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum StrikeAction {
+    Warned,
+    Kicked,
+    Banned,
+}
+
+impl fmt::Display for StrikeAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Warned => write!(f, "warned"),
+            Self::Kicked => write!(f, "kicked"),
+            Self::Banned => write!(f, "banned"),
+        }
+    }
+}
+
+impl FromStr for StrikeAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "warned" => Ok(Self::Warned),
+            "kicked" => Ok(Self::Kicked),
+            "banned" => Ok(Self::Banned),
+            _ => bail!("Unknown strike action '{s}'"),
+        }
+    }
+}
+
+/// A warning given to a player.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct Strike {
+    pub steamid: String,
+    pub reason: String,
+    pub issued_at: SystemTime,
+    /// Trigger of the warning, like a chat command or CLI invocation, see [trace::Trigger].
+    pub issued_by: String,
+    pub action: StrikeAction,
+}
+
+/// Strike counts at which players are kicked and banned, None to never.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct EscalationPolicy {
+    pub kick_at: Option<usize>,
+    pub ban_at: Option<usize>,
+}
+
+impl EscalationPolicy {
+    /// Action for a player's `count`th strike, bans winning over kicks.
+    pub fn action(&self, count: usize) -> StrikeAction {
+        let reached = |at: Option<usize>| at.is_some_and(|at| count >= at);
+        match (reached(self.ban_at), reached(self.kick_at)) {
+            (true, _) => StrikeAction::Banned,
+            (false, true) => StrikeAction::Kicked,
+            (false, false) => StrikeAction::Warned,
+        }
+    }
+}
+
+impl Default for EscalationPolicy {
+    fn default() -> Self {
+        Self {
+            kick_at: Some(3),
+            ban_at: Some(5),
+        }
+    }
+}
+
+/// A ban kept in the store, by [crate::tempban] or imported from other tools.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct BanRecord {
+    pub steamid: String,
+    pub reason: String,
+    pub banned_at: SystemTime,
+    /// None for permanent bans.
+    pub expires_at: Option<SystemTime>,
+    /// When the ban was lifted, None while it's in force.
+    pub lifted_at: Option<SystemTime>,
+}
+
+/// A player allowed on a whitelisted server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct WhitelistEntry {
+    pub steamid: String,
+    pub name: Option<String>,
+    pub added_at: SystemTime,
+}
+
+/// A warning given with [ModerationLedger::warn].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub strike: Strike,
+    /// Strikes of the player including this one.
+    pub count: usize,
+    /// Name of the player, None if they weren't online.
+    pub name: Option<String>,
+    /// Whether the server confirmed the kick or ban, true for plain warnings.
+    pub enforced: bool,
+}
+
+/// Warns players and escalates, see the [module documentation](self).
+#[derive(Debug)]
+pub struct ModerationLedger {
+    rcon: PalworldRCON,
+    store: Arc<SessionStore>,
+    pub policy: EscalationPolicy,
+    /// Space replacement passed to [PalworldRCON::broadcast].
+    pub replace_space: Option<String>,
+}
+
+impl ModerationLedger {
+    pub fn new(rcon: PalworldRCON, store: Arc<SessionStore>) -> Self {
+        Self {
+            rcon,
+            store,
+            policy: EscalationPolicy::default(),
+            replace_space: None,
+        }
+    }
+
+    /// Records a strike for `steamid` and acts on it. Offline players only get the strike
+    /// recorded, or the ban when it's due: there is nobody to warn or kick.
+    pub async fn warn(&self, steamid: &str, reason: &str) -> Result<Warning> {
+        let count = self.store.strike_count(steamid)? + 1;
+        let action = self.policy.action(count);
+        let issued_by = trace::current()
+            .map_or("unknown".to_string(), |context| context.trigger.to_string());
+        let strike = Strike {
+            steamid: steamid.to_string(),
+            reason: reason.to_string(),
+            issued_at: SystemTime::now(),
+            issued_by,
+            action,
+        };
+        self.store.record_strike(&strike)?;
+
+        let players = self.rcon.get_player_info().await?;
+        let name = players
+            .into_iter()
+            .find(|player| player.steamid == steamid)
+            .map(|player| player.name);
+        log::info!("Strike {count} for {steamid} ({reason}): {action}");
+        let Some(player) = &name else {
+            let enforced = match action {
+                StrikeAction::Banned => self.rcon.ban_player(steamid).await?,
+                _ => true,
+            };
+            return Ok(Warning {
+                strike,
+                count,
+                name,
+                enforced,
+            });
+        };
+        let message = match action {
+            StrikeAction::Warned => format!("Warning for {player}: {reason} (strike {count})"),
+            StrikeAction::Kicked => format!("{player} was kicked: {reason} (strike {count})"),
+            StrikeAction::Banned => format!("{player} was banned: {reason} (strike {count})"),
+        };
+        self.rcon
+            .broadcast(message.as_str(), self.replace_space.clone())
+            .await?;
+        let enforced = match action {
+            StrikeAction::Warned => true,
+            StrikeAction::Kicked => self.rcon.kick_player(steamid).await?,
+            StrikeAction::Banned => self.rcon.ban_player(steamid).await?,
+        };
+        Ok(Warning {
+            strike,
+            count,
+            name,
+            enforced,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escalation_policy() {
+        let policy = EscalationPolicy::default();
+        let actions: Vec<StrikeAction> = (1..=6).map(|count| policy.action(count)).collect();
+        use StrikeAction::*;
+        assert_eq!(actions, [Warned, Warned, Kicked, Kicked, Banned, Banned]);
+        let lenient = EscalationPolicy {
+            kick_at: None,
+            ban_at: Some(2),
+        };
+        assert_eq!(lenient.action(1), Warned);
+        assert_eq!(lenient.action(2), Banned);
+        let never = EscalationPolicy {
+            kick_at: None,
+            ban_at: None,
+        };
+        assert_eq!(never.action(100), Warned);
+        assert_eq!("kicked".parse::<StrikeAction>().unwrap(), Kicked);
+    }
+}
@@ -0,0 +1,712 @@
+//! Notifiers deliver [Event]s from the [EventBus] to people.
+//!
+//! Implement [Notifier] to add your own (Telegram, IRC, ...) and register it with
+//! [Notifiers] next to the built-in ones.
+//!
+//! # Example:
+//! ```no_run
+//! use palworld_server::events::{Event, EventBus};
+//! use palworld_server::notify::{DiscordNotifier, Notifiers, StdoutNotifier};
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let bus = EventBus::default();
+//!     let mut notifiers = Notifiers::new();
+//!     notifiers
+//!         .add(StdoutNotifier::new(false))
+//!         .add(DiscordNotifier::new("https://discord.com/api/webhooks/..."));
+//!     let handle = notifiers.spawn(&bus);
+//!     bus.publish(Event::SaveCompleted);
+//!     # handle.abort();
+//! }
+//! ```
+//!
+//! To send different events to different notifiers use a [Router] instead:
+//! ```
+//! use palworld_server::events::{Event, EventBus};
+//! use palworld_server::notify::{Router, StdoutNotifier};
+//!
+//! let mut router = Router::from_toml(r#"
+//!     [[routes]]
+//!     events = ["player_joined", "player_left"]
+//!     notifiers = ["stdout"]
+//!
+//!     [[routes]]
+//!     min_severity = "critical"
+//!     notifiers = ["json"]
+//! "#).unwrap();
+//! router
+//!     .register("stdout", StdoutNotifier::new(false))
+//!     .register("json", StdoutNotifier::new(true));
+//! assert_eq!(router.resolve(&Event::SaveCompleted), Vec::<&str>::new());
+//! assert_eq!(router.resolve(&Event::Crash { reason: String::new() }), vec!["json"]);
+//! ```
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+
+use crate::events::{Event, EventBus, EventKind, Severity};
+use crate::secret::Secret;
+use crate::validate::{find_key, unknown_key, Diagnostic};
+use crate::verbosity;
+
+/// Title used by notifiers that support one.
+pub static NOTIFICATION_TITLE: &str = "Palworld Server";
+
+/// Delivers an [Event] somewhere.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    async fn notify(&self, event: &Event) -> Result<()>;
+}
+
+/// Prints events to stdout, as text or one JSON object per line. Prints nothing when
+/// [crate::verbosity::quiet] was called.
+#[derive(Debug, Default)]
+pub struct StdoutNotifier {
+    pub json: bool,
+}
+
+impl StdoutNotifier {
+    pub fn new(json: bool) -> Self {
+        Self { json }
+    }
+
+    /// The line printed for `event`.
+    fn line(&self, event: &Event) -> Result<String> {
+        Ok(match self.json {
+            true => serde_json::to_string(event)?,
+            false => event.to_string(),
+        })
+    }
+}
+
+#[async_trait]
+impl Notifier for StdoutNotifier {
+    async fn notify(&self, event: &Event) -> Result<()> {
+        if !verbosity::verbosity().prints() {
+            return Ok(());
+        }
+        println!("{}", self.line(event)?);
+        Ok(())
+    }
+}
+
+/// Shows events as desktop notifications using `notify-send` on Linux, `osascript` on
+/// macOS and a PowerShell balloon tip on Windows.
+#[derive(Debug, Default)]
+pub struct DesktopNotifier;
+
+#[async_trait]
+impl Notifier for DesktopNotifier {
+    async fn notify(&self, event: &Event) -> Result<()> {
+        let message = event.to_string();
+        let mut command = if cfg!(target_os = "macos") {
+            let mut command = tokio::process::Command::new("osascript");
+            command.arg("-e").arg(format!(
+                "display notification {:?} with title {:?}",
+                message, NOTIFICATION_TITLE
+            ));
+            command
+        } else if cfg!(windows) {
+            let mut command = tokio::process::Command::new("powershell");
+            let script = format!(
+                "Add-Type -AssemblyName System.Windows.Forms; \
+                $n = New-Object System.Windows.Forms.NotifyIcon; \
+                $n.Icon = [System.Drawing.SystemIcons]::Information; \
+                $n.Visible = $true; \
+                $n.ShowBalloonTip(5000, '{}', '{}', 'Info'); \
+                Start-Sleep -Seconds 5; $n.Dispose()",
+                NOTIFICATION_TITLE.replace('\'', "''"),
+                message.replace('\'', "''")
+            );
+            command.args(["-NoProfile", "-NonInteractive", "-Command", &script]);
+            command
+        } else {
+            let mut command = tokio::process::Command::new("notify-send");
+            command.arg(NOTIFICATION_TITLE).arg(&message);
+            command
+        };
+        let status = command.status().await?;
+        if !status.success() {
+            anyhow::bail!("Desktop notification failed with {status}");
+        }
+        Ok(())
+    }
+}
+
+/// POSTs every event as JSON to a URL.
+#[derive(Debug)]
+pub struct WebhookNotifier {
+    /// Webhook URLs often carry a token, so they are kept out of logs and errors.
+    pub url: Secret<String>,
+    client: reqwest::Client,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: Secret::new(url.into()),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// The JSON body posted for `event`.
+    fn body(event: &Event) -> Result<serde_json::Value> {
+        Ok(serde_json::to_value(event)?)
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    async fn notify(&self, event: &Event) -> Result<()> {
+        self.client
+            .post(self.url.expose())
+            .json(&Self::body(event)?)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(reqwest::Error::without_url)?;
+        Ok(())
+    }
+}
+
+/// Posts events as messages to a Discord channel webhook.
+#[derive(Debug)]
+pub struct DiscordNotifier {
+    /// The webhook URL has the webhook's token in it.
+    pub url: Secret<String>,
+    /// Overrides the webhook's default username.
+    pub username: Option<String>,
+    client: reqwest::Client,
+}
+
+impl DiscordNotifier {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: Secret::new(url.into()),
+            username: None,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// The webhook message posted for `event`.
+    fn body(&self, event: &Event) -> serde_json::Value {
+        let mut body = json!({ "content": event.to_string() });
+        if let Some(username) = &self.username {
+            body["username"] = json!(username);
+        }
+        body
+    }
+}
+
+#[async_trait]
+impl Notifier for DiscordNotifier {
+    async fn notify(&self, event: &Event) -> Result<()> {
+        self.client
+            .post(self.url.expose())
+            .json(&self.body(event))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(reqwest::Error::without_url)?;
+        Ok(())
+    }
+}
+
+/// A set of notifiers that every event but [Event::Progress] is delivered to.
+#[derive(Default)]
+pub struct Notifiers {
+    notifiers: Vec<Box<dyn Notifier>>,
+}
+
+impl Notifiers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a notifier.
+    pub fn add(&mut self, notifier: impl Notifier + 'static) -> &mut Self {
+        self.notifiers.push(Box::new(notifier));
+        self
+    }
+
+    /// Number of registered notifiers.
+    pub fn len(&self) -> usize {
+        self.notifiers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.notifiers.is_empty()
+    }
+
+    /// Delivers an event to every notifier. A failing notifier is logged and doesn't
+    /// stop the others.
+    pub async fn notify(&self, event: &Event) {
+        for notifier in &self.notifiers {
+            if let Err(e) = notifier.notify(event).await {
+                log::error!("Notifier failed for {event:?}: {e}");
+            }
+        }
+    }
+
+    /// Subscribes to `bus` and delivers events in a background task until the bus is dropped.
+    pub fn spawn(self, bus: &EventBus) -> JoinHandle<()> {
+        let mut events = bus.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(Event::Progress(_)) => (),
+                    Ok(event) => self.notify(&event).await,
+                    Err(RecvError::Lagged(skipped)) => {
+                        log::warn!("Notifiers fell behind, skipped {skipped} event(s)")
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+}
+
+impl std::fmt::Debug for Notifiers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Notifiers")
+            .field("notifiers", &self.notifiers.len())
+            .finish()
+    }
+}
+
+/// Sends matching events to the named notifiers of a [Router].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Route {
+    /// Event kinds this route matches, empty matches every kind but [EventKind::Progress].
+    #[serde(default)]
+    pub events: Vec<EventKind>,
+    /// Events less severe than this don't match.
+    #[serde(default = "default_min_severity")]
+    pub min_severity: Severity,
+    /// Names of the notifiers registered with the [Router].
+    pub notifiers: Vec<String>,
+}
+
+fn default_min_severity() -> Severity {
+    Severity::Info
+}
+
+impl Route {
+    pub fn matches(&self, event: &Event) -> bool {
+        let kind = event.kind();
+        event.severity() >= self.min_severity
+            && match self.events.is_empty() {
+                true => kind != EventKind::Progress,
+                false => self.events.contains(&kind),
+            }
+    }
+}
+
+/// Declarative routing configuration, usually read from TOML.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RoutingConfig {
+    #[serde(default)]
+    pub routes: Vec<Route>,
+}
+
+/// Keys of a [RoutingConfig] and of its routes.
+const CONFIG_KEYS: [&str; 1] = ["routes"];
+const ROUTE_KEYS: [&str; 3] = ["events", "min_severity", "notifiers"];
+
+impl RoutingConfig {
+    /// Checks the TOML of a routing configuration before it is used, `file` names it in the
+    /// diagnostics. Syntax errors, unknown keys and values of the wrong type are errors,
+    /// routes that can't notify anyone are warnings.
+    pub fn validate(file: &str, config: &str) -> Vec<Diagnostic> {
+        let toml_error = |e: toml::de::Error| {
+            let diagnostic = Diagnostic::error(e.message().trim());
+            match e.span() {
+                Some(span) => diagnostic.at(file, config, span.start),
+                None => diagnostic.in_file(file),
+            }
+        };
+        let table: toml::Table = match config.parse() {
+            Ok(table) => table,
+            Err(e) => return vec![toml_error(e)],
+        };
+        let mut diagnostics: Vec<Diagnostic> = table
+            .keys()
+            .filter(|key| !CONFIG_KEYS.contains(&key.as_str()))
+            .map(|key| unknown_key(file, config, key, 0, &CONFIG_KEYS))
+            .collect();
+        let mut seen: HashMap<&str, usize> = HashMap::new();
+        let routes = table.get("routes").and_then(|routes| routes.as_array());
+        for route in routes
+            .into_iter()
+            .flatten()
+            .filter_map(|route| route.as_table())
+        {
+            for key in route.keys() {
+                if !ROUTE_KEYS.contains(&key.as_str()) {
+                    let nth = seen.entry(key).or_default();
+                    diagnostics.push(unknown_key(file, config, key, *nth, &ROUTE_KEYS));
+                    *nth += 1;
+                }
+            }
+        }
+        if !diagnostics.is_empty() {
+            return diagnostics;
+        }
+        let config_routes = match toml::from_str::<RoutingConfig>(config) {
+            Ok(parsed) => parsed.routes,
+            Err(e) => return vec![toml_error(e)],
+        };
+        if config_routes.is_empty() {
+            diagnostics.push(
+                Diagnostic::warning("no routes, no event is notified")
+                    .in_file(file)
+                    .help("add a [[routes]] table"),
+            );
+        }
+        for (i, route) in config_routes.iter().enumerate() {
+            if !route.notifiers.is_empty() {
+                continue;
+            }
+            let diagnostic = Diagnostic::warning(format!(
+                "route {} has no notifiers, it is unreachable",
+                i + 1
+            ))
+            .help("name the notifiers its events go to in notifiers");
+            diagnostics.push(match find_key(config, "notifiers", i) {
+                Some(offset) => diagnostic.at(file, config, offset),
+                None => diagnostic.in_file(file),
+            });
+        }
+        diagnostics
+    }
+}
+
+/// Directs each event to the notifiers of every matching [Route]. A notifier matched by
+/// several routes is only notified once.
+#[derive(Default)]
+pub struct Router {
+    notifiers: HashMap<String, Box<dyn Notifier>>,
+    routes: Vec<Route>,
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a [Router] from a [RoutingConfig], notifiers still need to be registered.
+    pub fn from_config(config: RoutingConfig) -> Self {
+        Self {
+            notifiers: HashMap::new(),
+            routes: config.routes,
+        }
+    }
+
+    /// Create a [Router] from the TOML representation of a [RoutingConfig].
+    pub fn from_toml(config: &str) -> Result<Self> {
+        Ok(Self::from_config(toml::from_str(config)?))
+    }
+
+    /// Registers a notifier under `name` for routes to refer to.
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        notifier: impl Notifier + 'static,
+    ) -> &mut Self {
+        self.notifiers.insert(name.into(), Box::new(notifier));
+        self
+    }
+
+    /// Adds a route.
+    pub fn route(&mut self, route: Route) -> &mut Self {
+        self.routes.push(route);
+        self
+    }
+
+    pub fn routes(&self) -> &[Route] {
+        &self.routes
+    }
+
+    /// Names of the notifiers `event` is delivered to, in route order.
+    pub fn resolve(&self, event: &Event) -> Vec<&str> {
+        let mut names: Vec<&str> = Vec::new();
+        for route in self.routes.iter().filter(|route| route.matches(event)) {
+            for name in &route.notifiers {
+                if !names.contains(&name.as_str()) {
+                    names.push(name);
+                }
+            }
+        }
+        names
+    }
+
+    /// Errors if a route names a notifier that isn't registered.
+    pub fn check(&self) -> Result<()> {
+        for (i, route) in self.routes.iter().enumerate() {
+            if let Some(name) = route
+                .notifiers
+                .iter()
+                .find(|name| !self.notifiers.contains_key(*name))
+            {
+                anyhow::bail!("Route {} refers to unregistered notifier '{name}'", i + 1);
+            }
+        }
+        Ok(())
+    }
+
+    /// Delivers an event to the notifiers it resolves to. Unknown names and failing
+    /// notifiers are logged.
+    pub async fn notify(&self, event: &Event) {
+        for name in self.resolve(event) {
+            let Some(notifier) = self.notifiers.get(name) else {
+                log::warn!("Route refers to unregistered notifier '{name}'");
+                continue;
+            };
+            if let Err(e) = notifier.notify(event).await {
+                log::error!("Notifier '{name}' failed for {event:?}: {e}");
+            }
+        }
+    }
+
+    /// Subscribes to `bus` and routes events in a background task until the bus is dropped.
+    /// Errors without subscribing if [Router::check] fails.
+    pub fn spawn(self, bus: &EventBus) -> Result<JoinHandle<()>> {
+        self.check()?;
+        let mut events = bus.subscribe();
+        Ok(tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(event) => self.notify(&event).await,
+                    Err(RecvError::Lagged(skipped)) => {
+                        log::warn!("Router fell behind, skipped {skipped} event(s)")
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        }))
+    }
+}
+
+impl std::fmt::Debug for Router {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Router")
+            .field(
+                "notifiers",
+                &self.notifiers.keys().collect::<Vec<&String>>(),
+            )
+            .field("routes", &self.routes)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::models::PlayerInfo;
+    use crate::validate::Level;
+
+    /// Events delivered, with the name of the notifier they were delivered to.
+    type Received = Arc<Mutex<Vec<(&'static str, Event)>>>;
+
+    /// Records the events it is notified of under its name.
+    struct Recorder {
+        name: &'static str,
+        received: Received,
+    }
+
+    #[async_trait]
+    impl Notifier for Recorder {
+        async fn notify(&self, event: &Event) -> Result<()> {
+            self.received
+                .lock()
+                .unwrap()
+                .push((self.name, event.clone()));
+            Ok(())
+        }
+    }
+
+    fn recording_router(config: &str) -> (Router, Received) {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let mut router = Router::from_toml(config).unwrap();
+        for name in ["discord", "email"] {
+            let received = received.clone();
+            router.register(name, Recorder { name, received });
+        }
+        (router, received)
+    }
+
+    fn player() -> PlayerInfo {
+        PlayerInfo {
+            name: "Bob".to_string(),
+            uid: "123".to_string(),
+            steamid: "76561190000000000".to_string(),
+        }
+    }
+
+    /// Serde name of an [Event] variant, camel case with the `serde-camel-case` feature.
+    fn variant(name: &str) -> String {
+        match cfg!(feature = "serde-camel-case") {
+            true => format!("{}{}", name[..1].to_lowercase(), &name[1..]),
+            false => name.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_stdout_line() {
+        let event = Event::PlayerJoined(player());
+        assert_eq!(
+            StdoutNotifier::new(false).line(&event).unwrap(),
+            "Bob joined the server"
+        );
+        let line = StdoutNotifier::new(true).line(&event).unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&line).unwrap(),
+            json!({ variant("PlayerJoined"): {
+                "name": "Bob",
+                "uid": "123",
+                "steamid": "76561190000000000",
+            }})
+        );
+        assert!(!line.contains('\n'));
+    }
+
+    #[test]
+    fn test_webhook_body() {
+        let event = Event::Crash {
+            reason: "exit code 139".to_string(),
+        };
+        assert_eq!(
+            WebhookNotifier::body(&event).unwrap(),
+            json!({ variant("Crash"): { "reason": "exit code 139" } })
+        );
+    }
+
+    #[test]
+    fn test_discord_body() {
+        let event = Event::ShutdownScheduled {
+            delay: std::time::Duration::from_secs(60),
+            message: "Updating".to_string(),
+        };
+        let mut discord = DiscordNotifier::new("https://discord.com/api/webhooks/1/token");
+        assert_eq!(
+            discord.body(&event),
+            json!({ "content": "Shutdown in 60s: Updating" })
+        );
+        discord.username = Some("Palworld".to_string());
+        assert_eq!(
+            discord.body(&event),
+            json!({ "content": "Shutdown in 60s: Updating", "username": "Palworld" })
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let valid = "[[routes]]\nevents = [\"crash\"]\nnotifiers = [\"discord\"]\n";
+        assert!(RoutingConfig::validate("routes.toml", valid).is_empty());
+
+        let typo = "[[routes]]\nnotifiers = [\"discord\"]\n\n[[routes]]\nnotifier = [\"email\"]\n";
+        let diagnostics = RoutingConfig::validate("routes.toml", typo);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "unknown key 'notifier'");
+        assert_eq!((diagnostics[0].line, diagnostics[0].column), (5, 1));
+        assert_eq!(
+            diagnostics[0].help.as_deref(),
+            Some("did you mean 'notifiers'?")
+        );
+
+        let wrong_event = "[[routes]]\nevents = [\"crashed\"]\nnotifiers = []\n";
+        let diagnostics = RoutingConfig::validate("routes.toml", wrong_event);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0]
+            .message
+            .starts_with("unknown variant `crashed`"));
+        assert_eq!(diagnostics[0].line, 2);
+
+        let unreachable = "[[routes]]\nnotifiers = []\n";
+        let diagnostics = RoutingConfig::validate("routes.toml", unreachable);
+        assert_eq!(diagnostics[0].level, Level::Warning);
+        assert_eq!(diagnostics[0].line, 2);
+        let syntax = RoutingConfig::validate("routes.toml", "[[routes]\n");
+        assert_eq!(syntax[0].level, Level::Error);
+        assert_eq!(syntax[0].line, 1);
+    }
+
+    #[tokio::test]
+    async fn test_router_routes_by_event() {
+        let (router, received) = recording_router(
+            r#"
+            [[routes]]
+            events = ["player_joined"]
+            notifiers = ["discord"]
+
+            [[routes]]
+            min_severity = "critical"
+            notifiers = ["email"]
+            "#,
+        );
+        let joined = Event::PlayerJoined(player());
+        let crash = Event::Crash {
+            reason: "exit code 139".to_string(),
+        };
+        router.notify(&joined).await;
+        router.notify(&Event::SaveCompleted).await;
+        router.notify(&crash).await;
+        assert_eq!(
+            *received.lock().unwrap(),
+            [("discord", joined), ("email", crash)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_router_dedup() {
+        let (router, received) = recording_router(
+            r#"
+            [[routes]]
+            events = ["crash"]
+            notifiers = ["email", "discord"]
+
+            [[routes]]
+            min_severity = "warning"
+            notifiers = ["discord"]
+            "#,
+        );
+        let crash = Event::Crash {
+            reason: "exit code 139".to_string(),
+        };
+        assert_eq!(router.resolve(&crash), ["email", "discord"]);
+        router.notify(&crash).await;
+        assert_eq!(
+            *received.lock().unwrap(),
+            [("email", crash.clone()), ("discord", crash)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_router_unregistered_notifier() {
+        let config =
+            "[[routes]]\nnotifiers = [\"discord\"]\n\n[[routes]]\nnotifiers = [\"slack\"]\n";
+        let (router, received) = recording_router(config);
+        let error = router.check().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Route 2 refers to unregistered notifier 'slack'"
+        );
+        // Delivery to the registered notifiers goes on.
+        router.notify(&Event::SaveCompleted).await;
+        assert_eq!(received.lock().unwrap().len(), 1);
+
+        let bus = EventBus::default();
+        assert!(router.spawn(&bus).is_err());
+        let (router, _) = recording_router(&config.replace("slack", "email"));
+        router.spawn(&bus).unwrap().abort();
+    }
+}
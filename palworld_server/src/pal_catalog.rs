@@ -0,0 +1,114 @@
+// Pal data only, the lookup code is in crate::pals. Keep entries sorted by name. IDs are the
+// internal names spawn commands and save files use, names as shown in game (English).
+
+use crate::pals::{Element::*, Pal};
+
+pub(crate) static PALS: &[Pal] = &[
+    Pal::new("Anubis", "Anubis", &[Ground]),
+    Pal::new("FlameBuffalo", "Arsox", &[Fire]),
+    Pal::new("BlackMetalDragon", "Astegon", &[Dragon, Dark]),
+    Pal::new("BlueDragon", "Azurobe", &[Water, Dragon]),
+    Pal::new("SoldierBee", "Beegarde", &[Grass]),
+    Pal::new("NightLady", "Bellanoir", &[Dark]),
+    Pal::new("KingBahamut", "Blazamut", &[Fire]),
+    Pal::new("Manticore", "Blazehowl", &[Fire]),
+    Pal::new("LittleBriarRose", "Bristla", &[Grass]),
+    Pal::new("SakuraSaurus", "Broncherry", &[Grass]),
+    Pal::new("Ronin", "Bushi", &[Fire]),
+    Pal::new("BerryGoat", "Caprity", &[Grass]),
+    Pal::new("PinkCat", "Cattiva", &[Neutral]),
+    Pal::new("DarkCrow", "Cawgnito", &[Dark]),
+    Pal::new("FlyingManta", "Celaray", &[Water]),
+    Pal::new("ChickenPal", "Chikipi", &[Neutral]),
+    Pal::new("WeaselDragon", "Chillet", &[Ice, Dragon]),
+    Pal::new("CuteButterfly", "Cinnamoth", &[Grass]),
+    Pal::new("WoolFox", "Cremis", &[Neutral]),
+    Pal::new("WhiteTiger", "Cryolinx", &[Ice]),
+    Pal::new("DreamDemon", "Daedream", &[Dark]),
+    Pal::new("RaijinDaughter", "Dazzi", &[Electric]),
+    Pal::new("NegativeKoala", "Depresso", &[Dark]),
+    Pal::new("DrillGame", "Digtoise", &[Ground]),
+    Pal::new("FlowerDinosaur", "Dinossom", &[Grass, Dragon]),
+    Pal::new("Garm", "Direhowl", &[Neutral]),
+    Pal::new("LazyCatfish", "Dumud", &[Ground]),
+    Pal::new("Deer", "Eikthyrdeer", &[Neutral]),
+    Pal::new("QueenBee", "Elizabee", &[Grass]),
+    Pal::new("FairyDragon", "Elphidran", &[Dragon]),
+    Pal::new("Horus", "Faleris", &[Fire]),
+    Pal::new("CatVampire", "Felbat", &[Dark]),
+    Pal::new("LavaGirl", "Flambelle", &[Fire]),
+    Pal::new("FlowerRabbit", "Flopie", &[Grass]),
+    Pal::new("IceFox", "Foxcicle", &[Ice]),
+    Pal::new("Kitsunebi", "Foxparks", &[Fire]),
+    Pal::new("IceHorse", "Frostallion", &[Ice]),
+    Pal::new("BluePlatypus", "Fuack", &[Water]),
+    Pal::new("CuteMole", "Fuddler", &[Ground]),
+    Pal::new("Eagle", "Galeclaw", &[Neutral]),
+    Pal::new("SharkKid", "Gobfin", &[Water]),
+    Pal::new("Gorilla", "Gorirat", &[Neutral]),
+    Pal::new("NaughtyCat", "Grintale", &[Neutral]),
+    Pal::new("ElecPanda", "Grizzbolt", &[Electric]),
+    Pal::new("PlantSlime", "Gumoss", &[Grass]),
+    Pal::new("HadesBird", "Helzephyr", &[Dark]),
+    Pal::new("WizardOwl", "Hoocrates", &[Dark]),
+    Pal::new("Baphomet", "Incineram", &[Fire, Dark]),
+    Pal::new("JetDragon", "Jetragon", &[Dragon]),
+    Pal::new("Hedgehog", "Jolthog", &[Electric]),
+    Pal::new("Hedgehog_Ice", "Jolthog Cryst", &[Ice]),
+    Pal::new("Umihebi", "Jormuntide", &[Dragon, Water]),
+    Pal::new("CatMage", "Katress", &[Dark]),
+    Pal::new("Kelpie", "Kelpsea", &[Water]),
+    Pal::new("NegativeOctopus", "Killamari", &[Dark]),
+    Pal::new("KingAlpaca", "Kingpaca", &[Neutral]),
+    Pal::new("AmaterasuWolf", "Kitsun", &[Fire]),
+    Pal::new("SheepBall", "Lamball", &[Neutral]),
+    Pal::new("Carbunclo", "Lifmunk", &[Grass]),
+    Pal::new("Werewolf", "Loupmoon", &[Dark]),
+    Pal::new("Mutant", "Lunaris", &[Neutral]),
+    Pal::new("LilyQueen", "Lyleen", &[Grass]),
+    Pal::new("LilyQueen_Dark", "Lyleen Noct", &[Dark]),
+    Pal::new("GrassMammoth", "Mammorest", &[Grass]),
+    Pal::new("GhostBeast", "Maraith", &[Dark]),
+    Pal::new("Bastet", "Mau", &[Dark]),
+    Pal::new("Alpaca", "Melpaca", &[Neutral]),
+    Pal::new("DarkScorpion", "Menasting", &[Dark, Ground]),
+    Pal::new("GrassPanda", "Mossanda", &[Grass]),
+    Pal::new("CowPal", "Mozzarina", &[Neutral]),
+    Pal::new("BlackCentaur", "Necromus", &[Dark]),
+    Pal::new("HawkBird", "Nitewing", &[Neutral]),
+    Pal::new("NightFox", "Nox", &[Dark]),
+    Pal::new("ThunderDragonMan", "Orserk", &[Dragon, Electric]),
+    Pal::new("SaintCentaur", "Paladius", &[Neutral]),
+    Pal::new("Penguin", "Pengullet", &[Water, Ice]),
+    Pal::new("CaptainPenguin", "Penking", &[Water, Ice]),
+    Pal::new("FlowerDoll", "Petallia", &[Grass]),
+    Pal::new("FireKirin", "Pyrin", &[Fire]),
+    Pal::new("SkyDragon", "Quivern", &[Dragon]),
+    Pal::new("RedArmorBird", "Ragnahawk", &[Fire]),
+    Pal::new("ThunderDog", "Rayhound", &[Electric]),
+    Pal::new("IceDeer", "Reindrix", &[Ice]),
+    Pal::new("LazyDragon", "Relaxaurus", &[Dragon, Water]),
+    Pal::new("VolcanicMonster", "Reptyro", &[Fire, Ground]),
+    Pal::new("PinkRabbit", "Ribbuny", &[Neutral]),
+    Pal::new("RobinHood", "Robinquill", &[Grass]),
+    Pal::new("FlameBambi", "Rooby", &[Fire]),
+    Pal::new("Boar", "Rushoar", &[Ground]),
+    Pal::new("BlackGriffon", "Shadowbeak", &[Dark]),
+    Pal::new("WhiteMoth", "Sibelyx", &[Ice]),
+    Pal::new("ElecCat", "Sparkit", &[Electric]),
+    Pal::new("Serpent", "Surfent", &[Water]),
+    Pal::new("Suzaku", "Suzaku", &[Fire]),
+    Pal::new("Monkey", "Tanzee", &[Grass]),
+    Pal::new("Ganesha", "Teafant", &[Water]),
+    Pal::new("ColorfulBird", "Tocotoco", &[Neutral]),
+    Pal::new("CatBat", "Tombat", &[Dark]),
+    Pal::new("Kirin", "Univolt", &[Electric]),
+    Pal::new("VioletFairy", "Vaelet", &[Grass]),
+    Pal::new("BirdDragon", "Vanwyrm", &[Fire, Dark]),
+    Pal::new("GrassRabbitMan", "Verdash", &[Grass]),
+    Pal::new("CuteFox", "Vixy", &[Neutral]),
+    Pal::new("HerculesBeetle", "Warsect", &[Grass, Ground]),
+    Pal::new("FoxMage", "Wixen", &[Fire]),
+    Pal::new("SweetsSheep", "Woolipop", &[Neutral]),
+    Pal::new("Yeti", "Wumpo", &[Ice]),
+];
@@ -0,0 +1,469 @@
+//! Commands of the PalGuard server mod, as typed [Command]s.
+//!
+//! PalGuard adds a whitelist, item giving and player positions to the RCON commands of
+//! Palworld. Its responses are free text that changes between releases, so the results are
+//! only as reliable as [succeeded] and [Position]'s parsing: failures are recognized by words
+//! like `failed` or `not found`, positions by their `X`, `Y` and `Z` values.
+//!
+//! [Locations] names places like the spawn or towers, so admins can teleport players there
+//! without remembering coordinates. [PalworldRCON::give_item] and [PalworldRCON::spawn_pal]
+//! take items and Pals by name, looked up in [crate::items] and [crate::pals].
+//!
+//! # Example:
+//! ```no_run
+//! use palworld_server::palguard::{GetPos, Give, Locations, WhitelistAdd};
+//! use palworld_server::rcon::{PalworldRCON, DEFAULT_SOURCE_PORT};
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let rcon = PalworldRCON::new("localhost", DEFAULT_SOURCE_PORT, "MyRCONPassword");
+//!     let steamid = "76561190000000001";
+//!     assert!(rcon.execute(&WhitelistAdd::new(steamid)).await.unwrap());
+//!     rcon.execute(&Give::new(steamid, "Wood", 10)).await.unwrap();
+//!     rcon.give_item(steamid, "refined ingot", 5).await.unwrap();
+//!     println!("{}", rcon.execute(&GetPos::new(steamid)).await.unwrap());
+//!
+//!     let mut locations = Locations::new();
+//!     locations.set("tower1", rcon.execute(&GetPos::new(steamid)).await.unwrap());
+//!     let tower = locations.resolve("Tower1").unwrap();
+//!     rcon.teleport_player_to(steamid, tower.x, tower.y, tower.z).await.unwrap();
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use anyhow::{bail, Result};
+
+use crate::command::Command;
+use crate::items;
+use crate::pals;
+use crate::rcon::PalworldRCON;
+use crate::validate::did_you_mean;
+
+/// Words in responses of failed PalGuard commands, matched ignoring case.
+pub const FAILURE_WORDS: [&str; 6] = [
+    "fail",
+    "error",
+    "invalid",
+    "not found",
+    "unknown",
+    "no player",
+];
+
+/// Whether a PalGuard response reports success, it isn't empty and has none of the
+/// [FAILURE_WORDS].
+pub fn succeeded(raw: &str) -> bool {
+    let raw = raw.to_lowercase();
+    !raw.trim().is_empty() && !FAILURE_WORDS.iter().any(|word| raw.contains(word))
+}
+
+/// A location in the world, in the units of the game.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Position {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Position {
+    pub fn new(x: f64, y: f64, z: f64) -> Self {
+        Self { x, y, z }
+    }
+
+    /// Parses `X=1.5 Y=-20 Z=300`, `X: 1.5, Y: -20, Z: 300` and the like, or the numbers of
+    /// the last `(1.5, -20, 300)` in `raw`.
+    pub fn parse(raw: &str) -> Result<Self> {
+        let axes = ['X', 'Y', 'Z'].map(|axis| axis_value(raw, axis));
+        if let [Some(x), Some(y), Some(z)] = axes {
+            return Ok(Self::new(x, y, z));
+        }
+        let tuple = raw
+            .rfind('(')
+            .and_then(|start| Some(&raw[start + 1..start + raw[start..].find(')')?]));
+        let numbers: Vec<f64> = tuple
+            .map(|tuple| {
+                tuple
+                    .split(',')
+                    .filter_map(|number| number.trim().parse().ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        match numbers.as_slice() {
+            [x, y, z] => Ok(Self::new(*x, *y, *z)),
+            _ => bail!("No position in '{}'", raw.trim()),
+        }
+    }
+}
+
+/// Number after `axis` followed by `=` or `:`, the axis not being part of a word.
+fn axis_value(raw: &str, axis: char) -> Option<f64> {
+    raw.char_indices()
+        .filter(|(i, c)| {
+            c.eq_ignore_ascii_case(&axis)
+                && !raw[..*i]
+                    .chars()
+                    .next_back()
+                    .is_some_and(char::is_alphanumeric)
+        })
+        .find_map(|(i, _)| {
+            let value = raw[i + 1..]
+                .trim_start()
+                .strip_prefix([':', '='])?
+                .trim_start();
+            let end = value
+                .find(|c: char| !(c.is_ascii_digit() || matches!(c, '.' | '-' | '+' | 'e')))
+                .unwrap_or(value.len());
+            value[..end].parse().ok()
+        })
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "X={} Y={} Z={}", self.x, self.y, self.z)
+    }
+}
+
+/// Named places in the world, names are matched ignoring case.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Locations {
+    pub places: BTreeMap<String, Position>,
+}
+
+impl Locations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The place named `name`.
+    pub fn get(&self, name: &str) -> Option<Position> {
+        self.places
+            .iter()
+            .find(|(place, _)| place.eq_ignore_ascii_case(name))
+            .map(|(_, position)| *position)
+    }
+
+    /// Names `position`, replacing a place with the same name.
+    pub fn set(&mut self, name: &str, position: Position) {
+        self.remove(name);
+        self.places.insert(name.to_string(), position);
+    }
+
+    /// Removes the place named `name`, returning where it was.
+    pub fn remove(&mut self, name: &str) -> Option<Position> {
+        let name = self
+            .places
+            .keys()
+            .find(|place| place.eq_ignore_ascii_case(name))?
+            .clone();
+        self.places.remove(&name)
+    }
+
+    /// The place named `target`, or coordinates like `-1234.5,567,89`. Unknown names fail
+    /// suggesting the closest one.
+    pub fn resolve(&self, target: &str) -> Result<Position> {
+        if let Some(position) = self.get(target) {
+            return Ok(position);
+        }
+        let numbers: Vec<f64> = target
+            .split([',', ' '])
+            .filter(|number| !number.is_empty())
+            .map_while(|number| number.parse().ok())
+            .collect();
+        if let [x, y, z] = numbers.as_slice() {
+            return Ok(Position::new(*x, *y, *z));
+        }
+        match did_you_mean(target, self.places.keys().map(String::as_str)) {
+            Some(name) => bail!("No location named '{target}', did you mean '{name}'?"),
+            None => bail!("No location named '{target}'"),
+        }
+    }
+}
+
+impl PalworldRCON {
+    /// Moves an online player to a position with PalGuard's teleport command. Returns true if
+    /// PalGuard confirmed it.
+    pub async fn teleport_player_to(&self, steamid: &str, x: f64, y: f64, z: f64) -> Result<bool> {
+        self.execute(&Teleport::new(steamid, Position::new(x, y, z)))
+            .await
+    }
+
+    /// Gives an online player `qty` of `item`, an ID or a name looked up with
+    /// [items::find]. Returns true if PalGuard confirmed it, fails without sending anything
+    /// for items not in the catalog, give those by ID with [Give].
+    pub async fn give_item(&self, steamid: &str, item: &str, qty: u32) -> Result<bool> {
+        if qty == 0 {
+            bail!("Can't give 0 of '{item}'");
+        }
+        let item = items::find(item)?;
+        self.execute(&Give::new(steamid, item.id, qty)).await
+    }
+
+    /// Spawns a Pal of `species`, an ID or a name looked up with [pals::find], at `level` next
+    /// to the online player `near_player`. Returns true if PalGuard confirmed it.
+    pub async fn spawn_pal(&self, species: &str, level: u32, near_player: &str) -> Result<bool> {
+        if level == 0 {
+            bail!("Pals start at level 1");
+        }
+        let pal = pals::find(species)?;
+        self.execute(&SpawnPal::new(near_player, pal.id, level))
+            .await
+    }
+}
+
+/// Allows a player on a whitelisted server, true if PalGuard confirmed it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WhitelistAdd {
+    pub steamid: String,
+}
+
+impl WhitelistAdd {
+    pub fn new(steamid: impl Into<String>) -> Self {
+        Self {
+            steamid: steamid.into(),
+        }
+    }
+}
+
+impl Command for WhitelistAdd {
+    const NAME: &'static str = "whitelist_add";
+    type Response = bool;
+
+    fn encode(&self) -> String {
+        format!("{} {}", Self::NAME, self.steamid)
+    }
+
+    fn parse(raw: &str) -> Result<bool> {
+        Ok(succeeded(raw))
+    }
+}
+
+/// Removes a player from the whitelist, true if PalGuard confirmed it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WhitelistRemove {
+    pub steamid: String,
+}
+
+impl WhitelistRemove {
+    pub fn new(steamid: impl Into<String>) -> Self {
+        Self {
+            steamid: steamid.into(),
+        }
+    }
+}
+
+impl Command for WhitelistRemove {
+    const NAME: &'static str = "whitelist_remove";
+    type Response = bool;
+
+    fn encode(&self) -> String {
+        format!("{} {}", Self::NAME, self.steamid)
+    }
+
+    fn parse(raw: &str) -> Result<bool> {
+        Ok(succeeded(raw))
+    }
+}
+
+/// Gives an online player `count` of an item by its ID, like `Wood` or `PalSphere`. See
+/// [PalworldRCON::give_item] to give items by name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Give {
+    pub steamid: String,
+    pub item: String,
+    pub count: u32,
+}
+
+impl Give {
+    pub fn new(steamid: impl Into<String>, item: impl Into<String>, count: u32) -> Self {
+        Self {
+            steamid: steamid.into(),
+            item: item.into(),
+            count,
+        }
+    }
+}
+
+impl Command for Give {
+    const NAME: &'static str = "give";
+    type Response = bool;
+
+    fn encode(&self) -> String {
+        format!(
+            "{} {} {} {}",
+            Self::NAME,
+            self.steamid,
+            self.item,
+            self.count
+        )
+    }
+
+    fn parse(raw: &str) -> Result<bool> {
+        Ok(succeeded(raw))
+    }
+}
+
+/// Where an online player is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GetPos {
+    pub steamid: String,
+}
+
+impl GetPos {
+    pub fn new(steamid: impl Into<String>) -> Self {
+        Self {
+            steamid: steamid.into(),
+        }
+    }
+}
+
+impl Command for GetPos {
+    const NAME: &'static str = "getpos";
+    const READ_ONLY: bool = true;
+    type Response = Position;
+
+    fn encode(&self) -> String {
+        format!("{} {}", Self::NAME, self.steamid)
+    }
+
+    fn parse(raw: &str) -> Result<Position> {
+        if !succeeded(raw) {
+            bail!("getpos failed: {}", raw.trim());
+        }
+        Position::parse(raw)
+    }
+}
+
+/// Moves an online player to a position, true if PalGuard confirmed it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Teleport {
+    pub steamid: String,
+    pub position: Position,
+}
+
+impl Teleport {
+    pub fn new(steamid: impl Into<String>, position: Position) -> Self {
+        Self {
+            steamid: steamid.into(),
+            position,
+        }
+    }
+}
+
+impl Command for Teleport {
+    const NAME: &'static str = "teleport";
+    type Response = bool;
+
+    fn encode(&self) -> String {
+        let Position { x, y, z } = self.position;
+        format!("{} {} {x} {y} {z}", Self::NAME, self.steamid)
+    }
+
+    fn parse(raw: &str) -> Result<bool> {
+        Ok(succeeded(raw))
+    }
+}
+
+/// Spawns a Pal by its ID, like `SheepBall`, next to an online player. True if PalGuard
+/// confirmed it, see [PalworldRCON::spawn_pal] to spawn Pals by name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpawnPal {
+    pub steamid: String,
+    pub species: String,
+    pub level: u32,
+}
+
+impl SpawnPal {
+    pub fn new(steamid: impl Into<String>, species: impl Into<String>, level: u32) -> Self {
+        Self {
+            steamid: steamid.into(),
+            species: species.into(),
+            level,
+        }
+    }
+}
+
+impl Command for SpawnPal {
+    const NAME: &'static str = "spawn_pal";
+    type Response = bool;
+
+    fn encode(&self) -> String {
+        format!(
+            "{} {} {} {}",
+            Self::NAME,
+            self.steamid,
+            self.species,
+            self.level
+        )
+    }
+
+    fn parse(raw: &str) -> Result<bool> {
+        Ok(succeeded(raw))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_position() {
+        let expected = Position::new(-1234.5, 567.0, 89.0);
+        for raw in [
+            "Shadow (76561190000000001) X=-1234.5 Y=567 Z=89",
+            "x: -1234.5, y: 567, z: 89\n",
+            "Player 76561190000000001 is at (-1234.5, 567, 89)",
+        ] {
+            assert_eq!(Position::parse(raw).unwrap(), expected, "{raw}");
+        }
+        assert!(Position::parse("Player not online").is_err());
+        assert_eq!(expected.to_string(), "X=-1234.5 Y=567 Z=89");
+    }
+
+    #[test]
+    fn test_locations() {
+        let mut locations = Locations::new();
+        locations.set("Spawn", Position::new(0.0, 0.0, 100.0));
+        locations.set("tower1", Position::new(-1234.5, 567.0, 89.0));
+        assert_eq!(locations.resolve("TOWER1").unwrap().x, -1234.5);
+        assert_eq!(
+            locations.resolve("1,2.5,-3").unwrap(),
+            Position::new(1.0, 2.5, -3.0)
+        );
+        assert_eq!(
+            locations.resolve("tower2").unwrap_err().to_string(),
+            "No location named 'tower2', did you mean 'tower1'?"
+        );
+        locations.set("spawn", Position::default());
+        assert_eq!(locations.places.len(), 2);
+        assert_eq!(locations.remove("SPAWN"), Some(Position::default()));
+        assert_eq!(locations.get("spawn"), None);
+    }
+
+    #[test]
+    fn test_commands() {
+        assert_eq!(Give::new("7656", "Wood", 10).encode(), "give 7656 Wood 10");
+        let spawn = SpawnPal::new("7656", "SheepBall", 5);
+        assert_eq!(spawn.encode(), "spawn_pal 7656 SheepBall 5");
+        let teleport = Teleport::new("7656", Position::new(1.5, -2.0, 3.0));
+        assert_eq!(teleport.encode(), "teleport 7656 1.5 -2 3");
+        assert_eq!(WhitelistAdd::new("7656").encode(), "whitelist_add 7656");
+        assert!(WhitelistAdd::parse("Added 7656 to the whitelist").unwrap());
+        assert!(!WhitelistRemove::parse("7656 not found in whitelist").unwrap());
+        assert!(!Give::parse("").unwrap());
+        assert!(GetPos::parse("Failed: no player 7656").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_give_item_unknown() {
+        let rcon = PalworldRCON::new("invalid host name", 25575, "password");
+        let error = rcon.give_item("7656", "Papyrus", 1).await.unwrap_err();
+        assert_eq!(error.to_string(), "No item matches 'Papyrus'");
+        assert!(rcon.give_item("7656", "Wood", 0).await.is_err());
+        let error = rcon.spawn_pal("Digimon", 10, "7656").await.unwrap_err();
+        assert_eq!(error.to_string(), "No Pal matches 'Digimon'");
+    }
+}
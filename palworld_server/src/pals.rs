@@ -0,0 +1,169 @@
+//! Pal species by internal ID, name and element.
+//!
+//! Spawn commands and save files know Pals by internal IDs that rarely match their names,
+//! Lamball is `SheepBall` and Anubis the exception. [find] accepts an ID, a name, part of a
+//! name or a misspelled name and returns the catalog entry, [search] lists every match and
+//! [with_element] the Pals of an element.
+//!
+//! # Example:
+//! ```
+//! use palworld_server::pals::{self, Element};
+//!
+//! let lamball = pals::find("lamball").unwrap();
+//! assert_eq!(lamball.id, "SheepBall");
+//! assert_eq!(lamball.elements, &[Element::Neutral]);
+//! assert_eq!(pals::find("Penguin").unwrap().name, "Pengullet");
+//! assert!(pals::with_element(Element::Ice).any(|pal| pal.name == "Frostallion"));
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+
+use crate::lookup::{self, Entry};
+use crate::pal_catalog::PALS;
+
+/// Element of a Pal, which has one or two.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Element {
+    Neutral,
+    Fire,
+    Water,
+    Grass,
+    Electric,
+    Ice,
+    Ground,
+    Dark,
+    Dragon,
+}
+
+impl fmt::Display for Element {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Neutral => write!(f, "neutral"),
+            Self::Fire => write!(f, "fire"),
+            Self::Water => write!(f, "water"),
+            Self::Grass => write!(f, "grass"),
+            Self::Electric => write!(f, "electric"),
+            Self::Ice => write!(f, "ice"),
+            Self::Ground => write!(f, "ground"),
+            Self::Dark => write!(f, "dark"),
+            Self::Dragon => write!(f, "dragon"),
+        }
+    }
+}
+
+impl FromStr for Element {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "neutral" | "normal" => Ok(Self::Neutral),
+            "fire" => Ok(Self::Fire),
+            "water" => Ok(Self::Water),
+            "grass" => Ok(Self::Grass),
+            "electric" | "electricity" => Ok(Self::Electric),
+            "ice" => Ok(Self::Ice),
+            "ground" | "earth" => Ok(Self::Ground),
+            "dark" => Ok(Self::Dark),
+            "dragon" => Ok(Self::Dragon),
+            _ => bail!("Unknown element '{s}'"),
+        }
+    }
+}
+
+/// A Pal species of the catalog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Pal {
+    /// Internal ID, as spawn commands take it.
+    pub id: &'static str,
+    /// Name shown in game.
+    pub name: &'static str,
+    pub elements: &'static [Element],
+}
+
+impl Pal {
+    pub const fn new(id: &'static str, name: &'static str, elements: &'static [Element]) -> Self {
+        Self { id, name, elements }
+    }
+}
+
+impl Entry for Pal {
+    fn id(&self) -> &'static str {
+        self.id
+    }
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl fmt::Display for Pal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.name, self.id)
+    }
+}
+
+/// Every Pal of the catalog, sorted by name.
+pub fn catalog() -> &'static [Pal] {
+    PALS
+}
+
+/// Pals whose ID or name is `query`, or whose name contains it, ignoring case. Exact
+/// matches come first.
+pub fn search(query: &str) -> Vec<&'static Pal> {
+    lookup::search(PALS, query)
+}
+
+/// The Pal `query` names: an exact ID or name, the only Pal whose name contains it, or the
+/// closest name for typos. Fails listing the candidates if several Pals match.
+pub fn find(query: &str) -> Result<&'static Pal> {
+    lookup::find(PALS, query, "Pal")
+}
+
+/// Pals having `element`, as their only or one of their two elements.
+pub fn with_element(element: Element) -> impl Iterator<Item = &'static Pal> {
+    PALS.iter()
+        .filter(move |pal| pal.elements.contains(&element))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catalog() {
+        let mut ids: Vec<&str> = catalog().iter().map(|pal| pal.id).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        assert_eq!(ids.len(), catalog().len(), "Pal IDs are unique");
+        assert!(catalog().windows(2).all(|pals| pals[0].name < pals[1].name));
+        assert!(catalog()
+            .iter()
+            .all(|pal| (1..=2).contains(&pal.elements.len())));
+    }
+
+    #[test]
+    fn test_find() {
+        assert_eq!(find("SheepBall").unwrap().name, "Lamball");
+        assert_eq!(find("jolthog cryst").unwrap().id, "Hedgehog_Ice");
+        assert_eq!(find("Jetragonn").unwrap().id, "JetDragon");
+        assert_eq!(find("Anubis").unwrap().elements, &[Element::Ground]);
+        // Exact names win over names containing them.
+        assert_eq!(find("lyleen").unwrap().id, "LilyQueen");
+        assert_eq!(
+            find("bee").unwrap_err().to_string(),
+            "'bee' matches Beegarde (SoldierBee), Elizabee (QueenBee), Warsect (HerculesBeetle)"
+        );
+        assert_eq!(
+            find("Digimon").unwrap_err().to_string(),
+            "No Pal matches 'Digimon'"
+        );
+        assert_eq!("Electricity".parse::<Element>().unwrap(), Element::Electric);
+        assert!(with_element(Element::Dragon).all(|pal| pal.elements.contains(&Element::Dragon)));
+    }
+}
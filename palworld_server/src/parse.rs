@@ -0,0 +1,428 @@
+//! Parsers for raw Palworld server responses and the host commands run over SSH.
+//!
+//! These only operate on `&str` so they can be reused without a connection,
+//! including on wasm32 targets. They never panic on malformed input, which the fuzz
+//! targets in `palworld_server/fuzz` check.
+//!
+//! # Example:
+//! ```
+//! use palworld_server::parse;
+//!
+//! let players = parse::parse_player_info("name,playeruid,steamid\nBob,123,76561190000000000\n");
+//! assert_eq!(players.len(), 1);
+//! assert_eq!(players[0].name, "Bob");
+//! ```
+
+use anyhow::Result;
+
+use std::collections::HashMap;
+
+use crate::mem::MemInfo;
+use crate::models::{
+    ByteSize, DiskUsage, FirewallRule, PlayerInfo, ProcessInfo, Protocol, RuleAction,
+};
+
+/// Parses the response of the `showplayers` command. The header line is skipped
+/// and malformed lines are ignored. Names may contain commas, the UID and Steam ID are the
+/// last two fields.
+pub fn parse_player_info(response: &str) -> Vec<PlayerInfo> {
+    response
+        .split('\n')
+        .skip(1)
+        .filter_map(parse_player_line)
+        .collect::<Vec<PlayerInfo>>()
+}
+
+/// A `name,playeruid,steamid` line of `showplayers`.
+fn parse_player_line(line: &str) -> Option<PlayerInfo> {
+    let mut split = line.rsplitn(3, ',');
+    let steamid = split.next()?;
+    let uid = split.next()?;
+    let name = split.next()?;
+    Some(PlayerInfo {
+        name: name.to_string(),
+        uid: uid.to_string(),
+        steamid: steamid.to_string(),
+    })
+}
+
+/// Longest response body that fits in one RCON packet, the server cuts longer ones off.
+pub const MAX_RESPONSE_LEN: usize = 4096 - 10;
+
+/// Whether a `showplayers` response was cut off, because it fills a whole packet or ends
+/// in a partial line. Players up to the last newline are still complete.
+pub fn is_player_list_truncated(response: &str) -> bool {
+    let last_line = response.rsplit('\n').next().unwrap_or_default();
+    response.len() >= MAX_RESPONSE_LEN
+        || (!last_line.is_empty() && parse_player_line(last_line).is_none())
+}
+
+/// Parses the server version out of the response of the `info` command.
+///
+/// `Welcome to Pal Server[v0.1.3.0] Default Palworld Server` returns `v0.1.3.0`.
+pub fn parse_version(response: &str) -> Result<String> {
+    response
+        .match_indices("[v")
+        .find_map(|(start, _)| {
+            let version = &response[start + 1..];
+            let version = &version[..version.find(']')?];
+            let parts = version[1..].split('.').collect::<Vec<&str>>();
+            let valid = parts.len() == 4
+                && parts
+                    .iter()
+                    .all(|p| (1..=9).contains(&p.len()) && p.bytes().all(|b| b.is_ascii_digit()));
+            valid.then(|| version.to_string())
+        })
+        .ok_or_else(|| anyhow::anyhow!("Failed to find version in info RCON command"))
+}
+
+/// Splits a version like `v0.1.3.0` or `0.1.3` into its numeric components.
+pub fn parse_version_numbers(version: &str) -> Result<Vec<u32>> {
+    version
+        .trim()
+        .trim_start_matches('v')
+        .split('.')
+        .map(|part| {
+            part.parse::<u32>()
+                .map_err(|_| anyhow::anyhow!("Invalid version '{version}'"))
+        })
+        .collect()
+}
+
+/// Compares two versions component by component, missing components count as 0.
+pub fn compare_versions(a: &str, b: &str) -> Result<std::cmp::Ordering> {
+    let mut a = parse_version_numbers(a)?;
+    let mut b = parse_version_numbers(b)?;
+    let len = a.len().max(b.len());
+    a.resize(len, 0);
+    b.resize(len, 0);
+    Ok(a.cmp(&b))
+}
+
+/// Parses `/proc/meminfo`. Keys are matched exactly, so `SwapCached` doesn't
+/// overwrite `Cached`, and missing keys are left at their defaults.
+pub fn parse_meminfo(output: &str) -> MemInfo {
+    let mut mem_info = MemInfo::default();
+    for line in output.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        // MemTotal:       16305832 kB
+        let Some(kib) = value
+            .trim()
+            .strip_suffix("kB")
+            .and_then(|kib| kib.trim().parse::<u64>().ok())
+        else {
+            continue;
+        };
+        let size = ByteSize::from_kib(kib);
+        match key.trim() {
+            "MemTotal" => mem_info.mem_total = size,
+            "MemFree" => mem_info.mem_free = size,
+            "MemAvailable" => mem_info.mem_available = size,
+            "Buffers" => mem_info.buffers = Some(size),
+            "Cached" => mem_info.cached = Some(size),
+            _ => (),
+        }
+    }
+    mem_info
+}
+
+/// Parses `Key=Value` lines with numeric values, ignoring anything else.
+pub fn parse_key_values(output: &str) -> HashMap<String, u64> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.trim().split_once('=')?;
+            Some((key.to_string(), value.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+/// Parses the output of `df -kP <path>`.
+pub fn parse_df(output: &str) -> Result<DiskUsage> {
+    // Filesystem 1024-blocks Used Available Capacity Mounted on
+    let line = output.lines().nth(1).unwrap_or_default();
+    let columns = line.split_whitespace().collect::<Vec<&str>>();
+    if columns.len() < 4 {
+        anyhow::bail!("Failed to parse df output: {line}");
+    }
+    Ok(DiskUsage {
+        total: ByteSize::from_kib(columns[1].parse()?),
+        used: ByteSize::from_kib(columns[2].parse()?),
+        available: ByteSize::from_kib(columns[3].parse()?),
+    })
+}
+
+/// Parses `pid memory(kB) name` lines, the output of `ps -o pid=,rss=,comm=`.
+/// Malformed lines are ignored.
+pub fn parse_processes(output: &str) -> Vec<ProcessInfo> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut columns = line.split_whitespace();
+            let pid = columns.next()?.parse().ok()?;
+            let memory = ByteSize::from_kib(columns.next()?.parse().ok()?);
+            let name = columns.collect::<Vec<&str>>().join(" ");
+            Some(ProcessInfo { pid, name, memory })
+        })
+        .collect()
+}
+
+/// Parses the output of `systemctl is-active`, true only for `active`.
+pub fn parse_systemctl_is_active(output: &str) -> bool {
+    output.trim() == "active"
+}
+
+/// Parses the rules of `ufw status`. Rules for application profiles like `OpenSSH` and
+/// port ranges are skipped, as are the IPv6 duplicates ufw lists for every rule.
+pub fn parse_ufw_status(output: &str) -> Vec<FirewallRule> {
+    output
+        .lines()
+        .skip_while(|line| !line.starts_with("--"))
+        .skip(1)
+        .filter(|line| !line.contains("(v6)"))
+        .filter_map(|line| {
+            // 25575/tcp                  ALLOW       203.0.113.5
+            let mut columns = line.split_whitespace();
+            let (port, protocol) = match columns.next()?.split_once('/') {
+                Some((port, "tcp")) => (port, Some(Protocol::Tcp)),
+                Some((port, "udp")) => (port, Some(Protocol::Udp)),
+                Some(_) => return None,
+                None => (line.split_whitespace().next()?, None),
+            };
+            let action = match columns.next()? {
+                "ALLOW" | "LIMIT" => RuleAction::Allow,
+                "DENY" | "REJECT" => RuleAction::Deny,
+                _ => return None,
+            };
+            // `ALLOW IN` when listed with `ufw status verbose`
+            let from = columns
+                .skip_while(|column| *column == "IN")
+                .collect::<Vec<&str>>()
+                .join(" ");
+            Some(FirewallRule {
+                port: port.parse().ok()?,
+                protocol,
+                action,
+                from: (!from.is_empty() && from != "Anywhere").then_some(from),
+            })
+        })
+        .collect()
+}
+
+/// Parses the `--dport` rules of `iptables -S INPUT`, other rules are skipped.
+pub fn parse_iptables_rules(output: &str) -> Vec<FirewallRule> {
+    output
+        .lines()
+        .filter_map(|line| {
+            // -A INPUT -s 203.0.113.5/32 -p tcp -m tcp --dport 25575 -j ACCEPT
+            let columns = line.split_whitespace().collect::<Vec<&str>>();
+            if columns.first() != Some(&"-A") {
+                return None;
+            }
+            let value = |flag: &str| {
+                let position = columns.iter().position(|column| *column == flag)?;
+                columns.get(position + 1).copied()
+            };
+            let protocol = match value("-p") {
+                Some("tcp") => Some(Protocol::Tcp),
+                Some("udp") => Some(Protocol::Udp),
+                Some(_) => return None,
+                None => None,
+            };
+            let action = match value("-j")? {
+                "ACCEPT" => RuleAction::Allow,
+                "DROP" | "REJECT" => RuleAction::Deny,
+                _ => return None,
+            };
+            Some(FirewallRule {
+                port: value("--dport")?.parse().ok()?,
+                protocol,
+                action,
+                from: value("-s").map(str::to_string),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_player_info() {
+        let response =
+            "name,playeruid,steamid\nAlice,1234,76561190000000001\nBob,5678,76561190000000002\n";
+        let players = parse_player_info(response);
+        assert_eq!(players.len(), 2);
+        assert_eq!(players[0].name, "Alice");
+        assert_eq!(players[0].uid, "1234");
+        assert_eq!(players[1].steamid, "76561190000000002");
+    }
+
+    #[test]
+    fn test_parse_player_info_comma() {
+        let players =
+            parse_player_info("name,playeruid,steamid\nMe, Myself,1234,76561190000000001\n");
+        assert_eq!(players.len(), 1);
+        assert_eq!(players[0].name, "Me, Myself");
+        assert_eq!(players[0].uid, "1234");
+        assert_eq!(players[0].steamid, "76561190000000001");
+        assert!(!is_player_list_truncated(
+            "name,playeruid,steamid\nA,B,1,2\n"
+        ));
+    }
+
+    #[test]
+    fn test_parse_player_info_empty() {
+        assert!(parse_player_info("name,playeruid,steamid\n").is_empty());
+        assert!(parse_player_info("").is_empty());
+    }
+
+    #[test]
+    fn test_is_player_list_truncated() {
+        let response =
+            "name,playeruid,steamid\nAlice,1234,76561190000000001\nBob,5678,76561190000000002\n";
+        assert!(!is_player_list_truncated(response));
+        assert!(!is_player_list_truncated("name,playeruid,steamid\n"));
+        assert!(!is_player_list_truncated(""));
+        assert!(is_player_list_truncated(&response[..response.len() - 22]));
+        // A full packet may have been cut off at a line end.
+        let mut full = "name,playeruid,steamid\n".to_string();
+        while full.len() < MAX_RESPONSE_LEN {
+            full.push_str("Alice,1234,76561190000000001\n");
+        }
+        assert!(is_player_list_truncated(&full));
+    }
+
+    #[test]
+    fn test_parse_version() {
+        let version =
+            parse_version("Welcome to Pal Server[v0.1.3.0] Default Palworld Server").unwrap();
+        assert_eq!(version, "v0.1.3.0");
+        assert!(parse_version("Welcome to Pal Server Default Palworld Server").is_err());
+        assert!(parse_version("[v1.2] [v0.1.5.1]").unwrap() == "v0.1.5.1");
+        assert!(parse_version("[v0.1.x.0]").is_err());
+    }
+
+    #[test]
+    fn test_compare_versions() {
+        use std::cmp::Ordering;
+        assert_eq!(
+            compare_versions("v0.1.3.0", "0.1.3").unwrap(),
+            Ordering::Equal
+        );
+        assert_eq!(
+            compare_versions("v0.1.10.0", "v0.1.9.0").unwrap(),
+            Ordering::Greater
+        );
+        assert_eq!(
+            compare_versions("v0.1.2.0", "v0.1.3").unwrap(),
+            Ordering::Less
+        );
+        assert!(compare_versions("v0.1.x", "v0.1").is_err());
+    }
+
+    #[test]
+    fn test_parse_meminfo() {
+        let output = "MemTotal:       16305832 kB\n\
+            MemFree:         1022604 kB\n\
+            MemAvailable:   11227316 kB\n\
+            Buffers:          482064 kB\n\
+            Cached:          9583524 kB\n\
+            SwapCached:        12345 kB\n\
+            HugePages_Total:       0\n";
+        let mem_info = parse_meminfo(output);
+        assert_eq!(mem_info.mem_total, ByteSize::from_kib(16305832));
+        assert_eq!(mem_info.mem_free, ByteSize::from_kib(1022604));
+        assert_eq!(mem_info.mem_available, ByteSize::from_kib(11227316));
+        assert_eq!(mem_info.buffers, Some(ByteSize::from_kib(482064)));
+        assert_eq!(mem_info.cached, Some(ByteSize::from_kib(9583524)));
+        assert_eq!(
+            parse_meminfo("garbage\n:\nMemTotal: x kB"),
+            MemInfo::default()
+        );
+    }
+
+    #[test]
+    fn test_parse_key_values() {
+        let values = parse_key_values("MemTotal=16658604\r\nMemFree=8123456\r\nBogus\r\n");
+        assert_eq!(values.get("MemTotal"), Some(&16658604));
+        assert_eq!(values.get("MemFree"), Some(&8123456));
+        assert_eq!(values.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_df() {
+        let output = "Filesystem     1024-blocks     Used Available Capacity Mounted on\n\
+            /dev/sda1        102687672 40187716  57241796      42% /\n";
+        let disk = parse_df(output).unwrap();
+        assert_eq!(disk.total, ByteSize::from_kib(102687672));
+        assert_eq!(disk.used, ByteSize::from_kib(40187716));
+        assert_eq!(disk.available, ByteSize::from_kib(57241796));
+        assert!(parse_df("").is_err());
+        assert!(parse_df("header\n/dev/sda1 x y z").is_err());
+    }
+
+    #[test]
+    fn test_parse_processes() {
+        let output = "  1234 8123456 PalServer-Linux\n\
+            5678 1024 PalServer Win64 Cmd\n\
+            bogus line\n";
+        let processes = parse_processes(output);
+        assert_eq!(processes.len(), 2);
+        assert_eq!(processes[0].pid, 1234);
+        assert_eq!(processes[0].memory, ByteSize::from_kib(8123456));
+        assert_eq!(processes[1].name, "PalServer Win64 Cmd");
+    }
+
+    #[test]
+    fn test_parse_systemctl_is_active() {
+        assert!(parse_systemctl_is_active("active\n"));
+        assert!(!parse_systemctl_is_active("inactive\n"));
+        assert!(!parse_systemctl_is_active("activating\n"));
+    }
+
+    #[test]
+    fn test_parse_ufw_status() {
+        let output = "Status: active\n\n\
+            To                         Action      From\n\
+            --                         ------      ----\n\
+            OpenSSH                    ALLOW       Anywhere\n\
+            8211/udp                   ALLOW       Anywhere\n\
+            25575/tcp                  ALLOW       203.0.113.5\n\
+            25575/tcp                  DENY        Anywhere\n\
+            27015                      ALLOW IN    Anywhere\n\
+            8211/udp (v6)              ALLOW       Anywhere (v6)\n";
+        let rules = parse_ufw_status(output);
+        assert_eq!(rules.len(), 4);
+        assert_eq!(
+            rules[1],
+            FirewallRule {
+                port: 25575,
+                protocol: Some(Protocol::Tcp),
+                action: RuleAction::Allow,
+                from: Some("203.0.113.5".to_string()),
+            }
+        );
+        assert_eq!(rules[2].action, RuleAction::Deny);
+        assert_eq!(rules[2].from, None);
+        assert_eq!(rules[3].protocol, None);
+        assert!(parse_ufw_status("Status: inactive\n").is_empty());
+    }
+
+    #[test]
+    fn test_parse_iptables_rules() {
+        let output = "-P INPUT ACCEPT\n\
+            -A INPUT -s 203.0.113.5/32 -p tcp -m tcp --dport 25575 -j ACCEPT\n\
+            -A INPUT -p tcp -m tcp --dport 25575 -j DROP\n\
+            -A INPUT -p udp -m udp --dport 8211 -j ACCEPT\n\
+            -A INPUT -i lo -j ACCEPT\n";
+        let rules = parse_iptables_rules(output);
+        assert_eq!(rules.len(), 3);
+        assert_eq!(rules[0].from.as_deref(), Some("203.0.113.5/32"));
+        assert_eq!(rules[1].action, RuleAction::Deny);
+        assert_eq!(rules[2].protocol, Some(Protocol::Udp));
+    }
+}
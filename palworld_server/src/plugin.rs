@@ -0,0 +1,249 @@
+//! Plugins let features like an economy or vote-restart live in other crates while
+//! reusing the RCON, SSH and event infrastructure.
+//!
+//! # Example:
+//! ```no_run
+//! use anyhow::Result;
+//! use async_trait::async_trait;
+//! use palworld_server::events::{Event, EventBus};
+//! use palworld_server::plugin::{Plugin, PluginContext, PluginManager};
+//! use palworld_server::rcon::{PalworldRCON, DEFAULT_SOURCE_PORT};
+//!
+//! struct Greeter;
+//!
+//! #[async_trait]
+//! impl Plugin for Greeter {
+//!     fn name(&self) -> &str {
+//!         "greeter"
+//!     }
+//!
+//!     async fn on_event(&mut self, ctx: &PluginContext, event: &Event) -> Result<()> {
+//!         if let Event::PlayerJoined(player) = event {
+//!             ctx.rcon.broadcast(format!("Welcome {}", player.name), None).await?;
+//!         }
+//!         Ok(())
+//!     }
+//! }
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let rcon = PalworldRCON::new("localhost", DEFAULT_SOURCE_PORT, "MyRCONPassword");
+//!     let mut manager = PluginManager::new(PluginContext::new(rcon, EventBus::default()));
+//!     manager.register(Greeter);
+//!     manager.run(async { tokio::signal::ctrl_c().await.unwrap() }).await;
+//! }
+//! ```
+
+use std::future::Future;
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::events::{Event, EventBus};
+use crate::rcon::PalworldRCON;
+#[cfg(feature = "ssh")]
+use crate::ssh::PalworldConnection;
+
+/// Default interval between [Plugin::on_tick] calls.
+pub const DEFAULT_TICK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// What plugins get access to.
+#[derive(Debug, Clone)]
+pub struct PluginContext {
+    pub rcon: PalworldRCON,
+    #[cfg(feature = "ssh")]
+    pub ssh: Option<PalworldConnection>,
+    /// Plugins can publish their own events here too.
+    pub events: EventBus,
+}
+
+impl PluginContext {
+    pub fn new(rcon: PalworldRCON, events: EventBus) -> Self {
+        Self {
+            rcon,
+            #[cfg(feature = "ssh")]
+            ssh: None,
+            events,
+        }
+    }
+}
+
+/// A plugin, every hook has a default that does nothing.
+#[async_trait]
+pub trait Plugin: Send + Sync {
+    /// Name used in logs.
+    fn name(&self) -> &str;
+
+    /// Called once before any other hook. A plugin that fails to initialize is removed.
+    async fn init(&mut self, _ctx: &PluginContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called for every event published to the bus.
+    async fn on_event(&mut self, _ctx: &PluginContext, _event: &Event) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called every [PluginManager::tick_interval].
+    async fn on_tick(&mut self, _ctx: &PluginContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called once when the manager stops.
+    async fn shutdown(&mut self, _ctx: &PluginContext) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Owns the registered plugins and drives their hooks.
+pub struct PluginManager {
+    ctx: PluginContext,
+    plugins: Vec<Box<dyn Plugin>>,
+    pub tick_interval: Duration,
+}
+
+impl PluginManager {
+    pub fn new(ctx: PluginContext) -> Self {
+        Self {
+            ctx,
+            plugins: Vec::new(),
+            tick_interval: DEFAULT_TICK_INTERVAL,
+        }
+    }
+
+    pub fn register(&mut self, plugin: impl Plugin + 'static) -> &mut Self {
+        self.plugins.push(Box::new(plugin));
+        self
+    }
+
+    pub fn context(&self) -> &PluginContext {
+        &self.ctx
+    }
+
+    /// Initializes the plugins and dispatches events and ticks until `shutdown` completes,
+    /// then calls [Plugin::shutdown]. Errors from hooks are logged.
+    pub async fn run(mut self, shutdown: impl Future<Output = ()>) {
+        let mut events = self.ctx.events.subscribe();
+
+        let mut plugins = Vec::with_capacity(self.plugins.len());
+        for mut plugin in self.plugins.drain(..) {
+            match plugin.init(&self.ctx).await {
+                Ok(()) => plugins.push(plugin),
+                Err(e) => log::error!("Plugin '{}' failed to initialize: {e}", plugin.name()),
+            }
+        }
+
+        let mut ticks = tokio::time::interval(self.tick_interval);
+        // The first tick completes immediately.
+        ticks.tick().await;
+        tokio::pin!(shutdown);
+        loop {
+            tokio::select! {
+                _ = &mut shutdown => break,
+                _ = ticks.tick() => {
+                    for plugin in plugins.iter_mut() {
+                        if let Err(e) = plugin.on_tick(&self.ctx).await {
+                            log::error!("Plugin '{}' on_tick failed: {e}", plugin.name());
+                        }
+                    }
+                }
+                event = events.recv() => match event {
+                    Ok(event) => {
+                        for plugin in plugins.iter_mut() {
+                            if let Err(e) = plugin.on_event(&self.ctx, &event).await {
+                                log::error!("Plugin '{}' on_event failed: {e}", plugin.name());
+                            }
+                        }
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        log::warn!("Plugins fell behind, skipped {skipped} event(s)")
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+            }
+        }
+
+        for plugin in plugins.iter_mut() {
+            if let Err(e) = plugin.shutdown(&self.ctx).await {
+                log::error!("Plugin '{}' shutdown failed: {e}", plugin.name());
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for PluginManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PluginManager")
+            .field(
+                "plugins",
+                &self.plugins.iter().map(|p| p.name()).collect::<Vec<&str>>(),
+            )
+            .field("tick_interval", &self.tick_interval)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct Recorder {
+        calls: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl Plugin for Recorder {
+        fn name(&self) -> &str {
+            "recorder"
+        }
+
+        async fn init(&mut self, _ctx: &PluginContext) -> Result<()> {
+            self.calls.lock().unwrap().push("init".to_string());
+            Ok(())
+        }
+
+        async fn on_event(&mut self, _ctx: &PluginContext, event: &Event) -> Result<()> {
+            self.calls.lock().unwrap().push(format!("{event}"));
+            Ok(())
+        }
+
+        async fn shutdown(&mut self, _ctx: &PluginContext) -> Result<()> {
+            self.calls.lock().unwrap().push("shutdown".to_string());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_plugin_hooks() {
+        let bus = EventBus::default();
+        let rcon = PalworldRCON::new("localhost", 25575, "password");
+        let recorder = Recorder::default();
+        let calls = recorder.calls.clone();
+        let mut manager = PluginManager::new(PluginContext::new(rcon, bus.clone()));
+        manager.register(recorder);
+
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+        let handle = tokio::spawn(manager.run(async {
+            stopped.await.ok();
+        }));
+        // Wait for the manager to subscribe before publishing.
+        while bus.subscriber_count() == 0 {
+            tokio::task::yield_now().await;
+        }
+        bus.publish(Event::SaveCompleted);
+        while calls.lock().unwrap().len() < 2 {
+            tokio::task::yield_now().await;
+        }
+        stop.send(()).unwrap();
+        handle.await.unwrap();
+
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec!["init", "World saved", "shutdown"]
+        );
+    }
+}
@@ -0,0 +1,171 @@
+//! Progress of long operations like backups, installs and migrations.
+//!
+//! Operations publish [ProgressUpdate]s to a [Progress], set on
+//! [crate::ssh::PalworldConnection::progress] for the operations over SSH. Closures
+//! implement it, and so does [crate::events::EventBus] so daemons get updates as
+//! [crate::events::Event::Progress].
+//!
+//! # Example:
+//! ```
+//! use std::sync::Arc;
+//!
+//! use palworld_server::progress::{Progress, ProgressUpdate, Reporter};
+//!
+//! let progress: Arc<dyn Progress> = Arc::new(|update: &ProgressUpdate| println!("{update}"));
+//! let mut reporter = Reporter::new(Some(progress), "backup", 2);
+//! reporter.step("Archiving saves");
+//! reporter.bytes(512, 1024);
+//! reporter.step("Uploading");
+//! ```
+
+use std::fmt;
+use std::sync::Arc;
+
+use crate::models::ByteSize;
+
+/// Where an operation is at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct ProgressUpdate {
+    /// Name of the operation, like `migrate`.
+    pub operation: String,
+    /// Current step, starting at 1.
+    pub step: usize,
+    /// Number of steps of the operation.
+    pub total: usize,
+    /// What the current step does.
+    pub message: String,
+    /// Bytes done of the current step, for transfers.
+    pub bytes: Option<u64>,
+    /// Bytes the current step moves in total.
+    pub total_bytes: Option<u64>,
+}
+
+impl fmt::Display for ProgressUpdate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} [{}/{}] {}",
+            self.operation, self.step, self.total, self.message
+        )?;
+        if let (Some(bytes), Some(total_bytes)) = (self.bytes, self.total_bytes) {
+            write!(f, " ({} / {})", ByteSize(bytes), ByteSize(total_bytes))?;
+        }
+        Ok(())
+    }
+}
+
+/// Receives [ProgressUpdate]s, must be cheap since transfers report every chunk.
+pub trait Progress: Send + Sync {
+    fn update(&self, update: &ProgressUpdate);
+}
+
+impl<F: Fn(&ProgressUpdate) + Send + Sync> Progress for F {
+    fn update(&self, update: &ProgressUpdate) {
+        self(update)
+    }
+}
+
+/// Counts the steps of one operation and publishes them, does nothing without a [Progress].
+#[derive(Clone)]
+pub struct Reporter {
+    progress: Option<Arc<dyn Progress>>,
+    current: ProgressUpdate,
+}
+
+impl Reporter {
+    pub fn new(progress: Option<Arc<dyn Progress>>, operation: &str, total: usize) -> Self {
+        Self {
+            progress,
+            current: ProgressUpdate {
+                operation: operation.to_string(),
+                step: 0,
+                total,
+                message: String::new(),
+                bytes: None,
+                total_bytes: None,
+            },
+        }
+    }
+
+    /// Starts the next step and logs it.
+    pub fn step(&mut self, message: impl Into<String>) {
+        self.current.step = (self.current.step + 1).min(self.current.total);
+        self.current.message = message.into();
+        log::info!("{}", self.current.message);
+        self.current.bytes = None;
+        self.current.total_bytes = None;
+        self.publish();
+    }
+
+    /// Bytes done of the current step.
+    pub fn bytes(&self, bytes: u64, total_bytes: u64) {
+        if let Some(progress) = &self.progress {
+            progress.update(&ProgressUpdate {
+                bytes: Some(bytes),
+                total_bytes: Some(total_bytes),
+                ..self.current.clone()
+            });
+        }
+    }
+
+    /// Forwards the bytes of a transfer to the current step, for
+    /// [crate::ssh::TransferOptions::progress]. Returns None without a [Progress].
+    #[cfg(feature = "ssh")]
+    pub fn transfer_progress(&self) -> Option<crate::ssh::TransferProgress> {
+        self.progress.as_ref()?;
+        let reporter = self.clone();
+        Some(Arc::new(move |bytes, total_bytes| {
+            reporter.bytes(bytes, total_bytes)
+        }))
+    }
+
+    fn publish(&self) {
+        if let Some(progress) = &self.progress {
+            progress.update(&self.current);
+        }
+    }
+}
+
+impl fmt::Debug for Reporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Reporter")
+            .field("current", &self.current)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_reporter() {
+        let updates = Arc::new(Mutex::new(Vec::new()));
+        let progress: Arc<dyn Progress> = {
+            let updates = updates.clone();
+            Arc::new(move |update: &ProgressUpdate| {
+                updates.lock().unwrap().push(update.to_string())
+            })
+        };
+        let mut reporter = Reporter::new(Some(progress), "migrate", 2);
+        reporter.step("Archiving");
+        reporter.step("Transferring");
+        reporter.bytes(ByteSize::MIB, 2 * ByteSize::MIB);
+        // Extra steps stay at the total.
+        reporter.step("Done");
+        assert_eq!(
+            *updates.lock().unwrap(),
+            vec![
+                "migrate [1/2] Archiving",
+                "migrate [2/2] Transferring",
+                "migrate [2/2] Transferring (1.00 MiB / 2.00 MiB)",
+                "migrate [2/2] Done",
+            ]
+        );
+        // Without a Progress nothing happens.
+        Reporter::new(None, "backup", 1).step("Archiving");
+    }
+}
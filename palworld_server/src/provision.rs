@@ -0,0 +1,361 @@
+//! Turns a bare Ubuntu host into a running Palworld server over SSH.
+//!
+//! [install] installs SteamCMD, downloads the dedicated server, generates a
+//! `PalWorldSettings.ini` with RCON enabled and a random password, writes a systemd unit
+//! and opens the game port with ufw. The SSH user needs to be root or have passwordless
+//! sudo, see [InstallOptions::sudo].
+//!
+//! # Example:
+//! ```no_run
+//! use palworld_server::provision::{self, InstallOptions};
+//! use palworld_server::ssh::PalworldConnection;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let connection = PalworldConnection::new("203.0.113.10:22", "root", "MySSHPassword");
+//!     let installation = provision::install(&connection, &InstallOptions::new())
+//!         .await
+//!         .unwrap();
+//!     println!("RCON password: {}", installation.rcon_password.expose());
+//! }
+//! ```
+
+use anyhow::{bail, Result};
+
+use crate::config::{WorldSettings, SETTINGS_SECTION};
+use crate::firewall::{Firewall, FirewallBackend, Protocol};
+use crate::progress::Reporter;
+use crate::secret::Secret;
+use crate::ssh::{
+    change_privileged, run_privileged, shell_quote, CommandResult, PalworldConnection,
+};
+
+/// Steam app id of the Palworld dedicated server.
+pub const PALWORLD_SERVER_APP_ID: u32 = 2394010;
+
+/// Where the steamcmd package installs the binary.
+pub static STEAMCMD_PATH: &str = "/usr/games/steamcmd";
+
+/// Options of [install].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct InstallOptions {
+    /// Unprivileged user the server runs as, created if missing.
+    pub user: String,
+    /// Directory the server is downloaded to.
+    pub install_dir: String,
+    /// Name of the systemd service.
+    pub service: String,
+    /// UDP port players connect to.
+    pub game_port: u16,
+    pub rcon_port: u16,
+    /// RCON password, a random one is generated on the host if None.
+    pub rcon_password: Option<Secret<String>>,
+    /// Allow the RCON port through the firewall. Off by default, RCON is unencrypted so
+    /// prefer [crate::rcon::PalworldRCON::via_ssh].
+    pub public_rcon: bool,
+    /// Only allow this address or network through to the RCON port, overrides
+    /// [InstallOptions::public_rcon].
+    pub rcon_allowed_from: Option<String>,
+    /// Install and configure ufw, allowing SSH and the game port.
+    pub firewall: bool,
+    /// Run privileged commands with `sudo -n`, for SSH users other than root.
+    pub sudo: bool,
+}
+
+impl InstallOptions {
+    pub fn new() -> Self {
+        Self {
+            user: "steam".to_string(),
+            install_dir: "/home/steam/PalServer".to_string(),
+            service: "palworld".to_string(),
+            game_port: 8211,
+            rcon_port: 25575,
+            rcon_password: None,
+            public_rcon: false,
+            rcon_allowed_from: None,
+            firewall: true,
+            sudo: false,
+        }
+    }
+
+    /// Path of the settings file the server reads.
+    pub fn settings_path(&self) -> String {
+        format!(
+            "{}/Pal/Saved/Config/LinuxServer/PalWorldSettings.ini",
+            self.install_dir
+        )
+    }
+}
+
+impl Default for InstallOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A finished installation.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct Installation {
+    pub install_dir: String,
+    pub settings_path: String,
+    pub service: String,
+    pub rcon_port: u16,
+    pub rcon_password: Secret<String>,
+}
+
+/// Installs and starts the server, see the [module documentation](self). Running it
+/// again updates the server and rewrites the unit and settings.
+pub async fn install(
+    connection: &PalworldConnection,
+    options: &InstallOptions,
+) -> Result<Installation> {
+    let user = shell_quote(&options.user);
+    let install_dir = shell_quote(&options.install_dir);
+    let steps = match options.firewall {
+        true => 5,
+        false => 4,
+    };
+    let mut progress = Reporter::new(connection.progress.clone(), "install", steps);
+
+    progress.step("Installing SteamCMD");
+    run(
+        connection,
+        options,
+        "add-apt-repository -y multiverse && dpkg --add-architecture i386 && apt-get update",
+    )
+    .await?;
+    run(
+        connection,
+        options,
+        "echo steam steam/question select 'I AGREE' | debconf-set-selections && \
+        echo steam steam/license note '' | debconf-set-selections && \
+        DEBIAN_FRONTEND=noninteractive apt-get install -y steamcmd ufw",
+    )
+    .await?;
+    run(
+        connection,
+        options,
+        &format!("id -u {user} >/dev/null 2>&1 || useradd -m -s /bin/bash {user}"),
+    )
+    .await?;
+
+    progress.step(format!(
+        "Downloading the dedicated server to {}",
+        options.install_dir
+    ));
+    run(
+        connection,
+        options,
+        &format!(
+            "install -d -o {user} -g {user} {install_dir} && \
+            su - {user} -c {}",
+            shell_quote(&steamcmd_update(&options.install_dir))
+        ),
+    )
+    .await?;
+
+    progress.step(format!("Writing {}", options.settings_path()));
+    let rcon_password = match &options.rcon_password {
+        Some(password) => password.clone(),
+        None => Secret::new(generate_password(connection, options).await?),
+    };
+    let defaults = match read(
+        connection,
+        options,
+        &format!("cat {install_dir}/DefaultPalWorldSettings.ini"),
+    )
+    .await
+    {
+        Ok(defaults) => defaults.output,
+        // Only downloaded by the recorded steamcmd command.
+        Err(e) if connection.dry_run.is_some() => {
+            log::warn!("Dry run: no default settings yet, {e}");
+            format!("{SETTINGS_SECTION}\nOptionSettings=()\n")
+        }
+        Err(e) => return Err(e),
+    };
+    let settings = configure_settings(&defaults, options, rcon_password.expose())?;
+    let settings_path = options.settings_path();
+    let settings_dir = settings_path
+        .rsplit_once('/')
+        .map(|(dir, _)| dir)
+        .unwrap_or(".");
+    run(
+        connection,
+        options,
+        &format!(
+            "install -d -o {user} -g {user} {dir} && printf '%s' {settings} > {path} && \
+            chown {user}:{user} {path} && chmod 600 {path}",
+            dir = shell_quote(settings_dir),
+            settings = shell_quote(&settings.to_ini()),
+            path = shell_quote(&settings_path),
+        ),
+    )
+    .await?;
+
+    progress.step(format!("Installing the {} service", options.service));
+    let unit_path = format!("/etc/systemd/system/{}.service", options.service);
+    run(
+        connection,
+        options,
+        &format!(
+            "printf '%s' {} > {} && systemctl daemon-reload && systemctl enable --now {}",
+            shell_quote(&systemd_unit(options)),
+            shell_quote(&unit_path),
+            shell_quote(&options.service)
+        ),
+    )
+    .await?;
+
+    if options.firewall {
+        progress.step("Configuring the firewall");
+        let mut firewall = Firewall::new(connection.clone());
+        firewall.backend = Some(FirewallBackend::Ufw);
+        firewall.sudo = options.sudo;
+        let ssh_port = connection
+            .hostname
+            .rsplit_once(':')
+            .and_then(|(_, port)| port.parse().ok())
+            .unwrap_or(22);
+        firewall.open_port(ssh_port, Protocol::Tcp).await?;
+        firewall.open_port(options.game_port, Protocol::Udp).await?;
+        match &options.rcon_allowed_from {
+            Some(source) => firewall.restrict_rcon_to(source, options.rcon_port).await?,
+            None if options.public_rcon => {
+                firewall.open_port(options.rcon_port, Protocol::Tcp).await?
+            }
+            None => (),
+        }
+        firewall.enable().await?;
+    }
+
+    Ok(Installation {
+        install_dir: options.install_dir.clone(),
+        settings_path,
+        service: options.service.clone(),
+        rcon_port: options.rcon_port,
+        rcon_password,
+    })
+}
+
+/// SteamCMD command line that installs or updates the server in `install_dir`.
+pub fn steamcmd_update(install_dir: &str) -> String {
+    format!(
+        "{STEAMCMD_PATH} +force_install_dir {} +login anonymous +app_update {PALWORLD_SERVER_APP_ID} validate +quit",
+        shell_quote(install_dir)
+    )
+}
+
+/// The systemd unit running the server as [InstallOptions::user].
+pub fn systemd_unit(options: &InstallOptions) -> String {
+    format!(
+        "[Unit]\n\
+        Description=Palworld dedicated server\n\
+        Wants=network-online.target\n\
+        After=network-online.target\n\
+        \n\
+        [Service]\n\
+        User={user}\n\
+        WorkingDirectory={dir}\n\
+        ExecStart={dir}/PalServer.sh -port={port} -useperfthreads -NoAsyncLoadingThread -UseMultithreadForDS\n\
+        Restart=on-failure\n\
+        RestartSec=10\n\
+        \n\
+        [Install]\n\
+        WantedBy=multi-user.target\n",
+        user = options.user,
+        dir = options.install_dir,
+        port = options.game_port,
+    )
+}
+
+/// Enables RCON in the server's default settings.
+pub fn configure_settings(
+    defaults: &str,
+    options: &InstallOptions,
+    rcon_password: &str,
+) -> Result<WorldSettings> {
+    let mut settings = WorldSettings::parse(defaults)?;
+    settings.set("RCONEnabled", "True");
+    settings.set("RCONPort", options.rcon_port.to_string());
+    settings.set("PublicPort", options.game_port.to_string());
+    settings.set_quoted("AdminPassword", rcon_password);
+    Ok(settings)
+}
+
+/// 24 random alphanumeric characters from the host's `/dev/urandom`.
+async fn generate_password(
+    connection: &PalworldConnection,
+    options: &InstallOptions,
+) -> Result<String> {
+    let result = read(
+        connection,
+        options,
+        "tr -dc 'A-Za-z0-9' < /dev/urandom | head -c 24",
+    )
+    .await?;
+    let password = result.output.trim().to_string();
+    if password.len() != 24 {
+        bail!("Failed to generate a password on the host");
+    }
+    Ok(password)
+}
+
+/// Runs a command changing the host, see [crate::dryrun].
+async fn run(
+    connection: &PalworldConnection,
+    options: &InstallOptions,
+    command: &str,
+) -> Result<CommandResult> {
+    change_privileged(connection, options.sudo, command).await
+}
+
+/// Runs a command that only reads, also in a dry run.
+async fn read(
+    connection: &PalworldConnection,
+    options: &InstallOptions,
+    command: &str,
+) -> Result<CommandResult> {
+    run_privileged(connection, options.sudo, command).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_configure_settings() {
+        let defaults = "; comment\n[/Script/Pal.PalGameWorldSettings]\n\
+            OptionSettings=(ServerName=\"Default Palworld Server\",AdminPassword=\"\",PublicPort=8211,RCONEnabled=False,RCONPort=25575)\n";
+        let mut options = InstallOptions::new();
+        options.rcon_port = 25576;
+        let settings = configure_settings(defaults, &options, "s3cret").unwrap();
+        assert_eq!(settings.get("RCONEnabled"), Some("True"));
+        assert_eq!(settings.get("RCONPort"), Some("25576"));
+        assert!(settings.to_ini().contains("AdminPassword=\"s3cret\""));
+
+        let settings = configure_settings(
+            "[/Script/Pal.PalGameWorldSettings]\nOptionSettings=(ServerName=\"x\")\n",
+            &options,
+            "s3cret",
+        )
+        .unwrap();
+        assert!(settings.to_ini().contains("AdminPassword=\"s3cret\""));
+        assert!(configure_settings("", &options, "s3cret").is_err());
+    }
+
+    #[test]
+    fn test_systemd_unit() {
+        let unit = systemd_unit(&InstallOptions::new());
+        assert!(unit.contains("User=steam\n"));
+        assert!(unit.contains("ExecStart=/home/steam/PalServer/PalServer.sh -port=8211 "));
+        assert_eq!(
+            steamcmd_update("/home/steam/Pal Server"),
+            "/usr/games/steamcmd +force_install_dir '/home/steam/Pal Server' +login anonymous +app_update 2394010 validate +quit"
+        );
+    }
+}
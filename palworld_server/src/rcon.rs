@@ -18,37 +18,432 @@
 //! }
 //! ```
 
-use std::time::Duration;
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
 
-use anyhow::Result;
-use rcon;
-use regex::Regex;
-use tokio;
-use serde::{Deserialize, Serialize};
+use anyhow::{Context, Result};
+
+use crate::command::{self, Command};
+use crate::message::{self, ResponseEncoding};
+use crate::parse;
+use crate::secret::Secret;
+#[cfg(feature = "ssh")]
+use crate::ssh::{PalworldConnection, PortForward};
+use crate::trace;
+use crate::verbosity;
+pub use crate::models::{BroadcastRecord, PlayerInfo, ServerInfo};
 
 /// Default Source Engine port, Palworld uses the same port also.
 pub static DEFAULT_SOURCE_PORT: u16 = 25575;
 
-/// Representation of /showplayers rcon command
-#[derive(Debug, Serialize, Deserialize)]
-pub struct PlayerInfo {
-    /// Player's name.
-    pub name: String,
-    /// Player's Unique ID inside the server.
-    pub uid: String,
-    /// Player's Steam ID.
-    pub steamid: String,
+/// Time a server gets to answer with the full Source protocol while detecting the protocol.
+pub const PROTOCOL_PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Protocols detected by `host:port`, kept for the life of the process.
+static DETECTED_PROTOCOLS: OnceLock<Mutex<HashMap<String, RconProtocol>>> = OnceLock::new();
+
+/// Addresses of a `host:port` and when they were resolved.
+type Resolved = (Vec<SocketAddr>, Instant);
+
+/// Resolutions kept for [PalworldRCON::resolve_interval].
+static RESOLVED: OnceLock<Mutex<HashMap<String, Resolved>>> = OnceLock::new();
+
+/// Server, password and command of a query.
+type FlightKey = (String, u16, Secret<String>, String);
+type Flight = Arc<tokio::sync::OnceCell<Result<String, Arc<anyhow::Error>>>>;
+
+/// Queries waiting for a response, see [PalworldRCON::query].
+static IN_FLIGHT: OnceLock<Mutex<HashMap<FlightKey, Flight>>> = OnceLock::new();
+
+/// Connections [set_connections_per_host] allows to a server unless changed, Palworld drops
+/// or mixes up responses with more at once.
+pub const DEFAULT_CONNECTIONS_PER_HOST: usize = 1;
+
+/// Open connections allowed by `host:port`, shared by every client in the process.
+static CONNECTION_LIMITS: OnceLock<Mutex<HashMap<String, Arc<tokio::sync::Semaphore>>>> =
+    OnceLock::new();
+
+fn connection_limit(host: &str, port: u16) -> Arc<tokio::sync::Semaphore> {
+    let limits = CONNECTION_LIMITS.get_or_init(Default::default);
+    let mut limits = limits.lock().unwrap();
+    limits
+        .entry(format!("{host}:{port}"))
+        .or_insert_with(|| Arc::new(tokio::sync::Semaphore::new(DEFAULT_CONNECTIONS_PER_HOST)))
+        .clone()
+}
+
+/// Allows `limit` (at least 1) connections at once to `host:port` from this process, across
+/// every [PalworldRCON] using that host and port. Connections open at the time of the call
+/// still count against the old limit until they close.
+pub fn set_connections_per_host(host: &str, port: u16, limit: usize) {
+    let limits = CONNECTION_LIMITS.get_or_init(Default::default);
+    let semaphore = Arc::new(tokio::sync::Semaphore::new(limit.max(1)));
+    limits
+        .lock()
+        .unwrap()
+        .insert(format!("{host}:{port}"), semaphore);
+}
+
+/// Connections to `host:port` that could be opened right now without waiting.
+pub fn available_connections(host: &str, port: u16) -> usize {
+    connection_limit(host, port).available_permits()
+}
+
+/// Waits until a connection to `host:port` is allowed, the connection may be open until
+/// the permit is dropped.
+pub(crate) async fn connection_permit(host: &str, port: u16) -> tokio::sync::OwnedSemaphorePermit {
+    connection_limit(host, port)
+        .acquire_owned()
+        .await
+        .expect("connection limits are never closed")
+}
+
+/// Called with every broadcast a client sends, see [PalworldRCON::broadcast_hook]. The
+/// initiator is the trigger of the current [trace], `unknown` outside one. With the `store`
+/// feature `SessionStore::broadcast_hook` keeps a history.
+#[derive(Clone)]
+pub struct BroadcastHook(Arc<dyn Fn(&BroadcastRecord) + Send + Sync>);
+
+impl BroadcastHook {
+    pub fn new(hook: impl Fn(&BroadcastRecord) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(hook))
+    }
+
+    /// Calls the hook if `cmd` is a broadcast that was sent, with `message` or else the text
+    /// as sent.
+    pub(crate) fn sent(&self, cmd: &str, message: Option<&str>) {
+        let Some((name, sent)) = cmd.trim().split_once(' ') else {
+            return;
+        };
+        if !name.eq_ignore_ascii_case(command::Broadcast::NAME) {
+            return;
+        }
+        let initiator =
+            trace::current().map_or("unknown".to_string(), |context| context.trigger.to_string());
+        (self.0)(&BroadcastRecord {
+            message: message.unwrap_or(sent).to_string(),
+            initiator,
+            sent_at: SystemTime::now(),
+        });
+    }
+}
+
+impl std::fmt::Debug for BroadcastHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("BroadcastHook")
+    }
+}
+
+impl PartialEq for BroadcastHook {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// RCON errors callers may want to handle, returned inside [anyhow::Error] and
+/// matched with `downcast_ref::<RconError>()`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RconError {
+    /// The password is empty, Palworld disables RCON without an AdminPassword.
+    EmptyPassword,
+    /// The password contains a control character that can't be sent, like NUL.
+    InvalidPasswordCharacter(char),
+    /// The server rejected the password.
+    AuthFailed,
+    /// The [CommandPolicy] doesn't allow sending the command.
+    PolicyDenied(String),
+    /// The player list didn't fit in a response, even after retries. Holds the players
+    /// that were complete in any of the responses.
+    Truncated(Vec<PlayerInfo>),
+    /// No online player matches the name, UID or Steam ID.
+    PlayerNotFound(String),
+    /// Several online players match the name, see [match_players].
+    AmbiguousPlayer {
+        query: String,
+        matches: Vec<PlayerInfo>,
+    },
+}
+
+impl std::fmt::Display for RconError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::EmptyPassword => write!(f, "RCON password is empty"),
+            Self::InvalidPasswordCharacter(c) => {
+                write!(f, "RCON password contains invalid character {c:?}")
+            }
+            Self::AuthFailed => write!(
+                f,
+                "RCON authentication failed, check the password matches AdminPassword"
+            ),
+            Self::PolicyDenied(cmd) => write!(f, "Command '{cmd}' is denied by the policy"),
+            Self::Truncated(players) => {
+                write!(f, "Player list is truncated, got {} players", players.len())
+            }
+            Self::PlayerNotFound(query) => write!(f, "No player online matches '{query}'"),
+            Self::AmbiguousPlayer { query, matches } => {
+                let players: Vec<String> = matches
+                    .iter()
+                    .map(|p| format!("{} ({})", p.name, p.steamid))
+                    .collect();
+                write!(f, "'{query}' matches {}", players.join(", "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for RconError {}
+
+/// Checks the password can be sent over RCON. Spaces, `%` and non-ASCII characters
+/// are sent as UTF-8 unchanged.
+///
+/// # Example:
+/// ```
+/// use palworld_server::rcon::{validate_password, RconError};
+///
+/// assert!(validate_password("p@ss w%rd ü").is_ok());
+/// let error = validate_password("").unwrap_err();
+/// assert_eq!(error.downcast_ref::<RconError>(), Some(&RconError::EmptyPassword));
+/// ```
+pub fn validate_password(password: &str) -> Result<()> {
+    if password.is_empty() {
+        return Err(RconError::EmptyPassword.into());
+    }
+    match password.chars().find(|c| c.is_control()) {
+        Some(c) => Err(RconError::InvalidPasswordCharacter(c).into()),
+        None => Ok(()),
+    }
+}
+
+/// Reads a password from a file, dropping the trailing newline editors add.
+/// Other whitespace is kept since it may be part of the password.
+pub fn read_password_file(path: impl AsRef<Path>) -> Result<String> {
+    let path = path.as_ref();
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read password file {}", path.display()))?;
+    parse_password_file(&contents)
+}
+
+/// The password in the contents of a password file, see [read_password_file]. For contents
+/// read some other way, like decrypted.
+pub fn parse_password_file(contents: &str) -> Result<String> {
+    let password = contents
+        .strip_suffix('\n')
+        .map(|p| p.strip_suffix('\r').unwrap_or(p))
+        .unwrap_or(contents);
+    validate_password(password)?;
+    Ok(password.to_string())
+}
+
+/// Deviations from the Source RCON protocol the connection allows for. By default the quirks
+/// are detected on the first connection to a host, see [PalworldRCON::detect_protocol]. The
+/// auth packet id isn't configurable, the rcon crate sends a fixed one that Palworld accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct RconProtocol {
+    /// Detect [RconProtocol::factorio_quirks] once per host instead of using the value set
+    /// here. Turn it off to skip the extra connection when the server is known.
+    pub detect: bool,
+    /// Take the first response packet as the whole response. Palworld answers every command
+    /// with a single packet and never answers the empty packet Source clients send to find the
+    /// end of a multi-packet response, so without this commands hang. Turn it off for proxies
+    /// that speak the full Source protocol and split long responses, like player lists of
+    /// big servers.
+    pub factorio_quirks: bool,
+    /// Pause briefly after each command and reject commands longer than Minecraft accepts,
+    /// for proxies that drop packets sent back to back.
+    pub minecraft_quirks: bool,
+    /// Encoding responses are decoded with before parsing, see [message::decode_bytes].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub encoding: ResponseEncoding,
+}
+
+impl RconProtocol {
+    pub fn new() -> Self {
+        Self {
+            detect: true,
+            factorio_quirks: true,
+            minecraft_quirks: false,
+            encoding: ResponseEncoding::Utf8,
+        }
+    }
+}
+
+impl Default for RconProtocol {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Commands a [PalworldRCON] may send, for exposing it to users who shouldn't be able to
+/// stop the server. Patterns ignore case and `*` matches anything. A pattern matches the whole
+/// command or just its name, so `DoExit` also matches `DoExit now`.
+///
+/// # Example:
+/// ```
+/// use palworld_server::rcon::{CommandPolicy, RconError};
+///
+/// let mut policy = CommandPolicy::new();
+/// policy.allow = vec!["ShowPlayers".to_string(), "Broadcast *".to_string()];
+/// policy.deny = vec!["Broadcast *admin*".to_string()];
+/// assert!(policy.check("showplayers").is_ok());
+/// assert!(policy.check("Broadcast Restart_soon").is_ok());
+/// let error = policy.check("DoExit").unwrap_err();
+/// assert_eq!(
+///     error.downcast_ref::<RconError>(),
+///     Some(&RconError::PolicyDenied("DoExit".to_string()))
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct CommandPolicy {
+    /// Only commands matching one of these are sent, empty allows every command.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub allow: Vec<String>,
+    /// Commands matching one of these are never sent, even when allowed.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub deny: Vec<String>,
+}
+
+impl CommandPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns [RconError::PolicyDenied] unless `cmd` may be sent.
+    pub fn check(&self, cmd: &str) -> Result<()> {
+        let cmd = cmd.trim();
+        let name = cmd.split_whitespace().next().unwrap_or_default();
+        let matches = |pattern: &String| {
+            let pattern = pattern.trim().to_lowercase();
+            glob_match(&pattern, &cmd.to_lowercase()) || glob_match(&pattern, &name.to_lowercase())
+        };
+        let allowed = self.allow.is_empty() || self.allow.iter().any(matches);
+        match allowed && !self.deny.iter().any(matches) {
+            true => Ok(()),
+            false => Err(RconError::PolicyDenied(cmd.to_string()).into()),
+        }
+    }
+
+    /// A policy allowing only the commands both `self` and `other` allow. Allow patterns of
+    /// one are kept when the other allows them as a command, which is exact for allow lists
+    /// of command names and stricter for patterns that only overlap.
+    pub fn intersect(&self, other: &CommandPolicy) -> CommandPolicy {
+        let mut deny: Vec<String> = self.deny.iter().chain(&other.deny).cloned().collect();
+        let allow = match (self.allow.is_empty(), other.allow.is_empty()) {
+            (true, _) => other.allow.clone(),
+            (false, true) => self.allow.clone(),
+            (false, false) => {
+                let kept = |from: &[String], by: &CommandPolicy| -> Vec<String> {
+                    from.iter()
+                        .filter(|pattern| by.check(pattern).is_ok())
+                        .cloned()
+                        .collect()
+                };
+                let mut allow = kept(&self.allow, other);
+                allow.extend(kept(&other.allow, self));
+                allow.sort();
+                allow.dedup();
+                // An empty allow list would allow everything.
+                if allow.is_empty() {
+                    deny.push("*".to_string());
+                }
+                allow
+            }
+        };
+        CommandPolicy { allow, deny }
+    }
+}
+
+/// Players matching `query`. A player whose Steam ID, UID or name equals it is preferred,
+/// then names equal ignoring case, then names starting with it ignoring case.
+///
+/// # Example:
+/// ```
+/// use palworld_server::rcon::{match_players, PlayerInfo};
+///
+/// let player = |name: &str, steamid: &str| PlayerInfo {
+///     name: name.to_string(),
+///     uid: "0".to_string(),
+///     steamid: steamid.to_string(),
+/// };
+/// let players = vec![player("Shadow", "76561190000000001"), player("Shade", "76561190000000002")];
+/// assert_eq!(match_players(&players, "shadow")[0].steamid, "76561190000000001");
+/// assert_eq!(match_players(&players, "sha").len(), 2);
+/// ```
+pub fn match_players<'a>(players: &'a [PlayerInfo], query: &str) -> Vec<&'a PlayerInfo> {
+    let query = query.trim();
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let lowercase = query.to_lowercase();
+    let matchers: [&dyn Fn(&PlayerInfo) -> bool; 3] = [
+        &|p| p.steamid == query || p.uid == query || p.name == query,
+        &|p| p.name.to_lowercase() == lowercase,
+        &|p| p.name.to_lowercase().starts_with(&lowercase),
+    ];
+    matchers
+        .iter()
+        .map(|matches| players.iter().filter(|p| matches(p)).collect::<Vec<_>>())
+        .find(|found| !found.is_empty())
+        .unwrap_or_default()
+}
+
+/// Matches `text` against `pattern` where `*` matches any characters.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
 }
 
 /// Palworld Server RCON
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
 pub struct PalworldRCON {
     /// Server hostname or IP address. "localhost" or "127.0.0.1" for the same machine.
+    /// See [PalworldRCON::resolve_interval] for how often hostnames are resolved.
     pub host: String,
     /// Server port, typically (DEFAULT_SOURCE_PORT).
     pub port: u16,
-    /// Server RCON password.
-    pub password: String,
+    /// Server RCON password, redacted in `Debug` output.
+    pub password: Secret<String>,
+    /// Protocol quirks, detected by default.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub protocol: RconProtocol,
+    /// Commands [PalworldRCON::send_command] refuses to send, None sends every command.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub policy: Option<CommandPolicy>,
+    /// Base URL of the server's REST API, like `http://palworld.lan:8212`. With the `rest`
+    /// feature, player lists too long for RCON are fetched from it instead.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub rest_url: Option<String>,
+    /// Called with every broadcast sent through the client, its sessions and batches.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub broadcast_hook: Option<BroadcastHook>,
+    /// How long resolved addresses of [PalworldRCON::host] are reused, shared by every client
+    /// in the process. None resolves on every connection, so dynamic DNS changes are picked
+    /// up right away. A failed connection always resolves again.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub resolve_interval: Option<Duration>,
 }
 
 impl PalworldRCON {
@@ -56,7 +451,7 @@ impl PalworldRCON {
     ///
     /// # Example:
     /// ```
-    /// use palworld_server::rcon::{PalworldRCON, DEFAULT_SOURCE_PORT};
+    /// use palworld_server::rcon::{PalworldRCON, RconProtocol, DEFAULT_SOURCE_PORT};
     ///
     /// #[tokio::main]
     /// async fn main() {
@@ -65,41 +460,255 @@ impl PalworldRCON {
     ///     assert_eq!(rcon,
     ///         PalworldRCON {
     ///             host: "localhost".to_string(),
-    ///             port: port,
-    ///             password: "MyRCONPassword".to_string()
+    ///             port,
+    ///             password: "MyRCONPassword".into(),
+    ///             protocol: RconProtocol::new(),
+    ///             policy: None,
+    ///             rest_url: None,
+    ///             broadcast_hook: None,
+    ///             resolve_interval: None,
     ///     });
     /// }
     /// ```
     pub fn new(host: impl Into<String>, port: u16, password: impl Into<String>) -> Self {
         Self {
             host: host.into(),
-            port: port,
-            password: password.into(),
+            port,
+            password: Secret::new(password.into()),
+            protocol: RconProtocol::new(),
+            policy: None,
+            rest_url: None,
+            broadcast_hook: None,
+            resolve_interval: None,
+        }
+    }
+
+    /// Connects through an SSH tunnel, for servers where the RCON port only listens on
+    /// localhost. RCON commands work as long as the returned [PortForward] is kept alive.
+    ///
+    /// # Example:
+    /// ```no_run
+    /// use palworld_server::rcon::{PalworldRCON, DEFAULT_SOURCE_PORT};
+    /// use palworld_server::ssh::PalworldConnection;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let ssh = PalworldConnection::new("palworld.lan:22", "steam", "MySSHPassword");
+    ///     let (rcon, _forward) = PalworldRCON::via_ssh(&ssh, DEFAULT_SOURCE_PORT, "MyRCONPassword")
+    ///         .await
+    ///         .unwrap();
+    ///     println!("{}", rcon.get_version().await.unwrap());
+    /// }
+    /// ```
+    #[cfg(feature = "ssh")]
+    pub async fn via_ssh(
+        connection: &PalworldConnection,
+        port: u16,
+        password: impl Into<String>,
+    ) -> Result<(Self, PortForward)> {
+        let forward = connection.forward_port(port, 0).await?;
+        let rcon = Self::new("127.0.0.1", forward.local_port(), password);
+        Ok((rcon, forward))
+    }
+
+    /// Resolves [PalworldRCON::host] to the addresses the next connection will try, reusing
+    /// the last resolution until [PalworldRCON::resolve_interval] has passed.
+    pub async fn resolve(&self) -> Result<Vec<SocketAddr>> {
+        let key = format!("{}:{}", self.host, self.port);
+        let resolved = RESOLVED.get_or_init(Default::default);
+        if let Some(interval) = self.resolve_interval {
+            if let Some((addresses, at)) = resolved.lock().unwrap().get(&key) {
+                if at.elapsed() < interval {
+                    return Ok(addresses.clone());
+                }
+            }
+        }
+        let addresses = tokio::net::lookup_host((self.host.as_str(), self.port))
+            .await
+            .with_context(|| format!("Failed to resolve {}", self.host))?
+            .collect::<Vec<SocketAddr>>();
+        log::debug!("Resolved {} to {addresses:?}", self.host);
+        resolved
+            .lock()
+            .unwrap()
+            .insert(key, (addresses.clone(), Instant::now()));
+        Ok(addresses)
+    }
+
+    /// Drops the addresses [PalworldRCON::resolve] keeps for this host.
+    fn forget_resolved(&self) {
+        if let Some(resolved) = RESOLVED.get() {
+            resolved
+                .lock()
+                .unwrap()
+                .remove(&format!("{}:{}", self.host, self.port));
+        }
+    }
+
+    /// Protocol the next connection uses, detected on the first call for a host when
+    /// [RconProtocol::detect] is set.
+    pub async fn effective_protocol(&self) -> Result<RconProtocol> {
+        if !self.protocol.detect {
+            return Ok(self.protocol);
+        }
+        let key = format!("{}:{}", self.host, self.port);
+        let detected = DETECTED_PROTOCOLS.get_or_init(Default::default);
+        if let Some(protocol) = detected.lock().unwrap().get(&key) {
+            return Ok(RconProtocol {
+                detect: false,
+                factorio_quirks: protocol.factorio_quirks,
+                ..self.protocol
+            });
+        }
+        let protocol = self.detect_protocol().await?;
+        detected.lock().unwrap().insert(key, protocol);
+        Ok(protocol)
+    }
+
+    /// Probes how the server speaks RCON. Palworld answers `info` with its version in a
+    /// single packet and never sends the empty packets of the full Source protocol, so it
+    /// gets [RconProtocol::factorio_quirks]. Anything else, like an RCON proxy, is asked
+    /// again with the full protocol and keeps it if it answers within
+    /// [PROTOCOL_PROBE_TIMEOUT], so long responses split over packets arrive whole.
+    pub async fn detect_protocol(&self) -> Result<RconProtocol> {
+        let single_packet = RconProtocol {
+            detect: false,
+            factorio_quirks: true,
+            ..self.protocol
+        };
+        let address = format!("{}:{}", self.host, self.port);
+        let info = self.send_command_with(single_packet, "info").await?;
+        if parse::parse_version(&info).is_ok() {
+            log::debug!("{address} is a Palworld server");
+            return Ok(single_packet);
+        }
+        let source = RconProtocol {
+            factorio_quirks: false,
+            ..single_packet
+        };
+        let probe = self.send_command_with(source, "info");
+        match tokio::time::timeout(PROTOCOL_PROBE_TIMEOUT, probe).await {
+            Ok(Ok(_)) => {
+                log::debug!("{address} speaks the full Source protocol");
+                Ok(source)
+            }
+            Ok(Err(e)) => {
+                log::debug!("{address} failed the full Source protocol: {e}");
+                Ok(single_packet)
+            }
+            Err(_) => {
+                log::debug!("{address} didn't answer the full Source protocol");
+                Ok(single_packet)
+            }
         }
     }
 
     /// Connect to the server.
-    async fn connect(&self) -> Result<rcon::Connection<tokio::net::TcpStream>> {
-        let host = format!("{}:{}", self.host, self.port);
-        let connection = <rcon::Connection<tokio::net::TcpStream>>::builder()
-            .enable_factorio_quirks(true)
-            .connect(host.as_str(), self.password.as_str())
-            .await?;
+    pub(crate) async fn connect(
+        &self,
+        protocol: RconProtocol,
+    ) -> Result<RconConnection> {
+        validate_password(self.password.expose())?;
+        let addresses = self.resolve().await?;
+        let stream = match tokio::net::TcpStream::connect(addresses.as_slice()).await {
+            Ok(stream) => stream,
+            Err(e) => {
+                self.forget_resolved();
+                return Err(e.into());
+            }
+        };
+        let connection = RconConnection::builder()
+            .enable_factorio_quirks(protocol.factorio_quirks)
+            .enable_minecraft_quirks(protocol.minecraft_quirks)
+            .handshake(
+                DecodingStream::new(stream, protocol.encoding),
+                self.password.expose().as_str(),
+            )
+            .await
+            .map_err(|e| match e {
+                rcon::Error::Auth => anyhow::Error::new(RconError::AuthFailed),
+                e => e.into(),
+            })?;
         Ok(connection)
     }
 
     /// Sends a command to the server via RCON. Returns a string of the command result.
+    /// Commands denied by [PalworldRCON::policy] return [RconError::PolicyDenied] without
+    /// connecting.
     pub async fn send_command(&self, cmd: impl Into<&str>) -> Result<String> {
-        let mut conn = self.connect().await?;
+        self.send(cmd.into(), None).await
+    }
+
+    /// Sends `cmd`, passing a broadcast to [PalworldRCON::broadcast_hook] with `message` if
+    /// given, the text as sent otherwise.
+    async fn send(&self, cmd: &str, message: Option<&str>) -> Result<String> {
+        if let Some(policy) = &self.policy {
+            policy.check(cmd)?;
+        }
+        let protocol = self.effective_protocol().await?;
+        log::debug!("{}RCON command '{cmd}'", trace::log_prefix());
+        let response = self.send_command_with(protocol, cmd).await?;
+        if verbosity::verbosity().logs_responses() {
+            let prefix = trace::log_prefix();
+            log::debug!("{prefix}RCON response to '{cmd}': {}", response.trim_end());
+        }
+        if let Some(hook) = &self.broadcast_hook {
+            hook.sent(cmd, message);
+        }
+        Ok(response)
+    }
 
-        Ok(conn.cmd(cmd.into()).await?)
+    async fn send_command_with(&self, protocol: RconProtocol, cmd: &str) -> Result<String> {
+        // Held until the response is read, the connection closes when it is dropped.
+        let _permit = connection_permit(&self.host, self.port).await;
+        let mut conn = self.connect(protocol).await?;
+        run_command(&mut conn, protocol, cmd).await
+    }
+
+    /// Sends a command without side effects, like `showplayers`. Callers sending the same
+    /// query to the same server while it is waiting for a response share that response
+    /// instead of each making a round trip.
+    pub async fn query(&self, cmd: &str) -> Result<String> {
+        let key = (
+            self.host.clone(),
+            self.port,
+            self.password.clone(),
+            cmd.to_string(),
+        );
+        single_flight(key, self.send_command(cmd)).await
+    }
+
+    /// Sends a typed [Command] and parses its response, read-only commands go through
+    /// [PalworldRCON::query].
+    ///
+    /// # Example:
+    /// ```no_run
+    /// use palworld_server::command::Save;
+    /// use palworld_server::rcon::{PalworldRCON, DEFAULT_SOURCE_PORT};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let rcon = PalworldRCON::new("localhost", DEFAULT_SOURCE_PORT, "MyRCONPassword");
+    ///     assert!(rcon.execute(&Save).await.unwrap());
+    /// }
+    /// ```
+    pub async fn execute<C: Command>(&self, command: &C) -> Result<C::Response> {
+        let cmd = command.encode();
+        let raw = match C::READ_ONLY {
+            true => self.query(&cmd).await?,
+            false => self.send_command(cmd.as_str()).await?,
+        };
+        C::parse(&raw)
     }
 
     /// Sends a broadcast command to the server via RCON. Returns a string of the command result.
+    /// Sent broadcasts are passed to [PalworldRCON::broadcast_hook] as given, before spaces
+    /// were replaced.
     ///
     /// # Arguments:
     /// * `message` - The message to broadcast to the server
     /// * `replace_string` - Replace spaces with a String, as of v0.1.3 server this is needed.
+    ///   See [message::encode_broadcast].
     /// 
     /// # Example:
     /// ```
@@ -123,17 +732,18 @@ impl PalworldRCON {
         message: impl Into<String>,
         replace_space: Option<String>,
     ) -> Result<String> {
-        let message: String = message.into();
-        let message = match &replace_space {
-            Some(s) => message.replace(" ", s),
-            None => message,
-        };
-        let message = format!("broadcast {message}");
-        self.send_command(message.as_str()).await
+        let text = message.into();
+        let encoded = message::encode_broadcast(&text, replace_space.as_deref())?;
+        let command = format!("broadcast {encoded}");
+        self.send(&command, Some(&text)).await
     }
 
     /// Gets active player information. Returns a vector of [PlayerInfo].
     ///
+    /// On big servers the list may not fit in one RCON packet, and asking again over RCON
+    /// gets the same cut. Truncated lists are fetched from [PalworldRCON::rest_url] if set,
+    /// otherwise [RconError::Truncated] is returned with the players that were received.
+    ///
     /// # Example:
     /// ```
     /// use palworld_server::rcon::{PalworldRCON, DEFAULT_SOURCE_PORT};
@@ -152,67 +762,227 @@ impl PalworldRCON {
     /// }
     /// ```
     pub async fn get_player_info(&self) -> Result<Vec<PlayerInfo>> {
-        let results = self
-            .send_command("showplayers")
-            .await?
-            .split("\n")
-            .skip(1)
-            .filter_map(|info| {
-                let split = info.split(",").collect::<Vec<&str>>();
-                if split.len() != 3 {
-                    return None;
-                }
-                Some(PlayerInfo {
-                    name: split[0].to_string(),
-                    uid: split[1].to_string(),
-                    steamid: split[2].to_string(),
-                })
-            })
-            .collect::<Vec<PlayerInfo>>();
-
-        Ok(results)
+        let response = self.query("showplayers").await?;
+        if !parse::is_player_list_truncated(&response) {
+            return Ok(parse::parse_player_info(&response));
+        }
+        // The last line may be cut off in the middle of a Steam ID.
+        let complete = response.rfind('\n').map_or("", |end| &response[..=end]);
+        let players = parse::parse_player_info(complete);
+        log::debug!("Player list truncated at {} players", players.len());
+        #[cfg(all(feature = "rcon", feature = "rest"))]
+        if let Some(url) = &self.rest_url {
+            log::info!("Player list too long for RCON, using the REST API");
+            return crate::rest::RestApi::new(url, self.password.expose())
+                .players()
+                .await;
+        }
+        Err(RconError::Truncated(players).into())
     }
 
     /// Sends a save command to the server via RCON. Returns true if server successfully saved.
     pub async fn save(&self) -> Result<bool> {
-        let msg = self.send_command("save").await?;
-        Ok(msg.contains("Complete Save"))
+        self.execute(&command::Save).await
     }
 
-    /// Sends a save command to the server via RCON. Returns true if server successfully saved.
+    /// The online player matching `query`, a name, name prefix, UID or Steam ID as in
+    /// [match_players]. Returns [RconError::PlayerNotFound] or [RconError::AmbiguousPlayer]
+    /// listing the matches when it isn't exactly one player.
+    pub async fn find_player(&self, query: &str) -> Result<PlayerInfo> {
+        let players = self.get_player_info().await?;
+        match match_players(&players, query).as_slice() {
+            [] => Err(RconError::PlayerNotFound(query.to_string()).into()),
+            [player] => Ok((*player).clone()),
+            matches => Err(RconError::AmbiguousPlayer {
+                query: query.to_string(),
+                matches: matches.iter().map(|p| (*p).clone()).collect(),
+            }
+            .into()),
+        }
+    }
+
+    /// Kicks a player by Steam ID via RCON. Returns true if the server kicked the player.
+    pub async fn kick_player(&self, steamid: impl Into<String>) -> Result<bool> {
+        self.execute(&command::Kick::new(steamid)).await
+    }
+
+    /// Bans a player by Steam ID via RCON. Returns true if the server banned the player.
+    pub async fn ban_player(&self, steamid: impl Into<String>) -> Result<bool> {
+        self.execute(&command::Ban::new(steamid)).await
+    }
+
+    /// Shuts the server down after `delay`, 30 seconds if None, via RCON. Returns true if the
+    /// server confirmed it.
     pub async fn shutdown(&self, delay: Option<Duration>, msg: impl Into<String>) -> Result<bool> {
-        let cmd = format!(
-            "shutdown {} {}",
-            delay.unwrap_or(Duration::new(30, 0)).as_secs(),
-            msg.into()
-        );
-        let msg = self.send_command(cmd.as_str()).await?;
-        println!("shutdown msg: {}", msg);
-        Ok(msg.contains("The server will shut down in"))
+        self.execute(&command::Shutdown::new(delay, msg)).await
     }
 
     pub async fn get_version(&self) -> Result<String> {
         // Welcome to Pal Server[v0.1.3.0] Default Palworld Server
-        let result = self.send_command("info").await?;
-        let re = Regex::new(r"\[v[0-9]{1,9}\.[0-9]{1,9}\.[0-9]{1,9}\.[0-9]{1,9}\]")?;
-        if !re.is_match(&result) {
-            anyhow::bail!("Failed to find version in info RCON command")
+        let result = self.query("info").await?;
+        parse::parse_version(&result)
+    }
+
+    /// Version and name of the server.
+    pub async fn get_server_info(&self) -> Result<ServerInfo> {
+        self.execute(&command::Info).await
+    }
+}
+
+/// Sends `cmd` on an open connection and decodes the response.
+pub(crate) async fn run_command(
+    conn: &mut RconConnection,
+    protocol: RconProtocol,
+    cmd: &str,
+) -> Result<String> {
+    let response = conn.cmd(cmd).await?;
+    Ok(message::decode_response(&response, protocol.encoding))
+}
+
+/// An RCON connection whose responses are always valid UTF-8, see [DecodingStream].
+pub(crate) type RconConnection = rcon::Connection<DecodingStream>;
+
+/// Passes a TCP stream through, re-encoding the body of every packet read as UTF-8 with
+/// [message::decode_bytes]. The rcon crate fails the whole response on a body that isn't
+/// UTF-8, so without this a single player name with a stray byte breaks `ShowPlayers`.
+pub(crate) struct DecodingStream {
+    inner: tokio::net::TcpStream,
+    encoding: ResponseEncoding,
+    /// Bytes read that don't make a whole packet yet.
+    pending: Vec<u8>,
+    /// Re-encoded packets not yet read by the connection.
+    decoded: Vec<u8>,
+    consumed: usize,
+}
+
+impl DecodingStream {
+    fn new(inner: tokio::net::TcpStream, encoding: ResponseEncoding) -> Self {
+        Self {
+            inner,
+            encoding,
+            pending: Vec::new(),
+            decoded: Vec::new(),
+            consumed: 0,
         }
-        let version = re
-            .find(result.as_str())
-            .expect("Failed to find version information")
-            .as_str();
-        
-        // remove the brackets at the front and end
-        let version = Regex::new(r"(\[)|(\])").unwrap().replace_all(version, "");
+    }
 
-        Ok(version.to_string())
+    /// Moves the whole packets in [DecodingStream::pending] to [DecodingStream::decoded].
+    fn decode_packets(&mut self) {
+        // Size, then id, type, body and two terminating nulls.
+        while let Some(size) = self.pending.get(..4) {
+            let size = i32::from_le_bytes(size.try_into().unwrap());
+            let Some(len) = usize::try_from(size).ok().filter(|&len| len >= 10) else {
+                // Not a packet this understands, left for the rcon crate to reject.
+                self.decoded.append(&mut self.pending);
+                return;
+            };
+            if self.pending.len() < 4 + len {
+                return;
+            }
+            let packet: Vec<u8> = self.pending.drain(..4 + len).collect();
+            let body = message::decode_bytes(&packet[12..packet.len() - 2], self.encoding);
+            let size = i32::try_from(body.len() + 10).unwrap_or(i32::MAX);
+            self.decoded.extend_from_slice(&size.to_le_bytes());
+            self.decoded.extend_from_slice(&packet[4..12]);
+            self.decoded.extend_from_slice(body.as_bytes());
+            self.decoded.extend_from_slice(&packet[packet.len() - 2..]);
+        }
     }
 }
 
+impl tokio::io::AsyncRead for DecodingStream {
+    fn poll_read(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        use std::task::Poll;
+        let this = &mut *self;
+        while this.consumed == this.decoded.len() {
+            this.decoded.clear();
+            this.consumed = 0;
+            let mut chunk = [0u8; 4096];
+            let mut chunk = tokio::io::ReadBuf::new(&mut chunk);
+            match std::pin::Pin::new(&mut this.inner).poll_read(cx, &mut chunk) {
+                Poll::Ready(Ok(())) if chunk.filled().is_empty() => {
+                    // EOF, a partial packet is passed on for the rcon crate to fail on.
+                    this.decoded.append(&mut this.pending);
+                    if this.decoded.is_empty() {
+                        return Poll::Ready(Ok(()));
+                    }
+                }
+                Poll::Ready(Ok(())) => {
+                    this.pending.extend_from_slice(chunk.filled());
+                    this.decode_packets();
+                }
+                other => return other,
+            }
+        }
+        let len = buf.remaining().min(this.decoded.len() - this.consumed);
+        buf.put_slice(&this.decoded[this.consumed..this.consumed + len]);
+        this.consumed += len;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl tokio::io::AsyncWrite for DecodingStream {
+    fn poll_write(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> std::task::Poll<std::io::Result<usize>> {
+        std::pin::Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        std::pin::Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        std::pin::Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Runs `fetch` unless a query with the same key is in flight, then waits for its result.
+async fn single_flight(
+    key: FlightKey,
+    fetch: impl Future<Output = Result<String>>,
+) -> Result<String> {
+    let in_flight = IN_FLIGHT.get_or_init(Default::default);
+    let flight = in_flight
+        .lock()
+        .unwrap()
+        .entry(key.clone())
+        .or_default()
+        .clone();
+    // If the caller fetching is dropped, a waiting caller fetches instead.
+    let result = flight
+        .get_or_init(|| async { fetch.await.map_err(Arc::new) })
+        .await
+        .clone();
+    // The first caller back ends the flight, later queries fetch again.
+    {
+        let mut in_flight = in_flight.lock().unwrap();
+        if in_flight.get(&key).is_some_and(|f| Arc::ptr_eq(f, &flight)) {
+            in_flight.remove(&key);
+        }
+    }
+    result.map_err(|e| match e.downcast_ref::<RconError>() {
+        Some(e) => e.clone().into(),
+        None => anyhow::anyhow!("{e:#}"),
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::mock::MockRcon;
     use dotenv::dotenv;
 
     fn get_server() -> PalworldRCON {
@@ -227,6 +997,316 @@ mod tests {
         PalworldRCON::new(hostname, port, password)
     }
 
+    #[test]
+    fn test_password_file() {
+        let dir = std::env::temp_dir().join(format!("palworld-password-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("password");
+        for (contents, expected) in [
+            ("p@ss w%rd ü\n", "p@ss w%rd ü"),
+            ("windows\r\n", "windows"),
+            (" spaces ", " spaces "),
+        ] {
+            std::fs::write(&path, contents).unwrap();
+            assert_eq!(read_password_file(&path).unwrap(), expected);
+        }
+        std::fs::write(&path, "nul\0").unwrap();
+        let error = read_password_file(&path).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<RconError>(),
+            Some(&RconError::InvalidPasswordCharacter('\0'))
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_password_login() {
+        let password = "p@ss w%rd ü";
+        let server = MockRcon::start(password, |_| Some("Complete Save".to_string())).await;
+        let mut rcon = PalworldRCON::new("127.0.0.1", server.port, password);
+        rcon.protocol.detect = false;
+        assert!(rcon.save().await.unwrap());
+        assert_eq!(server.commands(), ["Save"]);
+
+        rcon.password = Secret::new("p@ss w%rd u".to_string());
+        let error = rcon.save().await.unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<RconError>(),
+            Some(&RconError::AuthFailed)
+        );
+        assert_eq!(server.commands().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_hook() {
+        let server = MockRcon::start("password", |_| Some("Broadcasted: hi".to_string())).await;
+        let mut rcon = PalworldRCON::new("127.0.0.1", server.port, "password");
+        rcon.protocol.detect = false;
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        rcon.broadcast_hook = Some(BroadcastHook::new({
+            let sent = sent.clone();
+            move |broadcast| sent.lock().unwrap().push(broadcast.message.clone())
+        }));
+        rcon.broadcast("a b", Some("_".to_string())).await.unwrap();
+        rcon.execute(&command::Broadcast::new("c", None).unwrap())
+            .await
+            .unwrap();
+        rcon.send_command("Save").await.unwrap();
+        rcon.with_connection(|session| async move { session.send_command("broadcast d").await })
+            .await
+            .unwrap();
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        let results = rcon.send_commands(&["Broadcast e"], deadline).await;
+        assert!(results[0].done());
+        assert_eq!(*sent.lock().unwrap(), ["a b", "c", "d", "e"]);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_utf8_player_name() {
+        let server = MockRcon::start_raw("password", |_| {
+            Some(b"name,playeruid,steamid\nPal\xffy,1234,76561198000000001\nZo,5678,76561198000000002\n".to_vec())
+        })
+        .await;
+        let mut rcon = PalworldRCON::new("127.0.0.1", server.port, "password");
+        rcon.protocol.detect = false;
+        let players = rcon.get_player_info().await.unwrap();
+        assert_eq!(players.len(), 2);
+        assert_eq!(players[0].name, "Pal\u{fffd}y");
+        assert_eq!(players[0].steamid, "76561198000000001");
+        assert_eq!(players[1].name, "Zo");
+    }
+
+    #[cfg(feature = "cp932")]
+    #[tokio::test]
+    async fn test_cp932_response() {
+        // "パル" in CP932.
+        let server = MockRcon::start_raw("password", |_| {
+            Some(b"name,playeruid,steamid\n\x83p\x83\x8b,1234,76561198000000001\n".to_vec())
+        })
+        .await;
+        let mut rcon = PalworldRCON::new("127.0.0.1", server.port, "password");
+        rcon.protocol.detect = false;
+        rcon.protocol.encoding = ResponseEncoding::Cp932;
+        assert_eq!(rcon.get_player_info().await.unwrap()[0].name, "パル");
+    }
+
+    #[tokio::test]
+    async fn test_resolve() {
+        let rcon = PalworldRCON::new("127.0.0.1", DEFAULT_SOURCE_PORT, "MyRCONPassword");
+        assert_eq!(
+            rcon.resolve().await.unwrap(),
+            vec![SocketAddr::from(([127, 0, 0, 1], DEFAULT_SOURCE_PORT))]
+        );
+        let rcon = PalworldRCON::new("invalid host name", DEFAULT_SOURCE_PORT, "password");
+        assert!(rcon.resolve().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_interval() {
+        let server = MockRcon::start("password", |_| {
+            Some("Welcome to Pal Server[v0.1.5.1] Default Palworld Server".to_string())
+        })
+        .await;
+        let mut rcon = PalworldRCON::new("127.0.0.1", server.port, "password");
+        rcon.protocol.detect = false;
+        rcon.resolve_interval = Some(Duration::from_secs(60));
+        let key = format!("127.0.0.1:{}", server.port);
+        let resolved = rcon.resolve().await.unwrap();
+
+        // Pretend the host moved to a closed port while its address was cached
+        let closed = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let moved = |addresses: Vec<SocketAddr>| {
+            RESOLVED
+                .get()
+                .unwrap()
+                .lock()
+                .unwrap()
+                .insert(key.clone(), (addresses, Instant::now()));
+        };
+        moved(vec![closed]);
+        assert_eq!(rcon.resolve().await.unwrap(), vec![closed]);
+        rcon.resolve_interval = Some(Duration::ZERO);
+        assert_eq!(rcon.resolve().await.unwrap(), resolved);
+        rcon.resolve_interval = None;
+        moved(vec![closed]);
+        assert_eq!(rcon.resolve().await.unwrap(), resolved);
+
+        // A failed connection drops the cached address
+        rcon.resolve_interval = Some(Duration::from_secs(60));
+        moved(vec![closed]);
+        assert!(rcon.get_version().await.is_err());
+        rcon.get_version().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_single_flight() {
+        let fetches = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let fetch = |response: Result<String>| {
+            let fetches = fetches.clone();
+            async move {
+                fetches.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                response
+            }
+        };
+        let key = || {
+            let host = "palworld.lan".to_string();
+            (host, 25575, "pw".into(), "info".to_string())
+        };
+        let (a, b, c) = tokio::join!(
+            single_flight(key(), fetch(Ok("v0.1.5.0".to_string()))),
+            single_flight(key(), fetch(Ok("other".to_string()))),
+            single_flight(key(), fetch(Ok("other".to_string()))),
+        );
+        assert_eq!(fetches.load(std::sync::atomic::Ordering::SeqCst), 1);
+        for response in [a, b, c] {
+            assert_eq!(response.unwrap(), "v0.1.5.0");
+        }
+
+        // Errors are shared too, and the next query fetches again.
+        let (a, b) = tokio::join!(
+            single_flight(key(), fetch(Err(RconError::AuthFailed.into()))),
+            single_flight(key(), fetch(Ok("other".to_string()))),
+        );
+        assert_eq!(fetches.load(std::sync::atomic::Ordering::SeqCst), 2);
+        assert_eq!(a.unwrap_err().downcast_ref(), Some(&RconError::AuthFailed));
+        assert_eq!(b.unwrap_err().downcast_ref(), Some(&RconError::AuthFailed));
+    }
+
+    #[test]
+    fn test_command_policy() {
+        assert!(glob_match("kickplayer *", "kickplayer 7656"));
+        assert!(glob_match("*exit", "doexit"));
+        assert!(glob_match("a*b*c", "abbc"));
+        assert!(!glob_match("a*b*c", "acb"));
+        assert!(!glob_match("ab*ba", "aba"));
+        assert!(!glob_match("doexit", "doexit now"));
+
+        let mut policy = CommandPolicy::new();
+        policy.deny = vec!["DoExit".to_string(), "Shutdown".to_string()];
+        assert!(policy.check("ShowPlayers").is_ok());
+        assert!(policy.check("  doexit now").is_err());
+        assert!(policy.check("SHUTDOWN 30 bye").is_err());
+        policy.allow = vec!["KickPlayer *".to_string()];
+        assert!(policy.check("KickPlayer 7656").is_ok());
+        assert!(policy.check("KickPlayer").is_err());
+        assert!(policy.check("BanPlayer 7656").is_err());
+        assert!(CommandPolicy::new().check("DoExit").is_ok());
+
+        let operator = CommandPolicy {
+            allow: vec!["Show*".to_string(), "Broadcast".to_string()],
+            deny: vec!["Broadcast *admin*".to_string()],
+        };
+        let scope = CommandPolicy {
+            allow: vec!["ShowPlayers".to_string(), "Info".to_string()],
+            ..CommandPolicy::new()
+        };
+        let both = operator.intersect(&scope);
+        assert!(both.check("ShowPlayers").is_ok());
+        assert!(both.check("Info").is_err());
+        assert!(both.check("Broadcast hi").is_err());
+        let broadcast = CommandPolicy {
+            allow: vec!["Broadcast".to_string()],
+            ..CommandPolicy::new()
+        };
+        let both = operator.intersect(&broadcast);
+        assert!(both.check("Broadcast hi").is_ok());
+        assert!(both.check("Broadcast hi_admin").is_err());
+        let info = CommandPolicy {
+            allow: vec!["Info".to_string()],
+            ..CommandPolicy::new()
+        };
+        assert!(operator.intersect(&info).check("Info").is_err());
+        assert!(operator.intersect(&info).check("DoExit").is_err());
+        assert_eq!(CommandPolicy::new().intersect(&info), info);
+    }
+
+    #[test]
+    fn test_match_players() {
+        let player = |name: &str, uid: &str, steamid: &str| PlayerInfo {
+            name: name.to_string(),
+            uid: uid.to_string(),
+            steamid: steamid.to_string(),
+        };
+        let players = vec![
+            player("Shadow", "1001", "76561190000000001"),
+            player("shadow", "1002", "76561190000000002"),
+            player("Shade", "1003", "76561190000000003"),
+            player("Bob", "1004", "76561190000000004"),
+        ];
+        let names = |query| {
+            match_players(&players, query)
+                .iter()
+                .map(|p| p.uid.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names("76561190000000004"), vec!["1004"]);
+        assert_eq!(names("1003"), vec!["1003"]);
+        assert_eq!(names(" Shadow "), vec!["1001"]);
+        assert_eq!(names("SHADOW"), vec!["1001", "1002"]);
+        assert_eq!(names("sha"), vec!["1001", "1002", "1003"]);
+        assert_eq!(names("bo"), vec!["1004"]);
+        assert!(names("alice").is_empty());
+        assert!(names("").is_empty());
+
+        let error = RconError::AmbiguousPlayer {
+            query: "sha".to_string(),
+            matches: players[2..3].to_vec(),
+        };
+        assert_eq!(error.to_string(), "'sha' matches Shade (76561190000000003)");
+    }
+
+    #[tokio::test]
+    async fn test_policy_denied() {
+        // Denied before resolving the invalid host.
+        let mut rcon = PalworldRCON::new("invalid host name", DEFAULT_SOURCE_PORT, "password");
+        rcon.policy = Some(CommandPolicy {
+            deny: vec!["DoExit".to_string()],
+            ..CommandPolicy::new()
+        });
+        let error = rcon.send_command("DoExit").await.unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<RconError>(),
+            Some(&RconError::PolicyDenied("DoExit".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connection_limit() {
+        let (host, port) = ("limit.palworld.lan", DEFAULT_SOURCE_PORT);
+        assert_eq!(
+            available_connections(host, port),
+            DEFAULT_CONNECTIONS_PER_HOST
+        );
+        set_connections_per_host(host, port, 2);
+        let first = connection_permit(host, port).await;
+        let _second = connection_permit(host, port).await;
+        assert_eq!(available_connections(host, port), 0);
+        let third = tokio::time::timeout(Duration::from_millis(50), connection_permit(host, port));
+        assert!(third.await.is_err());
+        // Other servers aren't limited by this one.
+        assert_eq!(
+            available_connections(host, port + 1),
+            DEFAULT_CONNECTIONS_PER_HOST
+        );
+        drop(first);
+        assert_eq!(available_connections(host, port), 1);
+    }
+
+    #[tokio::test]
+    async fn test_explicit_protocol() {
+        // Nothing listens on the invalid host, an explicit protocol doesn't need to connect.
+        let mut rcon = PalworldRCON::new("invalid host name", DEFAULT_SOURCE_PORT, "password");
+        rcon.protocol.detect = false;
+        rcon.protocol.factorio_quirks = false;
+        assert_eq!(rcon.effective_protocol().await.unwrap(), rcon.protocol);
+        rcon.protocol.detect = true;
+        assert!(rcon.effective_protocol().await.is_err());
+    }
+
     #[tokio::test]
     async fn test_commands_overload() {
         let server = get_server();
@@ -270,16 +1350,17 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "system")]
     #[tokio::test]
     async fn test_commands_memory_broadcast() {
         let server = get_server();
 
-        let mem = psutil::memory::virtual_memory().unwrap();
+        let mem = crate::mem::MemInfo::get_memory_info().unwrap();
         println!(
             "{}",
             server
                 .broadcast(
-                    format!("DEBUG: memory free:  {}MiB", mem.free() / (1024 * 1024)),
+                    format!("DEBUG: memory free:  {:.0}MiB", mem.mem_free.as_mib()),
                     Some("_".to_string())
                 )
                 .await
@@ -290,9 +1371,9 @@ mod tests {
             server
                 .broadcast(
                     format!(
-                        "DEBUG: memory used:  {}MiB ({:.2}%)",
-                        mem.used() / (1024 * 1024),
-                        mem.percent()
+                        "DEBUG: memory used:  {:.0}MiB ({:.2}%)",
+                        mem.used().unwrap().as_mib(),
+                        mem.used_percent().unwrap()
                     ),
                     Some("_".to_string())
                 )
@@ -303,7 +1384,7 @@ mod tests {
             "{}",
             server
                 .broadcast(
-                    format!("DEBUG: memory total: {}MiB", mem.total() / (1024 * 1024)),
+                    format!("DEBUG: memory total: {:.0}MiB", mem.mem_total.as_mib()),
                     Some("_".to_string())
                 )
                 .await
@@ -0,0 +1,269 @@
+//! Waiting for a started or restarted server to accept RCON.
+//!
+//! While the world loads, the server already accepts TCP connections on the RCON port but
+//! rejects logins or drops the connection. Treated as a wrong password, that makes automation
+//! give up on a server that is only booting. [PalworldRCON::wait_until_ready] retries instead,
+//! classifying every failed attempt ([FailureKind]): a port that doesn't accept connections
+//! yet is unreachable, a rejected or dropped login is booting and an invalid password or a
+//! denied command fails right away. A login that is still rejected
+//! [ReadyWait::auth_grace] after the port first accepted a connection is a wrong password,
+//! returned as [RconError::AuthFailed].
+//!
+//! # Example:
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use palworld_server::rcon::{PalworldRCON, DEFAULT_SOURCE_PORT};
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let rcon = PalworldRCON::new("palworld.lan", DEFAULT_SOURCE_PORT, "MyRCONPassword");
+//!     let ready = rcon.wait_until_ready(Duration::from_secs(300)).await.unwrap();
+//!     println!("Server {} ready after {:?}", ready.version, ready.waited);
+//! }
+//! ```
+
+use std::fmt;
+use std::future::Future;
+use std::io::ErrorKind;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+
+use crate::rcon::{PalworldRCON, RconError};
+
+/// Default time logins may be rejected after the RCON port accepted a connection.
+pub const DEFAULT_AUTH_GRACE: Duration = Duration::from_secs(90);
+
+/// Default time between attempts.
+pub const DEFAULT_RETRY_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Why an attempt to reach a server failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum FailureKind {
+    /// The RCON port doesn't accept connections (yet).
+    Unreachable,
+    /// The RCON port accepts connections but the login was rejected or dropped, or the
+    /// answer was incomplete.
+    Booting,
+    /// Retrying won't help, like an empty password.
+    Fatal,
+}
+
+impl fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unreachable => write!(f, "unreachable"),
+            Self::Booting => write!(f, "booting"),
+            Self::Fatal => write!(f, "failed"),
+        }
+    }
+}
+
+impl FailureKind {
+    /// Classifies an error of an RCON command.
+    pub fn classify(error: &anyhow::Error) -> Self {
+        match error.downcast_ref::<RconError>() {
+            Some(RconError::AuthFailed) => return Self::Booting,
+            Some(_) => return Self::Fatal,
+            None => (),
+        }
+        match io_error(error).map(|e| e.kind()) {
+            // Accepted, then closed during the login.
+            Some(
+                ErrorKind::ConnectionReset
+                | ErrorKind::ConnectionAborted
+                | ErrorKind::UnexpectedEof
+                | ErrorKind::BrokenPipe,
+            ) => Self::Booting,
+            Some(_) => Self::Unreachable,
+            // Answered, but not like a server that is up.
+            None => Self::Booting,
+        }
+    }
+}
+
+/// The I/O error that caused `error`, if any.
+fn io_error(error: &anyhow::Error) -> Option<&std::io::Error> {
+    error.chain().find_map(|cause| match cause.downcast_ref() {
+        Some(::rcon::Error::Io(e)) => Some(e),
+        _ => cause.downcast_ref(),
+    })
+}
+
+/// A server that answered [PalworldRCON::wait_until_ready].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct Ready {
+    pub version: String,
+    pub waited: Duration,
+    pub attempts: u32,
+}
+
+/// Waits for a server to accept RCON, see the [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct ReadyWait {
+    pub timeout: Duration,
+    /// Time logins may be rejected after the RCON port first accepted a connection.
+    pub auth_grace: Duration,
+    pub interval: Duration,
+}
+
+impl ReadyWait {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            auth_grace: DEFAULT_AUTH_GRACE,
+            interval: DEFAULT_RETRY_INTERVAL,
+        }
+    }
+
+    pub async fn run(&self, rcon: &PalworldRCON) -> Result<Ready> {
+        self.poll(|| rcon.get_version()).await
+    }
+
+    async fn poll<F, Fut>(&self, mut attempt: F) -> Result<Ready>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<String>>,
+    {
+        let start = Instant::now();
+        let mut accepted: Option<Instant> = None;
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let error = match attempt().await {
+                Ok(version) => {
+                    return Ok(Ready {
+                        version,
+                        waited: start.elapsed(),
+                        attempts,
+                    })
+                }
+                Err(e) => e,
+            };
+            let kind = FailureKind::classify(&error);
+            if kind == FailureKind::Booting && accepted.is_none() {
+                log::info!("Server accepts connections, waiting for it to finish booting");
+                accepted = Some(Instant::now());
+            }
+            let rejected = error.downcast_ref::<RconError>() == Some(&RconError::AuthFailed);
+            match kind {
+                FailureKind::Fatal => return Err(error),
+                _ if rejected && accepted.is_some_and(|at| at.elapsed() >= self.auth_grace) => {
+                    return Err(error.context(format!(
+                        "Login still rejected {:?} after the server accepted connections",
+                        self.auth_grace
+                    )))
+                }
+                _ if start.elapsed() >= self.timeout => {
+                    return Err(error.context(format!(
+                        "Server not ready after {:?}, last attempt {kind}",
+                        self.timeout
+                    )))
+                }
+                _ => log::debug!("Server not ready, {kind}: {error}"),
+            }
+            let remaining = self.timeout.saturating_sub(start.elapsed());
+            tokio::time::sleep(self.interval.min(remaining)).await;
+        }
+    }
+}
+
+impl PalworldRCON {
+    /// Retries until the server answers, for up to `timeout`. Rejected logins are retried
+    /// while the server boots, see the [module documentation](crate::ready).
+    pub async fn wait_until_ready(&self, timeout: Duration) -> Result<Ready> {
+        ReadyWait::new(timeout).run(self).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    use super::*;
+
+    fn io(kind: ErrorKind) -> anyhow::Error {
+        anyhow::Error::new(::rcon::Error::Io(std::io::Error::from(kind)))
+    }
+
+    #[test]
+    fn test_classify() {
+        use FailureKind::*;
+
+        assert_eq!(
+            FailureKind::classify(&RconError::AuthFailed.into()),
+            Booting
+        );
+        assert_eq!(
+            FailureKind::classify(&RconError::EmptyPassword.into()),
+            Fatal
+        );
+        assert_eq!(
+            FailureKind::classify(&io(ErrorKind::ConnectionRefused)),
+            Unreachable
+        );
+        assert_eq!(
+            FailureKind::classify(&io(ErrorKind::ConnectionReset)),
+            Booting
+        );
+        let resolve = anyhow::Error::new(std::io::Error::from(ErrorKind::NotFound))
+            .context("Failed to resolve palworld.lan");
+        assert_eq!(FailureKind::classify(&resolve), Unreachable);
+        assert_eq!(
+            FailureKind::classify(&anyhow::anyhow!("No version")),
+            Booting
+        );
+    }
+
+    /// Answers attempts with `results` in order.
+    async fn poll(wait: &ReadyWait, results: Vec<Result<String>>) -> Result<Ready> {
+        let results = Mutex::new(VecDeque::from(results));
+        wait.poll(|| {
+            let result = results.lock().unwrap().pop_front().unwrap();
+            async move { result }
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_wait() {
+        let mut wait = ReadyWait::new(Duration::from_secs(60));
+        wait.interval = Duration::ZERO;
+        let booting = || {
+            vec![
+                Err(io(ErrorKind::ConnectionRefused)),
+                Err(RconError::AuthFailed.into()),
+                Err(io(ErrorKind::ConnectionReset)),
+                Err(RconError::AuthFailed.into()),
+                Ok("v0.1.5.1".to_string()),
+            ]
+        };
+        let ready = poll(&wait, booting()).await.unwrap();
+        assert_eq!(ready.version, "v0.1.5.1");
+        assert_eq!(ready.attempts, 5);
+
+        // Past the grace, a rejected login is a wrong password.
+        wait.auth_grace = Duration::ZERO;
+        let error = poll(&wait, booting()).await.unwrap_err();
+        assert_eq!(error.downcast_ref(), Some(&RconError::AuthFailed));
+
+        let error = poll(&wait, vec![Err(RconError::EmptyPassword.into())])
+            .await
+            .unwrap_err();
+        assert_eq!(error.downcast_ref(), Some(&RconError::EmptyPassword));
+
+        wait.timeout = Duration::ZERO;
+        let error = poll(&wait, vec![Err(io(ErrorKind::ConnectionRefused))])
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("last attempt unreachable"));
+    }
+}
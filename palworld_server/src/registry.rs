@@ -0,0 +1,315 @@
+//! RCON commands of modded servers, registered at runtime.
+//!
+//! Mods like PalGuard add commands such as `getpos` or `give` the library can't know about.
+//! A [CommandRegistry] maps their names to parsers turning the raw response into JSON,
+//! registered as closures or, for configuration files, as a [ParserKind] in a [CommandSpec].
+//! Commands known at compile time are better implemented as a [crate::command::Command].
+//!
+//! # Example:
+//! ```no_run
+//! use palworld_server::rcon::{PalworldRCON, DEFAULT_SOURCE_PORT};
+//! use palworld_server::registry::{CommandRegistry, ParserKind};
+//! use serde_json::json;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let mut registry = CommandRegistry::new();
+//!     registry.register_kind("getpos", ParserKind::KeyValues);
+//!     registry.register("give", |raw: &str| Ok(json!({ "given": raw.contains("Gave") })));
+//!
+//!     let rcon = PalworldRCON::new("localhost", DEFAULT_SOURCE_PORT, "MyRCONPassword");
+//!     let position = registry.execute(&rcon, "getpos 76561190000000001").await.unwrap();
+//!     println!("{position}");
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use serde_json::{Map, Value};
+
+use crate::rcon::PalworldRCON;
+
+/// Turns the raw response of a command into JSON.
+pub type Parser = Arc<dyn Fn(&str) -> Result<Value> + Send + Sync>;
+
+/// Built-in parsers, for commands registered from configuration files.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ParserKind {
+    /// The response as a string, trimmed.
+    #[default]
+    Text,
+    /// Non-empty lines as a list of strings.
+    Lines,
+    /// `Key: value` and `Key=value` pairs as an object, numbers as numbers.
+    KeyValues,
+    /// The response is JSON already.
+    Json,
+}
+
+impl ParserKind {
+    pub fn parse(&self, raw: &str) -> Result<Value> {
+        match self {
+            Self::Text => Ok(Value::String(raw.trim().to_string())),
+            Self::Lines => Ok(Value::Array(
+                raw.lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(|line| Value::String(line.to_string()))
+                    .collect(),
+            )),
+            Self::KeyValues => Ok(Value::Object(parse_key_values(raw))),
+            Self::Json => serde_json::from_str(raw.trim()).context("Response isn't JSON"),
+        }
+    }
+}
+
+impl fmt::Display for ParserKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Text => write!(f, "text"),
+            Self::Lines => write!(f, "lines"),
+            Self::KeyValues => write!(f, "key_values"),
+            Self::Json => write!(f, "json"),
+        }
+    }
+}
+
+impl FromStr for ParserKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().replace('-', "_").as_str() {
+            "text" => Ok(Self::Text),
+            "lines" => Ok(Self::Lines),
+            "key_values" => Ok(Self::KeyValues),
+            "json" => Ok(Self::Json),
+            _ => bail!("Unknown parser '{s}', expected text, lines, key_values or json"),
+        }
+    }
+}
+
+/// `Key: value` and `Key=value` pairs of every line, also several per line separated by
+/// commas like `X=1.5, Y=-20, Z=300`. Values that are numbers become JSON numbers.
+fn parse_key_values(raw: &str) -> Map<String, Value> {
+    raw.lines()
+        .flat_map(|line| line.split(','))
+        .filter_map(|pair| {
+            let (key, value) = pair.split_once([':', '='])?;
+            let (key, value) = (key.trim(), value.trim());
+            if key.is_empty() {
+                return None;
+            }
+            let value = match value.parse::<f64>() {
+                Ok(number) => serde_json::Number::from_f64(number)
+                    .map_or(Value::String(value.to_string()), Value::Number),
+                Err(_) => Value::String(value.to_string()),
+            };
+            Some((key.to_string(), value))
+        })
+        .collect()
+}
+
+/// A command registered by name with a built-in parser, as read from configuration files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct CommandSpec {
+    /// First word of the command, like `getpos`, matched ignoring case.
+    pub name: String,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub parser: ParserKind,
+    /// Commands without side effects share responses, see [PalworldRCON::query].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub read_only: bool,
+}
+
+#[derive(Clone)]
+struct Registered {
+    read_only: bool,
+    parser: Parser,
+}
+
+/// Commands registered by name, see the [module documentation](self).
+#[derive(Clone, Default)]
+pub struct CommandRegistry {
+    commands: HashMap<String, Registered>,
+}
+
+impl fmt::Debug for CommandRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CommandRegistry")
+            .field("commands", &self.names())
+            .finish()
+    }
+}
+
+impl CommandRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registry of `specs`, later specs replace earlier ones with the same name.
+    pub fn from_specs(specs: &[CommandSpec]) -> Self {
+        let mut registry = Self::new();
+        for spec in specs {
+            let kind = spec.parser;
+            registry.insert(
+                &spec.name,
+                spec.read_only,
+                Arc::new(move |raw| kind.parse(raw)),
+            );
+        }
+        registry
+    }
+
+    /// Registers `name` with a `parser` for its responses, replacing an earlier registration.
+    pub fn register<F>(&mut self, name: &str, parser: F) -> &mut Self
+    where
+        F: Fn(&str) -> Result<Value> + Send + Sync + 'static,
+    {
+        self.insert(name, false, Arc::new(parser))
+    }
+
+    /// Registers `name` with a built-in parser.
+    pub fn register_kind(&mut self, name: &str, kind: ParserKind) -> &mut Self {
+        self.insert(name, false, Arc::new(move |raw| kind.parse(raw)))
+    }
+
+    /// Registers a command without side effects, identical ones sent at the same time share a
+    /// response.
+    pub fn register_read_only<F>(&mut self, name: &str, parser: F) -> &mut Self
+    where
+        F: Fn(&str) -> Result<Value> + Send + Sync + 'static,
+    {
+        self.insert(name, true, Arc::new(parser))
+    }
+
+    fn insert(&mut self, name: &str, read_only: bool, parser: Parser) -> &mut Self {
+        let registered = Registered { read_only, parser };
+        self.commands.insert(name.to_lowercase(), registered);
+        self
+    }
+
+    /// Names of the registered commands, sorted.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.commands.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    fn lookup(&self, line: &str) -> Option<&Registered> {
+        let name = line.split_whitespace().next()?;
+        self.commands.get(&name.to_lowercase())
+    }
+
+    /// Whether the command of `line`, its first word, is registered.
+    pub fn contains(&self, line: &str) -> bool {
+        self.lookup(line).is_some()
+    }
+
+    /// Whether the command of `line` is registered as read-only.
+    pub fn is_read_only(&self, line: &str) -> bool {
+        self.lookup(line)
+            .is_some_and(|registered| registered.read_only)
+    }
+
+    /// Parses `raw`, the response to `line`, with the parser of its command. None if the
+    /// command isn't registered.
+    pub fn parse(&self, line: &str, raw: &str) -> Option<Result<Value>> {
+        let registered = self.lookup(line)?;
+        Some((registered.parser)(raw))
+    }
+
+    /// Sends `line` and parses the response, failing for commands that aren't registered.
+    /// [PalworldRCON::policy] applies as for any other command.
+    pub async fn execute(&self, rcon: &PalworldRCON, line: &str) -> Result<Value> {
+        let Some(registered) = self.lookup(line) else {
+            bail!("Command '{line}' isn't registered");
+        };
+        let raw = match registered.read_only {
+            true => rcon.query(line).await?,
+            false => rcon.send_command(line).await?,
+        };
+        (registered.parser)(&raw)
+            .with_context(|| format!("Failed to parse the response to '{line}'"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parser_kinds() {
+        assert_eq!(ParserKind::Text.parse(" ok \n").unwrap(), json!("ok"));
+        assert_eq!(
+            ParserKind::Lines.parse("a\n\n b\n").unwrap(),
+            json!(["a", "b"])
+        );
+        assert_eq!(
+            ParserKind::KeyValues
+                .parse("Player: Shadow\nX=1.5, Y=-20, Z=300")
+                .unwrap(),
+            json!({"Player": "Shadow", "X": 1.5, "Y": -20.0, "Z": 300.0})
+        );
+        assert_eq!(
+            ParserKind::Json.parse(r#"{"ok": true}"#).unwrap(),
+            json!({"ok": true})
+        );
+        assert!(ParserKind::Json.parse("ok").is_err());
+        assert_eq!(
+            "key-values".parse::<ParserKind>().unwrap(),
+            ParserKind::KeyValues
+        );
+        assert!("xml".parse::<ParserKind>().is_err());
+    }
+
+    #[test]
+    fn test_registry() {
+        let mut registry = CommandRegistry::from_specs(&[CommandSpec {
+            name: "GetPos".to_string(),
+            parser: ParserKind::KeyValues,
+            read_only: true,
+        }]);
+        registry.register("give", |raw| Ok(json!(raw.contains("Gave"))));
+        assert_eq!(registry.names(), vec!["getpos", "give"]);
+        assert!(registry.contains("getpos 7656"));
+        assert!(!registry.contains("ShowPlayers"));
+        assert!(registry.is_read_only("getpos 7656"));
+        assert!(!registry.is_read_only("give 7656 Wood"));
+        assert!(!registry.is_read_only("ShowPlayers"));
+        assert_eq!(
+            registry.parse("GETPOS 7656", "X=1").unwrap().unwrap(),
+            json!({"X": 1.0})
+        );
+        assert_eq!(
+            registry
+                .parse("give 7656 Wood 10", "Gave 10 Wood")
+                .unwrap()
+                .unwrap(),
+            json!(true)
+        );
+        assert!(registry.parse("info", "").is_none());
+        assert_eq!(
+            format!("{registry:?}"),
+            r#"CommandRegistry { commands: ["getpos", "give"] }"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_unregistered() {
+        let rcon = PalworldRCON::new("invalid host name", 25575, "password");
+        let error = CommandRegistry::new()
+            .execute(&rcon, "getpos")
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Command 'getpos' isn't registered");
+    }
+}
@@ -0,0 +1,203 @@
+//! Client of the REST API of Palworld servers, enabled with `RESTAPIEnabled=True`.
+//!
+//! RCON responses fit in one packet, so on big servers [crate::rcon::PalworldRCON] falls back
+//! to the REST API for the player list when [crate::rcon::PalworldRCON::rest_url] is set. The
+//! API uses the `admin` user with the AdminPassword, like RCON. It also announces, saves,
+//! kicks, bans and shuts down, so [crate::failover::FailoverClient] can use it when RCON
+//! doesn't answer.
+//!
+//! # Example:
+//! ```no_run
+//! use palworld_server::rest::RestApi;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let api = RestApi::new("http://palworld.lan:8212", "MyRCONPassword");
+//!     for player in api.players().await.unwrap() {
+//!         println!("{} {}", player.name, player.steamid);
+//!     }
+//! }
+//! ```
+
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use reqwest::Method;
+use serde_json::{json, Value};
+
+use crate::models::PlayerInfo;
+use crate::secret::Secret;
+
+/// Default port of the REST API.
+pub static DEFAULT_REST_PORT: u16 = 8212;
+
+/// REST API of one server.
+#[derive(Debug, Clone)]
+pub struct RestApi {
+    /// Base URL, like `http://palworld.lan:8212`.
+    pub url: String,
+    /// AdminPassword of the server.
+    pub password: Secret<String>,
+    client: reqwest::Client,
+}
+
+#[derive(serde::Deserialize)]
+struct Players {
+    players: Vec<Player>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Player {
+    name: String,
+    player_id: String,
+    user_id: String,
+}
+
+impl RestApi {
+    pub fn new(url: impl Into<String>, password: impl Into<String>) -> Self {
+        Self {
+            url: url.into().trim_end_matches('/').to_string(),
+            password: Secret::new(password.into()),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Sends a request to `path` below `/v1/api` with an optional JSON body, returning the
+    /// response body.
+    async fn request(&self, method: Method, path: &str, body: Option<Value>) -> Result<String> {
+        let mut request = self
+            .client
+            .request(method, format!("{}/v1/api/{path}", self.url))
+            .basic_auth("admin", Some(self.password.expose()));
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        Ok(request.send().await?.error_for_status()?.text().await?)
+    }
+
+    /// Players online, the complete list however many there are.
+    pub async fn players(&self) -> Result<Vec<PlayerInfo>> {
+        parse_players(&self.request(Method::GET, "players", None).await?)
+    }
+
+    /// Server version, like `v0.3.6.0`.
+    pub async fn version(&self) -> Result<String> {
+        let info: Value = serde_json::from_str(&self.request(Method::GET, "info", None).await?)
+            .context("Failed to parse REST API server info")?;
+        match info["version"].as_str() {
+            Some(version) => Ok(version.to_string()),
+            None => bail!("REST API server info has no version"),
+        }
+    }
+
+    /// Shows `message` to every player, spaces don't need replacing unlike RCON.
+    pub async fn announce(&self, message: &str) -> Result<()> {
+        let body = json!({ "message": message });
+        self.request(Method::POST, "announce", Some(body)).await?;
+        Ok(())
+    }
+
+    /// Saves the world.
+    pub async fn save(&self) -> Result<()> {
+        self.request(Method::POST, "save", None).await?;
+        Ok(())
+    }
+
+    /// Shuts the server down after `delay`, showing `message` to the players.
+    pub async fn shutdown(&self, delay: Duration, message: &str) -> Result<()> {
+        let body = json!({ "waittime": delay.as_secs(), "message": message });
+        self.request(Method::POST, "shutdown", Some(body)).await?;
+        Ok(())
+    }
+
+    /// Kicks a player by Steam ID, as in [PlayerInfo::steamid].
+    pub async fn kick(&self, steamid: &str, message: &str) -> Result<()> {
+        let body = json!({ "userid": user_id(steamid), "message": message });
+        self.request(Method::POST, "kick", Some(body)).await?;
+        Ok(())
+    }
+
+    /// Bans a player by Steam ID, as in [PlayerInfo::steamid].
+    pub async fn ban(&self, steamid: &str, message: &str) -> Result<()> {
+        let body = json!({ "userid": user_id(steamid), "message": message });
+        self.request(Method::POST, "ban", Some(body)).await?;
+        Ok(())
+    }
+}
+
+/// User id of the REST API for a Steam ID, which has a `steam_` prefix.
+fn user_id(steamid: &str) -> String {
+    match steamid.starts_with("steam_") {
+        true => steamid.to_string(),
+        false => format!("steam_{steamid}"),
+    }
+}
+
+/// The `showplayers` UID of a REST API player id: its first 8 hex digits in decimal. Other
+/// ids are kept as they are.
+fn rcon_uid(player_id: &str) -> String {
+    let hex = player_id.get(..8).filter(|_| player_id.len() == 32);
+    match hex.and_then(|hex| u32::from_str_radix(hex, 16).ok()) {
+        Some(uid) => uid.to_string(),
+        None => player_id.to_string(),
+    }
+}
+
+/// Parses the response of `/v1/api/players`. The REST API gives player ids as 32 hex digits
+/// and Steam IDs with a `steam_` prefix, both are converted to what `showplayers` gives so
+/// players from either compare equal.
+pub fn parse_players(response: &str) -> Result<Vec<PlayerInfo>> {
+    let players: Players =
+        serde_json::from_str(response).context("Failed to parse REST API player list")?;
+    Ok(players
+        .players
+        .into_iter()
+        .map(|player| PlayerInfo {
+            name: player.name,
+            uid: rcon_uid(&player.player_id),
+            steamid: player
+                .user_id
+                .strip_prefix("steam_")
+                .unwrap_or(&player.user_id)
+                .to_string(),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_players() {
+        let response = r#"{"players": [{
+            "name": "Alice",
+            "accountName": "alice",
+            "playerId": "C1A6B0E3000000000000000000000000",
+            "userId": "steam_76561190000000001",
+            "ip": "203.0.113.5",
+            "ping": 32.5,
+            "location_x": 1.0,
+            "location_y": 2.0,
+            "level": 12
+        }]}"#;
+        assert_eq!(
+            parse_players(response).unwrap(),
+            vec![PlayerInfo {
+                name: "Alice".to_string(),
+                uid: "3248926947".to_string(),
+                steamid: "76561190000000001".to_string(),
+            }]
+        );
+        assert!(parse_players(r#"{"players": []}"#).unwrap().is_empty());
+        assert_eq!(rcon_uid("0000007B000000000000000000000000"), "123");
+        assert_eq!(rcon_uid("123"), "123");
+        assert!(parse_players("Unauthorized").is_err());
+        assert_eq!(user_id("76561190000000001"), "steam_76561190000000001");
+        assert_eq!(
+            user_id("steam_76561190000000001"),
+            "steam_76561190000000001"
+        );
+    }
+}
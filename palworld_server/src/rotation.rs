@@ -0,0 +1,174 @@
+//! Rotates the RCON password of a server managed over SSH.
+//!
+//! # Example:
+//! ```no_run
+//! use palworld_server::rcon::{PalworldRCON, DEFAULT_SOURCE_PORT};
+//! use palworld_server::rotation::PasswordRotation;
+//! use palworld_server::ssh::PalworldConnection;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let rcon = PalworldRCON::new("palworld.lan", DEFAULT_SOURCE_PORT, "OldPassword");
+//!     let ssh = PalworldConnection::new("palworld.lan:22", "root", "MySSHPassword");
+//!     let rcon = PasswordRotation::new(ssh)
+//!         .rotate_rcon_password(&rcon, "NewPassword")
+//!         .await
+//!         .unwrap();
+//!     println!("{}", rcon.get_version().await.unwrap());
+//! }
+//! ```
+
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+
+use crate::config::WorldSettings;
+use crate::provision::InstallOptions;
+use crate::rcon::{self, PalworldRCON};
+use crate::ssh::{
+    change_privileged, change_privileged_with_input, run_privileged, shell_quote,
+    PalworldConnection,
+};
+use crate::verify::StartupVerification;
+use crate::world::WorldProfile;
+
+/// Default time the restarted server gets to accept the new password.
+pub const DEFAULT_ROTATION_TIMEOUT: Duration = Duration::from_secs(180);
+
+/// Where the settings live and how the server is restarted, defaults match
+/// [crate::provision::install].
+#[derive(Debug, Clone)]
+pub struct PasswordRotation {
+    pub connection: PalworldConnection,
+    /// Path of `PalWorldSettings.ini` on the host.
+    pub settings_path: String,
+    /// systemd service of the server.
+    pub service: String,
+    /// Run commands with `sudo -n`, for SSH users other than root.
+    pub sudo: bool,
+    /// Time the restarted server gets to accept the new password before the change is
+    /// rolled back.
+    pub timeout: Duration,
+}
+
+impl PasswordRotation {
+    pub fn new(connection: PalworldConnection) -> Self {
+        let defaults = InstallOptions::new();
+        Self {
+            connection,
+            settings_path: defaults.settings_path(),
+            service: defaults.service,
+            sudo: false,
+            timeout: DEFAULT_ROTATION_TIMEOUT,
+        }
+    }
+
+    /// Rotates the password of `world` instead of the default installation.
+    pub fn for_world(connection: PalworldConnection, world: &WorldProfile) -> Self {
+        Self {
+            settings_path: world.settings_path(),
+            service: world.service.clone(),
+            sudo: world.sudo,
+            ..Self::new(connection)
+        }
+    }
+
+    /// Writes `new` as the AdminPassword, saves the world and restarts the server, then
+    /// checks the new password authenticates and the player list can be read, see
+    /// [StartupVerification]. On failure the old settings are restored and the server
+    /// restarted again. Returns `rcon` with the new password, in a dry run without checking
+    /// it.
+    pub async fn rotate_rcon_password(
+        &self,
+        rcon: &PalworldRCON,
+        new: &str,
+    ) -> Result<PalworldRCON> {
+        rcon::validate_password(new)?;
+        if new.contains('"') {
+            bail!("RCON password can't contain '\"', it is written quoted to the settings");
+        }
+        let settings_path = shell_quote(&self.settings_path);
+        let original = self.run(&format!("cat {settings_path}")).await?.output;
+        let settings = with_admin_password(&original, new)?;
+
+        let rotated = PalworldRCON {
+            password: new.into(),
+            ..rcon.clone()
+        };
+        if self.connection.dry_run.is_some() {
+            self.write_settings(&settings.to_ini()).await?;
+            self.restart().await?;
+            return Ok(rotated);
+        }
+
+        if let Err(e) = rcon.save().await {
+            log::warn!("Failed to save before restarting: {e}");
+        }
+        log::info!("Writing the new password to {}", self.settings_path);
+        self.write_settings(&settings.to_ini()).await?;
+        self.restart().await?;
+        match StartupVerification::new(self.timeout).run(&rotated).await {
+            Ok(_) => {
+                log::info!("RCON password rotated");
+                Ok(rotated)
+            }
+            Err(e) => {
+                log::error!("New RCON password doesn't work, restoring the old settings: {e}");
+                self.write_settings(&original).await?;
+                self.restart().await?;
+                Err(e.context("RCON password rotation failed, the old password was restored"))
+            }
+        }
+    }
+
+    async fn run(&self, command: &str) -> Result<crate::ssh::CommandResult> {
+        run_privileged(&self.connection, self.sudo, command).await
+    }
+
+    async fn change(&self, command: &str) -> Result<crate::ssh::CommandResult> {
+        change_privileged(&self.connection, self.sudo, command).await
+    }
+
+    async fn write_settings(&self, contents: &str) -> Result<()> {
+        // Redirecting into the existing file keeps its owner and mode. The contents go on
+        // stdin, on the command line the password would show in `ps`.
+        let command = format!("cat > {}", shell_quote(&self.settings_path));
+        change_privileged_with_input(&self.connection, self.sudo, &command, contents).await?;
+        Ok(())
+    }
+
+    async fn restart(&self) -> Result<()> {
+        log::info!("Restarting {}", self.service);
+        self.change(&format!("systemctl restart {}", shell_quote(&self.service)))
+            .await?;
+        Ok(())
+    }
+}
+
+/// Sets the AdminPassword in the contents of `PalWorldSettings.ini`.
+fn with_admin_password(ini: &str, password: &str) -> Result<WorldSettings> {
+    let mut settings = WorldSettings::parse(ini)?;
+    settings.set_quoted("AdminPassword", password);
+    Ok(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_admin_password() {
+        let ini = "[/Script/Pal.PalGameWorldSettings]\n\
+            OptionSettings=(ServerName=\"x\",AdminPassword=\"old\",RCONEnabled=True)\n";
+        let settings = with_admin_password(ini, "n3w pass").unwrap();
+        assert_eq!(settings.get("AdminPassword"), Some("n3w pass"));
+        assert!(settings
+            .to_ini()
+            .contains("AdminPassword=\"n3w pass\",RCONEnabled=True"));
+        let ini = "[/Script/Pal.PalGameWorldSettings]\nOptionSettings=(ServerName=\"x\")\n";
+        assert!(with_admin_password(ini, "new")
+            .unwrap()
+            .to_ini()
+            .contains("AdminPassword=\"new\""));
+    }
+}
@@ -0,0 +1,1288 @@
+//! Reading Palworld `.sav` files.
+//!
+//! `Level.sav` holds the guilds, base camps and characters of a world, `Players/*.sav` the
+//! records of each player. Save files are zlib compressed GVAS (Unreal Engine save game) documents. Only the parts
+//! needed for server administration are decoded, everything else is skipped by size, so
+//! saves written by newer server versions still load as long as those parts keep their
+//! layout. Saves compressed with Oodle (`PlM`) are not supported.
+//!
+//! # Example:
+//! ```no_run
+//! use palworld_server::savefile::LevelSave;
+//!
+//! let level = LevelSave::open("Pal/Saved/SaveGames/0/0123456789ABCDEF/Level.sav").unwrap();
+//! for guild in &level.guilds {
+//!     println!("{}: {} base(s), {} member(s)", guild.name, guild.base_count(), guild.members.len());
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use flate2::read::ZlibDecoder;
+
+/// Magic of zlib compressed saves.
+const ZLIB_MAGIC: &[u8; 3] = b"PlZ";
+/// Magic of Oodle compressed saves.
+const OODLE_MAGIC: &[u8; 3] = b"PlM";
+/// Magic at the start of a decompressed save.
+const GVAS_MAGIC: &[u8; 4] = b"GVAS";
+/// Unreal ticks are 100ns.
+const TICKS_PER_SECOND: i64 = 10_000_000;
+/// In-game hour the day starts.
+pub const DAWN_HOUR: u32 = 6;
+/// In-game hour the night starts.
+pub const DUSK_HOUR: u32 = 18;
+
+/// Decompresses a `.sav` file into its GVAS document.
+pub fn decompress(sav: &[u8]) -> Result<Vec<u8>> {
+    if sav.len() < 12 {
+        bail!("Save file is too short");
+    }
+    let uncompressed_len = u32::from_le_bytes(sav[0..4].try_into()?) as usize;
+    let magic = &sav[8..11];
+    if magic == OODLE_MAGIC {
+        bail!("Oodle compressed saves are not supported");
+    }
+    if magic != ZLIB_MAGIC {
+        bail!("Not a Palworld save file");
+    }
+    let passes = match sav[11] {
+        0x31 => 1,
+        0x32 => 2,
+        save_type => bail!("Unknown save type {save_type:#x}"),
+    };
+    let mut data = sav[12..].to_vec();
+    for _ in 0..passes {
+        let mut decompressed = Vec::new();
+        ZlibDecoder::new(data.as_slice())
+            .read_to_end(&mut decompressed)
+            .context("Failed to decompress save file")?;
+        data = decompressed;
+    }
+    if data.len() != uncompressed_len {
+        bail!(
+            "Save file decompressed to {} bytes instead of {uncompressed_len}",
+            data.len()
+        );
+    }
+    Ok(data)
+}
+
+/// An Unreal `FGuid`, used for player, guild and base camp IDs.
+///
+/// Displayed like palworld-save-tools does, the player save file names use
+/// [Guid::to_file_stem] instead.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "String", try_from = "String")
+)]
+pub struct Guid(pub [u32; 4]);
+
+impl Guid {
+    /// The GUID of a player from the Unique ID in `ShowPlayers`.
+    pub fn from_player_uid(uid: u32) -> Self {
+        Self([uid, 0, 0, 0])
+    }
+
+    /// The Unique ID `ShowPlayers` reports for this player.
+    pub fn player_uid(&self) -> u32 {
+        self.0[0]
+    }
+
+    pub fn is_nil(&self) -> bool {
+        self.0 == [0; 4]
+    }
+
+    /// Name of the player save file without `.sav`, like `0123ABCD000000000000000000000000`.
+    pub fn to_file_stem(&self) -> String {
+        self.0.iter().map(|part| format!("{part:08X}")).collect()
+    }
+}
+
+impl fmt::Display for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [a, b, c, d] = self.0;
+        write!(
+            f,
+            "{a:08x}-{:04x}-{:04x}-{:04x}-{:04x}{d:08x}",
+            b >> 16,
+            b & 0xffff,
+            c >> 16,
+            c & 0xffff
+        )
+    }
+}
+
+impl FromStr for Guid {
+    type Err = anyhow::Error;
+
+    /// Parses both the dashed form and file stems.
+    fn from_str(s: &str) -> Result<Self> {
+        let hex: String = s.chars().filter(|c| *c != '-').collect();
+        if hex.len() != 32 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            bail!("'{s}' is not a GUID");
+        }
+        let mut parts = [0; 4];
+        for (i, part) in parts.iter_mut().enumerate() {
+            *part = u32::from_str_radix(&hex[i * 8..i * 8 + 8], 16)?;
+        }
+        Ok(Self(parts))
+    }
+}
+
+impl From<Guid> for String {
+    fn from(guid: Guid) -> Self {
+        guid.to_string()
+    }
+}
+
+impl TryFrom<String> for Guid {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+/// A guild member as stored in the guild.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct GuildMember {
+    pub uid: Guid,
+    pub name: String,
+    /// Server clock in ticks when the player was last online, see [LevelSave::offline_for].
+    pub last_online_ticks: i64,
+}
+
+/// A guild and the base camps it owns.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct Guild {
+    pub id: Guid,
+    pub name: String,
+    pub admin: Guid,
+    pub members: Vec<GuildMember>,
+    pub base_ids: Vec<Guid>,
+    pub base_camp_level: i32,
+}
+
+impl Guild {
+    pub fn base_count(&self) -> usize {
+        self.base_ids.len()
+    }
+
+    /// Most recent time any member was online, in server ticks.
+    pub fn last_online_ticks(&self) -> Option<i64> {
+        self.members.iter().map(|m| m.last_online_ticks).max()
+    }
+}
+
+/// A base camp placed in the world.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct BaseCamp {
+    pub id: Guid,
+    pub name: String,
+    /// The guild owning the base.
+    pub guild_id: Guid,
+    /// World coordinates of the palbox.
+    pub location: [f64; 3],
+}
+
+/// A player or Pal in the world.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct Character {
+    pub instance_id: Guid,
+    /// The ID of a player character, None for Pals.
+    pub player_uid: Option<Guid>,
+    /// Species of a Pal, like `SheepBall`.
+    pub character_id: String,
+    pub nickname: String,
+    pub level: u32,
+    /// The player a Pal belongs to, None for wild Pals and players.
+    pub owner: Option<Guid>,
+}
+
+/// In-game day and time of a world.
+///
+/// # Example:
+/// ```
+/// use palworld_server::savefile::WorldTime;
+///
+/// let time = WorldTime { day: 214, hour: 21, minute: 30 };
+/// assert!(time.is_night());
+/// assert_eq!(time.to_string(), "Day 214, 21:30");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct WorldTime {
+    /// Starts at day 1.
+    pub day: u64,
+    pub hour: u32,
+    pub minute: u32,
+}
+
+impl WorldTime {
+    /// From the game clock in ticks, day 1 starts at tick 0.
+    pub fn from_ticks(ticks: i64) -> Self {
+        let minutes = ticks.max(0) / (60 * TICKS_PER_SECOND);
+        Self {
+            day: (minutes / (24 * 60)) as u64 + 1,
+            hour: (minutes / 60 % 24) as u32,
+            minute: (minutes % 60) as u32,
+        }
+    }
+
+    /// Between [DUSK_HOUR] and [DAWN_HOUR].
+    pub fn is_night(&self) -> bool {
+        self.hour >= DUSK_HOUR || self.hour < DAWN_HOUR
+    }
+}
+
+impl fmt::Display for WorldTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Day {}, {:02}:{:02}", self.day, self.hour, self.minute)
+    }
+}
+
+/// Guilds, base camps and characters of a world from its `Level.sav`.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct LevelSave {
+    pub guilds: Vec<Guild>,
+    pub base_camps: Vec<BaseCamp>,
+    pub characters: Vec<Character>,
+    /// Server clock in ticks when the world was saved.
+    pub real_time_ticks: Option<i64>,
+    /// In-game clock in ticks when the world was saved, see [LevelSave::world_time].
+    pub game_time_ticks: Option<i64>,
+}
+
+impl LevelSave {
+    /// Reads and parses a `Level.sav` file.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let sav =
+            std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&sav)
+    }
+
+    /// Parses the contents of a compressed `Level.sav` file.
+    pub fn parse(sav: &[u8]) -> Result<Self> {
+        Self::from_gvas(&decompress(sav)?)
+    }
+
+    /// Parses a decompressed `Level.sav` document.
+    pub fn from_gvas(gvas: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(gvas);
+        reader.gvas_header()?;
+        let mut level = Self::default();
+        reader.properties(|header, mut value| {
+            if header.name == "worldSaveData" {
+                level.read_world(&mut value)?;
+            }
+            Ok(())
+        })?;
+        Ok(level)
+    }
+
+    /// In-game day and time when the world was saved.
+    pub fn world_time(&self) -> Option<WorldTime> {
+        self.game_time_ticks.map(WorldTime::from_ticks)
+    }
+
+    /// How long ago the save's server clock was at `ticks`.
+    pub fn offline_for(&self, ticks: i64) -> Option<Duration> {
+        let elapsed = self.real_time_ticks?.checked_sub(ticks)?.max(0);
+        Some(Duration::from_secs((elapsed / TICKS_PER_SECOND) as u64))
+    }
+
+    /// Base camps owned by `guild`.
+    pub fn bases_of<'a>(&'a self, guild: &'a Guild) -> impl Iterator<Item = &'a BaseCamp> {
+        self.base_camps
+            .iter()
+            .filter(move |base| base.guild_id == guild.id)
+    }
+
+    /// Player characters in the world.
+    pub fn players(&self) -> impl Iterator<Item = &Character> {
+        self.characters
+            .iter()
+            .filter(|character| character.player_uid.is_some())
+    }
+
+    /// Pals owned by the player `uid`.
+    pub fn pals_of(&self, uid: Guid) -> impl Iterator<Item = &Character> {
+        self.characters
+            .iter()
+            .filter(move |character| character.owner == Some(uid))
+    }
+
+    fn read_world(&mut self, world: &mut Reader) -> Result<()> {
+        world.properties(|header, mut value| {
+            match header.name.as_str() {
+                "GroupSaveDataMap" => value.map(|entry| {
+                    entry.guid()?;
+                    if let Some(guild) = read_group(&mut entry.property_list()?)? {
+                        self.guilds.push(guild);
+                    }
+                    Ok(())
+                })?,
+                "BaseCampSaveData" => value.map(|entry| {
+                    entry.guid()?;
+                    entry.property_list()?.properties(|header, mut value| {
+                        if header.name == "RawData" {
+                            self.base_camps
+                                .push(read_base_camp(&mut Reader::new(value.byte_array()?))?);
+                        }
+                        Ok(())
+                    })
+                })?,
+                "CharacterSaveParameterMap" => value.map(|entry| {
+                    let (mut player_uid, mut instance_id) = (Guid::default(), Guid::default());
+                    entry.property_list()?.properties(|header, mut value| {
+                        match header.name.as_str() {
+                            "PlayerUId" => player_uid = value.guid()?,
+                            "InstanceId" => instance_id = value.guid()?,
+                            _ => (),
+                        }
+                        Ok(())
+                    })?;
+                    entry.property_list()?.properties(|header, mut value| {
+                        if header.name == "RawData" {
+                            let mut raw = Reader::new(value.byte_array()?);
+                            self.characters.push(read_character(
+                                &mut raw,
+                                player_uid,
+                                instance_id,
+                            )?);
+                        }
+                        Ok(())
+                    })
+                })?,
+                "GameTimeSaveData" => value.properties(|header, mut value| {
+                    match header.name.as_str() {
+                        "GameDateTimeTicks" => self.game_time_ticks = Some(value.i64()?),
+                        "RealDateTimeTicks" => self.real_time_ticks = Some(value.i64()?),
+                        _ => (),
+                    }
+                    Ok(())
+                })?,
+                _ => (),
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Reads a `GroupSaveDataMap` value, groups other than guilds are skipped.
+fn read_group(group: &mut Reader) -> Result<Option<Guild>> {
+    let mut group_type = None;
+    let mut raw = None;
+    group.properties(|header, mut value| {
+        match header.name.as_str() {
+            "GroupType" => group_type = Some(value.fstring()?),
+            "RawData" => raw = Some(value.byte_array()?),
+            _ => (),
+        }
+        Ok(())
+    })?;
+    match (group_type.as_deref(), raw) {
+        (Some("EPalGroupType::Guild"), Some(raw)) => read_guild(&mut Reader::new(raw)).map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// Reads the raw data of a guild group.
+fn read_guild(raw: &mut Reader) -> Result<Guild> {
+    let id = raw.guid()?;
+    let _group_name = raw.fstring()?;
+    // Character handles, a player and an instance GUID each.
+    let handles = raw.count()?;
+    raw.skip(handles * 32)?;
+    let _org_type = raw.u8()?;
+    let base_ids = raw.guids()?;
+    let base_camp_level = raw.i32()?;
+    let _base_camp_points = raw.guids()?;
+    let name = raw.fstring()?;
+    let admin = raw.guid()?;
+    let mut members = Vec::new();
+    for _ in 0..raw.count()? {
+        members.push(GuildMember {
+            uid: raw.guid()?,
+            last_online_ticks: raw.i64()?,
+            name: raw.fstring()?,
+        });
+    }
+    Ok(Guild {
+        id,
+        name,
+        admin,
+        members,
+        base_ids,
+        base_camp_level,
+    })
+}
+
+/// Reads the raw data of a base camp.
+fn read_base_camp(raw: &mut Reader) -> Result<BaseCamp> {
+    let id = raw.guid()?;
+    let name = raw.fstring()?;
+    let _state = raw.u8()?;
+    // Transform: rotation quaternion, translation and scale as doubles.
+    raw.skip(4 * 8)?;
+    let location = [raw.f64()?, raw.f64()?, raw.f64()?];
+    raw.skip(3 * 8)?;
+    let _area_range = raw.f32()?;
+    let guild_id = raw.guid()?;
+    Ok(BaseCamp {
+        id,
+        name,
+        guild_id,
+        location,
+    })
+}
+
+/// Reads the raw data of a character, the parameters are a property list.
+fn read_character(raw: &mut Reader, player_uid: Guid, instance_id: Guid) -> Result<Character> {
+    let mut character = Character {
+        instance_id,
+        player_uid: Some(player_uid).filter(|uid| !uid.is_nil()),
+        character_id: String::new(),
+        nickname: String::new(),
+        // Properties at their default value are left out.
+        level: 1,
+        owner: None,
+    };
+    raw.properties(|header, mut value| {
+        if header.name != "SaveParameter" {
+            return Ok(());
+        }
+        value.properties(|header, mut value| {
+            match header.name.as_str() {
+                "CharacterID" => character.character_id = value.fstring()?,
+                "NickName" => character.nickname = value.fstring()?,
+                // An IntProperty in early versions.
+                "Level" => match header.kind.as_str() {
+                    "ByteProperty" => character.level = value.u8()?.into(),
+                    _ => character.level = value.i32()?.max(0) as u32,
+                },
+                "OwnerPlayerUId" => {
+                    character.owner = Some(value.guid()?).filter(|uid| !uid.is_nil())
+                }
+                _ => (),
+            }
+            Ok(())
+        })
+    })?;
+    Ok(character)
+}
+
+/// Records of a player from their `Players/<GUID>.sav`.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct PlayerSave {
+    pub uid: Guid,
+    /// Pals captured per species, like `SheepBall`.
+    pub captures: BTreeMap<String, u32>,
+}
+
+impl PlayerSave {
+    /// Reads and parses a player save file.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let sav =
+            std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&sav).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    /// Reads every `.sav` file in the `Players` directory of a world. Files that fail to
+    /// parse are logged and skipped.
+    pub fn open_all(players_dir: impl AsRef<Path>) -> Result<Vec<Self>> {
+        let mut players = Vec::new();
+        for entry in std::fs::read_dir(players_dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|extension| extension == "sav") {
+                match Self::open(&path) {
+                    Ok(player) => players.push(player),
+                    Err(e) => log::warn!("Skipping player save: {e:#}"),
+                }
+            }
+        }
+        Ok(players)
+    }
+
+    /// Parses the contents of a compressed player save file.
+    pub fn parse(sav: &[u8]) -> Result<Self> {
+        Self::from_gvas(&decompress(sav)?)
+    }
+
+    /// Parses a decompressed player save document.
+    pub fn from_gvas(gvas: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(gvas);
+        reader.gvas_header()?;
+        let mut player = Self::default();
+        reader.properties(|header, mut value| {
+            if header.name != "SaveData" {
+                return Ok(());
+            }
+            value.properties(|header, mut value| {
+                match header.name.as_str() {
+                    "PlayerUId" => player.uid = value.guid()?,
+                    "RecordData" => value.properties(|header, mut value| {
+                        if header.name == "PalCaptureCount" {
+                            value.map(|entry| {
+                                let species = entry.fstring()?;
+                                let count = entry.i32()?.max(0) as u32;
+                                player.captures.insert(species, count);
+                                Ok(())
+                            })?;
+                        }
+                        Ok(())
+                    })?,
+                    _ => (),
+                }
+                Ok(())
+            })
+        })?;
+        Ok(player)
+    }
+
+    /// Pals captured over all species.
+    pub fn capture_count(&self) -> u64 {
+        self.captures.values().map(|count| *count as u64).sum()
+    }
+}
+
+/// Capture and Pal statistics of a player, for leaderboards.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct PlayerStats {
+    pub uid: Guid,
+    pub name: String,
+    /// Level of the player character, None without one in `Level.sav`.
+    pub level: Option<u32>,
+    pub captures: u64,
+    /// Pals the player owns.
+    pub pals: usize,
+    pub highest_pal_level: Option<u32>,
+    pub average_pal_level: Option<f64>,
+}
+
+/// Statistics of every player in `level` or `players`, most captures first.
+pub fn player_stats(level: &LevelSave, players: &[PlayerSave]) -> Vec<PlayerStats> {
+    let mut uids: Vec<Guid> = level
+        .players()
+        .filter_map(|character| character.player_uid)
+        .chain(players.iter().map(|player| player.uid))
+        .collect();
+    uids.sort();
+    uids.dedup();
+    let mut stats: Vec<PlayerStats> = uids
+        .into_iter()
+        .map(|uid| {
+            let character = level
+                .players()
+                .find(|character| character.player_uid == Some(uid));
+            let pal_levels: Vec<u32> = level.pals_of(uid).map(|pal| pal.level).collect();
+            PlayerStats {
+                uid,
+                name: character
+                    .map(|character| character.nickname.clone())
+                    .unwrap_or(uid.to_string()),
+                level: character.map(|character| character.level),
+                captures: players
+                    .iter()
+                    .filter(|player| player.uid == uid)
+                    .map(PlayerSave::capture_count)
+                    .sum(),
+                pals: pal_levels.len(),
+                highest_pal_level: pal_levels.iter().max().copied(),
+                average_pal_level: match pal_levels.is_empty() {
+                    true => None,
+                    false => Some(pal_levels.iter().sum::<u32>() as f64 / pal_levels.len() as f64),
+                },
+            }
+        })
+        .collect();
+    stats.sort_by(|a, b| {
+        b.captures
+            .cmp(&a.captures)
+            .then(b.pals.cmp(&a.pals))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    stats
+}
+
+/// Header of a GVAS property, the value follows it.
+#[derive(Debug)]
+struct PropertyHeader {
+    name: String,
+    kind: String,
+}
+
+/// Little endian reader over a GVAS document.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let Some(bytes) = self
+            .pos
+            .checked_add(len)
+            .and_then(|end| self.data.get(self.pos..end))
+        else {
+            bail!("Unexpected end of save data at byte {}", self.pos);
+        };
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn skip(&mut self, len: usize) -> Result<()> {
+        self.bytes(len).map(|_| ())
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.bytes(N)?.try_into()?)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn i32(&mut self) -> Result<i32> {
+        Ok(i32::from_le_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn i64(&mut self) -> Result<i64> {
+        Ok(i64::from_le_bytes(self.array()?))
+    }
+
+    fn f32(&mut self) -> Result<f32> {
+        Ok(f32::from_le_bytes(self.array()?))
+    }
+
+    fn f64(&mut self) -> Result<f64> {
+        Ok(f64::from_le_bytes(self.array()?))
+    }
+
+    /// An element count, checked against the remaining data so corrupt saves can't make
+    /// it allocate gigabytes.
+    fn count(&mut self) -> Result<usize> {
+        let count = self.u32()? as usize;
+        if count > self.data.len() - self.pos {
+            bail!(
+                "Element count {count} exceeds the save data at byte {}",
+                self.pos
+            );
+        }
+        Ok(count)
+    }
+
+    fn guid(&mut self) -> Result<Guid> {
+        Ok(Guid([self.u32()?, self.u32()?, self.u32()?, self.u32()?]))
+    }
+
+    fn guids(&mut self) -> Result<Vec<Guid>> {
+        (0..self.count()?).map(|_| self.guid()).collect()
+    }
+
+    /// An `FString`, positive lengths are single byte characters and negative lengths
+    /// UTF-16, both include the terminating NUL.
+    fn fstring(&mut self) -> Result<String> {
+        let len = self.i32()?;
+        let s = match len {
+            0 => String::new(),
+            len if len > 0 => String::from_utf8_lossy(self.bytes(len as usize)?).into_owned(),
+            len => {
+                let units = self.bytes(len.unsigned_abs() as usize * 2)?;
+                String::from_utf16_lossy(
+                    &units
+                        .chunks_exact(2)
+                        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+                        .collect::<Vec<u16>>(),
+                )
+            }
+        };
+        Ok(s.trim_end_matches('\0').to_string())
+    }
+
+    fn gvas_header(&mut self) -> Result<()> {
+        if self.bytes(4)? != GVAS_MAGIC {
+            bail!("Not a GVAS save document");
+        }
+        let save_game_version = self.i32()?;
+        // UE4 package version, followed by the UE5 one from save game version 3.
+        self.skip(4)?;
+        if save_game_version >= 3 {
+            self.skip(4)?;
+        }
+        // Engine version: major, minor, patch, changelist and branch.
+        for _ in 0..3 {
+            self.u16()?;
+        }
+        self.u32()?;
+        self.fstring()?;
+        let _custom_version_format = self.i32()?;
+        for _ in 0..self.count()? {
+            self.guid()?;
+            self.i32()?;
+        }
+        let _save_game_class = self.fstring()?;
+        Ok(())
+    }
+
+    /// The header of the next property, None at the `None` terminating a property list.
+    /// Returns the header and the bytes of its value.
+    fn property(&mut self) -> Result<Option<(PropertyHeader, Reader<'a>)>> {
+        let name = self.fstring()?;
+        if name == "None" {
+            return Ok(None);
+        }
+        let kind = self.fstring()?;
+        let size = usize::try_from(self.i64()?).context("Negative property size")?;
+        match kind.as_str() {
+            "StructProperty" => {
+                self.fstring()?;
+                self.guid()?;
+            }
+            "ArrayProperty" | "SetProperty" | "ByteProperty" | "EnumProperty" => {
+                self.fstring()?;
+            }
+            "MapProperty" => {
+                self.fstring()?;
+                self.fstring()?;
+            }
+            "BoolProperty" => {
+                self.u8()?;
+            }
+            _ => (),
+        }
+        if self.u8()? != 0 {
+            self.guid()?;
+        }
+        let value = Reader::new(self.bytes(size)?);
+        Ok(Some((PropertyHeader { name, kind }, value)))
+    }
+
+    /// Calls `f` with every property of a property list, up to its `None` terminator.
+    fn properties(
+        &mut self,
+        mut f: impl FnMut(&PropertyHeader, Reader<'a>) -> Result<()>,
+    ) -> Result<()> {
+        while let Some((header, value)) = self.property()? {
+            f(&header, value)
+                .with_context(|| format!("Failed to read {} '{}'", header.kind, header.name))?;
+        }
+        Ok(())
+    }
+
+    /// Calls `f` to read each entry of a `MapProperty` value.
+    fn map(&mut self, mut f: impl FnMut(&mut Self) -> Result<()>) -> Result<()> {
+        let _removed = self.u32()?;
+        for _ in 0..self.count()? {
+            f(self)?;
+        }
+        Ok(())
+    }
+
+    /// A struct inside a map, which is a property list whose size is only known after
+    /// reading it.
+    fn property_list(&mut self) -> Result<Reader<'a>> {
+        let mut list = Reader::new(&self.data[self.pos..]);
+        list.properties(|_, _| Ok(()))?;
+        self.bytes(list.pos).map(Reader::new)
+    }
+
+    /// The value of an `ArrayProperty` of bytes.
+    fn byte_array(&mut self) -> Result<&'a [u8]> {
+        let len = self.count()?;
+        self.bytes(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+
+    use super::*;
+
+    /// Builds GVAS documents the way the server writes them.
+    #[derive(Default)]
+    struct Writer(Vec<u8>);
+
+    impl Writer {
+        fn fstring(&mut self, s: &str) -> &mut Self {
+            self.0.extend((s.len() as i32 + 1).to_le_bytes());
+            self.0.extend(s.as_bytes());
+            self.0.push(0);
+            self
+        }
+
+        fn guid(&mut self, guid: Guid) -> &mut Self {
+            guid.0
+                .iter()
+                .for_each(|part| self.0.extend(part.to_le_bytes()));
+            self
+        }
+
+        fn raw(&mut self, bytes: &[u8]) -> &mut Self {
+            self.0.extend(bytes);
+            self
+        }
+
+        fn property(&mut self, name: &str, kind: &str, params: &[&str], value: &[u8]) -> &mut Self {
+            self.fstring(name).fstring(kind);
+            self.0.extend((value.len() as i64).to_le_bytes());
+            for param in params {
+                self.fstring(param);
+            }
+            match kind {
+                "StructProperty" => {
+                    self.guid(Guid::default());
+                }
+                "BoolProperty" => self.0.push(1),
+                _ => (),
+            }
+            self.0.push(0);
+            self.raw(value)
+        }
+
+        fn byte_array(&mut self, name: &str, bytes: &[u8]) -> &mut Self {
+            let mut value = (bytes.len() as u32).to_le_bytes().to_vec();
+            value.extend(bytes);
+            self.property(name, "ArrayProperty", &["ByteProperty"], &value)
+        }
+
+        fn map(&mut self, name: &str, kinds: [&str; 2], entries: &[Vec<u8>]) -> &mut Self {
+            let mut value = Writer::default();
+            value.raw(&0u32.to_le_bytes());
+            value.raw(&(entries.len() as u32).to_le_bytes());
+            for entry in entries {
+                value.raw(entry);
+            }
+            self.property(name, "MapProperty", &kinds, &value.0)
+        }
+
+        fn struct_map(&mut self, name: &str, entries: &[(Guid, Vec<u8>)]) -> &mut Self {
+            let entries: Vec<Vec<u8>> = entries
+                .iter()
+                .map(|(key, entry)| {
+                    let mut value = Writer::default();
+                    value.guid(*key).raw(entry);
+                    value.0
+                })
+                .collect();
+            self.map(name, ["StructProperty", "StructProperty"], &entries)
+        }
+
+        fn guid_property(&mut self, name: &str, guid: Guid) -> &mut Self {
+            let mut value = Writer::default();
+            value.guid(guid);
+            self.property(name, "StructProperty", &["Guid"], &value.0)
+        }
+
+        fn str_property(&mut self, name: &str, kind: &str, s: &str) -> &mut Self {
+            let mut value = Writer::default();
+            value.fstring(s);
+            self.property(name, kind, &[], &value.0)
+        }
+
+        fn end(&mut self) -> Vec<u8> {
+            self.fstring("None");
+            std::mem::take(&mut self.0)
+        }
+    }
+
+    fn guild_raw(guild: &Guild) -> Vec<u8> {
+        let mut raw = Writer::default();
+        raw.guid(guild.id).fstring("Unnamed group");
+        raw.raw(&1u32.to_le_bytes()).raw(&[0xaa; 32]);
+        raw.raw(&[0]);
+        raw.raw(&(guild.base_ids.len() as u32).to_le_bytes());
+        guild.base_ids.iter().for_each(|id| {
+            raw.guid(*id);
+        });
+        raw.raw(&guild.base_camp_level.to_le_bytes());
+        raw.raw(&0u32.to_le_bytes());
+        raw.fstring(&guild.name).guid(guild.admin);
+        raw.raw(&(guild.members.len() as u32).to_le_bytes());
+        for member in &guild.members {
+            raw.guid(member.uid)
+                .raw(&member.last_online_ticks.to_le_bytes())
+                .fstring(&member.name);
+        }
+        raw.0
+    }
+
+    fn gvas(class: &str, properties: &[u8]) -> Vec<u8> {
+        let mut gvas = Writer::default();
+        gvas.raw(GVAS_MAGIC)
+            .raw(&3i32.to_le_bytes())
+            .raw(&522i32.to_le_bytes())
+            .raw(&1008i32.to_le_bytes());
+        for part in [5u16, 1, 1] {
+            gvas.raw(&part.to_le_bytes());
+        }
+        gvas.raw(&0u32.to_le_bytes()).fstring("++UE5+Release-5.1");
+        gvas.raw(&3i32.to_le_bytes()).raw(&1u32.to_le_bytes());
+        gvas.guid(Guid([1, 2, 3, 4])).raw(&7i32.to_le_bytes());
+        gvas.fstring(class).raw(properties);
+        gvas.0
+    }
+
+    fn character_entry(character: &Character) -> Vec<u8> {
+        let mut entry = Writer::default();
+        entry
+            .guid_property("PlayerUId", character.player_uid.unwrap_or_default())
+            .guid_property("InstanceId", character.instance_id)
+            .str_property("DebugName", "StrProperty", "");
+        entry.fstring("None");
+        let mut parameters = Writer::default();
+        if character.player_uid.is_some() {
+            parameters.property("IsPlayer", "BoolProperty", &[], &[]);
+            parameters.str_property("NickName", "StrProperty", &character.nickname);
+        } else {
+            parameters.str_property("CharacterID", "NameProperty", &character.character_id);
+        }
+        if character.level != 1 {
+            parameters.property("Level", "ByteProperty", &["None"], &[character.level as u8]);
+        }
+        if let Some(owner) = character.owner {
+            parameters.guid_property("OwnerPlayerUId", owner);
+        }
+        let parameters = parameters.end();
+        let mut raw = Writer::default()
+            .property(
+                "SaveParameter",
+                "StructProperty",
+                &["PalIndividualCharacterSaveParameter"],
+                &parameters,
+            )
+            .end();
+        raw.extend([0; 4]);
+        raw.extend([0; 16]);
+        entry.byte_array("RawData", &raw);
+        entry.fstring("None");
+        entry.0
+    }
+
+    fn level_gvas(guild: &Guild, base: &BaseCamp, characters: &[Character]) -> Vec<u8> {
+        let group = Writer::default()
+            .property("GroupType", "EnumProperty", &["EPalGroupType"], &{
+                let mut value = Writer::default();
+                value.fstring("EPalGroupType::Guild");
+                value.0
+            })
+            .byte_array("RawData", &guild_raw(guild))
+            .end();
+        let neutral = Writer::default()
+            .property("GroupType", "EnumProperty", &["EPalGroupType"], &{
+                let mut value = Writer::default();
+                value.fstring("EPalGroupType::Neutral");
+                value.0
+            })
+            .byte_array("RawData", &[1, 2, 3])
+            .end();
+        let mut base_raw = Writer::default();
+        base_raw.guid(base.id).fstring(&base.name).raw(&[1]);
+        for value in [0.0, 0.0, 0.0, 1.0]
+            .iter()
+            .chain(&base.location)
+            .chain(&[1.0, 1.0, 1.0])
+        {
+            base_raw.raw(&f64::to_le_bytes(*value));
+        }
+        base_raw.raw(&3500f32.to_le_bytes()).guid(base.guild_id);
+        let base_struct = Writer::default()
+            .byte_array("RawData", &base_raw.0)
+            .property(
+                "WorkerDirector",
+                "StructProperty",
+                &["PalBaseCampSaveData_WorkerDirector"],
+                &[0; 8],
+            )
+            .end();
+        let game_time = Writer::default()
+            .property(
+                "GameDateTimeTicks",
+                "Int64Property",
+                &[],
+                &((213 * 24 * 60 + 21 * 60 + 30) * 60 * TICKS_PER_SECOND).to_le_bytes(),
+            )
+            .property(
+                "RealDateTimeTicks",
+                "Int64Property",
+                &[],
+                &(90 * 24 * 3600 * TICKS_PER_SECOND).to_le_bytes(),
+            )
+            .end();
+        let world = Writer::default()
+            .property("Unknown", "BoolProperty", &[], &[])
+            .struct_map(
+                "GroupSaveDataMap",
+                &[(guild.id, group), (Guid([9, 0, 0, 0]), neutral)],
+            )
+            .struct_map("BaseCampSaveData", &[(base.id, base_struct)])
+            .map(
+                "CharacterSaveParameterMap",
+                ["StructProperty", "StructProperty"],
+                &characters
+                    .iter()
+                    .map(character_entry)
+                    .collect::<Vec<Vec<u8>>>(),
+            )
+            .property(
+                "GameTimeSaveData",
+                "StructProperty",
+                &["PalGameTimeSaveData"],
+                &game_time,
+            )
+            .end();
+        let properties = Writer::default()
+            .property("Version", "IntProperty", &[], &100i32.to_le_bytes())
+            .property(
+                "worldSaveData",
+                "StructProperty",
+                &["PalWorldSaveData"],
+                &world,
+            )
+            .end();
+        gvas("/Script/Pal.PalWorldSaveGame", &properties)
+    }
+
+    fn player_gvas(player: &PlayerSave) -> Vec<u8> {
+        let captures: Vec<Vec<u8>> = player
+            .captures
+            .iter()
+            .map(|(species, count)| {
+                let mut entry = Writer::default();
+                entry.fstring(species).raw(&(*count as i32).to_le_bytes());
+                entry.0
+            })
+            .collect();
+        let record = Writer::default()
+            .property("TribeCaptureCount", "IntProperty", &[], &1i32.to_le_bytes())
+            .map(
+                "PalCaptureCount",
+                ["NameProperty", "IntProperty"],
+                &captures,
+            )
+            .end();
+        let save_data = Writer::default()
+            .guid_property("PlayerUId", player.uid)
+            .guid_property("IndividualId", Guid([5, 5, 5, 5]))
+            .property(
+                "RecordData",
+                "StructProperty",
+                &["PalLoggedinPlayerSaveDataRecordData"],
+                &record,
+            )
+            .end();
+        let properties = Writer::default()
+            .property("Version", "IntProperty", &[], &100i32.to_le_bytes())
+            .property(
+                "SaveData",
+                "StructProperty",
+                &["PalWorldPlayerSaveData"],
+                &save_data,
+            )
+            .end();
+        gvas("/Script/Pal.PalWorldPlayerSaveGame", &properties)
+    }
+
+    fn compress(gvas: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(gvas).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let mut sav = (gvas.len() as u32).to_le_bytes().to_vec();
+        sav.extend((compressed.len() as u32).to_le_bytes());
+        sav.extend(b"PlZ1");
+        sav.extend(compressed);
+        sav
+    }
+
+    #[test]
+    fn test_guid() {
+        let guid: Guid = "0123abcd-0000-0000-0000-000000000001".parse().unwrap();
+        assert_eq!(guid, Guid([0x0123abcd, 0, 0, 1]));
+        assert_eq!(guid.to_string(), "0123abcd-0000-0000-0000-000000000001");
+        assert_eq!(guid.to_file_stem(), "0123ABCD000000000000000000000001");
+        assert_eq!(guid.to_file_stem().parse::<Guid>().unwrap(), guid);
+        assert_eq!(Guid::from_player_uid(1234).player_uid(), 1234);
+        assert!("0123abcd".parse::<Guid>().is_err());
+    }
+
+    fn character(
+        player_uid: Option<Guid>,
+        name: &str,
+        level: u32,
+        owner: Option<Guid>,
+    ) -> Character {
+        Character {
+            instance_id: Guid([level, 7, 7, 7]),
+            player_uid,
+            character_id: match player_uid {
+                Some(_) => String::new(),
+                None => name.to_string(),
+            },
+            nickname: match player_uid {
+                Some(_) => name.to_string(),
+                None => String::new(),
+            },
+            level,
+            owner,
+        }
+    }
+
+    fn sample() -> (Guild, BaseCamp) {
+        let member = GuildMember {
+            uid: Guid::from_player_uid(0x0123abcd),
+            name: "Alice".to_string(),
+            last_online_ticks: 60 * 24 * 3600 * TICKS_PER_SECOND,
+        };
+        let guild = Guild {
+            id: Guid([1, 1, 1, 1]),
+            name: "Pal Pals".to_string(),
+            admin: member.uid,
+            members: vec![member],
+            base_ids: vec![Guid([2, 2, 2, 2])],
+            base_camp_level: 7,
+        };
+        let base = BaseCamp {
+            id: Guid([2, 2, 2, 2]),
+            name: "Home".to_string(),
+            guild_id: guild.id,
+            location: [-1200.5, 3400.0, 250.0],
+        };
+        (guild, base)
+    }
+
+    #[test]
+    fn test_level_save() {
+        let (guild, base) = sample();
+        let alice = Guid::from_player_uid(0x0123abcd);
+        let characters = vec![
+            character(Some(alice), "Alice", 12, None),
+            character(None, "SheepBall", 1, Some(alice)),
+            character(None, "PinkCat", 3, None),
+        ];
+        let level = LevelSave::parse(&compress(&level_gvas(&guild, &base, &characters))).unwrap();
+        assert_eq!(level.guilds, vec![guild.clone()]);
+        assert_eq!(level.base_camps, vec![base]);
+        assert_eq!(level.bases_of(&guild).count(), 1);
+        assert_eq!(
+            level.offline_for(guild.last_online_ticks().unwrap()),
+            Some(Duration::from_secs(30 * 24 * 3600))
+        );
+        assert_eq!(
+            level.world_time(),
+            Some(WorldTime {
+                day: 214,
+                hour: 21,
+                minute: 30
+            })
+        );
+        assert_eq!(level.characters, characters);
+        assert_eq!(level.players().count(), 1);
+        assert_eq!(level.pals_of(alice).count(), 1);
+    }
+
+    #[test]
+    fn test_world_time() {
+        let hour = 3600 * TICKS_PER_SECOND;
+        assert_eq!(WorldTime::from_ticks(0).to_string(), "Day 1, 00:00");
+        assert!(WorldTime::from_ticks(0).is_night());
+        assert!(!WorldTime::from_ticks(6 * hour).is_night());
+        assert!(!WorldTime::from_ticks(17 * hour + hour / 2).is_night());
+        assert!(WorldTime::from_ticks(18 * hour).is_night());
+        assert_eq!(
+            WorldTime::from_ticks(49 * hour + hour / 4).to_string(),
+            "Day 3, 01:15"
+        );
+    }
+
+    #[test]
+    fn test_player_stats() {
+        let (alice, bob) = (Guid::from_player_uid(1), Guid::from_player_uid(2));
+        let player = PlayerSave {
+            uid: bob,
+            captures: BTreeMap::from([("SheepBall".to_string(), 4), ("PinkCat".to_string(), 2)]),
+        };
+        let parsed = PlayerSave::parse(&compress(&player_gvas(&player))).unwrap();
+        assert_eq!(parsed, player);
+        assert_eq!(parsed.capture_count(), 6);
+
+        let level = LevelSave {
+            characters: vec![
+                character(Some(alice), "Alice", 20, None),
+                character(None, "SheepBall", 5, Some(alice)),
+                character(None, "PinkCat", 10, Some(alice)),
+                character(None, "Anubis", 30, None),
+            ],
+            ..Default::default()
+        };
+        let stats = player_stats(&level, &[parsed]);
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].uid, bob);
+        assert_eq!(stats[0].name, bob.to_string());
+        assert_eq!(stats[0].captures, 6);
+        assert_eq!(stats[0].pals, 0);
+        assert_eq!(stats[0].average_pal_level, None);
+        assert_eq!(stats[1].name, "Alice");
+        assert_eq!(stats[1].level, Some(20));
+        assert_eq!(stats[1].pals, 2);
+        assert_eq!(stats[1].highest_pal_level, Some(10));
+        assert_eq!(stats[1].average_pal_level, Some(7.5));
+    }
+
+    #[test]
+    fn test_invalid_saves() {
+        assert!(decompress(b"short").is_err());
+        let mut oodle = compress(b"GVAS");
+        oodle[8..11].copy_from_slice(b"PlM");
+        assert!(decompress(&oodle)
+            .unwrap_err()
+            .to_string()
+            .contains("Oodle"));
+
+        let (guild, base) = sample();
+        let gvas = level_gvas(&guild, &base, &[]);
+        assert!(LevelSave::from_gvas(&gvas[..gvas.len() / 2]).is_err());
+        assert!(LevelSave::from_gvas(b"GVAS").is_err());
+    }
+}
@@ -0,0 +1,561 @@
+//! Jobs run on cron expressions in an explicit timezone, like daily restarts and backups.
+//!
+//! A [Schedule] is a standard five field cron expression (or `@daily`, `@hourly`, ...)
+//! evaluated in a [Tz], so `0 4 * * *` in `Europe/Berlin` stays at 04:00 local time across
+//! DST changes, or `@reboot` to run once when the [Scheduler] starts. With a
+//! [RemoteClock] set the schedules follow the host's clock instead of the local one.
+//! [Scheduler::next_run] and [Scheduler::upcoming] show when jobs run next.
+//!
+//! With [Scheduler::state_path] set the last successful run of every job is kept on disk, so
+//! a restarted daemon doesn't run a job twice and, for jobs with [CatchUp::RunOnce], runs a
+//! job it missed while it was down. A run is recorded when it succeeds, so one that failed or
+//! was cut short by the restart counts as missed.
+//!
+//! # Example:
+//! ```no_run
+//! use palworld_server::rcon::{PalworldRCON, DEFAULT_SOURCE_PORT};
+//! use palworld_server::scheduler::{CatchUp, Schedule, Scheduler, Tz};
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let rcon = PalworldRCON::new("localhost", DEFAULT_SOURCE_PORT, "MyRCONPassword");
+//!     let mut scheduler = Scheduler::new();
+//!     scheduler.state_path = Some("scheduler.state".into());
+//!     let restart = Schedule::parse("0 4 * * *", Tz::Europe__Berlin).unwrap();
+//!     scheduler.add("restart", restart, move || {
+//!         let rcon = rcon.clone();
+//!         async move {
+//!             rcon.shutdown(None, "Daily_restart").await?;
+//!             Ok(())
+//!         }
+//!     })
+//!     .catch_up = CatchUp::RunOnce;
+//!     for (name, at) in scheduler.upcoming() {
+//!         println!("{name}: {at}");
+//!     }
+//!     scheduler.run().await;
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+pub use chrono_tz::Tz;
+use croner::Cron;
+
+use crate::clock::RemoteClock;
+use crate::parse;
+use crate::trace::{self, TraceContext, Trigger};
+use crate::validate::{did_you_mean, Diagnostic};
+
+type JobFuture = Pin<Box<dyn Future<Output = Result<()>> + Send>>;
+type JobFn = Arc<dyn Fn() -> JobFuture + Send + Sync>;
+
+/// When a job runs.
+#[derive(Debug, Clone)]
+pub enum Schedule {
+    /// A cron expression evaluated in a timezone.
+    Cron {
+        expression: String,
+        timezone: Tz,
+        cron: Box<Cron>,
+    },
+    /// Once when the scheduler starts, `@reboot`.
+    OnStart,
+}
+
+impl Schedule {
+    /// Parses a cron expression like `30 4 * * 1-5` or `@daily`, or `@reboot`.
+    pub fn parse(expression: &str, timezone: Tz) -> Result<Self> {
+        let expression = expression.trim();
+        if expression.eq_ignore_ascii_case("@reboot") {
+            return Ok(Self::OnStart);
+        }
+        let cron = Cron::new(expression)
+            .parse()
+            .map_err(|e| anyhow!("Invalid cron expression '{expression}': {e}"))?;
+        Ok(Self::Cron {
+            expression: expression.to_string(),
+            timezone,
+            cron: Box::new(cron),
+        })
+    }
+
+    /// First run after `now`, None for [Schedule::OnStart] or expressions that never match.
+    pub fn next_after(&self, now: SystemTime) -> Option<DateTime<Tz>> {
+        match self {
+            Self::Cron { timezone, cron, .. } => {
+                let now = DateTime::<Utc>::from(now).with_timezone(timezone);
+                cron.find_next_occurrence(&now, false).ok()
+            }
+            Self::OnStart => None,
+        }
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cron {
+                expression,
+                timezone,
+                ..
+            } => write!(f, "{expression} ({timezone})"),
+            Self::OnStart => write!(f, "@reboot"),
+        }
+    }
+}
+
+/// Aliases [Schedule::parse] accepts instead of the five fields.
+const CRON_ALIASES: [&str; 8] = [
+    "@yearly",
+    "@annually",
+    "@monthly",
+    "@weekly",
+    "@daily",
+    "@midnight",
+    "@hourly",
+    "@reboot",
+];
+
+/// Name and allowed values of every cron field, seconds only in six field expressions.
+const CRON_FIELDS: [(&str, &str); 6] = [
+    ("second", "0-59"),
+    ("minute", "0-59"),
+    ("hour", "0-23"),
+    ("day of month", "1-31 or L"),
+    ("month", "1-12 or JAN-DEC"),
+    ("day of week", "0-7 or SUN-SAT"),
+];
+
+/// Diagnostic of an expression [Schedule::parse] rejects, pointing at the field in error.
+/// None if the expression is valid.
+pub fn validate_cron(expression: &str) -> Option<Diagnostic> {
+    let error = Schedule::parse(expression, Tz::UTC).err()?;
+    let at = |offset, message: String| Diagnostic::error(message).at("cron", expression, offset);
+    let mut fields = Vec::new();
+    let mut offset = 0;
+    while let Some(start) = expression[offset..].find(|c: char| !c.is_whitespace()) {
+        let field = &expression[offset + start..];
+        let len = field.find(char::is_whitespace).unwrap_or(field.len());
+        fields.push((offset + start, &field[..len]));
+        offset += start + len;
+    }
+    match fields.as_slice() {
+        [] => return Some(Diagnostic::error("empty cron expression").in_file("cron")),
+        [(offset, alias)] if alias.starts_with('@') => {
+            let diagnostic = at(*offset, format!("unknown cron alias '{alias}'"));
+            return Some(match did_you_mean(alias, CRON_ALIASES) {
+                Some(known) => diagnostic.help(format!("did you mean '{known}'?")),
+                None => diagnostic.help(format!("expected one of {}", CRON_ALIASES.join(", "))),
+            });
+        }
+        _ if !(5..=6).contains(&fields.len()) => {
+            let diagnostic = at(fields[0].0, format!("{} cron fields", fields.len()));
+            return Some(diagnostic.help(
+                "expected minute, hour, day of month, month and day of week, optionally \
+                 preceded by second",
+            ));
+        }
+        _ => (),
+    }
+    let names = &CRON_FIELDS[CRON_FIELDS.len() - fields.len()..];
+    for (i, (offset, field)) in fields.iter().enumerate() {
+        // The field alone, every other field matching anything.
+        let probe: Vec<&str> = (0..fields.len())
+            .map(|j| match i == j {
+                true => *field,
+                false => "*",
+            })
+            .collect();
+        if Schedule::parse(&probe.join(" "), Tz::UTC).is_err() {
+            let (name, values) = names[i];
+            let diagnostic = at(*offset, format!("invalid {name} field '{field}'"));
+            return Some(diagnostic.help(format!(
+                "{name} allows {values}, '*', ranges, lists and steps"
+            )));
+        }
+    }
+    Some(Diagnostic::error(error.to_string()).in_file("cron"))
+}
+
+/// What to do about runs missed while the scheduler wasn't running.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum CatchUp {
+    /// Wait for the next run.
+    #[default]
+    Skip,
+    /// Run once on start if any runs were missed, like a backup that must not be skipped.
+    RunOnce,
+}
+
+/// Last successful runs of jobs by name, at the time they were scheduled for.
+pub type JobState = HashMap<String, SystemTime>;
+
+/// A named job and when it runs.
+#[derive(Clone)]
+pub struct Job {
+    /// Name of the job, unique within a [Scheduler] and without `=` or line breaks.
+    pub name: String,
+    pub schedule: Schedule,
+    pub catch_up: CatchUp,
+    run: JobFn,
+}
+
+impl fmt::Debug for Job {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Job")
+            .field("name", &self.name)
+            .field("schedule", &self.schedule)
+            .field("catch_up", &self.catch_up)
+            .finish()
+    }
+}
+
+/// Runs [Job]s when their [Schedule] is due, see the [module documentation](self).
+#[derive(Debug, Default)]
+pub struct Scheduler {
+    pub jobs: Vec<Job>,
+    /// Clock of the server host, schedules follow it instead of the local clock.
+    pub clock: Option<RemoteClock>,
+    /// File keeping the last successful run of every job across restarts.
+    pub state_path: Option<PathBuf>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a job, `job` is called for every run. Returns the job to set its [CatchUp].
+    pub fn add<F, Fut>(&mut self, name: impl Into<String>, schedule: Schedule, job: F) -> &mut Job
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.jobs.push(Job {
+            name: name.into(),
+            schedule,
+            catch_up: CatchUp::default(),
+            run: Arc::new(move || Box::pin(job())),
+        });
+        self.jobs.last_mut().expect("job was just added")
+    }
+
+    /// The current time on the clock schedules follow.
+    pub fn now(&self) -> SystemTime {
+        self.clock
+            .as_ref()
+            .map_or_else(SystemTime::now, RemoteClock::now)
+    }
+
+    /// Next run of the job named `name`, None if there is no such job or it only runs
+    /// on start.
+    pub fn next_run(&self, name: &str) -> Option<DateTime<Tz>> {
+        let job = self.jobs.iter().find(|job| job.name == name)?;
+        job.schedule.next_after(self.now())
+    }
+
+    /// Names and next runs of the jobs, soonest first.
+    pub fn upcoming(&self) -> Vec<(String, DateTime<Tz>)> {
+        let now = self.now();
+        let mut upcoming: Vec<(String, DateTime<Tz>)> = self
+            .jobs
+            .iter()
+            .filter_map(|job| Some((job.name.clone(), job.schedule.next_after(now)?)))
+            .collect();
+        upcoming.sort_by_key(|(_, at)| *at);
+        upcoming
+    }
+
+    /// Runs `@reboot` jobs and missed jobs to catch up on, then every job when it is due,
+    /// forever. Runs are spawned so slow jobs don't hold up others, failures are logged.
+    pub async fn run(self) {
+        let state = match &self.state_path {
+            Some(path) => read_state(path).unwrap_or_else(|e| {
+                log::warn!("Failed to read scheduler state, starting fresh: {e:#}");
+                JobState::new()
+            }),
+            None => JobState::new(),
+        };
+        let state = Arc::new(Mutex::new(state));
+        // Runs started by this process, so a clock reading at or just before a run that
+        // already started doesn't start it again.
+        let mut started = JobState::new();
+        let now = self.now();
+        for job in &self.jobs {
+            let last_run = state.lock().unwrap().get(&job.name).copied();
+            let start = match job.schedule {
+                Schedule::OnStart => true,
+                Schedule::Cron { .. } => match (job.catch_up, last_run) {
+                    (CatchUp::RunOnce, Some(last_run)) => {
+                        missed_run(&job.schedule, last_run, now).is_some()
+                    }
+                    _ => false,
+                },
+            };
+            if start {
+                self.start(job, now, &state);
+                started.insert(job.name.clone(), now);
+            }
+        }
+        loop {
+            let now = self.now();
+            let due = self.due(now, &started);
+            let Some(next) = due.iter().map(|(_, at)| *at).min() else {
+                log::debug!("No scheduled jobs left");
+                return;
+            };
+            let delay = next.duration_since(now).unwrap_or_default();
+            tokio::time::sleep(delay).await;
+            for (job, _) in due.iter().filter(|(_, at)| *at == next) {
+                // Already run by an earlier process, with the state file shared.
+                if state
+                    .lock()
+                    .unwrap()
+                    .get(&job.name)
+                    .is_some_and(|last_run| *last_run >= next)
+                {
+                    continue;
+                }
+                self.start(job, next, &state);
+                started.insert(job.name.clone(), next);
+            }
+        }
+    }
+
+    /// Next run of every job after `now`, and after the run it last started in `started`.
+    fn due(&self, now: SystemTime, started: &JobState) -> Vec<(&Job, SystemTime)> {
+        self.jobs
+            .iter()
+            .filter_map(|job| {
+                let after = started.get(&job.name).map_or(now, |at| now.max(*at));
+                Some((job, job.schedule.next_after(after)?.into()))
+            })
+            .collect()
+    }
+
+    /// Spawns the run of `job` scheduled for `at`, recorded in `state` and the state file
+    /// once it succeeds.
+    fn start(&self, job: &Job, at: SystemTime, state: &Arc<Mutex<JobState>>) {
+        let name = job.name.clone();
+        let run = job.run.clone();
+        let state = state.clone();
+        let path = self.state_path.clone();
+        let context = TraceContext::new(Trigger::Schedule(name.clone()));
+        tokio::spawn(trace::traced(context, async move {
+            if let Err(e) = run().await {
+                log::error!("Scheduled job '{name}' failed: {e}");
+                return;
+            }
+            let mut state = state.lock().unwrap();
+            // A slow run may finish after a later one.
+            let last_run = state.entry(name).or_insert(at);
+            *last_run = (*last_run).max(at);
+            if let Some(path) = &path {
+                if let Err(e) = write_state(path, &state) {
+                    log::warn!("Failed to write scheduler state: {e:#}");
+                }
+            }
+        }));
+    }
+}
+
+/// The first run of `schedule` after `last_run` if it was due by `now`, None if none was
+/// missed. Any later missed runs are caught up on by this one too.
+pub fn missed_run(
+    schedule: &Schedule,
+    last_run: SystemTime,
+    now: SystemTime,
+) -> Option<DateTime<Tz>> {
+    let next = schedule.next_after(last_run)?;
+    (SystemTime::from(next) <= now).then_some(next)
+}
+
+/// Reads the last runs of jobs, none if the file doesn't exist yet.
+pub fn read_state(path: &Path) -> Result<JobState> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(JobState::new()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    Ok(parse::parse_key_values(&contents)
+        .into_iter()
+        .map(|(name, secs)| (name, UNIX_EPOCH + Duration::from_secs(secs)))
+        .collect())
+}
+
+/// Writes the last runs of jobs as `name=unix seconds` lines, replacing the file at once so
+/// it is never half written.
+pub fn write_state(path: &Path, state: &JobState) -> Result<()> {
+    let mut lines: Vec<String> = state
+        .iter()
+        .map(|(name, at)| {
+            let secs = at.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+            format!("{name}={secs}\n")
+        })
+        .collect();
+    lines.sort();
+    let temporary = path.with_extension("tmp");
+    std::fs::write(&temporary, lines.concat())
+        .with_context(|| format!("Failed to write {}", temporary.display()))?;
+    std::fs::rename(&temporary, path)
+        .with_context(|| format!("Failed to replace {}", path.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> SystemTime {
+        SystemTime::from(s.parse::<DateTime<Utc>>().unwrap())
+    }
+
+    #[test]
+    fn test_schedule() {
+        let berlin = Tz::Europe__Berlin;
+        let daily = Schedule::parse("0 4 * * *", berlin).unwrap();
+        assert_eq!(daily.to_string(), "0 4 * * * (Europe/Berlin)");
+        // 04:00 is 03:00 UTC in winter and 02:00 UTC in summer.
+        let next = daily.next_after(utc("2024-03-30T12:00:00Z")).unwrap();
+        assert_eq!(SystemTime::from(next), utc("2024-03-31T02:00:00Z"));
+        let next = daily.next_after(utc("2024-10-26T12:00:00Z")).unwrap();
+        assert_eq!(SystemTime::from(next), utc("2024-10-27T03:00:00Z"));
+
+        let weekdays = Schedule::parse("30 6 * * 1-5", Tz::UTC).unwrap();
+        // Saturday, so the next run is on Monday.
+        let next = weekdays.next_after(utc("2024-06-01T12:00:00Z")).unwrap();
+        assert_eq!(SystemTime::from(next), utc("2024-06-03T06:30:00Z"));
+        assert!(Schedule::parse("@hourly", Tz::UTC).is_ok());
+
+        let reboot = Schedule::parse(" @reboot ", Tz::UTC).unwrap();
+        assert!(matches!(reboot, Schedule::OnStart));
+        assert!(reboot.next_after(SystemTime::now()).is_none());
+        assert!(Schedule::parse("61 * * * *", Tz::UTC).is_err());
+        assert!(Schedule::parse("", Tz::UTC).is_err());
+    }
+
+    #[test]
+    fn test_validate_cron() {
+        assert!(validate_cron("0 4 * * *").is_none());
+        assert!(validate_cron("@reboot").is_none());
+        let diagnostic = validate_cron("0 25 * * *").unwrap();
+        assert_eq!(diagnostic.message, "invalid hour field '25'");
+        assert_eq!((diagnostic.line, diagnostic.column), (1, 3));
+        let diagnostic = validate_cron("  0 4 * FOO *").unwrap();
+        assert_eq!(diagnostic.message, "invalid month field 'FOO'");
+        assert_eq!(diagnostic.column, 9);
+        let diagnostic = validate_cron("@dialy").unwrap();
+        assert_eq!(diagnostic.help.as_deref(), Some("did you mean '@daily'?"));
+        let diagnostic = validate_cron("0 4 * *").unwrap();
+        assert_eq!(diagnostic.message, "4 cron fields");
+        assert!(validate_cron(" ").is_some());
+    }
+
+    #[test]
+    fn test_missed_run() {
+        let daily = Schedule::parse("0 4 * * *", Tz::UTC).unwrap();
+        let last_run = utc("2024-06-01T04:00:00Z");
+        // Down over the next run.
+        let missed = missed_run(&daily, last_run, utc("2024-06-02T09:00:00Z")).unwrap();
+        assert_eq!(SystemTime::from(missed), utc("2024-06-02T04:00:00Z"));
+        // Restarted before the next run.
+        assert!(missed_run(&daily, last_run, utc("2024-06-02T03:00:00Z")).is_none());
+        assert!(missed_run(&Schedule::OnStart, last_run, SystemTime::now()).is_none());
+    }
+
+    #[test]
+    fn test_state_file() {
+        let path = std::env::temp_dir().join(format!("scheduler-{}.state", std::process::id()));
+        assert!(read_state(&path).unwrap().is_empty());
+        let mut state = JobState::new();
+        state.insert("backup".to_string(), utc("2024-06-01T04:00:00Z"));
+        state.insert("restart".to_string(), utc("2024-06-01T05:30:00Z"));
+        write_state(&path, &state).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "backup=1717214400\nrestart=1717219800\n"
+        );
+        assert_eq!(read_state(&path).unwrap(), state);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_upcoming() {
+        let mut scheduler = Scheduler::new();
+        let schedule = |expression| Schedule::parse(expression, Tz::UTC).unwrap();
+        scheduler.add("yearly", schedule("@yearly"), || async { Ok(()) });
+        scheduler.add("minutely", schedule("* * * * *"), || async { Ok(()) });
+        scheduler.add("start", schedule("@reboot"), || async { Ok(()) });
+        let names: Vec<String> = scheduler.upcoming().into_iter().map(|(n, _)| n).collect();
+        assert_eq!(names, vec!["minutely", "yearly"]);
+        assert!(scheduler.next_run("minutely").is_some());
+        assert!(scheduler.next_run("start").is_none());
+        assert!(scheduler.next_run("missing").is_none());
+    }
+
+    #[test]
+    fn test_due_after_started() {
+        let mut scheduler = Scheduler::new();
+        let hourly = Schedule::parse("0 * * * *", Tz::UTC).unwrap();
+        scheduler.add("hourly", hourly, || async { Ok(()) });
+        let mut started = JobState::new();
+        // A clock reading just before the run, like one a bit behind after waking.
+        let now = utc("2024-06-01T03:59:59Z");
+        let next = |started: &JobState| scheduler.due(now, started)[0].1;
+        assert_eq!(next(&started), utc("2024-06-01T04:00:00Z"));
+        started.insert("hourly".to_string(), utc("2024-06-01T04:00:00Z"));
+        assert_eq!(next(&started), utc("2024-06-01T05:00:00Z"));
+    }
+
+    #[tokio::test]
+    async fn test_run_on_start() {
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let mut scheduler = Scheduler::new();
+        scheduler.add("start", Schedule::OnStart, move || {
+            let sender = sender.clone();
+            async move {
+                sender.send(trace::current().unwrap().trigger)?;
+                Ok(())
+            }
+        });
+        // Returns after the start jobs with nothing else scheduled.
+        scheduler.run().await;
+        assert_eq!(
+            receiver.recv().await,
+            Some(Trigger::Schedule("start".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_state_on_completion() {
+        let path = std::env::temp_dir().join(format!("scheduler-run-{}.state", std::process::id()));
+        let mut scheduler = Scheduler::new();
+        scheduler.state_path = Some(path.clone());
+        scheduler.add("slow", Schedule::OnStart, || async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            Ok(())
+        });
+        scheduler.add("fails", Schedule::OnStart, || async {
+            Err(anyhow!("failed"))
+        });
+        scheduler.run().await;
+        // Not recorded while running.
+        assert!(read_state(&path).unwrap().is_empty());
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let state = read_state(&path).unwrap();
+        assert!(state.contains_key("slow"));
+        assert!(!state.contains_key("fails"));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,381 @@
+//! Rhai scripting for admins who don't write Rust.
+//!
+//! [ScriptPlugin] loads every `*.rhai` file from a directory and calls the functions a
+//! script defines: `init()`, `on_event(event)` and `on_tick()`. Scripts run sandboxed,
+//! they can't touch the filesystem and only get this API:
+//!
+//! * `broadcast(message)` - broadcast a message, returns the server response.
+//! * `save()` - save the world, returns true on success.
+//! * `kick(steamid)` - kick a player, returns true on success.
+//! * `players()` - array of `#{ name, uid, steamid }` maps.
+//! * `log(message)` - write to the log.
+//!
+//! `event` is a map with `kind`, `severity` and `message` plus `name`/`steamid` for
+//! player events.
+//!
+//! ```rhai
+//! fn on_event(event) {
+//!     if event.kind == "player_joined" {
+//!         broadcast(`Welcome ${event.name}!`);
+//!     }
+//! }
+//! ```
+//!
+//! With the `schedule` feature a script can also run functions on cron schedules, by
+//! returning a map of cron expressions to function names from `schedules()`. Add them to a
+//! [crate::scheduler::Scheduler] with [ScriptPlugin::schedule] before registering the plugin:
+//!
+//! ```rhai
+//! fn schedules() {
+//!     #{ "0 */6 * * *": "backup_reminder" }
+//! }
+//!
+//! fn backup_reminder() {
+//!     broadcast("Saving the world");
+//!     save();
+//! }
+//! ```
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Map, Scope, AST};
+use tokio::runtime::Handle;
+
+use crate::events::Event;
+use crate::plugin::{Plugin, PluginContext};
+use crate::rcon::PalworldRCON;
+#[cfg(feature = "schedule")]
+use crate::scheduler::{Schedule, Scheduler, Tz};
+
+/// Maximum number of operations a single hook may run, stops runaway loops.
+pub const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// Runs the async `future` from a script callback. Scripts run on a blocking thread.
+fn block_on<T>(
+    future: impl std::future::Future<Output = Result<T>>,
+) -> Result<T, Box<EvalAltResult>> {
+    Handle::current()
+        .block_on(future)
+        .map_err(|e| e.to_string().into())
+}
+
+/// Creates a sandboxed engine with the scripting API bound to `rcon`.
+pub fn create_engine(rcon: PalworldRCON) -> Engine {
+    let mut engine = Engine::new();
+    // No `import` of other files.
+    engine.set_module_resolver(rhai::module_resolvers::DummyModuleResolver::new());
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.set_max_call_levels(32);
+    engine.set_max_string_size(64 * 1024);
+    engine.set_max_array_size(10_000);
+    engine.set_max_map_size(10_000);
+
+    let server = rcon.clone();
+    engine.register_fn("broadcast", move |message: &str| {
+        block_on(server.broadcast(message, None))
+    });
+    let server = rcon.clone();
+    engine.register_fn("save", move || block_on(server.save()));
+    let server = rcon.clone();
+    engine.register_fn("kick", move |steamid: &str| {
+        block_on(server.kick_player(steamid))
+    });
+    engine.register_fn("players", move || -> Result<Array, Box<EvalAltResult>> {
+        let players = block_on(rcon.get_player_info())?;
+        Ok(players
+            .into_iter()
+            .map(|player| {
+                let mut map = Map::new();
+                map.insert("name".into(), player.name.into());
+                map.insert("uid".into(), player.uid.into());
+                map.insert("steamid".into(), player.steamid.into());
+                Dynamic::from_map(map)
+            })
+            .collect())
+    });
+    engine.register_fn("log", |message: &str| log::info!("[script] {message}"));
+    engine
+}
+
+/// Converts an event to the map passed to `on_event`.
+pub fn event_to_map(event: &Event) -> Map {
+    let mut map = Map::new();
+    let kind = match serde_json::to_value(event.kind()) {
+        Ok(serde_json::Value::String(kind)) => kind,
+        _ => format!("{:?}", event.kind()),
+    };
+    map.insert("kind".into(), kind.into());
+    map.insert("severity".into(), event.severity().to_string().into());
+    map.insert("message".into(), event.to_string().into());
+    if let Event::PlayerJoined(player)
+    | Event::PlayerLeft(player)
+    | Event::PlaytimeMilestone { player, .. } = event
+    {
+        map.insert("name".into(), player.name.clone().into());
+        map.insert("steamid".into(), player.steamid.clone().into());
+    }
+    map
+}
+
+/// Calls `function` of `ast` on a blocking thread.
+async fn call_script(
+    engine: Arc<Engine>,
+    ast: Arc<AST>,
+    function: String,
+    args: Vec<Dynamic>,
+) -> Result<()> {
+    tokio::task::spawn_blocking(move || {
+        engine.call_fn::<Dynamic>(&mut Scope::new(), &ast, &function, args)
+    })
+    .await?
+    .map(|_| ())
+    .map_err(|e| anyhow::anyhow!("{e}"))
+}
+
+#[derive(Debug)]
+struct Script {
+    name: String,
+    ast: Arc<AST>,
+}
+
+impl Script {
+    fn defines(&self, function: &str, params: usize) -> bool {
+        self.ast
+            .iter_functions()
+            .any(|f| f.name == function && f.params.len() == params)
+    }
+
+    /// File name without the extension.
+    #[cfg(feature = "schedule")]
+    fn stem(&self) -> String {
+        std::path::Path::new(&self.name).file_stem().map_or_else(
+            || self.name.clone(),
+            |stem| stem.to_string_lossy().to_string(),
+        )
+    }
+}
+
+/// A [Plugin] running the Rhai scripts of a directory.
+#[derive(Debug)]
+pub struct ScriptPlugin {
+    pub dir: PathBuf,
+    engine: Option<Arc<Engine>>,
+    scripts: Vec<Script>,
+}
+
+impl ScriptPlugin {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            engine: None,
+            scripts: Vec::new(),
+        }
+    }
+
+    /// Compiles the scripts of [ScriptPlugin::dir] with the API bound to `rcon`. Called by
+    /// [Plugin::init] if it wasn't called before. Scripts that fail to compile are logged
+    /// and skipped.
+    pub fn load(&mut self, rcon: PalworldRCON) -> Result<()> {
+        let engine = create_engine(rcon);
+        let mut paths = std::fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "rhai"))
+            .collect::<Vec<PathBuf>>();
+        paths.sort();
+        self.scripts.clear();
+        for path in paths {
+            let name = path.display().to_string();
+            match engine.compile_file(path) {
+                Ok(ast) => {
+                    log::info!("Loaded script '{name}'");
+                    self.scripts.push(Script {
+                        name,
+                        ast: Arc::new(ast),
+                    });
+                }
+                Err(e) => log::error!("Failed to compile script '{name}': {e}"),
+            }
+        }
+        self.engine = Some(Arc::new(engine));
+        Ok(())
+    }
+
+    /// Adds a job to `scheduler` for every entry of the `schedules()` maps of the loaded
+    /// scripts, with the cron expressions evaluated in `timezone`. Jobs are named
+    /// `script::function`. Invalid entries are logged and skipped. Returns the number of
+    /// jobs added.
+    #[cfg(feature = "schedule")]
+    pub async fn schedule(&self, scheduler: &mut Scheduler, timezone: Tz) -> Result<usize> {
+        let Some(engine) = &self.engine else {
+            anyhow::bail!("Load the scripts before scheduling them");
+        };
+        let mut added = 0;
+        for script in self.scripts.iter().filter(|s| s.defines("schedules", 0)) {
+            let (schedule_engine, ast) = (engine.clone(), script.ast.clone());
+            let schedules = tokio::task::spawn_blocking(move || {
+                schedule_engine.call_fn::<Map>(&mut Scope::new(), &ast, "schedules", ())
+            })
+            .await?;
+            let schedules = match schedules {
+                Ok(schedules) => schedules,
+                Err(e) => {
+                    log::error!("Script '{}' schedules failed: {e}", script.name);
+                    continue;
+                }
+            };
+            for (expression, function) in schedules {
+                let Ok(function) = function.into_string() else {
+                    log::error!(
+                        "Script '{}' schedules '{expression}' to a value that isn't a function \
+                        name",
+                        script.name
+                    );
+                    continue;
+                };
+                if !script.defines(&function, 0) {
+                    log::error!(
+                        "Script '{}' schedules {function}(), which it doesn't define",
+                        script.name
+                    );
+                    continue;
+                }
+                let schedule = match Schedule::parse(&expression, timezone) {
+                    Ok(schedule) => schedule,
+                    Err(e) => {
+                        log::error!("Script '{}': {e}", script.name);
+                        continue;
+                    }
+                };
+                let (engine, ast) = (engine.clone(), script.ast.clone());
+                let name = format!("{}::{function}", script.stem());
+                scheduler.add(name, schedule, move || {
+                    call_script(engine.clone(), ast.clone(), function.clone(), vec![])
+                });
+                added += 1;
+            }
+        }
+        Ok(added)
+    }
+
+    /// Calls `function` in every script defining it, on a blocking thread.
+    async fn call(&self, function: &'static str, args: Vec<Dynamic>) {
+        let Some(engine) = &self.engine else {
+            return;
+        };
+        for script in self
+            .scripts
+            .iter()
+            .filter(|s| s.defines(function, args.len()))
+        {
+            let result = call_script(
+                engine.clone(),
+                script.ast.clone(),
+                function.to_string(),
+                args.clone(),
+            )
+            .await;
+            if let Err(e) = result {
+                log::error!("Script '{}' {function} failed: {e}", script.name);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Plugin for ScriptPlugin {
+    fn name(&self) -> &str {
+        "scripting"
+    }
+
+    async fn init(&mut self, ctx: &PluginContext) -> Result<()> {
+        if self.engine.is_none() {
+            self.load(ctx.rcon.clone())?;
+        }
+        self.call("init", vec![]).await;
+        Ok(())
+    }
+
+    async fn on_event(&mut self, _ctx: &PluginContext, event: &Event) -> Result<()> {
+        self.call("on_event", vec![Dynamic::from_map(event_to_map(event))])
+            .await;
+        Ok(())
+    }
+
+    async fn on_tick(&mut self, _ctx: &PluginContext) -> Result<()> {
+        self.call("on_tick", vec![]).await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::PlayerInfo;
+
+    #[test]
+    fn test_event_to_map() {
+        let map = event_to_map(&Event::PlayerJoined(PlayerInfo {
+            name: "Alice".to_string(),
+            uid: "1".to_string(),
+            steamid: "76561190000000001".to_string(),
+        }));
+        assert_eq!(map["kind"].clone().into_string().unwrap(), "player_joined");
+        assert_eq!(map["name"].clone().into_string().unwrap(), "Alice");
+    }
+
+    #[test]
+    fn test_sandbox_limits() {
+        let engine = create_engine(PalworldRCON::new("localhost", 25575, "password"));
+        assert!(engine.eval::<i64>("let x = 0; loop { x += 1; } x").is_err());
+        assert!(engine
+            .eval::<Dynamic>(r#"import "/etc/passwd" as p;"#)
+            .is_err());
+    }
+
+    #[cfg(feature = "schedule")]
+    #[tokio::test]
+    async fn test_scheduled_function() {
+        let server =
+            crate::mock::MockRcon::start("password", |_| Some("Complete Save".to_string())).await;
+        let mut rcon = PalworldRCON::new("127.0.0.1", server.port, "password");
+        rcon.protocol.detect = false;
+        let dir = std::env::temp_dir().join(format!("scripts-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("nightly.rhai"),
+            r#"
+            fn schedules() {
+                #{ "@reboot": "save_now", "0 4 * * *": "save_now", "0 25 * * *": "save_now", "@daily": "missing" }
+            }
+
+            fn save_now() {
+                save();
+            }
+            "#,
+        )
+        .unwrap();
+
+        let mut plugin = ScriptPlugin::new(&dir);
+        plugin.load(rcon).unwrap();
+        let mut scheduler = Scheduler::new();
+        // The invalid hour and the undefined function are skipped.
+        assert_eq!(plugin.schedule(&mut scheduler, Tz::UTC).await.unwrap(), 2);
+        assert!(scheduler
+            .jobs
+            .iter()
+            .all(|job| job.name == "nightly::save_now"));
+        let handle = tokio::spawn(scheduler.run());
+        for _ in 0..50 {
+            if !server.commands().is_empty() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        handle.abort();
+        assert_eq!(server.commands(), ["Save"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
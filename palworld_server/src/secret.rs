@@ -0,0 +1,162 @@
+//! Passwords and tokens that never show up in logs, traces or error messages.
+//!
+//! A [Secret] prints as `[redacted]` with `{:?}` and has no `Display`, so formatting it by
+//! accident doesn't compile or prints nothing useful. The value is only reachable through
+//! [Secret::expose], which makes every place a credential is actually sent easy to find.
+//! Serialization keeps the value, configuration files need it.
+//!
+//! The value is overwritten with zeros when a [Secret] is dropped, so passwords of a
+//! long-running daemon don't linger in freed memory other processes or core dumps could read.
+//! Copies made with [Secret::expose], like the ones sent over the network, aren't.
+//!
+//! # Example:
+//! ```
+//! use palworld_server::rcon::PalworldRCON;
+//! use palworld_server::secret::Secret;
+//!
+//! let token = Secret::new("123456:ABC-DEF".to_string());
+//! assert_eq!(format!("{token:?}"), "[redacted]");
+//! assert_eq!(token.expose(), "123456:ABC-DEF");
+//!
+//! let rcon = PalworldRCON::new("localhost", 25575, "MyRCONPassword");
+//! assert!(!format!("{rcon:?}").contains("MyRCONPassword"));
+//! ```
+
+use std::borrow::Borrow;
+use std::fmt;
+
+use zeroize::Zeroize;
+
+/// Shown instead of a secret value.
+pub static REDACTED: &str = "[redacted]";
+
+/// A password or token, redacted in `Debug` output and zeroed on drop.
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Secret<T: Zeroize>(T);
+
+impl<T: Zeroize> Secret<T> {
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    /// The secret value, only for sending it where it is needed.
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Zeroize> Drop for Secret<T> {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl<T: Zeroize> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl<T: Zeroize> From<T> for Secret<T> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl From<&str> for Secret<String> {
+    fn from(value: &str) -> Self {
+        Self(value.to_string())
+    }
+}
+
+/// Looks up maps keyed by secrets, like tokens, with a `&str`.
+impl Borrow<str> for Secret<String> {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Whether the value of a setting or header named `key` is a credential, like
+/// `AdminPassword` or `Authorization`.
+pub fn is_secret_key(key: &str) -> bool {
+    let key = key.to_lowercase();
+    ["password", "token", "secret", "authorization"]
+        .iter()
+        .any(|secret| key.contains(secret))
+}
+
+/// `text` with the values of `Key=value` and `Key="value"` assignments of secret keys, see
+/// [is_secret_key], replaced by [REDACTED]. For logging commands and settings that may carry
+/// a password, like the `AdminPassword` of `PalWorldSettings.ini`.
+pub fn redact_assignments(text: &str) -> String {
+    let mut redacted = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(equals) = rest.find('=') {
+        let (before, after) = (&rest[..equals], &rest[equals + 1..]);
+        redacted.push_str(before);
+        redacted.push('=');
+        let key_start = before
+            .rfind(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+            .map_or(0, |i| i + 1);
+        if !is_secret_key(&before[key_start..]) {
+            rest = after;
+            continue;
+        }
+        let value_len = match after.strip_prefix('"') {
+            Some(quoted) => quoted.find('"').map_or(after.len(), |end| end + 2),
+            None => after
+                .find([',', ')', ' ', '\'', '\n', ';', '&'])
+                .unwrap_or(after.len()),
+        };
+        match after.starts_with('"') {
+            true => redacted.push_str(&format!("\"{REDACTED}\"")),
+            false => redacted.push_str(REDACTED),
+        }
+        rest = &after[value_len..];
+    }
+    redacted.push_str(rest);
+    redacted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret() {
+        let secret: Secret<String> = "hunter2".into();
+        assert_eq!(format!("{secret:?}"), "[redacted]");
+        assert_eq!(format!("{:?}", Some(&secret)), "Some([redacted])");
+        assert_eq!(secret.expose(), "hunter2");
+        assert_eq!(secret, Secret::new("hunter2".to_string()));
+
+        let mut wiped = Secret::new(vec![1u8, 2, 3]);
+        wiped.0.zeroize();
+        assert!(wiped.expose().is_empty());
+
+        assert!(is_secret_key("AdminPassword"));
+        assert!(is_secret_key("ServerPassword"));
+        assert!(!is_secret_key("ServerName"));
+    }
+
+    #[test]
+    fn test_redact_assignments() {
+        assert_eq!(
+            redact_assignments(
+                "printf '%s' 'OptionSettings=(ServerName=\"a=b\",AdminPassword=\"x,y\",RCONPort=25575)'"
+            ),
+            "printf '%s' 'OptionSettings=(ServerName=\"a=b\",AdminPassword=\"[redacted]\",RCONPort=25575)'"
+        );
+        assert_eq!(
+            redact_assignments("run --token=abc --port=1"),
+            "run --token=[redacted] --port=1"
+        );
+        assert_eq!(
+            redact_assignments("ServerPassword="),
+            "ServerPassword=[redacted]"
+        );
+        assert_eq!(redact_assignments("no assignments"), "no assignments");
+    }
+}
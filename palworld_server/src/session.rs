@@ -0,0 +1,381 @@
+//! Several RCON commands on one TCP session.
+//!
+//! [PalworldRCON] opens a connection for every command, so a `save` followed by a `shutdown`
+//! may reach the server on different connections, with another client's command in between.
+//! Some servers and RCON proxies act on a command differently depending on what came before
+//! it on the same connection. [PalworldRCON::with_connection] hands a closure an authenticated
+//! [RconSession] that sends every command on the same connection, in order. The session holds
+//! the host's connection slot (see [crate::rcon::set_connections_per_host]) until it ends, so
+//! other clients in the process wait instead of interleaving.
+//!
+//! [PalworldRCON::send_commands] sends a batch of commands on one session and gives up at a
+//! deadline, so scripts like restarts finish in bounded time. Every command gets a
+//! [CommandResult], those not sent because the deadline passed or an earlier command failed
+//! are [CommandOutcome::Skipped].
+//!
+//! # Example:
+//! ```no_run
+//! use std::time::{Duration, Instant};
+//!
+//! use palworld_server::rcon::{PalworldRCON, DEFAULT_SOURCE_PORT};
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let rcon = PalworldRCON::new("palworld.lan", DEFAULT_SOURCE_PORT, "MyRCONPassword");
+//!     rcon.with_connection(|session| async move {
+//!         session.save().await?;
+//!         session.shutdown(Some(Duration::from_secs(10)), "Restarting").await
+//!     })
+//!     .await
+//!     .unwrap();
+//!
+//!     let deadline = Instant::now() + Duration::from_secs(30);
+//!     for result in rcon.send_commands(&["Save", "Shutdown 10 Restarting"], deadline).await {
+//!         println!("{}: {:?}", result.command, result.outcome);
+//!     }
+//! }
+//! ```
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use tokio::sync::{Mutex, OwnedSemaphorePermit};
+
+use crate::command::{self, Command};
+use crate::rcon::{
+    connection_permit, run_command, BroadcastHook, CommandPolicy, PalworldRCON, RconConnection,
+    RconProtocol,
+};
+use crate::trace;
+
+/// What happened to a command of a batch, see [PalworldRCON::send_commands].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum CommandOutcome {
+    /// Sent, with the response.
+    Done(String),
+    /// Refused or failed, with the error.
+    Failed(String),
+    /// Sent, but the deadline passed before the response came.
+    TimedOut,
+    /// Not sent, the deadline passed or an earlier command didn't succeed.
+    Skipped,
+}
+
+/// A command of a batch and what happened to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct CommandResult {
+    pub command: String,
+    pub outcome: CommandOutcome,
+    /// Time until the response or error, zero if skipped.
+    pub elapsed: Duration,
+}
+
+impl CommandResult {
+    pub fn done(&self) -> bool {
+        matches!(self.outcome, CommandOutcome::Done(_))
+    }
+}
+
+/// Results of `commands`, the first with `outcome` and the rest skipped.
+fn stop<S: AsRef<str>>(
+    commands: &[S],
+    outcome: CommandOutcome,
+    elapsed: Duration,
+) -> Vec<CommandResult> {
+    let mut results: Vec<CommandResult> = commands
+        .iter()
+        .map(|command| CommandResult {
+            command: command.as_ref().to_string(),
+            outcome: CommandOutcome::Skipped,
+            elapsed: Duration::ZERO,
+        })
+        .collect();
+    if let Some(first) = results.first_mut() {
+        first.outcome = outcome;
+        first.elapsed = elapsed;
+    }
+    results
+}
+
+struct Connection {
+    conn: RconConnection,
+    // Released when the last handle of the session is dropped.
+    _permit: OwnedSemaphorePermit,
+}
+
+/// An authenticated connection to a server, see the [module documentation](self). Clones
+/// share the connection, which closes when the last clone is dropped.
+#[derive(Clone)]
+pub struct RconSession {
+    connection: Arc<Mutex<Connection>>,
+    protocol: RconProtocol,
+    policy: Option<CommandPolicy>,
+    broadcast_hook: Option<BroadcastHook>,
+    address: String,
+}
+
+impl std::fmt::Debug for RconSession {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RconSession")
+            .field("address", &self.address)
+            .field("protocol", &self.protocol)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RconSession {
+    /// Sends a command on the session's connection, after the commands sent before it.
+    /// Commands denied by [PalworldRCON::policy] return
+    /// [RconError::PolicyDenied](crate::rcon::RconError::PolicyDenied) without being sent.
+    /// Broadcasts are passed to [PalworldRCON::broadcast_hook].
+    pub async fn send_command(&self, cmd: impl Into<&str>) -> Result<String> {
+        let cmd = cmd.into();
+        if let Some(policy) = &self.policy {
+            policy.check(cmd)?;
+        }
+        let mut connection = self.connection.lock().await;
+        log::debug!(
+            "{}RCON command '{cmd}' in session with {}",
+            trace::log_prefix(),
+            self.address
+        );
+        let response = run_command(&mut connection.conn, self.protocol, cmd).await?;
+        if let Some(hook) = &self.broadcast_hook {
+            hook.sent(cmd, None);
+        }
+        Ok(response)
+    }
+
+    /// Sends a typed [Command] and parses its response.
+    pub async fn execute<C: Command>(&self, command: &C) -> Result<C::Response> {
+        let raw = self.send_command(command.encode().as_str()).await?;
+        C::parse(&raw)
+    }
+
+    /// See [PalworldRCON::save].
+    pub async fn save(&self) -> Result<bool> {
+        self.execute(&command::Save).await
+    }
+
+    /// See [PalworldRCON::shutdown].
+    pub async fn shutdown(&self, delay: Option<Duration>, msg: impl Into<String>) -> Result<bool> {
+        self.execute(&command::Shutdown::new(delay, msg)).await
+    }
+
+    /// Sends `commands` in order until one doesn't succeed or `deadline` passes, see
+    /// [PalworldRCON::send_commands].
+    pub async fn send_commands<S: AsRef<str>>(
+        &self,
+        commands: &[S],
+        deadline: Instant,
+    ) -> Vec<CommandResult> {
+        let mut results = Vec::with_capacity(commands.len());
+        for (i, command) in commands.iter().enumerate() {
+            let start = Instant::now();
+            if start >= deadline {
+                results.extend(stop(
+                    &commands[i..],
+                    CommandOutcome::Skipped,
+                    Duration::ZERO,
+                ));
+                break;
+            }
+            let sent =
+                tokio::time::timeout_at(deadline.into(), self.send_command(command.as_ref()));
+            let outcome = match sent.await {
+                Ok(Ok(response)) => CommandOutcome::Done(response),
+                Ok(Err(e)) => CommandOutcome::Failed(format!("{e:#}")),
+                Err(_) => CommandOutcome::TimedOut,
+            };
+            if !matches!(outcome, CommandOutcome::Done(_)) {
+                log::warn!("Batch stopped at '{}': {outcome:?}", command.as_ref());
+                results.extend(stop(&commands[i..], outcome, start.elapsed()));
+                break;
+            }
+            results.push(CommandResult {
+                command: command.as_ref().to_string(),
+                outcome,
+                elapsed: start.elapsed(),
+            });
+        }
+        results
+    }
+}
+
+impl PalworldRCON {
+    /// Opens a connection for a sequence of commands, see [crate::session]. Waits for a free
+    /// connection slot to the host first.
+    pub async fn open_session(&self) -> Result<RconSession> {
+        let protocol = self.effective_protocol().await?;
+        let permit = connection_permit(&self.host, self.port).await;
+        let conn = self.connect(protocol).await?;
+        Ok(RconSession {
+            connection: Arc::new(Mutex::new(Connection {
+                conn,
+                _permit: permit,
+            })),
+            protocol,
+            policy: self.policy.clone(),
+            broadcast_hook: self.broadcast_hook.clone(),
+            address: format!("{}:{}", self.host, self.port),
+        })
+    }
+
+    /// Runs `f` with a session whose commands all go over one connection, closed when `f`
+    /// returns unless `f` kept a clone of the session.
+    pub async fn with_connection<F, Fut, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(RconSession) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        f(self.open_session().await?).await
+    }
+
+    /// Sends `commands` in order on one connection, giving up at `deadline`. Returns a result
+    /// for every command: commands after one that failed or timed out, or once the deadline
+    /// passed, are skipped. Failing to connect fails the first command.
+    pub async fn send_commands<S: AsRef<str>>(
+        &self,
+        commands: &[S],
+        deadline: Instant,
+    ) -> Vec<CommandResult> {
+        let start = Instant::now();
+        if start >= deadline {
+            return stop(commands, CommandOutcome::Skipped, Duration::ZERO);
+        }
+        match tokio::time::timeout_at(deadline.into(), self.open_session()).await {
+            Ok(Ok(session)) => session.send_commands(commands, deadline).await,
+            Ok(Err(e)) => stop(
+                commands,
+                CommandOutcome::Failed(format!("{e:#}")),
+                start.elapsed(),
+            ),
+            Err(_) => stop(commands, CommandOutcome::TimedOut, start.elapsed()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use super::*;
+    use crate::mock::MockRcon;
+    use crate::rcon::available_connections;
+
+    #[tokio::test]
+    async fn test_unreachable() {
+        // Closed again, so connections are refused.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+        let mut rcon = PalworldRCON::new("127.0.0.1", port, "password");
+        rcon.protocol.detect = false;
+        let ran = AtomicBool::new(false);
+        let result = rcon
+            .with_connection(|_| async {
+                ran.store(true, Ordering::SeqCst);
+                Ok(())
+            })
+            .await;
+        assert!(result.is_err());
+        assert!(!ran.load(Ordering::SeqCst));
+        // The slot taken for the failed connection is free again.
+        assert_eq!(available_connections("127.0.0.1", port), 1);
+    }
+
+    #[tokio::test]
+    async fn test_send_commands() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+        let mut rcon = PalworldRCON::new("127.0.0.1", port, "password");
+        rcon.protocol.detect = false;
+        let commands = ["Save", "Shutdown 10"];
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let results = rcon.send_commands(&commands, deadline).await;
+        assert!(matches!(results[0].outcome, CommandOutcome::Failed(_)));
+        assert_eq!(results[1].command, "Shutdown 10");
+        assert_eq!(results[1].outcome, CommandOutcome::Skipped);
+        assert!(!results[0].done());
+
+        let results = rcon.send_commands(&commands, Instant::now()).await;
+        let skipped = |result: &CommandResult| result.outcome == CommandOutcome::Skipped;
+        assert!(results.iter().all(skipped));
+        assert!(rcon.send_commands::<&str>(&[], deadline).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_send_commands_deadline() {
+        // Never answers the second command.
+        let server = MockRcon::start("password", |command| match command {
+            "Save" => Some("Complete Save".to_string()),
+            "Hang" => None,
+            _ => Some("ok".to_string()),
+        })
+        .await;
+        let mut rcon = PalworldRCON::new("127.0.0.1", server.port, "password");
+        rcon.protocol.detect = false;
+        let within = Duration::from_millis(300);
+
+        let start = Instant::now();
+        let results = rcon
+            .send_commands(&["Save", "Hang", "Shutdown 10"], start + within)
+            .await;
+        let elapsed = start.elapsed();
+        assert!(elapsed >= within && elapsed < within * 3, "{elapsed:?}");
+        assert_eq!(
+            results[0].outcome,
+            CommandOutcome::Done("Complete Save".to_string())
+        );
+        assert_eq!(results[1].outcome, CommandOutcome::TimedOut);
+        assert!(results[0].elapsed + results[1].elapsed < within * 2);
+        assert!(results[1].elapsed > within / 2, "{:?}", results[1].elapsed);
+        assert_eq!(results[2].command, "Shutdown 10");
+        assert_eq!(results[2].outcome, CommandOutcome::Skipped);
+        assert_eq!(results[2].elapsed, Duration::ZERO);
+        // In order, and nothing sent after the deadline.
+        assert_eq!(server.commands(), ["Save", "Hang"]);
+    }
+
+    #[tokio::test]
+    async fn test_with_connection_not_interleaved() {
+        let server = MockRcon::start("password", |command| match command.split(' ').next() {
+            Some("Save") => Some("Complete Save".to_string()),
+            Some("Shutdown") => Some("The server will shut down in 10 seconds.".to_string()),
+            _ => Some("Broadcasted: hi".to_string()),
+        })
+        .await;
+        let mut rcon = PalworldRCON::new("127.0.0.1", server.port, "password");
+        rcon.protocol.detect = false;
+
+        let other = rcon.clone();
+        let concurrent = rcon
+            .with_connection(|session| async move {
+                assert!(session.save().await?);
+                // Another client sends while the session is open.
+                let concurrent =
+                    tokio::spawn(async move { other.send_command("Broadcast hi").await });
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                let shutdown = Some(Duration::from_secs(10));
+                assert!(session.shutdown(shutdown, "Restarting").await?);
+                Ok(concurrent)
+            })
+            .await
+            .unwrap();
+        concurrent.await.unwrap().unwrap();
+
+        let received = server.commands_by_connection();
+        let commands: Vec<&str> = received.iter().map(|(_, c)| c.as_str()).collect();
+        assert_eq!(commands, ["Save", "Shutdown 10 Restarting", "Broadcast hi"]);
+        assert_eq!(received[0].0, received[1].0);
+        assert_ne!(received[1].0, received[2].0);
+    }
+}
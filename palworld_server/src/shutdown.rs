@@ -0,0 +1,156 @@
+//! Shutdown notices that survive the trip to the in-game chat.
+//!
+//! Palworld cuts broadcast and shutdown messages at the first space, so messages are
+//! built from localized templates with spaces replaced.
+//!
+//! # Example:
+//! ```
+//! use std::time::Duration;
+//!
+//! use palworld_server::shutdown::{Language, ShutdownMessage};
+//!
+//! let message = ShutdownMessage::new(Language::De).render(Duration::from_secs(300)).unwrap();
+//! assert_eq!(message, "Server-Neustart_in_5_Minute(n)");
+//! ```
+
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+
+/// Default longest rendered message, in characters.
+pub const DEFAULT_MAX_MESSAGE_LEN: usize = 100;
+
+/// Language of the built-in templates.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Language {
+    #[default]
+    En,
+    De,
+    Fr,
+    Ja,
+}
+
+impl Language {
+    /// Shutdown template, `{minutes}` is replaced by the delay rounded up to minutes.
+    pub fn shutdown_template(&self) -> &'static str {
+        match self {
+            Self::En => "Server restarting in {minutes} minute(s)",
+            Self::De => "Server-Neustart in {minutes} Minute(n)",
+            Self::Fr => "Redémarrage du serveur dans {minutes} minute(s)",
+            Self::Ja => "{minutes}分後にサーバーを再起動します",
+        }
+    }
+}
+
+impl std::str::FromStr for Language {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "en" => Ok(Self::En),
+            "de" => Ok(Self::De),
+            "fr" => Ok(Self::Fr),
+            "ja" => Ok(Self::Ja),
+            _ => bail!("Unsupported language '{s}', expected en, de, fr or ja"),
+        }
+    }
+}
+
+/// Builds the message passed to [crate::rcon::PalworldRCON::shutdown].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct ShutdownMessage {
+    /// Template with an optional `{minutes}` placeholder.
+    pub template: String,
+    /// Replaces spaces, None sends them as is.
+    pub replace_space: Option<String>,
+    /// Rendered messages longer than this, in characters, are rejected.
+    pub max_len: usize,
+}
+
+impl ShutdownMessage {
+    /// Create a new [ShutdownMessage] from the template of `language`.
+    pub fn new(language: Language) -> Self {
+        Self::with_template(language.shutdown_template())
+    }
+
+    /// Create a new [ShutdownMessage] from a custom template.
+    pub fn with_template(template: impl Into<String>) -> Self {
+        Self {
+            template: template.into(),
+            replace_space: Some("_".to_string()),
+            max_len: DEFAULT_MAX_MESSAGE_LEN,
+        }
+    }
+
+    pub fn replace_space(mut self, replace_space: Option<String>) -> Self {
+        self.replace_space = replace_space;
+        self
+    }
+
+    pub fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+
+    /// Renders the message for a shutdown in `delay`.
+    pub fn render(&self, delay: Duration) -> Result<String> {
+        let minutes = delay.as_secs().div_ceil(60);
+        let message = self.template.replace("{minutes}", &minutes.to_string());
+        let message = match &self.replace_space {
+            Some(s) => message.replace(' ', s),
+            None => message,
+        };
+        let len = message.chars().count();
+        if len > self.max_len {
+            bail!(
+                "Shutdown message is {len} characters, longer than {}",
+                self.max_len
+            );
+        }
+        Ok(message)
+    }
+}
+
+impl Default for ShutdownMessage {
+    fn default() -> Self {
+        Self::new(Language::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let minute = Duration::from_secs(60);
+        assert_eq!(
+            ShutdownMessage::default().render(minute * 2).unwrap(),
+            "Server_restarting_in_2_minute(s)"
+        );
+        // Rounded up so players never get less warning than announced.
+        assert_eq!(
+            ShutdownMessage::new(Language::Ja)
+                .render(Duration::from_secs(61))
+                .unwrap(),
+            "2分後にサーバーを再起動します"
+        );
+        assert_eq!(
+            ShutdownMessage::new(Language::Fr)
+                .replace_space(None)
+                .render(minute)
+                .unwrap(),
+            "Redémarrage du serveur dans 1 minute(s)"
+        );
+        assert!(ShutdownMessage::default()
+            .max_len(10)
+            .render(minute)
+            .is_err());
+        assert_eq!("DE".parse::<Language>().unwrap(), Language::De);
+        assert!("xx".parse::<Language>().is_err());
+    }
+}
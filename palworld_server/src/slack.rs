@@ -0,0 +1,238 @@
+//! Slack notifier rendering events as Block Kit messages.
+//!
+//! # Example:
+//! ```no_run
+//! use palworld_server::events::{Event, Severity};
+//! use palworld_server::notify::Notifier;
+//! use palworld_server::slack::SlackNotifier;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let mut slack = SlackNotifier::webhook("https://hooks.slack.com/services/...");
+//!     // Only warnings and crashes.
+//!     slack.min_severity = Severity::Warning;
+//!     slack.notify(&Event::SaveCompleted).await.unwrap();
+//! }
+//! ```
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+
+use crate::events::{Event, Severity};
+use crate::mem::MemInfo;
+use crate::models::PlayerInfo;
+use crate::notify::{Notifier, NOTIFICATION_TITLE};
+use crate::secret::Secret;
+
+/// Where Slack messages are posted.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SlackTarget {
+    /// An incoming webhook URL, the channel is fixed by the webhook. The URL is a credential.
+    Webhook(Secret<String>),
+    /// `chat.postMessage` of the Web API with a bot token.
+    Api {
+        token: Secret<String>,
+        channel: String,
+    },
+}
+
+/// Posts events to Slack.
+#[derive(Debug)]
+pub struct SlackNotifier {
+    pub target: SlackTarget,
+    /// Events less severe than this are skipped.
+    pub min_severity: Severity,
+    client: reqwest::Client,
+}
+
+impl SlackNotifier {
+    pub fn new(target: SlackTarget) -> Self {
+        Self {
+            target,
+            min_severity: Severity::Info,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Create a [SlackNotifier] posting to an incoming webhook.
+    pub fn webhook(url: impl Into<String>) -> Self {
+        Self::new(SlackTarget::Webhook(Secret::new(url.into())))
+    }
+
+    /// Create a [SlackNotifier] posting to `channel` with a bot token.
+    pub fn api(token: impl Into<String>, channel: impl Into<String>) -> Self {
+        Self::new(SlackTarget::Api {
+            token: Secret::new(token.into()),
+            channel: channel.into(),
+        })
+    }
+
+    /// Posts a message with a fallback `text` and Block Kit `blocks`.
+    pub async fn post(&self, text: &str, blocks: Value) -> Result<()> {
+        match &self.target {
+            SlackTarget::Webhook(url) => {
+                self.client
+                    .post(url.expose())
+                    .json(&json!({ "text": text, "blocks": blocks }))
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status())
+                    // Errors would show the URL, which has the token in it.
+                    .map_err(reqwest::Error::without_url)?;
+            }
+            SlackTarget::Api { token, channel } => {
+                let response: Value = self
+                    .client
+                    .post("https://slack.com/api/chat.postMessage")
+                    .bearer_auth(token.expose())
+                    .json(&json!({ "channel": channel, "text": text, "blocks": blocks }))
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                // The Web API reports errors with HTTP 200 and ok = false
+                if response["ok"] != json!(true) {
+                    anyhow::bail!("Slack chat.postMessage failed: {}", response["error"]);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Posts a table of the online players.
+    pub async fn post_players(&self, players: &[PlayerInfo]) -> Result<()> {
+        let text = format!("{} player(s) online", players.len());
+        self.post(&text, player_table_blocks(players)).await
+    }
+
+    /// Posts a memory health summary.
+    pub async fn post_health(&self, mem_info: &MemInfo) -> Result<()> {
+        self.post("Server health", health_blocks(mem_info)).await
+    }
+}
+
+#[async_trait]
+impl Notifier for SlackNotifier {
+    async fn notify(&self, event: &Event) -> Result<()> {
+        if event.severity() < self.min_severity {
+            return Ok(());
+        }
+        self.post(&event.to_string(), event_blocks(event)).await
+    }
+}
+
+fn severity_emoji(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Info => ":information_source:",
+        Severity::Warning => ":warning:",
+        Severity::Critical => ":rotating_light:",
+    }
+}
+
+fn header(text: &str) -> Value {
+    json!({ "type": "header", "text": { "type": "plain_text", "text": text } })
+}
+
+fn fields(fields: &[(&str, String)]) -> Value {
+    let fields = fields
+        .iter()
+        .map(|(name, value)| json!({ "type": "mrkdwn", "text": format!("*{name}*\n{value}") }))
+        .collect::<Vec<Value>>();
+    json!({ "type": "section", "fields": fields })
+}
+
+/// Block Kit blocks for an event.
+pub fn event_blocks(event: &Event) -> Value {
+    let mut blocks = vec![
+        header(NOTIFICATION_TITLE),
+        json!({
+            "type": "section",
+            "text": {
+                "type": "mrkdwn",
+                "text": format!("{} {event}", severity_emoji(event.severity())),
+            },
+        }),
+    ];
+    match event {
+        Event::PlayerJoined(player) | Event::PlayerLeft(player) => blocks.push(fields(&[
+            ("Name", player.name.clone()),
+            ("Steam ID", player.steamid.clone()),
+        ])),
+        Event::MemoryAlert(mem_info) => blocks.push(health_fields(mem_info)),
+        _ => (),
+    }
+    Value::Array(blocks)
+}
+
+fn health_fields(mem_info: &MemInfo) -> Value {
+    let used = match (mem_info.used(), mem_info.used_percent()) {
+        (Some(used), Some(percent)) => format!("{used} ({percent:.1}%)"),
+        _ => "unknown".to_string(),
+    };
+    fields(&[
+        ("Used", used),
+        ("Total", mem_info.mem_total.to_string()),
+        ("Available", mem_info.mem_available.to_string()),
+    ])
+}
+
+/// Block Kit blocks summarizing memory usage.
+pub fn health_blocks(mem_info: &MemInfo) -> Value {
+    json!([header("Server health"), health_fields(mem_info)])
+}
+
+/// Block Kit blocks with a table of players.
+pub fn player_table_blocks(players: &[PlayerInfo]) -> Value {
+    let mut table = String::from("```\n");
+    let width = players
+        .iter()
+        .map(|p| p.name.len())
+        .max()
+        .unwrap_or(4)
+        .max(4);
+    table.push_str(&format!("{:width$}  Steam ID\n", "Name"));
+    for player in players {
+        table.push_str(&format!("{:width$}  {}\n", player.name, player.steamid));
+    }
+    table.push_str("```");
+    json!([
+        header(&format!("{} player(s) online", players.len())),
+        { "type": "section", "text": { "type": "mrkdwn", "text": table } },
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_player_table_blocks() {
+        let players = vec![PlayerInfo {
+            name: "Alice".to_string(),
+            uid: "1".to_string(),
+            steamid: "76561190000000001".to_string(),
+        }];
+        let blocks = player_table_blocks(&players);
+        assert_eq!(blocks[0]["text"]["text"], "1 player(s) online");
+        let table = blocks[1]["text"]["text"].as_str().unwrap();
+        assert!(table.contains("Alice  76561190000000001"));
+    }
+
+    #[test]
+    fn test_event_blocks() {
+        let blocks = event_blocks(&Event::Crash {
+            reason: "segfault".to_string(),
+        });
+        let text = blocks[1]["text"]["text"].as_str().unwrap();
+        assert!(text.starts_with(":rotating_light:"));
+        assert!(text.contains("segfault"));
+    }
+
+    #[test]
+    fn test_webhook_redacted() {
+        let slack = SlackNotifier::webhook("https://hooks.slack.com/services/T0/B0/token");
+        assert!(!format!("{slack:?}").contains("token"));
+    }
+}
@@ -1,22 +1,284 @@
+use crate::dryrun::{DryRun, PlannedAction};
 use crate::mem::MemInfo;
+use crate::models::ByteSize;
+pub use crate::models::{DiskUsage, ProcessInfo};
+use crate::parse;
+use crate::progress::Progress;
+use crate::secret::{redact_assignments, Secret};
+#[cfg(feature = "savefile")]
+use crate::savefile::{LevelSave, WorldTime};
+use crate::trace;
+use crate::verbosity;
 use anyhow::Result;
-use log::{error, info, warn};
-use ssh2::Session;
-use std::io::Read;
-use tokio::net::TcpStream;
-use tokio::task;
+use base64::Engine;
+use sha2::{Digest, Sha256};
+use ssh2::{Channel, Session};
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::task::{self, JoinHandle};
 
-#[derive(Debug)]
+/// Default for [PalworldConnection::disk_low_percent].
+pub const DEFAULT_DISK_LOW_PERCENT: f64 = 90.0;
+
+/// Errors specific to the SSH connection, returned inside [anyhow::Error] so they can be
+/// matched with `downcast_ref::<SshError>()`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SshError {
+    /// Connecting or running the command took longer than the timeout.
+    Timeout(Duration),
+    /// The SHA-256 of a transferred file doesn't match the source, the file was removed.
+    IntegrityCheckFailed(String),
+}
+
+impl std::fmt::Display for SshError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Timeout(timeout) => write!(f, "SSH timed out after {timeout:?}"),
+            Self::IntegrityCheckFailed(path) => {
+                write!(f, "{path} doesn't match the source after the transfer")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SshError {}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
 pub struct PalworldConnection {
     pub hostname: String,
     pub username: String,
-    pub password: String,
+    /// Redacted in `Debug` output.
+    pub password: Secret<String>,
+    /// Timeout for connecting and running a command, None waits forever.
+    pub timeout: Option<Duration>,
+    /// Interval between SSH keepalive messages, None disables keepalives.
+    pub keepalive: Option<Duration>,
+    /// Operating system of the host, None detects it once, the first time a command depends
+    /// on it.
+    pub host_os: Option<HostOs>,
+    /// The operating system detected for a hostname, shared by clones.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    detected_os: Arc<Mutex<Option<(String, HostOs)>>>,
+    /// Throttling, resuming and verification of SFTP transfers.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub transfer: TransferOptions,
+    /// Record commands and transfers that change the host instead of running them, see
+    /// [crate::dryrun].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub dry_run: Option<DryRun>,
+    /// Receives the steps of installs, backups and migrations, see [crate::progress].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub progress: Option<Arc<dyn Progress>>,
+    /// Receives failures of backups and full disks found by
+    /// [PalworldConnection::get_disk_usage].
+    #[cfg(feature = "rcon")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub events: Option<crate::events::EventBus>,
+    /// Used percentage of a filesystem at which [PalworldConnection::get_disk_usage]
+    /// publishes [crate::events::Event::DiskLow] to `events`.
+    #[cfg_attr(feature = "serde", serde(default = "default_disk_low_percent"))]
+    pub disk_low_percent: f64,
+}
+
+#[cfg(feature = "serde")]
+fn default_disk_low_percent() -> f64 {
+    DEFAULT_DISK_LOW_PERCENT
+}
+
+impl std::fmt::Debug for PalworldConnection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PalworldConnection")
+            .field("hostname", &self.hostname)
+            .field("username", &self.username)
+            .field("password", &self.password)
+            .field("timeout", &self.timeout)
+            .field("keepalive", &self.keepalive)
+            .field("host_os", &self.host_os)
+            .field("transfer", &self.transfer)
+            .field("dry_run", &self.dry_run)
+            .field("progress", &self.progress.is_some())
+            .field("disk_low_percent", &self.disk_low_percent)
+            .finish()
+    }
+}
+
+/// Called with the bytes transferred so far and the size of the file.
+pub type TransferProgress = Arc<dyn Fn(u64, u64) + Send + Sync>;
+
+/// Options of the SFTP transfers [PalworldConnection::download], [PalworldConnection::upload]
+/// and [PalworldConnection::copy_to].
+#[derive(Clone)]
+pub struct TransferOptions {
+    /// Bytes per second, None is unlimited.
+    pub bandwidth_limit: Option<u64>,
+    /// Bytes read and written at a time, progress is reported after each chunk.
+    pub chunk_size: usize,
+    /// Continue a partial destination file left by an interrupted transfer instead of
+    /// starting over. Off by default: any smaller destination counts as partial, even a
+    /// different or older file, so only turn it on with [TransferOptions::verify] or when
+    /// the destination is known to be an earlier attempt.
+    pub resume: bool,
+    /// Compare the SHA-256 of both ends afterwards, a mismatch removes the destination and
+    /// returns [SshError::IntegrityCheckFailed].
+    pub verify: bool,
+    pub progress: Option<TransferProgress>,
+}
+
+impl TransferOptions {
+    pub fn new() -> Self {
+        Self {
+            bandwidth_limit: None,
+            chunk_size: 256 * 1024,
+            resume: false,
+            verify: true,
+            progress: None,
+        }
+    }
+}
+
+impl Default for TransferOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for TransferOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TransferOptions")
+            .field("bandwidth_limit", &self.bandwidth_limit)
+            .field("chunk_size", &self.chunk_size)
+            .field("resume", &self.resume)
+            .field("verify", &self.verify)
+            .field("progress", &self.progress.is_some())
+            .finish()
+    }
+}
+
+/// Operating system of the SSH host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub enum HostOs {
+    Linux,
+    Windows,
+}
+
+/// Service control actions, mapped to systemctl on Linux and the *-Service cmdlets on Windows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub enum ServiceAction {
+    Start,
+    Stop,
+    Restart,
+}
+
+impl std::str::FromStr for ServiceAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "start" => Ok(Self::Start),
+            "stop" => Ok(Self::Stop),
+            "restart" => Ok(Self::Restart),
+            _ => anyhow::bail!("Unknown service action '{s}', expected start, stop or restart"),
+        }
+    }
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
 pub struct CommandResult {
-    output: String,
-    exit_status: i32,
+    /// The command that was executed, with passwords in `key=value` assignments redacted.
+    pub command: String,
+    /// Standard output of the command.
+    pub output: String,
+    /// Standard error of the command.
+    pub stderr: String,
+    /// Exit status of the command, 0 is success.
+    pub exit_status: i32,
+}
+
+impl CommandResult {
+    /// Returns true if the command exited with a status of 0.
+    pub fn success(&self) -> bool {
+        self.exit_status == 0
+    }
+}
+
+/// A single line of output from a streamed command.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub enum OutputLine {
+    Stdout(String),
+    Stderr(String),
+}
+
+/// Output of a long running command started with [PalworldConnection::command_streamed].
+#[derive(Debug)]
+pub struct CommandStream {
+    lines: mpsc::Receiver<OutputLine>,
+    send_eof: Arc<AtomicBool>,
+    terminate: Arc<AtomicBool>,
+    handle: JoinHandle<Result<i32>>,
+}
+
+impl CommandStream {
+    /// Waits for the next line of output. Returns None once the command has finished.
+    pub async fn next_line(&mut self) -> Option<OutputLine> {
+        self.lines.recv().await
+    }
+
+    /// Sends EOF to the standard input of the remote command.
+    pub fn send_eof(&self) {
+        self.send_eof.store(true, Ordering::SeqCst);
+    }
+
+    /// Closes the channel, the remote command is hung up on and no more output is read.
+    pub fn terminate(&self) {
+        self.terminate.store(true, Ordering::SeqCst);
+    }
+
+    /// Waits for the command to finish and returns its exit status.
+    pub async fn wait(self) -> Result<i32> {
+        // Drop the receiver so the reader can't block on a full channel.
+        drop(self.lines);
+        self.handle.await?
+    }
+}
+
+/// A local port forwarded to the SSH host, started with [PalworldConnection::forward_port].
+/// The forward stops when this is dropped.
+#[derive(Debug)]
+pub struct PortForward {
+    /// Local address accepting connections, on 127.0.0.1.
+    pub local_addr: SocketAddr,
+    handle: JoinHandle<Result<()>>,
+}
+
+impl PortForward {
+    pub fn local_port(&self) -> u16 {
+        self.local_addr.port()
+    }
+
+    /// Stops accepting connections, connections already forwarded are left to finish.
+    pub fn close(self) {}
+}
+
+impl Drop for PortForward {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
 }
 
 impl PalworldConnection {
@@ -28,79 +290,1025 @@ impl PalworldConnection {
         Self {
             hostname: hostname.into(),
             username: username.into(),
-            password: password.into(),
+            password: Secret::new(password.into()),
+            timeout: None,
+            keepalive: None,
+            host_os: None,
+            detected_os: Arc::default(),
+            transfer: TransferOptions::new(),
+            dry_run: None,
+            progress: None,
+            #[cfg(feature = "rcon")]
+            events: None,
+            disk_low_percent: DEFAULT_DISK_LOW_PERCENT,
         }
     }
 
-    async fn connect(&self) -> Result<Session> {
+    /// Publishes `event` to [PalworldConnection::events], if set.
+    #[cfg(feature = "rcon")]
+    pub(crate) fn publish(&self, event: crate::events::Event) {
+        if let Some(events) = &self.events {
+            events.publish(event);
+        }
+    }
+
+    async fn connect(&self, timeout: Option<Duration>) -> Result<Session> {
         log::trace!("Connecting to {}...", self.hostname);
-        let tcp_stream = TcpStream::connect(&self.hostname).await?;
+        let tcp_stream = match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, TcpStream::connect(&self.hostname))
+                .await
+                .map_err(|_| SshError::Timeout(timeout))??,
+            None => TcpStream::connect(&self.hostname).await?,
+        };
         let mut session: Session = Session::new()?;
         session.set_tcp_stream(tcp_stream);
+        if let Some(timeout) = timeout {
+            session.set_timeout(timeout.as_millis().try_into().unwrap_or(u32::MAX));
+        }
+        if let Some(keepalive) = self.keepalive {
+            session.set_keepalive(false, keepalive.as_secs().max(1).try_into().unwrap_or(u32::MAX));
+        }
         log::trace!("Session handshake...");
-        session.handshake()?;
+        session.handshake().map_err(|e| map_timeout(e.into(), timeout))?;
         log::trace!("Session user auth with password...");
-        session.userauth_password(self.username.as_str(), self.password.as_str())?;
+        session
+            .userauth_password(self.username.as_str(), self.password.expose().as_str())
+            .map_err(|e| map_timeout(e.into(), timeout))?;
         log::trace!("Session userauth with password ok!");
         Ok(session)
     }
 
+    /// Executes a command, using [PalworldConnection::timeout].
     pub async fn command(&self, cmd: impl Into<String>) -> Result<CommandResult> {
-        let session = self.connect().await?;
+        self.command_with_timeout(cmd, self.timeout).await
+    }
+
+    /// Executes a command, returning [SshError::Timeout] if it takes longer than `timeout`.
+    pub async fn command_with_timeout(
+        &self,
+        cmd: impl Into<String>,
+        timeout: Option<Duration>,
+    ) -> Result<CommandResult> {
+        self.run_command_with_timeout(cmd.into(), None, timeout).await
+    }
+
+    /// Executes a command with `input` on its standard input, using
+    /// [PalworldConnection::timeout]. Contents passed this way stay off the remote command
+    /// line, where other users of the host could read them with `ps`.
+    pub async fn command_with_input(
+        &self,
+        cmd: impl Into<String>,
+        input: impl Into<Vec<u8>>,
+    ) -> Result<CommandResult> {
+        self.run_command_with_timeout(cmd.into(), Some(input.into()), self.timeout)
+            .await
+    }
+
+    async fn run_command_with_timeout(
+        &self,
+        cmd: String,
+        input: Option<Vec<u8>>,
+        timeout: Option<Duration>,
+    ) -> Result<CommandResult> {
+        match timeout {
+            Some(timeout) => {
+                tokio::time::timeout(timeout, self.run_command(cmd, input, Some(timeout)))
+                    .await
+                    .map_err(|_| SshError::Timeout(timeout))?
+            }
+            None => self.run_command(cmd, input, None).await,
+        }
+    }
+
+    async fn run_command(
+        &self,
+        cmd: String,
+        input: Option<Vec<u8>>,
+        timeout: Option<Duration>,
+    ) -> Result<CommandResult> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let session = self.connect(timeout).await?;
         log::trace!("Creating new channel");
         let mut channel = session.channel_session()?;
 
-        let cmd: String = cmd.into();
+        // The trace doesn't follow into the blocking thread.
+        let prefix = trace::log_prefix();
         let command_result = task::spawn_blocking(move || -> Result<CommandResult> {
-            log::info!("Executing command '{}'", &cmd);
+            let command = redact_assignments(&cmd);
+            log::info!("{prefix}Executing command '{command}'");
             channel.exec(cmd.as_str())?;
-            let mut buffer = String::new();
-            channel.read_to_string(&mut buffer)?;
-            log::trace!("Sending EOF");
-            channel.send_eof()?;
+            if let Some(input) = &input {
+                channel.write_all(input)?;
+                channel.send_eof()?;
+            }
+            let (buffer, stderr) = read_channel(&session, &mut channel, deadline)?;
+            if input.is_none() {
+                log::trace!("Sending EOF");
+                channel.send_eof()?;
+            }
             log::trace!("Waiting for close...");
             channel.wait_close()?;
             let exit_status = channel.exit_status()?;
             log::info!("Exit status: {exit_status}");
+            if verbosity::verbosity().logs_responses() {
+                log::debug!("{prefix}Output: {}", redact_assignments(buffer.trim_end()));
+            }
+            if exit_status != 0 {
+                log::warn!("{prefix}Command '{command}' failed: {}", stderr.trim_end());
+            }
             Ok(CommandResult {
+                command,
                 output: buffer,
+                stderr,
                 exit_status,
             })
         })
-        .await??;
+        .await?
+        .map_err(|e| map_timeout(e, timeout))?;
         Ok(command_result)
     }
 
+    /// Executes a command that changes the host. With [PalworldConnection::dry_run] set it's
+    /// recorded instead and an empty, successful result returned.
+    pub async fn change(&self, cmd: impl Into<String>) -> Result<CommandResult> {
+        let cmd: String = cmd.into();
+        match &self.dry_run {
+            Some(dry_run) => {
+                let command = redact_assignments(&cmd);
+                dry_run.record(PlannedAction::Command {
+                    host: self.hostname.clone(),
+                    command: command.clone(),
+                });
+                Ok(CommandResult {
+                    command,
+                    output: String::new(),
+                    stderr: String::new(),
+                    exit_status: 0,
+                })
+            }
+            None => self.command(cmd).await,
+        }
+    }
+
+    /// [PalworldConnection::command_with_input] for commands that change the host, recorded
+    /// without the input when [PalworldConnection::dry_run] is set.
+    pub async fn change_with_input(
+        &self,
+        cmd: impl Into<String>,
+        input: impl Into<Vec<u8>>,
+    ) -> Result<CommandResult> {
+        match &self.dry_run {
+            Some(_) => self.change(cmd).await,
+            None => self.command_with_input(cmd, input).await,
+        }
+    }
+
+    /// Records a transfer if [PalworldConnection::dry_run] is set, returns true if it was.
+    fn record_transfer(&self, from: String, to: String) -> bool {
+        match &self.dry_run {
+            Some(dry_run) => {
+                dry_run.record(PlannedAction::Transfer { from, to });
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Executes a command and streams its output line by line instead of buffering it,
+    /// for commands like `journalctl -f` that may never finish on their own.
+    pub async fn command_streamed(&self, cmd: impl Into<String>) -> Result<CommandStream> {
+        let session = self.connect(self.timeout).await?;
+        log::trace!("Creating new channel");
+        let mut channel = session.channel_session()?;
+
+        let cmd: String = cmd.into();
+        let command = redact_assignments(&cmd);
+        log::info!("{}Executing streamed command '{command}'", trace::log_prefix());
+        channel.exec(cmd.as_str())?;
+
+        let (tx, rx) = mpsc::channel(128);
+        let send_eof = Arc::new(AtomicBool::new(false));
+        let terminate = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let send_eof = send_eof.clone();
+            let terminate = terminate.clone();
+            task::spawn_blocking(move || -> Result<i32> {
+                stream_channel(session, channel, tx, send_eof, terminate)
+            })
+        };
+        Ok(CommandStream {
+            lines: rx,
+            send_eof,
+            terminate,
+            handle,
+        })
+    }
+
+    /// Forwards `local_port` on 127.0.0.1 to `remote_port` on the host's loopback interface,
+    /// like `ssh -L local_port:localhost:remote_port`. A local port of 0 picks a free one,
+    /// see [PortForward::local_addr]. Every local connection gets its own SSH session so a
+    /// dropped SSH connection only affects the connections using it.
+    pub async fn forward_port(&self, remote_port: u16, local_port: u16) -> Result<PortForward> {
+        let listener = TcpListener::bind(("127.0.0.1", local_port)).await?;
+        let local_addr = listener.local_addr()?;
+        log::info!(
+            "Forwarding {local_addr} to {}, port {remote_port}",
+            self.hostname
+        );
+        let connection = self.clone();
+        let handle = tokio::spawn(async move {
+            loop {
+                let (client, peer) = listener.accept().await?;
+                log::debug!("Forwarding connection from {peer}");
+                let connection = connection.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = connection.forward_client(client, remote_port).await {
+                        log::warn!("Forwarded connection from {peer} failed: {e}");
+                    }
+                });
+            }
+        });
+        Ok(PortForward { local_addr, handle })
+    }
+
+    async fn forward_client(&self, client: TcpStream, remote_port: u16) -> Result<()> {
+        let session = self.connect(self.timeout).await?;
+        let channel = session.channel_direct_tcpip("127.0.0.1", remote_port, None)?;
+        let client = client.into_std()?;
+        task::spawn_blocking(move || forward_channel(session, channel, client)).await?
+    }
+
+    /// Downloads `remote` from the host to `local` over SFTP with [PalworldConnection::transfer]
+    /// and returns the size of the file.
+    pub async fn download(&self, remote: &str, local: impl AsRef<Path>) -> Result<u64> {
+        let from = format!("{}:{remote}", self.hostname);
+        if self.record_transfer(from, local.as_ref().display().to_string()) {
+            return Ok(0);
+        }
+        let session = self.connect(self.timeout).await?;
+        let options = self.transfer.clone();
+        let remote_path = PathBuf::from(remote);
+        let local = local.as_ref().to_path_buf();
+        let size = {
+            let local = local.clone();
+            task::spawn_blocking(move || -> Result<u64> {
+                let sftp = session.sftp()?;
+                let total = sftp.stat(&remote_path)?.size.unwrap_or(0);
+                let existing = std::fs::metadata(&local).ok().map(|metadata| metadata.len());
+                let offset = resume_offset(options.resume, existing, total);
+                log::info!(
+                    "Downloading {} to {} from byte {offset}",
+                    remote_path.display(),
+                    local.display()
+                );
+                let mut source = sftp.open(&remote_path)?;
+                source.seek(SeekFrom::Start(offset))?;
+                let mut destination = std::fs::OpenOptions::new()
+                    .create(true)
+                    .write(true)
+                    .truncate(offset == 0)
+                    .open(&local)?;
+                destination.seek(SeekFrom::Start(offset))?;
+                Ok(offset + copy_chunked(&mut source, &mut destination, offset, total, &options)?)
+            })
+            .await??
+        };
+        if self.transfer.verify {
+            let expected = self.sha256(remote).await?;
+            if sha256_file(&local)? != expected {
+                std::fs::remove_file(&local)?;
+                return Err(SshError::IntegrityCheckFailed(local.display().to_string()).into());
+            }
+        }
+        Ok(size)
+    }
+
+    /// Uploads `local` to `remote` on the host over SFTP with [PalworldConnection::transfer]
+    /// and returns the size of the file.
+    pub async fn upload(&self, local: impl AsRef<Path>, remote: &str) -> Result<u64> {
+        let to = format!("{}:{remote}", self.hostname);
+        if self.record_transfer(local.as_ref().display().to_string(), to) {
+            return Ok(0);
+        }
+        let session = self.connect(self.timeout).await?;
+        let options = self.transfer.clone();
+        let local = local.as_ref().to_path_buf();
+        let remote_path = PathBuf::from(remote);
+        let size = {
+            let local = local.clone();
+            task::spawn_blocking(move || -> Result<u64> {
+                let sftp = session.sftp()?;
+                let total = std::fs::metadata(&local)?.len();
+                let existing = sftp.stat(&remote_path).ok().and_then(|stat| stat.size);
+                let offset = resume_offset(options.resume, existing, total);
+                log::info!(
+                    "Uploading {} to {} from byte {offset}",
+                    local.display(),
+                    remote_path.display()
+                );
+                let mut source = std::fs::File::open(&local)?;
+                source.seek(SeekFrom::Start(offset))?;
+                let mut destination = open_remote(&sftp, &remote_path, offset)?;
+                Ok(offset + copy_chunked(&mut source, &mut destination, offset, total, &options)?)
+            })
+            .await??
+        };
+        if self.transfer.verify && self.sha256(remote).await? != sha256_file(&local)? {
+            self.remove_file(remote).await?;
+            return Err(SshError::IntegrityCheckFailed(remote.to_string()).into());
+        }
+        Ok(size)
+    }
+
+    /// Copies `path` on this host to `destination_path` on `destination` over SFTP with
+    /// [PalworldConnection::transfer] and returns the size of the file. The file is streamed
+    /// through this machine without a local copy.
+    pub async fn copy_to(
+        &self,
+        path: &str,
+        destination: &PalworldConnection,
+        destination_path: &str,
+    ) -> Result<u64> {
+        let from = format!("{}:{path}", self.hostname);
+        let to = format!("{}:{destination_path}", destination.hostname);
+        if self.record_transfer(from, to) {
+            return Ok(0);
+        }
+        let source_session = self.connect(self.timeout).await?;
+        let destination_session = destination.connect(destination.timeout).await?;
+        let options = self.transfer.clone();
+        let source_path = PathBuf::from(path);
+        let target_path = PathBuf::from(destination_path);
+        let hosts = format!("{} to {}", self.hostname, destination.hostname);
+        let size = task::spawn_blocking(move || -> Result<u64> {
+            let source_sftp = source_session.sftp()?;
+            let destination_sftp = destination_session.sftp()?;
+            let total = source_sftp.stat(&source_path)?.size.unwrap_or(0);
+            let existing = destination_sftp
+                .stat(&target_path)
+                .ok()
+                .and_then(|stat| stat.size);
+            let offset = resume_offset(options.resume, existing, total);
+            log::info!(
+                "Copying {} to {} from {hosts}, from byte {offset}",
+                source_path.display(),
+                target_path.display()
+            );
+            let mut source = source_sftp.open(&source_path)?;
+            source.seek(SeekFrom::Start(offset))?;
+            let mut target = open_remote(&destination_sftp, &target_path, offset)?;
+            Ok(offset + copy_chunked(&mut source, &mut target, offset, total, &options)?)
+        })
+        .await??;
+        if self.transfer.verify
+            && self.sha256(path).await? != destination.sha256(destination_path).await?
+        {
+            destination.remove_file(destination_path).await?;
+            return Err(SshError::IntegrityCheckFailed(destination_path.to_string()).into());
+        }
+        Ok(size)
+    }
+
+    /// Removes a file from the host, a missing file isn't an error.
+    async fn remove_file(&self, path: &str) -> Result<()> {
+        let result = match self.host_os().await? {
+            HostOs::Linux => self.command(format!("rm -f {}", shell_quote(path))).await?,
+            HostOs::Windows => {
+                let path = powershell_quote(path);
+                self.powershell(&format!(
+                    "if (Test-Path -LiteralPath {path}) \
+                    {{ Remove-Item -Force -ErrorAction Stop -LiteralPath {path} }}"
+                ))
+                .await?
+            }
+        };
+        if !result.success() {
+            anyhow::bail!("Failed to remove {path}: {}", result.stderr.trim());
+        }
+        Ok(())
+    }
+
+    /// Hex SHA-256 of a file on the host.
+    pub async fn sha256(&self, path: &str) -> Result<String> {
+        let result = match self.host_os().await? {
+            HostOs::Linux => self.command(format!("sha256sum {}", shell_quote(path))).await?,
+            HostOs::Windows => {
+                self.powershell(&format!(
+                    "(Get-FileHash -Algorithm SHA256 -LiteralPath {}).Hash",
+                    powershell_quote(path)
+                ))
+                .await?
+            }
+        };
+        match result.output.split_whitespace().next() {
+            Some(hash) if result.success() => Ok(hash.to_lowercase()),
+            _ => anyhow::bail!("Failed to hash {path}: {}", result.stderr.trim()),
+        }
+    }
+
+    /// Detects the operating system of the host.
+    pub async fn detect_host_os(&self) -> Result<HostOs> {
+        let result = self.command("uname -s").await?;
+        if result.success() && result.output.trim() == "Linux" {
+            return Ok(HostOs::Linux);
+        }
+        // The default shell of OpenSSH for Windows is cmd.exe
+        let result = self.command("ver").await?;
+        if result.output.contains("Windows") {
+            return Ok(HostOs::Windows);
+        }
+        anyhow::bail!("Unsupported host operating system: {}", result.output.trim())
+    }
+
+    /// Returns [PalworldConnection::host_os], or detects it the first time.
+    pub(crate) async fn host_os(&self) -> Result<HostOs> {
+        if let Some(host_os) = self.host_os {
+            return Ok(host_os);
+        }
+        if let Some((hostname, host_os)) = &*self.detected_os.lock().unwrap() {
+            if *hostname == self.hostname {
+                return Ok(*host_os);
+            }
+        }
+        let host_os = self.detect_host_os().await?;
+        *self.detected_os.lock().unwrap() = Some((self.hostname.clone(), host_os));
+        Ok(host_os)
+    }
+
+    /// Runs a PowerShell script on a Windows host.
+    async fn powershell(&self, script: &str) -> Result<CommandResult> {
+        self.command(powershell_command(script)).await
+    }
+
+    /// Gets memory information of the host.
     pub async fn get_memory_info(&self) -> Result<MemInfo> {
-        let bytes_regex = regex::Regex::new(r"[0-9]{1,99} kB$")?;
-        let cmd = "cat /proc/meminfo | grep -e 'Mem' -e 'Cached' -e 'Buffers'";
-        let result = self
-            .command(cmd)
-            .await?;
-        let mut mem_info = MemInfo::default();
-        for line in result.output.split("\n") {
-            if !bytes_regex.is_match(line) {
-                continue;
+        match self.host_os().await? {
+            HostOs::Linux => self.get_memory_info_linux().await,
+            HostOs::Windows => self.get_memory_info_windows().await,
+        }
+    }
+
+    async fn get_memory_info_windows(&self) -> Result<MemInfo> {
+        let script = "$os = Get-CimInstance Win32_OperatingSystem; \
+            \"MemTotal=$($os.TotalVisibleMemorySize)\"; \
+            \"MemFree=$($os.FreePhysicalMemory)\"";
+        let result = self.powershell(script).await?;
+        let values = windows_values(&result, "memory information", &["MemTotal", "MemFree"])?;
+        // Both are reported in kB
+        let mem_total = ByteSize::from_kib(values[0]);
+        let mem_free = ByteSize::from_kib(values[1]);
+        Ok(MemInfo {
+            mem_total,
+            mem_free,
+            // Windows has no separate notion of available memory, standby pages count as free.
+            mem_available: mem_free,
+            ..Default::default()
+        })
+    }
+
+    /// Gets disk usage of the filesystem containing `path`. On Windows only the drive of
+    /// `path` is used, defaulting to `C:`. Publishes [crate::events::Event::DiskLow] when
+    /// it is at least [PalworldConnection::disk_low_percent] full.
+    pub async fn get_disk_usage(&self, path: &str) -> Result<DiskUsage> {
+        let usage = self.read_disk_usage(path).await?;
+        #[cfg(feature = "rcon")]
+        if usage
+            .used_percent()
+            .is_some_and(|percent| percent >= self.disk_low_percent)
+        {
+            self.publish(crate::events::Event::DiskLow {
+                path: path.to_string(),
+                usage: usage.clone(),
+            });
+        }
+        Ok(usage)
+    }
+
+    async fn read_disk_usage(&self, path: &str) -> Result<DiskUsage> {
+        match self.host_os().await? {
+            HostOs::Linux => {
+                let result = self.command(format!("df -kP {}", shell_quote(path))).await?;
+                if !result.success() {
+                    anyhow::bail!("df failed: {}", result.stderr.trim());
+                }
+                parse::parse_df(&result.output)
+            }
+            HostOs::Windows => {
+                let drive = match path.get(..2) {
+                    Some(drive) if drive.ends_with(':') => drive.to_uppercase(),
+                    _ => "C:".to_string(),
+                };
+                let script = format!(
+                    "$d = Get-CimInstance Win32_LogicalDisk -Filter \"DeviceID={}\"; \
+                    \"Size=$($d.Size)\"; \"FreeSpace=$($d.FreeSpace)\"",
+                    powershell_quote(&drive)
+                );
+                let result = self.powershell(&script).await?;
+                let what = format!("disk usage of {drive}");
+                let values = windows_values(&result, &what, &["Size", "FreeSpace"])?;
+                let (total, available) = (values[0], values[1]);
+                Ok(DiskUsage {
+                    total: ByteSize(total),
+                    used: ByteSize(total.saturating_sub(available)),
+                    available: ByteSize(available),
+                })
+            }
+        }
+    }
+
+    /// Gets the processes named `name`, for example `PalServer-Linux-Test` or `PalServer-Win64-Test-Cmd`.
+    pub async fn get_processes(&self, name: &str) -> Result<Vec<ProcessInfo>> {
+        let result = match self.host_os().await? {
+            HostOs::Linux => {
+                self.command(format!("ps -C {} -o pid=,rss=,comm=", shell_quote(name)))
+                    .await?
+            }
+            HostOs::Windows => {
+                let script = format!(
+                    "Get-CimInstance Win32_Process -Filter \"Name LIKE {}\" | \
+                    ForEach-Object {{ \"$($_.ProcessId) $([math]::Round($_.WorkingSetSize / 1024)) $($_.Name)\" }}",
+                    powershell_quote(&format!("{name}%"))
+                );
+                self.powershell(&script).await?
+            }
+        };
+        Ok(parse::parse_processes(&result.output))
+    }
+
+    /// Starts, stops or restarts a service, systemd on Linux and the service manager on Windows.
+    pub async fn service(&self, action: ServiceAction, name: &str) -> Result<CommandResult> {
+        let result = match self.host_os().await? {
+            HostOs::Linux => {
+                let action = match action {
+                    ServiceAction::Start => "start",
+                    ServiceAction::Stop => "stop",
+                    ServiceAction::Restart => "restart",
+                };
+                self.change(format!("systemctl {action} {}", shell_quote(name)))
+                    .await?
+            }
+            HostOs::Windows => {
+                let action = match action {
+                    ServiceAction::Start => "Start-Service",
+                    ServiceAction::Stop => "Stop-Service",
+                    ServiceAction::Restart => "Restart-Service",
+                };
+                let script = format!("{action} -Name {}", powershell_quote(name));
+                self.change(powershell_command(&script)).await?
+            }
+        };
+        Ok(result)
+    }
+
+    /// Returns true if the service is running.
+    pub async fn is_service_active(&self, name: &str) -> Result<bool> {
+        match self.host_os().await? {
+            HostOs::Linux => {
+                let result = self
+                    .command(format!("systemctl is-active {}", shell_quote(name)))
+                    .await?;
+                Ok(parse::parse_systemctl_is_active(&result.output))
+            }
+            HostOs::Windows => {
+                let result = self
+                    .powershell(&format!("(Get-Service -Name {}).Status", powershell_quote(name)))
+                    .await?;
+                Ok(result.output.trim() == "Running")
+            }
+        }
+    }
+
+    /// Reads a file from the host, binary files like saves included.
+    pub async fn read_file(&self, path: &str) -> Result<Vec<u8>> {
+        let result = match self.host_os().await? {
+            HostOs::Linux => self.command(format!("base64 -w0 {}", shell_quote(path))).await?,
+            HostOs::Windows => {
+                self.powershell(&format!(
+                    "[Convert]::ToBase64String([IO.File]::ReadAllBytes({}))",
+                    powershell_quote(path)
+                ))
+                .await?
+            }
+        };
+        if !result.success() {
+            anyhow::bail!("Failed to read {path}: {}", result.stderr.trim());
+        }
+        Ok(base64::engine::general_purpose::STANDARD.decode(result.output.trim())?)
+    }
+
+    /// In-game day and time from the world's `Level.sav`, as of the server's last save.
+    #[cfg(feature = "savefile")]
+    pub async fn get_world_time(&self, level_sav: &str) -> Result<WorldTime> {
+        let level = LevelSave::parse(&self.read_file(level_sav).await?)?;
+        level
+            .world_time()
+            .ok_or_else(|| anyhow::anyhow!("{level_sav} has no game time"))
+    }
+
+    async fn get_memory_info_linux(&self) -> Result<MemInfo> {
+        let result = self.command("cat /proc/meminfo").await?;
+        if !result.success() {
+            anyhow::bail!("Failed to read /proc/meminfo: {}", result.stderr.trim());
+        }
+        Ok(parse::parse_meminfo(&result.output))
+    }
+}
+
+/// Runs a shell command as root, through `sudo -n` if `sudo` is set, failing on a non
+/// zero exit status.
+pub(crate) async fn run_privileged(
+    connection: &PalworldConnection,
+    sudo: bool,
+    command: &str,
+) -> Result<CommandResult> {
+    check_status(connection.command(privileged(sudo, command)).await?)
+}
+
+/// [run_privileged] for commands that change the host, recorded instead when
+/// [PalworldConnection::dry_run] is set.
+pub(crate) async fn change_privileged(
+    connection: &PalworldConnection,
+    sudo: bool,
+    command: &str,
+) -> Result<CommandResult> {
+    check_status(connection.change(privileged(sudo, command)).await?)
+}
+
+/// [change_privileged] with `input` on the standard input of the command.
+#[cfg(feature = "rcon")]
+pub(crate) async fn change_privileged_with_input(
+    connection: &PalworldConnection,
+    sudo: bool,
+    command: &str,
+    input: &str,
+) -> Result<CommandResult> {
+    check_status(
+        connection
+            .change_with_input(privileged(sudo, command), input)
+            .await?,
+    )
+}
+
+fn privileged(sudo: bool, command: &str) -> String {
+    match sudo {
+        true => format!("sudo -n sh -c {}", shell_quote(command)),
+        false => command.to_string(),
+    }
+}
+
+fn check_status(result: CommandResult) -> Result<CommandResult> {
+    if !result.success() {
+        anyhow::bail!(
+            "'{}' failed with exit status {}: {}",
+            result.command,
+            result.exit_status,
+            result.stderr.trim()
+        );
+    }
+    Ok(result)
+}
+
+/// Values of `keys` printed as `key=value` lines by a PowerShell script, failing with its
+/// stderr if it exited with an error or left a key out.
+fn windows_values(result: &CommandResult, what: &str, keys: &[&str]) -> Result<Vec<u64>> {
+    if !result.success() {
+        anyhow::bail!("Failed to get {what}: {}", result.stderr.trim());
+    }
+    let values = parse::parse_key_values(&result.output);
+    keys.iter()
+        .map(|key| match values.get(*key) {
+            Some(value) => Ok(*value),
+            None => anyhow::bail!("Failed to get {what}, no {key}: {}", result.stderr.trim()),
+        })
+        .collect()
+}
+
+/// Quotes an argument for a POSIX shell.
+pub(crate) fn shell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+/// Command line running a PowerShell script.
+fn powershell_command(script: &str) -> String {
+    // -EncodedCommand takes base64 UTF-16LE and avoids quoting through cmd.exe
+    let utf16: Vec<u8> = script.encode_utf16().flat_map(|c| c.to_le_bytes()).collect();
+    let encoded = base64::engine::general_purpose::STANDARD.encode(utf16);
+    format!("powershell -NoProfile -NonInteractive -EncodedCommand {encoded}")
+}
+
+/// Quotes an argument as a PowerShell string literal.
+fn powershell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', "''"))
+}
+
+/// Where a transfer starts, the size of a partial destination if resuming and it isn't
+/// larger than the source.
+fn resume_offset(resume: bool, existing: Option<u64>, total: u64) -> u64 {
+    match resume {
+        true => existing.filter(|&existing| existing <= total).unwrap_or(0),
+        false => 0,
+    }
+}
+
+/// Opens a remote file for writing at `offset`, truncating it when starting over.
+fn open_remote(sftp: &ssh2::Sftp, path: &Path, offset: u64) -> Result<ssh2::File> {
+    let mut flags = ssh2::OpenFlags::WRITE | ssh2::OpenFlags::CREATE;
+    if offset == 0 {
+        flags |= ssh2::OpenFlags::TRUNCATE;
+    }
+    let mut file = sftp.open_mode(path, flags, 0o644, ssh2::OpenType::File)?;
+    file.seek(SeekFrom::Start(offset))?;
+    Ok(file)
+}
+
+/// Copies `source` to `destination` in chunks, reporting progress and sleeping to stay under
+/// the bandwidth limit. Returns the bytes copied, `offset` bytes were copied before.
+fn copy_chunked(
+    source: &mut impl Read,
+    destination: &mut impl Write,
+    offset: u64,
+    total: u64,
+    options: &TransferOptions,
+) -> Result<u64> {
+    let mut buffer = vec![0u8; options.chunk_size.max(1)];
+    let start = Instant::now();
+    let mut copied = 0u64;
+    loop {
+        let read = source.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        destination.write_all(&buffer[..read])?;
+        copied += read as u64;
+        if let Some(progress) = &options.progress {
+            progress(offset + copied, total);
+        }
+        if let Some(limit) = options.bandwidth_limit {
+            let due = Duration::from_secs_f64(copied as f64 / limit.max(1) as f64);
+            if let Some(wait) = due.checked_sub(start.elapsed()) {
+                std::thread::sleep(wait);
             }
-            let kb = match bytes_regex.find(line) {
-                Some(kb) => kb.as_str(),
-                None => continue,
+        }
+    }
+    destination.flush()?;
+    Ok(copied)
+}
+
+/// Hex SHA-256 of a local file.
+fn sha256_file(path: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Converts libssh2 timeouts into [SshError::Timeout] so callers can match on them.
+fn map_timeout(err: anyhow::Error, timeout: Option<Duration>) -> anyhow::Error {
+    let timed_out = if let Some(e) = err.downcast_ref::<ssh2::Error>() {
+        // LIBSSH2_ERROR_TIMEOUT
+        e.code() == ssh2::ErrorCode::Session(-9)
+    } else if let Some(e) = err.downcast_ref::<std::io::Error>() {
+        e.kind() == ErrorKind::TimedOut
+    } else {
+        false
+    };
+    match (timed_out, timeout) {
+        (true, Some(timeout)) => SshError::Timeout(timeout).into(),
+        _ => err,
+    }
+}
+
+/// Reads stdout and stderr of `channel` until EOF. Both are read as data arrives, so a command
+/// filling one while the other is read doesn't stall on the channel window. Past `deadline`
+/// the channel is closed and a [ErrorKind::TimedOut] error returned.
+fn read_channel(
+    session: &Session,
+    channel: &mut Channel,
+    deadline: Option<Instant>,
+) -> Result<(String, String)> {
+    session.set_blocking(false);
+    let result = poll_channel(session, channel, deadline);
+    session.set_blocking(true);
+    let (stdout, stderr) = match result {
+        Ok(output) => output,
+        Err(e) => {
+            // Hang up so the remote command isn't left attached to a dead reader.
+            if let Err(close) = channel.close() {
+                log::debug!("Failed to close the channel: {close}");
+            }
+            return Err(e);
+        }
+    };
+    Ok((
+        String::from_utf8(stdout).map_err(|e| std::io::Error::new(ErrorKind::InvalidData, e))?,
+        String::from_utf8(stderr).map_err(|e| std::io::Error::new(ErrorKind::InvalidData, e))?,
+    ))
+}
+
+fn poll_channel(
+    session: &Session,
+    channel: &mut Channel,
+    deadline: Option<Instant>,
+) -> Result<(Vec<u8>, Vec<u8>)> {
+    let mut stdout: Vec<u8> = Vec::new();
+    let mut stderr: Vec<u8> = Vec::new();
+    let mut buffer = [0u8; 4096];
+    loop {
+        let mut read_any = false;
+        for (output, is_stderr) in [(&mut stdout, false), (&mut stderr, true)] {
+            let read = match is_stderr {
+                true => channel.stderr().read(&mut buffer),
+                false => channel.read(&mut buffer),
             };
-            match &line.to_lowercase() {
-                x if x.contains("memtotal:") => {
-                    mem_info.mem_total = kb.replace(" kB", "").parse()?
-                }
-                x if x.contains("memfree:") => mem_info.mem_free = kb.replace(" kB", "").parse()?,
-                x if x.contains("memavailable:") => {
-                    mem_info.mem_available = kb.replace(" kB", "").parse()?
+            match read {
+                Ok(0) => (),
+                Ok(n) => {
+                    read_any = true;
+                    output.extend_from_slice(&buffer[..n]);
                 }
-                x if x.contains("buffers:") => mem_info.buffers = kb.replace(" kB", "").parse()?,
-                x if x.contains("cached:") => mem_info.cached = kb.replace(" kB", "").parse()?,
-                _ => (),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => (),
+                Err(e) => return Err(e.into()),
+            }
+        }
+        if !read_any {
+            if channel.eof() {
+                return Ok((stdout, stderr));
+            }
+            // The session timeout doesn't apply in non-blocking mode.
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Err(std::io::Error::from(ErrorKind::TimedOut).into());
+            }
+            send_keepalive(session)?;
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+/// Sends a keepalive if the interval has elapsed. A non-blocking session that can't send
+/// right now tries again on the next call.
+fn send_keepalive(session: &Session) -> Result<()> {
+    match session.keepalive_send() {
+        Ok(_) => Ok(()),
+        // LIBSSH2_ERROR_EAGAIN
+        Err(e) if e.code() == ssh2::ErrorCode::Session(-37) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Reads stdout and stderr of `channel` until EOF, sending every complete line to `tx`.
+fn stream_channel(
+    session: Session,
+    mut channel: Channel,
+    tx: mpsc::Sender<OutputLine>,
+    send_eof: Arc<AtomicBool>,
+    terminate: Arc<AtomicBool>,
+) -> Result<i32> {
+    // Non-blocking so both streams can be polled and the flags checked.
+    session.set_blocking(false);
+    let mut stdout: Vec<u8> = Vec::new();
+    let mut stderr: Vec<u8> = Vec::new();
+    let mut buffer = [0u8; 4096];
+    loop {
+        if terminate.load(Ordering::SeqCst) || tx.is_closed() {
+            log::trace!("Terminating streamed command");
+            session.set_blocking(true);
+            channel.close()?;
+            return Ok(-1);
+        }
+        if send_eof.swap(false, Ordering::SeqCst) {
+            log::trace!("Sending EOF");
+            session.set_blocking(true);
+            channel.send_eof()?;
+            session.set_blocking(false);
+        }
+        send_keepalive(&session)?;
+        let mut read_any = false;
+        for (pending, is_stderr) in [(&mut stdout, false), (&mut stderr, true)] {
+            let read = if is_stderr {
+                channel.stderr().read(&mut buffer)
+            } else {
+                channel.read(&mut buffer)
             };
+            match read {
+                Ok(0) => (),
+                Ok(n) => {
+                    read_any = true;
+                    pending.extend_from_slice(&buffer[..n]);
+                    while let Some(pos) = pending.iter().position(|b| *b == b'\n') {
+                        let line: Vec<u8> = pending.drain(..=pos).collect();
+                        let line = String::from_utf8_lossy(&line[..pos]).to_string();
+                        let line = match is_stderr {
+                            true => OutputLine::Stderr(line),
+                            false => OutputLine::Stdout(line),
+                        };
+                        if tx.blocking_send(line).is_err() {
+                            break;
+                        }
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => (),
+                Err(e) => return Err(e.into()),
+            }
+        }
+        if !read_any {
+            if channel.eof() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(50));
         }
-        Ok(mem_info)
     }
+    // Flush anything left without a trailing newline.
+    for (pending, is_stderr) in [(stdout, false), (stderr, true)] {
+        if pending.is_empty() {
+            continue;
+        }
+        let line = String::from_utf8_lossy(&pending).to_string();
+        let line = match is_stderr {
+            true => OutputLine::Stderr(line),
+            false => OutputLine::Stdout(line),
+        };
+        let _ = tx.blocking_send(line);
+    }
+    session.set_blocking(true);
+    channel.wait_close()?;
+    let exit_status = channel.exit_status()?;
+    log::info!("Exit status: {exit_status}");
+    Ok(exit_status)
+}
+
+/// Copies data between a forwarded `channel` and the local `client` until either side closes.
+fn forward_channel(
+    session: Session,
+    mut channel: Channel,
+    mut client: std::net::TcpStream,
+) -> Result<()> {
+    // Non-blocking so both directions can be served from one thread.
+    client.set_nonblocking(true)?;
+    session.set_blocking(false);
+    let mut buffer = [0u8; 4096];
+    let mut to_remote: Vec<u8> = Vec::new();
+    let mut to_local: Vec<u8> = Vec::new();
+    let mut client_closed = false;
+    loop {
+        let mut progressed = false;
+        if to_remote.is_empty() && !client_closed {
+            match client.read(&mut buffer) {
+                Ok(0) => {
+                    client_closed = true;
+                    progressed = true;
+                    session.set_blocking(true);
+                    channel.send_eof()?;
+                    session.set_blocking(false);
+                }
+                Ok(n) => {
+                    progressed = true;
+                    to_remote.extend_from_slice(&buffer[..n]);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => (),
+                Err(e) => return Err(e.into()),
+            }
+        }
+        if !to_remote.is_empty() {
+            match channel.write(&to_remote) {
+                Ok(n) => {
+                    progressed = true;
+                    to_remote.drain(..n);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => (),
+                Err(e) => return Err(e.into()),
+            }
+        }
+        if to_local.is_empty() {
+            match channel.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(n) => {
+                    progressed = true;
+                    to_local.extend_from_slice(&buffer[..n]);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => (),
+                Err(e) => return Err(e.into()),
+            }
+        }
+        if !to_local.is_empty() {
+            match client.write(&to_local) {
+                Ok(n) => {
+                    progressed = true;
+                    to_local.drain(..n);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => (),
+                // The local client hung up.
+                Err(_) => break,
+            }
+        }
+        if !progressed {
+            send_keepalive(&session)?;
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+    client.set_nonblocking(false)?;
+    let _ = client.write_all(&to_local);
+    session.set_blocking(true);
+    channel.close()?;
+    Ok(())
 }
 
 #[cfg(test)]
@@ -129,7 +1337,8 @@ mod test {
         let result = connection
             .command("cat /proc/meminfo | grep \"Mem\"")
             .await?;
-        assert_eq!(result.exit_status, 0);
+        assert!(result.success());
+        assert_eq!(result.command, "cat /proc/meminfo | grep \"Mem\"");
         for line in result.output.split("\n") {
             println!("{line}");
         }
@@ -137,6 +1346,104 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_command_streamed() -> Result<()> {
+        let connection = get_connection();
+
+        let mut stream = connection
+            .command_streamed("echo one; echo two 1>&2; echo three")
+            .await?;
+        let mut lines = Vec::new();
+        while let Some(line) = stream.next_line().await {
+            lines.push(line);
+        }
+        assert!(lines.contains(&OutputLine::Stdout("three".to_string())));
+        assert!(lines.contains(&OutputLine::Stderr("two".to_string())));
+        assert_eq!(stream.wait().await?, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_command_timeout() -> Result<()> {
+        let connection = get_connection();
+
+        let timeout = Duration::from_secs(1);
+        let err = connection
+            .command_with_timeout("sleep 5", Some(timeout))
+            .await
+            .unwrap_err();
+        assert_eq!(err.downcast_ref::<SshError>(), Some(&SshError::Timeout(timeout)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_forward_port() -> Result<()> {
+        let connection = get_connection();
+        // Forward to the SSH server itself, it greets first.
+        let forward = connection.forward_port(22, 0).await?;
+        let mut stream = TcpStream::connect(forward.local_addr).await?;
+        let mut banner = [0u8; 4];
+        tokio::io::AsyncReadExt::read_exact(&mut stream, &mut banner).await?;
+        assert_eq!(&banner, b"SSH-");
+        forward.close();
+        Ok(())
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(shell_quote("pal world"), "'pal world'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+        assert_eq!(powershell_quote("it's"), "'it''s'");
+    }
+
+    #[tokio::test]
+    async fn test_command_result_redacted() -> Result<()> {
+        let mut connection = PalworldConnection::new("localhost:22", "steam", "password");
+        connection.dry_run = Some(DryRun::new());
+        let result = connection
+            .change("sed -i 's/AdminPassword=\"old\"/AdminPassword=\"hunter2\"/' settings.ini")
+            .await?;
+        assert!(!result.command.contains("hunter2"));
+        assert!(!format!("{result:?}").contains("hunter2"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_chunked() -> Result<()> {
+        assert_eq!(resume_offset(true, Some(40), 100), 40);
+        assert_eq!(resume_offset(true, Some(120), 100), 0);
+        assert_eq!(resume_offset(true, None, 100), 0);
+        assert_eq!(resume_offset(false, Some(40), 100), 0);
+
+        let data: Vec<u8> = (0..100).collect();
+        let reported = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut options = TransferOptions::new();
+        assert!(!options.resume);
+        options.chunk_size = 30;
+        options.bandwidth_limit = Some(200);
+        options.progress = Some({
+            let reported = reported.clone();
+            Arc::new(move |done, total| reported.lock().unwrap().push((done, total)))
+        });
+        // Resume after the first 40 bytes.
+        let mut destination = data[..40].to_vec();
+        let start = Instant::now();
+        let copied = copy_chunked(&mut &data[40..], &mut destination, 40, 100, &options)?;
+        assert_eq!(copied, 60);
+        assert_eq!(destination, data);
+        assert_eq!(*reported.lock().unwrap(), vec![(70, 100), (100, 100)]);
+        // 60 bytes at 200 bytes per second.
+        assert!(start.elapsed() >= Duration::from_millis(300));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_detect_host_os() -> Result<()> {
+        let connection = get_connection();
+        assert_eq!(connection.detect_host_os().await?, HostOs::Linux);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_get_mem_info() -> Result<()> {
         let connection = get_connection();
@@ -145,8 +1452,8 @@ mod test {
 
         println!("{mem_info:#?}");
         println!(
-            "Used memory: {}MiB {}%",
-            mem_info.used().unwrap().checked_div(1024).unwrap(),
+            "Used memory: {} {:.2}%",
+            mem_info.used().unwrap(),
             mem_info.used_percent().unwrap()
         );
         Ok(())
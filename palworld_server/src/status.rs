@@ -0,0 +1,151 @@
+//! Versioned status document for external controllers.
+//!
+//! Unlike the other types in this crate the field names never change with the
+//! `serde-camel-case` feature, and missing values are serialized as explicit `null`s.
+//! Fields are only added within a [STATUS_SCHEMA_VERSION], anything else bumps it.
+//!
+//! # Example:
+//! ```no_run
+//! use palworld_server::health::HealthCheck;
+//! use palworld_server::rcon::{PalworldRCON, DEFAULT_SOURCE_PORT};
+//! use palworld_server::status::ServerStatus;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let rcon = PalworldRCON::new("localhost", DEFAULT_SOURCE_PORT, "MyRCONPassword");
+//!     let status = ServerStatus::collect(&rcon, &HealthCheck::default(), None).await;
+//!     println!("{}", serde_json::to_string(&status).unwrap());
+//! }
+//! ```
+
+use std::time::SystemTime;
+
+use crate::health::HealthCheck;
+use crate::mem::MemInfo;
+use crate::models::PlayerInfo;
+use crate::rcon::PalworldRCON;
+
+/// Version of the [ServerStatus] schema.
+pub const STATUS_SCHEMA_VERSION: u32 = 1;
+
+/// Health, players, memory and version of a server.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ServerStatus {
+    pub schema_version: u32,
+    /// RFC 3339 timestamp in UTC.
+    pub generated_at: String,
+    pub healthy: bool,
+    pub failures: Vec<String>,
+    pub latency_ms: u64,
+    pub version: Option<String>,
+    pub player_count: Option<usize>,
+    pub players: Option<Vec<StatusPlayer>>,
+    pub memory: Option<StatusMemory>,
+}
+
+/// A player in a [ServerStatus].
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct StatusPlayer {
+    pub name: String,
+    pub uid: String,
+    pub steamid: String,
+}
+
+impl From<PlayerInfo> for StatusPlayer {
+    fn from(player: PlayerInfo) -> Self {
+        Self {
+            name: player.name,
+            uid: player.uid,
+            steamid: player.steamid,
+        }
+    }
+}
+
+/// Memory in a [ServerStatus], in bytes.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct StatusMemory {
+    pub total_bytes: u64,
+    pub used_bytes: Option<u64>,
+    pub available_bytes: u64,
+    pub used_percent: Option<f64>,
+}
+
+impl From<&MemInfo> for StatusMemory {
+    fn from(mem_info: &MemInfo) -> Self {
+        Self {
+            total_bytes: mem_info.mem_total.as_bytes(),
+            used_bytes: mem_info.used().map(|used| used.as_bytes()),
+            available_bytes: mem_info.mem_available.as_bytes(),
+            used_percent: mem_info.used_percent(),
+        }
+    }
+}
+
+impl ServerStatus {
+    /// Runs `check` and fetches the players, `memory` is passed in since it may come
+    /// from the local machine or over SSH. Failures end up in the status, never as errors.
+    pub async fn collect(
+        rcon: &PalworldRCON,
+        check: &HealthCheck,
+        memory: Option<&MemInfo>,
+    ) -> Self {
+        let report = check.run(rcon).await;
+        let mut failures = report.failures;
+        // Skip the player list of a server that didn't answer the health check.
+        let players = match report.version.is_some() {
+            true => match rcon.get_player_info().await {
+                Ok(players) => Some(players),
+                Err(e) => {
+                    failures.push(format!("Failed to get players: {e}"));
+                    None
+                }
+            },
+            false => None,
+        };
+        Self {
+            schema_version: STATUS_SCHEMA_VERSION,
+            generated_at: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+            healthy: failures.is_empty(),
+            failures,
+            latency_ms: report.latency.as_millis() as u64,
+            version: report.version,
+            player_count: players.as_ref().map(|players| players.len()),
+            players: players.map(|players| players.into_iter().map(StatusPlayer::from).collect()),
+            memory: memory.map(StatusMemory::from),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_explicit_nulls() {
+        let status = ServerStatus {
+            schema_version: STATUS_SCHEMA_VERSION,
+            generated_at: "2024-01-01T00:00:00Z".to_string(),
+            healthy: false,
+            failures: vec!["Timed out after 5s".to_string()],
+            latency_ms: 5000,
+            version: None,
+            player_count: None,
+            players: None,
+            memory: None,
+        };
+        assert_eq!(
+            serde_json::to_value(&status).unwrap(),
+            serde_json::json!({
+                "schema_version": 1,
+                "generated_at": "2024-01-01T00:00:00Z",
+                "healthy": false,
+                "failures": ["Timed out after 5s"],
+                "latency_ms": 5000,
+                "version": null,
+                "player_count": null,
+                "players": null,
+                "memory": null,
+            })
+        );
+    }
+}
@@ -0,0 +1,1017 @@
+//! SQLite store of player sessions.
+//!
+//! # Example:
+//! ```
+//! use std::time::{Duration, SystemTime};
+//!
+//! use palworld_server::models::PlayerInfo;
+//! use palworld_server::store::SessionStore;
+//!
+//! let store = SessionStore::open_in_memory().unwrap();
+//! let player = PlayerInfo {
+//!     name: "Alice".to_string(),
+//!     uid: "1".to_string(),
+//!     steamid: "76561190000000001".to_string(),
+//! };
+//! let joined = SystemTime::now();
+//! store.record_join(&player, joined).unwrap();
+//! store.record_leave(&player, joined + Duration::from_secs(3600)).unwrap();
+//! assert_eq!(store.playtime(&player.steamid, joined).unwrap(), Duration::from_secs(3600));
+//! ```
+
+use std::ops::Range;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use rusqlite::{params, Connection, OptionalExtension};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+
+#[cfg(feature = "billing")]
+use crate::billing::{self, MonthlyPlaytime, Tz};
+use crate::events::{Event, EventBus};
+#[cfg(feature = "metrics")]
+use crate::metrics::MetricSample;
+#[cfg(feature = "metrics")]
+use crate::models::ByteSize;
+use crate::models::{BroadcastRecord, PlayerInfo};
+use crate::moderation::{BanRecord, Strike, WhitelistEntry};
+use crate::rcon;
+#[cfg(feature = "schedule")]
+use crate::tempban::TempBan;
+use crate::uptime::{Probe, UptimeReport};
+
+/// Converts a [SystemTime] to unix seconds.
+pub fn to_unix(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs() as i64),
+    }
+}
+
+/// Converts unix seconds to a [SystemTime].
+pub fn from_unix(secs: i64) -> SystemTime {
+    match secs >= 0 {
+        true => UNIX_EPOCH + Duration::from_secs(secs as u64),
+        false => UNIX_EPOCH - Duration::from_secs(secs.unsigned_abs()),
+    }
+}
+
+/// A single play session.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Session {
+    pub steamid: String,
+    pub name: String,
+    pub joined_at: SystemTime,
+    /// None while the player is still online.
+    pub left_at: Option<SystemTime>,
+}
+
+/// How often [SessionStore::spawn_recorder] marks open sessions as seen, the playtime
+/// counted for a session left open by a stopped daemon is at most this much too long.
+pub const SEEN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Player sessions persisted in SQLite.
+#[derive(Debug)]
+pub struct SessionStore {
+    conn: Mutex<Connection>,
+}
+
+impl SessionStore {
+    /// Opens or creates the store at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    /// Opens a store that only lives in memory, useful for tests.
+    pub fn open_in_memory() -> Result<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(conn: Connection) -> Result<Self> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS sessions (
+                id INTEGER PRIMARY KEY,
+                steamid TEXT NOT NULL,
+                name TEXT NOT NULL,
+                joined_at INTEGER NOT NULL,
+                left_at INTEGER,
+                uid TEXT,
+                seen_at INTEGER
+            );
+            CREATE INDEX IF NOT EXISTS sessions_steamid ON sessions (steamid);
+            CREATE TABLE IF NOT EXISTS milestones (
+                steamid TEXT NOT NULL,
+                playtime INTEGER NOT NULL,
+                reached_at INTEGER NOT NULL,
+                PRIMARY KEY (steamid, playtime)
+            );
+            CREATE TABLE IF NOT EXISTS probes (
+                at INTEGER NOT NULL,
+                reachable INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS probes_at ON probes (at);
+            CREATE TABLE IF NOT EXISTS metrics (
+                at INTEGER NOT NULL,
+                players INTEGER NOT NULL,
+                memory_used INTEGER,
+                cpu_percent REAL
+            );
+            CREATE INDEX IF NOT EXISTS metrics_at ON metrics (at);
+            CREATE TABLE IF NOT EXISTS broadcasts (
+                id INTEGER PRIMARY KEY,
+                sent_at INTEGER NOT NULL,
+                message TEXT NOT NULL,
+                initiator TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS strikes (
+                id INTEGER PRIMARY KEY,
+                steamid TEXT NOT NULL,
+                reason TEXT NOT NULL,
+                issued_at INTEGER NOT NULL,
+                issued_by TEXT NOT NULL,
+                action TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS strikes_steamid ON strikes (steamid);
+            CREATE TABLE IF NOT EXISTS bans (
+                id INTEGER PRIMARY KEY,
+                steamid TEXT NOT NULL,
+                reason TEXT NOT NULL,
+                banned_at INTEGER NOT NULL,
+                expires_at INTEGER,
+                lifted_at INTEGER
+            );
+            CREATE INDEX IF NOT EXISTS bans_expires_at ON bans (expires_at);
+            CREATE TABLE IF NOT EXISTS whitelist (
+                steamid TEXT PRIMARY KEY,
+                name TEXT,
+                added_at INTEGER NOT NULL
+            );",
+        )?;
+        // Stores created before sessions recorded the Unique ID.
+        if conn.prepare("SELECT uid FROM sessions LIMIT 0").is_err() {
+            conn.execute("ALTER TABLE sessions ADD COLUMN uid TEXT", [])?;
+        }
+        // And before they recorded when the player was last seen.
+        if conn
+            .prepare("SELECT seen_at FROM sessions LIMIT 0")
+            .is_err()
+        {
+            conn.execute("ALTER TABLE sessions ADD COLUMN seen_at INTEGER", [])?;
+        }
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// Locks the underlying connection, for queries not covered by the store API.
+    pub fn connection(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().expect("Session store lock poisoned")
+    }
+
+    /// Starts a session, a session left open by a previous run is closed first.
+    pub fn record_join(&self, player: &PlayerInfo, at: SystemTime) -> Result<()> {
+        self.record_leave(player, at)?;
+        self.connection().execute(
+            "INSERT INTO sessions (steamid, name, joined_at, uid, seen_at)
+            VALUES (?1, ?2, ?3, ?4, ?3)",
+            params![player.steamid, player.name, to_unix(at), player.uid],
+        )?;
+        Ok(())
+    }
+
+    /// Ends the open session of a player, if any.
+    pub fn record_leave(&self, player: &PlayerInfo, at: SystemTime) -> Result<()> {
+        self.connection().execute(
+            "UPDATE sessions SET left_at = ?2 WHERE steamid = ?1 AND left_at IS NULL",
+            params![player.steamid, to_unix(at)],
+        )?;
+        Ok(())
+    }
+
+    /// Notes that the players of open sessions are still online at `at`.
+    pub fn record_seen(&self, at: SystemTime) -> Result<()> {
+        self.connection().execute(
+            "UPDATE sessions SET seen_at = ?1 WHERE left_at IS NULL",
+            params![to_unix(at)],
+        )?;
+        Ok(())
+    }
+
+    /// Closes the open sessions of players not in `online`, when they were last seen or at
+    /// `at` for sessions that never were. Returns the number closed.
+    pub fn close_sessions_except(&self, online: &[PlayerInfo], at: SystemTime) -> Result<usize> {
+        let conn = self.connection();
+        let open = conn
+            .prepare("SELECT id, steamid FROM sessions WHERE left_at IS NULL")?
+            .query_map([], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<(i64, String)>>>()?;
+        let mut closed = 0;
+        for (id, steamid) in open {
+            if online.iter().any(|player| player.steamid == steamid) {
+                continue;
+            }
+            closed += conn.execute(
+                "UPDATE sessions SET left_at = MAX(joined_at, COALESCE(seen_at, ?2)) WHERE id = ?1",
+                params![id, to_unix(at)],
+            )?;
+        }
+        Ok(closed)
+    }
+
+    /// Records the players found online at `at`, after the daemon wasn't watching. Sessions
+    /// of everyone else are closed, see [SessionStore::close_sessions_except], and sessions
+    /// are started for the players without one.
+    pub fn record_online(&self, players: &[PlayerInfo], at: SystemTime) -> Result<()> {
+        self.close_sessions_except(players, at)?;
+        for player in players {
+            let open: bool = self.connection().query_row(
+                "SELECT EXISTS (SELECT 1 FROM sessions WHERE steamid = ?1 AND left_at IS NULL)",
+                params![player.steamid],
+                |row| row.get(0),
+            )?;
+            if !open {
+                self.record_join(player, at)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Records [Event::PlayerJoined], [Event::PlayerLeft] and [Event::PlayersOnline], other
+    /// events are ignored.
+    pub fn record_event(&self, event: &Event, at: SystemTime) -> Result<()> {
+        match event {
+            Event::PlayerJoined(player) => self.record_join(player, at),
+            Event::PlayerLeft(player) => self.record_leave(player, at),
+            Event::PlayersOnline(players) => self.record_online(players, at),
+            _ => Ok(()),
+        }
+    }
+
+    /// Number of finished sessions of a player, 0 for someone on their first visit.
+    pub fn completed_sessions(&self, steamid: &str) -> Result<u64> {
+        let count: i64 = self.connection().query_row(
+            "SELECT COUNT(*) FROM sessions WHERE steamid = ?1 AND left_at IS NOT NULL",
+            params![steamid],
+            |row| row.get(0),
+        )?;
+        Ok(count as u64)
+    }
+
+    /// When a player was first seen.
+    pub fn first_seen(&self, steamid: &str) -> Result<Option<SystemTime>> {
+        let joined: Option<i64> = self
+            .connection()
+            .query_row(
+                "SELECT MIN(joined_at) FROM sessions WHERE steamid = ?1",
+                params![steamid],
+                |row| row.get(0),
+            )
+            .optional()?
+            .flatten();
+        Ok(joined.map(from_unix))
+    }
+
+    /// When the player with the Unique ID `uid` was last online, `now` if they still are.
+    /// None if they were never seen or only before sessions recorded the Unique ID.
+    pub fn last_seen_by_uid(&self, uid: &str, now: SystemTime) -> Result<Option<SystemTime>> {
+        let seen: Option<i64> = self.connection().query_row(
+            "SELECT MAX(COALESCE(left_at, ?2)) FROM sessions WHERE uid = ?1",
+            params![uid, to_unix(now)],
+            |row| row.get(0),
+        )?;
+        Ok(seen.map(from_unix))
+    }
+
+    /// Total playtime of a player, an open session counts up to `now`.
+    pub fn playtime(&self, steamid: &str, now: SystemTime) -> Result<Duration> {
+        let secs: i64 = self.connection().query_row(
+            "SELECT COALESCE(SUM(COALESCE(left_at, ?2) - joined_at), 0)
+            FROM sessions WHERE steamid = ?1",
+            params![steamid, to_unix(now)],
+            |row| row.get(0),
+        )?;
+        Ok(Duration::from_secs(secs.max(0) as u64))
+    }
+
+    /// All sessions of a player, oldest first.
+    pub fn sessions(&self, steamid: &str) -> Result<Vec<Session>> {
+        let conn = self.connection();
+        let mut statement = conn.prepare(
+            "SELECT steamid, name, joined_at, left_at FROM sessions
+            WHERE steamid = ?1 ORDER BY joined_at",
+        )?;
+        let sessions = statement
+            .query_map(params![steamid], |row| {
+                Ok(Session {
+                    steamid: row.get(0)?,
+                    name: row.get(1)?,
+                    joined_at: from_unix(row.get(2)?),
+                    left_at: row.get::<_, Option<i64>>(3)?.map(from_unix),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<Session>>>()?;
+        Ok(sessions)
+    }
+
+    /// Whether a playtime milestone was marked as reached.
+    pub fn milestone_reached(&self, steamid: &str, playtime: Duration) -> Result<bool> {
+        let count: i64 = self.connection().query_row(
+            "SELECT COUNT(*) FROM milestones WHERE steamid = ?1 AND playtime = ?2",
+            params![steamid, playtime.as_secs() as i64],
+            |row| row.get(0),
+        )?;
+        Ok(count > 0)
+    }
+
+    /// Marks a playtime milestone as reached. Returns false if it already was.
+    pub fn record_milestone(
+        &self,
+        steamid: &str,
+        playtime: Duration,
+        at: SystemTime,
+    ) -> Result<bool> {
+        let inserted = self.connection().execute(
+            "INSERT OR IGNORE INTO milestones (steamid, playtime, reached_at) VALUES (?1, ?2, ?3)",
+            params![steamid, playtime.as_secs() as i64, to_unix(at)],
+        )?;
+        Ok(inserted > 0)
+    }
+
+    /// Records whether the server answered a reachability probe.
+    pub fn record_probe(&self, at: SystemTime, reachable: bool) -> Result<()> {
+        self.connection().execute(
+            "INSERT INTO probes (at, reachable) VALUES (?1, ?2)",
+            params![to_unix(at), reachable],
+        )?;
+        Ok(())
+    }
+
+    /// Probes within `range`, oldest first.
+    pub fn probes(&self, range: Range<SystemTime>) -> Result<Vec<Probe>> {
+        let conn = self.connection();
+        let mut statement = conn
+            .prepare("SELECT at, reachable FROM probes WHERE at >= ?1 AND at < ?2 ORDER BY at")?;
+        let probes = statement
+            .query_map(params![to_unix(range.start), to_unix(range.end)], |row| {
+                Ok(Probe {
+                    at: from_unix(row.get(0)?),
+                    reachable: row.get(1)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<Probe>>>()?;
+        Ok(probes)
+    }
+
+    /// Stores a sent broadcast.
+    pub fn record_broadcast(&self, broadcast: &BroadcastRecord) -> Result<()> {
+        self.connection().execute(
+            "INSERT INTO broadcasts (sent_at, message, initiator) VALUES (?1, ?2, ?3)",
+            params![
+                to_unix(broadcast.sent_at),
+                broadcast.message,
+                broadcast.initiator
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// The last `count` broadcasts, newest first.
+    pub fn recent_broadcasts(&self, count: usize) -> Result<Vec<BroadcastRecord>> {
+        let conn = self.connection();
+        let mut statement = conn.prepare(
+            "SELECT message, initiator, sent_at FROM broadcasts
+            ORDER BY sent_at DESC, id DESC LIMIT ?1",
+        )?;
+        let broadcasts = statement
+            .query_map(params![count as i64], |row| {
+                Ok(BroadcastRecord {
+                    message: row.get(0)?,
+                    initiator: row.get(1)?,
+                    sent_at: from_unix(row.get(2)?),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<BroadcastRecord>>>()?;
+        Ok(broadcasts)
+    }
+
+    /// Stores a strike of the moderation ledger.
+    pub fn record_strike(&self, strike: &Strike) -> Result<()> {
+        self.connection().execute(
+            "INSERT INTO strikes (steamid, reason, issued_at, issued_by, action)
+            VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                strike.steamid,
+                strike.reason,
+                to_unix(strike.issued_at),
+                strike.issued_by,
+                strike.action.to_string()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Number of strikes of a player.
+    pub fn strike_count(&self, steamid: &str) -> Result<usize> {
+        let count: i64 = self.connection().query_row(
+            "SELECT COUNT(*) FROM strikes WHERE steamid = ?1",
+            params![steamid],
+            |row| row.get(0),
+        )?;
+        Ok(count as usize)
+    }
+
+    /// Strikes of a player, oldest first.
+    pub fn strikes(&self, steamid: &str) -> Result<Vec<Strike>> {
+        let conn = self.connection();
+        let mut statement = conn.prepare(
+            "SELECT reason, issued_at, issued_by, action FROM strikes WHERE steamid = ?1
+            ORDER BY issued_at, id",
+        )?;
+        let rows = statement
+            .query_map(params![steamid], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        rows.into_iter()
+            .map(|(reason, issued_at, issued_by, action)| {
+                Ok(Strike {
+                    steamid: steamid.to_string(),
+                    reason,
+                    issued_at: from_unix(issued_at),
+                    issued_by,
+                    action: action.parse()?,
+                })
+            })
+            .collect()
+    }
+
+    /// Every strike, oldest first.
+    pub fn all_strikes(&self) -> Result<Vec<Strike>> {
+        let conn = self.connection();
+        let mut statement = conn.prepare(
+            "SELECT steamid, reason, issued_at, issued_by, action FROM strikes
+            ORDER BY issued_at, id",
+        )?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, String>(4)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        rows.into_iter()
+            .map(|(steamid, reason, issued_at, issued_by, action)| {
+                Ok(Strike {
+                    steamid,
+                    reason,
+                    issued_at: from_unix(issued_at),
+                    issued_by,
+                    action: action.parse()?,
+                })
+            })
+            .collect()
+    }
+
+    /// Every ban, lifted or not, oldest first.
+    pub fn bans(&self) -> Result<Vec<BanRecord>> {
+        let conn = self.connection();
+        let mut statement = conn.prepare(
+            "SELECT steamid, reason, banned_at, expires_at, lifted_at FROM bans
+            ORDER BY banned_at, id",
+        )?;
+        let bans = statement
+            .query_map([], |row| {
+                Ok(BanRecord {
+                    steamid: row.get(0)?,
+                    reason: row.get(1)?,
+                    banned_at: from_unix(row.get(2)?),
+                    expires_at: row.get::<_, Option<i64>>(3)?.map(from_unix),
+                    lifted_at: row.get::<_, Option<i64>>(4)?.map(from_unix),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(bans)
+    }
+
+    /// Adds a player to the whitelist, replacing their previous entry.
+    pub fn record_whitelisted(&self, entry: &WhitelistEntry) -> Result<()> {
+        self.connection().execute(
+            "INSERT OR REPLACE INTO whitelist (steamid, name, added_at) VALUES (?1, ?2, ?3)",
+            params![entry.steamid, entry.name, to_unix(entry.added_at)],
+        )?;
+        Ok(())
+    }
+
+    /// Removes a player from the whitelist.
+    pub fn remove_whitelisted(&self, steamid: &str) -> Result<()> {
+        self.connection()
+            .execute("DELETE FROM whitelist WHERE steamid = ?1", params![steamid])?;
+        Ok(())
+    }
+
+    /// The whitelist, oldest entries first.
+    pub fn whitelist(&self) -> Result<Vec<WhitelistEntry>> {
+        let conn = self.connection();
+        let mut statement =
+            conn.prepare("SELECT steamid, name, added_at FROM whitelist ORDER BY added_at, steamid")?;
+        let entries = statement
+            .query_map([], |row| {
+                Ok(WhitelistEntry {
+                    steamid: row.get(0)?,
+                    name: row.get(1)?,
+                    added_at: from_unix(row.get(2)?),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(entries)
+    }
+
+    /// Replaces every ban, strike and whitelist entry in one transaction.
+    pub fn replace_moderation(
+        &self,
+        bans: &[BanRecord],
+        strikes: &[Strike],
+        whitelist: &[WhitelistEntry],
+    ) -> Result<()> {
+        let mut conn = self.connection();
+        let transaction = conn.transaction()?;
+        transaction.execute_batch("DELETE FROM bans; DELETE FROM strikes; DELETE FROM whitelist;")?;
+        for ban in bans {
+            transaction.execute(
+                "INSERT INTO bans (steamid, reason, banned_at, expires_at, lifted_at)
+                VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    ban.steamid,
+                    ban.reason,
+                    to_unix(ban.banned_at),
+                    ban.expires_at.map(to_unix),
+                    ban.lifted_at.map(to_unix)
+                ],
+            )?;
+        }
+        for strike in strikes {
+            transaction.execute(
+                "INSERT INTO strikes (steamid, reason, issued_at, issued_by, action)
+                VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    strike.steamid,
+                    strike.reason,
+                    to_unix(strike.issued_at),
+                    strike.issued_by,
+                    strike.action.to_string()
+                ],
+            )?;
+        }
+        for entry in whitelist {
+            transaction.execute(
+                "INSERT OR REPLACE INTO whitelist (steamid, name, added_at) VALUES (?1, ?2, ?3)",
+                params![entry.steamid, entry.name, to_unix(entry.added_at)],
+            )?;
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Stores a temporary ban until it's lifted with [SessionStore::lift_ban], returns its id.
+    #[cfg(feature = "schedule")]
+    pub fn record_temp_ban(&self, ban: &TempBan) -> Result<i64> {
+        let conn = self.connection();
+        conn.execute(
+            "INSERT INTO bans (steamid, reason, banned_at, expires_at) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                ban.steamid,
+                ban.reason,
+                to_unix(ban.banned_at),
+                to_unix(ban.expires_at)
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Temporary bans not lifted yet, soonest to expire first.
+    #[cfg(feature = "schedule")]
+    pub fn pending_bans(&self) -> Result<Vec<TempBan>> {
+        let conn = self.connection();
+        let mut statement = conn.prepare(
+            "SELECT id, steamid, reason, banned_at, expires_at FROM bans WHERE lifted_at IS NULL
+            AND expires_at IS NOT NULL ORDER BY expires_at, id",
+        )?;
+        let bans = statement
+            .query_map([], |row| {
+                Ok(TempBan {
+                    id: Some(row.get(0)?),
+                    steamid: row.get(1)?,
+                    reason: row.get(2)?,
+                    banned_at: from_unix(row.get(3)?),
+                    expires_at: from_unix(row.get(4)?),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(bans)
+    }
+
+    /// Marks the ban with `id` lifted at `at`, the other bans of the player are kept.
+    #[cfg(feature = "schedule")]
+    pub fn lift_ban(&self, id: i64, at: SystemTime) -> Result<()> {
+        self.connection().execute(
+            "UPDATE bans SET lifted_at = ?2 WHERE id = ?1 AND lifted_at IS NULL",
+            params![id, to_unix(at)],
+        )?;
+        Ok(())
+    }
+
+    /// Whether `steamid` has a ban that isn't lifted and doesn't lapse by `now`, permanent
+    /// bans included.
+    #[cfg(feature = "schedule")]
+    pub fn is_banned(&self, steamid: &str, now: SystemTime) -> Result<bool> {
+        let banned = self.connection().query_row(
+            "SELECT EXISTS (SELECT 1 FROM bans WHERE steamid = ?1 AND lifted_at IS NULL
+            AND (expires_at IS NULL OR expires_at > ?2))",
+            params![steamid, to_unix(now)],
+            |row| row.get(0),
+        )?;
+        Ok(banned)
+    }
+
+    /// A hook recording the broadcasts of a client, set as
+    /// [rcon::PalworldRCON::broadcast_hook].
+    pub fn broadcast_hook(self: Arc<Self>) -> rcon::BroadcastHook {
+        rcon::BroadcastHook::new(move |broadcast| {
+            if let Err(e) = self.record_broadcast(broadcast) {
+                log::error!("Failed to record broadcast '{}': {e}", broadcast.message);
+            }
+        })
+    }
+
+    /// Availability, incidents and MTTR over `range`.
+    pub fn uptime_report(&self, range: Range<SystemTime>) -> Result<UptimeReport> {
+        let probes = self.probes(range.clone())?;
+        Ok(UptimeReport::from_probes(&probes, range.end))
+    }
+
+    /// Stores a metrics sample.
+    #[cfg(feature = "metrics")]
+    pub fn record_metrics(&self, sample: &MetricSample) -> Result<()> {
+        self.connection().execute(
+            "INSERT INTO metrics (at, players, memory_used, cpu_percent) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                to_unix(sample.at),
+                sample.players.round() as i64,
+                sample.memory_used.map(|m| m.as_bytes() as i64),
+                sample.cpu_percent
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Metrics between `start` and `end` averaged into buckets of `step`, oldest first.
+    /// Buckets without samples are left out.
+    #[cfg(feature = "metrics")]
+    pub fn metrics_between(
+        &self,
+        start: SystemTime,
+        end: SystemTime,
+        step: Duration,
+    ) -> Result<Vec<MetricSample>> {
+        if step.as_secs() == 0 {
+            anyhow::bail!("Metrics step must be at least a second");
+        }
+        let conn = self.connection();
+        let mut statement = conn.prepare(
+            "SELECT ?1 + ((at - ?1) / ?3) * ?3 AS bucket,
+                AVG(players), AVG(memory_used), AVG(cpu_percent)
+            FROM metrics WHERE at >= ?1 AND at < ?2
+            GROUP BY bucket ORDER BY bucket",
+        )?;
+        let samples = statement
+            .query_map(
+                params![to_unix(start), to_unix(end), step.as_secs() as i64],
+                |row| {
+                    Ok(MetricSample {
+                        at: from_unix(row.get(0)?),
+                        players: row.get(1)?,
+                        memory_used: row
+                            .get::<_, Option<f64>>(2)?
+                            .map(|m| ByteSize(m.round() as u64)),
+                        cpu_percent: row.get(3)?,
+                    })
+                },
+            )?
+            .collect::<rusqlite::Result<Vec<MetricSample>>>()?;
+        Ok(samples)
+    }
+
+    /// Playtime per player and month within `range`, ordered by month and SteamID.
+    /// Months are calendar months in `tz`, an open session counts up to the end of `range`.
+    /// Players in `exempt` are left out.
+    #[cfg(feature = "billing")]
+    pub fn monthly_playtime(
+        &self,
+        range: Range<SystemTime>,
+        tz: Tz,
+        exempt: &[String],
+    ) -> Result<Vec<MonthlyPlaytime>> {
+        let (start, end) = (to_unix(range.start), to_unix(range.end));
+        let conn = self.connection();
+        let mut statement = conn.prepare(
+            "SELECT steamid, name, MAX(joined_at, ?1), MIN(COALESCE(left_at, ?2), ?2)
+            FROM sessions WHERE joined_at < ?2 AND COALESCE(left_at, ?2) > ?1
+            ORDER BY joined_at",
+        )?;
+        let sessions = statement
+            .query_map(params![start, end], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, i64>(3)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut totals = std::collections::BTreeMap::<(String, String), MonthlyPlaytime>::new();
+        for (steamid, name, joined_at, left_at) in sessions {
+            if exempt.contains(&steamid) {
+                continue;
+            }
+            for (month, playtime) in
+                billing::split_by_month(from_unix(joined_at), from_unix(left_at), tz)
+            {
+                let total = totals
+                    .entry((month.clone(), steamid.clone()))
+                    .or_insert_with(|| MonthlyPlaytime {
+                        month,
+                        steamid: steamid.clone(),
+                        name: name.clone(),
+                        playtime: Duration::ZERO,
+                        sessions: 0,
+                    });
+                // Sessions are ordered by join time, the last one has the current name.
+                total.name.clone_from(&name);
+                total.playtime += playtime;
+                total.sessions += 1;
+            }
+        }
+        Ok(totals.into_values().collect())
+    }
+
+    /// Records player events from `bus` in a background task until the bus is dropped.
+    ///
+    /// Sessions left open by a previous run are closed first, when their players were last
+    /// seen, so time the daemon was down doesn't count as playtime. While it runs, open
+    /// sessions are marked as seen every [SEEN_INTERVAL].
+    pub fn spawn_recorder(self: Arc<Self>, bus: &EventBus) -> JoinHandle<()> {
+        let mut events = bus.subscribe();
+        tokio::spawn(async move {
+            match self.close_sessions_except(&[], SystemTime::now()) {
+                Ok(0) => (),
+                Ok(closed) => log::info!("Closed {closed} session(s) left open by the last run"),
+                Err(e) => log::error!("Failed to close sessions of the last run: {e}"),
+            }
+            let mut seen = tokio::time::interval(SEEN_INTERVAL);
+            loop {
+                tokio::select! {
+                    received = events.recv() => match received {
+                        Ok(event) => {
+                            if let Err(e) = self.record_event(&event, SystemTime::now()) {
+                                log::error!("Failed to record {event:?}: {e}");
+                            }
+                        }
+                        Err(RecvError::Lagged(skipped)) => {
+                            log::warn!("Session recorder fell behind, skipped {skipped} event(s)")
+                        }
+                        Err(RecvError::Closed) => break,
+                    },
+                    _ = seen.tick() => {
+                        if let Err(e) = self.record_seen(SystemTime::now()) {
+                            log::error!("Failed to record open sessions as seen: {e}");
+                        }
+                    }
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alice() -> PlayerInfo {
+        PlayerInfo {
+            name: "Alice".to_string(),
+            uid: "1".to_string(),
+            steamid: "76561190000000001".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_sessions() {
+        let store = SessionStore::open_in_memory().unwrap();
+        let start = from_unix(1_700_000_000);
+        let hour = Duration::from_secs(3600);
+
+        assert_eq!(store.completed_sessions(&alice().steamid).unwrap(), 0);
+        assert_eq!(store.first_seen(&alice().steamid).unwrap(), None);
+
+        store.record_join(&alice(), start).unwrap();
+        assert_eq!(store.completed_sessions(&alice().steamid).unwrap(), 0);
+        store.record_leave(&alice(), start + hour).unwrap();
+        store.record_join(&alice(), start + hour * 2).unwrap();
+
+        assert_eq!(store.completed_sessions(&alice().steamid).unwrap(), 1);
+        assert_eq!(store.first_seen(&alice().steamid).unwrap(), Some(start));
+        // One finished hour plus 30 minutes of the open session.
+        assert_eq!(
+            store
+                .playtime(&alice().steamid, start + hour * 2 + hour / 2)
+                .unwrap(),
+            hour + hour / 2
+        );
+        let sessions = store.sessions(&alice().steamid).unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[1].left_at, None);
+    }
+
+    fn bob() -> PlayerInfo {
+        PlayerInfo {
+            name: "Bob".to_string(),
+            uid: "2".to_string(),
+            steamid: "76561190000000002".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_restart() {
+        let path = std::env::temp_dir().join(format!("sessions-{}.db", std::process::id()));
+        let start = from_unix(1_700_000_000);
+        let minute = Duration::from_secs(60);
+        {
+            let store = SessionStore::open(&path).unwrap();
+            store.record_join(&alice(), start).unwrap();
+            store.record_join(&bob(), start + minute * 10).unwrap();
+            store.record_seen(start + minute * 30).unwrap();
+            // The daemon stops here with both sessions open.
+        }
+
+        let store = Arc::new(SessionStore::open(&path).unwrap());
+        let bus = EventBus::default();
+        let recorder = store.clone().spawn_recorder(&bus);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        // Closed when last seen, not when the daemon came back hours later.
+        let now = SystemTime::now();
+        assert_eq!(store.playtime(&alice().steamid, now).unwrap(), minute * 30);
+        assert_eq!(store.playtime(&bob().steamid, now).unwrap(), minute * 20);
+
+        // Alice is still online at the first poll, Bob isn't.
+        bus.publish(Event::PlayersOnline(vec![alice()]));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        recorder.abort();
+        let sessions = store.sessions(&alice().steamid).unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[1].left_at, None);
+        assert_eq!(store.sessions(&bob().steamid).unwrap().len(), 1);
+        drop(store);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_players_online() {
+        let store = SessionStore::open_in_memory().unwrap();
+        let start = from_unix(1_700_000_000);
+        let hour = Duration::from_secs(3600);
+        store.record_join(&alice(), start).unwrap();
+        store.record_join(&bob(), start).unwrap();
+        store.record_seen(start + hour).unwrap();
+        // Missing from the first poll of a restarted watcher.
+        store
+            .record_event(&Event::PlayersOnline(vec![alice()]), start + hour * 2)
+            .unwrap();
+        assert_eq!(
+            store.playtime(&bob().steamid, start + hour * 5).unwrap(),
+            hour
+        );
+        // Still online, the session goes on.
+        assert_eq!(store.sessions(&alice().steamid).unwrap().len(), 1);
+        assert_eq!(
+            store.playtime(&alice().steamid, start + hour * 3).unwrap(),
+            hour * 3
+        );
+    }
+
+    #[test]
+    fn test_broadcasts() {
+        let store = SessionStore::open_in_memory().unwrap();
+        let start = from_unix(1_700_000_000);
+        for (i, message) in ["Restart in 10 minutes", "Restart in 1 minute", "Back up!"]
+            .into_iter()
+            .enumerate()
+        {
+            let broadcast = BroadcastRecord {
+                message: message.to_string(),
+                initiator: "task 'restart'".to_string(),
+                sent_at: start + Duration::from_secs(60 * i as u64),
+            };
+            store.record_broadcast(&broadcast).unwrap();
+        }
+        let recent = store.recent_broadcasts(2).unwrap();
+        let messages: Vec<&str> = recent.iter().map(|b| b.message.as_str()).collect();
+        assert_eq!(messages, ["Back up!", "Restart in 1 minute"]);
+        assert_eq!(recent[0].sent_at, start + Duration::from_secs(120));
+        assert_eq!(store.recent_broadcasts(10).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_strikes() {
+        use crate::moderation::StrikeAction;
+
+        let store = SessionStore::open_in_memory().unwrap();
+        let start = from_unix(1_700_000_000);
+        let steamid = alice().steamid;
+        assert_eq!(store.strike_count(&steamid).unwrap(), 0);
+        for (i, action) in [StrikeAction::Warned, StrikeAction::Kicked]
+            .into_iter()
+            .enumerate()
+        {
+            let strike = Strike {
+                steamid: steamid.clone(),
+                reason: format!("Griefing #{i}"),
+                issued_at: start + Duration::from_secs(60 * i as u64),
+                issued_by: "palworldcli by root".to_string(),
+                action,
+            };
+            store.record_strike(&strike).unwrap();
+        }
+        assert_eq!(store.strike_count(&steamid).unwrap(), 2);
+        assert_eq!(store.strike_count("7656").unwrap(), 0);
+        let strikes = store.strikes(&steamid).unwrap();
+        assert_eq!(strikes[0].reason, "Griefing #0");
+        assert_eq!(strikes[1].action, StrikeAction::Kicked);
+        assert_eq!(strikes[1].issued_at, start + Duration::from_secs(60));
+    }
+
+    #[cfg(feature = "schedule")]
+    #[test]
+    fn test_temp_bans() {
+        use crate::tempban::TempBan;
+
+        let store = SessionStore::open_in_memory().unwrap();
+        let start = from_unix(1_700_000_000);
+        for (steamid, hours) in [("76561190000000002", 24), ("76561190000000001", 1)] {
+            let ban = TempBan {
+                id: None,
+                steamid: steamid.to_string(),
+                reason: "Griefing".to_string(),
+                banned_at: start,
+                expires_at: start + Duration::from_secs(3600 * hours),
+            };
+            store.record_temp_ban(&ban).unwrap();
+        }
+        let pending = store.pending_bans().unwrap();
+        assert_eq!(pending[0].steamid, "76561190000000001");
+        assert_eq!(pending[1].expires_at, start + Duration::from_secs(24 * 3600));
+        assert!(store.is_banned("76561190000000001", start).unwrap());
+        store.lift_ban(pending[0].id.unwrap(), start).unwrap();
+        assert!(!store.is_banned("76561190000000001", start).unwrap());
+        let pending = store.pending_bans().unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].steamid, "76561190000000002");
+        assert_eq!(store.bans().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_whitelist() {
+        use crate::moderation::WhitelistEntry;
+
+        let store = SessionStore::open_in_memory().unwrap();
+        let start = from_unix(1_700_000_000);
+        let mut entry = WhitelistEntry {
+            steamid: alice().steamid,
+            name: None,
+            added_at: start,
+        };
+        store.record_whitelisted(&entry).unwrap();
+        entry.name = Some("Alice".to_string());
+        store.record_whitelisted(&entry).unwrap();
+        assert_eq!(store.whitelist().unwrap(), [entry.clone()]);
+        store.replace_moderation(&[], &[], &[]).unwrap();
+        assert!(store.whitelist().unwrap().is_empty());
+        store.record_whitelisted(&entry).unwrap();
+        store.remove_whitelisted(&entry.steamid).unwrap();
+        assert!(store.whitelist().unwrap().is_empty());
+    }
+}
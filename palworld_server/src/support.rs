@@ -0,0 +1,290 @@
+//! Support bundles to attach to bug reports.
+//!
+//! Hosting providers and Pocketpair ask for the same things with every report: logs, the
+//! settings, the server version and whether the server is reachable at all. A [SupportBundle]
+//! gathers them into a single `.tar.gz`. [SupportBundle::collect_server] adds version info
+//! and a [Diagnostics] report, [SupportBundle::collect_host] adds the service journal, the
+//! server's logs and `PalWorldSettings.ini` over SSH. Credentials in the settings and logs
+//! are redacted, see [redact_assignments]. Whatever couldn't be gathered is listed in
+//! `errors.txt` instead of failing the bundle, a partial bundle still helps.
+//!
+//! # Example:
+//! ```no_run
+//! use std::fs::File;
+//!
+//! use palworld_server::diagnostics::Diagnostics;
+//! use palworld_server::rcon::{PalworldRCON, DEFAULT_SOURCE_PORT};
+//! use palworld_server::ssh::PalworldConnection;
+//! use palworld_server::support::{SupportBundle, DEFAULT_LOG_LINES};
+//! use palworld_server::world::WorldProfile;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let rcon = PalworldRCON::new("palworld.lan", DEFAULT_SOURCE_PORT, "MyRCONPassword");
+//!     let connection = PalworldConnection::new("palworld.lan:22", "root", "MySSHPassword");
+//!     let mut bundle = SupportBundle::new();
+//!     bundle.collect_server(&rcon, &Diagnostics::new()).await;
+//!     bundle
+//!         .collect_host(&connection, &WorldProfile::new("palworld"), DEFAULT_LOG_LINES)
+//!         .await;
+//!     bundle.write(File::create("support.tar.gz").unwrap()).unwrap();
+//! }
+//! ```
+
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Result};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::diagnostics::Diagnostics;
+use crate::rcon::PalworldRCON;
+use crate::secret::redact_assignments;
+use crate::ssh::PalworldConnection;
+use crate::world::WorldProfile;
+
+/// Default number of lines kept of each log.
+pub const DEFAULT_LOG_LINES: usize = 2000;
+
+const BLOCK_SIZE: usize = 512;
+
+/// A file in a [SupportBundle].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BundleEntry {
+    /// Path in the archive, at most 100 bytes.
+    pub path: String,
+    pub contents: Vec<u8>,
+}
+
+/// Files for a bug report, see the [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SupportBundle {
+    pub entries: Vec<BundleEntry>,
+    /// What couldn't be gathered, written to `errors.txt`.
+    pub errors: Vec<String>,
+    /// Modification time of the files in the archive.
+    pub created_at: SystemTime,
+}
+
+impl Default for SupportBundle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SupportBundle {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            errors: Vec::new(),
+            created_at: SystemTime::now(),
+        }
+    }
+
+    pub fn add(&mut self, path: impl Into<String>, contents: impl Into<Vec<u8>>) {
+        self.entries.push(BundleEntry {
+            path: path.into(),
+            contents: contents.into(),
+        });
+    }
+
+    /// Adds `value` as pretty printed JSON.
+    pub fn add_json(&mut self, path: impl Into<String>, value: &impl serde::Serialize) {
+        let path = path.into();
+        match serde_json::to_vec_pretty(value) {
+            Ok(json) => self.add(path, json),
+            Err(e) => self.errors.push(format!("{path}: {e}")),
+        }
+    }
+
+    /// The value of `result`, or None with the error recorded for `errors.txt`.
+    pub fn record<T>(&mut self, what: &str, result: Result<T>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(e) => {
+                log::warn!("Support bundle is missing {what}: {e:#}");
+                self.errors.push(format!("{what}: {e:#}"));
+                None
+            }
+        }
+    }
+
+    /// Adds `version.json`, with the server and library versions, and `diagnostics.json`.
+    pub async fn collect_server(&mut self, rcon: &PalworldRCON, diagnostics: &Diagnostics) {
+        let server = self.record("server info", rcon.get_server_info().await);
+        self.add_json(
+            "version.json",
+            &serde_json::json!({
+                "server": server,
+                "palworld_server": env!("CARGO_PKG_VERSION"),
+                "os": std::env::consts::OS,
+                "arch": std::env::consts::ARCH,
+            }),
+        );
+        self.add_json("diagnostics.json", &diagnostics.run(rcon).await);
+    }
+
+    /// Adds the last `log_lines` lines of the service journal and the server's logs under
+    /// `logs/`, and the redacted settings as `config/PalWorldSettings.ini`.
+    pub async fn collect_host(
+        &mut self,
+        connection: &PalworldConnection,
+        world: &WorldProfile,
+        log_lines: usize,
+    ) {
+        let journal = world.service_log(connection, log_lines).await;
+        if let Some(journal) = self.record("service journal", journal) {
+            self.add("logs/journal.log", redact_assignments(&journal));
+        }
+        let logs = world.game_logs(connection, log_lines).await;
+        for (name, log) in self.record("server logs", logs).unwrap_or_default() {
+            self.add(format!("logs/{name}"), redact_assignments(&log));
+        }
+        let settings = world.read_settings(connection).await;
+        if let Some(settings) = self.record("settings", settings) {
+            self.add(
+                "config/PalWorldSettings.ini",
+                redact_assignments(&settings.to_ini()),
+            );
+        }
+    }
+
+    /// Adds `metrics.csv` with the samples recorded since `since`, averaged over `step`.
+    #[cfg(feature = "metrics")]
+    pub fn collect_metrics(
+        &mut self,
+        store: &crate::store::SessionStore,
+        since: SystemTime,
+        step: std::time::Duration,
+    ) {
+        let samples = store.metrics_between(since, SystemTime::now(), step);
+        if let Some(samples) = self.record("metrics", samples) {
+            let mut csv = Vec::new();
+            match crate::metrics::write_csv(&samples, &mut csv) {
+                Ok(()) => self.add("metrics.csv", csv),
+                Err(e) => self.errors.push(format!("metrics: {e:#}")),
+            }
+        }
+    }
+
+    /// Writes the bundle as a gzipped tar archive.
+    pub fn write(&self, writer: impl Write) -> Result<()> {
+        let mtime = self
+            .created_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let errors = (!self.errors.is_empty()).then(|| BundleEntry {
+            path: "errors.txt".to_string(),
+            contents: format!("{}\n", self.errors.join("\n")).into_bytes(),
+        });
+        let mut archive = GzEncoder::new(writer, Compression::default());
+        for entry in self.entries.iter().chain(&errors) {
+            archive.write_all(&tar_header(&entry.path, entry.contents.len(), mtime)?)?;
+            archive.write_all(&entry.contents)?;
+            let padding = (BLOCK_SIZE - entry.contents.len() % BLOCK_SIZE) % BLOCK_SIZE;
+            archive.write_all(&[0; BLOCK_SIZE][..padding])?;
+        }
+        // The archive ends with two empty blocks.
+        archive.write_all(&[0; 2 * BLOCK_SIZE])?;
+        archive.finish()?;
+        Ok(())
+    }
+}
+
+/// POSIX ustar header of a regular file.
+fn tar_header(path: &str, size: usize, mtime: u64) -> Result<[u8; BLOCK_SIZE]> {
+    if path.is_empty() || path.len() > 100 {
+        bail!("Invalid path '{path}' in the support bundle");
+    }
+    let mut header = [0u8; BLOCK_SIZE];
+    let mut field = |offset: usize, value: &[u8]| {
+        header[offset..offset + value.len()].copy_from_slice(value);
+    };
+    field(0, path.as_bytes());
+    field(100, b"0000644\0");
+    field(108, b"0000000\0");
+    field(116, b"0000000\0");
+    field(124, format!("{size:011o}\0").as_bytes());
+    field(136, format!("{mtime:011o}\0").as_bytes());
+    // The checksum is computed with its own field as spaces.
+    field(148, b"        ");
+    field(156, b"0");
+    field(257, b"ustar\0");
+    field(263, b"00");
+    let checksum: u32 = header.iter().map(|&byte| byte as u32).sum();
+    header[148..156].copy_from_slice(format!("{checksum:06o}\0 ").as_bytes());
+    Ok(header)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
+
+    use super::*;
+
+    /// Reads back (path, contents) of every file in an archive written by [SupportBundle::write].
+    fn read_archive(archive: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let mut tar = Vec::new();
+        GzDecoder::new(archive).read_to_end(&mut tar).unwrap();
+        assert_eq!(tar.len() % BLOCK_SIZE, 0);
+        let mut files = Vec::new();
+        let mut blocks = tar.chunks(BLOCK_SIZE);
+        while let Some(header) = blocks.next() {
+            if header.iter().all(|&byte| byte == 0) {
+                break;
+            }
+            assert_eq!(&header[257..263], b"ustar\0");
+            let octal = |field: &[u8]| {
+                let digits = std::str::from_utf8(field).unwrap();
+                u64::from_str_radix(digits.trim_matches(['\0', ' ']), 8).unwrap()
+            };
+            let mut unsigned = header.to_vec();
+            unsigned[148..156].fill(b' ');
+            let sum: u64 = unsigned.iter().map(|&byte| byte as u64).sum();
+            assert_eq!(octal(&header[148..156]), sum);
+            let path = String::from_utf8_lossy(&header[..100]);
+            let size = octal(&header[124..136]) as usize;
+            let mut contents = Vec::new();
+            for _ in 0..size.div_ceil(BLOCK_SIZE) {
+                contents.extend_from_slice(blocks.next().unwrap());
+            }
+            contents.truncate(size);
+            files.push((path.trim_end_matches('\0').to_string(), contents));
+        }
+        files
+    }
+
+    #[test]
+    fn test_write() {
+        let mut bundle = SupportBundle::new();
+        bundle.add("logs/Pal.log", "x".repeat(BLOCK_SIZE + 1));
+        bundle.add("empty.txt", "");
+        bundle.add_json("version.json", &serde_json::json!({ "server": null }));
+        let failed: Result<()> = Err(anyhow::anyhow!("connection refused"));
+        assert!(bundle.record("settings", failed).is_none());
+
+        let mut archive = Vec::new();
+        bundle.write(&mut archive).unwrap();
+        let files = read_archive(&archive);
+        let paths: Vec<_> = files.iter().map(|(path, _)| path.as_str()).collect();
+        assert_eq!(
+            paths,
+            ["logs/Pal.log", "empty.txt", "version.json", "errors.txt"]
+        );
+        assert_eq!(files[0].1.len(), BLOCK_SIZE + 1);
+        assert!(files[1].1.is_empty());
+        assert_eq!(files[3].1, b"settings: connection refused\n");
+    }
+
+    #[test]
+    fn test_invalid_path() {
+        let mut bundle = SupportBundle::new();
+        bundle.add("a".repeat(101), "");
+        assert!(bundle.write(Vec::new()).is_err());
+        assert!(tar_header("", 0, 0).is_err());
+    }
+}
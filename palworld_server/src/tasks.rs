@@ -0,0 +1,348 @@
+//! Supervises long-running background tasks like watchers, samplers and bridges.
+//!
+//! Tasks are registered as factories so they can be restarted, failures and panics are
+//! published as [Event::TaskFailed], and on shutdown tasks are stopped in reverse
+//! dependency order.
+//!
+//! # Example:
+//! ```no_run
+//! use palworld_server::events::EventBus;
+//! use palworld_server::rcon::{PalworldRCON, DEFAULT_SOURCE_PORT};
+//! use palworld_server::tasks::{RestartPolicy, TaskManager};
+//! use palworld_server::watcher::PlayerWatcher;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let rcon = PalworldRCON::new("localhost", DEFAULT_SOURCE_PORT, "MyRCONPassword");
+//!     let bus = EventBus::default();
+//!     let mut tasks = TaskManager::new(bus.clone());
+//!     tasks
+//!         .spawn("watcher", RestartPolicy::default(), &[], move |mut shutdown| {
+//!             let watcher = PlayerWatcher::new(rcon.clone(), bus.clone());
+//!             async move {
+//!                 tokio::select! {
+//!                     result = watcher.run() => result,
+//!                     _ = shutdown.wait() => Ok(()),
+//!                 }
+//!             }
+//!         })
+//!         .unwrap();
+//!     tokio::signal::ctrl_c().await.unwrap();
+//!     tasks.shutdown().await;
+//! }
+//! ```
+
+use std::future::Future;
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use tokio::sync::watch;
+use tokio::task::{JoinError, JoinHandle};
+
+use crate::events::{Event, EventBus};
+use crate::trace::{self, TraceContext, Trigger};
+
+/// Default time a task gets to stop on its own before it is aborted.
+pub const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+/// What to do when a task finishes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub enum RestartPolicy {
+    /// Let the task finish for good.
+    Never,
+    /// Restart after an error or panic, up to `max_restarts` times if set.
+    OnFailure {
+        delay: Duration,
+        max_restarts: Option<u32>,
+    },
+    /// Restart whenever the task finishes.
+    Always { delay: Duration },
+}
+
+impl RestartPolicy {
+    fn restart_delay(&self, failed: bool, restarts: u32) -> Option<Duration> {
+        match *self {
+            Self::Never => None,
+            Self::OnFailure {
+                delay,
+                max_restarts,
+            } => {
+                let exhausted = matches!(max_restarts, Some(max) if restarts >= max);
+                match failed && !exhausted {
+                    true => Some(delay),
+                    false => None,
+                }
+            }
+            Self::Always { delay } => Some(delay),
+        }
+    }
+}
+
+impl Default for RestartPolicy {
+    /// Restart failed tasks after 5 seconds, forever.
+    fn default() -> Self {
+        Self::OnFailure {
+            delay: Duration::from_secs(5),
+            max_restarts: None,
+        }
+    }
+}
+
+/// Handed to every task, resolves when the task should stop.
+#[derive(Debug, Clone)]
+pub struct ShutdownSignal(watch::Receiver<bool>);
+
+impl ShutdownSignal {
+    /// Waits until shutdown is requested.
+    pub async fn wait(&mut self) {
+        // An error means the manager was dropped, which also means stop.
+        let _ = self.0.wait_for(|stop| *stop).await;
+    }
+
+    pub fn is_shutdown(&self) -> bool {
+        *self.0.borrow()
+    }
+}
+
+#[derive(Debug)]
+struct ManagedTask {
+    name: String,
+    stop: watch::Sender<bool>,
+    supervisor: JoinHandle<()>,
+}
+
+/// Owns the background tasks of an application.
+#[derive(Debug)]
+pub struct TaskManager {
+    bus: EventBus,
+    tasks: Vec<ManagedTask>,
+    /// Time a task gets to stop after its [ShutdownSignal] fires before it is aborted.
+    pub grace_period: Duration,
+}
+
+impl TaskManager {
+    pub fn new(bus: EventBus) -> Self {
+        Self {
+            bus,
+            tasks: Vec::new(),
+            grace_period: DEFAULT_GRACE_PERIOD,
+        }
+    }
+
+    /// Starts a task. `factory` is called again for every restart. Tasks in `depends_on`
+    /// must already be registered, they are stopped after this task on shutdown.
+    pub fn spawn<F, Fut>(
+        &mut self,
+        name: &str,
+        policy: RestartPolicy,
+        depends_on: &[&str],
+        factory: F,
+    ) -> Result<()>
+    where
+        F: Fn(ShutdownSignal) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        if self.tasks.iter().any(|task| task.name == name) {
+            bail!("Task '{name}' is already registered");
+        }
+        if let Some(missing) = depends_on
+            .iter()
+            .find(|dependency| !self.tasks.iter().any(|task| task.name == **dependency))
+        {
+            bail!("Task '{name}' depends on '{missing}' which isn't registered");
+        }
+        let (stop, signal) = watch::channel(false);
+        let supervisor = tokio::spawn(supervise(
+            name.to_string(),
+            policy,
+            factory,
+            ShutdownSignal(signal),
+            self.bus.clone(),
+            self.grace_period,
+        ));
+        self.tasks.push(ManagedTask {
+            name: name.to_string(),
+            stop,
+            supervisor,
+        });
+        Ok(())
+    }
+
+    /// True while the task is running or waiting to restart.
+    pub fn is_running(&self, name: &str) -> bool {
+        self.tasks
+            .iter()
+            .any(|task| task.name == name && !task.supervisor.is_finished())
+    }
+
+    /// Stops the tasks one by one, dependents before their dependencies.
+    pub async fn shutdown(mut self) {
+        while let Some(task) = self.tasks.pop() {
+            log::debug!("Stopping task '{}'", task.name);
+            let _ = task.stop.send(true);
+            if let Err(e) = task.supervisor.await {
+                log::error!("Supervisor of task '{}' failed: {e}", task.name);
+            }
+        }
+    }
+}
+
+async fn supervise<F, Fut>(
+    name: String,
+    policy: RestartPolicy,
+    factory: F,
+    mut signal: ShutdownSignal,
+    bus: EventBus,
+    grace_period: Duration,
+) where
+    F: Fn(ShutdownSignal) -> Fut,
+    Fut: Future<Output = Result<()>> + Send + 'static,
+{
+    let mut restarts = 0;
+    loop {
+        let context = TraceContext::new(Trigger::Schedule(name.clone()));
+        let mut handle = tokio::spawn(trace::traced(context, factory(signal.clone())));
+        let outcome = tokio::select! {
+            outcome = &mut handle => outcome,
+            _ = signal.wait() => match tokio::time::timeout(grace_period, &mut handle).await {
+                Ok(outcome) => outcome,
+                Err(_) => {
+                    log::warn!("Task '{name}' didn't stop within {grace_period:?}, aborting");
+                    handle.abort();
+                    return;
+                }
+            },
+        };
+        if signal.is_shutdown() {
+            return;
+        }
+        let failure = match outcome {
+            Ok(Ok(())) => None,
+            Ok(Err(e)) => Some(e.to_string()),
+            Err(e) => Some(join_error_reason(e)),
+        };
+        if let Some(reason) = &failure {
+            log::error!("Task '{name}' failed: {reason}");
+            bus.publish_wait(Event::TaskFailed {
+                task: name.clone(),
+                reason: reason.clone(),
+            })
+            .await;
+        }
+        let Some(delay) = policy.restart_delay(failure.is_some(), restarts) else {
+            return;
+        };
+        restarts += 1;
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => log::info!("Restarting task '{name}'"),
+            _ = signal.wait() => return,
+        }
+    }
+}
+
+/// The panic message of a panicked task.
+fn join_error_reason(error: JoinError) -> String {
+    if !error.is_panic() {
+        return error.to_string();
+    }
+    let panic = error.into_panic();
+    match panic.downcast_ref::<&str>() {
+        Some(message) => format!("panicked: {message}"),
+        None => match panic.downcast_ref::<String>() {
+            Some(message) => format!("panicked: {message}"),
+            None => "panicked".to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    #[tokio::test]
+    async fn test_restart_after_panic() {
+        let bus = EventBus::default();
+        let mut events = bus.subscribe();
+        let mut tasks = TaskManager::new(bus);
+        let runs = Arc::new(AtomicU32::new(0));
+        let counter = runs.clone();
+        let policy = RestartPolicy::OnFailure {
+            delay: Duration::ZERO,
+            max_restarts: Some(1),
+        };
+        tasks
+            .spawn("flaky", policy, &[], move |_| {
+                let counter = counter.clone();
+                async move {
+                    if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                        panic!("first run");
+                    }
+                    anyhow::bail!("second run")
+                }
+            })
+            .unwrap();
+
+        assert_eq!(
+            events.recv().await.unwrap(),
+            Event::TaskFailed {
+                task: "flaky".to_string(),
+                reason: "panicked: first run".to_string()
+            }
+        );
+        assert_eq!(
+            events.recv().await.unwrap(),
+            Event::TaskFailed {
+                task: "flaky".to_string(),
+                reason: "second run".to_string()
+            }
+        );
+        while tasks.is_running("flaky") {
+            tokio::task::yield_now().await;
+        }
+        // Only one restart allowed.
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_order() {
+        let mut tasks = TaskManager::new(EventBus::default());
+        tasks.grace_period = Duration::from_millis(50);
+        let stopped = Arc::new(Mutex::new(Vec::new()));
+        for (name, depends_on) in [("bus", vec![]), ("watcher", vec!["bus"])] {
+            let stopped = stopped.clone();
+            tasks
+                .spawn(
+                    name,
+                    RestartPolicy::Never,
+                    &depends_on,
+                    move |mut shutdown| {
+                        let stopped = stopped.clone();
+                        async move {
+                            shutdown.wait().await;
+                            stopped.lock().unwrap().push(name);
+                            Ok(())
+                        }
+                    },
+                )
+                .unwrap();
+        }
+        // Never returns and ignores the shutdown signal.
+        tasks
+            .spawn("stuck", RestartPolicy::Never, &[], |_| {
+                std::future::pending::<Result<()>>()
+            })
+            .unwrap();
+        assert!(tasks
+            .spawn("orphan", RestartPolicy::Never, &["missing"], |_| async {
+                Ok(())
+            })
+            .is_err());
+
+        tasks.shutdown().await;
+        assert_eq!(*stopped.lock().unwrap(), vec!["watcher", "bus"]);
+    }
+}
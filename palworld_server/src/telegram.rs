@@ -0,0 +1,422 @@
+//! Telegram integration: a [Notifier] that posts events to a chat and a bot
+//! [TelegramBridge] that lets authorized users run `/players`, `/save` and `/broadcast`.
+//!
+//! # Example:
+//! ```no_run
+//! use palworld_server::rcon::{PalworldRCON, DEFAULT_SOURCE_PORT};
+//! use palworld_server::telegram::TelegramBridge;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let rcon = PalworldRCON::new("localhost", DEFAULT_SOURCE_PORT, "MyRCONPassword");
+//!     // Only Telegram user 123456789 may run commands.
+//!     let bridge = TelegramBridge::new("123:BOT_TOKEN", rcon, vec![123456789]);
+//!     bridge.run().await.unwrap();
+//! }
+//! ```
+
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::task::JoinHandle;
+
+use crate::events::Event;
+use crate::notify::Notifier;
+use crate::rcon::PalworldRCON;
+use crate::secret::Secret;
+
+/// The official Bot API server.
+pub static TELEGRAM_API: &str = "https://api.telegram.org";
+
+/// Sends a message to a chat with the Bot API at `api_url`.
+async fn send_message(
+    client: &reqwest::Client,
+    api_url: &str,
+    token: &str,
+    chat_id: i64,
+    text: &str,
+) -> Result<()> {
+    client
+        .post(format!("{api_url}/bot{token}/sendMessage"))
+        .json(&json!({ "chat_id": chat_id, "text": text }))
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        // Errors would show the URL, which has the token in it.
+        .map_err(reqwest::Error::without_url)?;
+    Ok(())
+}
+
+/// Posts events as messages to a Telegram chat.
+#[derive(Debug)]
+pub struct TelegramNotifier {
+    /// Bot token, redacted in `Debug` output.
+    pub token: Secret<String>,
+    pub chat_id: i64,
+    /// Bot API server, [TELEGRAM_API] by default.
+    pub api_url: String,
+    client: reqwest::Client,
+}
+
+impl TelegramNotifier {
+    pub fn new(token: impl Into<String>, chat_id: i64) -> Self {
+        Self {
+            token: Secret::new(token.into()),
+            chat_id,
+            api_url: TELEGRAM_API.to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for TelegramNotifier {
+    async fn notify(&self, event: &Event) -> Result<()> {
+        send_message(
+            &self.client,
+            &self.api_url,
+            self.token.expose(),
+            self.chat_id,
+            &event.to_string(),
+        )
+        .await
+    }
+}
+
+/// Commands understood by the [TelegramBridge].
+#[derive(Debug, Clone, PartialEq)]
+pub enum BotCommand {
+    Players,
+    Save,
+    Broadcast(String),
+    Help,
+}
+
+/// Parses a chat message into a [BotCommand], returns None for anything else.
+/// Handles the `/command@BotName` form Telegram uses in groups, commands addressed to a
+/// bot other than `bot_username` are ignored.
+pub fn parse_command(text: &str, bot_username: &str) -> Option<BotCommand> {
+    let text = text.trim();
+    let (command, args) = match text.split_once(char::is_whitespace) {
+        Some((command, args)) => (command, args.trim()),
+        None => (text, ""),
+    };
+    let command = match command.split_once('@') {
+        // Telegram usernames are case-insensitive.
+        Some((command, bot)) if bot.eq_ignore_ascii_case(bot_username) => command,
+        Some(_) => return None,
+        None => command,
+    };
+    match command {
+        "/players" => Some(BotCommand::Players),
+        "/save" => Some(BotCommand::Save),
+        "/broadcast" if !args.is_empty() => Some(BotCommand::Broadcast(args.to_string())),
+        "/broadcast" | "/help" | "/start" => Some(BotCommand::Help),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Updates {
+    result: Vec<Update>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Me {
+    result: Bot,
+}
+
+#[derive(Debug, Deserialize)]
+struct Bot {
+    username: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Update {
+    update_id: i64,
+    message: Option<Message>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Message {
+    from: Option<User>,
+    chat: Chat,
+    text: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct User {
+    id: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct Chat {
+    id: i64,
+}
+
+/// Long-polls a Telegram bot and maps commands from authorized users to RCON.
+#[derive(Debug)]
+pub struct TelegramBridge {
+    /// Bot token, redacted in `Debug` output.
+    pub token: Secret<String>,
+    rcon: PalworldRCON,
+    /// Telegram user IDs allowed to run commands, everyone else is ignored.
+    pub authorized_users: Vec<i64>,
+    /// Space replacement passed to [PalworldRCON::broadcast].
+    pub replace_space: Option<String>,
+    /// How long each getUpdates request waits for new messages.
+    pub poll_timeout: Duration,
+    /// Bot API server, [TELEGRAM_API] by default.
+    pub api_url: String,
+    client: reqwest::Client,
+}
+
+impl TelegramBridge {
+    pub fn new(token: impl Into<String>, rcon: PalworldRCON, authorized_users: Vec<i64>) -> Self {
+        Self {
+            token: Secret::new(token.into()),
+            rcon,
+            authorized_users,
+            replace_space: None,
+            poll_timeout: Duration::from_secs(30),
+            api_url: TELEGRAM_API.to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Polls for commands forever. Messages sent while the bridge wasn't running are skipped,
+    /// so old commands don't run again after a restart. Errors if the bot's username or the
+    /// last pending update can't be read at the start.
+    pub async fn run(self) -> Result<()> {
+        let username = self.get_me().await?;
+        // Offset -1 returns only the last pending update and confirms the earlier ones.
+        let mut offset = match self.get_updates(-1, Duration::ZERO).await?.last() {
+            Some(update) => {
+                log::info!("Skipping Telegram messages sent before the bridge started");
+                update.update_id + 1
+            }
+            None => 0,
+        };
+        loop {
+            let updates = match self.get_updates(offset, self.poll_timeout).await {
+                Ok(updates) => updates,
+                Err(e) => {
+                    log::warn!("Telegram getUpdates failed: {e}");
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    continue;
+                }
+            };
+            for update in updates {
+                offset = update.update_id + 1;
+                let Some(message) = update.message else {
+                    continue;
+                };
+                let (Some(from), Some(text)) = (message.from, message.text) else {
+                    continue;
+                };
+                if !self.authorized_users.contains(&from.id) {
+                    log::warn!(
+                        "Ignoring Telegram message from unauthorized user {}",
+                        from.id
+                    );
+                    continue;
+                }
+                let Some(command) = parse_command(&text, &username) else {
+                    continue;
+                };
+                let reply = match self.execute(command).await {
+                    Ok(reply) => reply,
+                    Err(e) => format!("Error: {e}"),
+                };
+                if let Err(e) = send_message(
+                    &self.client,
+                    &self.api_url,
+                    self.token.expose(),
+                    message.chat.id,
+                    &reply,
+                )
+                .await
+                {
+                    log::warn!("Telegram sendMessage failed: {e}");
+                }
+            }
+        }
+    }
+
+    /// Runs the bridge in a background task.
+    pub fn spawn(self) -> JoinHandle<Result<()>> {
+        tokio::spawn(self.run())
+    }
+
+    /// Username of the bot, which group commands are addressed to.
+    async fn get_me(&self) -> Result<String> {
+        let me: Me = self
+            .client
+            .get(format!("{}/bot{}/getMe", self.api_url, self.token.expose()))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(reqwest::Error::without_url)?
+            .json()
+            .await
+            .map_err(reqwest::Error::without_url)?;
+        Ok(me.result.username)
+    }
+
+    /// Updates from `offset` on, waiting up to `timeout` for one.
+    async fn get_updates(&self, offset: i64, timeout: Duration) -> Result<Vec<Update>> {
+        let updates: Updates = self
+            .client
+            .get(format!(
+                "{}/bot{}/getUpdates",
+                self.api_url,
+                self.token.expose()
+            ))
+            .query(&[
+                ("offset", offset.to_string()),
+                ("timeout", timeout.as_secs().to_string()),
+            ])
+            .timeout(timeout + Duration::from_secs(10))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(reqwest::Error::without_url)?
+            .json()
+            .await
+            .map_err(reqwest::Error::without_url)?;
+        Ok(updates.result)
+    }
+
+    /// Runs a command and returns the reply text.
+    async fn execute(&self, command: BotCommand) -> Result<String> {
+        let reply = match command {
+            BotCommand::Players => {
+                let players = self.rcon.get_player_info().await?;
+                let mut reply = format!("{} player(s) online", players.len());
+                for player in &players {
+                    reply.push_str(&format!("\n{} ({})", player.name, player.steamid));
+                }
+                reply
+            }
+            BotCommand::Save => match self.rcon.save().await? {
+                true => "World saved".to_string(),
+                false => "Save failed".to_string(),
+            },
+            BotCommand::Broadcast(message) => {
+                self.rcon
+                    .broadcast(message, self.replace_space.clone())
+                    .await?
+            }
+            BotCommand::Help => "Commands: /players, /save, /broadcast <message>".to_string(),
+        };
+        Ok(reply)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use serde_json::Value;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+    use crate::mock::MockRcon;
+
+    /// A message from user 1 in chat 1.
+    fn update(update_id: i64, text: &str) -> Value {
+        json!({
+            "update_id": update_id,
+            "message": { "from": { "id": 1 }, "chat": { "id": 1 }, "text": text },
+        })
+    }
+
+    /// Bot API answering getUpdates from `updates` like Telegram does, returns its URL.
+    async fn bot_api(updates: Arc<Mutex<Vec<Value>>>) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = [0; 4096];
+                let read = stream.read(&mut request).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&request[..read]).to_string();
+                let path = request.split(' ').nth(1).unwrap_or_default();
+                let result = match path.split_once("offset=") {
+                    Some((_, query)) => {
+                        let offset: i64 = query.split('&').next().unwrap().parse().unwrap();
+                        let updates = updates.lock().unwrap().clone();
+                        let pending: Vec<Value> = match offset {
+                            -1 => updates.last().cloned().into_iter().collect(),
+                            offset => updates
+                                .into_iter()
+                                .filter(|update| update["update_id"].as_i64() >= Some(offset))
+                                .collect(),
+                        };
+                        if pending.is_empty() {
+                            // Long poll, shortened.
+                            tokio::time::sleep(Duration::from_millis(20)).await;
+                        }
+                        json!(pending)
+                    }
+                    None if path.ends_with("/getMe") => json!({ "username": "PalBot" }),
+                    None => json!({}),
+                };
+                let body = json!({ "ok": true, "result": result }).to_string();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+                    Connection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn test_skips_stale_commands() {
+        let server = MockRcon::start("password", |_| Some(String::new())).await;
+        let mut rcon = PalworldRCON::new("127.0.0.1", server.port, "password");
+        rcon.protocol.detect = false;
+        // Sent while the bridge was down.
+        let updates = Arc::new(Mutex::new(vec![
+            update(4, "/save"),
+            update(5, "/broadcast Restarting"),
+        ]));
+        let mut bridge = TelegramBridge::new("123:TOKEN", rcon, vec![1]);
+        bridge.api_url = bot_api(updates.clone()).await;
+        bridge.poll_timeout = Duration::ZERO;
+        let handle = bridge.spawn();
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        updates.lock().unwrap().push(update(6, "/players"));
+        for _ in 0..50 {
+            if !server.commands().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        handle.abort();
+        assert_eq!(server.commands(), ["showplayers"]);
+    }
+
+    #[test]
+    fn test_parse_command() {
+        let parse = |text| parse_command(text, "PalBot");
+        assert_eq!(parse("/players"), Some(BotCommand::Players));
+        assert_eq!(parse("/save@PalBot"), Some(BotCommand::Save));
+        assert_eq!(parse("/save@palbot"), Some(BotCommand::Save));
+        assert_eq!(
+            parse("/broadcast  Restart in 5 minutes "),
+            Some(BotCommand::Broadcast("Restart in 5 minutes".to_string()))
+        );
+        assert_eq!(parse("/broadcast"), Some(BotCommand::Help));
+        assert_eq!(parse("hello"), None);
+        // Addressed to another bot in the same group.
+        assert_eq!(parse("/save@OtherBot"), None);
+        assert_eq!(parse("/broadcast@OtherBot hi"), None);
+    }
+}
@@ -0,0 +1,280 @@
+//! Temporary bans, lifted by a scheduled job once they lapse.
+//!
+//! [TempBans::ban_player_for] bans a player over RCON and records when the ban expires in
+//! the [SessionStore]. The job added with [TempBans::schedule] removes lapsed bans from the
+//! world's ban list over SSH, see [WorldProfile::unban]. Both publish events,
+//! [Event::PlayerBanned] and [Event::BanExpired], for notifiers to announce.
+//!
+//! # Example:
+//! ```no_run
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//!
+//! use palworld_server::events::EventBus;
+//! use palworld_server::rcon::{PalworldRCON, DEFAULT_SOURCE_PORT};
+//! use palworld_server::scheduler::{Schedule, Scheduler, Tz};
+//! use palworld_server::ssh::PalworldConnection;
+//! use palworld_server::store::SessionStore;
+//! use palworld_server::tempban::TempBans;
+//! use palworld_server::world::WorldProfile;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let rcon = PalworldRCON::new("localhost", DEFAULT_SOURCE_PORT, "MyRCONPassword");
+//!     let ssh = PalworldConnection::new("localhost:22", "steam", "MySSHPassword");
+//!     let store = Arc::new(SessionStore::open("sessions.db").unwrap());
+//!     let bans = Arc::new(TempBans::new(rcon, ssh, WorldProfile::new("main"), store, EventBus::default()));
+//!
+//!     let day = Duration::from_secs(24 * 60 * 60);
+//!     bans.ban_player_for("76561190000000001", day, "Griefing").await.unwrap();
+//!
+//!     let mut scheduler = Scheduler::new();
+//!     bans.schedule(&mut scheduler, Schedule::parse("*/5 * * * *", Tz::UTC).unwrap());
+//!     scheduler.run().await;
+//! }
+//! ```
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+
+use crate::events::{Event, EventBus};
+use crate::rcon::PalworldRCON;
+use crate::scheduler::{CatchUp, Job, Schedule, Scheduler};
+use crate::ssh::PalworldConnection;
+use crate::store::SessionStore;
+use crate::world::WorldProfile;
+
+/// A ban that expires.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct TempBan {
+    /// Id in the [SessionStore], None until the ban is recorded.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub id: Option<i64>,
+    pub steamid: String,
+    pub reason: String,
+    pub banned_at: SystemTime,
+    pub expires_at: SystemTime,
+}
+
+impl TempBan {
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        now >= self.expires_at
+    }
+}
+
+/// Bans players for a while, see the [module documentation](self).
+#[derive(Debug)]
+pub struct TempBans {
+    rcon: PalworldRCON,
+    ssh: PalworldConnection,
+    /// World whose ban list lapsed bans are removed from.
+    pub world: WorldProfile,
+    store: Arc<SessionStore>,
+    bus: EventBus,
+}
+
+impl TempBans {
+    pub fn new(
+        rcon: PalworldRCON,
+        ssh: PalworldConnection,
+        world: WorldProfile,
+        store: Arc<SessionStore>,
+        bus: EventBus,
+    ) -> Self {
+        Self {
+            rcon,
+            ssh,
+            world,
+            store,
+            bus,
+        }
+    }
+
+    /// Bans `steamid` for `duration`. Returns true if the server banned the player, the
+    /// expiry is only recorded then.
+    pub async fn ban_player_for(
+        &self,
+        steamid: &str,
+        duration: Duration,
+        reason: &str,
+    ) -> Result<bool> {
+        if !self.rcon.ban_player(steamid).await? {
+            return Ok(false);
+        }
+        let banned_at = SystemTime::now();
+        let mut ban = TempBan {
+            id: None,
+            steamid: steamid.to_string(),
+            reason: reason.to_string(),
+            banned_at,
+            expires_at: banned_at + duration,
+        };
+        ban.id = Some(self.store.record_temp_ban(&ban)?);
+        log::info!(
+            "Banned {steamid} until {}: {reason}",
+            humantime::format_rfc3339_seconds(ban.expires_at)
+        );
+        self.bus.publish(Event::PlayerBanned {
+            steamid: ban.steamid,
+            reason: ban.reason,
+            until: ban.expires_at,
+        });
+        Ok(true)
+    }
+
+    /// Bans that haven't been lifted yet, soonest to expire first.
+    pub fn pending(&self) -> Result<Vec<TempBan>> {
+        self.store.pending_bans()
+    }
+
+    /// Unbans every player whose ban lapsed by `now`, returns their bans. A lapsed ban of a
+    /// player with another ban still running is closed without unbanning them. A failed
+    /// unban is logged and retried on the next call.
+    pub async fn lift_expired(&self, now: SystemTime) -> Result<Vec<TempBan>> {
+        let mut lifted: Vec<TempBan> = Vec::new();
+        for ban in self.pending()? {
+            let Some(id) = ban.id.filter(|_| ban.is_expired(now)) else {
+                continue;
+            };
+            // Unbanned for an earlier ban of theirs.
+            if lifted.iter().any(|lifted| lifted.steamid == ban.steamid) {
+                self.store.lift_ban(id, now)?;
+                continue;
+            }
+            if self.store.is_banned(&ban.steamid, now)? {
+                log::info!("Ban of {} expired, another ban is in place", ban.steamid);
+                self.store.lift_ban(id, now)?;
+                continue;
+            }
+            if let Err(e) = self.world.unban(&self.ssh, &ban.steamid).await {
+                log::error!("Failed to lift the ban of {}: {e}", ban.steamid);
+                continue;
+            }
+            self.store.lift_ban(id, now)?;
+            log::info!("Ban of {} expired", ban.steamid);
+            self.bus.publish(Event::BanExpired {
+                steamid: ban.steamid.clone(),
+                reason: ban.reason.clone(),
+            });
+            lifted.push(ban);
+        }
+        Ok(lifted)
+    }
+
+    /// Adds the job lifting lapsed bans to `scheduler`, run on `schedule`. Bans that lapsed
+    /// while the scheduler wasn't running are lifted on its next start.
+    pub fn schedule<'a>(
+        self: &Arc<Self>,
+        scheduler: &'a mut Scheduler,
+        schedule: Schedule,
+    ) -> &'a mut Job {
+        let bans = self.clone();
+        let job = scheduler.add("unban", schedule, move || {
+            let bans = bans.clone();
+            async move {
+                bans.lift_expired(SystemTime::now()).await?;
+                Ok(())
+            }
+        });
+        job.catch_up = CatchUp::RunOnce;
+        job
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::from_unix;
+
+    #[tokio::test]
+    async fn test_lift_expired() {
+        let dry_run = crate::dryrun::DryRun::new();
+        let mut ssh = PalworldConnection::new("palworld.lan:22", "root", "password");
+        ssh.dry_run = Some(dry_run.clone());
+        let rcon = PalworldRCON::new("invalid host name", 25575, "password");
+        let store = Arc::new(SessionStore::open_in_memory().unwrap());
+        let bus = EventBus::default();
+        let mut events = bus.subscribe();
+        let bans = TempBans::new(rcon, ssh, WorldProfile::new("main"), store.clone(), bus);
+
+        let start = from_unix(1_700_000_000);
+        let hour = Duration::from_secs(3600);
+        for (steamid, hours) in [("76561190000000001", 1), ("76561190000000002", 24)] {
+            let ban = TempBan {
+                id: None,
+                steamid: steamid.to_string(),
+                reason: "Griefing".to_string(),
+                banned_at: start,
+                expires_at: start + hour * hours,
+            };
+            store.record_temp_ban(&ban).unwrap();
+        }
+        assert!(bans.lift_expired(start).await.unwrap().is_empty());
+        let lifted = bans.lift_expired(start + hour * 2).await.unwrap();
+        assert_eq!(lifted.len(), 1);
+        assert_eq!(lifted[0].steamid, "76561190000000001");
+        assert_eq!(dry_run.actions().len(), 1);
+        assert_eq!(
+            events.recv().await.unwrap(),
+            Event::BanExpired {
+                steamid: "76561190000000001".to_string(),
+                reason: "Griefing".to_string()
+            }
+        );
+        // Lifted bans aren't lifted twice.
+        assert!(bans
+            .lift_expired(start + hour * 3)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(bans.pending().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_lift_expired_keeps_running_bans() {
+        let dry_run = crate::dryrun::DryRun::new();
+        let mut ssh = PalworldConnection::new("palworld.lan:22", "root", "password");
+        ssh.dry_run = Some(dry_run.clone());
+        let rcon = PalworldRCON::new("invalid host name", 25575, "password");
+        let store = Arc::new(SessionStore::open_in_memory().unwrap());
+        let bans = TempBans::new(
+            rcon,
+            ssh,
+            WorldProfile::new("main"),
+            store.clone(),
+            EventBus::default(),
+        );
+
+        let start = from_unix(1_700_000_000);
+        let hour = Duration::from_secs(3600);
+        // Re-banned for 30 days while banned for an hour, a SteamID the unban rejects and a
+        // player after it.
+        for (steamid, hours) in [
+            ("76561190000000001", 1),
+            ("76561190000000001", 30 * 24),
+            ("bad id", 1),
+            ("76561190000000002", 1),
+        ] {
+            let ban = TempBan {
+                id: None,
+                steamid: steamid.to_string(),
+                reason: "Griefing".to_string(),
+                banned_at: start,
+                expires_at: start + hour * hours,
+            };
+            store.record_temp_ban(&ban).unwrap();
+        }
+        let lifted = bans.lift_expired(start + hour * 2).await.unwrap();
+        assert_eq!(lifted.len(), 1);
+        assert_eq!(lifted[0].steamid, "76561190000000002");
+        assert_eq!(dry_run.actions().len(), 1);
+        let pending = bans.pending().unwrap();
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0].steamid, "bad id");
+        assert_eq!(pending[1].expires_at, start + hour * 30 * 24);
+    }
+}
@@ -0,0 +1,140 @@
+//! Correlation ids that follow an action from its trigger to the commands and events it causes.
+//!
+//! Work started by a trigger, a background task, an HTTP request or a chat command, runs
+//! inside [traced]. RCON and SSH commands sent from inside log the id as a `[id]` prefix and
+//! events published from inside carry the [TraceContext], see
+//! [crate::events::EventReceiver::recv_traced], so "who triggered this restart" is a search
+//! for the id. The context is task-local, tasks spawned from inside need their own [traced].
+//!
+//! # Example:
+//! ```
+//! use palworld_server::trace::{self, TraceContext, Trigger};
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let context = TraceContext::new(Trigger::Chat {
+//!         player: "Shadow".to_string(),
+//!         command: "voterestart".to_string(),
+//!     });
+//!     let id = context.id.clone();
+//!     trace::traced(context, async move {
+//!         assert_eq!(trace::current().unwrap().id, id);
+//!     })
+//!     .await;
+//!     assert!(trace::current().is_none());
+//! }
+//! ```
+
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::future::Future;
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+
+tokio::task_local! {
+    static CURRENT: TraceContext;
+}
+
+/// What started an action.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub enum Trigger {
+    /// A background task or schedule, by name.
+    Schedule(String),
+    /// A request to an HTTP or WebSocket API.
+    Http { method: String, path: String },
+    /// A chat command from a player.
+    Chat { player: String, command: String },
+    /// Anything else, like a CLI invocation.
+    Manual(String),
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Schedule(name) => write!(f, "task '{name}'"),
+            Self::Http { method, path } => write!(f, "{method} {path}"),
+            Self::Chat { player, command } => write!(f, "chat command '{command}' from {player}"),
+            Self::Manual(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+/// Correlation id of an action and its trigger.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct TraceContext {
+    /// Random looking 16 hex digit id.
+    pub id: String,
+    pub trigger: Trigger,
+}
+
+impl TraceContext {
+    /// A context with a new id.
+    pub fn new(trigger: Trigger) -> Self {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        static STATE: OnceLock<RandomState> = OnceLock::new();
+        // Hashing a counter with a random key gives unique ids that don't reveal the count.
+        let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let id = STATE.get_or_init(RandomState::new).hash_one(count);
+        Self {
+            id: format!("{id:016x}"),
+            trigger,
+        }
+    }
+}
+
+impl fmt::Display for TraceContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.id, self.trigger)
+    }
+}
+
+/// Runs `future` with `context` as the [current] trace.
+pub async fn traced<F: Future>(context: TraceContext, future: F) -> F::Output {
+    log::info!("[{}] Started by {}", context.id, context.trigger);
+    CURRENT.scope(context, future).await
+}
+
+/// The trace of the running task, None outside [traced].
+pub fn current() -> Option<TraceContext> {
+    CURRENT.try_with(TraceContext::clone).ok()
+}
+
+/// `[id] ` of the current trace for log lines, empty outside [traced].
+pub fn log_prefix() -> String {
+    CURRENT
+        .try_with(|context| format!("[{}] ", context.id))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_traced() {
+        let first = TraceContext::new(Trigger::Schedule("watcher".to_string()));
+        let second = TraceContext::new(Trigger::Schedule("watcher".to_string()));
+        assert_ne!(first.id, second.id);
+        assert_eq!(first.id.len(), 16);
+
+        assert_eq!(log_prefix(), "");
+        let id = first.id.clone();
+        traced(first.clone(), async {
+            assert_eq!(current(), Some(first));
+            assert_eq!(log_prefix(), format!("[{id}] "));
+            // Nested traces replace the outer one until they end.
+            traced(second.clone(), async {
+                assert_eq!(current(), Some(second));
+            })
+            .await;
+            assert_eq!(current().unwrap().id, id);
+        })
+        .await;
+        assert_eq!(current(), None);
+    }
+}
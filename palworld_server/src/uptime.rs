@@ -0,0 +1,208 @@
+//! Reachability probes and availability reports for servers with uptime promises.
+//!
+//! # Example:
+//! ```no_run
+//! use std::sync::Arc;
+//! use std::time::{Duration, SystemTime};
+//!
+//! use palworld_server::rcon::{PalworldRCON, DEFAULT_SOURCE_PORT};
+//! use palworld_server::store::SessionStore;
+//! use palworld_server::uptime::UptimeMonitor;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let rcon = PalworldRCON::new("localhost", DEFAULT_SOURCE_PORT, "MyRCONPassword");
+//!     let store = Arc::new(SessionStore::open("palworld.db").unwrap());
+//!     UptimeMonitor::new(rcon, store.clone()).spawn();
+//!
+//!     let now = SystemTime::now();
+//!     let week = Duration::from_secs(7 * 24 * 3600);
+//!     let report = store.uptime_report(now - week..now).unwrap();
+//!     if let Some(availability) = report.availability {
+//!         println!("{availability:.3}% available");
+//!     }
+//! }
+//! ```
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+use tokio::task::JoinHandle;
+
+use crate::rcon::PalworldRCON;
+use crate::store::SessionStore;
+
+/// Default interval between reachability probes.
+pub const DEFAULT_PROBE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Result of a single reachability probe.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Probe {
+    pub at: SystemTime,
+    pub reachable: bool,
+}
+
+/// A period where the server could not be reached.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct Incident {
+    /// First failed probe.
+    pub started_at: SystemTime,
+    /// First successful probe afterwards, None if the server is still down.
+    pub resolved_at: Option<SystemTime>,
+}
+
+impl Incident {
+    /// Length of the incident, ongoing incidents count up to `now`.
+    pub fn duration(&self, now: SystemTime) -> Duration {
+        self.resolved_at
+            .unwrap_or(now)
+            .duration_since(self.started_at)
+            .unwrap_or_default()
+    }
+}
+
+/// Availability over a range of probes.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct UptimeReport {
+    /// Number of probes in the range.
+    pub probes: usize,
+    /// Percentage of successful probes (0-100), None without probes.
+    pub availability: Option<f64>,
+    pub incidents: Vec<Incident>,
+    /// Total time spent in incidents.
+    pub downtime: Duration,
+    /// Mean time to recovery over resolved incidents.
+    pub mttr: Option<Duration>,
+}
+
+impl UptimeReport {
+    /// Builds a report from probes sorted oldest first, ongoing incidents count up to `end`.
+    pub fn from_probes(probes: &[Probe], end: SystemTime) -> Self {
+        let mut incidents: Vec<Incident> = Vec::new();
+        let mut down = false;
+        for probe in probes {
+            match (down, probe.reachable) {
+                (false, false) => incidents.push(Incident {
+                    started_at: probe.at,
+                    resolved_at: None,
+                }),
+                (true, true) => {
+                    if let Some(incident) = incidents.last_mut() {
+                        incident.resolved_at = Some(probe.at);
+                    }
+                }
+                _ => (),
+            }
+            down = !probe.reachable;
+        }
+
+        let reachable = probes.iter().filter(|p| p.reachable).count();
+        let availability = match probes.len() {
+            0 => None,
+            total => Some(reachable as f64 / total as f64 * 100.0),
+        };
+        let downtime = incidents.iter().map(|i| i.duration(end)).sum();
+        let resolved: Vec<Duration> = incidents
+            .iter()
+            .filter(|i| i.resolved_at.is_some())
+            .map(|i| i.duration(end))
+            .collect();
+        let mttr = match resolved.len() {
+            0 => None,
+            count => Some(resolved.iter().sum::<Duration>() / count as u32),
+        };
+        Self {
+            probes: probes.len(),
+            availability,
+            incidents,
+            downtime,
+            mttr,
+        }
+    }
+}
+
+/// Probes the server over RCON and records the result in a [SessionStore].
+#[derive(Debug)]
+pub struct UptimeMonitor {
+    rcon: PalworldRCON,
+    store: Arc<SessionStore>,
+    /// Time between probes.
+    pub interval: Duration,
+}
+
+impl UptimeMonitor {
+    /// Create a new [UptimeMonitor] probing every [DEFAULT_PROBE_INTERVAL].
+    pub fn new(rcon: PalworldRCON, store: Arc<SessionStore>) -> Self {
+        Self {
+            rcon,
+            store,
+            interval: DEFAULT_PROBE_INTERVAL,
+        }
+    }
+
+    /// Probes once, the server is reachable if it answers `info`.
+    pub async fn probe(&self) -> Result<bool> {
+        let reachable = match self.rcon.send_command("info").await {
+            Ok(_) => true,
+            Err(e) => {
+                log::warn!("Server unreachable: {e}");
+                false
+            }
+        };
+        self.store.record_probe(SystemTime::now(), reachable)?;
+        Ok(reachable)
+    }
+
+    /// Probes forever. Failures to record a probe are logged.
+    pub async fn run(self) -> Result<()> {
+        loop {
+            if let Err(e) = self.probe().await {
+                log::error!("Failed to record probe: {e}");
+            }
+            tokio::time::sleep(self.interval).await;
+        }
+    }
+
+    /// Runs the monitor in a background task.
+    pub fn spawn(self) -> JoinHandle<Result<()>> {
+        tokio::spawn(self.run())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_probes() {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let minute = Duration::from_secs(60);
+        let probes: Vec<Probe> = [true, false, false, true, true, false, true, false]
+            .iter()
+            .enumerate()
+            .map(|(i, reachable)| Probe {
+                at: start + minute * i as u32,
+                reachable: *reachable,
+            })
+            .collect();
+        let end = start + minute * 10;
+        let report = UptimeReport::from_probes(&probes, end);
+
+        assert_eq!(report.probes, 8);
+        assert_eq!(report.availability, Some(50.0));
+        assert_eq!(report.incidents.len(), 3);
+        assert_eq!(report.incidents[0].resolved_at, Some(start + minute * 3));
+        assert_eq!(report.incidents[2].resolved_at, None);
+        // 2 + 1 minutes resolved, 3 minutes ongoing.
+        assert_eq!(report.downtime, minute * 6);
+        assert_eq!(report.mttr, Some(minute * 3 / 2));
+
+        assert_eq!(UptimeReport::from_probes(&[], end).availability, None);
+    }
+}
@@ -0,0 +1,268 @@
+//! Diagnostics for configuration files, pointing at the line and column of a problem and
+//! suggesting a fix where one is likely, like a misspelled key.
+//!
+//! The checks live next to what they check, [crate::scheduler::validate_cron] and
+//! [crate::notify::RoutingConfig::validate], these are the building blocks they share.
+//!
+//! # Example:
+//! ```
+//! use palworld_server::validate::{unknown_key, Level};
+//!
+//! let config = "{\n  \"rcon_prot\": 25575\n}";
+//! let diagnostic = unknown_key("worlds.json", config, "rcon_prot", 0, &["name", "rcon_port"]);
+//! assert_eq!(diagnostic.level, Level::Error);
+//! assert_eq!((diagnostic.line, diagnostic.column), (2, 3));
+//! assert_eq!(diagnostic.help.as_deref(), Some("did you mean 'rcon_port'?"));
+//! ```
+
+use std::fmt;
+
+/// How bad a [Diagnostic] is, errors make the configuration unusable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Level {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Warning => write!(f, "warning"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A problem in a configuration file. Line and column start at 1, 0 when the problem isn't
+/// at a single place.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct Diagnostic {
+    pub level: Level,
+    pub message: String,
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    /// The line the problem is on.
+    pub source_line: Option<String>,
+    /// How to fix the problem.
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(level: Level, message: impl Into<String>) -> Self {
+        Self {
+            level,
+            message: message.into(),
+            file: String::new(),
+            line: 0,
+            column: 0,
+            source_line: None,
+            help: None,
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::new(Level::Error, message)
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self::new(Level::Warning, message)
+    }
+
+    /// Places the problem at byte `offset` of `text`, the contents of `file`.
+    pub fn at(mut self, file: impl Into<String>, text: &str, offset: usize) -> Self {
+        let (line, column) = line_column(text, offset);
+        self.file = file.into();
+        self.line = line;
+        self.column = column;
+        self.source_line = text.lines().nth(line - 1).map(str::to_string);
+        self
+    }
+
+    /// Names the file of a problem without a position.
+    pub fn in_file(mut self, file: impl Into<String>) -> Self {
+        self.file = file.into();
+        self
+    }
+
+    pub fn help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+}
+
+/// Formatted like compiler errors, with the line and a caret under the column.
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.level, self.message)?;
+        if !self.file.is_empty() {
+            match self.line {
+                0 => write!(f, "\n --> {}", self.file)?,
+                line => write!(f, "\n --> {}:{line}:{}", self.file, self.column)?,
+            }
+        }
+        if let Some(source_line) = &self.source_line {
+            let number = self.line.to_string();
+            let gutter = " ".repeat(number.len());
+            let caret = " ".repeat(self.column.saturating_sub(1));
+            write!(
+                f,
+                "\n{gutter} |\n{number} | {source_line}\n{gutter} | {caret}^"
+            )?;
+        }
+        if let Some(help) = &self.help {
+            write!(f, "\n  = help: {help}")?;
+        }
+        Ok(())
+    }
+}
+
+/// 1-based line and column, in characters, of byte `offset` of `text`.
+pub fn line_column(text: &str, offset: usize) -> (usize, usize) {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let before = &text[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+/// Byte offset of the `nth` (from 0) occurrence of `key` used as a key in JSON or TOML,
+/// optionally quoted and followed by `:` or `=`.
+pub fn find_key(text: &str, key: &str, nth: usize) -> Option<usize> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_' || c == '-';
+    text.match_indices(key)
+        .filter(|(start, _)| {
+            let before = text[..*start].chars().next_back();
+            let after = &text[start + key.len()..];
+            let (quoted, after) = match (before, after.strip_prefix('"')) {
+                (Some('"'), Some(after)) => (true, after),
+                _ => (false, after),
+            };
+            (quoted || !before.is_some_and(is_word))
+                && after
+                    .trim_start_matches([' ', '\t'])
+                    .starts_with([':', '='])
+                && (quoted || !after.starts_with(is_word))
+        })
+        .map(|(start, _)| match text[..start].ends_with('"') {
+            true => start - 1,
+            false => start,
+        })
+        .nth(nth)
+}
+
+/// Number of single character insertions, deletions and substitutions turning `a` into `b`.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The candidate closest to `name`, unless even that is too different to be a typo.
+pub fn did_you_mean<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let name = name.to_lowercase();
+    let limit = (name.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(&name, &candidate.to_lowercase()), candidate))
+        .filter(|(distance, _)| *distance <= limit)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Error for the `nth` occurrence of `key` in `text`, which isn't one of `known`, suggesting
+/// the closest known key.
+pub fn unknown_key(file: &str, text: &str, key: &str, nth: usize, known: &[&str]) -> Diagnostic {
+    let diagnostic = Diagnostic::error(format!("unknown key '{key}'"));
+    let diagnostic = match find_key(text, key, nth) {
+        Some(offset) => diagnostic.at(file, text, offset),
+        None => diagnostic.in_file(file),
+    };
+    match did_you_mean(key, known.iter().copied()) {
+        Some(known) => diagnostic.help(format!("did you mean '{known}'?")),
+        None => diagnostic.help(format!("expected one of {}", known.join(", "))),
+    }
+}
+
+/// Whether any of `diagnostics` is an error.
+pub fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics
+        .iter()
+        .any(|diagnostic| diagnostic.level == Level::Error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("rcon_port", "rcon_port"), 0);
+        assert_eq!(edit_distance("rcon_prot", "rcon_port"), 2);
+        assert_eq!(edit_distance("sevice", "service"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(
+            did_you_mean("sevice", ["name", "service", "sudo"]),
+            Some("service")
+        );
+        assert_eq!(
+            did_you_mean("Notifier", ["events", "notifiers"]),
+            Some("notifiers")
+        );
+        assert_eq!(did_you_mean("colour", ["name", "service", "sudo"]), None);
+    }
+
+    #[test]
+    fn test_find_key() {
+        let json = r#"[{"name": "a", "rcon_port": 1}, {"name": "rcon_port", "rcon_port": 2}]"#;
+        assert_eq!(find_key(json, "rcon_port", 0), Some(15));
+        assert_eq!(find_key(json, "rcon_port", 1), Some(54));
+        assert_eq!(find_key(json, "rcon_port", 2), None);
+        assert_eq!(find_key(json, "port", 0), None);
+
+        let toml = "[[routes]]\nmin_severity = \"info\"\nnotifiers=[]\n";
+        assert_eq!(find_key(toml, "notifiers", 0), Some(33));
+        assert_eq!(find_key(toml, "severity", 0), None);
+        assert_eq!(line_column(toml, 33), (3, 1));
+    }
+
+    #[test]
+    fn test_display() {
+        let text = "{\n  \"sevice\": \"palworld\"\n}";
+        let diagnostic = Diagnostic::error("unknown key 'sevice'")
+            .at("worlds.json", text, find_key(text, "sevice", 0).unwrap())
+            .help("did you mean 'service'?");
+        assert_eq!(
+            diagnostic.to_string(),
+            "error: unknown key 'sevice'
+ --> worlds.json:2:3
+  |
+2 |   \"sevice\": \"palworld\"
+  |   ^
+  = help: did you mean 'service'?"
+        );
+        let diagnostic = Diagnostic::warning("no routes").in_file("routes.toml");
+        assert_eq!(
+            diagnostic.to_string(),
+            "warning: no routes\n --> routes.toml"
+        );
+    }
+}
@@ -0,0 +1,110 @@
+//! How much the library writes on its own, for applications embedding it that own stdout.
+//!
+//! The library never prints unless asked to, like by a [crate::notify::StdoutNotifier] in a
+//! routing configuration. [quiet] silences even those for the whole process, and
+//! [Verbosity::Verbose] adds server responses to the `debug` log records, which otherwise
+//! only name the commands sent. Log records go to the `log` logger of the application.
+//!
+//! # Example:
+//! ```
+//! use palworld_server::verbosity::{self, Verbosity};
+//!
+//! verbosity::quiet();
+//! assert_eq!(verbosity::verbosity(), Verbosity::Quiet);
+//! verbosity::set_verbosity(Verbosity::Normal);
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use anyhow::{bail, Result};
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+
+/// What the library writes, see the [module documentation](self).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Verbosity {
+    /// Nothing on stdout or stderr, even from notifiers configured to print.
+    Quiet = 0,
+    #[default]
+    Normal = 1,
+    /// Server responses are logged too.
+    Verbose = 2,
+}
+
+impl Verbosity {
+    /// Whether printing notifiers print.
+    pub fn prints(&self) -> bool {
+        *self > Self::Quiet
+    }
+
+    /// Whether server responses are logged.
+    pub fn logs_responses(&self) -> bool {
+        *self >= Self::Verbose
+    }
+}
+
+impl fmt::Display for Verbosity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Quiet => write!(f, "quiet"),
+            Self::Normal => write!(f, "normal"),
+            Self::Verbose => write!(f, "verbose"),
+        }
+    }
+}
+
+impl FromStr for Verbosity {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "quiet" => Ok(Self::Quiet),
+            "normal" => Ok(Self::Normal),
+            "verbose" => Ok(Self::Verbose),
+            _ => bail!("Unknown verbosity '{s}', expected quiet, normal or verbose"),
+        }
+    }
+}
+
+/// Verbosity of the process, [Verbosity::Normal] unless set.
+pub fn verbosity() -> Verbosity {
+    match VERBOSITY.load(Ordering::Relaxed) {
+        0 => Verbosity::Quiet,
+        1 => Verbosity::Normal,
+        _ => Verbosity::Verbose,
+    }
+}
+
+/// Sets the verbosity of every subsystem in the process.
+pub fn set_verbosity(verbosity: Verbosity) {
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+}
+
+/// Keeps the library off stdout and stderr, call before using it.
+pub fn quiet() {
+    set_verbosity(Verbosity::Quiet);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verbosity() {
+        assert_eq!("Verbose".parse::<Verbosity>().unwrap(), Verbosity::Verbose);
+        assert!("loud".parse::<Verbosity>().is_err());
+        assert!(!Verbosity::Quiet.prints());
+        assert!(Verbosity::Normal.prints() && !Verbosity::Normal.logs_responses());
+        assert!(Verbosity::Verbose.logs_responses());
+        for verbosity in [Verbosity::Quiet, Verbosity::Verbose, Verbosity::Normal] {
+            assert_eq!(
+                verbosity.to_string().parse::<Verbosity>().unwrap(),
+                verbosity
+            );
+        }
+    }
+}
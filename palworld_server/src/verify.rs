@@ -0,0 +1,200 @@
+//! Checks that a started or restarted server works, not only that its service runs.
+//!
+//! [StartupVerification::run] waits for the server to accept RCON, see [crate::ready], then
+//! checks the version is what the restart should have left, the player list can be read and,
+//! if configured, a broadcast goes through. The first failing check is returned as a
+//! [VerificationFailed] error, which callers can downcast to tell what broke.
+//!
+//! # Example:
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use palworld_server::rcon::{PalworldRCON, DEFAULT_SOURCE_PORT};
+//! use palworld_server::verify::{StartupVerification, VersionCheck};
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let rcon = PalworldRCON::new("palworld.lan", DEFAULT_SOURCE_PORT, "MyRCONPassword");
+//!     let before = rcon.get_version().await.unwrap();
+//!     // ... update and restart the server ...
+//!     let mut verification = StartupVerification::new(Duration::from_secs(300));
+//!     verification.version = VersionCheck::Changed(before);
+//!     verification.broadcast = Some("Server updated".to_string());
+//!     let verified = verification.run(&rcon).await.unwrap();
+//!     println!("Running {} after {:?}", verified.version, verified.waited);
+//! }
+//! ```
+
+use std::fmt;
+use std::time::Duration;
+
+use anyhow::Result;
+
+use crate::rcon::PalworldRCON;
+
+/// What the version after a restart is checked against.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum VersionCheck {
+    /// Any version.
+    #[default]
+    Any,
+    /// The version before the restart, for restarts that shouldn't update the server.
+    Unchanged(String),
+    /// Anything but the version before the restart, for updates.
+    Changed(String),
+}
+
+/// Why a [StartupVerification] failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerificationFailed {
+    /// The server didn't accept RCON in time, the cause is the last error.
+    NotReady,
+    /// The version changed on a restart that should have kept it.
+    VersionChanged { expected: String, actual: String },
+    /// The version is the same after an update.
+    VersionUnchanged(String),
+    /// The player list couldn't be read, the cause is the error.
+    PlayerList,
+    /// The test broadcast failed, the cause is the error.
+    Broadcast,
+}
+
+impl fmt::Display for VerificationFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotReady => write!(f, "Server didn't accept RCON after starting"),
+            Self::VersionChanged { expected, actual } => {
+                write!(f, "Server version changed from {expected} to {actual}")
+            }
+            Self::VersionUnchanged(version) => {
+                write!(f, "Server version is still {version} after the update")
+            }
+            Self::PlayerList => write!(f, "Player list can't be read after starting"),
+            Self::Broadcast => write!(f, "Test broadcast failed after starting"),
+        }
+    }
+}
+
+impl std::error::Error for VerificationFailed {}
+
+/// A server that passed a [StartupVerification].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct Verified {
+    pub version: String,
+    /// Time until the server accepted RCON.
+    pub waited: Duration,
+    /// Online players, None if the player list wasn't checked.
+    pub players: Option<usize>,
+}
+
+/// Checks after a start, see the [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct StartupVerification {
+    /// Time the server gets to accept RCON.
+    pub timeout: Duration,
+    pub version: VersionCheck,
+    pub player_list: bool,
+    /// Message broadcast to check commands with side effects work, none if None.
+    pub broadcast: Option<String>,
+}
+
+impl StartupVerification {
+    /// Waits up to `timeout` and checks the player list, any version is accepted.
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            version: VersionCheck::Any,
+            player_list: true,
+            broadcast: None,
+        }
+    }
+
+    /// Runs the checks in order and fails on the first one failing, the error downcasts to
+    /// [VerificationFailed].
+    pub async fn run(&self, rcon: &PalworldRCON) -> Result<Verified> {
+        let ready = rcon
+            .wait_until_ready(self.timeout)
+            .await
+            .map_err(|e| e.context(VerificationFailed::NotReady))?;
+        check_version(&self.version, &ready.version)?;
+        let players = match self.player_list {
+            true => Some(
+                rcon.get_player_info()
+                    .await
+                    .map_err(|e| e.context(VerificationFailed::PlayerList))?
+                    .len(),
+            ),
+            false => None,
+        };
+        if let Some(message) = &self.broadcast {
+            rcon.broadcast(message.as_str(), None)
+                .await
+                .map_err(|e| e.context(VerificationFailed::Broadcast))?;
+        }
+        log::info!("Server {} verified after starting", ready.version);
+        Ok(Verified {
+            version: ready.version,
+            waited: ready.waited,
+            players,
+        })
+    }
+}
+
+fn check_version(check: &VersionCheck, actual: &str) -> Result<(), VerificationFailed> {
+    match check {
+        VersionCheck::Unchanged(expected) if expected != actual => {
+            Err(VerificationFailed::VersionChanged {
+                expected: expected.clone(),
+                actual: actual.to_string(),
+            })
+        }
+        VersionCheck::Changed(before) if before == actual => {
+            Err(VerificationFailed::VersionUnchanged(before.clone()))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_version() {
+        let v = |version: &str| version.to_string();
+        assert!(check_version(&VersionCheck::Any, "v0.1.5.1").is_ok());
+        assert!(check_version(&VersionCheck::Unchanged(v("v0.1.5.1")), "v0.1.5.1").is_ok());
+        assert_eq!(
+            check_version(&VersionCheck::Unchanged(v("v0.1.5.1")), "v0.2.0.6"),
+            Err(VerificationFailed::VersionChanged {
+                expected: v("v0.1.5.1"),
+                actual: v("v0.2.0.6")
+            })
+        );
+        assert!(check_version(&VersionCheck::Changed(v("v0.1.5.1")), "v0.2.0.6").is_ok());
+        assert_eq!(
+            check_version(&VersionCheck::Changed(v("v0.1.5.1")), "v0.1.5.1"),
+            Err(VerificationFailed::VersionUnchanged(v("v0.1.5.1")))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_not_ready() {
+        // Closed again, so connections are refused.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+        let rcon = PalworldRCON::new("127.0.0.1", port, "password");
+        let error = StartupVerification::new(Duration::ZERO)
+            .run(&rcon)
+            .await
+            .unwrap_err();
+        assert_eq!(error.downcast_ref(), Some(&VerificationFailed::NotReady));
+    }
+}
@@ -0,0 +1,633 @@
+//! Player votes through the [ChatBridge], like `!voterestart`.
+//!
+//! [VoteManager] restarts the server once enough players vote for it. [PollManager] runs
+//! polls with several options, players answer with `!vote <number>` and the caller gets a
+//! [PollOutcome] once the poll closes.
+//!
+//! # Example:
+//! ```no_run
+//! use std::sync::Arc;
+//!
+//! use palworld_server::chat::ChatBridge;
+//! use palworld_server::rcon::{PalworldRCON, DEFAULT_SOURCE_PORT};
+//! use palworld_server::ssh::PalworldConnection;
+//! use palworld_server::vote::VoteManager;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let rcon = PalworldRCON::new("localhost", DEFAULT_SOURCE_PORT, "MyRCONPassword");
+//!     let ssh = PalworldConnection::new("localhost:22", "steam", "MySSHPassword");
+//!     let mut bridge = ChatBridge::new(ssh, rcon.clone(), "/home/steam/palworld.log");
+//!     Arc::new(VoteManager::new(rcon)).register(&mut bridge, "voterestart");
+//!     bridge.run().await.unwrap();
+//! }
+//! ```
+//!
+//! Polls need the bridge running while the caller waits for the outcome:
+//! ```no_run
+//! # use std::sync::Arc;
+//! # use std::time::Duration;
+//! # use palworld_server::chat::ChatBridge;
+//! # use palworld_server::rcon::{PalworldRCON, DEFAULT_SOURCE_PORT};
+//! # use palworld_server::ssh::PalworldConnection;
+//! use palworld_server::vote::PollManager;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! # let rcon = PalworldRCON::new("localhost", DEFAULT_SOURCE_PORT, "MyRCONPassword");
+//! # let ssh = PalworldConnection::new("localhost:22", "steam", "MySSHPassword");
+//! let mut bridge = ChatBridge::new(ssh, rcon.clone(), "/home/steam/palworld.log");
+//! let polls = Arc::new(PollManager::new(rcon));
+//! polls.clone().register(&mut bridge, "vote");
+//! tokio::spawn(async move { bridge.run().await });
+//!
+//! let options = ["PvP on", "PvP off"].map(String::from).to_vec();
+//! let outcome = polls
+//!     .run("PvP this weekend?", options, Duration::from_secs(120))
+//!     .await
+//!     .unwrap();
+//! println!("{:?}", outcome.winner());
+//! # }
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Result};
+
+use crate::chat::ChatBridge;
+use crate::rcon::PalworldRCON;
+use crate::shutdown::ShutdownMessage;
+
+/// Settings of a vote.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoteConfig {
+    /// Fraction of online players that must vote, 0.5 is half.
+    pub threshold: f64,
+    /// Votes older than this are discarded and the vote starts over.
+    pub window: Duration,
+    /// Time after a passed vote before a new one can start.
+    pub cooldown: Duration,
+}
+
+impl Default for VoteConfig {
+    fn default() -> Self {
+        Self {
+            threshold: 0.5,
+            window: Duration::from_secs(5 * 60),
+            cooldown: Duration::from_secs(30 * 60),
+        }
+    }
+}
+
+/// Result of casting a vote.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VoteOutcome {
+    /// A vote passed recently, try again after the remaining time.
+    Cooldown(Duration),
+    /// The voter isn't in the player list.
+    NotOnline,
+    /// Vote counted but the threshold isn't reached yet.
+    Progress { votes: usize, required: usize },
+    /// The threshold was reached.
+    Passed,
+}
+
+/// Counts votes against the online player list, independent of any connection.
+#[derive(Debug, Default)]
+pub struct VoteTally {
+    votes: HashSet<String>,
+    started: Option<Instant>,
+    passed: Option<Instant>,
+}
+
+impl VoteTally {
+    /// Number of votes needed with `online` players.
+    pub fn required(online: usize, threshold: f64) -> usize {
+        ((online as f64 * threshold).ceil() as usize).max(1)
+    }
+
+    /// Casts a vote for `player` given the names of the `online` players.
+    pub fn vote(
+        &mut self,
+        config: &VoteConfig,
+        player: &str,
+        online: &[String],
+        now: Instant,
+    ) -> VoteOutcome {
+        if let Some(passed) = self.passed {
+            let elapsed = now.saturating_duration_since(passed);
+            if elapsed < config.cooldown {
+                return VoteOutcome::Cooldown(config.cooldown - elapsed);
+            }
+        }
+        if !online.iter().any(|name| name == player) {
+            return VoteOutcome::NotOnline;
+        }
+        match self.started {
+            Some(started) if now.saturating_duration_since(started) <= config.window => (),
+            _ => {
+                self.votes.clear();
+                self.started = Some(now);
+            }
+        }
+        self.votes.insert(player.to_string());
+        // Players that left don't count anymore.
+        self.votes.retain(|name| online.contains(name));
+
+        let required = Self::required(online.len(), config.threshold);
+        if self.votes.len() >= required {
+            self.votes.clear();
+            self.started = None;
+            self.passed = Some(now);
+            return VoteOutcome::Passed;
+        }
+        VoteOutcome::Progress {
+            votes: self.votes.len(),
+            required,
+        }
+    }
+}
+
+/// Runs a restart vote: when it passes the world is saved and the server shut down,
+/// leaving the restart to the service manager or container restart policy.
+#[derive(Debug)]
+pub struct VoteManager {
+    rcon: PalworldRCON,
+    pub config: VoteConfig,
+    /// Warning time given to players before the shutdown.
+    pub shutdown_delay: Duration,
+    /// Notice shown to players when the vote passes.
+    pub shutdown_message: ShutdownMessage,
+    tally: Mutex<VoteTally>,
+}
+
+impl VoteManager {
+    pub fn new(rcon: PalworldRCON) -> Self {
+        Self {
+            rcon,
+            config: VoteConfig::default(),
+            shutdown_delay: Duration::from_secs(60),
+            shutdown_message: ShutdownMessage::default(),
+            tally: Mutex::new(VoteTally::default()),
+        }
+    }
+
+    /// Casts a restart vote for `player`, returns the message to broadcast.
+    pub async fn vote_restart(&self, player: &str) -> Result<String> {
+        let online = self
+            .rcon
+            .get_player_info()
+            .await?
+            .into_iter()
+            .map(|player| player.name)
+            .collect::<Vec<String>>();
+        let outcome = self.tally.lock().expect("Vote tally lock poisoned").vote(
+            &self.config,
+            player,
+            &online,
+            Instant::now(),
+        );
+        let message = match outcome {
+            VoteOutcome::Cooldown(remaining) => {
+                format!("Vote restart is on cooldown for {}s", remaining.as_secs())
+            }
+            VoteOutcome::NotOnline => format!("{player} is not online"),
+            VoteOutcome::Progress { votes, required } => {
+                format!("Vote restart: {votes}/{required}, type !voterestart to vote")
+            }
+            VoteOutcome::Passed => {
+                log::info!("Restart vote passed");
+                let delay = self.shutdown_delay;
+                let notice = self.shutdown_message.render(delay)?;
+                // On one connection, so nothing runs between the save and the shutdown.
+                self.rcon
+                    .with_connection(|session| async move {
+                        session.save().await?;
+                        session.shutdown(Some(delay), notice).await
+                    })
+                    .await?;
+                format!("Vote passed, restarting in {}s", delay.as_secs())
+            }
+        };
+        Ok(message)
+    }
+
+    /// Registers `!name` with the chat bridge to vote for a restart.
+    pub fn register(self: Arc<Self>, bridge: &mut ChatBridge, name: &str) {
+        bridge.command(name, move |command| {
+            let manager = self.clone();
+            async move { Ok(Some(manager.vote_restart(&command.player).await?)) }
+        });
+    }
+}
+
+/// Result of a `!vote` in a poll.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PollVote {
+    /// Vote counted for the option at this index.
+    Counted(usize),
+    /// The player voted before, their vote moved to the option at this index.
+    Changed(usize),
+    /// Not one of the numbers of the options.
+    InvalidOption,
+}
+
+/// Winner of a closed poll, options as indexes into [PollOutcome::options].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PollResult {
+    Winner(usize),
+    /// Options with the same number of votes.
+    Tie(Vec<usize>),
+    NoVotes,
+}
+
+/// Votes of a closed poll.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct PollOutcome {
+    pub question: String,
+    pub options: Vec<String>,
+    /// Votes per option, in the order of [PollOutcome::options].
+    pub counts: Vec<usize>,
+    pub result: PollResult,
+}
+
+impl PollOutcome {
+    /// The winning option, None for ties and polls without votes.
+    pub fn winner(&self) -> Option<&str> {
+        match self.result {
+            PollResult::Winner(index) => Some(&self.options[index]),
+            _ => None,
+        }
+    }
+
+    /// Total number of votes.
+    pub fn votes(&self) -> usize {
+        self.counts.iter().sum()
+    }
+}
+
+/// A poll's question, options and votes, independent of any connection. Players vote
+/// with the 1-based number of an option, their last vote counts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Poll {
+    pub question: String,
+    pub options: Vec<String>,
+    votes: HashMap<String, usize>,
+}
+
+impl Poll {
+    /// A poll needs at least two options.
+    pub fn new(question: impl Into<String>, options: Vec<String>) -> Result<Self> {
+        if options.len() < 2 {
+            bail!("A poll needs at least 2 options, got {}", options.len());
+        }
+        Ok(Self {
+            question: question.into(),
+            options,
+            votes: HashMap::new(),
+        })
+    }
+
+    /// Votes for option `number` as `player`, replacing their earlier vote.
+    pub fn vote(&mut self, player: &str, number: usize) -> PollVote {
+        if !(1..=self.options.len()).contains(&number) {
+            return PollVote::InvalidOption;
+        }
+        let index = number - 1;
+        match self.votes.insert(player.to_string(), index) {
+            Some(_) => PollVote::Changed(index),
+            None => PollVote::Counted(index),
+        }
+    }
+
+    /// Votes per option.
+    pub fn counts(&self) -> Vec<usize> {
+        let mut counts = vec![0; self.options.len()];
+        for index in self.votes.values() {
+            counts[*index] += 1;
+        }
+        counts
+    }
+
+    /// The votes so far as an outcome, final once the poll closed.
+    pub fn outcome(&self) -> PollOutcome {
+        let counts = self.counts();
+        let most = counts.iter().copied().max().unwrap_or_default();
+        let leaders: Vec<usize> = (0..counts.len()).filter(|i| counts[*i] == most).collect();
+        let result = match (most, leaders.as_slice()) {
+            (0, _) => PollResult::NoVotes,
+            (_, [winner]) => PollResult::Winner(*winner),
+            _ => PollResult::Tie(leaders),
+        };
+        PollOutcome {
+            question: self.question.clone(),
+            options: self.options.clone(),
+            counts,
+            result,
+        }
+    }
+
+    /// `A 3, B 1`, the options with their votes.
+    pub fn tally(&self) -> String {
+        let counts = self.counts();
+        self.options
+            .iter()
+            .zip(counts)
+            .map(|(option, count)| format!("{option} {count}"))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+/// Runs one [Poll] at a time, broadcasting the question, interim tallies and the result.
+#[derive(Debug)]
+pub struct PollManager {
+    rcon: PalworldRCON,
+    /// Chat command players vote with, as registered with [PollManager::register].
+    pub vote_command: String,
+    /// Time between interim tallies, only broadcast if votes came in since the last one.
+    pub interim_interval: Duration,
+    /// Space replacement passed to [PalworldRCON::broadcast].
+    pub replace_space: Option<String>,
+    poll: Mutex<Option<Poll>>,
+}
+
+impl PollManager {
+    pub fn new(rcon: PalworldRCON) -> Self {
+        Self {
+            rcon,
+            vote_command: "vote".to_string(),
+            interim_interval: Duration::from_secs(30),
+            replace_space: None,
+            poll: Mutex::new(None),
+        }
+    }
+
+    fn poll(&self) -> std::sync::MutexGuard<'_, Option<Poll>> {
+        self.poll.lock().expect("Poll lock poisoned")
+    }
+
+    async fn broadcast(&self, message: &str) -> Result<()> {
+        self.rcon
+            .broadcast(message, self.replace_space.clone())
+            .await?;
+        Ok(())
+    }
+
+    /// Opens a poll for `window`, fails if another one is running. Returns the outcome once
+    /// it closed, after broadcasting it.
+    pub async fn run(
+        &self,
+        question: &str,
+        options: Vec<String>,
+        window: Duration,
+    ) -> Result<PollOutcome> {
+        let poll = Poll::new(question, options)?;
+        let numbered: Vec<String> = poll
+            .options
+            .iter()
+            .enumerate()
+            .map(|(i, option)| format!("{}) {option}", i + 1))
+            .collect();
+        let open = {
+            let mut current = self.poll();
+            if current.is_some() {
+                bail!("Another poll is running");
+            }
+            *current = Some(poll);
+            OpenPoll { manager: self }
+        };
+        let result = self.collect(question, &numbered, window).await;
+        let poll = open.close();
+        result?;
+
+        let outcome = poll.outcome();
+        let message = match &outcome.result {
+            PollResult::Winner(index) => format!(
+                "Poll result: {} wins with {} of {} votes",
+                outcome.options[*index],
+                outcome.counts[*index],
+                outcome.votes()
+            ),
+            PollResult::Tie(indexes) => {
+                let tied: Vec<&str> = indexes
+                    .iter()
+                    .map(|i| outcome.options[*i].as_str())
+                    .collect();
+                format!("Poll result: tie between {}", tied.join(" and "))
+            }
+            PollResult::NoVotes => "Poll closed without votes".to_string(),
+        };
+        log::info!("Poll '{question}' closed: {}", poll.tally());
+        self.broadcast(&message).await?;
+        Ok(outcome)
+    }
+
+    /// Announces the poll and broadcasts interim tallies until `window` is over.
+    async fn collect(&self, question: &str, numbered: &[String], window: Duration) -> Result<()> {
+        self.broadcast(&format!(
+            "Poll: {question} {}, type !{} <number>",
+            numbered.join(" "),
+            self.vote_command
+        ))
+        .await?;
+        let closes = Instant::now() + window;
+        let mut last_tally = None;
+        loop {
+            let left = closes.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return Ok(());
+            }
+            tokio::time::sleep(left.min(self.interim_interval)).await;
+            let left = closes.saturating_duration_since(Instant::now());
+            let tally = self.poll().as_ref().map(Poll::tally);
+            if !left.is_zero() && tally != last_tally {
+                let text = tally.clone().unwrap_or_default();
+                self.broadcast(&format!("Poll: {text} ({}s left)", left.as_secs()))
+                    .await?;
+                last_tally = tally;
+            }
+        }
+    }
+
+    /// Counts `!vote <number>` from `player`, returns the message to broadcast.
+    pub fn vote(&self, player: &str, args: &[String]) -> String {
+        let mut current = self.poll();
+        let Some(poll) = current.as_mut() else {
+            return "No poll running".to_string();
+        };
+        let number = args.first().and_then(|arg| arg.parse::<usize>().ok());
+        match number.map(|number| poll.vote(player, number)) {
+            Some(PollVote::Counted(index)) => {
+                format!("{player} voted for {}", poll.options[index])
+            }
+            Some(PollVote::Changed(index)) => {
+                format!("{player} changed their vote to {}", poll.options[index])
+            }
+            Some(PollVote::InvalidOption) | None => format!(
+                "Type !{} 1 to {} to vote",
+                self.vote_command,
+                poll.options.len()
+            ),
+        }
+    }
+
+    /// Registers `!name <number>` with the chat bridge to vote in the running poll. `name`
+    /// should match [PollManager::vote_command], which the poll's announcement mentions.
+    pub fn register(self: Arc<Self>, bridge: &mut ChatBridge, name: &str) {
+        bridge.command(name, move |command| {
+            let manager = self.clone();
+            async move { Ok(Some(manager.vote(&command.player, &command.args))) }
+        });
+    }
+}
+
+/// The poll [PollManager::run] opened, cleared when dropped so a run that is cancelled
+/// doesn't block every later poll.
+struct OpenPoll<'a> {
+    manager: &'a PollManager,
+}
+
+impl OpenPoll<'_> {
+    fn close(self) -> Poll {
+        self.manager.poll().take().expect("Poll still open")
+    }
+}
+
+impl Drop for OpenPoll<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut poll) = self.manager.poll.lock() {
+            poll.take();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockRcon;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_vote_tally() {
+        let config = VoteConfig::default();
+        let online = names(&["Alice", "Bob", "Carol", "Dave"]);
+        let mut tally = VoteTally::default();
+        let now = Instant::now();
+
+        assert_eq!(
+            tally.vote(&config, "Eve", &online, now),
+            VoteOutcome::NotOnline
+        );
+        assert_eq!(
+            tally.vote(&config, "Alice", &online, now),
+            VoteOutcome::Progress {
+                votes: 1,
+                required: 2
+            }
+        );
+        // Voting twice doesn't count twice.
+        assert_eq!(
+            tally.vote(&config, "Alice", &online, now),
+            VoteOutcome::Progress {
+                votes: 1,
+                required: 2
+            }
+        );
+        assert_eq!(
+            tally.vote(&config, "Bob", &online, now),
+            VoteOutcome::Passed
+        );
+        assert!(matches!(
+            tally.vote(&config, "Carol", &online, now + Duration::from_secs(60)),
+            VoteOutcome::Cooldown(_)
+        ));
+    }
+
+    #[test]
+    fn test_poll() {
+        assert!(Poll::new("PvP?", names(&["Yes"])).is_err());
+        let mut poll = Poll::new("Boss?", names(&["Anubis", "Jetragon", "Frostallion"])).unwrap();
+        assert_eq!(poll.outcome().result, PollResult::NoVotes);
+        assert_eq!(poll.vote("Alice", 1), PollVote::Counted(0));
+        assert_eq!(poll.vote("Bob", 2), PollVote::Counted(1));
+        assert_eq!(poll.vote("Carol", 4), PollVote::InvalidOption);
+        assert_eq!(poll.vote("Carol", 0), PollVote::InvalidOption);
+        assert_eq!(poll.outcome().result, PollResult::Tie(vec![0, 1]));
+        assert_eq!(poll.vote("Alice", 2), PollVote::Changed(1));
+        assert_eq!(poll.tally(), "Anubis 0, Jetragon 2, Frostallion 0");
+        let outcome = poll.outcome();
+        assert_eq!(outcome.result, PollResult::Winner(1));
+        assert_eq!(outcome.winner(), Some("Jetragon"));
+        assert_eq!(outcome.votes(), 2);
+    }
+
+    #[test]
+    fn test_poll_manager_vote() {
+        let rcon = PalworldRCON::new("invalid host name", 25575, "password");
+        let manager = PollManager::new(rcon);
+        assert_eq!(manager.vote("Alice", &names(&["1"])), "No poll running");
+        *manager.poll() = Some(Poll::new("PvP?", names(&["On", "Off"])).unwrap());
+        assert_eq!(manager.vote("Alice", &names(&["2"])), "Alice voted for Off");
+        assert_eq!(
+            manager.vote("Alice", &names(&["1"])),
+            "Alice changed their vote to On"
+        );
+        assert_eq!(
+            manager.vote("Bob", &names(&["yes"])),
+            "Type !vote 1 to 2 to vote"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_poll() {
+        let server = MockRcon::start("password", |_| Some("Broadcasted: poll".to_string())).await;
+        let mut rcon = PalworldRCON::new("127.0.0.1", server.port, "password");
+        rcon.protocol.detect = false;
+        let manager = Arc::new(PollManager::new(rcon));
+        let running = tokio::spawn({
+            let manager = manager.clone();
+            async move {
+                let hour = Duration::from_secs(3600);
+                manager.run("PvP?", names(&["On", "Off"]), hour).await
+            }
+        });
+        while manager.vote("Alice", &names(&["1"])) == "No poll running" {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        running.abort();
+        assert!(running.await.unwrap_err().is_cancelled());
+        assert_eq!(manager.vote("Alice", &names(&["1"])), "No poll running");
+
+        let outcome = manager
+            .run("Boss?", names(&["Anubis", "Jetragon"]), Duration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(outcome.result, PollResult::NoVotes);
+    }
+
+    #[test]
+    fn test_vote_window_expires() {
+        let config = VoteConfig::default();
+        let online = names(&["Alice", "Bob", "Carol"]);
+        let mut tally = VoteTally::default();
+        let now = Instant::now();
+
+        tally.vote(&config, "Alice", &online, now);
+        assert_eq!(
+            tally.vote(&config, "Bob", &online, now + config.window * 2),
+            VoteOutcome::Progress {
+                votes: 1,
+                required: 2
+            }
+        );
+    }
+}
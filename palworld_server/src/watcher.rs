@@ -0,0 +1,286 @@
+//! Polls the server and publishes changes to an [EventBus].
+//!
+//! Polling backs off with a [PollInterval] while nobody is online or the server is
+//! unreachable, so idle servers aren't polled every few seconds overnight.
+
+use std::time::Duration;
+
+use anyhow::Result;
+use tokio::task::JoinHandle;
+
+use crate::events::{Event, EventBus};
+use crate::models::PlayerInfo;
+use crate::rcon::{PalworldRCON, RconError};
+
+/// Default interval between `showplayers` polls while players are online.
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Default longest interval between polls of an empty or unreachable server.
+pub const DEFAULT_MAX_POLL_INTERVAL: Duration = Duration::from_secs(120);
+
+/// Time between polls, `min` while there is something to watch and doubling up to `max`
+/// while there isn't.
+///
+/// # Example:
+/// ```
+/// use std::time::Duration;
+///
+/// use palworld_server::watcher::PollInterval;
+///
+/// let interval = PollInterval::new(Duration::from_secs(10), Duration::from_secs(30));
+/// let idle = interval.next(interval.min, false);
+/// assert_eq!(idle, Duration::from_secs(20));
+/// assert_eq!(interval.next(idle, false), Duration::from_secs(30));
+/// assert_eq!(interval.next(idle, true), Duration::from_secs(10));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct PollInterval {
+    pub min: Duration,
+    pub max: Duration,
+}
+
+impl PollInterval {
+    pub fn new(min: Duration, max: Duration) -> Self {
+        Self { min, max }
+    }
+
+    /// Polls every `interval` without backing off.
+    pub fn fixed(interval: Duration) -> Self {
+        Self::new(interval, interval)
+    }
+
+    /// The interval after `current`, [PollInterval::min] if the last poll found something
+    /// `active`.
+    pub fn next(&self, current: Duration, active: bool) -> Duration {
+        match active {
+            true => self.min,
+            false => current
+                .saturating_mul(2)
+                .clamp(self.min, self.max.max(self.min)),
+        }
+    }
+}
+
+impl Default for PollInterval {
+    fn default() -> Self {
+        Self::new(DEFAULT_POLL_INTERVAL, DEFAULT_MAX_POLL_INTERVAL)
+    }
+}
+
+/// Publishes [Event::PlayerJoined] and [Event::PlayerLeft] by polling the player list, and
+/// [Event::PlayersOnline] with the players found by the first poll.
+///
+/// Without [PalworldRCON::rest_url] the list of a big server may be cut off, see
+/// [RconError::Truncated]. Joins in the partial list are still published, but leaves can't
+/// be told apart from players cut off the list, so they are only published once the list
+/// is complete again.
+#[derive(Debug)]
+pub struct PlayerWatcher {
+    rcon: PalworldRCON,
+    bus: EventBus,
+    /// Time between polls, backing off while nobody is online or polls fail.
+    pub interval: PollInterval,
+}
+
+impl PlayerWatcher {
+    /// Create a new [PlayerWatcher] polling every [DEFAULT_POLL_INTERVAL] while players are
+    /// online and up to every [DEFAULT_MAX_POLL_INTERVAL] otherwise.
+    pub fn new(rcon: PalworldRCON, bus: EventBus) -> Self {
+        Self {
+            rcon,
+            bus,
+            interval: PollInterval::default(),
+        }
+    }
+
+    /// Polls forever. Failed polls are retried with backoff, only the first failure of a
+    /// row is logged as a warning.
+    pub async fn run(self) -> Result<()> {
+        let mut players: Option<Vec<PlayerInfo>> = None;
+        let mut interval = self.interval.min;
+        let mut failing = false;
+        let mut truncated = false;
+        loop {
+            let polled = match self.rcon.get_player_info().await {
+                Ok(current) => Ok((current, true)),
+                Err(e) => match e.downcast::<RconError>() {
+                    Ok(RconError::Truncated(partial)) => Ok((partial, false)),
+                    Ok(e) => Err(e.into()),
+                    Err(e) => Err(e),
+                },
+            };
+            let active = match polled {
+                Ok((current, complete)) => {
+                    if failing {
+                        log::info!("Polling players again");
+                        failing = false;
+                    }
+                    if !complete && !truncated {
+                        log::warn!(
+                            "Player list truncated, leaves aren't published until it is \
+                            complete again. Set the REST API URL to read long lists whole"
+                        );
+                    }
+                    truncated = !complete;
+                    // The first poll only establishes who is already online.
+                    let (events, current) = match players {
+                        Some(previous) if complete => {
+                            (player_changes(&previous, &current), current)
+                        }
+                        Some(previous) => truncated_changes(previous, &current),
+                        None => (vec![Event::PlayersOnline(current.clone())], current),
+                    };
+                    for event in events {
+                        self.bus.publish_wait(event).await;
+                    }
+                    let active = !current.is_empty();
+                    players = Some(current);
+                    active
+                }
+                Err(e) => {
+                    match failing {
+                        true => log::debug!("Failed to poll players: {e}"),
+                        false => log::warn!("Failed to poll players: {e}"),
+                    }
+                    failing = true;
+                    false
+                }
+            };
+            interval = self.interval.next(interval, active);
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    /// Runs the watcher in a background task.
+    pub fn spawn(self) -> JoinHandle<Result<()>> {
+        tokio::spawn(self.run())
+    }
+}
+
+/// Compares two player lists by Steam ID and returns the joins and leaves.
+pub fn player_changes(previous: &[PlayerInfo], current: &[PlayerInfo]) -> Vec<Event> {
+    let left = previous
+        .iter()
+        .filter(|p| !current.iter().any(|c| c.steamid == p.steamid))
+        .map(|p| Event::PlayerLeft(p.clone()));
+    let joined = current
+        .iter()
+        .filter(|c| !previous.iter().any(|p| p.steamid == c.steamid))
+        .map(|c| Event::PlayerJoined(c.clone()));
+    left.chain(joined).collect()
+}
+
+/// The joins in `partial`, a cut off player list, and the players online after them.
+/// Players missing from it may only have been cut off, so nobody leaves.
+pub fn truncated_changes(
+    mut previous: Vec<PlayerInfo>,
+    partial: &[PlayerInfo],
+) -> (Vec<Event>, Vec<PlayerInfo>) {
+    let joined: Vec<PlayerInfo> = partial
+        .iter()
+        .filter(|c| !previous.iter().any(|p| p.steamid == c.steamid))
+        .cloned()
+        .collect();
+    let events = joined.iter().cloned().map(Event::PlayerJoined).collect();
+    previous.extend(joined);
+    (events, previous)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player(name: &str, steamid: &str) -> PlayerInfo {
+        PlayerInfo {
+            name: name.to_string(),
+            uid: "0".to_string(),
+            steamid: steamid.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_poll_interval() {
+        let secs = Duration::from_secs;
+        let interval = PollInterval::default();
+        let mut current = interval.min;
+        let mut idle = Vec::new();
+        for _ in 0..6 {
+            current = interval.next(current, false);
+            idle.push(current.as_secs());
+        }
+        assert_eq!(idle, vec![20, 40, 80, 120, 120, 120]);
+        assert_eq!(interval.next(current, true), secs(10));
+        assert_eq!(PollInterval::fixed(secs(5)).next(secs(5), false), secs(5));
+        // A max below the min polls at the min.
+        assert_eq!(
+            PollInterval::new(secs(5), secs(1)).next(secs(5), false),
+            secs(5)
+        );
+    }
+
+    #[test]
+    fn test_player_changes() {
+        let alice = player("Alice", "1");
+        let bob = player("Bob", "2");
+        let carol = player("Carol", "3");
+        let changes = player_changes(&[alice.clone(), bob.clone()], &[bob, carol.clone()]);
+        assert_eq!(
+            changes,
+            vec![Event::PlayerLeft(alice), Event::PlayerJoined(carol)]
+        );
+        assert!(player_changes(&[], &[]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_truncated_player_list() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let polls = AtomicUsize::new(0);
+        // Lists ending in a partial line were cut off.
+        let server = crate::mock::MockRcon::start("password", move |_| {
+            let response = match polls.fetch_add(1, Ordering::SeqCst) {
+                0 => "name,playeruid,steamid\nAlice,0,1\nBo",
+                1 => "name,playeruid,steamid\nCarol,0,3\nAlice,0,1\nBo",
+                2 => "name,playeruid,steamid\nCarol,0,3\nZe",
+                _ => "name,playeruid,steamid\nCarol,0,3\n",
+            };
+            Some(response.to_string())
+        })
+        .await;
+        let mut rcon = PalworldRCON::new("127.0.0.1", server.port, "password");
+        rcon.protocol.detect = false;
+        let bus = EventBus::default();
+        let mut events = bus.subscribe();
+        let mut watcher = PlayerWatcher::new(rcon, bus);
+        watcher.interval = PollInterval::fixed(Duration::from_millis(10));
+        let handle = watcher.spawn();
+
+        let mut received = Vec::new();
+        for _ in 0..3 {
+            let event = tokio::time::timeout(Duration::from_secs(5), events.recv());
+            received.push(event.await.unwrap().unwrap());
+        }
+        handle.abort();
+        let (alice, carol) = (player("Alice", "1"), player("Carol", "3"));
+        assert_eq!(
+            received,
+            vec![
+                Event::PlayersOnline(vec![alice.clone()]),
+                Event::PlayerJoined(carol),
+                // Only once the list is complete.
+                Event::PlayerLeft(alice),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_truncated_changes() {
+        let alice = player("Alice", "1");
+        let bob = player("Bob", "2");
+        let (events, online) = truncated_changes(vec![alice.clone()], std::slice::from_ref(&bob));
+        assert_eq!(events, vec![Event::PlayerJoined(bob.clone())]);
+        assert_eq!(online, vec![alice, bob]);
+    }
+}
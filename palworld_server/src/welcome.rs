@@ -0,0 +1,169 @@
+//! Welcome messages for players joining the server.
+//!
+//! # Example:
+//! ```no_run
+//! use palworld_server::events::EventBus;
+//! use palworld_server::rcon::{PalworldRCON, DEFAULT_SOURCE_PORT};
+//! use palworld_server::watcher::PlayerWatcher;
+//! use palworld_server::welcome::WelcomePolicy;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let rcon = PalworldRCON::new("localhost", DEFAULT_SOURCE_PORT, "MyRCONPassword");
+//!     let bus = EventBus::default();
+//!     let mut welcome = WelcomePolicy::new(rcon.clone());
+//!     welcome.template = "Welcome {name}, {player_count} online".to_string();
+//!     welcome.spawn(&bus);
+//!     PlayerWatcher::new(rcon, bus).run().await.unwrap();
+//! }
+//! ```
+
+use std::time::Duration;
+
+use anyhow::Result;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+
+use crate::events::{Event, EventBus};
+use crate::models::PlayerInfo;
+use crate::rcon::PalworldRCON;
+
+/// Default welcome, see [render] for the placeholders.
+pub static DEFAULT_WELCOME_TEMPLATE: &str = "Welcome {name}! {player_count} player(s) online";
+
+/// Replaces `{name}` and `{player_count}` in `template`.
+pub fn render(template: &str, name: &str, player_count: usize) -> String {
+    template
+        .replace("{name}", name)
+        .replace("{player_count}", &player_count.to_string())
+}
+
+/// Broadcasts a welcome when a player joins.
+#[derive(Debug)]
+pub struct WelcomePolicy {
+    rcon: PalworldRCON,
+    /// Welcome for every player, or only returning players when a store is set.
+    pub template: String,
+    /// Welcome for players on their first visit, needs a session store.
+    pub first_time_template: Option<String>,
+    /// Wait before the broadcast so the player has finished loading in.
+    pub delay: Duration,
+    /// Space replacement passed to [PalworldRCON::broadcast].
+    pub replace_space: Option<String>,
+    #[cfg(feature = "store")]
+    store: Option<std::sync::Arc<crate::store::SessionStore>>,
+}
+
+impl WelcomePolicy {
+    pub fn new(rcon: PalworldRCON) -> Self {
+        Self {
+            rcon,
+            template: DEFAULT_WELCOME_TEMPLATE.to_string(),
+            first_time_template: None,
+            delay: Duration::from_secs(10),
+            replace_space: None,
+            #[cfg(feature = "store")]
+            store: None,
+        }
+    }
+
+    /// Uses the session store to tell first-time players from returning ones.
+    #[cfg(feature = "store")]
+    pub fn with_store(mut self, store: std::sync::Arc<crate::store::SessionStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Returns true if the player has played before, false without a session store.
+    fn is_returning(&self, _player: &PlayerInfo) -> Result<bool> {
+        #[cfg(feature = "store")]
+        if let Some(store) = &self.store {
+            return Ok(store.completed_sessions(&_player.steamid)? > 0);
+        }
+        Ok(false)
+    }
+
+    /// Template used for `player`.
+    fn template_for(&self, player: &PlayerInfo) -> Result<&str> {
+        match &self.first_time_template {
+            Some(first_time) if !self.is_returning(player)? => Ok(first_time),
+            _ => Ok(&self.template),
+        }
+    }
+
+    /// Waits for [WelcomePolicy::delay] and broadcasts the welcome for `player`.
+    pub async fn welcome(&self, player: &PlayerInfo) -> Result<String> {
+        let template = self.template_for(player)?.to_string();
+        tokio::time::sleep(self.delay).await;
+        let player_count = self.rcon.get_player_info().await?.len();
+        let message = render(&template, &player.name, player_count);
+        self.rcon
+            .broadcast(message.as_str(), self.replace_space.clone())
+            .await?;
+        Ok(message)
+    }
+
+    /// Welcomes every [Event::PlayerJoined] from `bus` in a background task.
+    pub fn spawn(self, bus: &EventBus) -> JoinHandle<()> {
+        let mut events = bus.subscribe();
+        let policy = std::sync::Arc::new(self);
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(Event::PlayerJoined(player)) => {
+                        // Don't hold up the next join while waiting for the delay.
+                        let policy = policy.clone();
+                        tokio::spawn(async move {
+                            if let Err(e) = policy.welcome(&player).await {
+                                log::warn!("Failed to welcome {}: {e}", player.name);
+                            }
+                        });
+                    }
+                    Ok(_) => (),
+                    Err(RecvError::Lagged(skipped)) => {
+                        log::warn!("Welcome policy fell behind, skipped {skipped} event(s)")
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        assert_eq!(
+            render(DEFAULT_WELCOME_TEMPLATE, "Alice", 3),
+            "Welcome Alice! 3 player(s) online"
+        );
+    }
+
+    #[cfg(feature = "store")]
+    #[test]
+    fn test_first_time_template() {
+        use crate::store::SessionStore;
+        use std::sync::Arc;
+        use std::time::SystemTime;
+
+        let store = Arc::new(SessionStore::open_in_memory().unwrap());
+        let mut policy = WelcomePolicy::new(PalworldRCON::new("localhost", 25575, "password"))
+            .with_store(store.clone());
+        policy.first_time_template = Some("First time {name}".to_string());
+        let alice = PlayerInfo {
+            name: "Alice".to_string(),
+            uid: "1".to_string(),
+            steamid: "76561190000000001".to_string(),
+        };
+        assert_eq!(policy.template_for(&alice).unwrap(), "First time {name}");
+        store.record_join(&alice, SystemTime::now()).unwrap();
+        store.record_leave(&alice, SystemTime::now()).unwrap();
+        assert_eq!(
+            policy.template_for(&alice).unwrap(),
+            DEFAULT_WELCOME_TEMPLATE
+        );
+    }
+}
@@ -0,0 +1,151 @@
+//! Wake-on-LAN for powering up a sleeping server host before connecting to it.
+//!
+//! # Example:
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use palworld_server::wol::{self, MacAddress};
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let mac: MacAddress = "aa:bb:cc:dd:ee:ff".parse().unwrap();
+//!     wol::wake(&mac, wol::DEFAULT_BROADCAST_ADDR).await.unwrap();
+//!     // Wait for SSH to come up.
+//!     let timeout = Duration::from_secs(300);
+//!     let waited = wol::wait_until_reachable("palworld.lan", 22, timeout)
+//!         .await
+//!         .unwrap();
+//!     println!("Server reachable after {waited:?}");
+//! }
+//! ```
+
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use tokio::net::{TcpStream, UdpSocket};
+
+/// Limited broadcast on the discard port, reaches the local network segment.
+pub const DEFAULT_BROADCAST_ADDR: SocketAddr =
+    SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::BROADCAST, 9));
+
+/// Time between connection attempts in [wait_until_reachable].
+pub const REACHABLE_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// A network card's hardware address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MacAddress(pub [u8; 6]);
+
+impl FromStr for MacAddress {
+    type Err = anyhow::Error;
+
+    /// Parses `aa:bb:cc:dd:ee:ff`, `aa-bb-cc-dd-ee-ff` or `aabbccddeeff`.
+    fn from_str(s: &str) -> Result<Self> {
+        let hex = s.trim().replace([':', '-'], "");
+        if hex.len() != 12 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            bail!("Invalid MAC address '{s}'");
+        }
+        let mut mac = [0u8; 6];
+        for (i, byte) in mac.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)?;
+        }
+        Ok(Self(mac))
+    }
+}
+
+impl std::fmt::Display for MacAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let [a, b, c, d, e, g] = self.0;
+        write!(f, "{a:02x}:{b:02x}:{c:02x}:{d:02x}:{e:02x}:{g:02x}")
+    }
+}
+
+/// Builds the magic packet: 6 bytes of `0xff` followed by the MAC address 16 times.
+pub fn magic_packet(mac: &MacAddress) -> [u8; 102] {
+    let mut packet = [0xffu8; 102];
+    for chunk in packet[6..].chunks_mut(6) {
+        chunk.copy_from_slice(&mac.0);
+    }
+    packet
+}
+
+/// Sends a magic packet for `mac` to `broadcast_addr`, usually [DEFAULT_BROADCAST_ADDR]
+/// or the subnet's broadcast address like `192.168.1.255:9`.
+pub async fn wake(mac: &MacAddress, broadcast_addr: SocketAddr) -> Result<()> {
+    let bind: SocketAddr = match broadcast_addr {
+        SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        SocketAddr::V6(_) => (std::net::Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket = UdpSocket::bind(bind).await?;
+    socket.set_broadcast(true)?;
+    socket
+        .send_to(&magic_packet(mac), broadcast_addr)
+        .await
+        .with_context(|| format!("Failed to send magic packet to {broadcast_addr}"))?;
+    log::info!("Sent magic packet for {mac} to {broadcast_addr}");
+    Ok(())
+}
+
+/// Tries to open a TCP connection to `host:port` until it succeeds or `timeout` passes.
+/// Returns how long it took.
+pub async fn wait_until_reachable(host: &str, port: u16, timeout: Duration) -> Result<Duration> {
+    let start = Instant::now();
+    loop {
+        let attempt =
+            tokio::time::timeout(REACHABLE_POLL_INTERVAL, TcpStream::connect((host, port)));
+        match attempt.await {
+            Ok(Ok(_)) => return Ok(start.elapsed()),
+            Ok(Err(e)) => log::debug!("{host}:{port} not reachable yet: {e}"),
+            Err(_) => log::debug!("{host}:{port} not reachable yet: timed out"),
+        }
+        if start.elapsed() >= timeout {
+            bail!("{host}:{port} not reachable after {timeout:?}");
+        }
+        tokio::time::sleep(REACHABLE_POLL_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mac_address() {
+        let mac: MacAddress = "AA:bb:cc:dd:ee:0f".parse().unwrap();
+        assert_eq!(mac, MacAddress([0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0x0f]));
+        assert_eq!(mac.to_string(), "aa:bb:cc:dd:ee:0f");
+        assert_eq!("aa-bb-cc-dd-ee-0f".parse::<MacAddress>().unwrap(), mac);
+        assert_eq!("aabbccddee0f".parse::<MacAddress>().unwrap(), mac);
+        assert!("aa:bb:cc:dd:ee".parse::<MacAddress>().is_err());
+        assert!("aa:bb:cc:dd:ee:gg".parse::<MacAddress>().is_err());
+    }
+
+    #[test]
+    fn test_magic_packet() {
+        let mac = MacAddress([1, 2, 3, 4, 5, 6]);
+        let packet = magic_packet(&mac);
+        assert_eq!(packet[..6], [0xff; 6]);
+        assert!(packet[6..].chunks(6).all(|chunk| chunk == mac.0));
+    }
+
+    #[tokio::test]
+    async fn test_wake_and_wait() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let mac = MacAddress([1, 2, 3, 4, 5, 6]);
+        wake(&mac, receiver.local_addr().unwrap()).await.unwrap();
+        let mut buf = [0u8; 128];
+        let len = receiver.recv(&mut buf).await.unwrap();
+        assert_eq!(buf[..len], magic_packet(&mac));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert!(wait_until_reachable("127.0.0.1", port, Duration::ZERO)
+            .await
+            .is_ok());
+        drop(listener);
+        assert!(wait_until_reachable("127.0.0.1", port, Duration::ZERO)
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,374 @@
+//! Several worlds served from one host.
+//!
+//! A [WorldProfile] names where the settings and saves of a world live, the service running
+//! it and its ports. Backups, settings changes and service control take the profile so they
+//! act on that world and not on whatever the default installation is.
+//!
+//! # Example:
+//! ```no_run
+//! use palworld_server::ssh::{PalworldConnection, ServiceAction};
+//! use palworld_server::world::WorldProfile;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let connection = PalworldConnection::new("palworld.lan:22", "root", "MySSHPassword");
+//!     let mut pvp = WorldProfile::new("pvp");
+//!     pvp.saved_dir = "/home/steam/worlds/pvp/Saved".to_string();
+//!     pvp.service = "palworld-pvp".to_string();
+//!     pvp.game_port = 8212;
+//!     pvp.rcon_port = 25576;
+//!
+//!     let mut settings = pvp.read_settings(&connection).await.unwrap();
+//!     settings.set("bIsPvP", "True");
+//!     pvp.write_settings(&connection, &settings).await.unwrap();
+//!     pvp.service(&connection, ServiceAction::Restart).await.unwrap();
+//! }
+//! ```
+
+use anyhow::{bail, Result};
+
+use crate::config::{self, SettingsTemplate, Violation, WorldSettings};
+#[cfg(feature = "backup")]
+use crate::progress::Reporter;
+use crate::provision::InstallOptions;
+#[cfg(feature = "rcon")]
+use crate::rcon::PalworldRCON;
+use crate::ssh::{
+    change_privileged, run_privileged, shell_quote, CommandResult, PalworldConnection,
+    ServiceAction,
+};
+
+/// Paths, service and ports of one world on a host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde-camel-case", serde(rename_all = "camelCase"))]
+pub struct WorldProfile {
+    /// Name of the world, used in backup file names.
+    pub name: String,
+    /// Directory of the server installation, may be shared with other worlds.
+    pub install_dir: String,
+    /// `Pal/Saved` directory of the world, holding its settings and saves.
+    pub saved_dir: String,
+    /// systemd service running the world.
+    pub service: String,
+    /// UDP port players connect to.
+    pub game_port: u16,
+    pub rcon_port: u16,
+    /// Run privileged commands with `sudo -n`, for SSH users other than root.
+    pub sudo: bool,
+}
+
+impl WorldProfile {
+    /// The world of a [crate::provision::install] with default options.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self::from_install_options(name, &InstallOptions::new())
+    }
+
+    /// The world `options` install.
+    pub fn from_install_options(name: impl Into<String>, options: &InstallOptions) -> Self {
+        Self {
+            name: name.into(),
+            install_dir: options.install_dir.clone(),
+            saved_dir: format!("{}/Pal/Saved", options.install_dir),
+            service: options.service.clone(),
+            game_port: options.game_port,
+            rcon_port: options.rcon_port,
+            sudo: options.sudo,
+        }
+    }
+
+    /// Options installing the server for this world.
+    pub fn install_options(&self) -> InstallOptions {
+        InstallOptions {
+            install_dir: self.install_dir.clone(),
+            service: self.service.clone(),
+            game_port: self.game_port,
+            rcon_port: self.rcon_port,
+            sudo: self.sudo,
+            ..InstallOptions::new()
+        }
+    }
+
+    /// Path of the world's `PalWorldSettings.ini`.
+    pub fn settings_path(&self) -> String {
+        format!("{}/Config/LinuxServer/PalWorldSettings.ini", self.saved_dir)
+    }
+
+    /// Directory of the world's saves, `Level.sav` is in a subdirectory named after the
+    /// world's id.
+    pub fn save_games_dir(&self) -> String {
+        format!("{}/SaveGames", self.saved_dir)
+    }
+
+    /// Path of the world's ban list, one `steam_<SteamID>` per line.
+    pub fn banlist_path(&self) -> String {
+        format!("{}/banlist.txt", self.save_games_dir())
+    }
+
+    /// Directory of the server's own logs, like `Pal.log`.
+    pub fn logs_dir(&self) -> String {
+        format!("{}/Logs", self.saved_dir)
+    }
+
+    /// Removes a player from the world's ban list. The server reads the list when it starts,
+    /// so the unban takes effect with the next restart.
+    pub async fn unban(&self, connection: &PalworldConnection, steamid: &str) -> Result<()> {
+        let id = steamid.strip_prefix("steam_").unwrap_or(steamid);
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric()) {
+            bail!("Invalid SteamID '{steamid}'");
+        }
+        let result = self
+            .change(
+                connection,
+                &format!(
+                    "sed -i -e '/^steam_{id}$/d' -e '/^{id}$/d' {}",
+                    shell_quote(&self.banlist_path())
+                ),
+            )
+            .await?;
+        if !result.success() {
+            bail!(
+                "Failed to unban {steamid} in world '{}': {}",
+                self.name,
+                result.stderr.trim()
+            );
+        }
+        Ok(())
+    }
+
+    /// RCON client of the world on `host`.
+    #[cfg(feature = "rcon")]
+    pub fn rcon(&self, host: &str, password: &str) -> PalworldRCON {
+        PalworldRCON::new(host, self.rcon_port, password)
+    }
+
+    pub async fn read_settings(&self, connection: &PalworldConnection) -> Result<WorldSettings> {
+        let result = self
+            .run(
+                connection,
+                &format!("cat {}", shell_quote(&self.settings_path())),
+            )
+            .await?;
+        WorldSettings::parse(&result.output)
+    }
+
+    /// Writes `settings`, they take effect when the service restarts.
+    pub async fn write_settings(
+        &self,
+        connection: &PalworldConnection,
+        settings: &WorldSettings,
+    ) -> Result<()> {
+        // Redirecting into the existing file keeps its owner and mode.
+        self.change(
+            connection,
+            &format!(
+                "printf '%s' {} > {}",
+                shell_quote(&settings.to_ini()),
+                shell_quote(&self.settings_path())
+            ),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// The last `lines` lines the world's service wrote to the journal.
+    pub async fn service_log(
+        &self,
+        connection: &PalworldConnection,
+        lines: usize,
+    ) -> Result<String> {
+        let result = self
+            .run(
+                connection,
+                &format!(
+                    "journalctl -u {} -n {lines} --no-pager",
+                    shell_quote(&self.service)
+                ),
+            )
+            .await?;
+        if !result.success() {
+            bail!(
+                "Failed to read the journal of service '{}': {}",
+                self.service,
+                result.stderr.trim()
+            );
+        }
+        Ok(result.output)
+    }
+
+    /// The last `lines` lines of every `.log` file in [Self::logs_dir], by file name.
+    pub async fn game_logs(
+        &self,
+        connection: &PalworldConnection,
+        lines: usize,
+    ) -> Result<Vec<(String, String)>> {
+        let dir = self.logs_dir();
+        let listing = self
+            .run(connection, &format!("ls -1 {}", shell_quote(&dir)))
+            .await?;
+        if !listing.success() {
+            bail!("Failed to list {dir}: {}", listing.stderr.trim());
+        }
+        let mut logs = Vec::new();
+        for name in listing.output.lines().filter(|name| name.ends_with(".log")) {
+            let path = format!("{dir}/{name}");
+            let result = self
+                .run(
+                    connection,
+                    &format!("tail -n {lines} {}", shell_quote(&path)),
+                )
+                .await?;
+            logs.push((name.to_string(), result.output));
+        }
+        Ok(logs)
+    }
+
+    /// Checks the world's settings against `template`, see [crate::config::enforce]. With
+    /// `fix`, the violations that have a fix are written to the settings, they take effect
+    /// when the service restarts. Returns the violations found.
+    pub async fn enforce_settings(
+        &self,
+        connection: &PalworldConnection,
+        template: &SettingsTemplate,
+        fix: bool,
+    ) -> Result<Vec<Violation>> {
+        let mut settings = self.read_settings(connection).await?;
+        let violations = config::enforce(template, &settings);
+        for violation in &violations {
+            log::warn!("World '{}' drifted from its template: {violation}", self.name);
+        }
+        if fix && settings.apply(&violations) > 0 {
+            self.write_settings(connection, &settings).await?;
+            log::info!("Fixed the settings of world '{}'", self.name);
+        }
+        Ok(violations)
+    }
+
+    /// Starts, stops or restarts the world's service.
+    pub async fn service(
+        &self,
+        connection: &PalworldConnection,
+        action: ServiceAction,
+    ) -> Result<CommandResult> {
+        let action = match action {
+            ServiceAction::Start => "start",
+            ServiceAction::Stop => "stop",
+            ServiceAction::Restart => "restart",
+        };
+        self.change(
+            connection,
+            &format!("systemctl {action} {}", shell_quote(&self.service)),
+        )
+        .await
+    }
+
+    pub async fn is_running(&self, connection: &PalworldConnection) -> Result<bool> {
+        connection.is_service_active(&self.service).await
+    }
+
+    /// Archives the world's saves to a timestamped `.tar.gz` in `dest_dir` on the host and
+    /// returns its path. Save the world over RCON first for an up to date backup. A dry run
+    /// returns the path with a placeholder for the timestamp. Failures are published as
+    /// [crate::events::Event::BackupFailed] to [PalworldConnection::events].
+    #[cfg(feature = "backup")]
+    pub async fn backup(&self, connection: &PalworldConnection, dest_dir: &str) -> Result<String> {
+        let result = self.archive(connection, dest_dir).await;
+        #[cfg(feature = "rcon")]
+        if let Err(e) = &result {
+            connection.publish(crate::events::Event::BackupFailed {
+                world: self.name.clone(),
+                reason: e.to_string(),
+            });
+        }
+        result
+    }
+
+    #[cfg(feature = "backup")]
+    async fn archive(&self, connection: &PalworldConnection, dest_dir: &str) -> Result<String> {
+        let placeholder = format!("{dest_dir}/{}-<timestamp>.tar.gz", self.name);
+        let mut progress = Reporter::new(connection.progress.clone(), "backup", 1);
+        progress.step(format!("Archiving {} to {dest_dir}", self.save_games_dir()));
+        let dest_dir = shell_quote(dest_dir);
+        let result = self
+            .change(
+                connection,
+                &format!(
+                    "archive={dest_dir}/{name}-$(date -u +%Y%m%dT%H%M%SZ).tar.gz && \
+                    mkdir -p {dest_dir} && tar -czf \"$archive\" -C {saved} SaveGames && \
+                    echo \"$archive\"",
+                    name = shell_quote(&self.name),
+                    saved = shell_quote(&self.saved_dir),
+                ),
+            )
+            .await?;
+        if connection.dry_run.is_some() {
+            return Ok(placeholder);
+        }
+        let archive = result.output.trim();
+        if archive.is_empty() {
+            bail!("Backup of world '{}' failed", self.name);
+        }
+        Ok(archive.to_string())
+    }
+
+    async fn run(&self, connection: &PalworldConnection, command: &str) -> Result<CommandResult> {
+        run_privileged(connection, self.sudo, command).await
+    }
+
+    /// Runs a command changing the host, see [crate::dryrun].
+    async fn change(
+        &self,
+        connection: &PalworldConnection,
+        command: &str,
+    ) -> Result<CommandResult> {
+        change_privileged(connection, self.sudo, command).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_world_profile() {
+        let world = WorldProfile::new("main");
+        assert_eq!(world.settings_path(), InstallOptions::new().settings_path());
+        assert_eq!(
+            world.save_games_dir(),
+            "/home/steam/PalServer/Pal/Saved/SaveGames"
+        );
+        assert_eq!(world.install_options(), InstallOptions::new());
+
+        let mut pvp = WorldProfile::new("pvp");
+        pvp.saved_dir = "/home/steam/worlds/pvp/Saved".to_string();
+        pvp.service = "palworld-pvp".to_string();
+        pvp.game_port = 8212;
+        assert_eq!(
+            pvp.settings_path(),
+            "/home/steam/worlds/pvp/Saved/Config/LinuxServer/PalWorldSettings.ini"
+        );
+        let options = pvp.install_options();
+        assert_eq!(options.install_dir, world.install_dir);
+        assert_eq!(options.service, "palworld-pvp");
+        assert_eq!(options.game_port, 8212);
+    }
+
+    #[tokio::test]
+    async fn test_unban() {
+        let dry_run = crate::dryrun::DryRun::new();
+        let mut connection = PalworldConnection::new("palworld.lan:22", "root", "password");
+        connection.dry_run = Some(dry_run.clone());
+        let world = WorldProfile::new("main");
+        world
+            .unban(&connection, "steam_76561190000000001")
+            .await
+            .unwrap();
+        let action = dry_run.actions()[0].to_string();
+        assert!(
+            action.contains("sed -i -e '/^steam_76561190000000001$/d' -e '/^76561190000000001$/d'"),
+            "{action}"
+        );
+        assert!(action.contains("/home/steam/PalServer/Pal/Saved/SaveGames/banlist.txt"));
+        assert!(world.unban(&connection, "7656; rm -rf /").await.is_err());
+    }
+}
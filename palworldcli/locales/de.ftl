@@ -0,0 +1,172 @@
+# Deutsche Meldungen von palworldcli. Argumente in { $Klammern } bitte unverändert lassen.
+
+## Server
+
+wake-reachable = { $address } nach { $secs }s erreichbar
+password-required = --password oder --password-file wird für die Verbindung zum Server benötigt
+password-rotated = RCON-Passwort geändert
+password-rotated-file = RCON-Passwort geändert, { $path } aktualisiert
+new-password-prompt = Neues RCON-Passwort:
+new-password-confirm = Neues RCON-Passwort wiederholen:
+new-password-mismatch = Die neuen Passwörter stimmen nicht überein
+health-ok = Gesund ({ $ms }ms)
+health-failed = Nicht gesund: { $failures }
+ping-rcon = RCON hat in { $ms }ms geantwortet ({ $version })
+ping-game = Spielport { $port } hat in { $ms }ms geantwortet
+ping-game-players = Spielport { $port } hat in { $ms }ms geantwortet, { $players }/{ $max } Spieler
+batch-done = { $command }: { $response }
+batch-failed = { $command } fehlgeschlagen: { $error }
+batch-timed-out = { $command } hat nicht rechtzeitig geantwortet
+batch-skipped = { $command } übersprungen
+diagnose-fix = { $check }: { $fix }
+diagnose-ok = Alle Prüfungen bestanden
+diagnose-problems = { $count ->
+    [one] Ein Problem gefunden
+   *[other] { $count } Probleme gefunden
+}
+support-bundle-written = { $count ->
+    [one] Eine Datei
+   *[other] { $count } Dateien
+} nach { $path } geschrieben
+support-bundle-missing = Fehlt: { $error }
+players-found = Spielerinfos abgerufen: { $count } online!
+saved = Gespeichert: { $result }
+shutdown-result = Herunterfahren: { $result }
+kicked = { $name } ({ $steamid }) gekickt: { $result }
+banned = { $name } ({ $steamid }) gebannt: { $result }
+mod-warned = Verwarnung { $count } für { $name } ({ $steamid }): { $action }
+mod-no-strikes = Keine Verwarnungen für { $steamid }
+mod-imported = { $path } importiert: { $added } hinzugefügt, { $replaced } ersetzt, { $skipped } übersprungen
+mod-exported = { $bans } Banns, { $warnings } Verwarnungen und { $whitelist } Whitelist-Einträge nach { $path } exportiert
+config-same = Die Einstellungen von { $a } und { $b } stimmen überein
+config-differ = { $count ->
+    [one] Eine Einstellung unterscheidet sich
+   *[other] { $count } Einstellungen unterscheiden sich
+}
+config-compliant = Alle Einstellungen folgen der Vorlage
+config-fixed = { $violation }, korrigiert auf { $value }
+config-violations = { $count ->
+    [one] Eine Einstellung verletzt die Vorlage
+   *[other] { $count } Einstellungen verletzen die Vorlage
+}
+profile-encrypted = { $path } verschlüsselt
+profile-decrypted = { $path } entschlüsselt
+profile-already-encrypted = { $path } ist bereits verschlüsselt
+profile-not-encrypted = { $path } ist nicht verschlüsselt
+passphrase-prompt = Passphrase:
+passphrase-confirm = Passphrase wiederholen:
+passphrase-mismatch = Die Passphrasen stimmen nicht überein
+passphrase-no-terminal = Verschlüsselte Profile brauchen eine Passphrase, PALWORLD_PASSPHRASE setzen oder in einem Terminal ausführen
+palguard-whitelisted = { $steamid } zur Whitelist hinzugefügt: { $result }
+palguard-unwhitelisted = { $steamid } von der Whitelist entfernt: { $result }
+palguard-given = { $steamid } { $count } { $item } gegeben: { $result }
+palguard-spawned = { $pal } mit Level { $level } neben { $steamid } erschienen: { $result }
+palguard-position = { $steamid } ist bei { $position }
+palguard-teleported = { $steamid } nach { $position } teleportiert: { $result }
+teleported = { $name } nach { $location } teleportiert: { $result }
+location-saved = Ort { $name } bei { $position } in { $path } gespeichert
+location-removed = Ort { $name } aus { $path } entfernt
+locations-empty = Keine Orte in { $path }
+items-empty = Kein Gegenstand passt zu '{ $query }'
+pals-empty = Kein Pal passt zu '{ $query }'
+installed = Nach { $dir } installiert, Dienst '{ $service }' läuft
+installed-rcon = RCON-Port { $port }, Passwort: { $password }
+backed-up = Welt '{ $world }' nach { $archive } gesichert
+service-done = { $service } { $action ->
+    [start] gestartet
+    [stop] gestoppt
+   *[restart] neu gestartet
+}
+server-verified = Server { $version } nach { $secs } s geprüft, { $players } Spieler online
+migrated = Welt '{ $world }' nach { $host } umgezogen, { $bytes } Bytes, Version { $version }
+cutover-checklist = Checkliste für die Umstellung:
+dry-run = Probelauf, nichts wurde geändert. Würde ausführen:
+
+## Bestätigung destruktiver Aktionen
+
+operation-shutdown = den Server in { $secs }s herunterfahren
+operation-kick = { $player } kicken
+operation-ban = { $player } bannen
+operation-service-stop = den Dienst stoppen
+operation-service-restart = den Dienst neu starten
+operation-rotate-password = den Server mit neuem RCON-Passwort neu starten
+operation-migrate = den Server stoppen und nach { $host } umziehen
+operation-command = { $command } senden
+confirm-no-terminal = Ohne Bestätigung abgelehnt auf { $target }: { $operations }. Mit --yes bestätigen
+confirm-continue = Dies wird auf { $target } { $operations }. Fortfahren? [y/N]
+confirm-type-name = Zur Bestätigung den Servernamen '{ $target }' eingeben:
+confirm-aborted = Abgebrochen
+confirm-mismatch = '{ $answer }' ist nicht '{ $target }', abgebrochen
+
+## Zeitpläne und Verlauf
+
+next-runs = Nächste Ausführungen von { $schedule }:
+
+## Spielstände
+
+guild = { $name } ({ $id }): { $bases } Basis/Basen, Level { $level }, zuletzt online { $online }
+guild-admin = (Admin)
+guild-base = Basis '{ $name }' ({ $id }) bei { $location }
+days-ago = vor { $days } Tagen
+unknown = unbekannt
+never = nie
+world-day = Tag
+world-night = Nacht
+cleanup-plan = Probelauf, nichts wird gelöscht. { $bases } Basis/Basen von { $guilds } Gilde(n), inaktiv seit { $inactive }:
+cleanup-guild = { $name } ({ $id }), zuletzt gesehen { $seen }, Mitglieder: { $members }
+cleanup-base = Basis { $id } bei { $location } entfernen
+
+## Metriken und Verfügbarkeit
+
+no-metrics = Keine Metriken aufgezeichnet, zuerst metrics monitor ausführen
+graph-row = { $label }{ $sparkline } min { $min } max { $max } zuletzt { $last }
+graph-players = Spieler
+graph-memory = Speicher
+graph-cpu = CPU
+uptime = Verfügbarkeit seit { $since }: { $availability }% ({ $probes } Proben)
+no-probes = Keine Proben seit { $since }, zuerst uptime --monitor ausführen
+downtime = Ausfallzeit: { $downtime }, MTTR: { $mttr }
+ongoing = andauernd
+
+## Prüfung der Konfiguration
+
+validate-ok = Keine Probleme gefunden
+validate-warnings = { $warnings } Warnung(en), die Konfiguration ist verwendbar
+validate-failed = { $errors } Fehler und { $warnings } Warnung(en) in der Konfiguration
+
+## Selbstaktualisierung
+
+update-latest = palworldcli { $version } ist die neueste Version
+update-available = palworldcli { $latest } ist verfügbar, installiert ist { $current }. Mit self-update installieren
+update-installed = palworldcli von { $current } auf { $latest } aktualisiert
+update-no-key = Kein öffentlicher Schlüssel für die Signatur der Version, --public-key angeben, oder --insecure um nur die Prüfsumme zu prüfen
+
+## Tabellenköpfe
+
+column-name = Name
+column-uid = UID
+column-steamid = SteamID
+column-rank = #
+column-captures = Fänge
+column-pals = Pals
+column-best = Bestes
+column-average = Schnitt
+column-level = Level
+column-started = Beginn
+column-resolved = Behoben
+column-duration = Dauer
+column-sent = Gesendet
+column-initiator = Gesendet von
+column-message = Nachricht
+column-position = Position
+column-id = ID
+column-category = Kategorie
+column-elements = Elemente
+column-issued = Erteilt
+column-moderator = Von
+column-action = Maßnahme
+column-reason = Grund
+column-status = Status
+column-check = Prüfung
+column-detail = Details
+column-setting = Einstellung
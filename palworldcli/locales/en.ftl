@@ -0,0 +1,173 @@
+# Messages and table headers of palworldcli. Arguments are in { $braces }, keep them as they
+# are when translating. Messages missing from a translation are shown in English.
+
+## Server
+
+wake-reachable = { $address } reachable after { $secs }s
+password-required = --password or --password-file is required to connect to the server
+password-rotated = RCON password rotated
+password-rotated-file = RCON password rotated, updated { $path }
+new-password-prompt = New RCON password:
+new-password-confirm = New RCON password again:
+new-password-mismatch = The new passwords don't match
+health-ok = Healthy ({ $ms }ms)
+health-failed = Unhealthy: { $failures }
+ping-rcon = RCON answered in { $ms }ms ({ $version })
+ping-game = Game port { $port } answered in { $ms }ms
+ping-game-players = Game port { $port } answered in { $ms }ms, { $players }/{ $max } players
+batch-done = { $command }: { $response }
+batch-failed = { $command } failed: { $error }
+batch-timed-out = { $command } didn't answer in time
+batch-skipped = { $command } skipped
+diagnose-fix = { $check }: { $fix }
+diagnose-ok = Every check passed
+diagnose-problems = { $count ->
+    [one] One problem found
+   *[other] { $count } problems found
+}
+support-bundle-written = Wrote { $count ->
+    [one] one file
+   *[other] { $count } files
+} to { $path }
+support-bundle-missing = Missing { $error }
+players-found = Got player info: found { $count } online!
+saved = Saved: { $result }
+shutdown-result = Shutdown: { $result }
+kicked = Kicked { $name } ({ $steamid }): { $result }
+banned = Banned { $name } ({ $steamid }): { $result }
+mod-warned = Strike { $count } for { $name } ({ $steamid }): { $action }
+mod-no-strikes = No strikes for { $steamid }
+mod-imported = Imported { $path }: { $added } added, { $replaced } replaced, { $skipped } skipped
+mod-exported = Exported { $bans } bans, { $warnings } warnings and { $whitelist } whitelist entries to { $path }
+config-same = Settings of { $a } and { $b } match
+config-differ = { $count ->
+    [one] One setting differs
+   *[other] { $count } settings differ
+}
+config-compliant = All settings follow the template
+config-fixed = { $violation }, fixed to { $value }
+config-violations = { $count ->
+    [one] One setting breaks the template
+   *[other] { $count } settings break the template
+}
+profile-encrypted = Encrypted { $path }
+profile-decrypted = Decrypted { $path }
+profile-already-encrypted = { $path } is already encrypted
+profile-not-encrypted = { $path } isn't encrypted
+passphrase-prompt = Passphrase:
+passphrase-confirm = Passphrase again:
+passphrase-mismatch = The passphrases don't match
+passphrase-no-terminal = Encrypted profiles need a passphrase, set PALWORLD_PASSPHRASE or run in a terminal
+palguard-whitelisted = Whitelisted { $steamid }: { $result }
+palguard-unwhitelisted = Removed { $steamid } from the whitelist: { $result }
+palguard-given = Gave { $count } { $item } to { $steamid }: { $result }
+palguard-spawned = Spawned a level { $level } { $pal } next to { $steamid }: { $result }
+palguard-position = { $steamid } is at { $position }
+palguard-teleported = Teleported { $steamid } to { $position }: { $result }
+teleported = Teleported { $name } to { $location }: { $result }
+location-saved = Saved location { $name } at { $position } to { $path }
+location-removed = Removed location { $name } from { $path }
+locations-empty = No locations in { $path }
+items-empty = No item matches '{ $query }'
+pals-empty = No Pal matches '{ $query }'
+installed = Installed to { $dir }, service '{ $service }' is running
+installed-rcon = RCON port { $port }, password: { $password }
+backed-up = Backed up world '{ $world }' to { $archive }
+service-done = { $action ->
+    [start] Started
+    [stop] Stopped
+   *[restart] Restarted
+} { $service }
+server-verified = Server { $version } verified after { $secs }s, { $players } players online
+migrated = Moved world '{ $world }' to { $host }, { $bytes } bytes, running { $version }
+cutover-checklist = Cutover checklist:
+dry-run = Dry run, nothing was changed. Would run:
+
+## Confirmation of destructive operations
+
+operation-shutdown = shut down the server in { $secs }s
+operation-kick = kick { $player }
+operation-ban = ban { $player }
+operation-service-stop = stop the service
+operation-service-restart = restart the service
+operation-rotate-password = restart the server with a new RCON password
+operation-migrate = stop the server and move it to { $host }
+operation-command = send { $command }
+confirm-no-terminal = Refusing to { $operations } on { $target } without confirmation, pass --yes
+confirm-continue = This will { $operations } on { $target }. Continue? [y/N]
+confirm-type-name = Type the server name '{ $target }' to confirm:
+confirm-aborted = Aborted
+confirm-mismatch = '{ $answer }' isn't '{ $target }', aborted
+
+## Schedules and history
+
+next-runs = Next runs of { $schedule }:
+
+## Saves
+
+guild = { $name } ({ $id }): { $bases } base(s), level { $level }, last online { $online }
+guild-admin = (admin)
+guild-base = Base '{ $name }' ({ $id }) at { $location }
+days-ago = { $days }d ago
+unknown = unknown
+never = never
+world-day = day
+world-night = night
+cleanup-plan = Dry run, nothing is deleted. { $bases } base(s) of { $guilds } guild(s) inactive for { $inactive }:
+cleanup-guild = { $name } ({ $id }), last seen { $seen }, members: { $members }
+cleanup-base = remove base { $id } at { $location }
+
+## Metrics and uptime
+
+no-metrics = No metrics recorded, run metrics monitor first
+graph-row = { $label }{ $sparkline } min { $min } max { $max } last { $last }
+graph-players = Players
+graph-memory = Memory
+graph-cpu = CPU
+uptime = Uptime since { $since }: { $availability }% ({ $probes } probes)
+no-probes = No probes since { $since }, run uptime --monitor first
+downtime = Downtime: { $downtime }, MTTR: { $mttr }
+ongoing = ongoing
+
+## Validation
+
+validate-ok = No problems found
+validate-warnings = { $warnings } warning(s), the configuration is usable
+validate-failed = { $errors } error(s) and { $warnings } warning(s) in the configuration
+
+## Self-update
+
+update-latest = palworldcli { $version } is the latest version
+update-available = palworldcli { $latest } is available, this is { $current }. Run self-update to install it
+update-installed = Updated palworldcli from { $current } to { $latest }
+update-no-key = No release public key to verify the signature with, pass --public-key, or --insecure to only check the checksum
+
+## Table headers
+
+column-name = Name
+column-uid = UID
+column-steamid = SteamID
+column-rank = #
+column-captures = Captures
+column-pals = Pals
+column-best = Best
+column-average = Average
+column-level = Level
+column-started = Started
+column-resolved = Resolved
+column-duration = Duration
+column-sent = Sent
+column-initiator = Sent by
+column-message = Message
+column-position = Position
+column-id = ID
+column-category = Category
+column-elements = Elements
+column-issued = Issued
+column-moderator = By
+column-action = Action
+column-reason = Reason
+column-status = Status
+column-check = Check
+column-detail = Detail
+column-setting = Setting
@@ -0,0 +1,160 @@
+# palworldcli の日本語メッセージ。{ $引数 } はそのまま残してください。
+
+## サーバー
+
+wake-reachable = { $address } に { $secs } 秒後に接続できました
+password-required = サーバーに接続するには --password か --password-file が必要です
+password-rotated = RCON パスワードを変更しました
+password-rotated-file = RCON パスワードを変更し、{ $path } を更新しました
+new-password-prompt = 新しい RCON パスワード:
+new-password-confirm = 新しい RCON パスワード (確認):
+new-password-mismatch = 新しいパスワードが一致しません
+health-ok = 正常 ({ $ms }ms)
+health-failed = 異常: { $failures }
+ping-rcon = RCON が { $ms }ms で応答しました ({ $version })
+ping-game = ゲームポート { $port } が { $ms }ms で応答しました
+ping-game-players = ゲームポート { $port } が { $ms }ms で応答しました、プレイヤー { $players }/{ $max }
+batch-done = { $command }: { $response }
+batch-failed = { $command } が失敗しました: { $error }
+batch-timed-out = { $command } は時間内に応答しませんでした
+batch-skipped = { $command } をスキップしました
+diagnose-fix = { $check }: { $fix }
+diagnose-ok = すべてのチェックに合格しました
+diagnose-problems = { $count } 件の問題が見つかりました
+support-bundle-written = { $count } 個のファイルを { $path } に書き込みました
+support-bundle-missing = 取得できませんでした: { $error }
+players-found = プレイヤー情報を取得しました: { $count } 人がオンラインです
+saved = 保存: { $result }
+shutdown-result = シャットダウン: { $result }
+kicked = { $name } ({ $steamid }) をキックしました: { $result }
+banned = { $name } ({ $steamid }) を BAN しました: { $result }
+mod-warned = { $name } ({ $steamid }) のストライク { $count }: { $action }
+mod-no-strikes = { $steamid } のストライクはありません
+mod-imported = { $path } をインポートしました: 追加 { $added }、置換 { $replaced }、スキップ { $skipped }
+mod-exported = BAN { $bans } 件、警告 { $warnings } 件、ホワイトリスト { $whitelist } 件を { $path } にエクスポートしました
+config-same = { $a } と { $b } の設定は一致しています
+config-differ = { $count } 件の設定が異なります
+config-compliant = すべての設定がテンプレートに従っています
+config-fixed = { $violation }、{ $value } に修正しました
+config-violations = { $count } 件の設定がテンプレートに違反しています
+profile-encrypted = { $path } を暗号化しました
+profile-decrypted = { $path } を復号しました
+profile-already-encrypted = { $path } は既に暗号化されています
+profile-not-encrypted = { $path } は暗号化されていません
+passphrase-prompt = パスフレーズ:
+passphrase-confirm = パスフレーズ (確認):
+passphrase-mismatch = パスフレーズが一致しません
+passphrase-no-terminal = 暗号化されたプロファイルにはパスフレーズが必要です。PALWORLD_PASSPHRASE を設定するか端末で実行してください
+palguard-whitelisted = { $steamid } をホワイトリストに追加しました: { $result }
+palguard-unwhitelisted = { $steamid } をホワイトリストから削除しました: { $result }
+palguard-given = { $steamid } に { $item } を { $count } 個渡しました: { $result }
+palguard-spawned = { $steamid } の近くにレベル { $level } の { $pal } を出現させました: { $result }
+palguard-position = { $steamid } の位置: { $position }
+palguard-teleported = { $steamid } を { $position } にテレポートしました: { $result }
+teleported = { $name } を { $location } にテレポートしました: { $result }
+location-saved = 場所 { $name } ({ $position }) を { $path } に保存しました
+location-removed = 場所 { $name } を { $path } から削除しました
+locations-empty = { $path } に場所がありません
+items-empty = '{ $query }' に一致するアイテムがありません
+pals-empty = '{ $query }' に一致するパルがいません
+installed = { $dir } にインストールしました。サービス '{ $service }' は稼働中です
+installed-rcon = RCON ポート { $port }、パスワード: { $password }
+backed-up = ワールド '{ $world }' を { $archive } にバックアップしました
+service-done = { $service } を{ $action ->
+    [start] 起動しました
+    [stop] 停止しました
+   *[restart] 再起動しました
+}
+server-verified = サーバー { $version } を { $secs } 秒後に確認しました、{ $players } 人がオンラインです
+migrated = ワールド '{ $world }' を { $host } に移行しました ({ $bytes } バイト、バージョン { $version })
+cutover-checklist = 切り替えチェックリスト:
+dry-run = ドライランのため何も変更していません。実行予定のコマンド:
+
+## 破壊的な操作の確認
+
+operation-shutdown = { $secs } 秒後にサーバーをシャットダウン
+operation-kick = { $player } をキック
+operation-ban = { $player } を BAN
+operation-service-stop = サービスを停止
+operation-service-restart = サービスを再起動
+operation-rotate-password = 新しい RCON パスワードでサーバーを再起動
+operation-migrate = サーバーを停止して { $host } に移行
+operation-command = { $command } を送信
+confirm-no-terminal = 確認なしで { $target } に対して「{ $operations }」は実行できません。--yes を指定してください
+confirm-continue = { $target } に対して「{ $operations }」を実行します。続行しますか? [y/N]
+confirm-type-name = 確認のためサーバー名 '{ $target }' を入力してください:
+confirm-aborted = 中止しました
+confirm-mismatch = '{ $answer }' は '{ $target }' と一致しないため中止しました
+
+## スケジュールと履歴
+
+next-runs = { $schedule } の次回実行:
+
+## セーブデータ
+
+guild = { $name } ({ $id }): 拠点 { $bases } 件、レベル { $level }、最終オンライン { $online }
+guild-admin = (管理者)
+guild-base = 拠点 '{ $name }' ({ $id }) 座標 { $location }
+days-ago = { $days } 日前
+unknown = 不明
+never = なし
+world-day = 昼
+world-night = 夜
+cleanup-plan = ドライランのため何も削除していません。{ $inactive } 非アクティブなギルド { $guilds } 件の拠点 { $bases } 件:
+cleanup-guild = { $name } ({ $id })、最終確認 { $seen }、メンバー: { $members }
+cleanup-base = 拠点 { $id } を削除 (座標 { $location })
+
+## メトリクスと稼働率
+
+no-metrics = メトリクスが記録されていません。先に metrics monitor を実行してください
+graph-row = { $label }{ $sparkline } 最小 { $min } 最大 { $max } 最新 { $last }
+graph-players = 人数
+graph-memory = メモリ
+graph-cpu = CPU
+uptime = { $since } からの稼働率: { $availability }% (プローブ { $probes } 回)
+no-probes = { $since } 以降のプローブがありません。先に uptime --monitor を実行してください
+downtime = 停止時間: { $downtime }、MTTR: { $mttr }
+ongoing = 継続中
+
+## 検証
+
+validate-ok = 問題は見つかりませんでした
+validate-warnings = 警告 { $warnings } 件、設定は使用できます
+validate-failed = 設定にエラー { $errors } 件、警告 { $warnings } 件
+
+## 自動更新
+
+update-latest = palworldcli { $version } は最新版です
+update-available = palworldcli { $latest } が利用可能です (現在 { $current })。self-update でインストールできます
+update-installed = palworldcli を { $current } から { $latest } に更新しました
+update-no-key = 署名を検証する公開鍵がありません。--public-key を指定するか、チェックサムのみ確認する場合は --insecure を指定してください
+
+## 表の見出し
+
+column-name = 名前
+column-uid = UID
+column-steamid = SteamID
+column-rank = #
+column-captures = 捕獲数
+column-pals = パル数
+column-best = 最高
+column-average = 平均
+column-level = レベル
+column-started = 開始
+column-resolved = 復旧
+column-duration = 期間
+column-sent = 送信日時
+column-initiator = 送信元
+column-message = メッセージ
+column-position = 位置
+column-id = ID
+column-category = カテゴリ
+column-elements = 属性
+column-issued = 日時
+column-moderator = 実行者
+column-action = 処分
+column-reason = 理由
+column-status = 状態
+column-check = チェック
+column-detail = 詳細
+column-setting = 設定
@@ -0,0 +1,152 @@
+//! `palworldcli dist`: release metadata for the built binaries.
+//!
+//! Writes `SHA256SUMS`, which [crate::update] verifies downloads against, and
+//! `manifest.json` with the version and the target, file, size, SHA-256 and URL of every
+//! binary, for Homebrew formulas and Scoop manifests to be generated from.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use palworld_server::models::ServerVersion;
+use serde_json::json;
+
+use crate::update;
+
+/// A release binary, named like [update::asset_name].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Artifact {
+    pub file: String,
+    /// Architecture and OS, like `x86_64-linux`.
+    pub target: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+impl Artifact {
+    /// Reads and hashes a release binary.
+    pub fn read(path: &Path) -> Result<Self> {
+        let file = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .with_context(|| format!("{} has no file name", path.display()))?;
+        let Some(target) = target_of(file) else {
+            bail!("{file} isn't named like palworldcli-<arch>-<os>");
+        };
+        let data =
+            std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        Ok(Self {
+            file: file.to_string(),
+            target: target.to_string(),
+            size: data.len() as u64,
+            sha256: update::sha256_hex(&data),
+        })
+    }
+}
+
+/// Target of a release binary name, like `x86_64-windows` for
+/// `palworldcli-x86_64-windows.exe`.
+pub fn target_of(file: &str) -> Option<&str> {
+    let target = file.strip_prefix("palworldcli-")?;
+    let target = target.strip_suffix(".exe").unwrap_or(target);
+    target.contains('-').then_some(target)
+}
+
+/// `sha256sum` style checksums of `artifacts`.
+pub fn checksums(artifacts: &[Artifact]) -> String {
+    artifacts
+        .iter()
+        .map(|artifact| format!("{}  {}\n", artifact.sha256, artifact.file))
+        .collect()
+}
+
+/// Manifest of a release, download URLs are `base_url` followed by the file name.
+pub fn manifest(
+    version: &ServerVersion,
+    artifacts: &[Artifact],
+    base_url: Option<&str>,
+) -> serde_json::Value {
+    let artifacts: Vec<serde_json::Value> = artifacts
+        .iter()
+        .map(|artifact| {
+            let url =
+                base_url.map(|base| format!("{}/{}", base.trim_end_matches('/'), artifact.file));
+            json!({
+                "target": artifact.target,
+                "file": artifact.file,
+                "size": artifact.size,
+                "sha256": artifact.sha256,
+                "url": url,
+            })
+        })
+        .collect();
+    json!({
+        "name": "palworldcli",
+        "version": version.to_string(),
+        "artifacts": artifacts,
+    })
+}
+
+/// Writes `SHA256SUMS` and `manifest.json` for `paths` to `out`, returning the written files.
+pub fn write(
+    paths: &[PathBuf],
+    out: &Path,
+    version: &ServerVersion,
+    base_url: Option<&str>,
+) -> Result<Vec<PathBuf>> {
+    let artifacts = paths
+        .iter()
+        .map(|path| Artifact::read(path))
+        .collect::<Result<Vec<Artifact>>>()?;
+    std::fs::create_dir_all(out)?;
+    let sums = out.join("SHA256SUMS");
+    std::fs::write(&sums, checksums(&artifacts))?;
+    let manifest_path = out.join("manifest.json");
+    let manifest = manifest(version, &artifacts, base_url);
+    std::fs::write(&manifest_path, serde_json::to_string_pretty(&manifest)?)?;
+    Ok(vec![sums, manifest_path])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target_of() {
+        assert_eq!(target_of("palworldcli-x86_64-linux"), Some("x86_64-linux"));
+        assert_eq!(
+            target_of("palworldcli-x86_64-windows.exe"),
+            Some("x86_64-windows")
+        );
+        assert_eq!(target_of("palworldcli"), None);
+        assert_eq!(target_of("SHA256SUMS"), None);
+    }
+
+    #[test]
+    fn test_write() {
+        let dir = std::env::temp_dir().join(format!("palworldcli-dist-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let binary = dir.join("palworldcli-x86_64-linux");
+        std::fs::write(&binary, "hello").unwrap();
+        let version = ServerVersion(vec![0, 2, 0]);
+        let base_url = "https://example.com/v0.2.0/";
+        write(&[binary], &dir, &version, Some(base_url)).unwrap();
+
+        let hash = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        let sums = std::fs::read_to_string(dir.join("SHA256SUMS")).unwrap();
+        assert_eq!(
+            update::find_checksum(&sums, "palworldcli-x86_64-linux"),
+            Some(hash)
+        );
+        let manifest: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(dir.join("manifest.json")).unwrap())
+                .unwrap();
+        assert_eq!(manifest["version"], "v0.2.0");
+        assert_eq!(manifest["artifacts"][0]["sha256"], hash);
+        assert_eq!(manifest["artifacts"][0]["size"], 5);
+        assert_eq!(
+            manifest["artifacts"][0]["url"],
+            "https://example.com/v0.2.0/palworldcli-x86_64-linux"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
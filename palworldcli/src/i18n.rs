@@ -0,0 +1,178 @@
+//! Translated messages and table headers, from the Fluent bundles in `locales/`.
+//!
+//! The language is `--lang` or the one of `LC_ALL`, `LC_MESSAGES` or `LANG`. Messages
+//! missing from a translation are shown in English, JSON output and logs aren't translated.
+
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use anyhow::{bail, Result};
+use fluent::concurrent::FluentBundle;
+use fluent::{FluentArgs, FluentResource};
+use unic_langid::LanguageIdentifier;
+
+/// Languages with a bundle in `locales/`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Lang {
+    #[default]
+    En,
+    Ja,
+    De,
+}
+
+impl Lang {
+    /// Language of the locale environment variables, English if unset or unsupported.
+    pub fn detect() -> Self {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|locale| !locale.is_empty())
+            .and_then(|locale| locale.parse().ok())
+            .unwrap_or_default()
+    }
+
+    fn id(&self) -> &'static str {
+        match self {
+            Self::En => "en",
+            Self::Ja => "ja",
+            Self::De => "de",
+        }
+    }
+
+    fn source(&self) -> &'static str {
+        match self {
+            Self::En => include_str!("../locales/en.ftl"),
+            Self::Ja => include_str!("../locales/ja.ftl"),
+            Self::De => include_str!("../locales/de.ftl"),
+        }
+    }
+}
+
+impl FromStr for Lang {
+    type Err = anyhow::Error;
+
+    /// Parses a language like `ja` or a locale like `de_DE.UTF-8`.
+    fn from_str(s: &str) -> Result<Self> {
+        let language = s.split(['_', '-', '.', '@']).next().unwrap_or_default();
+        match language.to_lowercase().as_str() {
+            "en" | "c" | "posix" => Ok(Self::En),
+            "ja" => Ok(Self::Ja),
+            "de" => Ok(Self::De),
+            _ => bail!("Unsupported language '{s}', expected en, ja or de"),
+        }
+    }
+}
+
+/// Bundles of a language, falling back to English.
+pub struct Messages {
+    bundles: Vec<FluentBundle<FluentResource>>,
+}
+
+impl Messages {
+    pub fn new(lang: Lang) -> Self {
+        let mut bundles = vec![bundle(lang)];
+        if lang != Lang::En {
+            bundles.push(bundle(Lang::En));
+        }
+        Self { bundles }
+    }
+
+    /// Message `id` with `args`, the id itself if no bundle has it.
+    pub fn format(&self, id: &str, args: Option<&FluentArgs>) -> String {
+        for bundle in &self.bundles {
+            let Some(pattern) = bundle.get_message(id).and_then(|message| message.value()) else {
+                continue;
+            };
+            let mut errors = Vec::new();
+            let text = bundle.format_pattern(pattern, args, &mut errors);
+            if !errors.is_empty() {
+                log::warn!("Failed to format message '{id}': {errors:?}");
+            }
+            return text.into_owned();
+        }
+        log::warn!("Missing message '{id}'");
+        id.to_string()
+    }
+}
+
+fn bundle(lang: Lang) -> FluentBundle<FluentResource> {
+    let id: LanguageIdentifier = lang.id().parse().expect("Valid language id");
+    let resource =
+        FluentResource::try_new(lang.source().to_string()).expect("Valid Fluent messages");
+    let mut bundle = FluentBundle::new_concurrent(vec![id]);
+    // Terminals print the Unicode isolation marks around arguments.
+    bundle.set_use_isolating(false);
+    bundle
+        .add_resource(resource)
+        .expect("Messages are only defined once");
+    bundle
+}
+
+static MESSAGES: OnceLock<Messages> = OnceLock::new();
+
+/// Selects the language of [tr], English if never called.
+pub fn init(lang: Lang) {
+    if MESSAGES.set(Messages::new(lang)).is_err() {
+        log::warn!("Language was already selected, ignoring {lang:?}");
+    }
+}
+
+pub fn messages() -> &'static Messages {
+    MESSAGES.get_or_init(|| Messages::new(Lang::En))
+}
+
+/// Translated message, like `tr!("players-found", count = players.len())`.
+macro_rules! tr {
+    ($id:expr) => {
+        $crate::i18n::messages().format($id, None)
+    };
+    ($id:expr, $($key:ident = $value:expr),+ $(,)?) => {{
+        let mut args = fluent::FluentArgs::new();
+        $(args.set(stringify!($key), $value);)+
+        $crate::i18n::messages().format($id, Some(&args))
+    }};
+}
+pub(crate) use tr;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lang() {
+        assert_eq!("ja_JP.UTF-8".parse::<Lang>().unwrap(), Lang::Ja);
+        assert_eq!("de-AT".parse::<Lang>().unwrap(), Lang::De);
+        assert_eq!("C.UTF-8".parse::<Lang>().unwrap(), Lang::En);
+        assert!("fr_FR".parse::<Lang>().is_err());
+    }
+
+    #[test]
+    fn test_translations_complete() {
+        let ids: Vec<&str> = Lang::En
+            .source()
+            .lines()
+            .filter_map(|line| line.split_once(" = "))
+            .map(|(id, _)| id)
+            .filter(|id| !id.starts_with(['#', ' ']))
+            .collect();
+        assert!(ids.contains(&"players-found"));
+        for lang in [Lang::En, Lang::Ja, Lang::De] {
+            let messages = Messages::new(lang);
+            for id in &ids {
+                assert!(
+                    messages.bundles[0].has_message(id),
+                    "{lang:?} is missing '{id}'"
+                );
+            }
+        }
+        let german = Messages::new(Lang::De);
+        let mut args = FluentArgs::new();
+        args.set("action", "stop");
+        args.set("service", "palworld");
+        assert_eq!(
+            german.format("service-done", Some(&args)),
+            "palworld gestoppt"
+        );
+        assert_eq!(german.format("no-such-message", None), "no-such-message");
+    }
+}
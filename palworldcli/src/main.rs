@@ -1,16 +1,64 @@
-use anyhow::Result;
-use clap::Parser;
+mod dist;
+mod i18n;
+mod style;
+mod update;
+mod validate;
+
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand};
+use i18n::{tr, Lang};
 use palworld_server::{
-    mem,
-    rcon::{PalworldRCON, DEFAULT_SOURCE_PORT},
+    billing::{self, Tz},
+    chaos::{ChaosProxy, FaultConfig},
+    cleanup,
+    command::{self, Command},
+    config,
+    diagnostics::{CheckStatus, DiagnosticReport, Diagnostics},
+    dryrun::DryRun,
+    encryption,
+    gameport::DEFAULT_GAME_PORT,
+    health::{HealthCheck, HealthReport},
+    items, mem,
+    metrics::{self, MetricsSampler},
+    migrate::{self, MigrationOptions},
+    moddata::{self, MergeStrategy, ModerationData},
+    models::ByteSize,
+    moderation::{EscalationPolicy, ModerationLedger, WhitelistEntry},
+    notify::RoutingConfig,
+    palguard, pals,
+    progress::{Progress, ProgressUpdate},
+    provision::{self, InstallOptions},
+    rcon::{self, PalworldRCON, DEFAULT_SOURCE_PORT},
+    registry::{CommandRegistry, CommandSpec, ParserKind},
+    rotation::PasswordRotation,
+    savefile::{self, LevelSave, PlayerSave},
+    scheduler::{self, Schedule},
+    secret::Secret,
+    session::{CommandOutcome, CommandResult},
     ssh,
+    status::ServerStatus,
+    store::SessionStore,
+    support::{SupportBundle, DEFAULT_LOG_LINES},
+    trace::{self, TraceContext, Trigger},
+    uptime::{UptimeMonitor, UptimeReport},
+    validate::{has_errors, Diagnostic, Level},
+    verify::{StartupVerification, VersionCheck},
+    wol::{self, MacAddress},
+    world::WorldProfile,
 };
 use serde_json::json;
+use std::io::{IsTerminal, Read, Write};
+use std::sync::Arc;
+use style::ColorChoice;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)] // Read from `Cargo.toml`
+#[command(subcommand_negates_reqs = true)]
 struct Args {
-    #[arg(short = 'd', long = "debug_level")]
+    #[command(subcommand)]
+    action: Option<Action>,
+
+    #[arg(short = 'd', long = "debug-level", alias = "debug_level")]
     log_level_verbosity: Option<String>,
 
     #[arg(value_name = "localhost")]
@@ -22,11 +70,20 @@ struct Args {
     server_port: Option<u16>,
 
     /// Password of the palworld server (RCON or SSH)
-    #[arg(short = 'p', long)]
-    password: String,
+    #[arg(short = 'p', long, required_unless_present = "password_file")]
+    password: Option<String>,
+
+    /// Read the password from a file instead, keeps it out of the shell history. May be
+    /// encrypted, see `profiles`
+    #[arg(
+        long = "password-file",
+        value_name = "PATH",
+        conflicts_with = "password"
+    )]
+    password_file: Option<std::path::PathBuf>,
 
     /// output in json format
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     json: bool,
 
     /// Get player name, Unique ID, and SteamID
@@ -34,7 +91,7 @@ struct Args {
     player_info: bool,
 
     /// Get server version
-    #[arg(short = 'v', long = "server_version")]
+    #[arg(short = 'v', long = "server-version", alias = "server_version")]
     server_version: bool,
 
     /// Tell the server to save
@@ -45,7 +102,7 @@ struct Args {
     #[arg(short = 'S', long, value_name = "30")]
     shutdown: Option<u64>,
 
-    /// Broadcast a message to the server
+    /// Broadcast a message to the server, recorded in --store for `broadcasts history`
     #[arg(short, long)]
     broadcast: Option<String>,
 
@@ -57,45 +114,1142 @@ struct Args {
     #[arg(short, long)]
     command: Option<String>,
 
+    /// JSON file with commands added by server mods: name, parser (text, lines, key_values or
+    /// json) and read_only
+    #[arg(long, value_name = "commands.json")]
+    commands: Option<std::path::PathBuf>,
+
+    /// Print the response of --command as JSON, parsed by its parser from --commands
+    #[arg(long = "raw-json", requires = "command")]
+    raw_json: bool,
+
+    /// Kick an online player by name, name prefix, UID or SteamID
+    #[arg(long, value_name = "PLAYER")]
+    kick: Option<String>,
+
+    /// Ban an online player by name, name prefix, UID or SteamID
+    #[arg(long, value_name = "PLAYER")]
+    ban: Option<String>,
+
     /// Get memory usage of the server
     #[arg(short, long)]
     memory: bool,
 
     /// Get memory usage of the server through SSH
-    #[arg(short = 'M', long = "memory_ssh")]
+    #[arg(short = 'M', long = "memory-ssh", alias = "memory_ssh")]
     memory_ssh: bool,
 
     /// Username to use with an SSH connection
     #[arg(short, long)]
     username: Option<String>,
+
+    /// SQLite store used by uptime, metrics, stats, mod, --export-playtime and the broadcast
+    /// history
+    #[arg(long, value_name = "palworld.db", default_value = "palworld.db")]
+    store: String,
+
+    /// JSON file with the named places of `tp` and `locations`
+    #[arg(long, value_name = "locations.json", default_value = "locations.json")]
+    locations: std::path::PathBuf,
+
+    /// Export playtime per player and month over the given period as CSV, e.g. 90d
+    #[arg(long = "export-playtime", value_name = "90d")]
+    export_playtime: Option<humantime::Duration>,
+
+    /// Timezone the --export-playtime months start in and next-runs prints times in
+    #[arg(long, value_name = "Europe/Berlin", default_value = "UTC")]
+    timezone: Tz,
+
+    /// SteamIDs left out of --export-playtime, e.g. admins
+    #[arg(long, value_name = "STEAMID", value_delimiter = ',')]
+    exempt: Vec<String>,
+
+    /// Time healthcheck, status, ping and diagnose may take
+    #[arg(long, value_name = "5s", default_value = "5s", global = true)]
+    timeout: humantime::Duration,
+
+    /// Install SteamCMD, the server and a systemd service on a bare Ubuntu host over SSH
+    #[arg(long)]
+    install: bool,
+
+    /// With --install, only allow this address or network through to the RCON port
+    #[arg(long = "rcon-allowed-from", value_name = "IP", requires = "install")]
+    rcon_allowed_from: Option<String>,
+
+    /// Change the RCON password in the server settings over SSH and restart the server,
+    /// rolls back if the new password doesn't work. The new password is read from a password
+    /// file, which may be encrypted, or from stdin with -, so it doesn't show in the process
+    /// list. Updates --password-file if given
+    #[arg(long = "rotate-password", value_name = "FILE")]
+    rotate_password: Option<std::path::PathBuf>,
+
+    /// SSH password for --rotate-password, --backup and --service, defaults to --password
+    #[arg(long = "ssh-password")]
+    ssh_password: Option<String>,
+
+    /// SSH port for --rotate-password, --backup and --service
+    #[arg(long = "ssh-port", value_name = "22", default_value_t = 22)]
+    ssh_port: u16,
+
+    /// JSON file with a list of world profiles: name, install_dir, saved_dir, service,
+    /// game_port, rcon_port and sudo
+    #[arg(long, value_name = "worlds.json", requires = "world")]
+    worlds: Option<std::path::PathBuf>,
+
+    /// World from --worlds that --port, --install, --rotate-password, --backup and --service
+    /// act on, the default installation if not specified
+    #[arg(long, value_name = "NAME", requires = "worlds")]
+    world: Option<String>,
+
+    /// Archive the world's saves to this directory on the host over SSH
+    #[arg(long, value_name = "DIR")]
+    backup: Option<String>,
+
+    /// Start, stop or restart the world's service over SSH
+    #[arg(long, value_name = "start|stop|restart")]
+    service: Option<ssh::ServiceAction>,
+
+    /// After --service start or restart, wait up to this long for the server to accept RCON
+    /// logins, then check its version and player list. Logins rejected while it boots are
+    /// retried instead of failing as a wrong password
+    #[arg(long = "wait-ready", value_name = "5m", requires = "service")]
+    wait_ready: Option<humantime::Duration>,
+
+    /// With --wait-ready, the version has to change across --service restart, for restarts
+    /// that update the server. Otherwise it has to stay the same
+    #[arg(long = "expect-update", requires = "wait_ready")]
+    expect_update: bool,
+
+    /// With --wait-ready, broadcast this message to check commands go through
+    #[arg(
+        long = "verify-broadcast",
+        value_name = "MESSAGE",
+        requires = "wait_ready"
+    )]
+    verify_broadcast: Option<String>,
+
+    /// Move the world to another host over SSH: stops it here, transfers the saves and
+    /// settings, installs and starts the server there and prints a cutover checklist
+    #[arg(long = "migrate-to", value_name = "HOST:22")]
+    migrate_to: Option<String>,
+
+    /// SSH password of the --migrate-to host, defaults to --ssh-password
+    #[arg(long = "migrate-password", requires = "migrate_to")]
+    migrate_password: Option<String>,
+
+    /// With --migrate-to, the server is already installed on the destination
+    #[arg(long = "skip-install", requires = "migrate_to")]
+    skip_install: bool,
+
+    /// Print the commands and transfers --install, --rotate-password, --backup, --service and
+    /// --migrate-to would run on the hosts instead of running them. RCON commands are still
+    /// sent, destructive ones after confirmation
+    #[arg(long = "dry-run")]
+    dry_run: bool,
+
+    /// Don't ask before destructive operations like --shutdown, --ban and --service stop,
+    /// needed to run them without a terminal
+    #[arg(short, long)]
+    yes: bool,
+
+    /// Color output, auto colors a terminal unless NO_COLOR is set
+    #[arg(long, value_name = "auto|always|never", default_value = "auto")]
+    color: ColorChoice,
+
+    /// Language of messages and table headers, detected from LANG if not specified
+    #[arg(long, value_name = "en|ja|de")]
+    lang: Option<Lang>,
+
+    /// Limit SFTP transfers of --migrate-to to this many KiB per second
+    #[arg(long = "bandwidth-limit", value_name = "KIB")]
+    bandwidth_limit: Option<u64>,
+
+    /// Inject RCON faults for soak testing, e.g. latency=200ms,disconnect=5,truncate=3,auth=10
+    #[arg(long, value_name = "FAULTS", hide = true)]
+    chaos: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Action {
+    /// Inspect world save files, no connection to the server is made
+    Saves {
+        #[command(subcommand)]
+        command: SavesCommand,
+    },
+    /// Print the next runs of a cron expression in --timezone, no connection to the server
+    /// is made
+    NextRuns {
+        /// Cron expression, e.g. "0 4 * * *" or @daily
+        expression: String,
+
+        /// Number of runs to print
+        #[arg(long, default_value_t = 5)]
+        count: usize,
+    },
+    /// Check a world profiles file, a notification routing file and cron expressions, printing
+    /// where each problem is and how to fix it. No connection to the server is made
+    Validate {
+        /// World profiles, as given to --worlds
+        #[arg(long, value_name = "worlds.json")]
+        worlds: Option<std::path::PathBuf>,
+
+        /// Notification routing configuration
+        #[arg(long, value_name = "routes.toml")]
+        routes: Option<std::path::PathBuf>,
+
+        /// Cron expression, may be given several times
+        #[arg(long, value_name = "EXPRESSION")]
+        cron: Vec<String>,
+    },
+    /// Compare world settings or check them against a template, from PalWorldSettings.ini files
+    /// or read over SSH
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Encrypt or decrypt password files and --worlds profiles with a passphrase. Encrypted
+    /// files are decrypted when read, with the passphrase from PALWORLD_PASSPHRASE or a prompt
+    Profiles {
+        #[command(subcommand)]
+        command: ProfilesCommand,
+    },
+    /// Commands of the PalGuard server mod, which has to be installed on the server
+    Palguard {
+        #[command(subcommand)]
+        command: PalguardCommand,
+    },
+    /// Teleport an online player to a named place from --locations or to coordinates like
+    /// -1234.5,567,89, needs PalGuard
+    Tp {
+        /// Name, name prefix, UID or SteamID of the player
+        player: String,
+
+        /// Name of the place or its coordinates
+        #[arg(allow_hyphen_values = true)]
+        location: String,
+    },
+    /// Wake the host with a Wake-on-LAN packet and wait until --port is reachable
+    Wake {
+        /// MAC address of the host, like aa:bb:cc:dd:ee:ff
+        #[arg(long)]
+        mac: MacAddress,
+
+        /// Where to send the packet, e.g. the subnet broadcast address
+        #[arg(
+            long = "broadcast-addr",
+            value_name = "255.255.255.255:9",
+            default_value = "255.255.255.255:9"
+        )]
+        broadcast_addr: std::net::SocketAddr,
+
+        /// Time the host may take to become reachable
+        #[arg(long, value_name = "5m", default_value = "5m")]
+        within: humantime::Duration,
+    },
+    /// Check the server answers within --timeout for container health checks, exits 1 if
+    /// unhealthy
+    Healthcheck {
+        /// Fails if the server is older than this version
+        #[arg(long = "min-version", value_name = "v0.1.5.0")]
+        min_version: Option<String>,
+
+        /// Fails if more players than this are online
+        #[arg(long = "max-players", value_name = "32")]
+        max_players: Option<usize>,
+    },
+    /// Print health, players, memory and version as versioned JSON. Memory is read from the
+    /// host over SSH with --memory-ssh or --username, and null otherwise
+    Status,
+    /// Time a round trip to RCON within --timeout, and to the game UDP port with --game.
+    /// Exits 1 if either doesn't answer
+    Ping {
+        /// Also probe the game port with a Steam A2S_INFO query
+        #[arg(long)]
+        game: bool,
+
+        /// Game port (PublicPort) of the server
+        #[arg(long = "game-port", default_value_t = DEFAULT_GAME_PORT)]
+        game_port: u16,
+    },
+    /// Send RCON commands in order on one connection, stopping at the first that fails or when
+    /// --within runs out. Exits 1 unless every command succeeded
+    Batch {
+        /// Commands like Save or "Shutdown 10 Restarting"
+        #[arg(required = true)]
+        commands: Vec<String>,
+
+        /// Time the whole batch may take, commands not sent by then are skipped
+        #[arg(long, default_value = "30s")]
+        within: humantime::Duration,
+    },
+    /// Check DNS, the RCON port, authentication, large responses, the game UDP port and SSH
+    /// (--ssh-port) one after the other, printing suggested fixes. Exits 1 if a check fails
+    Diagnose {
+        /// Game port (PublicPort) of the server
+        #[arg(long = "game-port", default_value_t = DEFAULT_GAME_PORT)]
+        game_port: u16,
+
+        /// Skip the SSH check, for servers not managed over SSH
+        #[arg(long = "no-ssh")]
+        no_ssh: bool,
+    },
+    /// Gather the server logs and redacted settings over SSH (--ssh-port), version info,
+    /// recent metrics from --store and diagnostics into a .tar.gz to attach to bug reports
+    SupportBundle {
+        /// Archive to write
+        #[arg(long, default_value = "palworld-support.tar.gz")]
+        output: std::path::PathBuf,
+
+        /// Include metrics recorded within this long ago
+        #[arg(long, value_name = "24h", default_value = "24h")]
+        since: humantime::Duration,
+
+        /// Average the included metrics over this step
+        #[arg(long, value_name = "5m", default_value = "5m")]
+        step: humantime::Duration,
+
+        /// Lines kept of each log
+        #[arg(long = "log-lines", default_value_t = DEFAULT_LOG_LINES)]
+        log_lines: usize,
+
+        /// Leave out logs and settings, for servers not managed over SSH
+        #[arg(long = "no-ssh")]
+        no_ssh: bool,
+    },
+    /// Search the item catalog for IDs to give, lists every item without a query. No
+    /// connection to the server is made
+    Items { query: Option<String> },
+    /// Search the Pal species catalog for IDs to spawn, lists every Pal without a query. No
+    /// connection to the server is made
+    Pals {
+        query: Option<String>,
+
+        /// Only Pals of this element, e.g. fire or dragon
+        #[arg(long)]
+        element: Option<pals::Element>,
+    },
+    /// Manage the named places in --locations, no connection to the server is made
+    Locations {
+        #[command(subcommand)]
+        command: LocationsCommand,
+    },
+    /// Warn players with strikes that escalate to kicks and bans, kept in --store with bans
+    /// and the whitelist
+    #[command(name = "mod")]
+    Mod {
+        #[command(subcommand)]
+        command: ModCommand,
+    },
+    /// Broadcasts sent through this crate, read from --store
+    Broadcasts {
+        #[command(subcommand)]
+        command: BroadcastsCommand,
+    },
+    /// Report uptime from the reachability probes in --store, no connection to the server is
+    /// made unless --monitor
+    Uptime {
+        /// Report over this period, e.g. 7d or 12h
+        #[arg(long, value_name = "7d", default_value = "7d")]
+        since: humantime::Duration,
+
+        /// Probe the server every minute and record uptime to --store until interrupted
+        #[arg(long)]
+        monitor: bool,
+    },
+    /// Player, memory and CPU samples kept in --store
+    Metrics {
+        #[command(subcommand)]
+        command: MetricsCommand,
+    },
+    /// Player count, memory and CPU over a period from --store, no connection to the server is
+    /// made
+    Stats {
+        /// Over this period, e.g. 24h
+        #[arg(long, value_name = "24h", default_value = "24h")]
+        since: humantime::Duration,
+
+        /// Draw sparklines of the samples
+        #[arg(long)]
+        graph: bool,
+    },
+    /// Replace this binary with the latest GitHub release after verifying its checksum and
+    /// signature
+    SelfUpdate {
+        /// Only check whether a newer release exists
+        #[arg(long)]
+        check: bool,
+
+        /// Minisign public key the release must be signed with, instead of the built-in one
+        #[arg(long = "public-key", value_name = "KEY")]
+        public_key: Option<String>,
+
+        /// Install without a public key, only checking the release's checksum
+        #[arg(long, conflicts_with = "public_key")]
+        insecure: bool,
+    },
+    /// Write SHA256SUMS and a manifest.json with the checksum of every release binary, for
+    /// package managers and self-update
+    Dist {
+        /// Release binaries named like palworldcli-x86_64-linux
+        #[arg(required = true)]
+        artifacts: Vec<std::path::PathBuf>,
+
+        /// Directory to write the metadata to
+        #[arg(long, value_name = "DIR", default_value = ".")]
+        out: std::path::PathBuf,
+
+        /// URL the binaries are downloaded from, adds a url to every artifact
+        #[arg(long = "base-url", value_name = "URL")]
+        base_url: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum PalguardCommand {
+    /// Allow a player on the whitelist
+    WhitelistAdd { steamid: String },
+    /// Remove a player from the whitelist
+    WhitelistRemove { steamid: String },
+    /// Give an online player an item by name or ID, e.g. "refined ingot" or StealIngot
+    Give {
+        steamid: String,
+
+        item: String,
+
+        /// Number of items
+        #[arg(long, default_value_t = 1)]
+        count: u32,
+
+        /// Send ITEM as is, for IDs missing from the item catalog
+        #[arg(long)]
+        id: bool,
+    },
+    /// Spawn a Pal by name or ID next to an online player, e.g. Lamball or SheepBall
+    Spawn {
+        steamid: String,
+
+        species: String,
+
+        /// Level of the Pal
+        #[arg(long, default_value_t = 1)]
+        level: u32,
+    },
+    /// Print where an online player is
+    Getpos { steamid: String },
+    /// Move an online player to coordinates, as printed by getpos
+    #[command(allow_negative_numbers = true)]
+    Teleport {
+        steamid: String,
+        x: f64,
+        y: f64,
+        z: f64,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum LocationsCommand {
+    /// List the named places
+    List,
+    /// Name a place, replacing a place with the same name
+    #[command(allow_negative_numbers = true)]
+    Set {
+        name: String,
+        x: f64,
+        y: f64,
+        z: f64,
+    },
+    /// Remove a named place
+    Remove { name: String },
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    /// Print the settings that differ between two servers. Exits 1 if any differ
+    Diff {
+        /// PalWorldSettings.ini file, or a host to read the settings of --world from over SSH
+        /// (--ssh-password, --ssh-port)
+        a: String,
+
+        /// The server compared with the first, like `a`
+        b: String,
+
+        /// Setting meant to differ, like ServerName or PublicPort, may be given several times
+        #[arg(long, value_name = "KEY")]
+        ignore: Vec<String>,
+    },
+    /// Check settings against a template of required values and allowed ranges. Exits 1 if
+    /// any setting breaks its rule and wasn't fixed
+    Check {
+        /// JSON template, like {"ExpRate": {"value": "1.000000"}, "ServerPlayerMaxNum":
+        /// {"min": 4, "max": 32}, "DeathPenalty": {"one_of": ["None", "Item"]}}
+        template: std::path::PathBuf,
+
+        /// PalWorldSettings.ini file, or a host to read the settings of --world from over SSH
+        source: String,
+
+        /// Write the fixes, they take effect when the server restarts
+        #[arg(long)]
+        fix: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ProfilesCommand {
+    /// Encrypt files in place, asking for a new passphrase unless PALWORLD_PASSPHRASE is set
+    Encrypt {
+        #[arg(required = true)]
+        files: Vec<std::path::PathBuf>,
+    },
+    /// Decrypt files in place
+    Decrypt {
+        #[arg(required = true)]
+        files: Vec<std::path::PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ModCommand {
+    /// Warn an online player, kicking or banning them once they collected enough strikes
+    Warn {
+        /// Name, name prefix, UID or SteamID of the player
+        player: String,
+
+        reason: String,
+
+        /// Kick at this many strikes
+        #[arg(long = "kick-at", default_value_t = 3)]
+        kick_at: usize,
+
+        /// Ban at this many strikes
+        #[arg(long = "ban-at", default_value_t = 5)]
+        ban_at: usize,
+    },
+    /// The strikes of a player, no connection to the server is made
+    History { steamid: String },
+    /// Export bans, warnings and whitelist entries of --store as JSON or CSV, no connection
+    /// to the server is made
+    Export {
+        /// File to write, stdout if not given
+        #[arg(long)]
+        output: Option<std::path::PathBuf>,
+
+        /// json or csv, from the extension of --output if not given, else json
+        #[arg(long)]
+        format: Option<moddata::Format>,
+    },
+    /// Import bans, warnings and whitelist entries into --store from JSON or CSV, no
+    /// connection to the server is made
+    Import {
+        path: std::path::PathBuf,
+
+        /// json or csv, from the extension of the file if not given
+        #[arg(long)]
+        format: Option<moddata::Format>,
+
+        /// Records conflicting with --store: ours keeps them, theirs replaces them, union
+        /// keeps both
+        #[arg(long, default_value_t = MergeStrategy::Union)]
+        strategy: MergeStrategy,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum MetricsCommand {
+    /// Export samples as CSV, no connection to the server is made
+    Export {
+        /// Over this period, e.g. 7d or 12h
+        #[arg(long, value_name = "7d", default_value = "7d")]
+        since: humantime::Duration,
+
+        /// Average the samples over this step
+        #[arg(long, value_name = "5m", default_value = "5m")]
+        step: humantime::Duration,
+
+        /// Write CSV, the default without --json
+        #[arg(long, conflicts_with = "json")]
+        csv: bool,
+    },
+    /// Sample players, memory and CPU into --store until interrupted, every minute while
+    /// players are online and up to every 5 minutes otherwise
+    Monitor,
+}
+
+#[derive(Subcommand, Debug)]
+enum BroadcastsCommand {
+    /// The most recent broadcasts with when and by what they were sent
+    History {
+        /// Number of broadcasts to print
+        #[arg(long, default_value_t = 20)]
+        count: usize,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum SavesCommand {
+    /// List guilds with their members and base camps
+    Guilds {
+        /// Path to the world's Level.sav
+        level: std::path::PathBuf,
+
+        /// Only list guilds with more bases than this
+        #[arg(long = "max-bases", value_name = "N")]
+        max_bases: Option<usize>,
+    },
+    /// Plan removing bases of guilds nobody played in for a while, using the last seen
+    /// times from --store and the save. Nothing is deleted
+    Cleanup {
+        /// Path to the world's Level.sav
+        level: std::path::PathBuf,
+
+        /// Guilds count as abandoned after this long without any member online
+        #[arg(long, value_name = "30d", default_value = "30d")]
+        inactive: humantime::Duration,
+    },
+    /// Leaderboard of captured Pals, Pals owned and their levels per player
+    Stats {
+        /// World directory containing Level.sav and Players/
+        world: std::path::PathBuf,
+
+        /// Only list this many players
+        #[arg(long, value_name = "10")]
+        top: Option<usize>,
+    },
+    /// In-game day and time when the world was last saved
+    Time {
+        /// Path to the world's Level.sav
+        level: std::path::PathBuf,
+    },
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> std::process::ExitCode {
+    match run().await {
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{} {e:?}", style::stderr::error("Error:"));
+            std::process::ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run() -> Result<()> {
     let args = Args::parse();
 
     // Initialize the log before we do anything else
-    initialize_log(args.log_level_verbosity)?;
+    initialize_log(args.log_level_verbosity.clone())?;
+    i18n::init(args.lang.unwrap_or_else(Lang::detect));
+    style::init(args.color);
+
+    if let Some(Action::Saves { command }) = &args.action {
+        return run_saves(command, &args.store, args.json);
+    }
+    if let Some(Action::NextRuns { expression, count }) = &args.action {
+        return print_next_runs(expression, args.timezone, *count, args.json);
+    }
+    if let Some(Action::Validate {
+        worlds,
+        routes,
+        cron,
+    }) = &args.action
+    {
+        return run_validate(worlds.as_deref(), routes.as_deref(), cron, args.json);
+    }
+    if let Some(Action::Broadcasts { command }) = &args.action {
+        return run_broadcasts(command, &args.store, args.json);
+    }
+    if let Some(Action::Uptime {
+        since,
+        monitor: false,
+    }) = &args.action
+    {
+        let store = SessionStore::open(&args.store)?;
+        let now = std::time::SystemTime::now();
+        let start = now - **since;
+        let report = store.uptime_report(start..now)?;
+        return print_uptime(&report, start, now, args.json);
+    }
+    if let Some(Action::Metrics {
+        command: MetricsCommand::Export { since, step, .. },
+    }) = &args.action
+    {
+        let store = SessionStore::open(&args.store)?;
+        let now = std::time::SystemTime::now();
+        let samples = store.metrics_between(now - **since, now, **step)?;
+        match args.json {
+            true => println!("{}", serde_json::to_string(&samples)?),
+            false => metrics::write_csv(&samples, std::io::stdout())?,
+        }
+        return Ok(());
+    }
+    if let Some(Action::Stats { since, graph }) = &args.action {
+        let store = SessionStore::open(&args.store)?;
+        let now = std::time::SystemTime::now();
+        // One column per step, at least a second apart.
+        let step = std::time::Duration::from_secs((since.as_secs() / GRAPH_WIDTH).max(1));
+        let samples = store.metrics_between(now - **since, now, step)?;
+        match args.json {
+            true => println!("{}", serde_json::to_string(&samples)?),
+            false => print_stats(&samples, *graph),
+        }
+        return Ok(());
+    }
+    if let Some(Action::Mod {
+        command: ModCommand::History { steamid },
+    }) = &args.action
+    {
+        return run_mod_history(steamid, &args.store, args.json);
+    }
+    if let Some(Action::Mod {
+        command: ModCommand::Export { output, format },
+    }) = &args.action
+    {
+        return run_mod_export(output.as_deref(), *format, &args.store);
+    }
+    if let Some(Action::Mod {
+        command:
+            ModCommand::Import {
+                path,
+                format,
+                strategy,
+            },
+    }) = &args.action
+    {
+        return run_mod_import(path, *format, *strategy, &args.store, args.json);
+    }
+    if let Some(Action::Locations { command }) = &args.action {
+        return run_locations(command, &args.locations, args.json);
+    }
+    if let Some(Action::Profiles { command }) = &args.action {
+        return run_profiles(command);
+    }
+    if let Some(Action::Items { query }) = &args.action {
+        return run_items(query.as_deref(), args.json);
+    }
+    if let Some(Action::Pals { query, element }) = &args.action {
+        return run_pals(query.as_deref(), *element, args.json);
+    }
+    if let Some(Action::Dist {
+        artifacts,
+        out,
+        base_url,
+    }) = &args.action
+    {
+        let version = update::current_version();
+        for path in dist::write(artifacts, out, &version, base_url.as_deref())? {
+            println!("{}", path.display());
+        }
+        return Ok(());
+    }
+    if let Some(Action::SelfUpdate {
+        check,
+        public_key,
+        insecure,
+    }) = &args.action
+    {
+        let public_key = public_key.as_deref().or(update::RELEASE_PUBLIC_KEY);
+        if !check && !insecure && public_key.is_none() {
+            anyhow::bail!(tr!("update-no-key"));
+        }
+        return self_update(*check, public_key, args.json).await;
+    }
+
+    let world = match (&args.worlds, &args.world) {
+        (Some(path), Some(name)) => Some(load_world(path, name)?),
+        _ => None,
+    };
+    let world_or_default = || {
+        world.clone().unwrap_or_else(|| {
+            let mut world = WorldProfile::new("palworld");
+            world.sudo = args.username.as_deref().is_some_and(|user| user != "root");
+            world
+        })
+    };
+    if let Some(Action::Config {
+        command: ConfigCommand::Diff { a, b, ignore },
+    }) = &args.action
+    {
+        let world = world_or_default();
+        let (settings_a, settings_b) = tokio::try_join!(
+            read_world_settings(a, &world, &args),
+            read_world_settings(b, &world, &args)
+        )?;
+        let mut changes = config::diff(&settings_a, &settings_b).redacted();
+        changes.ignore(ignore);
+        match args.json {
+            true => println!("{}", serde_json::to_string(&changes)?),
+            false => print_settings_diff(&changes, a, b),
+        }
+        std::process::exit(if changes.is_empty() { 0 } else { 1 });
+    }
+    if let Some(Action::Config {
+        command:
+            ConfigCommand::Check {
+                template,
+                source,
+                fix,
+            },
+    }) = &args.action
+    {
+        let template: config::SettingsTemplate = serde_json::from_str(
+            &std::fs::read_to_string(template)
+                .with_context(|| format!("Failed to read {}", template.display()))?,
+        )
+        .with_context(|| format!("Invalid template {}", template.display()))?;
+        let world = world_or_default();
+        let violations = match std::path::Path::new(source).is_file() {
+            true => {
+                let mut settings = read_world_settings(source, &world, &args).await?;
+                let violations = config::enforce(&template, &settings);
+                if *fix && settings.apply(&violations) > 0 {
+                    std::fs::write(source, settings.to_ini())
+                        .with_context(|| format!("Failed to write {source}"))?;
+                }
+                violations
+            }
+            false => world
+                .enforce_settings(&settings_connection(source, &args)?, &template, *fix)
+                .await
+                .with_context(|| format!("Failed to check the settings of {source}"))?,
+        };
+        let violations: Vec<_> = violations.iter().map(|v| v.redacted()).collect();
+        match args.json {
+            true => println!("{}", serde_json::to_string(&violations)?),
+            false => print_violations(&violations, *fix),
+        }
+        let unfixed = violations.iter().any(|v| !*fix || v.fix.is_none());
+        std::process::exit(if unfixed { 1 } else { 0 });
+    }
+
+    if !args.yes {
+        let target = match &world {
+            Some(world) => world.name.as_str(),
+            None => args.server_ip.as_deref().unwrap_or("localhost"),
+        };
+        confirm_destructive(&destructive_operations(&args), target)?;
+    }
 
     // Setup server credentials
     let server_ip = args.server_ip.unwrap_or("localhost".to_string());
-    let server_port = args.server_port.unwrap_or(DEFAULT_SOURCE_PORT);
+    let server_port = args
+        .server_port
+        .or(world.as_ref().map(|world| world.rcon_port))
+        .unwrap_or(DEFAULT_SOURCE_PORT);
+
+    if let Some(Action::Wake {
+        mac,
+        broadcast_addr,
+        within,
+    }) = &args.action
+    {
+        wol::wake(mac, *broadcast_addr).await?;
+        let waited = wol::wait_until_reachable(&server_ip, server_port, **within).await?;
+        let address = format!("{server_ip}:{server_port}");
+        let secs = waited.as_secs();
+        println!("{}", tr!("wake-reachable", address = address, secs = secs));
+        return Ok(());
+    }
+
+    let password = match (args.password, &args.password_file) {
+        (Some(password), _) => Secret::new(password),
+        (None, Some(path)) => Secret::new(read_password_file(path)?),
+        // Subcommands lift the requirement, those connecting still need a password.
+        (None, None) => anyhow::bail!(tr!("password-required")),
+    };
+    let password = password.expose().as_str();
 
     // Connect to the server
-    let server = PalworldRCON::new(&server_ip, server_port, &args.password);
+    let mut server = PalworldRCON::new(&server_ip, server_port, password);
+    // Kept alive until exiting, dropping it stops the proxy.
+    let _chaos = match &args.chaos {
+        Some(faults) => {
+            let config = FaultConfig::parse(faults)?;
+            let proxy = ChaosProxy::start(format!("{server_ip}:{server_port}"), config).await?;
+            server = proxy.wrap(&server);
+            Some(proxy)
+        }
+        None => None,
+    };
+    if let Some(Action::Palguard { command }) = &args.action {
+        return run_palguard(&server, command, &args.store, args.json).await;
+    }
+    if let Some(Action::Mod {
+        command:
+            ModCommand::Warn {
+                player,
+                reason,
+                kick_at,
+                ban_at,
+            },
+    }) = &args.action
+    {
+        let player = server.find_player(player).await?;
+        let store = Arc::new(SessionStore::open(&args.store)?);
+        let mut ledger = ModerationLedger::new(server.clone(), store);
+        ledger.policy = EscalationPolicy {
+            kick_at: Some(*kick_at),
+            ban_at: Some(*ban_at),
+        };
+        let warn = ledger.warn(&player.steamid, reason);
+        let warning = trace::traced(TraceContext::new(manual_trigger()), warn).await?;
+        match args.json {
+            true => println!("{}", serde_json::to_string(&warning.strike)?),
+            false => {
+                let message = tr!(
+                    "mod-warned",
+                    count = warning.count,
+                    name = player.name.as_str(),
+                    steamid = player.steamid.as_str(),
+                    action = warning.strike.action.to_string()
+                );
+                println!("{}", style::outcome(warning.enforced, &message));
+            }
+        }
+        return Ok(());
+    }
+    if let Some(Action::Healthcheck {
+        min_version,
+        max_players,
+    }) = &args.action
+    {
+        let mut check = HealthCheck::new(*args.timeout);
+        check.min_version = min_version.clone();
+        check.max_players = *max_players;
+        let report = check.run(&server).await;
+        if args.json {
+            println!("{}", serde_json::to_string(&report)?);
+        } else if report.healthy() {
+            let ms = report.latency.as_millis() as u64;
+            println!("{}", style::success(&tr!("health-ok", ms = ms)));
+        } else {
+            let failures = report.failures.join(", ");
+            println!(
+                "{}",
+                style::error(&tr!("health-failed", failures = failures))
+            );
+        }
+        std::process::exit(if report.healthy() { 0 } else { 1 });
+    }
+    if let Some(Action::Status) = &args.action {
+        let check = HealthCheck::new(*args.timeout);
+        // The machine running this may not be the server, like a sidecar, so its own memory
+        // isn't reported.
+        let mem_info = match args.memory_ssh || args.username.is_some() {
+            true => ssh::PalworldConnection::new(
+                format!("{server_ip}:{}", args.ssh_port),
+                args.username.clone().unwrap_or("root".to_string()),
+                args.ssh_password.as_deref().unwrap_or(password),
+            )
+            .get_memory_info()
+            .await
+            .map_err(|e| log::warn!("Failed to get memory: {e}"))
+            .ok(),
+            false => None,
+        };
+        let status = ServerStatus::collect(&server, &check, mem_info.as_ref()).await;
+        println!("{}", serde_json::to_string(&status)?);
+        return Ok(());
+    }
+    if let Some(Action::Uptime { monitor: true, .. }) = &args.action {
+        let store = Arc::new(SessionStore::open(&args.store)?);
+        UptimeMonitor::new(server.clone(), store).spawn();
+        tokio::signal::ctrl_c().await?;
+        log::info!("Stopping the uptime monitor");
+        return Ok(());
+    }
+    if let Some(Action::Metrics {
+        command: MetricsCommand::Monitor,
+    }) = &args.action
+    {
+        let store = Arc::new(SessionStore::open(&args.store)?);
+        MetricsSampler::new(server.clone(), store)?.spawn();
+        tokio::signal::ctrl_c().await?;
+        log::info!("Stopping the metrics sampler");
+        return Ok(());
+    }
+    if let Some(Action::Ping { game, game_port }) = &args.action {
+        let mut check = HealthCheck::new(*args.timeout);
+        check.game_port = game.then_some(*game_port);
+        let report = check.run(&server).await;
+        if args.json {
+            println!("{}", serde_json::to_string(&report)?);
+        } else {
+            print_ping(&report);
+        }
+        std::process::exit(if report.healthy() { 0 } else { 1 });
+    }
+    if let Some(Action::Batch { commands, within }) = &args.action {
+        let deadline = std::time::Instant::now() + **within;
+        let results = server.send_commands(commands, deadline).await;
+        match args.json {
+            true => println!("{}", serde_json::to_string(&results)?),
+            false => print_batch(&results),
+        }
+        let done = results.iter().all(|result| result.done());
+        std::process::exit(if done { 0 } else { 1 });
+    }
+    if let Some(Action::Diagnose { game_port, no_ssh }) = &args.action {
+        let mut diagnostics = Diagnostics::new();
+        diagnostics.timeout = *args.timeout;
+        diagnostics.game_port = Some(*game_port);
+        diagnostics.ssh_port = (!no_ssh).then_some(args.ssh_port);
+        let report = diagnostics.run(&server).await;
+        if args.json {
+            println!("{}", serde_json::to_string(&report)?);
+        } else {
+            print_diagnostics(&report);
+        }
+        std::process::exit(if report.passed() { 0 } else { 1 });
+    }
+    if let Some(Action::Tp { player, location }) = &args.action {
+        let position = load_locations(&args.locations)?.resolve(location)?;
+        let player = server.find_player(player).await?;
+        let palguard::Position { x, y, z } = position;
+        let result = server.teleport_player_to(&player.steamid, x, y, z).await?;
+        match args.json {
+            true => println!("{}", json!({ "result": result, "position": position })),
+            false => {
+                let message = tr!(
+                    "teleported",
+                    name = player.name.as_str(),
+                    location = location.as_str(),
+                    result = result.to_string()
+                );
+                println!("{}", style::outcome(result, &message));
+            }
+        }
+        return Ok(());
+    }
+    let dry_run = args.dry_run.then(DryRun::new);
+    let ssh_connection = || {
+        let mut connection = ssh::PalworldConnection::new(
+            format!("{server_ip}:{}", args.ssh_port),
+            args.username.clone().unwrap_or("root".to_string()),
+            args.ssh_password.as_deref().unwrap_or(password),
+        );
+        connection.dry_run = dry_run.clone();
+        connection
+    };
+    // JSON output stays machine readable without progress bars.
+    let progress_bar = || match args.json {
+        true => None,
+        false => Some(Arc::new(ProgressBar::new()) as Arc<dyn Progress>),
+    };
+    if let Some(Action::SupportBundle {
+        output,
+        since,
+        step,
+        log_lines,
+        no_ssh,
+    }) = &args.action
+    {
+        let mut diagnostics = Diagnostics::new();
+        diagnostics.timeout = *args.timeout;
+        diagnostics.ssh_port = (!no_ssh).then_some(args.ssh_port);
+        let mut bundle = SupportBundle::new();
+        bundle.collect_server(&server, &diagnostics).await;
+        if !no_ssh {
+            bundle
+                .collect_host(&ssh_connection(), &world_or_default(), *log_lines)
+                .await;
+        }
+        // Opening a missing store would create an empty one.
+        if std::path::Path::new(&args.store).exists() {
+            let store = SessionStore::open(&args.store);
+            if let Some(store) = bundle.record("metrics", store) {
+                let now = std::time::SystemTime::now();
+                bundle.collect_metrics(&store, now - **since, **step);
+            }
+        }
+        bundle.write(std::fs::File::create(output)?)?;
+        let path = output.display().to_string();
+        match args.json {
+            true => println!(
+                "{}",
+                json!({ "path": path, "files": bundle.entries.len(), "errors": bundle.errors })
+            ),
+            false => {
+                println!(
+                    "{}",
+                    style::success(&tr!(
+                        "support-bundle-written",
+                        path = path.as_str(),
+                        count = bundle.entries.len()
+                    ))
+                );
+                for error in &bundle.errors {
+                    println!(
+                        "  {}",
+                        style::warning(&tr!("support-bundle-missing", error = error.as_str()))
+                    );
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    // Password rotation, runs first so everything after uses the new password
+    if let Some(path) = &args.rotate_password {
+        let new_password = Secret::new(read_new_password(path)?);
+        let new_password = new_password.expose();
+        let ssh_password = args.ssh_password.as_deref().unwrap_or(password);
+        let username = args.username.clone().unwrap_or("root".to_string());
+        let mut connection = ssh::PalworldConnection::new(
+            format!("{server_ip}:{}", args.ssh_port),
+            &username,
+            ssh_password,
+        );
+        connection.dry_run = dry_run.clone();
+        let mut rotation = match &world {
+            Some(world) => PasswordRotation::for_world(connection, world),
+            None => PasswordRotation::new(connection),
+        };
+        rotation.sudo = username != "root";
+        let rotated = rotation.rotate_rcon_password(&server, new_password).await?;
+        // Nothing changed in a dry run, the current password still works.
+        if !args.dry_run {
+            server = rotated;
+            if let Some(path) = &args.password_file {
+                write_profile(path, format!("{new_password}\n").as_bytes())?;
+                let path = path.display().to_string();
+                println!(
+                    "{}",
+                    style::success(&tr!("password-rotated-file", path = path))
+                );
+            } else {
+                println!("{}", style::success(&tr!("password-rotated")));
+            }
+        }
+    }
 
     // Player info
     if args.player_info {
-        let player_info = server.get_player_info().await?;
+        let player_info = match server.get_player_info().await {
+            Ok(players) => players,
+            Err(e) => match e.downcast_ref::<rcon::RconError>() {
+                Some(rcon::RconError::Truncated(players)) => {
+                    log::warn!("{e}, the list is incomplete");
+                    players.clone()
+                }
+                _ => return Err(e),
+            },
+        };
         if args.json {
             let output = serde_json::to_string(&player_info)?;
             println!("{output}");
         } else {
-            println!("Got player info: found {} online!", player_info.len());
-            println!("Name\tUID\tSteamID");
-            for player in &player_info {
-                println!("{}\t{}\t{}", player.name, player.uid, player.steamid);
-            }
+            println!("{}", tr!("players-found", count = player_info.len()));
+            let header = [tr!("column-name"), tr!("column-uid"), tr!("column-steamid")];
+            let rows: Vec<Vec<String>> = player_info
+                .iter()
+                .map(|player| {
+                    vec![
+                        player.name.clone(),
+                        player.uid.clone(),
+                        player.steamid.clone(),
+                    ]
+                })
+                .collect();
+            println!("{}", style::table(&header, &rows));
         }
     }
     // Server version
@@ -108,33 +1262,74 @@ async fn main() -> Result<()> {
     }
     // save the server
     if args.save {
-        println!("Saved: {}", server.save().await?);
+        let saved = server.save().await?;
+        let message = tr!("saved", result = saved.to_string());
+        println!("{}", style::outcome(saved, &message));
     }
     // Shutdown the server
-    match args.shutdown {
-        Some(delay) => {
-            let success = server
-                .shutdown(Some(std::time::Duration::from_secs(delay)), "")
-                .await?;
-            println!("Shutdown: {success}");
-        }
-        None => {}
+    if let Some(delay) = args.shutdown {
+        let success = server
+            .shutdown(Some(std::time::Duration::from_secs(delay)), "")
+            .await?;
+        let message = tr!("shutdown-result", result = success.to_string());
+        println!("{}", style::outcome(success, &message));
     }
     // Broadcast message
-    match args.broadcast {
-        Some(msg) => {
-            let result = server.broadcast(msg, args.replace_broadcast_space).await?;
-            println!("{result}");
+    if let Some(msg) = args.broadcast {
+        match SessionStore::open(&args.store) {
+            Ok(store) => server.broadcast_hook = Some(Arc::new(store).broadcast_hook()),
+            Err(e) => log::warn!("Broadcast isn't recorded, failed to open the store: {e}"),
         }
-        None => {}
+        let context = TraceContext::new(manual_trigger());
+        let broadcast = server.broadcast(msg, args.replace_broadcast_space);
+        let result = trace::traced(context, broadcast).await?;
+        println!("{result}");
     }
     // Send a command
-    match args.command {
-        Some(cmd) => {
-            let result = server.send_command(cmd.as_str()).await?;
-            println!("{result}");
+    if let Some(cmd) = args.command {
+        let registry = match &args.commands {
+            Some(path) => load_commands(path)?,
+            None => CommandRegistry::new(),
+        };
+        match (args.raw_json, registry.contains(&cmd)) {
+            (true, true) => {
+                let value = registry.execute(&server, &cmd).await?;
+                println!("{}", serde_json::to_string(&value)?);
+            }
+            (true, false) => {
+                let value = ParserKind::Text.parse(&server.send_command(cmd.as_str()).await?)?;
+                println!("{}", serde_json::to_string(&value)?);
+            }
+            (false, _) => {
+                let result = server.send_command(cmd.as_str()).await?;
+                println!("{result}");
+            }
         }
-        None => {}
+    }
+    // Kick or ban a player, ambiguous names fail listing the matching players
+    if let Some(query) = &args.kick {
+        let player = server.find_player(query).await?;
+        let kicked = server.kick_player(&player.steamid).await?;
+        let (name, steamid) = (player.name.as_str(), player.steamid.as_str());
+        let message = tr!(
+            "kicked",
+            name = name,
+            steamid = steamid,
+            result = kicked.to_string()
+        );
+        println!("{}", style::outcome(kicked, &message));
+    }
+    if let Some(query) = &args.ban {
+        let player = server.find_player(query).await?;
+        let banned = server.ban_player(&player.steamid).await?;
+        let (name, steamid) = (player.name.as_str(), player.steamid.as_str());
+        let message = tr!(
+            "banned",
+            name = name,
+            steamid = steamid,
+            result = banned.to_string()
+        );
+        println!("{}", style::outcome(banned, &message));
     }
     // Get memory usage
     if args.memory {
@@ -142,25 +1337,1376 @@ async fn main() -> Result<()> {
         if args.json {
             println!("{}", serde_json::to_string(&mem_info)?);
         } else {
-            println!("{mem_info:#?}");
+            println!("{mem_info}");
         }
     } else if args.memory_ssh {
         // Dual purpose server_port here. We are going to grab it again and set to 22 (SSH default port now)
         let server_port = args.server_port.unwrap_or(22);
         let ssh_hostname = format!("{}:{server_port}", &server_ip);
-        let username = args.username.or(Some("root".to_string())).unwrap();
-        let connection = ssh::PalworldConnection::new(ssh_hostname, username, &args.password);
+        let username = args.username.clone().unwrap_or("root".to_string());
+        let connection = ssh::PalworldConnection::new(ssh_hostname, username, password);
         let mem_info = connection.get_memory_info().await?;
         if args.json {
             println!("{}", serde_json::to_string(&mem_info)?);
         } else {
-            println!("{mem_info:#?}");
+            println!("{mem_info}");
+        }
+    }
+    // Provision a new server
+    if args.install {
+        let mut connection = ssh_connection();
+        connection.progress = progress_bar();
+        let mut options = world
+            .as_ref()
+            .map_or_else(InstallOptions::new, WorldProfile::install_options);
+        options.sudo = connection.username != "root";
+        options.rcon_allowed_from = args.rcon_allowed_from.clone();
+        let installation = provision::install(&connection, &options).await?;
+        drop(connection);
+        if args.json {
+            println!("{}", serde_json::to_string(&installation)?);
+        } else {
+            let (dir, service) = (&installation.install_dir, &installation.service);
+            println!(
+                "{}",
+                style::success(&tr!(
+                    "installed",
+                    dir = dir.as_str(),
+                    service = service.as_str()
+                ))
+            );
+            let (port, password) = (installation.rcon_port, &installation.rcon_password);
+            println!(
+                "{}",
+                tr!(
+                    "installed-rcon",
+                    port = port,
+                    password = password.expose().as_str()
+                )
+            );
+        }
+    }
+    // Back up the world's saves
+    if let Some(dest_dir) = &args.backup {
+        let world = world_or_default();
+        if args.dry_run {
+            log::info!("Dry run, not saving before the backup");
+        } else if let Err(e) = server.save().await {
+            log::warn!("Failed to save before the backup: {e}");
+        }
+        let mut connection = ssh_connection();
+        connection.progress = progress_bar();
+        let archive = world.backup(&connection, dest_dir).await?;
+        drop(connection);
+        let name = world.name.as_str();
+        println!(
+            "{}",
+            style::success(&tr!("backed-up", world = name, archive = archive.as_str()))
+        );
+    }
+    // Control the world's service
+    if let Some(action) = args.service {
+        let world = world_or_default();
+        let started = !matches!(action, ssh::ServiceAction::Stop);
+        let verify = args.wait_ready.filter(|_| started && !args.dry_run);
+        // The version before the restart, to check the one after against.
+        let before = match (verify, action) {
+            (Some(_), ssh::ServiceAction::Restart) => server.get_version().await.ok(),
+            _ => None,
+        };
+        let result = world.service(&ssh_connection(), action).await?;
+        if !result.success() {
+            anyhow::bail!(
+                "{action:?} {} failed: {}",
+                world.service,
+                result.stderr.trim()
+            );
+        }
+        if !args.dry_run {
+            let action = format!("{action:?}").to_lowercase();
+            let service = world.service.as_str();
+            println!(
+                "{}",
+                style::success(&tr!("service-done", action = action, service = service))
+            );
+        }
+        if let Some(timeout) = verify {
+            let mut verification = StartupVerification::new(*timeout);
+            verification.version = match (before, args.expect_update) {
+                (Some(before), true) => VersionCheck::Changed(before),
+                (Some(before), false) => VersionCheck::Unchanged(before),
+                (None, _) => VersionCheck::Any,
+            };
+            verification.broadcast = args.verify_broadcast.clone();
+            let verified = verification.run(&server).await?;
+            let message = tr!(
+                "server-verified",
+                version = verified.version.as_str(),
+                secs = verified.waited.as_secs(),
+                players = verified.players.unwrap_or_default()
+            );
+            println!("{}", style::success(&message));
+        }
+    }
+    // Move the world to another host
+    if let Some(destination) = &args.migrate_to {
+        let world = world_or_default();
+        let mut source = ssh_connection();
+        source.progress = progress_bar();
+        source.transfer.bandwidth_limit = args.bandwidth_limit.map(|kib| kib * ByteSize::KIB);
+        let mut destination = ssh::PalworldConnection::new(
+            destination,
+            source.username.clone(),
+            args.migrate_password
+                .as_deref()
+                .unwrap_or(source.password.expose()),
+        );
+        destination.transfer = source.transfer.clone();
+        destination.dry_run = dry_run.clone();
+        let mut options = MigrationOptions::new();
+        options.install = !args.skip_install;
+        let migration = migrate::migrate_with(&source, &destination, &world, &options).await?;
+        drop(source);
+        if args.json {
+            println!("{}", serde_json::to_string(&migration)?);
+        } else {
+            let moved = tr!(
+                "migrated",
+                world = world.name.as_str(),
+                host = destination.hostname.as_str(),
+                bytes = migration.bytes,
+                version = migration.server_version.to_string(),
+            );
+            println!("{}", style::success(&moved));
+            println!("{}", tr!("cutover-checklist"));
+            for step in &migration.checklist {
+                println!("  [ ] {step}");
+            }
+        }
+    }
+    // Playtime export
+    if let Some(since) = args.export_playtime {
+        let store = SessionStore::open(&args.store)?;
+        let now = std::time::SystemTime::now();
+        let rows = store.monthly_playtime(now - *since..now, args.timezone, &args.exempt)?;
+        if args.json {
+            println!("{}", serde_json::to_string(&rows)?);
+        } else {
+            billing::write_csv(&rows, std::io::stdout())?;
+        }
+    }
+    if let Some(dry_run) = &dry_run {
+        if args.json {
+            println!("{}", serde_json::to_string(&dry_run.actions())?);
+        } else {
+            println!("{}", style::warning(&tr!("dry-run")));
+            for action in dry_run.actions() {
+                println!("  {action}");
+            }
         }
     }
     log::debug!("Done.");
     Ok(())
 }
 
+/// Operations of `args` that lose player progress or lock players out, every destructive
+/// option is listed here so it is confirmed before anything runs. Raw commands count unless
+/// they are known to be read-only. With --dry-run the operations over SSH are only recorded
+/// and left out, RCON commands are still sent.
+fn destructive_operations(args: &Args) -> Vec<String> {
+    let registry = args
+        .commands
+        .as_deref()
+        .and_then(|path| load_commands(path).ok())
+        .unwrap_or_default();
+    let destructive =
+        |command: &str| !command::is_read_only(command) && !registry.is_read_only(command);
+    let mut operations = Vec::new();
+    if let Some(delay) = args.shutdown {
+        operations.push(tr!("operation-shutdown", secs = delay));
+    }
+    if let Some(player) = &args.kick {
+        operations.push(tr!("operation-kick", player = player.as_str()));
+    }
+    if let Some(player) = &args.ban {
+        operations.push(tr!("operation-ban", player = player.as_str()));
+    }
+    if let Some(command) = args
+        .command
+        .as_deref()
+        .filter(|command| destructive(command))
+    {
+        operations.push(tr!("operation-command", command = command));
+    }
+    if let Some(Action::Palguard {
+        command: PalguardCommand::WhitelistRemove { steamid },
+    }) = &args.action
+    {
+        let command = palguard::WhitelistRemove::new(steamid).encode();
+        operations.push(tr!("operation-command", command = command.as_str()));
+    }
+    if let Some(Action::Batch { commands, .. }) = &args.action {
+        for command in commands {
+            let name = command.split_whitespace().next().unwrap_or_default();
+            let destructive = ["Shutdown", "DoExit", "KickPlayer", "BanPlayer"]
+                .iter()
+                .any(|destructive| destructive.eq_ignore_ascii_case(name));
+            if destructive {
+                operations.push(tr!("operation-command", command = command.as_str()));
+            }
+        }
+    }
+    if args.dry_run {
+        return operations;
+    }
+    match args.service {
+        Some(ssh::ServiceAction::Stop) => operations.push(tr!("operation-service-stop")),
+        Some(ssh::ServiceAction::Restart) => operations.push(tr!("operation-service-restart")),
+        _ => (),
+    }
+    if args.rotate_password.is_some() {
+        operations.push(tr!("operation-rotate-password"));
+    }
+    if let Some(host) = &args.migrate_to {
+        operations.push(tr!("operation-migrate", host = host.as_str()));
+    }
+    operations
+}
+
+/// Asks to confirm `operations`, then to type `target` so they don't run against the wrong
+/// server. Fails without a terminal to ask on, --yes skips asking.
+fn confirm_destructive(operations: &[String], target: &str) -> Result<()> {
+    if operations.is_empty() {
+        return Ok(());
+    }
+    let operations = operations.join(", ");
+    if !std::io::stdin().is_terminal() {
+        let operations = operations.as_str();
+        anyhow::bail!(tr!(
+            "confirm-no-terminal",
+            operations = operations,
+            target = target
+        ));
+    }
+    let ask = |question: String| -> Result<String> {
+        eprint!("{} ", style::stderr::warning(&question));
+        std::io::stderr().flush()?;
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        Ok(answer.trim().to_string())
+    };
+    let operations = operations.as_str();
+    let answer = ask(tr!(
+        "confirm-continue",
+        operations = operations,
+        target = target
+    ))?;
+    if !matches!(answer.to_lowercase().as_str(), "y" | "yes") {
+        anyhow::bail!(tr!("confirm-aborted"));
+    }
+    let answer = ask(tr!("confirm-type-name", target = target))?;
+    if answer != target {
+        let answer = answer.as_str();
+        anyhow::bail!(tr!("confirm-mismatch", answer = answer, target = target));
+    }
+    Ok(())
+}
+
+fn print_next_runs(expression: &str, timezone: Tz, count: usize, json: bool) -> Result<()> {
+    let schedule = Schedule::parse(expression, timezone)?;
+    let mut runs = Vec::new();
+    let mut after = std::time::SystemTime::now();
+    while runs.len() < count {
+        let Some(run) = schedule.next_after(after) else {
+            break;
+        };
+        after = run.into();
+        runs.push(run.to_rfc3339());
+    }
+    if json {
+        println!("{}", serde_json::to_string(&runs)?);
+    } else {
+        println!("{}", tr!("next-runs", schedule = schedule.to_string()));
+        for run in &runs {
+            println!("  {run}");
+        }
+    }
+    Ok(())
+}
+
+/// Settings of `source`, a `PalWorldSettings.ini` file or a host to read the settings of
+/// `world` from over SSH.
+async fn read_world_settings(
+    source: &str,
+    world: &WorldProfile,
+    args: &Args,
+) -> Result<config::WorldSettings> {
+    let path = std::path::Path::new(source);
+    if path.is_file() {
+        let ini =
+            std::fs::read_to_string(path).with_context(|| format!("Failed to read {source}"))?;
+        return config::WorldSettings::parse(&ini).with_context(|| format!("Invalid {source}"));
+    }
+    world
+        .read_settings(&settings_connection(source, args)?)
+        .await
+        .with_context(|| format!("Failed to read the settings of {source}"))
+}
+
+/// SSH connection to the host `source`, with the port of --ssh-port unless it has one.
+fn settings_connection(source: &str, args: &Args) -> Result<ssh::PalworldConnection> {
+    let password = match (&args.ssh_password, &args.password, &args.password_file) {
+        (Some(password), _, _) | (None, Some(password), _) => password.clone(),
+        (None, None, Some(path)) => read_password_file(path)?,
+        (None, None, None) => anyhow::bail!("--ssh-password is needed to reach {source} over SSH"),
+    };
+    let host = match source.contains(':') {
+        true => source.to_string(),
+        false => format!("{source}:{}", args.ssh_port),
+    };
+    Ok(ssh::PalworldConnection::new(
+        host,
+        args.username.clone().unwrap_or("root".to_string()),
+        &password,
+    ))
+}
+
+fn print_settings_diff(changes: &config::SettingsDiff, a: &str, b: &str) {
+    if changes.is_empty() {
+        println!("{}", style::success(&tr!("config-same", a = a, b = b)));
+        return;
+    }
+    let header = [tr!("column-setting"), a.to_string(), b.to_string()];
+    let rows: Vec<Vec<String>> = changes
+        .changes
+        .iter()
+        .map(|change| {
+            let value = |value: &Option<String>| value.clone().unwrap_or("-".to_string());
+            vec![change.key.clone(), value(&change.old), value(&change.new)]
+        })
+        .collect();
+    println!("{}", style::table(&header, &rows));
+    let count = changes.changes.len();
+    println!("{}", style::warning(&tr!("config-differ", count = count)));
+}
+
+fn print_violations(violations: &[config::Violation], fix: bool) {
+    if violations.is_empty() {
+        println!("{}", style::success(&tr!("config-compliant")));
+        return;
+    }
+    for violation in violations {
+        match (fix, &violation.fix) {
+            (true, Some(value)) => println!(
+                "{}",
+                style::success(&tr!(
+                    "config-fixed",
+                    violation = violation.to_string(),
+                    value = value.as_str()
+                ))
+            ),
+            _ => println!("{}", style::warning(&violation.to_string())),
+        }
+    }
+    // Left to fix by hand.
+    let count = violations
+        .iter()
+        .filter(|violation| !fix || violation.fix.is_none())
+        .count();
+    if count > 0 {
+        println!(
+            "{}",
+            style::warning(&tr!("config-violations", count = count))
+        );
+    }
+}
+
+fn run_validate(
+    worlds: Option<&std::path::Path>,
+    routes: Option<&std::path::Path>,
+    cron: &[String],
+    json: bool,
+) -> Result<()> {
+    let read = |path: &std::path::Path| read_profile(path);
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+    if let Some(path) = worlds {
+        diagnostics.extend(validate::worlds(&path.display().to_string(), &read(path)?));
+    }
+    if let Some(path) = routes {
+        let file = path.display().to_string();
+        diagnostics.extend(RoutingConfig::validate(&file, &read(path)?));
+    }
+    diagnostics.extend(
+        cron.iter()
+            .filter_map(|expression| scheduler::validate_cron(expression)),
+    );
+    let errors = diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.level == Level::Error)
+        .count();
+    let warnings = diagnostics.len() - errors;
+    if json {
+        println!("{}", serde_json::to_string(&diagnostics)?);
+    } else {
+        for diagnostic in &diagnostics {
+            let text = diagnostic.to_string();
+            let (level, rest) = text.split_once(':').unwrap_or_default();
+            let level = match diagnostic.level {
+                Level::Error => style::error(level),
+                Level::Warning => style::warning(level),
+            };
+            println!("{level}:{rest}\n");
+        }
+        match warnings {
+            0 if errors == 0 => println!("{}", style::success(&tr!("validate-ok"))),
+            _ if errors == 0 => {
+                println!(
+                    "{}",
+                    style::warning(&tr!("validate-warnings", warnings = warnings))
+                )
+            }
+            _ => (),
+        }
+    }
+    if has_errors(&diagnostics) {
+        anyhow::bail!(tr!("validate-failed", errors = errors, warnings = warnings));
+    }
+    Ok(())
+}
+
+async fn self_update(check: bool, public_key: Option<&str>, json: bool) -> Result<()> {
+    let client = update::client()?;
+    let release = update::latest_release(&client).await?;
+    let current = update::current_version();
+    let available = release.version > current;
+    if json && (check || !available) {
+        let output = json!({
+            "current": current.to_string(),
+            "latest": release.version.to_string(),
+            "update_available": available,
+        });
+        println!("{output}");
+        return Ok(());
+    }
+    let (current_text, latest) = (current.to_string(), release.version.to_string());
+    if !available {
+        println!("{}", tr!("update-latest", version = current_text));
+        return Ok(());
+    }
+    if check {
+        let message = tr!("update-available", latest = latest, current = current_text);
+        println!("{}", style::warning(&message));
+        return Ok(());
+    }
+    update::install(&client, &release, public_key).await?;
+    if json {
+        println!("{}", json!({"updated": true, "version": latest}));
+    } else {
+        let message = tr!("update-installed", current = current_text, latest = latest);
+        println!("{}", style::success(&message));
+    }
+    Ok(())
+}
+
+/// Named places from a JSON file, none if it doesn't exist yet.
+fn load_locations(path: &std::path::Path) -> Result<palguard::Locations> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text)
+            .with_context(|| format!("Failed to read locations from {}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(palguard::Locations::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn run_locations(command: &LocationsCommand, path: &std::path::Path, json: bool) -> Result<()> {
+    let mut locations = load_locations(path)?;
+    let path_name = path.display().to_string();
+    match command {
+        LocationsCommand::List => {
+            if json {
+                println!("{}", serde_json::to_string(&locations)?);
+            } else if locations.places.is_empty() {
+                println!("{}", tr!("locations-empty", path = path_name));
+            } else {
+                let header = [tr!("column-name"), tr!("column-position")];
+                let rows: Vec<Vec<String>> = locations
+                    .places
+                    .iter()
+                    .map(|(name, position)| vec![name.clone(), position.to_string()])
+                    .collect();
+                println!("{}", style::table(&header, &rows));
+            }
+            return Ok(());
+        }
+        LocationsCommand::Set { name, x, y, z } => {
+            let position = palguard::Position::new(*x, *y, *z);
+            locations.set(name, position);
+            let position = position.to_string();
+            let message = tr!(
+                "location-saved",
+                name = name.as_str(),
+                position = position,
+                path = path_name
+            );
+            println!("{}", style::success(&message));
+        }
+        LocationsCommand::Remove { name } => {
+            if locations.remove(name).is_none() {
+                anyhow::bail!("No location named '{name}' in {path_name}");
+            }
+            let message = tr!("location-removed", name = name.as_str(), path = path_name);
+            println!("{}", style::success(&message));
+        }
+    }
+    std::fs::write(path, serde_json::to_string_pretty(&locations)?)?;
+    Ok(())
+}
+
+fn run_items(query: Option<&str>, json: bool) -> Result<()> {
+    let matches: Vec<&items::Item> = match query {
+        Some(query) => items::search(query),
+        None => items::catalog().iter().collect(),
+    };
+    if json {
+        println!("{}", serde_json::to_string(&matches)?);
+        return Ok(());
+    }
+    if matches.is_empty() {
+        println!("{}", tr!("items-empty", query = query.unwrap_or_default()));
+        return Ok(());
+    }
+    let header = [tr!("column-name"), tr!("column-id"), tr!("column-category")];
+    let rows: Vec<Vec<String>> = matches
+        .iter()
+        .map(|item| {
+            vec![
+                item.name.to_string(),
+                item.id.to_string(),
+                item.category.to_string(),
+            ]
+        })
+        .collect();
+    println!("{}", style::table(&header, &rows));
+    Ok(())
+}
+
+fn run_pals(query: Option<&str>, element: Option<pals::Element>, json: bool) -> Result<()> {
+    let matches: Vec<&pals::Pal> = match query {
+        Some(query) => pals::search(query),
+        None => pals::catalog().iter().collect(),
+    }
+    .into_iter()
+    .filter(|pal| element.is_none_or(|element| pal.elements.contains(&element)))
+    .collect();
+    if json {
+        println!("{}", serde_json::to_string(&matches)?);
+        return Ok(());
+    }
+    if matches.is_empty() {
+        println!("{}", tr!("pals-empty", query = query.unwrap_or_default()));
+        return Ok(());
+    }
+    let header = [tr!("column-name"), tr!("column-id"), tr!("column-elements")];
+    let rows: Vec<Vec<String>> = matches
+        .iter()
+        .map(|pal| {
+            let elements: Vec<String> = pal.elements.iter().map(|e| e.to_string()).collect();
+            vec![
+                pal.name.to_string(),
+                pal.id.to_string(),
+                elements.join(", "),
+            ]
+        })
+        .collect();
+    println!("{}", style::table(&header, &rows));
+    Ok(())
+}
+
+async fn run_palguard(
+    server: &PalworldRCON,
+    command: &PalguardCommand,
+    store: &str,
+    json: bool,
+) -> Result<()> {
+    let (message, result) = match command {
+        PalguardCommand::WhitelistAdd { steamid } => {
+            let result = server
+                .execute(&palguard::WhitelistAdd::new(steamid))
+                .await?;
+            if result {
+                record_whitelist(store, steamid, true);
+            }
+            let message = tr!(
+                "palguard-whitelisted",
+                steamid = steamid.as_str(),
+                result = result.to_string()
+            );
+            (message, result)
+        }
+        PalguardCommand::WhitelistRemove { steamid } => {
+            let result = server
+                .execute(&palguard::WhitelistRemove::new(steamid))
+                .await?;
+            if result {
+                record_whitelist(store, steamid, false);
+            }
+            let message = tr!(
+                "palguard-unwhitelisted",
+                steamid = steamid.as_str(),
+                result = result.to_string()
+            );
+            (message, result)
+        }
+        PalguardCommand::Give {
+            steamid,
+            item,
+            count,
+            id,
+        } => {
+            let item = match id {
+                true => item.clone(),
+                false => items::find(item)?.id.to_string(),
+            };
+            let result = server
+                .execute(&palguard::Give::new(steamid, &item, *count))
+                .await?;
+            let message = tr!(
+                "palguard-given",
+                steamid = steamid.as_str(),
+                item = item.as_str(),
+                count = *count,
+                result = result.to_string()
+            );
+            (message, result)
+        }
+        PalguardCommand::Spawn {
+            steamid,
+            species,
+            level,
+        } => {
+            let pal = pals::find(species)?;
+            let result = server.spawn_pal(pal.id, *level, steamid).await?;
+            let message = tr!(
+                "palguard-spawned",
+                steamid = steamid.as_str(),
+                pal = pal.name,
+                level = *level,
+                result = result.to_string()
+            );
+            (message, result)
+        }
+        PalguardCommand::Getpos { steamid } => {
+            let position = server.execute(&palguard::GetPos::new(steamid)).await?;
+            match json {
+                true => println!("{}", serde_json::to_string(&position)?),
+                false => {
+                    let position = position.to_string();
+                    println!(
+                        "{}",
+                        tr!(
+                            "palguard-position",
+                            steamid = steamid.as_str(),
+                            position = position
+                        )
+                    );
+                }
+            }
+            return Ok(());
+        }
+        PalguardCommand::Teleport { steamid, x, y, z } => {
+            let position = palguard::Position::new(*x, *y, *z);
+            let result = server
+                .execute(&palguard::Teleport::new(steamid, position))
+                .await?;
+            let position = position.to_string();
+            let message = tr!(
+                "palguard-teleported",
+                steamid = steamid.as_str(),
+                position = position,
+                result = result.to_string()
+            );
+            (message, result)
+        }
+    };
+    match json {
+        true => println!("{}", json!({ "result": result })),
+        false => println!("{}", style::outcome(result, &message)),
+    }
+    Ok(())
+}
+
+fn print_batch(results: &[CommandResult]) {
+    for result in results {
+        let command = result.command.as_str();
+        let line = match &result.outcome {
+            CommandOutcome::Done(response) => {
+                let response = response.trim_end();
+                style::success(&tr!("batch-done", command = command, response = response))
+            }
+            CommandOutcome::Failed(error) => {
+                let error = error.as_str();
+                style::error(&tr!("batch-failed", command = command, error = error))
+            }
+            CommandOutcome::TimedOut => style::error(&tr!("batch-timed-out", command = command)),
+            CommandOutcome::Skipped => style::warning(&tr!("batch-skipped", command = command)),
+        };
+        println!("{line}");
+    }
+}
+
+fn print_ping(report: &HealthReport) {
+    if let Some(version) = &report.version {
+        let ms = report.latency.as_millis() as u64;
+        let message = tr!("ping-rcon", ms = ms, version = version.as_str());
+        println!("{}", style::success(&message));
+    }
+    if let Some(game) = &report.game {
+        if let Some(latency) = game.latency {
+            let ms = latency.as_millis() as u64;
+            let message = match &game.info {
+                Some(info) => tr!(
+                    "ping-game-players",
+                    port = game.port,
+                    ms = ms,
+                    players = info.players,
+                    max = info.max_players
+                ),
+                None => tr!("ping-game", port = game.port, ms = ms),
+            };
+            println!("{}", style::success(&message));
+        }
+    }
+    if !report.healthy() {
+        let failures = report.failures.join(", ");
+        println!(
+            "{}",
+            style::error(&tr!("health-failed", failures = failures))
+        );
+    }
+}
+
+fn print_diagnostics(report: &DiagnosticReport) {
+    let header = [
+        tr!("column-status"),
+        tr!("column-check"),
+        tr!("column-detail"),
+    ];
+    let rows: Vec<Vec<String>> = report
+        .checks
+        .iter()
+        .map(|check| {
+            vec![
+                check.status.to_string(),
+                check.kind.to_string(),
+                check.detail.clone(),
+            ]
+        })
+        .collect();
+    println!("{}", style::table(&header, &rows));
+    let problems: Vec<_> = report.problems().collect();
+    for check in &problems {
+        let Some(fix) = &check.fix else {
+            continue;
+        };
+        let message = tr!(
+            "diagnose-fix",
+            check = check.kind.to_string(),
+            fix = fix.as_str()
+        );
+        match check.status {
+            CheckStatus::Fail => println!("{}", style::error(&message)),
+            _ => println!("{}", style::warning(&message)),
+        }
+    }
+    match problems.len() {
+        0 => println!("{}", style::success(&tr!("diagnose-ok"))),
+        count => println!("{}", tr!("diagnose-problems", count = count)),
+    }
+}
+
+/// Trigger of commands run from the CLI, naming the user running it.
+fn manual_trigger() -> Trigger {
+    let user = std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or("unknown user".to_string());
+    Trigger::Manual(format!("palworldcli by {user}"))
+}
+
+fn run_mod_history(steamid: &str, store: &str, json: bool) -> Result<()> {
+    let strikes = SessionStore::open(store)?.strikes(steamid)?;
+    if json {
+        println!("{}", serde_json::to_string(&strikes)?);
+        return Ok(());
+    }
+    if strikes.is_empty() {
+        println!("{}", tr!("mod-no-strikes", steamid = steamid));
+        return Ok(());
+    }
+    let header = [
+        tr!("column-issued"),
+        tr!("column-moderator"),
+        tr!("column-action"),
+        tr!("column-reason"),
+    ];
+    let rows: Vec<Vec<String>> = strikes
+        .iter()
+        .map(|strike| {
+            vec![
+                humantime::format_rfc3339_seconds(strike.issued_at).to_string(),
+                strike.issued_by.clone(),
+                strike.action.to_string(),
+                strike.reason.clone(),
+            ]
+        })
+        .collect();
+    println!("{}", style::table(&header, &rows));
+    Ok(())
+}
+
+fn run_mod_export(
+    output: Option<&std::path::Path>,
+    format: Option<moddata::Format>,
+    store: &str,
+) -> Result<()> {
+    let format = format
+        .or_else(|| output.and_then(moddata::Format::from_path))
+        .unwrap_or(moddata::Format::Json);
+    let data = ModerationData::from_store(&SessionStore::open(store)?)?;
+    let Some(output) = output else {
+        return data.write(format, std::io::stdout().lock());
+    };
+    data.write(format, std::fs::File::create(output)?)?;
+    let message = tr!(
+        "mod-exported",
+        bans = data.bans.len(),
+        warnings = data.warnings.len(),
+        whitelist = data.whitelist.len(),
+        path = output.display().to_string()
+    );
+    println!("{}", style::success(&message));
+    Ok(())
+}
+
+fn run_mod_import(
+    path: &std::path::Path,
+    format: Option<moddata::Format>,
+    strategy: MergeStrategy,
+    store: &str,
+    json: bool,
+) -> Result<()> {
+    let Some(format) = format.or_else(|| moddata::Format::from_path(path)) else {
+        anyhow::bail!(
+            "Unknown format of {}, pass --format json or csv",
+            path.display()
+        );
+    };
+    let file = std::fs::File::open(path).with_context(|| path.display().to_string())?;
+    let data = ModerationData::read(format, file).with_context(|| path.display().to_string())?;
+    let summary = data.import(&SessionStore::open(store)?, strategy)?;
+    match json {
+        true => println!("{}", serde_json::to_string(&summary)?),
+        false => {
+            let message = tr!(
+                "mod-imported",
+                path = path.display().to_string(),
+                added = summary.added,
+                replaced = summary.replaced,
+                skipped = summary.skipped
+            );
+            println!("{}", style::success(&message));
+        }
+    }
+    Ok(())
+}
+
+/// Keeps the whitelist in the store in step with PalGuard's, for `mod export`.
+fn record_whitelist(store: &str, steamid: &str, added: bool) {
+    let recorded = SessionStore::open(store).and_then(|store| match added {
+        true => store.record_whitelisted(&WhitelistEntry {
+            steamid: steamid.to_string(),
+            name: None,
+            added_at: std::time::SystemTime::now(),
+        }),
+        false => store.remove_whitelisted(steamid),
+    });
+    if let Err(e) = recorded {
+        log::warn!("Whitelist change isn't recorded in the store: {e}");
+    }
+}
+
+fn run_broadcasts(command: &BroadcastsCommand, store: &str, json: bool) -> Result<()> {
+    match command {
+        BroadcastsCommand::History { count } => {
+            let broadcasts = SessionStore::open(store)?.recent_broadcasts(*count)?;
+            if json {
+                println!("{}", serde_json::to_string(&broadcasts)?);
+                return Ok(());
+            }
+            let header = [
+                tr!("column-sent"),
+                tr!("column-initiator"),
+                tr!("column-message"),
+            ];
+            let rows: Vec<Vec<String>> = broadcasts
+                .iter()
+                .rev()
+                .map(|broadcast| {
+                    vec![
+                        humantime::format_rfc3339_seconds(broadcast.sent_at).to_string(),
+                        broadcast.initiator.clone(),
+                        broadcast.message.clone(),
+                    ]
+                })
+                .collect();
+            println!("{}", style::table(&header, &rows));
+        }
+    }
+    Ok(())
+}
+
+fn run_saves(command: &SavesCommand, store: &str, json: bool) -> Result<()> {
+    match command {
+        SavesCommand::Guilds { level, max_bases } => {
+            let level = LevelSave::open(level)?;
+            let guilds: Vec<_> = level
+                .guilds
+                .iter()
+                .filter(|guild| max_bases.is_none_or(|max| guild.base_count() > max))
+                .collect();
+            if json {
+                println!("{}", serde_json::to_string(&guilds)?);
+                return Ok(());
+            }
+            let format_offline = |ticks| {
+                level
+                    .offline_for(ticks)
+                    .map(|offline| {
+                        let days = offline.as_secs() / (24 * 3600);
+                        tr!("days-ago", days = days)
+                    })
+                    .unwrap_or(tr!("unknown"))
+            };
+            for guild in guilds {
+                let line = tr!(
+                    "guild",
+                    name = guild.name.as_str(),
+                    id = guild.id.to_string(),
+                    bases = guild.base_count(),
+                    level = guild.base_camp_level.to_string(),
+                    online = guild
+                        .last_online_ticks()
+                        .map(format_offline)
+                        .unwrap_or(tr!("never")),
+                );
+                println!("{line}");
+                for member in &guild.members {
+                    let admin = match member.uid == guild.admin {
+                        true => format!(" {}", tr!("guild-admin")),
+                        false => String::new(),
+                    };
+                    println!(
+                        "  {}{admin}\t{}\t{}",
+                        member.name,
+                        member.uid.player_uid(),
+                        format_offline(member.last_online_ticks)
+                    );
+                }
+                for base in level.bases_of(guild) {
+                    let [x, y, z] = base.location;
+                    let line = tr!(
+                        "guild-base",
+                        name = base.name.as_str(),
+                        id = base.id.to_string(),
+                        location = format!("{x:.0}, {y:.0}, {z:.0}"),
+                    );
+                    println!("  {line}");
+                }
+            }
+        }
+        SavesCommand::Cleanup { level, inactive } => {
+            let saved_at = std::fs::metadata(level)?.modified()?;
+            let level = LevelSave::open(level)?;
+            let store = SessionStore::open(store)?;
+            let now = std::time::SystemTime::now();
+            let plan = cleanup::plan(&level, saved_at, &store, **inactive, now)?;
+            print_cleanup(&plan, json);
+        }
+        SavesCommand::Stats { world, top } => {
+            let level = LevelSave::open(world.join("Level.sav"))?;
+            let players = PlayerSave::open_all(world.join("Players"))?;
+            let mut stats = savefile::player_stats(&level, &players);
+            stats.truncate(top.unwrap_or(stats.len()));
+            if json {
+                println!("{}", serde_json::to_string(&stats)?);
+                return Ok(());
+            }
+            let header = [
+                tr!("column-rank"),
+                tr!("column-captures"),
+                tr!("column-pals"),
+                tr!("column-best"),
+                tr!("column-average"),
+                tr!("column-level"),
+                tr!("column-name"),
+            ];
+            let format_level =
+                |level: Option<u32>| level.map_or("-".to_string(), |l| l.to_string());
+            let rows: Vec<Vec<String>> = stats
+                .iter()
+                .enumerate()
+                .map(|(rank, player)| {
+                    vec![
+                        (rank + 1).to_string(),
+                        player.captures.to_string(),
+                        player.pals.to_string(),
+                        format_level(player.highest_pal_level),
+                        player
+                            .average_pal_level
+                            .map_or("-".to_string(), |average| format!("{average:.1}")),
+                        format_level(player.level),
+                        player.name.clone(),
+                    ]
+                })
+                .collect();
+            println!("{}", style::table(&header, &rows));
+        }
+        SavesCommand::Time { level } => {
+            let Some(time) = LevelSave::open(level)?.world_time() else {
+                anyhow::bail!("{} has no game time", level.display());
+            };
+            if json {
+                println!("{}", serde_json::to_string(&time)?);
+                return Ok(());
+            }
+            let period = match time.is_night() {
+                true => tr!("world-night"),
+                false => tr!("world-day"),
+            };
+            println!("{time} ({period})");
+        }
+    }
+    Ok(())
+}
+
+/// Shows the steps of a long operation, cleared when the operation drops it.
+struct ProgressBar(indicatif::ProgressBar);
+
+impl ProgressBar {
+    fn new() -> Self {
+        let bar = indicatif::ProgressBar::new_spinner();
+        bar.set_style(
+            indicatif::ProgressStyle::with_template("{spinner} {elapsed:>4} {msg}")
+                .unwrap_or_else(|_| indicatif::ProgressStyle::default_spinner()),
+        );
+        bar.enable_steady_tick(std::time::Duration::from_millis(120));
+        Self(bar)
+    }
+}
+
+impl Progress for ProgressBar {
+    fn update(&self, update: &ProgressUpdate) {
+        self.0.set_message(update.to_string());
+    }
+}
+
+impl Drop for ProgressBar {
+    fn drop(&mut self) {
+        self.0.finish_and_clear();
+    }
+}
+
+/// Registry of the commands in a JSON list of [CommandSpec]s.
+fn load_commands(path: &std::path::Path) -> Result<CommandRegistry> {
+    let specs: Vec<CommandSpec> = serde_json::from_str(&std::fs::read_to_string(path)?)
+        .with_context(|| format!("Failed to read commands from {}", path.display()))?;
+    Ok(CommandRegistry::from_specs(&specs))
+}
+
+/// Passphrase of encrypted profiles, from PALWORLD_PASSPHRASE or asked for once.
+static PASSPHRASE: std::sync::OnceLock<Secret<String>> = std::sync::OnceLock::new();
+
+/// The passphrase of encrypted profiles, asked for twice when `new` to catch typos.
+fn profile_passphrase(new: bool) -> Result<String> {
+    if let Some(passphrase) = PASSPHRASE.get() {
+        return Ok(passphrase.expose().clone());
+    }
+    let passphrase = match std::env::var("PALWORLD_PASSPHRASE") {
+        Ok(passphrase) => passphrase,
+        Err(_) if !std::io::stdin().is_terminal() => anyhow::bail!(tr!("passphrase-no-terminal")),
+        Err(_) => {
+            let passphrase = rpassword::prompt_password(format!("{} ", tr!("passphrase-prompt")))?;
+            if new
+                && rpassword::prompt_password(format!("{} ", tr!("passphrase-confirm")))?
+                    != passphrase
+            {
+                anyhow::bail!(tr!("passphrase-mismatch"));
+            }
+            passphrase
+        }
+    };
+    Ok(PASSPHRASE
+        .get_or_init(|| Secret::new(passphrase))
+        .expose()
+        .clone())
+}
+
+/// The new password of --rotate-password, read from stdin for `-` and asked for twice when
+/// stdin is a terminal.
+fn read_new_password(path: &std::path::Path) -> Result<String> {
+    if path != std::path::Path::new("-") {
+        return read_password_file(path);
+    }
+    if !std::io::stdin().is_terminal() {
+        let mut input = String::new();
+        std::io::stdin().read_to_string(&mut input)?;
+        return rcon::parse_password_file(&input).context("Invalid password on stdin");
+    }
+    let password = rpassword::prompt_password(format!("{} ", tr!("new-password-prompt")))?;
+    if rpassword::prompt_password(format!("{} ", tr!("new-password-confirm")))? != password {
+        anyhow::bail!(tr!("new-password-mismatch"));
+    }
+    rcon::validate_password(&password)?;
+    Ok(password)
+}
+
+/// Contents of a password file or profile, decrypted if it is encrypted.
+fn read_profile(path: &std::path::Path) -> Result<String> {
+    encryption::read_to_string(path, || profile_passphrase(false))
+}
+
+/// Overwrites a password file or profile, encrypted again if it was encrypted.
+fn write_profile(path: &std::path::Path, contents: &[u8]) -> Result<()> {
+    let encrypted = std::fs::read(path).is_ok_and(|data| encryption::is_encrypted(&data));
+    let data = match encrypted {
+        true => encryption::encrypt(contents, &profile_passphrase(false)?)?,
+        false => contents.to_vec(),
+    };
+    std::fs::write(path, data).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn read_password_file(path: &std::path::Path) -> Result<String> {
+    rcon::parse_password_file(&read_profile(path)?)
+        .with_context(|| format!("Invalid password file {}", path.display()))
+}
+
+fn run_profiles(command: &ProfilesCommand) -> Result<()> {
+    let (files, encrypt) = match command {
+        ProfilesCommand::Encrypt { files } => (files, true),
+        ProfilesCommand::Decrypt { files } => (files, false),
+    };
+    for path in files {
+        let data =
+            std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let file = path.display().to_string();
+        match (encrypt, encryption::is_encrypted(&data)) {
+            (true, true) => println!(
+                "{}",
+                style::warning(&tr!("profile-already-encrypted", path = file))
+            ),
+            (false, false) => println!(
+                "{}",
+                style::warning(&tr!("profile-not-encrypted", path = file))
+            ),
+            (true, false) => {
+                let data = encryption::encrypt(&data, &profile_passphrase(true)?)?;
+                std::fs::write(path, data).with_context(|| format!("Failed to write {file}"))?;
+                println!("{}", style::success(&tr!("profile-encrypted", path = file)));
+            }
+            (false, true) => {
+                let data = encryption::decrypt(&data, &profile_passphrase(false)?)
+                    .with_context(|| format!("Failed to decrypt {file}"))?;
+                std::fs::write(path, data).with_context(|| format!("Failed to write {file}"))?;
+                println!("{}", style::success(&tr!("profile-decrypted", path = file)));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The world named `name` from a JSON list of profiles.
+fn load_world(path: &std::path::Path, name: &str) -> Result<WorldProfile> {
+    let worlds: Vec<WorldProfile> = serde_json::from_str(&read_profile(path)?)?;
+    match worlds.into_iter().find(|world| world.name == name) {
+        Some(world) => Ok(world),
+        None => anyhow::bail!("No world named '{name}' in {}", path.display()),
+    }
+}
+
+fn print_cleanup(plan: &cleanup::CleanupPlan, json: bool) {
+    let format_seen = |seen: Option<std::time::SystemTime>| {
+        seen.map(|seen| humantime::format_rfc3339_seconds(seen).to_string())
+    };
+    if json {
+        let guilds: Vec<serde_json::Value> = plan
+            .guilds
+            .iter()
+            .map(|guild| {
+                json!({
+                    "id": guild.id,
+                    "name": guild.name,
+                    "members": guild.members,
+                    "last_seen": format_seen(guild.last_seen),
+                    "bases": guild.bases,
+                })
+            })
+            .collect();
+        let output = json!({
+            "dry_run": true,
+            "inactive_secs": plan.inactive_for.as_secs(),
+            "base_count": plan.base_count(),
+            "guilds": guilds,
+        });
+        println!("{output}");
+        return;
+    }
+    let header = tr!(
+        "cleanup-plan",
+        bases = plan.base_count(),
+        guilds = plan.guilds.len(),
+        inactive = humantime::format_duration(plan.inactive_for).to_string(),
+    );
+    println!("{}", style::warning(&header));
+    for guild in &plan.guilds {
+        let line = tr!(
+            "cleanup-guild",
+            name = guild.name.as_str(),
+            id = guild.id.to_string(),
+            seen = format_seen(guild.last_seen).unwrap_or(tr!("never")),
+            members = guild.members.join(", "),
+        );
+        println!("{line}");
+        for base in &guild.bases {
+            let [x, y, z] = base.location;
+            let line = tr!(
+                "cleanup-base",
+                id = base.id.to_string(),
+                location = format!("{x:.0}, {y:.0}, {z:.0}"),
+            );
+            println!("  {line}");
+        }
+    }
+}
+
+/// Number of columns of the --graph sparklines.
+const GRAPH_WIDTH: u64 = 60;
+
+/// Minimum, maximum and last value of each metric, after a sparkline with `graph`.
+fn print_stats(samples: &[metrics::MetricSample], graph: bool) {
+    if samples.is_empty() {
+        println!("{}", style::warning(&tr!("no-metrics")));
+        return;
+    }
+    let print_row = |label: &str, values: &[f64], format: &dyn Fn(f64) -> String| {
+        if values.is_empty() {
+            return;
+        }
+        let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let row = tr!(
+            "graph-row",
+            label = format!("{label:<8}"),
+            sparkline = match graph {
+                true => metrics::sparkline(values),
+                false => String::new(),
+            },
+            min = format(min),
+            max = format(max),
+            last = format(values[values.len() - 1]),
+        );
+        println!("{row}");
+    };
+    let players: Vec<f64> = samples.iter().map(|s| s.players).collect();
+    let memory: Vec<f64> = samples
+        .iter()
+        .filter_map(|s| s.memory_used.map(|m| m.as_bytes() as f64))
+        .collect();
+    let cpu: Vec<f64> = samples.iter().filter_map(|s| s.cpu_percent).collect();
+    print_row(&tr!("graph-players"), &players, &|v| format!("{v:.0}"));
+    print_row(&tr!("graph-memory"), &memory, &|v| {
+        ByteSize(v as u64).to_string()
+    });
+    print_row(&tr!("graph-cpu"), &cpu, &|v| format!("{v:.1}%"));
+}
+
+fn print_uptime(
+    report: &UptimeReport,
+    start: std::time::SystemTime,
+    end: std::time::SystemTime,
+    json: bool,
+) -> Result<()> {
+    let format_time = |time| humantime::format_rfc3339_seconds(time).to_string();
+    let format_duration = |duration: std::time::Duration| {
+        humantime::format_duration(std::time::Duration::from_secs(duration.as_secs())).to_string()
+    };
+    if json {
+        let incidents: Vec<serde_json::Value> = report
+            .incidents
+            .iter()
+            .map(|incident| {
+                json!({
+                    "started_at": format_time(incident.started_at),
+                    "resolved_at": incident.resolved_at.map(format_time),
+                    "duration_secs": incident.duration(end).as_secs(),
+                })
+            })
+            .collect();
+        let output = json!({
+            "since": format_time(start),
+            "probes": report.probes,
+            "availability": report.availability,
+            "downtime_secs": report.downtime.as_secs(),
+            "mttr_secs": report.mttr.map(|mttr| mttr.as_secs()),
+            "incidents": incidents,
+        });
+        println!("{output}");
+        return Ok(());
+    }
+    match report.availability {
+        Some(availability) => println!(
+            "{}",
+            style::bold(&tr!(
+                "uptime",
+                since = format_time(start),
+                availability = format!("{availability:.3}"),
+                probes = report.probes,
+            ))
+        ),
+        None => println!(
+            "{}",
+            style::warning(&tr!("no-probes", since = format_time(start)))
+        ),
+    }
+    let downtime = tr!(
+        "downtime",
+        downtime = format_duration(report.downtime),
+        mttr = report.mttr.map(format_duration).unwrap_or("-".to_string()),
+    );
+    println!("{downtime}");
+    if !report.incidents.is_empty() {
+        let header = [
+            tr!("column-started"),
+            tr!("column-resolved"),
+            tr!("column-duration"),
+        ];
+        let rows: Vec<Vec<String>> = report
+            .incidents
+            .iter()
+            .map(|incident| {
+                vec![
+                    format_time(incident.started_at),
+                    incident
+                        .resolved_at
+                        .map(format_time)
+                        .unwrap_or(tr!("ongoing")),
+                    format_duration(incident.duration(end)),
+                ]
+            })
+            .collect();
+        println!("{}", style::table(&header, &rows));
+    }
+    Ok(())
+}
+
 fn initialize_log(log_level: Option<String>) -> Result<()> {
     let log_level = match &log_level {
         Some(ll) => match &ll.to_lowercase() {
@@ -179,7 +2725,11 @@ fn initialize_log(log_level: Option<String>) -> Result<()> {
             out.finish(format_args!(
                 "[{} {} {}] {}",
                 humantime::format_rfc3339(std::time::SystemTime::now()),
-                record.level(),
+                match record.level() {
+                    log::Level::Error => style::error("ERROR"),
+                    log::Level::Warn => style::warning("WARN"),
+                    level => level.to_string(),
+                },
                 record.target(),
                 message
             ))
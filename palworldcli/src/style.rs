@@ -0,0 +1,160 @@
+//! Colors and aligned tables for terminal output.
+//!
+//! Colors follow `--color`: `auto` colors a terminal unless `NO_COLOR` is set, see
+//! <https://no-color.org>. Stdout and stderr are detected apart, text for stderr is styled
+//! with [stderr]. Successes are green, warnings yellow, errors red and table headers bold.
+//! JSON output is never colored. Tables are aligned on a terminal and tab-separated
+//! otherwise, so scripts can split them.
+
+use std::io::IsTerminal;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::{bail, Result};
+use unicode_width::UnicodeWidthStr;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static STDERR_ENABLED: AtomicBool = AtomicBool::new(false);
+static ALIGNED: AtomicBool = AtomicBool::new(false);
+
+/// When to color output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for ColorChoice {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(Self::Auto),
+            "always" => Ok(Self::Always),
+            "never" => Ok(Self::Never),
+            _ => bail!("Unknown color choice '{s}', expected auto, always or never"),
+        }
+    }
+}
+
+/// Enables colors for `choice` and aligned tables on a terminal, off until called.
+pub fn init(choice: ColorChoice) {
+    let enabled = |terminal: bool| match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => {
+            std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()) && terminal
+        }
+    };
+    let stdout = std::io::stdout().is_terminal();
+    ENABLED.store(enabled(stdout), Ordering::Relaxed);
+    STDERR_ENABLED.store(enabled(std::io::stderr().is_terminal()), Ordering::Relaxed);
+    ALIGNED.store(stdout, Ordering::Relaxed);
+}
+
+fn paint(enabled: &AtomicBool, code: &str, text: &str) -> String {
+    match enabled.load(Ordering::Relaxed) {
+        true => format!("\x1b[{code}m{text}\x1b[0m"),
+        false => text.to_string(),
+    }
+}
+
+pub fn success(text: &str) -> String {
+    paint(&ENABLED, "32", text)
+}
+
+pub fn warning(text: &str) -> String {
+    paint(&ENABLED, "33", text)
+}
+
+pub fn error(text: &str) -> String {
+    paint(&ENABLED, "31", text)
+}
+
+pub fn bold(text: &str) -> String {
+    paint(&ENABLED, "1", text)
+}
+
+/// A [success] if the server reported one, a [warning] if it refused or failed.
+pub fn outcome(ok: bool, text: &str) -> String {
+    match ok {
+        true => success(text),
+        false => warning(text),
+    }
+}
+
+/// Styles for text printed to stderr.
+pub mod stderr {
+    use super::{paint, STDERR_ENABLED};
+
+    pub fn warning(text: &str) -> String {
+        paint(&STDERR_ENABLED, "33", text)
+    }
+
+    pub fn error(text: &str) -> String {
+        paint(&STDERR_ENABLED, "31", text)
+    }
+}
+
+/// The rows under `header`, aligned on a terminal and tab-separated otherwise.
+pub fn table(header: &[String], rows: &[Vec<String>]) -> String {
+    match ALIGNED.load(Ordering::Relaxed) {
+        true => aligned(header, rows),
+        false => std::iter::once(header)
+            .chain(rows.iter().map(Vec::as_slice))
+            .map(|row| row.join("\t"))
+            .collect::<Vec<String>>()
+            .join("\n"),
+    }
+}
+
+/// Columns padded to their widest cell, wide characters like Japanese names count twice.
+/// The header is bold, the last column isn't padded.
+fn aligned(header: &[String], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = header.iter().map(|cell| cell.width()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.width());
+        }
+    }
+    let format_row = |row: &[String]| {
+        let last = row.len().saturating_sub(1);
+        let cells: Vec<String> = row
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| match i == last {
+                true => cell.clone(),
+                false => format!("{cell}{}", " ".repeat(widths[i] - cell.width())),
+            })
+            .collect();
+        cells.join("  ")
+    };
+    let mut lines = vec![bold(&format_row(header))];
+    lines.extend(rows.iter().map(|row| format_row(row)));
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table() {
+        let header = ["Name".to_string(), "UID".to_string()];
+        let rows = [
+            vec!["Alice".to_string(), "1".to_string()],
+            vec!["パルマスター".to_string(), "22".to_string()],
+        ];
+        assert_eq!(
+            aligned(&header, &rows),
+            "Name          UID\nAlice         1\nパルマスター  22"
+        );
+        // Not a terminal.
+        assert_eq!(
+            table(&header, &rows),
+            "Name\tUID\nAlice\t1\nパルマスター\t22"
+        );
+    }
+}
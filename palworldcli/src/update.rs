@@ -0,0 +1,242 @@
+//! `palworldcli self-update`: replaces the running binary with the latest GitHub release.
+//!
+//! A release has one binary per platform named like [asset_name] and a `SHA256SUMS` file
+//! listing their checksums. A minisign public key given with `--public-key`, or built in
+//! through the `PALWORLDCLI_RELEASE_KEY` environment variable, requires `SHA256SUMS.minisig`
+//! to be a valid signature of the checksums before anything is replaced. Without a key the
+//! CLI refuses to install unless `--insecure` is passed. `palworldcli dist`
+//! writes the checksums, see [crate::dist].
+
+use anyhow::{bail, Context, Result};
+use minisign_verify::{PublicKey, Signature};
+use palworld_server::models::ServerVersion;
+use sha2::{Digest, Sha256};
+
+/// Latest release of the repository in the GitHub API.
+pub const RELEASES_URL: &str = "https://api.github.com/repos/ic3man5/rcon_palworld/releases/latest";
+
+/// Minisign public key release builds embed, signatures are required with it.
+pub const RELEASE_PUBLIC_KEY: Option<&str> = option_env!("PALWORLDCLI_RELEASE_KEY");
+
+const CHECKSUMS: &str = "SHA256SUMS";
+const SIGNATURE: &str = "SHA256SUMS.minisig";
+
+/// A release and the download URLs of its files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Release {
+    pub version: ServerVersion,
+    /// File name and download URL of every asset.
+    pub assets: Vec<(String, String)>,
+}
+
+impl Release {
+    /// Parses a release of the GitHub API, the tag is the version like `v0.2.0`.
+    pub fn from_json(release: &serde_json::Value) -> Result<Self> {
+        let tag = release["tag_name"].as_str().context("Release has no tag")?;
+        let version = tag
+            .parse()
+            .with_context(|| format!("Release tag '{tag}' isn't a version"))?;
+        let assets = release["assets"]
+            .as_array()
+            .map(|assets| {
+                assets
+                    .iter()
+                    .filter_map(|asset| {
+                        let name = asset["name"].as_str()?;
+                        let url = asset["browser_download_url"].as_str()?;
+                        Some((name.to_string(), url.to_string()))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(Self { version, assets })
+    }
+
+    pub fn asset_url(&self, name: &str) -> Option<&str> {
+        self.assets
+            .iter()
+            .find(|(asset, _)| asset == name)
+            .map(|(_, url)| url.as_str())
+    }
+}
+
+/// Version of the running binary.
+pub fn current_version() -> ServerVersion {
+    env!("CARGO_PKG_VERSION")
+        .parse()
+        .expect("Package version is numeric")
+}
+
+/// Name of the release binary for this platform, like `palworldcli-x86_64-linux`.
+pub fn asset_name() -> String {
+    let name = format!(
+        "palworldcli-{}-{}",
+        std::env::consts::ARCH,
+        std::env::consts::OS
+    );
+    match std::env::consts::EXE_SUFFIX {
+        "" => name,
+        suffix => format!("{name}{suffix}"),
+    }
+}
+
+/// Checksum of `name` in a `sha256sum` style list of `<hex>  <name>` lines.
+pub fn find_checksum<'a>(checksums: &'a str, name: &str) -> Option<&'a str> {
+    checksums.lines().find_map(|line| {
+        let (hash, file) = line.trim().split_once(char::is_whitespace)?;
+        // Binary mode lists the name with a `*` in front.
+        (file.trim_start().trim_start_matches('*') == name).then_some(hash)
+    })
+}
+
+/// Lowercase SHA-256 hex digest of `data`.
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Fails unless `data` has the SHA-256 hex digest `expected`.
+pub fn verify_checksum(data: &[u8], expected: &str) -> Result<()> {
+    let actual = sha256_hex(data);
+    if !actual.eq_ignore_ascii_case(expected) {
+        bail!("Checksum mismatch, expected {expected} but the download has {actual}");
+    }
+    Ok(())
+}
+
+/// Fails unless `signature` is a minisign signature of `data` by `public_key` (base64).
+pub fn verify_signature(data: &[u8], signature: &str, public_key: &str) -> Result<()> {
+    let public_key =
+        PublicKey::from_base64(public_key.trim()).context("Invalid minisign public key")?;
+    let signature = Signature::decode(signature).context("Invalid minisign signature")?;
+    public_key
+        .verify(data, &signature, false)
+        .context("Release signature doesn't match the public key")
+}
+
+async fn download(client: &reqwest::Client, url: &str) -> Result<Vec<u8>> {
+    let response = client.get(url).send().await?.error_for_status()?;
+    Ok(response.bytes().await?.to_vec())
+}
+
+/// The latest release.
+pub async fn latest_release(client: &reqwest::Client) -> Result<Release> {
+    let body = download(client, RELEASES_URL)
+        .await
+        .context("Failed to fetch the latest release")?;
+    Release::from_json(&serde_json::from_slice(&body)?)
+}
+
+/// Downloads and verifies the binary of `release` for this platform, then replaces the
+/// running binary with it. Without `public_key` only the checksum is verified.
+pub async fn install(
+    client: &reqwest::Client,
+    release: &Release,
+    public_key: Option<&str>,
+) -> Result<()> {
+    let name = asset_name();
+    let Some(url) = release.asset_url(&name) else {
+        bail!(
+            "Release {} has no binary for this platform ({name})",
+            release.version
+        );
+    };
+    let Some(checksums_url) = release.asset_url(CHECKSUMS) else {
+        bail!("Release {} has no {CHECKSUMS}", release.version);
+    };
+    let checksums = download(client, checksums_url).await?;
+    if let Some(public_key) = public_key {
+        let Some(signature_url) = release.asset_url(SIGNATURE) else {
+            bail!("Release {} isn't signed, no {SIGNATURE}", release.version);
+        };
+        let signature = String::from_utf8(download(client, signature_url).await?)?;
+        verify_signature(&checksums, &signature, public_key)?;
+    } else {
+        log::warn!("Insecure update, only checking the checksum");
+    }
+    let checksums = String::from_utf8(checksums)?;
+    let Some(expected) = find_checksum(&checksums, &name) else {
+        bail!(
+            "{CHECKSUMS} of release {} doesn't list {name}",
+            release.version
+        );
+    };
+    let binary = download(client, url).await?;
+    verify_checksum(&binary, expected)?;
+
+    let current = std::env::current_exe()?;
+    let download = current.with_file_name(format!(".{name}.download"));
+    std::fs::write(&download, &binary)
+        .with_context(|| format!("Failed to write {}", download.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&download, std::fs::Permissions::from_mode(0o755))?;
+    }
+    let replaced = self_replace::self_replace(&download)
+        .with_context(|| format!("Failed to replace {}", current.display()));
+    std::fs::remove_file(&download).ok();
+    replaced
+}
+
+/// Client for the GitHub API, which requires a User-Agent.
+pub fn client() -> Result<reqwest::Client> {
+    Ok(reqwest::Client::builder()
+        .user_agent(concat!("palworldcli/", env!("CARGO_PKG_VERSION")))
+        .build()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_release() {
+        let release = serde_json::json!({
+            "tag_name": "v0.2.0",
+            "assets": [
+                {"name": "SHA256SUMS", "browser_download_url": "https://example.com/SHA256SUMS"},
+                {"name": "palworldcli-x86_64-linux", "browser_download_url": "https://example.com/bin"},
+            ],
+        });
+        let release = Release::from_json(&release).unwrap();
+        assert_eq!(release.version, ServerVersion(vec![0, 2, 0]));
+        assert!(release.version > "0.1.9".parse().unwrap());
+        assert_eq!(
+            release.asset_url("palworldcli-x86_64-linux"),
+            Some("https://example.com/bin")
+        );
+        assert_eq!(release.asset_url("SHA256SUMS.minisig"), None);
+    }
+
+    #[test]
+    fn test_checksums() {
+        let hash = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        let checksums =
+            format!("0000  palworldcli-aarch64-linux\n{hash} *palworldcli-x86_64-linux\n");
+        assert_eq!(
+            find_checksum(&checksums, "palworldcli-x86_64-linux"),
+            Some(hash)
+        );
+        assert_eq!(
+            find_checksum(&checksums, "palworldcli-x86_64-windows.exe"),
+            None
+        );
+        assert!(verify_checksum(b"hello", hash).is_ok());
+        assert!(verify_checksum(b"hello!", hash).is_err());
+    }
+
+    #[test]
+    fn test_signature() {
+        // Example key and signature of "test" from the minisign-verify documentation.
+        let public_key = "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3";
+        let signature = "untrusted comment: signature from minisign secret key
+RUQf6LRCGA9i559r3g7V1qNyJDApGip8MfqcadIgT9CuhV3EMhHoN1mGTkUidF/z7SrlQgXdy8ofjb7bNJJylDOocrCo8KLzZwo=
+trusted comment: timestamp:1633700835\tfile:test\tprehashed
+wLMDjy9FLAuxZ3q4NlEvkgtyhrr0gtTu6KC4KBJdITbbOeAi1zBIYo0v4iTgt8jJpIidRJnp94ABQkJAgAooBQ==";
+        assert!(verify_signature(b"test", signature, public_key).is_ok());
+        assert!(verify_signature(b"tampered", signature, public_key).is_err());
+    }
+}
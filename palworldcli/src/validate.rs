@@ -0,0 +1,156 @@
+//! `palworldcli validate`: checks the `--worlds` profiles before they are used.
+//!
+//! Unknown keys are reported with the closest known key, values of the wrong type at their
+//! line and column, and profiles `--world` can never select or whose ports clash with
+//! another world as warnings. Cron expressions and the notification routing are checked by
+//! [palworld_server::scheduler::validate_cron] and
+//! [palworld_server::notify::RoutingConfig::validate].
+
+use std::collections::HashMap;
+
+use palworld_server::validate::{find_key, has_errors, unknown_key, Diagnostic};
+use palworld_server::world::WorldProfile;
+
+/// Checks a JSON list of world profiles, `file` names it in the diagnostics.
+pub fn worlds(file: &str, text: &str) -> Vec<Diagnostic> {
+    let value: serde_json::Value = match serde_json::from_str(text) {
+        Ok(value) => value,
+        Err(e) => return vec![json_error(file, text, &e)],
+    };
+    let Some(profiles) = value.as_array() else {
+        return vec![Diagnostic::error("expected a list of world profiles").at(file, text, 0)];
+    };
+    let known = match serde_json::to_value(WorldProfile::new("")) {
+        Ok(serde_json::Value::Object(fields)) => fields.keys().cloned().collect(),
+        _ => Vec::new(),
+    };
+    let known: Vec<&str> = known.iter().map(String::as_str).collect();
+    let mut diagnostics = Vec::new();
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    for profile in profiles.iter().filter_map(|profile| profile.as_object()) {
+        for key in profile.keys() {
+            if !known.contains(&key.as_str()) {
+                let nth = seen.entry(key).or_default();
+                diagnostics.push(unknown_key(file, text, key, *nth, &known));
+                *nth += 1;
+            }
+        }
+    }
+    if has_errors(&diagnostics) {
+        diagnostics.sort_by_key(|diagnostic| (diagnostic.line, diagnostic.column));
+        return diagnostics;
+    }
+    let profiles: Vec<WorldProfile> = match serde_json::from_str(text) {
+        Ok(profiles) => profiles,
+        Err(e) => return vec![json_error(file, text, &e)],
+    };
+    // Keys of the fields as serialized, camelCase with the serde-camel-case feature.
+    let key_of = |field: &str| {
+        let field = field.replace('_', "");
+        known
+            .iter()
+            .copied()
+            .find(|key| key.replace('_', "").eq_ignore_ascii_case(&field))
+            .unwrap_or_default()
+    };
+    let at = |diagnostic: Diagnostic, key: &str, nth: usize| match find_key(text, key, nth) {
+        Some(offset) => diagnostic.at(file, text, offset),
+        None => diagnostic.in_file(file),
+    };
+    let ports = |world: &WorldProfile| {
+        [
+            ("game_port", world.game_port),
+            ("rcon_port", world.rcon_port),
+        ]
+    };
+    for (i, profile) in profiles.iter().enumerate() {
+        let earlier = &profiles[..i];
+        if earlier.iter().any(|world| world.name == profile.name) {
+            let diagnostic = Diagnostic::warning(format!(
+                "world '{}' is unreachable, --world selects the first profile with its name",
+                profile.name
+            ));
+            diagnostics.push(at(diagnostic, key_of("name"), i).help("rename one of them"));
+        }
+        for (j, (field, port)) in ports(profile).into_iter().enumerate() {
+            if let Some(world) = earlier.iter().find(|world| ports(world)[j].1 == port) {
+                let diagnostic = Diagnostic::warning(format!(
+                    "{field} {port} of world '{}' is also used by world '{}'",
+                    profile.name, world.name
+                ));
+                let help = "worlds on one host need their own ports";
+                diagnostics.push(at(diagnostic, key_of(field), i).help(help));
+            }
+        }
+    }
+    diagnostics
+}
+
+/// Error at the position serde_json reports.
+fn json_error(file: &str, text: &str, e: &serde_json::Error) -> Diagnostic {
+    let message = e.to_string();
+    let message = match message.rsplit_once(" at line ") {
+        Some((message, _)) => message.to_string(),
+        None => message,
+    };
+    let diagnostic = Diagnostic::error(message);
+    if e.line() == 0 {
+        return diagnostic.in_file(file);
+    }
+    let line_start: usize = text
+        .split_inclusive('\n')
+        .take(e.line() - 1)
+        .map(str::len)
+        .sum();
+    diagnostic.at(file, text, line_start + e.column().saturating_sub(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use palworld_server::validate::Level;
+
+    #[test]
+    fn test_worlds() {
+        let valid = serde_json::to_string_pretty(&[WorldProfile::new("main")]).unwrap();
+        assert!(worlds("worlds.json", &valid).is_empty());
+
+        let typo = valid.replace("\"service\"", "\"sevice\"");
+        let diagnostics = worlds("worlds.json", &typo);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "unknown key 'sevice'");
+        assert_eq!(
+            diagnostics[0].help.as_deref(),
+            Some("did you mean 'service'?")
+        );
+        assert_eq!(
+            diagnostics[0].source_line.as_deref().map(str::trim),
+            Some("\"sevice\": \"palworld\",")
+        );
+
+        let wrong_type = valid.replace("25575", "\"25575\"");
+        let diagnostics = worlds("worlds.json", &wrong_type);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.starts_with("invalid type: string"));
+        assert!(diagnostics[0]
+            .source_line
+            .as_ref()
+            .unwrap()
+            .contains("25575"));
+
+        let mut pvp = WorldProfile::new("pvp");
+        pvp.game_port = 8212;
+        let duplicate = [WorldProfile::new("main"), pvp, WorldProfile::new("main")];
+        let diagnostics = worlds("worlds.json", &serde_json::to_string(&duplicate).unwrap());
+        assert_eq!(diagnostics.len(), 4);
+        assert!(diagnostics.iter().all(|d| d.level == Level::Warning));
+        assert_eq!(
+            diagnostics[0].message,
+            "rcon_port 25575 of world 'pvp' is also used by world 'main'"
+        );
+        assert!(diagnostics[1].message.contains("unreachable"));
+
+        let syntax = worlds("worlds.json", "[\n  {\"name\": \"main\",}\n]");
+        assert_eq!((syntax[0].line, syntax[0].column), (2, 19));
+    }
+}